@@ -0,0 +1,77 @@
+use bevy::app::ScheduleRunnerPlugin;
+use bevy::log::LogPlugin;
+use bevy::prelude::*;
+use clap::Parser;
+use sslgame::{DemoGame, MockHost, MockHostConfig, PacketSource, PresharedKey, parse_host_addr};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Serves a demo game or a recording like a real host, for testing clients without a full ssl stack.
+/// Can also relay a real host to clients on another network segment.
+#[derive(Parser, Debug)]
+#[command(version, about)]
+struct Args {
+    /// Address of the websocket server. Link-local ipv6 addresses can name their interface, e.g. [fe80::1%eth0]:0
+    #[arg(long, default_value = "0.0.0.0:0", value_parser = parse_host_addr)]
+    bind: SocketAddr,
+
+    /// Name shown to clients in the host list
+    #[arg(long, default_value = "Mock Host")]
+    hostname: String,
+
+    /// Serve a recording created with the desktop --record option instead of a demo game
+    #[arg(long, value_name = "FILE")]
+    replay: Option<PathBuf>,
+
+    /// Relay the host at this websocket address instead of serving a demo game, e.g. for headsets on a guest wifi
+    #[arg(long, value_name = "ADDR", value_parser = parse_host_addr, conflicts_with = "replay")]
+    relay: Option<SocketAddr>,
+
+    /// Seed of the demo game
+    #[arg(long, default_value_t = 1)]
+    seed: u64,
+
+    /// Don't send host advertisements, clients have to connect directly
+    #[arg(long)]
+    no_advertise: bool,
+
+    /// Also send host advertisements to this address, e.g. a headset or the broadcast address of another subnet.
+    /// Can be repeated.
+    #[arg(long, value_name = "ADDR", value_parser = parse_host_addr)]
+    advertise_to: Vec<SocketAddr>,
+
+    /// Only accept clients that encrypt their connection with the same pre-shared key
+    #[arg(long, value_name = "PASSPHRASE")]
+    psk: Option<String>,
+}
+
+fn main() -> AppExit {
+    let args = Args::parse();
+
+    let mut app = App::new();
+    // The host runs on the io task pool, the app only provides logging and keeps the process alive
+    app.add_plugins((
+        MinimalPlugins.set(ScheduleRunnerPlugin::run_loop(Duration::from_millis(100))),
+        LogPlugin::default(),
+    ));
+
+    let key = args.psk.as_deref().map(PresharedKey::new);
+    let source = match (&args.replay, args.relay) {
+        (Some(path), _) => PacketSource::recording(path)
+            .unwrap_or_else(|e| panic!("Failed to load recording {}: {e}", path.display())),
+        // A relay connects to its host with the same key that its own clients use
+        (None, Some(upstream)) => PacketSource::Relay(upstream, key.clone()),
+        (None, None) => PacketSource::Demo(DemoGame::new(args.seed)),
+    };
+    let config = MockHostConfig {
+        bind_addr: args.bind,
+        hostname: Some(args.hostname),
+        advertise: !args.no_advertise,
+        advertise_to: args.advertise_to,
+        key,
+    };
+    app.insert_resource(MockHost::spawn(config, source).expect("Failed to start mock host"));
+
+    app.run()
+}
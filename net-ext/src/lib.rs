@@ -1,4 +1,5 @@
 pub mod interface_flags;
+pub mod name_lookup;
 pub mod ssm_socket;
 
 #[cfg(unix)]
@@ -0,0 +1,59 @@
+use socket2::SockAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::{io, ptr};
+
+/// Long enough for any hostname, NI_MAXHOST isn't defined on all platforms
+const MAX_HOST: usize = 1025;
+
+/// Looks up the hostname of the address with the system resolver (DNS, hosts file, and mDNS where the system
+/// supports it). This blocks until the resolver answers, which can take several seconds without a DNS server.
+#[cfg(unix)]
+pub fn lookup_addr(ip: IpAddr) -> io::Result<String> {
+    let addr = SockAddr::from(SocketAddr::new(ip, 0));
+    let mut host = [0 as libc::c_char; MAX_HOST];
+    let res = unsafe {
+        libc::getnameinfo(
+            addr.as_ptr().cast(),
+            addr.len(),
+            host.as_mut_ptr(),
+            host.len() as _,
+            ptr::null_mut(),
+            0,
+            libc::NI_NAMEREQD,
+        )
+    };
+    if res != 0 {
+        let message = unsafe { std::ffi::CStr::from_ptr(libc::gai_strerror(res)) };
+        return Err(io::Error::other(message.to_string_lossy().into_owned()));
+    }
+    Ok(unsafe { std::ffi::CStr::from_ptr(host.as_ptr()) }
+        .to_string_lossy()
+        .into_owned())
+}
+
+/// Looks up the hostname of the address with the system resolver (DNS, hosts file, and mDNS where the system
+/// supports it). This blocks until the resolver answers, which can take several seconds without a DNS server.
+/// Winsock has to be initialized already, which std does with the first socket.
+#[cfg(windows)]
+pub fn lookup_addr(ip: IpAddr) -> io::Result<String> {
+    use windows_sys::Win32::Networking::WinSock::{NI_NAMEREQD, getnameinfo};
+
+    let addr = SockAddr::from(SocketAddr::new(ip, 0));
+    let mut host = [0u8; MAX_HOST];
+    let res = unsafe {
+        getnameinfo(
+            addr.as_ptr().cast(),
+            addr.len(),
+            host.as_mut_ptr(),
+            host.len() as u32,
+            ptr::null_mut(),
+            0,
+            NI_NAMEREQD as _,
+        )
+    };
+    if res != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    let end = host.iter().position(|c| *c == 0).unwrap_or(host.len());
+    Ok(String::from_utf8_lossy(&host[..end]).into_owned())
+}
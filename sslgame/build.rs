@@ -1,14 +1,41 @@
 use std::io::Result;
 
+/// Protobuf types wrapped by reflected components, so the components can be edited in the inspector.
+/// Nested types (e.g. oneofs) are matched by their parent's path.
+const REFLECTED_TYPES: [&str; 12] = [
+    ".remote.GameState",
+    ".remote.TeamState",
+    ".remote.VisualizationFilter",
+    ".remote.Visualization",
+    ".remote.VisPart",
+    ".remote.BorderStyle",
+    ".remote.Color",
+    ".remote.Circle",
+    ".remote.Point",
+    ".remote.Polygon",
+    ".remote.Path",
+    ".remote.CustomGeom",
+];
+
 fn main() -> Result<()> {
-    let proto_files =
-        ["remote", "remote_meta", "remote_status"].map(|name| format!("src/proto/{}.proto", name));
+    let proto_files = [
+        "remote",
+        "remote_meta",
+        "remote_status",
+        "session",
+        "viewer",
+    ]
+    .map(|name| format!("src/proto/{}.proto", name));
 
     for path in &proto_files {
         println!("cargo:rerun-if-changed={}", path);
     }
 
-    prost_build::compile_protos(&proto_files, &["src/proto/"])?;
+    let mut config = prost_build::Config::new();
+    for path in REFLECTED_TYPES {
+        config.type_attribute(path, "#[derive(::bevy::reflect::Reflect)]");
+    }
+    config.compile_protos(&proto_files, &["src/proto/"])?;
 
     Ok(())
 }
@@ -0,0 +1,65 @@
+//! Display settings for users with impaired vision, or for panels and screens viewed from far away.
+//! The desktop and VR apps apply them to their UIs, sessions store them with the render settings.
+
+use bevy::prelude::*;
+use std::ops::RangeInclusive;
+
+pub(crate) fn accessibility_plugin(app: &mut App) {
+    app.init_resource::<AccessibilitySettings>();
+    app.register_type::<AccessibilitySettings>();
+}
+
+#[derive(Resource, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Resource, Debug, Default, Clone, PartialEq)]
+pub struct AccessibilitySettings {
+    /// Size of the whole UI, the panels and windows grow with it
+    pub ui_scale: f32,
+    /// Size of the text relative to the rest of the UI
+    pub text_scale: f32,
+    /// Opaque black backgrounds and strong outlines
+    pub high_contrast: bool,
+}
+
+impl Default for AccessibilitySettings {
+    fn default() -> Self {
+        Self {
+            ui_scale: 1.,
+            text_scale: 1.,
+            high_contrast: false,
+        }
+    }
+}
+
+impl AccessibilitySettings {
+    pub const SCALE_RANGE: RangeInclusive<f32> = 0.5..=3.;
+    /// Increment of the +/- buttons
+    pub const SCALE_STEP: f32 = 0.25;
+
+    /// Moves a scale by whole steps, snapped to the step grid and clamped to [`Self::SCALE_RANGE`]
+    pub fn step_scale(scale: f32, steps: i32) -> f32 {
+        let snapped = (scale / Self::SCALE_STEP).round() + steps as f32;
+        Self::clamp_scale(snapped * Self::SCALE_STEP)
+    }
+
+    pub fn clamp_scale(scale: f32) -> f32 {
+        if scale.is_finite() {
+            scale.clamp(*Self::SCALE_RANGE.start(), *Self::SCALE_RANGE.end())
+        } else {
+            1.
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_steps_snap_and_clamp() {
+        assert_eq!(AccessibilitySettings::step_scale(1., 1), 1.25);
+        assert_eq!(AccessibilitySettings::step_scale(1.1, -1), 0.75);
+        assert_eq!(AccessibilitySettings::step_scale(3., 1), 3.);
+        assert_eq!(AccessibilitySettings::step_scale(0.5, -4), 0.5);
+        assert_eq!(AccessibilitySettings::clamp_scale(f32::NAN), 1.);
+    }
+}
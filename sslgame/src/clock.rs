@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use std::time::{Duration, Instant, SystemTime};
+
+/// World states older than this are considered stalled, e.g. to highlight them in clock overlays
+pub const STALE_WORLD_STATE: Duration = Duration::from_millis(500);
+
+/// Reception times of a field's streams, to show the game time and notice stalled streams
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component, Debug, Default)]
+pub struct FieldClock {
+    #[reflect(ignore)]
+    last_world_state: Option<Instant>,
+    /// Stage time left in µs when the game state was received
+    #[reflect(ignore)]
+    stage_time: Option<(i64, Instant)>,
+}
+
+impl FieldClock {
+    /// Time since the newest world state packet was received
+    pub fn world_state_age(&self) -> Option<Duration> {
+        self.last_world_state.map(|received| received.elapsed())
+    }
+
+    pub fn is_stale(&self) -> bool {
+        self.world_state_age()
+            .is_none_or(|age| age > STALE_WORLD_STATE)
+    }
+
+    /// Remaining time of the current game stage in µs, counted down locally between game state updates
+    pub fn stage_time_left(&self) -> Option<i64> {
+        self.stage_time
+            .map(|(time_left, received)| time_left - received.elapsed().as_micros() as i64)
+    }
+
+    pub(crate) fn world_state_received(&mut self) {
+        self.last_world_state = Some(Instant::now());
+    }
+
+    pub(crate) fn game_state_received(&mut self, stage_time_left: Option<i64>) {
+        self.stage_time = stage_time_left.map(|time_left| (time_left, Instant::now()));
+    }
+}
+
+/// Formats the time of day as `HH:MM:SS UTC`
+pub fn format_wall_clock(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    format!(
+        "{:02}:{:02}:{:02} UTC",
+        secs / 3600 % 24,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Formats a stage time in µs as `M:SS`, with a leading `-` in overtime
+pub fn format_stage_time(time_left: i64) -> String {
+    let secs = time_left.unsigned_abs() / 1_000_000;
+    let sign = if time_left < 0 { "-" } else { "" };
+    format!("{sign}{}:{:02}", secs / 60, secs % 60)
+}
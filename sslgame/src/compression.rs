@@ -0,0 +1,49 @@
+//! Optional compression of udp packets. Vis-heavy packets can exceed the MTU, and fragmented packets are
+//! often lost over wifi. The compression is requested by the client, see `UdpStreamRequest.compression`.
+
+use crate::proto::remote::UdpCompression;
+use std::borrow::Cow;
+use std::io::{self, Read};
+
+/// First byte of zstd compressed packets. An end-group tag of field 24, which a valid UdpPacket can't start with.
+pub(crate) const ZSTD_MAGIC: u8 = 0xC4;
+/// Packets up to this size fit into a single ethernet frame and are always sent uncompressed
+pub(crate) const COMPRESSION_THRESHOLD: usize = 1200;
+/// Limits the memory used by malicious or corrupt packets
+const MAX_DECOMPRESSED_SIZE: u64 = 1 << 20;
+
+/// Compresses an encoded UdpPacket if it is large and the compression makes it smaller
+pub(crate) fn compress(packet: Vec<u8>, compression: UdpCompression) -> Vec<u8> {
+    if packet.len() <= COMPRESSION_THRESHOLD {
+        return packet;
+    }
+    match compression {
+        UdpCompression::Uncompressed => packet,
+        UdpCompression::Zstd => {
+            let mut compressed = vec![ZSTD_MAGIC];
+            compressed.extend(ruzstd::encoding::compress_to_vec(
+                packet.as_slice(),
+                ruzstd::encoding::CompressionLevel::Fastest,
+            ));
+            if compressed.len() < packet.len() {
+                compressed
+            } else {
+                packet
+            }
+        }
+    }
+}
+
+/// Returns the encoded UdpPacket of a received datagram, which may or may not be compressed
+pub(crate) fn decompress(datagram: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    let Some((&ZSTD_MAGIC, payload)) = datagram.split_first() else {
+        return Ok(Cow::Borrowed(datagram));
+    };
+    let decoder = ruzstd::decoding::StreamingDecoder::new(payload)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+    let mut packet = Vec::new();
+    decoder
+        .take(MAX_DECOMPRESSED_SIZE)
+        .read_to_end(&mut packet)?;
+    Ok(Cow::Owned(packet))
+}
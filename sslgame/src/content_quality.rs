@@ -0,0 +1,347 @@
+//! Automatic degradation of the field content while the frame work or dropped frames exceed the frame budget,
+//! restored step by step once there is headroom again.
+//! The work time is measured from [`First`] to [`Last`], as the frame time itself is locked to the display rate with vsync.
+
+use crate::RobotRenderSettings;
+use bevy::prelude::*;
+use std::time::{Duration, Instant};
+
+/// The frame work or dropped frames have to stay above the budget this long before the quality is reduced
+const DEGRADE_AFTER: Duration = Duration::from_secs(1);
+/// The frame work has to stay below [`HEADROOM`] times the budget without dropped frames this long
+/// before the quality is restored
+const RESTORE_AFTER: Duration = Duration::from_secs(5);
+/// Restores that had to be reverted double the restore time, up to this
+const MAX_RESTORE_AFTER: Duration = Duration::from_secs(60);
+const HEADROOM: f32 = 0.8;
+/// Frames taking this much longer than the budget missed the display
+const DROPPED_FRAME_FACTOR: f32 = 1.5;
+/// Share of dropped frames that counts as over budget
+const MAX_DROPPED_SHARE: f32 = 0.05;
+/// Smoothing factors of the work time and dropped frame averages
+const SMOOTHING: f32 = 0.1;
+const DROPPED_SMOOTHING: f32 = 0.02;
+/// Minimum time between visualization updates at [`QualityLevel::LimitedUpdates`]
+const LIMITED_VIS_INTERVAL: Duration = Duration::from_millis(100);
+
+pub(crate) fn content_quality_plugin(app: &mut App) {
+    app.init_resource::<ContentQuality>();
+    app.register_type::<ContentQuality>();
+    app.add_systems(First, mark_frame_start);
+    app.add_systems(
+        Last,
+        update_quality_level.run_if(|quality: Res<ContentQuality>| quality.auto),
+    );
+}
+
+/// Content is degraded in this order, each level includes the previous ones
+#[derive(Reflect, Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+#[reflect(Debug, Default, Clone, PartialEq)]
+pub enum QualityLevel {
+    #[default]
+    Full,
+    /// Fewer segments for new visualization circles
+    ReducedCircles,
+    /// No path histories and ball streaks
+    NoTrails,
+    /// Robot models are replaced by cutouts if allowed, see [`ContentQuality::robots`]
+    SimpleRobots,
+    /// Visualizations are updated at most every [`LIMITED_VIS_INTERVAL`]
+    LimitedUpdates,
+}
+
+impl QualityLevel {
+    const LEVELS: [QualityLevel; 5] = [
+        QualityLevel::Full,
+        QualityLevel::ReducedCircles,
+        QualityLevel::NoTrails,
+        QualityLevel::SimpleRobots,
+        QualityLevel::LimitedUpdates,
+    ];
+
+    fn index(self) -> usize {
+        Self::LEVELS
+            .iter()
+            .position(|level| *level == self)
+            .unwrap_or_default()
+    }
+
+    fn lower(self) -> Self {
+        Self::LEVELS[(self.index() + 1).min(Self::LEVELS.len() - 1)]
+    }
+
+    fn higher(self) -> Self {
+        Self::LEVELS[self.index().saturating_sub(1)]
+    }
+}
+
+#[derive(Resource, Reflect, Debug, Clone)]
+#[reflect(Resource, Debug, Default)]
+pub struct ContentQuality {
+    /// Lowers the quality automatically, the level stays as it is otherwise
+    pub auto: bool,
+    /// Frame time to stay below, e.g. the display period of a headset
+    pub frame_budget: Duration,
+    /// Cutouts hide the robots without passthrough, so they have to be allowed by the app
+    pub allow_cutout: bool,
+    pub level: QualityLevel,
+    /// Smoothed work time per frame in seconds
+    work_time: f32,
+    /// Smoothed share of frames that missed the display
+    dropped_share: f32,
+    #[reflect(ignore)]
+    frame_start: Option<Instant>,
+    /// Since when the frames are over budget, or have headroom
+    #[reflect(ignore)]
+    trend_start: Option<(bool, Instant)>,
+    #[reflect(ignore)]
+    restore_after: Duration,
+    #[reflect(ignore)]
+    last_restore: Option<Instant>,
+}
+
+impl Default for ContentQuality {
+    fn default() -> Self {
+        Self {
+            auto: false,
+            frame_budget: Duration::from_secs_f32(1.0 / 60.0),
+            allow_cutout: false,
+            level: QualityLevel::Full,
+            work_time: 0.0,
+            dropped_share: 0.0,
+            frame_start: None,
+            trend_start: None,
+            restore_after: RESTORE_AFTER,
+            last_restore: None,
+        }
+    }
+}
+
+impl ContentQuality {
+    /// Factor for the number of segments of visualization circles
+    pub fn circle_detail(&self) -> f32 {
+        if self.level >= QualityLevel::ReducedCircles {
+            0.25
+        } else {
+            1.0
+        }
+    }
+
+    pub fn trails(&self) -> bool {
+        self.level < QualityLevel::NoTrails
+    }
+
+    /// The robot rendering actually used for the rendering chosen in the [`crate::RenderSettings`].
+    /// Only cutouts are cheaper than the models, the fallback uses the same model as the detailed rendering.
+    pub fn robots(&self, robots: &RobotRenderSettings) -> RobotRenderSettings {
+        match robots {
+            RobotRenderSettings::Detailed | RobotRenderSettings::Fallback
+                if self.allow_cutout && self.level >= QualityLevel::SimpleRobots =>
+            {
+                RobotRenderSettings::Cutout
+            }
+            robots => robots.clone(),
+        }
+    }
+
+    pub fn work_time(&self) -> Duration {
+        Duration::from_secs_f32(self.work_time)
+    }
+
+    /// Updates the averages with a frame and adjusts the level. Returns true if the level changed.
+    fn sample_frame(&mut self, work_time: Duration, frame_time: Duration, now: Instant) -> bool {
+        let budget = self.frame_budget.as_secs_f32();
+        let work_time = work_time.as_secs_f32();
+        self.work_time = if self.work_time > 0.0 {
+            self.work_time * (1.0 - SMOOTHING) + work_time * SMOOTHING
+        } else {
+            work_time
+        };
+        // Also catches overload outside of the measured work, e.g. on the gpu
+        let dropped = frame_time.as_secs_f32() > budget * DROPPED_FRAME_FACTOR;
+        self.dropped_share = self.dropped_share * (1.0 - DROPPED_SMOOTHING)
+            + if dropped { DROPPED_SMOOTHING } else { 0.0 };
+
+        let over_budget = self.work_time > budget || self.dropped_share > MAX_DROPPED_SHARE;
+        let headroom = !over_budget && self.work_time < budget * HEADROOM && !dropped;
+        if !over_budget && !headroom {
+            self.trend_start = None;
+            return false;
+        }
+        let since = match self.trend_start {
+            Some((over, since)) if over == over_budget => since,
+            _ => {
+                self.trend_start = Some((over_budget, now));
+                return false;
+            }
+        };
+
+        let new_level = if over_budget && now - since >= DEGRADE_AFTER {
+            // The restored level can't be sustained, e.g. because the gpu is the bottleneck
+            if self
+                .last_restore
+                .is_some_and(|restore| now - restore < self.restore_after)
+            {
+                self.restore_after = (self.restore_after * 2).min(MAX_RESTORE_AFTER);
+            }
+            self.level.lower()
+        } else if headroom && now - since >= self.restore_after {
+            self.last_restore = Some(now);
+            self.level.higher()
+        } else {
+            return false;
+        };
+        // The next step has to wait for the effect of this one
+        self.trend_start = Some((over_budget, now));
+        if new_level == self.level {
+            return false;
+        }
+        info!("Content quality {:?} -> {new_level:?}", self.level);
+        self.level = new_level;
+        true
+    }
+}
+
+/// Run condition for the visualization updates, limited at [`QualityLevel::LimitedUpdates`]
+pub(crate) fn vis_update_due(
+    quality: Res<ContentQuality>,
+    mut last_update: Local<Option<Instant>>,
+) -> bool {
+    if quality.level < QualityLevel::LimitedUpdates {
+        return true;
+    }
+    let now = Instant::now();
+    if last_update.is_some_and(|last| now - last < LIMITED_VIS_INTERVAL) {
+        return false;
+    }
+    *last_update = Some(now);
+    true
+}
+
+fn mark_frame_start(mut quality: ResMut<ContentQuality>) {
+    quality.bypass_change_detection().frame_start = Some(Instant::now());
+}
+
+fn update_quality_level(time: Res<Time<Real>>, mut content_quality: ResMut<ContentQuality>) {
+    let now = Instant::now();
+    // Only level changes are reported, not every frame sample
+    let quality = content_quality.bypass_change_detection();
+    let Some(frame_start) = quality.frame_start.take() else {
+        return;
+    };
+    if quality.sample_frame(now - frame_start, time.delta(), now) {
+        content_quality.set_changed();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quality(level: QualityLevel) -> ContentQuality {
+        ContentQuality {
+            auto: true,
+            frame_budget: Duration::from_millis(10),
+            level,
+            ..default()
+        }
+    }
+
+    /// Feeds frames of the given work and frame times for the duration
+    fn run(
+        quality: &mut ContentQuality,
+        now: &mut Instant,
+        work_ms: u64,
+        frame_ms: u64,
+        secs: f32,
+    ) {
+        let frames = (secs * 1000.0) as u64 / frame_ms;
+        for _ in 0..frames {
+            *now += Duration::from_millis(frame_ms);
+            quality.sample_frame(
+                Duration::from_millis(work_ms),
+                Duration::from_millis(frame_ms),
+                *now,
+            );
+        }
+    }
+
+    #[test]
+    fn degrades_while_work_is_over_budget() {
+        let mut quality = quality(QualityLevel::Full);
+        let mut now = Instant::now();
+        run(&mut quality, &mut now, 12, 12, 1.5);
+        assert_eq!(quality.level, QualityLevel::ReducedCircles);
+        run(&mut quality, &mut now, 12, 12, 1.0);
+        assert_eq!(quality.level, QualityLevel::NoTrails);
+    }
+
+    #[test]
+    fn restores_with_frames_locked_to_the_display() {
+        let mut quality = quality(QualityLevel::NoTrails);
+        let mut now = Instant::now();
+        // With vsync, every frame takes exactly the budget
+        run(&mut quality, &mut now, 4, 10, 5.5);
+        assert_eq!(quality.level, QualityLevel::ReducedCircles);
+        run(&mut quality, &mut now, 4, 10, 5.5);
+        assert_eq!(quality.level, QualityLevel::Full);
+    }
+
+    #[test]
+    fn dropped_frames_degrade_without_work_load() {
+        let mut quality = quality(QualityLevel::Full);
+        let mut now = Instant::now();
+        for frame in 0..150 {
+            let frame_ms = if frame % 5 == 0 { 25 } else { 10 };
+            now += Duration::from_millis(frame_ms);
+            quality.sample_frame(
+                Duration::from_millis(4),
+                Duration::from_millis(frame_ms),
+                now,
+            );
+        }
+        assert_eq!(quality.level, QualityLevel::ReducedCircles);
+    }
+
+    #[test]
+    fn simple_robots_only_replace_models_with_cutouts() {
+        let mut quality = quality(QualityLevel::SimpleRobots);
+        // Without cutouts there is nothing cheaper to switch to
+        assert_eq!(
+            quality.robots(&RobotRenderSettings::Detailed),
+            RobotRenderSettings::Detailed
+        );
+
+        quality.allow_cutout = true;
+        assert_eq!(
+            quality.robots(&RobotRenderSettings::Fallback),
+            RobotRenderSettings::Cutout
+        );
+        assert_eq!(
+            quality.robots(&RobotRenderSettings::None),
+            RobotRenderSettings::None
+        );
+        quality.level = QualityLevel::NoTrails;
+        assert_eq!(
+            quality.robots(&RobotRenderSettings::Detailed),
+            RobotRenderSettings::Detailed
+        );
+    }
+
+    #[test]
+    fn reverted_restores_back_off() {
+        let mut quality = quality(QualityLevel::ReducedCircles);
+        let mut now = Instant::now();
+        run(&mut quality, &mut now, 4, 10, 5.5);
+        assert_eq!(quality.level, QualityLevel::Full);
+
+        // The restored level is too expensive
+        run(&mut quality, &mut now, 12, 12, 1.5);
+        assert_eq!(quality.level, QualityLevel::ReducedCircles);
+
+        run(&mut quality, &mut now, 4, 10, 6.0);
+        assert_eq!(quality.level, QualityLevel::ReducedCircles);
+        run(&mut quality, &mut now, 4, 10, 5.0);
+        assert_eq!(quality.level, QualityLevel::Full);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::proto::remote::vis_part::Geom;
+use crate::proto::remote::{CustomGeom, VisPart};
+use crate::{VisualizationData, update_visualizations};
+use bevy::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+pub(crate) fn custom_vis_plugin(app: &mut App) {
+    app.init_resource::<CustomVisHandlers>();
+    app.add_systems(
+        Update,
+        spawn_custom_visualizations.after(update_visualizations),
+    );
+}
+
+/// Called with the part containing the custom geometry and the visualization entity it belongs to.
+/// Anything spawned as a child of the visualization entity is despawned together with the visualization.
+pub type CustomVisHandler =
+    Box<dyn Fn(&mut Commands, Entity, &VisPart, &CustomGeom) + Send + Sync + 'static>;
+
+/// Handlers for [`CustomGeom`] visualization parts by their kind.
+/// Parts without a registered handler are ignored.
+///
+/// ```ignore
+/// app.world_mut()
+///     .resource_mut::<CustomVisHandlers>()
+///     .register("erforce.trajectory", |commands, vis_entity, _part, geom| {
+///         commands.entity(vis_entity).with_child(Trajectory::decode(geom.payload()));
+///     });
+/// ```
+#[derive(Resource, Default)]
+pub struct CustomVisHandlers {
+    handlers: HashMap<String, CustomVisHandler>,
+    /// Kinds that were already reported as unknown, to only warn once
+    unknown_kinds: HashSet<String>,
+}
+
+impl CustomVisHandlers {
+    /// Registers the handler for the kind, replacing any previous handler.
+    pub fn register(
+        &mut self,
+        kind: impl Into<String>,
+        handler: impl Fn(&mut Commands, Entity, &VisPart, &CustomGeom) + Send + Sync + 'static,
+    ) -> &mut Self {
+        self.handlers.insert(kind.into(), Box::new(handler));
+        self
+    }
+}
+
+fn spawn_custom_visualizations(
+    mut commands: Commands,
+    mut handlers: ResMut<CustomVisHandlers>,
+    q_new_visualizations: Query<(&VisualizationData, Entity), Added<VisualizationData>>,
+) {
+    for (visualization, vis_entity) in &q_new_visualizations {
+        for part in &visualization.part {
+            let Some(Geom::Custom(custom)) = &part.geom else {
+                continue;
+            };
+
+            if let Some(handler) = handlers.handlers.get(&custom.kind) {
+                handler(&mut commands, vis_entity, part, custom);
+            } else if handlers.unknown_kinds.insert(custom.kind.clone()) {
+                warn!(
+                    "No handler for custom visualization kind \"{}\"",
+                    custom.kind
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::proto::remote::{DebugValues, debug_value};
+use bevy::prelude::*;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// Separates the levels of the tree in debug value keys
+pub const DEBUG_KEY_SEPARATOR: char = '/';
+
+#[derive(Reflect, Debug, Clone, PartialEq)]
+#[reflect(Debug, Clone, PartialEq)]
+pub enum DebugValue {
+    Number(f64),
+    Text(String),
+    Flag(bool),
+}
+
+impl fmt::Display for DebugValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            // Counters and ids are sent as numbers too and shouldn't get decimals
+            DebugValue::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{n:.0}"),
+            DebugValue::Number(n) => write!(f, "{n:.3}"),
+            DebugValue::Text(text) => write!(f, "{text}"),
+            DebugValue::Flag(flag) => write!(f, "{flag}"),
+        }
+    }
+}
+
+/// Debug values of the strategy running on the host of a field, by their [`DEBUG_KEY_SEPARATOR`]-separated keys.
+#[derive(Component, Reflect, Debug, Default, Clone)]
+#[reflect(Component, Debug, Default, Clone)]
+pub struct DebugTree {
+    values: BTreeMap<String, DebugValue>,
+    /// Keys pinned by the user. Kept when the host stops sending a value, so it shows up again when it returns.
+    pinned: BTreeSet<String>,
+}
+
+impl DebugTree {
+    pub fn values(&self) -> &BTreeMap<String, DebugValue> {
+        &self.values
+    }
+
+    pub fn get(&self, key: &str) -> Option<&DebugValue> {
+        self.values.get(key)
+    }
+
+    pub fn is_pinned(&self, key: &str) -> bool {
+        self.pinned.contains(key)
+    }
+
+    pub fn set_pinned(&mut self, key: &str, pinned: bool) {
+        if pinned {
+            self.pinned.insert(key.to_string());
+        } else {
+            self.pinned.remove(key);
+        }
+    }
+
+    /// All pinned keys in order, with their current value if the host still sends them
+    pub fn pinned(&self) -> impl Iterator<Item = (&str, Option<&DebugValue>)> {
+        self.pinned
+            .iter()
+            .map(|key| (key.as_str(), self.values.get(key)))
+    }
+
+    /// Builds the tree structure from the flat keys
+    pub fn root(&self) -> DebugNode<'_> {
+        let mut root = DebugNode::default();
+        for (key, value) in &self.values {
+            let mut node = &mut root;
+            let mut start = 0;
+            for segment in key.split(DEBUG_KEY_SEPARATOR) {
+                let end = start + segment.len();
+                node = node.children.entry(segment).or_insert_with(|| DebugNode {
+                    key: &key[..end],
+                    ..default()
+                });
+                start = end + DEBUG_KEY_SEPARATOR.len_utf8();
+            }
+            node.value = Some(value);
+        }
+        root
+    }
+
+    /// Every update contains the whole tree
+    pub(crate) fn replace(&mut self, update: DebugValues) {
+        self.values = update
+            .value
+            .into_iter()
+            .filter_map(|value| {
+                let converted = match value.value? {
+                    debug_value::Value::Number(n) => DebugValue::Number(n),
+                    debug_value::Value::Text(text) => DebugValue::Text(text),
+                    debug_value::Value::Flag(flag) => DebugValue::Flag(flag),
+                };
+                Some((value.key, converted))
+            })
+            .collect();
+    }
+}
+
+/// A level of a [`DebugTree`]. Inner nodes usually don't have a value, but the host is free to send one.
+#[derive(Debug, Default)]
+pub struct DebugNode<'a> {
+    /// Full key of this node, empty for the root
+    pub key: &'a str,
+    pub value: Option<&'a DebugValue>,
+    /// Child nodes by their last key segment
+    pub children: BTreeMap<&'a str, DebugNode<'a>>,
+}
@@ -0,0 +1,580 @@
+use crate::proto::remote::*;
+use crate::update_packet::UpdatePacket;
+use async_channel::{Receiver, Sender};
+use bevy::log::debug;
+use bevy::math::Vec2;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+const ROBOT_SPEED: f32 = 1.5;
+const PASS_SPEED: f32 = 3.5;
+const SHOT_SPEED: f32 = 6.0;
+const HOLD_TIME: f32 = 0.8;
+/// The demo game is played in halves of this length, without any break
+const HALF_TIME: Duration = Duration::from_secs(5 * 60);
+
+const VIS_SOURCE: u32 = 1;
+const VIS_PASS_LINE: u32 = 1;
+const VIS_BALL_OWNER: u32 = 2;
+const VIS_TARGETS: u32 = 3;
+
+/// Formation of a team defending the goal on the negative x side, as offsets in meters.
+const FORMATION: [Vec2; 6] = [
+    Vec2::new(-5.5, 0.0),
+    Vec2::new(-3.8, 1.2),
+    Vec2::new(-3.8, -1.2),
+    Vec2::new(-1.5, 2.5),
+    Vec2::new(-1.5, -2.5),
+    Vec2::new(-0.8, 0.0),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DemoTeam {
+    Yellow,
+    Blue,
+}
+
+impl DemoTeam {
+    /// Direction towards the goal of the opponent. Yellow plays towards the blue goal on the positive x side.
+    fn attack_dir(&self) -> f32 {
+        match self {
+            DemoTeam::Yellow => 1.0,
+            DemoTeam::Blue => -1.0,
+        }
+    }
+
+    fn opponent(&self) -> Self {
+        match self {
+            DemoTeam::Yellow => DemoTeam::Blue,
+            DemoTeam::Blue => DemoTeam::Yellow,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DemoRobot {
+    team: DemoTeam,
+    id: u32,
+    pos: Vec2,
+    phi: f32,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum BallState {
+    /// The ball is held by the robot with the index for the remaining time
+    Held(usize, f32),
+    /// The ball moves towards a receiving robot
+    Pass { to: usize },
+    /// The ball moves towards the goal of the team
+    Shot { at: DemoTeam },
+}
+
+/// Procedurally generates a plausible game with passes, shots and formations that follow the ball.
+///
+/// The output consists of regular [`UpdatePacket`]s, so it can be fed through the normal pipeline
+/// (see [`crate::Field::demo`]) or served by a host.
+#[derive(Debug, Clone)]
+pub struct DemoGame {
+    rng: u64,
+    time: Duration,
+    geometry: FieldGeometry,
+    robots: Vec<DemoRobot>,
+    ball: Vec2,
+    ball_state: BallState,
+    score: HashMap<u32, u32>,
+}
+
+impl Default for DemoGame {
+    /// The game shown by [`crate::Field::demo`]
+    fn default() -> Self {
+        Self::new(0x5EED)
+    }
+}
+
+impl DemoGame {
+    pub fn new(seed: u64) -> Self {
+        let geometry = FieldGeometry {
+            field_size_x: 12.0,
+            field_size_y: 9.0,
+            boundary_width: Some(0.3),
+            defense_size_x: Some(1.8),
+            defense_size_y: Some(3.6),
+            goal_width: Some(1.8),
+        };
+
+        let robots = [DemoTeam::Yellow, DemoTeam::Blue]
+            .into_iter()
+            .flat_map(|team| {
+                FORMATION
+                    .iter()
+                    .enumerate()
+                    .map(move |(id, offset)| DemoRobot {
+                        team,
+                        id: id as u32,
+                        pos: *offset * Vec2::new(team.attack_dir(), 1.0),
+                        phi: if team == DemoTeam::Yellow { 0.0 } else { PI },
+                    })
+            })
+            .collect();
+
+        Self {
+            // The xorshift state must never be zero
+            rng: seed.max(1),
+            time: Duration::ZERO,
+            geometry,
+            robots,
+            ball: Vec2::ZERO,
+            ball_state: BallState::Held(FORMATION.len() - 1, HOLD_TIME),
+            score: HashMap::new(),
+        }
+    }
+
+    /// Packets describing the static parts of the game. These are sent once after connecting.
+    pub fn initial_packets(&self) -> Vec<UpdatePacket> {
+        vec![
+            UpdatePacket::FieldGeom(self.geometry),
+            UpdatePacket::GameState(self.game_state()),
+            UpdatePacket::VisMappings(VisMappings {
+                source: HashMap::from([(VIS_SOURCE, "Demo".to_string())]),
+                name: HashMap::from([
+                    (VIS_PASS_LINE, "Pass line".to_string()),
+                    (VIS_BALL_OWNER, "Ball owner".to_string()),
+                    (VIS_TARGETS, "Formation targets".to_string()),
+                ]),
+            }),
+        ]
+    }
+
+    /// Advances the simulation and returns the packets for the new frame.
+    /// The game state is only included if it has changed.
+    pub fn step(&mut self, dt: Duration) -> Vec<UpdatePacket> {
+        let previous_second = self.time.as_secs();
+        self.time += dt;
+        let dt = dt.as_secs_f32();
+        let mut packets = Vec::new();
+
+        // ======== Ball ========
+
+        match self.ball_state {
+            BallState::Held(owner, remaining) => {
+                let owner_robot = &self.robots[owner];
+                self.ball = owner_robot.pos + Vec2::from_angle(owner_robot.phi) * 0.1;
+                if remaining > dt {
+                    self.ball_state = BallState::Held(owner, remaining - dt);
+                } else {
+                    self.ball_state = self.next_ball_action(owner);
+                }
+            }
+            BallState::Pass { to } => {
+                let target = self.robots[to].pos;
+                if self.move_ball_towards(target, PASS_SPEED * dt) {
+                    self.ball_state = BallState::Held(to, HOLD_TIME);
+                }
+            }
+            BallState::Shot { at } => {
+                let goal = self.goal_pos(at);
+                if self.move_ball_towards(goal, SHOT_SPEED * dt) {
+                    // Kick-off for the team that conceded the goal
+                    *self.score.entry(at.opponent() as u32).or_default() += 1;
+                    self.ball = Vec2::ZERO;
+                    let kick_off = self.robot_index(at, FORMATION.len() as u32 - 1);
+                    self.ball_state = BallState::Held(kick_off, HOLD_TIME * 2.0);
+                    packets.push(UpdatePacket::GameState(self.game_state()));
+                }
+            }
+        }
+
+        // ======== Robots ========
+
+        let receiver = match self.ball_state {
+            BallState::Pass { to } => Some(to),
+            _ => None,
+        };
+        let owner = match self.ball_state {
+            BallState::Held(owner, _) => Some(owner),
+            _ => None,
+        };
+        let targets: Vec<_> = (0..self.robots.len())
+            .map(|i| self.formation_target(i))
+            .collect();
+
+        for (i, robot) in self.robots.iter_mut().enumerate() {
+            // The receiver stays in place to make the passes look intentional
+            if Some(i) != receiver && Some(i) != owner {
+                let delta = targets[i] - robot.pos;
+                robot.pos += delta.clamp_length_max(ROBOT_SPEED * dt);
+            }
+            let to_ball = self.ball - robot.pos;
+            if to_ball.length_squared() > 0.0001 {
+                robot.phi = to_ball.to_angle();
+            }
+        }
+
+        // ======== Output ========
+
+        packets.push(UpdatePacket::WorldState(self.world_state()));
+        packets.push(UpdatePacket::VisualizationUpdate(
+            self.visualizations(&targets),
+        ));
+        packets.push(UpdatePacket::StrategyPlans(self.strategy_plans(&targets)));
+        // Telemetry and the stage time are only sent once per second, like on real robots and referees
+        if self.time.as_secs() != previous_second {
+            packets.push(UpdatePacket::RobotTelemetry(self.telemetry()));
+            packets.push(UpdatePacket::GameState(self.game_state()));
+        }
+        packets.push(UpdatePacket::DebugValues(self.debug_values()));
+        packets
+    }
+
+    /// Passes to a random teammate or shoots if the ball is in the opponent half.
+    fn next_ball_action(&mut self, owner: usize) -> BallState {
+        let team = self.robots[owner].team;
+        let in_opponent_half = self.ball.x * team.attack_dir() > 1.5;
+
+        if in_opponent_half && self.random() < 0.35 {
+            return BallState::Shot {
+                at: team.opponent(),
+            };
+        }
+
+        // Some passes are intercepted by the opponent
+        let receiving_team = if self.random() < 0.15 {
+            team.opponent()
+        } else {
+            team
+        };
+        let candidates: Vec<_> = self
+            .robots
+            .iter()
+            .enumerate()
+            .filter(|(i, r)| *i != owner && r.team == receiving_team && r.id != 0)
+            // Prefer passing forwards
+            .filter(|(_, r)| {
+                receiving_team != team || (r.pos.x - self.ball.x) * team.attack_dir() > -1.0
+            })
+            .map(|(i, _)| i)
+            .collect();
+
+        if candidates.is_empty() {
+            return BallState::Held(owner, HOLD_TIME);
+        }
+        let to = candidates[(self.random() * candidates.len() as f32) as usize % candidates.len()];
+        BallState::Pass { to }
+    }
+
+    /// Moves the ball and returns true if the target was reached
+    fn move_ball_towards(&mut self, target: Vec2, max_distance: f32) -> bool {
+        let delta = target - self.ball;
+        self.ball += delta.clamp_length_max(max_distance);
+        delta.length() <= max_distance
+    }
+
+    /// The formation shifts with the ball, and each robot drifts a bit to look less static
+    fn formation_target(&self, index: usize) -> Vec2 {
+        let robot = &self.robots[index];
+        let offset = FORMATION[robot.id as usize] * Vec2::new(robot.team.attack_dir(), 1.0);
+        let drift_phase = self.time.as_secs_f32() * 0.7 + index as f32 * 1.3;
+        let drift = Vec2::new(drift_phase.sin(), (drift_phase * 1.7).cos()) * 0.3;
+
+        // Goalies only move along the goal line
+        if robot.id == 0 {
+            let half_goal_width = self.geometry.goal_width.unwrap_or(1.0) / 2.0;
+            return Vec2::new(
+                offset.x,
+                (self.ball.y * 0.3).clamp(-half_goal_width, half_goal_width),
+            );
+        }
+
+        let half_size = Vec2::new(self.geometry.field_size_x, self.geometry.field_size_y) / 2.0;
+        (offset + self.ball * Vec2::new(0.6, 0.4) + drift).clamp(-half_size, half_size)
+    }
+
+    fn goal_pos(&self, team: DemoTeam) -> Vec2 {
+        Vec2::new(-team.attack_dir() * self.geometry.field_size_x / 2.0, 0.0)
+    }
+
+    fn robot_index(&self, team: DemoTeam, id: u32) -> usize {
+        self.robots
+            .iter()
+            .position(|r| r.team == team && r.id == id)
+            .unwrap_or_default()
+    }
+
+    fn game_state(&self) -> GameState {
+        let team_state = |name: &str, team: DemoTeam| TeamState {
+            name: Some(name.to_string()),
+            score: Some(self.score.get(&(team as u32)).copied().unwrap_or_default()),
+            fouls: Some(0),
+            yellow_cards: Some(0),
+            red_cards: Some(0),
+            yellow_card_times: Vec::new(),
+            timeouts_left: Some(4),
+            timeout_active: Some(false),
+            max_robots: Some(FORMATION.len() as u32),
+            substitution_allowed: Some(false),
+        };
+        GameState {
+            game_stage: Some("Demo".to_string()),
+            yellow_team: Some(team_state("Yellow Demo", DemoTeam::Yellow)),
+            blue_team: Some(team_state("Blue Demo", DemoTeam::Blue)),
+            stage_time_left: Some(
+                (HALF_TIME.as_micros() - self.time.as_micros() % HALF_TIME.as_micros()) as i64,
+            ),
+        }
+    }
+
+    fn telemetry(&self) -> RobotTelemetryUpdate {
+        let minutes = self.time.as_secs_f32() / 60.0;
+        let owner = match self.ball_state {
+            BallState::Held(owner, _) => Some(owner),
+            _ => None,
+        };
+        let telemetry = |team: DemoTeam| {
+            self.robots
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| r.team == team)
+                .map(|(i, r)| {
+                    // Recharges within 5 seconds after each kick
+                    let kicker_charge = (self.time.as_secs_f32() / 5.0 + r.id as f32 * 0.3).fract();
+                    RobotTelemetry {
+                        id: r.id,
+                        // Slowly drains, with some robots starting with a weaker battery
+                        battery_voltage: Some(16.6 - 0.15 * r.id as f32 - 0.05 * minutes),
+                        kicker_charge: Some(kicker_charge),
+                        radio_rssi: Some(-45.0 - r.pos.length() * 3.0),
+                        // By position in the formation
+                        role: Some(match r.id {
+                            0 => RobotRole::Keeper,
+                            1 | 2 => RobotRole::Defender,
+                            3 | 4 => RobotRole::Midfielder,
+                            _ => RobotRole::Striker,
+                        } as i32),
+                        kicker_armed: Some(kicker_charge > 0.8),
+                        dribbler_active: Some(owner == Some(i)),
+                    }
+                })
+                .collect()
+        };
+        RobotTelemetryUpdate {
+            yellow_robot: telemetry(DemoTeam::Yellow),
+            blue_robot: telemetry(DemoTeam::Blue),
+        }
+    }
+
+    fn debug_values(&self) -> DebugValues {
+        let value = |key: String, value: debug_value::Value| DebugValue {
+            key,
+            value: Some(value),
+        };
+        let ball_state = match self.ball_state {
+            BallState::Held(owner, _) => format!("held by {}", self.robots[owner].id),
+            BallState::Pass { to } => format!("pass to {}", self.robots[to].id),
+            BallState::Shot { at } => format!("shot at {at:?}"),
+        };
+
+        let mut values = vec![
+            value(
+                "demo/time".to_string(),
+                debug_value::Value::Number(self.time.as_secs_f64()),
+            ),
+            value(
+                "demo/ball/state".to_string(),
+                debug_value::Value::Text(ball_state),
+            ),
+            value(
+                "demo/ball/x".to_string(),
+                debug_value::Value::Number(self.ball.x as f64),
+            ),
+            value(
+                "demo/ball/y".to_string(),
+                debug_value::Value::Number(self.ball.y as f64),
+            ),
+        ];
+        for robot in &self.robots {
+            let prefix = format!("{:?}/robot {}", robot.team, robot.id).to_lowercase();
+            values.push(value(
+                format!("{prefix}/ball distance"),
+                debug_value::Value::Number(robot.pos.distance(self.ball) as f64),
+            ));
+            values.push(value(
+                format!("{prefix}/goalie"),
+                debug_value::Value::Flag(robot.id == 0),
+            ));
+        }
+        DebugValues { value: values }
+    }
+
+    fn world_state(&self) -> WorldState {
+        let robots = |team: DemoTeam| {
+            self.robots
+                .iter()
+                .filter(|r| r.team == team)
+                .map(|r| Robot {
+                    id: r.id,
+                    p_x: r.pos.x,
+                    p_y: r.pos.y,
+                    phi: r.phi,
+                    v_phi: None,
+                })
+                .collect()
+        };
+        WorldState {
+            timestamp: Some(self.time.as_micros() as u64),
+            ball: vec![Ball {
+                p_x: self.ball.x,
+                p_y: self.ball.y,
+                p_z: Some(0.0),
+            }],
+            yellow_robot: robots(DemoTeam::Yellow),
+            blue_robot: robots(DemoTeam::Blue),
+            keyframe: None,
+        }
+    }
+
+    fn strategy_plans(&self, targets: &[Vec2]) -> StrategyPlans {
+        /// Robots closer to their target than this aren't moving, so their target isn't shown
+        const MIN_TARGET_DISTANCE: f32 = 0.2;
+
+        let passes = |team: DemoTeam| match self.ball_state {
+            BallState::Pass { to } if self.robots[to].team == team => vec![PlannedPass {
+                from_x: self.ball.x,
+                from_y: self.ball.y,
+                to_x: self.robots[to].pos.x,
+                to_y: self.robots[to].pos.y,
+            }],
+            _ => Vec::new(),
+        };
+        let targets = |team: DemoTeam| {
+            self.robots
+                .iter()
+                .zip(targets)
+                .filter(|(r, target)| {
+                    r.team == team && r.pos.distance(**target) > MIN_TARGET_DISTANCE
+                })
+                .map(|(r, target)| TargetPosition {
+                    robot_id: r.id,
+                    p_x: target.x,
+                    p_y: target.y,
+                })
+                .collect()
+        };
+        StrategyPlans {
+            yellow_pass: passes(DemoTeam::Yellow),
+            blue_pass: passes(DemoTeam::Blue),
+            yellow_target: targets(DemoTeam::Yellow),
+            blue_target: targets(DemoTeam::Blue),
+        }
+    }
+
+    fn visualizations(&self, targets: &[Vec2]) -> VisualizationUpdate {
+        let point = |p: Vec2| Point { x: p.x, y: p.y };
+        let color = |red, green, blue, alpha| Color {
+            red,
+            green,
+            blue,
+            alpha,
+        };
+
+        let mut visualizations = Vec::new();
+
+        if let BallState::Pass { to } = self.ball_state {
+            visualizations.push(Visualization {
+                id: VIS_PASS_LINE,
+                part: vec![VisPart {
+                    border_style: Some(BorderStyle {
+                        style: None,
+                        color: Some(color(255, 255, 255, 200)),
+                    }),
+                    fill_color: None,
+                    geom: Some(vis_part::Geom::Path(Path {
+                        point: vec![point(self.ball), point(self.robots[to].pos)],
+                    })),
+                }],
+            });
+        }
+
+        if let BallState::Held(owner, _) = self.ball_state {
+            let owner_pos = self.robots[owner].pos;
+            visualizations.push(Visualization {
+                id: VIS_BALL_OWNER,
+                part: vec![VisPart {
+                    border_style: Some(BorderStyle {
+                        style: None,
+                        color: Some(color(255, 80, 0, 255)),
+                    }),
+                    fill_color: Some(color(255, 80, 0, 60)),
+                    geom: Some(vis_part::Geom::Circle(Circle {
+                        p_x: owner_pos.x,
+                        p_y: owner_pos.y,
+                        radius: 0.25,
+                    })),
+                }],
+            });
+        }
+
+        visualizations.push(Visualization {
+            id: VIS_TARGETS,
+            part: targets
+                .iter()
+                .map(|target| VisPart {
+                    border_style: None,
+                    fill_color: Some(color(80, 160, 255, 120)),
+                    geom: Some(vis_part::Geom::Circle(Circle {
+                        p_x: target.x,
+                        p_y: target.y,
+                        radius: 0.05,
+                    })),
+                })
+                .collect(),
+        });
+
+        VisualizationUpdate {
+            visualization_group: None,
+            visualization_set: vec![VisualizationSet {
+                source: Some(VIS_SOURCE),
+                visualization: visualizations,
+            }],
+        }
+    }
+
+    /// xorshift64, good enough for a demo and avoids an extra dependency
+    fn random(&mut self) -> f32 {
+        self.rng ^= self.rng << 13;
+        self.rng ^= self.rng >> 7;
+        self.rng ^= self.rng << 17;
+        (self.rng >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+/// Runs a [`DemoGame`] in real time at 60 fps.
+pub(crate) async fn demo_task(
+    mut game: DemoGame,
+    packets_out: Sender<UpdatePacket>,
+    requests_in: Receiver<ws_request::Content>,
+) {
+    const FRAME_TIME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+    for packet in game.initial_packets() {
+        if packets_out.send(packet).await.is_err() {
+            return;
+        }
+    }
+
+    let mut next_frame = Instant::now();
+    loop {
+        next_frame += FRAME_TIME;
+        async_io::Timer::at(next_frame).await;
+
+        // The demo always sends everything, so requests are just discarded
+        while requests_in.try_recv().is_ok() {}
+
+        for packet in game.step(FRAME_TIME) {
+            if packets_out.send(packet).await.is_err() {
+                debug!("Packet receiver dropped, stopping demo task");
+                return;
+            }
+        }
+    }
+}
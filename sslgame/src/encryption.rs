@@ -0,0 +1,87 @@
+//! Optional encryption of the websocket and udp streams with a pre-shared key, e.g. for demos on open venue wifi.
+//! Every message is sealed with ChaCha20-Poly1305 and framed as [`ENCRYPTED_MAGIC`], a random nonce and the ciphertext.
+//! This protects against eavesdropping and injected packets, but replayed packets aren't detected.
+
+use bevy::prelude::Resource;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use std::borrow::Cow;
+use std::fmt;
+use std::io;
+use std::sync::Arc;
+
+/// First byte of encrypted messages. An end-group tag of field 25, which no valid protobuf message can start with.
+pub(crate) const ENCRYPTED_MAGIC: u8 = 0xCC;
+const NONCE_SIZE: usize = 12;
+
+/// A key derived from a passphrase that is shared between the host and its clients
+#[derive(Clone)]
+pub struct PresharedKey(Arc<ChaCha20Poly1305>);
+
+impl fmt::Debug for PresharedKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("PresharedKey(..)")
+    }
+}
+
+/// The key for connections to hosts that advertise encrypted streams, see [`crate::FieldHost::encrypted`].
+/// Encrypted hosts are only listed in [`crate::AvailableHosts`] while a key is set.
+/// Changing the key only affects connections that are created afterwards.
+#[derive(Resource, Debug, Default, Clone)]
+pub struct ClientKey(pub Option<PresharedKey>);
+
+impl PresharedKey {
+    pub fn new(passphrase: &str) -> Self {
+        let key = blake3::derive_key("xrvis 2025 pre-shared stream key", passphrase.as_bytes());
+        Self(Arc::new(ChaCha20Poly1305::new(Key::from_slice(&key))))
+    }
+
+    fn seal(&self, message: &[u8]) -> Vec<u8> {
+        let mut nonce = [0u8; NONCE_SIZE];
+        getrandom::fill(&mut nonce).expect("No random source for the encryption nonce");
+        let ciphertext = self
+            .0
+            .encrypt(Nonce::from_slice(&nonce), message)
+            .expect("Encryption only fails for messages larger than 256GB");
+
+        let mut frame = Vec::with_capacity(1 + NONCE_SIZE + ciphertext.len());
+        frame.push(ENCRYPTED_MAGIC);
+        frame.extend(nonce);
+        frame.extend(ciphertext);
+        frame
+    }
+
+    fn open(&self, frame: &[u8]) -> io::Result<Vec<u8>> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let Some((&ENCRYPTED_MAGIC, rest)) = frame.split_first() else {
+            return Err(invalid("unencrypted message on an encrypted connection"));
+        };
+        if rest.len() < NONCE_SIZE {
+            return Err(invalid("truncated encrypted message"));
+        }
+        let (nonce, ciphertext) = rest.split_at(NONCE_SIZE);
+        self.0
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| invalid("message wasn't encrypted with the pre-shared key"))
+    }
+}
+
+/// Encrypts a message if a key is used
+pub(crate) fn seal(key: Option<&PresharedKey>, message: Vec<u8>) -> Vec<u8> {
+    match key {
+        Some(key) => key.seal(&message),
+        None => message,
+    }
+}
+
+/// Decrypts a message if a key is used. Unencrypted messages are rejected when a key is used, and the other way around.
+pub(crate) fn open(key: Option<&PresharedKey>, message: &[u8]) -> io::Result<Cow<'_, [u8]>> {
+    match key {
+        Some(key) => key.open(message).map(Cow::Owned),
+        None if message.first() == Some(&ENCRYPTED_MAGIC) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "encrypted message, a pre-shared key is required",
+        )),
+        None => Ok(Cow::Borrowed(message)),
+    }
+}
@@ -0,0 +1,64 @@
+//! Sizes of annotations on scaled fields, e.g. tabletop fields in VR.
+
+use crate::Field;
+use bevy::prelude::*;
+use bevy::transform::TransformSystems;
+
+/// Annotations shrink with the square root of the field scale
+const COMPENSATION: f32 = 0.5;
+
+pub(crate) fn field_scale_plugin(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        update_field_scales.after(TransformSystems::Propagate),
+    );
+}
+
+/// Uniform scale of a field, taken from its global transform.
+/// Annotations like rings, icons, bars and the ball are sized with [`Self::size`]: They shrink less than the field,
+/// so they stay readable on small fields. Positions on the field scale with the field as usual.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
+pub struct FieldScale(pub f32);
+
+impl Default for FieldScale {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+impl FieldScale {
+    /// World size of an annotation that is `size` meters large on a full size field
+    pub fn size(&self, size: f32) -> f32 {
+        size * self.factor()
+    }
+
+    /// Like [`Self::size`], but in the coordinates of the field, which are scaled with the field already
+    pub fn local_size(&self, size: f32) -> f32 {
+        if self.0 > 0.0 {
+            size * self.factor() / self.0
+        } else {
+            size
+        }
+    }
+
+    /// Enlarged fields scale their annotations fully, they are readable anyways
+    pub fn factor(&self) -> f32 {
+        if self.0 < 1.0 {
+            self.0.powf(COMPENSATION)
+        } else {
+            self.0
+        }
+    }
+}
+
+fn update_field_scales(
+    mut q_fields: Query<
+        (&GlobalTransform, &mut FieldScale),
+        (With<Field>, Changed<GlobalTransform>),
+    >,
+) {
+    for (transform, mut scale) in &mut q_fields {
+        scale.set_if_neq(FieldScale(transform.scale().max_element()));
+    }
+}
@@ -0,0 +1,320 @@
+use crate::proto::remote::TeamState;
+use crate::{
+    BALL_RADIUS, FieldGeometry, GameState, GameStateChanged, ROBOT_RADIUS, Team, WorldStateUpdated,
+    receive_field_updates, update_world_state,
+};
+use bevy::prelude::*;
+use std::collections::HashSet;
+
+/// The ball speed has to increase by at least this much between two world states to count as a kick, in m/s
+const KICK_SPEED_INCREASE: f32 = 1.5;
+const MIN_KICK_SPEED: f32 = 2.0;
+/// Robots further away from the ball than this can't have kicked it
+const MAX_KICKER_DISTANCE: f32 = 0.25;
+/// Minimum time between two kicks in µs, so that noise right after a kick isn't detected again
+const KICK_COOLDOWN: u64 = 250_000;
+
+pub(crate) fn game_events_plugin(app: &mut App) {
+    app.add_message::<GameEvent>();
+    app.init_resource::<NearCollisionSettings>();
+    app.register_type::<NearCollisionSettings>();
+    app.add_systems(Update, detect_referee_goals.after(receive_field_updates));
+    app.add_systems(PostUpdate, detect_ball_events.after(update_world_state));
+    app.add_systems(PostUpdate, detect_near_collisions.after(update_world_state));
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameEventKind {
+    /// The ball fully crossed the goal line inside the goal of the other team
+    Goal { scoring_team: Team },
+    /// The ball was accelerated, by the closest robot if one was near the ball
+    Kick {
+        kicker: Option<(Team, u32)>,
+        speed: f32,
+    },
+    /// The ball fully left the field outside of the goals
+    OutOfBounds,
+    /// Two robots came closer than [`NearCollisionSettings::distance`] while approaching each other fast
+    NearCollision {
+        robots: [(Team, u32); 2],
+        /// Speed at which the robots approached each other, in m/s
+        closing_speed: f32,
+    },
+}
+
+/// Thresholds of the [`GameEventKind::NearCollision`] detection, e.g. to tune it for testing a motion planner
+#[derive(Resource, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Resource, Debug, Default, Clone, PartialEq)]
+pub struct NearCollisionSettings {
+    /// Gap between the robot hulls in m
+    pub distance: f32,
+    /// Minimum speed in m/s at which the robots approach each other
+    pub closing_speed: f32,
+}
+
+impl Default for NearCollisionSettings {
+    fn default() -> Self {
+        Self {
+            distance: 0.05,
+            closing_speed: 1.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameEventSource {
+    /// Detected from the displayed world state
+    WorldState,
+    /// Derived from the game state sent by the host, e.g. score changes
+    Referee,
+}
+
+/// Written when a goal, kick or out of bounds ball is detected on a field, e.g. for visual or audio cues.
+/// Goals are only detected from the world state if the host doesn't send scores.
+#[derive(Message, Debug, Clone, Copy)]
+pub struct GameEvent {
+    pub field: Entity,
+    pub kind: GameEventKind,
+    pub source: GameEventSource,
+    /// Where the event happened, relative to the field
+    pub position: Vec3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BallZone {
+    Field,
+    /// Inside the goal of the other team
+    Goal {
+        scoring_team: Team,
+    },
+    Out,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct BallSample {
+    /// Host timestamp in µs
+    timestamp: u64,
+    position: Vec3,
+    speed: f32,
+}
+
+/// State of the event detection on a field
+#[derive(Component, Debug, Default)]
+pub(crate) struct GameEventDetector {
+    last_ball: Option<BallSample>,
+    last_kick: u64,
+    zone: Option<BallZone>,
+    last_scores: Option<(u32, u32)>,
+    /// Robot pairs that are currently too close, they are reported again once they separated
+    near_collisions: HashSet<[(Team, u32); 2]>,
+}
+
+fn ball_zone(position: Vec3, geom: &FieldGeometry) -> Option<BallZone> {
+    let half_size = geom.play_area_size / 2.0;
+    // Only fully crossed lines count, the lines belong to the field
+    let outside_x = position.x.abs() > half_size.x + BALL_RADIUS;
+    let outside_z = position.z.abs() > half_size.y + BALL_RADIUS;
+
+    if outside_x && !outside_z && position.z.abs() < geom.goal_width / 2.0 {
+        // x points towards the blue goal
+        let scoring_team = if position.x > 0.0 {
+            Team::Yellow
+        } else {
+            Team::Blue
+        };
+        Some(BallZone::Goal { scoring_team })
+    } else if outside_x || outside_z {
+        Some(BallZone::Out)
+    } else if position.x.abs() < half_size.x && position.z.abs() < half_size.y {
+        Some(BallZone::Field)
+    } else {
+        // On a line, keep the previous zone
+        None
+    }
+}
+
+pub(crate) fn detect_ball_events(
+    mut world_state_updates: MessageReader<WorldStateUpdated>,
+    mut game_events: MessageWriter<GameEvent>,
+    mut q_fields: Query<(&FieldGeometry, &GameState, &mut GameEventDetector)>,
+) {
+    for update in world_state_updates.read() {
+        let Ok((geom, game_state, mut detector)) = q_fields.get_mut(update.field) else {
+            continue;
+        };
+        // Replays aren't new events, and the jumps to and from the clip would look like kicks or goals
+        if update.replay {
+            detector.last_ball = None;
+            detector.zone = None;
+            continue;
+        }
+        let world_state = &update.world_state;
+        let Some(ball) = world_state.balls.first() else {
+            detector.last_ball = None;
+            continue;
+        };
+        let mut event = |kind| {
+            game_events.write(GameEvent {
+                field: update.field,
+                kind,
+                source: GameEventSource::WorldState,
+                position: ball.translation,
+            });
+        };
+
+        // ======== Kicks ========
+
+        let speed = match detector.last_ball {
+            // Without interpolation, the same packet is displayed for multiple frames
+            Some(last) if last.timestamp >= world_state.timestamp => continue,
+            Some(last) => {
+                let dt = (world_state.timestamp - last.timestamp) as f32 / 1_000_000.0;
+                let speed = last.position.distance(ball.translation) / dt;
+
+                if speed - last.speed > KICK_SPEED_INCREASE
+                    && speed > MIN_KICK_SPEED
+                    && world_state.timestamp > detector.last_kick + KICK_COOLDOWN
+                {
+                    let kicker = world_state
+                        .robots
+                        .iter()
+                        .map(|r| (r, r.translation.xz().distance(last.position.xz())))
+                        .filter(|(_, distance)| *distance < MAX_KICKER_DISTANCE)
+                        .min_by(|(_, a), (_, b)| a.total_cmp(b))
+                        .map(|(r, _)| (r.team, r.id));
+                    event(GameEventKind::Kick { kicker, speed });
+                    detector.last_kick = world_state.timestamp;
+                }
+                speed
+            }
+            None => 0.0,
+        };
+        detector.last_ball = Some(BallSample {
+            timestamp: world_state.timestamp,
+            position: ball.translation,
+            speed,
+        });
+
+        // ======== Goals and out of bounds ========
+
+        let Some(zone) = ball_zone(ball.translation, geom) else {
+            continue;
+        };
+        // Events are only triggered when the ball leaves the field, not when it's placed outside
+        if detector.zone == Some(BallZone::Field) {
+            match zone {
+                // Goals are reported by detect_referee_goals if the host sends scores
+                BallZone::Goal { scoring_team } => {
+                    if !has_scores(game_state) {
+                        event(GameEventKind::Goal { scoring_team });
+                    }
+                }
+                BallZone::Out => event(GameEventKind::OutOfBounds),
+                BallZone::Field => {}
+            }
+        }
+        detector.zone = Some(zone);
+    }
+}
+
+fn detect_near_collisions(
+    settings: Res<NearCollisionSettings>,
+    mut world_state_updates: MessageReader<WorldStateUpdated>,
+    mut game_events: MessageWriter<GameEvent>,
+    mut q_fields: Query<&mut GameEventDetector>,
+) {
+    let max_center_distance = settings.distance + 2.0 * ROBOT_RADIUS;
+    for update in world_state_updates.read() {
+        let Ok(mut detector) = q_fields.get_mut(update.field) else {
+            continue;
+        };
+        if update.replay {
+            continue;
+        }
+        let robots = &update.world_state.robots;
+
+        let mut close_pairs = HashSet::new();
+        for (i, a) in robots.iter().enumerate() {
+            for b in &robots[i + 1..] {
+                let offset = b.translation.xz() - a.translation.xz();
+                if offset.length() > max_center_distance {
+                    continue;
+                }
+                let pair = [(a.team, a.id), (b.team, b.id)];
+                close_pairs.insert(pair);
+                if detector.near_collisions.contains(&pair) {
+                    continue;
+                }
+
+                // Robots without a velocity estimate can't be judged yet
+                let (Some(velocity_a), Some(velocity_b)) = (a.velocity, b.velocity) else {
+                    continue;
+                };
+                let closing_speed =
+                    -(velocity_b.xz() - velocity_a.xz()).dot(offset.normalize_or_zero());
+                if closing_speed >= settings.closing_speed {
+                    detector.near_collisions.insert(pair);
+                    game_events.write(GameEvent {
+                        field: update.field,
+                        kind: GameEventKind::NearCollision {
+                            robots: pair,
+                            closing_speed,
+                        },
+                        source: GameEventSource::WorldState,
+                        position: a.translation.midpoint(b.translation),
+                    });
+                }
+            }
+        }
+        detector
+            .near_collisions
+            .retain(|pair| close_pairs.contains(pair));
+    }
+}
+
+fn has_scores(game_state: &GameState) -> bool {
+    [&game_state.yellow_team, &game_state.blue_team]
+        .into_iter()
+        .any(|team| team.as_ref().is_some_and(|t| t.score.is_some()))
+}
+
+/// Goals are taken from the score if the host sends one, it's more reliable than the ball position
+fn detect_referee_goals(
+    mut game_state_changes: MessageReader<GameStateChanged>,
+    mut game_events: MessageWriter<GameEvent>,
+    mut q_fields: Query<(&FieldGeometry, &mut GameEventDetector)>,
+) {
+    for change in game_state_changes.read() {
+        let Ok((geom, mut detector)) = q_fields.get_mut(change.field) else {
+            continue;
+        };
+        let score = |team: &Option<TeamState>| team.as_ref().and_then(|t| t.score).unwrap_or(0);
+        let scores = (
+            score(&change.game_state.yellow_team),
+            score(&change.game_state.blue_team),
+        );
+
+        // The first game state only sets the initial scores
+        if let Some((last_yellow, last_blue)) = detector.last_scores.replace(scores) {
+            for (scoring_team, scored) in [
+                (Team::Yellow, scores.0 > last_yellow),
+                (Team::Blue, scores.1 > last_blue),
+            ] {
+                if !scored {
+                    continue;
+                }
+                // x points towards the blue goal
+                let goal_x = match scoring_team {
+                    Team::Yellow => geom.play_area_size.x / 2.0,
+                    Team::Blue => -geom.play_area_size.x / 2.0,
+                };
+                game_events.write(GameEvent {
+                    field: change.field,
+                    kind: GameEventKind::Goal { scoring_team },
+                    source: GameEventSource::Referee,
+                    position: Vec3::new(goal_x, 0.0, 0.0),
+                });
+            }
+        }
+    }
+}
@@ -0,0 +1,163 @@
+//! A second world state source for a field, displayed as translucent "ghost" robots and balls.
+//! Useful to compare e.g. the vision tracker with a team's internal world model.
+
+#[cfg(feature = "networking")]
+use crate::PresharedKey;
+#[cfg(feature = "networking")]
+use crate::proto::remote::udp_stream_request::UdpStream;
+#[cfg(feature = "networking")]
+use crate::proto::remote::{UdpStreamRequest, ws_request};
+use crate::{
+    Field, FieldConnection, FieldHost, FieldOrientation, Paused, StateFilter, Team, UpdatePacket,
+    WorldStateSampling, receive_field_updates, update_world_state,
+};
+use bevy::prelude::*;
+use bevy::transform::TransformSystems;
+use std::time::Instant;
+
+pub(crate) fn ghost_plugin(app: &mut App) {
+    app.add_systems(Update, receive_ghost_updates.after(receive_field_updates));
+    app.add_systems(
+        PostUpdate,
+        update_ghost_world_state
+            .run_if(|paused: Res<Paused>| !paused.0)
+            .after(update_world_state)
+            .before(TransformSystems::Propagate),
+    );
+}
+
+/// Only the world state of a ghost source is used, all other packets are dropped.
+/// Spawn it as a child of the field it should be compared with:
+/// `commands.entity(field).with_child(GhostSource::bind(host, key))`
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component, Debug, from_reflect = false)]
+#[require(Transform, StateFilter)]
+pub struct GhostSource {
+    pub host: FieldHost,
+    #[reflect(ignore)]
+    connection: FieldConnection,
+}
+
+impl GhostSource {
+    /// The key is only used for encrypted hosts
+    #[cfg(feature = "networking")]
+    pub fn bind(host: FieldHost, key: Option<&PresharedKey>) -> Self {
+        let field = Field::connect(host, key);
+        // Everything else would be dropped anyways
+        _ = field
+            .connection
+            .sender
+            .send_blocking(ws_request::Content::UdpStreamReq(UdpStreamRequest {
+                stream: vec![UdpStream::WorldState as i32],
+                port: 0,
+                compression: None,
+                world_state_deltas: None,
+            }));
+        field.into_ghost()
+    }
+}
+
+impl Field {
+    /// Uses the connection of this field as a ghost source for another field, e.g. to compare a recording with a live host.
+    pub fn into_ghost(self) -> GhostSource {
+        GhostSource {
+            host: self.host,
+            connection: self.connection,
+        }
+    }
+}
+
+/// A robot of a [`GhostSource`]. Not a [`crate::Robot`], so it isn't affected by anything that works on the actual robots.
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component, Debug, Clone)]
+#[require(Transform)]
+pub struct GhostRobot {
+    pub id: u32,
+    pub team: Team,
+}
+
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component, Debug, Default, Clone)]
+#[require(Transform)]
+pub struct GhostBall;
+
+fn receive_ghost_updates(
+    mut commands: Commands,
+    mut q_ghosts: Query<(&GhostSource, &mut StateFilter, Option<&ChildOf>, Entity)>,
+    q_orientations: Query<&FieldOrientation>,
+) {
+    for (ghost, mut state_filter, child_of, entity) in &mut q_ghosts {
+        if ghost.connection.io_task.is_finished() {
+            info!(
+                "Connection to ghost source {} closed",
+                ghost.host.websocket_addr
+            );
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        // Ghosts are compared against their field, so they use its coordinate convention
+        let orientation = child_of
+            .and_then(|child_of| q_orientations.get(child_of.parent()).ok())
+            .copied()
+            .unwrap_or_default();
+        while let Ok(mut packet) = ghost.connection.receiver.try_recv() {
+            orientation.apply(&mut packet);
+            if let UpdatePacket::WorldState(world_state) = packet {
+                state_filter.push_packet(world_state.into());
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_ghost_world_state(
+    mut commands: Commands,
+    sampling: Res<WorldStateSampling>,
+    (q_ghosts, mut q_robots, q_balls): (
+        Query<(&StateFilter, Entity), With<GhostSource>>,
+        Query<(&GhostRobot, &mut Transform, &ChildOf, Entity)>,
+        Query<(&ChildOf, Entity), With<GhostBall>>,
+    ),
+) {
+    let sample_time = sampling.display_time.unwrap_or_else(Instant::now);
+    for (state_filter, ghost_entity) in &q_ghosts {
+        let world_state = state_filter.sample_at(sample_time, sampling.interpolate);
+
+        // Balls have no id, so they are recreated like the actual balls
+        q_balls
+            .iter()
+            .filter(|(c, _)| c.parent() == ghost_entity)
+            .for_each(|(_, e)| commands.entity(e).despawn());
+        for ball in &world_state.balls {
+            commands
+                .entity(ghost_entity)
+                .with_child((GhostBall, Transform::from_translation(ball.translation)));
+        }
+
+        let mut leftover_robots = q_robots
+            .iter_mut()
+            .filter(|(_, _, c, _)| c.parent() == ghost_entity)
+            .collect::<Vec<_>>();
+        for robot in &world_state.robots {
+            let leftover_index = leftover_robots
+                .iter()
+                .position(|(r, _, _, _)| r.team == robot.team && r.id == robot.id);
+            if let Some(i) = leftover_index {
+                let (_, mut transform, _, _) = leftover_robots.remove(i);
+                transform.set_if_neq(robot.transform());
+            } else {
+                commands.entity(ghost_entity).with_child((
+                    GhostRobot {
+                        id: robot.id,
+                        team: robot.team,
+                    },
+                    robot.transform(),
+                ));
+            }
+        }
+        leftover_robots
+            .into_iter()
+            .for_each(|(_, _, _, e)| commands.entity(e).despawn());
+    }
+}
@@ -0,0 +1,212 @@
+use crate::game_events::detect_ball_events;
+use crate::snapshot::WorldSnapshot;
+use crate::world_state_filter::interpolate_world_state;
+use crate::{GameEvent, GameEventKind, Paused, WorldStateUpdated, update_world_state};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// Displayed world states before a goal that are included in its replay
+pub const GOAL_REPLAY_DURATION: Duration = Duration::from_secs(10);
+/// Playback speed for slow motion replays
+pub const SLOW_MOTION_SPEED: f32 = 0.25;
+/// The replay continues this long after the goal, to show the ball in the net
+const GOAL_FOLLOW_UP: Duration = Duration::from_secs(2);
+/// Replays are offered this long after they become available, unless a new goal replaces them
+const REPLAY_OFFER_DURATION: Duration = Duration::from_secs(30);
+
+pub(crate) fn goal_replay_plugin(app: &mut App) {
+    app.add_systems(
+        PostUpdate,
+        advance_goal_replays
+            .run_if(|paused: Res<Paused>| !paused.0)
+            .before(update_world_state),
+    );
+    app.add_systems(PostUpdate, record_goal_replays.after(detect_ball_events));
+}
+
+/// Instant replays of the last goal of a field.
+/// The displayed world states are buffered, and once a goal is detected the states around it are kept as a clip
+/// that can be played back in place of the live state.
+#[derive(Component, Debug, Default)]
+pub struct GoalReplay {
+    history: VecDeque<(Instant, Arc<WorldSnapshot>)>,
+    /// Time and ball position of a goal that is still followed up before its clip is cut
+    pending_goal: Option<(Instant, Vec3)>,
+    clip: Option<ReplayClip>,
+    playback: Option<ReplayPlayback>,
+}
+
+/// The world states around a goal
+#[derive(Debug)]
+pub struct ReplayClip {
+    pub created: Instant,
+    /// Where the goal was scored, in field coordinates
+    pub goal_position: Vec3,
+    /// Relative to the first frame
+    frames: Vec<(Duration, Arc<WorldSnapshot>)>,
+}
+
+impl ReplayClip {
+    pub fn duration(&self) -> Duration {
+        self.frames.last().map_or(Duration::ZERO, |(time, _)| *time)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ReplayPlayback {
+    /// Relative to real time, e.g. [`SLOW_MOTION_SPEED`]
+    pub speed: f32,
+    /// Requests a camera behind the goal from clients that control their camera
+    pub goal_cam: bool,
+    pub position: Duration,
+}
+
+impl GoalReplay {
+    /// The clip of the last goal, while it is offered
+    pub fn clip(&self) -> Option<&ReplayClip> {
+        self.clip.as_ref()
+    }
+
+    pub fn playback(&self) -> Option<&ReplayPlayback> {
+        self.playback.as_ref()
+    }
+
+    /// Starts the replay from the beginning, does nothing if no clip is available
+    pub fn play(&mut self, speed: f32, goal_cam: bool) {
+        if self.clip.is_some() {
+            self.playback = Some(ReplayPlayback {
+                speed,
+                goal_cam,
+                position: Duration::ZERO,
+            });
+        }
+    }
+
+    /// Returns to the live state, the clip stays available
+    pub fn stop(&mut self) {
+        self.playback = None;
+    }
+
+    /// The world state to display instead of the live state while a replay is playing
+    pub(crate) fn current_frame(&self) -> Option<Arc<WorldSnapshot>> {
+        let (playback, clip) = (self.playback.as_ref()?, self.clip.as_ref()?);
+        let next_idx = clip
+            .frames
+            .partition_point(|(time, _)| *time <= playback.position);
+        // Slow motion shows the same frames for longer, so they are interpolated just like live packets
+        match (
+            clip.frames.get(next_idx.wrapping_sub(1)),
+            clip.frames.get(next_idx),
+        ) {
+            (Some((prev_time, prev)), Some((next_time, next))) => {
+                Some(Arc::new(interpolate_world_state(
+                    playback.position.as_micros() as u64,
+                    prev_time.as_micros() as u64,
+                    prev,
+                    next_time.as_micros() as u64,
+                    next,
+                )))
+            }
+            (Some((_, frame)), None) | (None, Some((_, frame))) => Some(Arc::clone(frame)),
+            (None, None) => None,
+        }
+    }
+}
+
+fn advance_goal_replays(time: Res<Time>, mut q_fields: Query<&mut GoalReplay>) {
+    for mut replay in &mut q_fields {
+        let Some(playback) = replay.playback else {
+            continue;
+        };
+        let duration = replay
+            .clip
+            .as_ref()
+            .map_or(Duration::ZERO, ReplayClip::duration);
+        let position = playback.position + time.delta().mul_f32(playback.speed);
+        // Return to live once the clip is over
+        replay.playback = (position <= duration).then_some(ReplayPlayback {
+            position,
+            ..playback
+        });
+    }
+}
+
+fn record_goal_replays(
+    mut world_state_updates: MessageReader<WorldStateUpdated>,
+    mut game_events: MessageReader<GameEvent>,
+    mut q_fields: Query<&mut GoalReplay>,
+) {
+    let now = Instant::now();
+    for update in world_state_updates.read() {
+        let Ok(mut replay) = q_fields.get_mut(update.field) else {
+            continue;
+        };
+        // Replayed states are displayed through the same messages, but only the live state is recorded
+        if replay.playback.is_none() {
+            replay
+                .history
+                .push_back((now, Arc::clone(&update.world_state)));
+        }
+    }
+
+    for event in game_events.read() {
+        let GameEventKind::Goal { .. } = event.kind else {
+            continue;
+        };
+        let Ok(mut replay) = q_fields.get_mut(event.field) else {
+            continue;
+        };
+        // Goals can be reported by both the referee and the world state, the first one is kept.
+        // The history has a gap while a replay is playing, so goals during replays aren't offered.
+        if replay.pending_goal.is_none() && replay.playback.is_none() {
+            replay.pending_goal = Some((now, event.position));
+        }
+    }
+
+    for mut replay in &mut q_fields {
+        let replay = &mut *replay;
+
+        if let Some((goal_time, goal_position)) = replay.pending_goal
+            && now - goal_time >= GOAL_FOLLOW_UP
+        {
+            replay.pending_goal = None;
+            let start = goal_time
+                .checked_sub(GOAL_REPLAY_DURATION)
+                .unwrap_or(goal_time);
+            let frames = replay
+                .history
+                .iter()
+                .filter(|(time, _)| *time >= start)
+                .collect::<Vec<_>>();
+            if let Some((first_time, _)) = frames.first() {
+                replay.clip = Some(ReplayClip {
+                    created: now,
+                    goal_position,
+                    frames: frames
+                        .iter()
+                        .map(|(time, state)| (*time - *first_time, Arc::clone(state)))
+                        .collect(),
+                });
+            }
+        }
+
+        if replay.playback.is_none()
+            && replay
+                .clip
+                .as_ref()
+                .is_some_and(|clip| now - clip.created > REPLAY_OFFER_DURATION)
+        {
+            replay.clip = None;
+        }
+
+        while replay
+            .history
+            .front()
+            .is_some_and(|(time, _)| now - *time > GOAL_REPLAY_DURATION + GOAL_FOLLOW_UP)
+        {
+            replay.history.pop_front();
+        }
+    }
+}
@@ -0,0 +1,409 @@
+//! Translations of user-facing strings. The English text is the key, so untranslated strings stay readable.
+//! Log messages are not translated, they are meant for the developers.
+
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::sync::LazyLock;
+
+pub(crate) fn i18n_plugin(app: &mut App) {
+    app.init_resource::<Language>();
+    app.register_type::<Language>();
+}
+
+/// Language of the UI, can be changed at runtime
+#[derive(Resource, Reflect, Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[reflect(Resource, Debug, Default, Clone, PartialEq)]
+pub enum Language {
+    #[default]
+    English,
+    German,
+}
+
+impl Language {
+    pub const ALL: [Self; 2] = [Self::English, Self::German];
+
+    /// The name in the language itself, so it can be found without understanding the current one
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::English => "English",
+            Language::German => "Deutsch",
+        }
+    }
+
+    /// ISO 639-1 code
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::English => "en",
+            Language::German => "de",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            Language::English => Language::German,
+            Language::German => Language::English,
+        }
+    }
+
+    /// Parses a language code or POSIX locale like `de`, `de_DE.UTF-8` or `en-US`
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let code = locale.split(['_', '-', '.', '@']).next()?;
+        Self::ALL
+            .into_iter()
+            .find(|language| language.code().eq_ignore_ascii_case(code))
+    }
+
+    /// The language of the user's locale, English if it isn't supported
+    pub fn from_env() -> Self {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .into_iter()
+            .filter_map(|var| std::env::var(var).ok())
+            .find(|locale| !locale.is_empty())
+            .and_then(|locale| Self::from_locale(&locale))
+            .unwrap_or_default()
+    }
+
+    /// Translates an English UI string, or returns it unchanged if there is no translation
+    pub fn tr<'a>(self, text: &'a str) -> &'a str {
+        let catalog = match self {
+            Language::English => return text,
+            Language::German => &*GERMAN,
+        };
+        catalog.get(text).copied().unwrap_or(text)
+    }
+
+    /// Translates a sentence with `{name}` placeholders and fills them in.
+    /// Whole sentences are translated, so the translation can put the values where its grammar needs them.
+    pub fn trf(self, text: &str, args: &[(&str, &dyn Display)]) -> String {
+        args.iter()
+            .fold(self.tr(text).to_string(), |text, (name, value)| {
+                text.replace(&format!("{{{name}}}"), &value.to_string())
+            })
+    }
+
+    /// Translates a game stage as sent by the hosts, e.g. `NORMAL_FIRST_HALF` or `NormalFirstHalf`.
+    /// Unknown stages are returned unchanged.
+    pub fn stage<'a>(self, stage: &'a str) -> &'a str {
+        let key: String = stage
+            .chars()
+            .filter(char::is_ascii_alphanumeric)
+            .map(|c| c.to_ascii_lowercase())
+            .collect();
+        GAME_STAGES
+            .iter()
+            .find(|(stage_key, _)| *stage_key == key)
+            .map_or(stage, |&(_, name)| self.tr(name))
+    }
+}
+
+/// English names of the stages of the SSL referee protocol, by their lowercase enum names without separators
+const GAME_STAGES: &[(&str, &str)] = &[
+    ("normalfirsthalfpre", "Before the first half"),
+    ("normalfirsthalf", "First half"),
+    ("normalhalftime", "Half time"),
+    ("normalsecondhalfpre", "Before the second half"),
+    ("normalsecondhalf", "Second half"),
+    ("extratimebreak", "Break before extra time"),
+    ("extrafirsthalfpre", "Before the first extra half"),
+    ("extrafirsthalf", "First extra half"),
+    ("extrahalftime", "Extra half time"),
+    ("extrasecondhalfpre", "Before the second extra half"),
+    ("extrasecondhalf", "Second extra half"),
+    ("penaltyshootoutbreak", "Break before the shootout"),
+    ("penaltyshootout", "Penalty shootout"),
+    ("postgame", "Game over"),
+];
+
+static GERMAN: LazyLock<HashMap<&'static str, &'static str>> =
+    LazyLock::new(|| GERMAN_CATALOG.iter().copied().collect());
+
+#[rustfmt::skip]
+const GERMAN_CATALOG: &[(&str, &str)] = &[
+    // Wrist clock and VR panels
+    ("Measure", "Messen"),
+    ("Clear", "Leeren"),
+    ("Grid", "Raster"),
+    ("Solo", "Solo"),
+    ("Tags", "Marker"),
+    ("Save", "Speichern"),
+    ("Load", "Laden"),
+    ("Share", "Teilen"),
+    ("Net", "Netz"),
+    ("Pass", "Passthr."),
+    ("Comfort", "Komfort"),
+    ("Perf", "Leistung"),
+    ("Language", "Sprache"),
+    ("Vignette", "Vignette"),
+    ("Placement", "Platzierung"),
+    ("Seated", "Sitzend"),
+    ("Origin", "Ursprung"),
+    ("Instant", "Sofort"),
+    ("Animated", "Animiert"),
+    ("Fade", "Blende"),
+    ("Stage", "Stage"),
+    ("Local", "Lokal"),
+    ("Brightness", "Helligkeit"),
+    ("Contrast", "Kontrast"),
+    ("Saturation", "Sättigung"),
+    ("Reset", "Zurücksetzen"),
+    ("Occlusion", "Verdeckung"),
+    ("Frame", "Frame"),
+    ("Frame {ms} ms ({fps} fps)", "Frame {ms} ms ({fps} fps)"),
+    ("display {rate} Hz", "Display {rate} Hz"),
+    ("Dropped frames", "Verlorene Frames"),
+    ("Quality", "Qualität"),
+    ("Full", "Voll"),
+    ("ReducedCircles", "Weniger Kreissegmente"),
+    ("NoTrails", "Keine Spuren"),
+    ("SimpleRobots", "Einfache Roboter"),
+    ("LimitedUpdates", "Seltenere Updates"),
+    ("Entities {entities}, meshes {meshes}", "Entitäten {entities}, Meshes {meshes}"),
+    ("Headset hot, throttling ({level})", "Headset heiß, gedrosselt ({level})"),
+    ("Battery", "Akku"),
+    ("Battery {percent}%", "Akku {percent}%"),
+    ("Moderate", "mäßig"),
+    ("Severe", "stark"),
+    ("Critical", "kritisch"),
+    ("Emergency", "Notfall"),
+    ("Shutdown", "Abschaltung"),
+    ("Debug values", "Debug-Werte"),
+    ("Plots", "Diagramme"),
+    ("Replay", "Wiederholung"),
+    ("Replay {field}  {position} / {duration} s", "Wiederholung {field}  {position} / {duration} s"),
+    ("Slow motion", "Zeitlupe"),
+    ("Live", "Live"),
+    ("Unknown", "Unbekannt"),
+    ("Self-test", "Selbsttest"),
+    ("Dismiss", "Schließen"),
+    ("no vision", "keine Vision"),
+
+    // Game stages
+    ("Before the first half", "Vor der ersten Halbzeit"),
+    ("First half", "Erste Halbzeit"),
+    ("Half time", "Halbzeitpause"),
+    ("Before the second half", "Vor der zweiten Halbzeit"),
+    ("Second half", "Zweite Halbzeit"),
+    ("Break before extra time", "Pause vor der Verlängerung"),
+    ("Before the first extra half", "Vor der ersten Verlängerungshälfte"),
+    ("First extra half", "Erste Verlängerungshälfte"),
+    ("Extra half time", "Halbzeitpause der Verlängerung"),
+    ("Before the second extra half", "Vor der zweiten Verlängerungshälfte"),
+    ("Second extra half", "Zweite Verlängerungshälfte"),
+    ("Break before the shootout", "Pause vor dem Penaltyschießen"),
+    ("Penalty shootout", "Penaltyschießen"),
+    ("Game over", "Spielende"),
+
+    // Network diagnostics
+    ("Network", "Netzwerk"),
+    ("Interfaces", "Schnittstellen"),
+    ("Multicast groups", "Multicast-Gruppen"),
+    ("Recent errors", "Letzte Fehler"),
+    ("joined", "beigetreten"),
+    ("up", "aktiv"),
+    ("down", "inaktiv"),
+    ("multicast", "Multicast"),
+    ("Name", "Name"),
+    ("Index", "Index"),
+    ("Up", "Aktiv"),
+    ("Multicast", "Multicast"),
+    ("Addresses", "Adressen"),
+    ("No groups joined", "Keinen Gruppen beigetreten"),
+    ("No errors", "Keine Fehler"),
+    ("yes", "ja"),
+    ("no", "nein"),
+
+    // Self-test checks
+    ("ok", "ok"),
+    ("warning", "Warnung"),
+    ("error", "Fehler"),
+    ("Assets", "Assets"),
+    ("Robot model and shaders loaded", "Robotermodell und Shader geladen"),
+    ("Network interfaces", "Netzwerkschnittstellen"),
+    ("No interface is up and supports multicast, hosts can't be discovered. Connect to the network of the hosts or add them by address.", "Keine Schnittstelle ist aktiv und unterstützt Multicast, Hosts können nicht gefunden werden. Mit dem Netzwerk der Hosts verbinden oder sie per Adresse hinzufügen."),
+    ("Discovery socket (ipv4)", "Discovery-Socket (IPv4)"),
+    ("Discovery socket (ipv6)", "Discovery-Socket (IPv6)"),
+    ("Discovery groups joined", "Discovery-Gruppen beigetreten"),
+    ("Hand tracking", "Hand-Tracking"),
+    ("Hand interaction", "Hand-Interaktion"),
+    ("Passthrough", "Passthrough"),
+    ("Extension enabled", "Erweiterung aktiviert"),
+    ("The runtime doesn't offer hand tracking, gestures and the wrist panel are unavailable. Enable hand tracking in the headset settings.", "Die Runtime bietet kein Hand-Tracking, Gesten und das Handgelenk-Panel sind nicht verfügbar. Hand-Tracking in den Headset-Einstellungen aktivieren."),
+    ("The runtime doesn't offer the hand interaction profile, so panels can't be clicked with a pinch. Update the headset software.", "Die Runtime bietet kein Hand-Interaktionsprofil, Panels können nicht per Pinch-Geste angeklickt werden. Die Headset-Software aktualisieren."),
+    ("Passthrough isn't available, fields are shown in front of black. Allow passthrough for the app in the headset settings.", "Passthrough ist nicht verfügbar, Felder werden vor Schwarz angezeigt. Passthrough für die App in den Headset-Einstellungen erlauben."),
+
+    // Desktop windows
+    ("Visualizations", "Visualisierungen"),
+    ("selection pending", "Auswahl ausstehend"),
+    ("selection ignored by host", "Auswahl vom Host ignoriert"),
+    ("{count} decode errors in the last minute", "{count} Dekodierfehler in der letzten Minute"),
+    ("Use host colors", "Host-Farben verwenden"),
+    ("auto", "automatisch"),
+    ("Normal", "Normal"),
+    ("Mirrored", "Gespiegelt"),
+    ("Rotated 180°", "Um 180° gedreht"),
+    ("Swap sides", "Seiten tauschen"),
+    ("View the field from the other side", "Das Feld von der anderen Seite ansehen"),
+    ("vision {age} ms", "Vision {age} ms"),
+    ("robots Y {yellow} B {blue}", "Roboter G {yellow} B {blue}"),
+    ("Robots", "Roboter"),
+    ("Robot", "Roboter"),
+    ("Kicker", "Kicker"),
+    ("Dribbler", "Dribbler"),
+    ("Radio", "Funk"),
+    ("Role", "Rolle"),
+    ("Path", "Pfad"),
+    ("Path {number}", "Pfad {number}"),
+    ("{charge} armed", "{charge} scharf"),
+    ("on", "an"),
+    ("off", "aus"),
+    ("Record", "Aufzeichnen"),
+    ("Roles", "Rollen"),
+    ("Icon", "Symbol"),
+    ("Yellow", "Gelb"),
+    ("Blue", "Blau"),
+    ("Keeper", "Torwart"),
+    ("Defender", "Verteidiger"),
+    ("Midfielder", "Mittelfeld"),
+    ("Striker", "Stürmer"),
+    ("Square", "Quadrat"),
+    ("Circle", "Kreis"),
+    ("Diamond", "Raute"),
+    ("Triangle", "Dreieck"),
+    ("Time window (s)", "Zeitfenster (s)"),
+    ("Add plot", "Diagramm hinzufügen"),
+    ("Ball speed (m/s)", "Ballgeschwindigkeit (m/s)"),
+    ("Buffered packets", "Gepufferte Pakete"),
+    ("Min. buffer time (ms)", "Min. Pufferzeit (ms)"),
+    ("Fields", "Felder"),
+    ("Solo next", "Nächstes solo"),
+    ("Show all", "Alle zeigen"),
+    ("Field", "Feld"),
+    ("Score", "Spielstand"),
+    ("Connection", "Verbindung"),
+    ("Go to field", "Zum Feld"),
+    ("no recent vision", "keine aktuelle Vision"),
+    ("{age} ms, {errors} errors, {stutters} stutters", "{age} ms, {errors} Fehler, {stutters} Ruckler"),
+    ("Camera paths", "Kamerafahrten"),
+    ("Stop", "Stopp"),
+    ("Play", "Abspielen"),
+    ("Delete", "Löschen"),
+    ("at ({x}, {y}, {z})", "bei ({x}, {y}, {z})"),
+    ("Add current view", "Aktuelle Ansicht hinzufügen"),
+    ("New path", "Neuer Pfad"),
+    ("Picture in picture", "Bild im Bild"),
+    ("Show inset", "Einblendung zeigen"),
+    ("View", "Ansicht"),
+    ("Corner", "Ecke"),
+    ("Size", "Größe"),
+    ("Aspect ratio", "Seitenverhältnis"),
+    ("Top-down", "Von oben"),
+    ("Yellow goal", "Gelbes Tor"),
+    ("Blue goal", "Blaues Tor"),
+    ("Top left", "Oben links"),
+    ("Top right", "Oben rechts"),
+    ("Bottom left", "Unten links"),
+    ("Bottom right", "Unten rechts"),
+    ("Back to live", "Zurück zu live"),
+    ("Goal on {field}", "Tor auf {field}"),
+    ("Goal cam", "Torkamera"),
+    ("Cursor not on a field", "Cursor nicht auf einem Feld"),
+    ("Accessibility", "Barrierefreiheit"),
+    ("UI scale", "UI-Skalierung"),
+    ("Text size", "Textgröße"),
+    ("High contrast", "Hoher Kontrast"),
+
+    // Command palette
+    ("Type a command...", "Befehl eingeben..."),
+    ("Toggle visualizations", "Visualisierungen ein/aus"),
+    ("Toggle field", "Feld ein/aus"),
+    ("Toggle ball", "Ball ein/aus"),
+    ("Cycle robot rendering", "Roboterdarstellung wechseln"),
+    ("Toggle telemetry bars", "Telemetriebalken ein/aus"),
+    ("Toggle ruler grid", "Messraster ein/aus"),
+    ("Toggle robot marker tags", "Robotermarker ein/aus"),
+    ("Cycle field lights", "Feldbeleuchtung wechseln"),
+    ("Pause/Resume", "Pause/Fortsetzen"),
+    ("Camera: Overview", "Kamera: Übersicht"),
+    ("Camera: Top down", "Kamera: Von oben"),
+    ("Camera: Behind yellow goal", "Kamera: Hinter dem gelben Tor"),
+    ("Camera: Behind blue goal", "Kamera: Hinter dem blauen Tor"),
+    ("Camera: Auto director", "Kamera: Automatische Regie"),
+    ("Show only the next field", "Nur das nächste Feld zeigen"),
+    ("Show all fields", "Alle Felder zeigen"),
+    ("Measure distances", "Abstände messen"),
+    ("Clear measurements", "Messungen löschen"),
+    ("Save session", "Sitzung speichern"),
+    ("Load session", "Sitzung laden"),
+    ("Share snapshot with other viewers", "Snapshot mit anderen Zuschauern teilen"),
+    ("Save snapshot", "Snapshot speichern"),
+    ("Command palette", "Befehlspalette"),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locales_are_parsed() {
+        assert_eq!(Language::from_locale("de_DE.UTF-8"), Some(Language::German));
+        assert_eq!(Language::from_locale("en-US"), Some(Language::English));
+        assert_eq!(Language::from_locale("DE"), Some(Language::German));
+        assert_eq!(Language::from_locale("fr_FR"), None);
+        assert_eq!(Language::from_locale("C"), None);
+    }
+
+    #[test]
+    fn untranslated_strings_fall_back_to_english() {
+        assert_eq!(Language::German.tr("no such string"), "no such string");
+        assert_eq!(Language::English.tr("Save"), "Save");
+        assert_ne!(Language::German.tr("Save"), "Save");
+    }
+
+    #[test]
+    fn catalog_has_no_duplicate_keys() {
+        assert_eq!(GERMAN.len(), GERMAN_CATALOG.len());
+    }
+
+    #[test]
+    fn translations_keep_the_placeholders() {
+        let placeholders = |text: &str| {
+            let mut names: Vec<_> = text
+                .split('{')
+                .skip(1)
+                .filter_map(|part| part.split_once('}').map(|(name, _)| name.to_string()))
+                .collect();
+            names.sort();
+            names
+        };
+        for (english, german) in GERMAN_CATALOG {
+            assert_eq!(placeholders(english), placeholders(german), "{english}");
+        }
+    }
+
+    #[test]
+    fn placeholders_are_filled_in() {
+        assert_eq!(
+            Language::German.trf("Goal on {field}", &[("field", &"field-a")]),
+            "Tor auf field-a"
+        );
+        assert_eq!(
+            Language::English.trf(
+                "{age} ms, {errors} errors, {stutters} stutters",
+                &[("age", &12), ("errors", &0), ("stutters", &3)]
+            ),
+            "12 ms, 0 errors, 3 stutters"
+        );
+    }
+
+    #[test]
+    fn game_stages_are_translated() {
+        assert_eq!(Language::English.stage("NORMAL_FIRST_HALF"), "First half");
+        assert_eq!(Language::German.stage("NormalFirstHalf"), "Erste Halbzeit");
+        assert_eq!(
+            Language::German.stage("PENALTY_SHOOTOUT"),
+            "Penaltyschießen"
+        );
+        assert_eq!(Language::German.stage("Demo"), "Demo");
+    }
+}
@@ -0,0 +1,87 @@
+//! Picks one address for hosts that are visible on several network interfaces, e.g. over wifi and a usb tether.
+
+use crate::FieldHost;
+use crate::network_tasks::DiscoveredHost;
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+
+/// Interfaces that are preferred when a host is visible on several of them, matched by name.
+/// Interfaces that aren't listed come last, ordered by their interface index.
+#[derive(Resource, Reflect, Debug, Default, Clone, PartialEq)]
+#[reflect(Resource, Debug, Default, Clone, PartialEq)]
+pub struct InterfacePreference {
+    /// Earlier interfaces are preferred
+    pub priority: Vec<String>,
+    /// Interface per hostname, overrides the priority list. Hosts without a hostname can't be overridden.
+    pub host_overrides: HashMap<String, String>,
+}
+
+/// The interfaces each host is visible on, by hostname, ordered by preference
+#[derive(Resource, Debug, Default, Clone, PartialEq)]
+pub struct HostInterfaces(pub HashMap<String, Vec<String>>);
+
+#[derive(PartialEq, Eq, Hash)]
+enum HostKey {
+    Addr(SocketAddr),
+    Id(u32),
+}
+
+impl InterfacePreference {
+    /// Lower is better, ties are broken by the interface index
+    fn rank(&self, hostname: Option<&str>, interface: Option<&(u32, String)>) -> (usize, u32) {
+        let Some((index, name)) = interface else {
+            return (usize::MAX, u32::MAX);
+        };
+        if hostname.and_then(|hostname| self.host_overrides.get(hostname)) == Some(name) {
+            return (0, *index);
+        }
+        let priority = self
+            .priority
+            .iter()
+            .position(|preferred| preferred == name)
+            .map_or(usize::MAX - 1, |position| position + 1);
+        (priority, *index)
+    }
+
+    /// Groups the discovered addresses by host instance and keeps the preferred one of each host
+    pub(crate) fn select(&self, discovered: &[DiscoveredHost]) -> (Vec<FieldHost>, HostInterfaces) {
+        let mut instances: HashMap<HostKey, Vec<&DiscoveredHost>> = HashMap::new();
+        for host in discovered {
+            let key = host
+                .advertisement
+                .instance_id
+                .map_or(HostKey::Addr(host.addr), HostKey::Id);
+            instances.entry(key).or_default().push(host);
+        }
+
+        let mut hosts = Vec::new();
+        let mut interfaces = HostInterfaces::default();
+        for mut addresses in instances.into_values() {
+            addresses.sort_by_key(|host| {
+                let hostname = host.advertisement.hostname.as_deref();
+                (self.rank(hostname, host.interface.as_ref()), host.addr)
+            });
+            let preferred = addresses[0];
+
+            let mut websocket_addr = preferred.addr;
+            websocket_addr.set_port(preferred.advertisement.websocket_port as u16);
+            hosts.push(FieldHost {
+                websocket_addr,
+                hostname: preferred.advertisement.hostname.clone(),
+                encrypted: preferred.advertisement.encrypted(),
+            });
+
+            if let Some(hostname) = &preferred.advertisement.hostname {
+                let mut names: Vec<_> = addresses
+                    .iter()
+                    .filter_map(|host| Some(host.interface.as_ref()?.1.clone()))
+                    .collect();
+                names.dedup();
+                interfaces.0.insert(hostname.clone(), names);
+            }
+        }
+
+        (hosts, interfaces)
+    }
+}
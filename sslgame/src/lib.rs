@@ -2,30 +2,133 @@ pub mod proto {
     pub mod remote {
         include!(concat!(env!("OUT_DIR"), "/remote.rs"));
     }
+    pub mod session {
+        include!(concat!(env!("OUT_DIR"), "/session.rs"));
+    }
+    pub mod viewer {
+        include!(concat!(env!("OUT_DIR"), "/viewer.rs"));
+    }
 }
+mod accessibility;
+mod clock;
+#[cfg(feature = "networking")]
+mod compression;
+mod content_quality;
+mod custom_vis;
+mod debug_tree;
+mod demo;
+#[cfg(feature = "rendering")]
 mod depth_mask_material;
+#[cfg(feature = "networking")]
+mod encryption;
+mod field_scale;
+mod game_events;
+mod ghost;
+mod goal_replay;
+mod i18n;
+#[cfg(feature = "networking")]
+mod interfaces;
+#[cfg(feature = "networking")]
+mod mdns;
+#[cfg(feature = "vis-mesh")]
+mod measurement;
 mod mesh_generators;
+#[cfg(feature = "networking")]
+mod mock_host;
+#[cfg(feature = "networking")]
+mod network_diagnostics;
+#[cfg(feature = "networking")]
 mod network_tasks;
+mod orientation;
+mod path_history;
+mod plans;
+mod plotting;
+mod recording;
+#[cfg(feature = "rendering")]
+mod rendering;
+mod robot_count;
+mod self_test;
+mod services;
+mod session;
+mod sharing;
+mod snapshot;
+mod sources;
+mod telemetry;
+mod update_packet;
+#[cfg(feature = "rendering")]
+mod vis_mesh_key;
 mod visualization_tracker;
+#[cfg(feature = "networking")]
+mod world_state_delta;
 mod world_state_filter;
 
-use crate::depth_mask_material::DepthMaskMaterial;
-use crate::mesh_generators::*;
-use crate::network_tasks::{UpdatePacket, host_discovery_task};
-use crate::proto::remote::udp_stream_request::UdpStream;
-use crate::proto::remote::ws_stream_request::WsStream;
-use crate::proto::remote::{
-    HostAdvertisement, UdpStreamRequest, VisualizationFilter, WsStreamRequest, ws_request,
-};
+use crate::game_events::GameEventDetector;
+#[cfg(feature = "networking")]
+use crate::network_tasks::{DiscoveredHost, host_discovery_task};
+use crate::proto::remote::{VisualizationFilter, ws_request};
 use crate::visualization_tracker::VisualizationTracker;
-use crate::world_state_filter::WorldStateFilter;
 use async_channel::{Receiver, Sender};
-use bevy::mesh::{CylinderAnchor, CylinderMeshBuilder, SphereKind, SphereMeshBuilder};
+use bevy::ecs::component::Mutable;
 use bevy::prelude::*;
 use bevy::tasks::{IoTaskPool, Task};
+use bevy::transform::TransformSystems;
 use std::cmp::PartialEq;
-use std::collections::{HashMap, HashSet};
-use std::net::SocketAddr;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+pub use crate::accessibility::AccessibilitySettings;
+pub use crate::clock::{FieldClock, STALE_WORLD_STATE, format_stage_time, format_wall_clock};
+pub use crate::content_quality::{ContentQuality, QualityLevel};
+pub use crate::custom_vis::{CustomVisHandler, CustomVisHandlers};
+pub use crate::debug_tree::{DEBUG_KEY_SEPARATOR, DebugNode, DebugTree, DebugValue};
+pub use crate::demo::DemoGame;
+#[cfg(feature = "rendering")]
+pub use crate::depth_mask_material::DepthMaskMaterial;
+#[cfg(feature = "networking")]
+pub use crate::encryption::{ClientKey, PresharedKey};
+pub use crate::field_scale::FieldScale;
+pub use crate::game_events::{GameEvent, GameEventKind, GameEventSource, NearCollisionSettings};
+pub use crate::ghost::{GhostBall, GhostRobot, GhostSource};
+pub use crate::goal_replay::{
+    GOAL_REPLAY_DURATION, GoalReplay, ReplayClip, ReplayPlayback, SLOW_MOTION_SPEED,
+};
+pub use crate::i18n::Language;
+#[cfg(feature = "networking")]
+pub use crate::interfaces::{HostInterfaces, InterfacePreference};
+#[cfg(feature = "vis-mesh")]
+pub use crate::measurement::{Measurement, field_to_local, local_to_field};
+pub use crate::mesh_generators::{field_mesh, grid_mesh, visualization_mesh};
+#[cfg(feature = "networking")]
+pub use crate::mock_host::{MockHost, MockHostConfig, PacketSource};
+#[cfg(feature = "networking")]
+pub use crate::network_diagnostics::{
+    GroupMembership, InterfaceInfo, NetworkDiagnostics, SocketError,
+};
+#[cfg(feature = "networking")]
+pub use crate::network_tasks::parse_host_addr;
+pub use crate::orientation::FieldOrientation;
+pub use crate::path_history::{PATH_HISTORY_DURATION, PathHistory, RobotPath};
+pub use crate::plans::{FieldPlans, PlannedPass, TargetPosition};
+pub use crate::plotting::{MAX_PLOT_WINDOW, Plot, PlotSource, Plots};
+pub use crate::recording::{FieldRecorder, PlaybackClock, Recording};
+pub use crate::robot_count::{RobotCount, RobotFlag, TeamRobotCount};
+pub use crate::self_test::{CheckStatus, SelfTestCheck, SelfTestReport};
+pub use crate::services::{SERVICE_MAGIC_RANGE, ServiceKind};
+pub use crate::session::{RestoredField, Session, SessionState};
+pub use crate::sharing::{SharedSnapshot, SnapshotCapture, SnapshotReceived};
+pub use crate::snapshot::{BallState, RobotState, WorldSnapshot};
+pub use crate::sources::{DataSource, SourceStreams};
+pub use crate::telemetry::{FieldTelemetry, Role, Telemetry};
+pub use crate::update_packet::UpdatePacket;
+#[cfg(feature = "rendering")]
+pub use crate::vis_mesh_key::VisMeshTolerance;
+pub use crate::world_state_filter::{
+    BufferedStateFilter, FilterMetrics, StateFilter, WorldStateFilter,
+};
 
 pub fn ssl_game_plugin(app: &mut App) {
     // Resources
@@ -34,63 +137,119 @@ pub fn ssl_game_plugin(app: &mut App) {
         robots: RobotRenderSettings::Fallback,
         ball: true,
         visualizations: true,
+        telemetry: false,
+        grid: false,
+        marker_tags: false,
+        lights: FieldLights::Soft,
+        vis_cull_angle: DEFAULT_VIS_CULL_ANGLE,
     });
 
-    app.add_plugins(MaterialPlugin::<DepthMaskMaterial>::default());
-
-    let world = app.world_mut();
-
-    // Meshes
-    let mut meshes = world.resource_mut::<Assets<Mesh>>();
-    let robot_mask_mesh = meshes.add(MeshBuilder::build(
-        &CylinderMeshBuilder::new(0.09, 0.15, 32).anchor(CylinderAnchor::Bottom),
-    ));
-    // FIXME: Ball in the ground
-    let ball_mesh = meshes.add(MeshBuilder::build(&SphereMeshBuilder::new(
-        0.0215,
-        SphereKind::Ico { subdivisions: 3 },
-    )));
-
-    // Materials
-    let robot_mask_material = world
-        .resource_mut::<Assets<DepthMaskMaterial>>()
-        .add(DepthMaskMaterial {});
-    let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
-    let ball_material = materials.add(StandardMaterial::from_color(Color::srgb_u8(255, 136, 0)));
-    let white_mat_opaque = materials.add(StandardMaterial::from_color(Color::WHITE));
-    let white_mat_translucent = materials.add({
-        let mut tmp = StandardMaterial::from_color(Color::WHITE);
-        tmp.alpha_mode = AlphaMode::Blend;
-        tmp
-    });
+    // The rendering plugin reports its assets to it
+    app.add_plugins(self_test::self_test_plugin);
 
-    app.insert_resource(RobotMaskMesh(robot_mask_mesh, robot_mask_material));
-    app.insert_resource(BallMesh(ball_mesh, ball_material));
-    app.insert_resource(DefaultMaterial {
-        opaque: white_mat_opaque,
-        translucent: white_mat_translucent,
-    });
+    // Nothing to load without a renderer, the rendering plugin resets it while preloading
+    app.insert_resource(AssetsLoaded(true));
+
+    // Without a renderer (e.g. with MinimalPlugins), only the networking and state filtering is done
+    #[cfg(feature = "rendering")]
+    if app.world().contains_resource::<Assets<Mesh>>()
+        && app.world().contains_resource::<Assets<StandardMaterial>>()
+    {
+        app.add_plugins(rendering::rendering_plugin);
+    } else {
+        info!("No renderer available, running sslgame headless");
+    }
 
     app.insert_resource(AvailableHosts::default());
+    app.insert_resource(Paused::default());
+    app.init_resource::<SoloField>();
+    app.init_resource::<WorldStateSampling>();
+
+    // Reflection
+    app.register_type::<RenderSettings>()
+        .register_type::<Paused>()
+        .register_type::<SoloField>()
+        .register_type::<AssetsLoaded>()
+        .register_type::<WorldStateSampling>()
+        .register_type::<Field>()
+        .register_type::<FieldGeometry>()
+        .register_type::<GameState>()
+        .register_type::<AvailableVisualizations>()
+        .register_type::<SelectedVisualizations>()
+        .register_type::<VisColorOverrides>()
+        .register_type::<FieldOrientation>()
+        .register_type::<FieldScale>()
+        .register_type::<VisSelectionStatus>()
+        .register_type::<DecodeErrors>()
+        .register_type::<FieldClock>()
+        .register_type::<Robot>()
+        .register_type::<Ball>()
+        .register_type::<Velocity>()
+        .register_type::<AngularVelocity>()
+        .register_type::<Team>()
+        .register_type::<Telemetry>()
+        .register_type::<DebugTree>()
+        .register_type::<Plots>()
+        .register_type::<GhostSource>()
+        .register_type::<GhostRobot>()
+        .register_type::<GhostBall>()
+        .register_type::<Measurement>()
+        .register_type::<DataSource>()
+        .register_type::<SourceStreams>()
+        .register_type::<Visualization>()
+        .register_type::<VisualizationData>();
+
+    // Messages
+    app.add_message::<WorldStateUpdated>();
+    app.add_message::<GameStateChanged>();
+    app.add_message::<GeometryChanged>();
 
     // Systems
     app.add_systems(
         Update,
         (
-            (
-                receive_host_advertisements,
-                receive_field_updates,
-                send_vis_selection,
-                handle_render_settings_change.run_if(resource_changed::<RenderSettings>),
-            ),
-            (
-                update_field_geometry,
-                update_world_state,
-                update_visualizations,
-            ),
+            (receive_field_updates, send_vis_selection),
+            update_visualizations
+                .run_if(|paused: Res<Paused>| !paused.0)
+                .run_if(content_quality::vis_update_due),
         )
             .chain(),
     );
+    // The world state is sampled as late as possible, so that long frames don't delay the displayed positions
+    app.add_systems(
+        PostUpdate,
+        (
+            update_world_state.run_if(|paused: Res<Paused>| !paused.0),
+            telemetry::apply_robot_telemetry,
+        )
+            .chain()
+            .before(TransformSystems::Propagate),
+    );
+    #[cfg(feature = "networking")]
+    {
+        app.init_resource::<InterfacePreference>();
+        app.init_resource::<HostInterfaces>();
+        app.init_resource::<ClientKey>();
+        app.register_type::<InterfacePreference>();
+        app.add_systems(
+            Update,
+            (receive_host_advertisements, update_field_hostnames).chain(),
+        );
+        app.add_plugins(network_diagnostics::network_diagnostics_plugin);
+    }
+
+    app.add_plugins(custom_vis::custom_vis_plugin);
+    app.add_plugins(plotting::plotting_plugin);
+    app.add_plugins(game_events::game_events_plugin);
+    app.add_plugins(robot_count::robot_count_plugin);
+    app.add_plugins(path_history::path_history_plugin);
+    app.add_plugins(goal_replay::goal_replay_plugin);
+    app.add_plugins(ghost::ghost_plugin);
+    app.add_plugins(field_scale::field_scale_plugin);
+    app.add_plugins(content_quality::content_quality_plugin);
+    app.add_plugins(i18n::i18n_plugin);
+    app.add_plugins(accessibility::accessibility_plugin);
+    app.add_plugins(sharing::sharing_plugin);
 }
 
 // ======== Resources ========
@@ -98,13 +257,68 @@ pub fn ssl_game_plugin(app: &mut App) {
 #[derive(Resource, Debug, Default)]
 pub struct AvailableHosts(pub HashSet<FieldHost>);
 
+/// Freezes the displayed world state and visualizations of all fields while set.
+/// Packets are still received in the background, so resuming continues with the live state.
+#[derive(Resource, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource, Debug, Default, Clone, PartialEq)]
+pub struct Paused(pub bool);
+
+/// Set once the robot models and shaders are loaded. Apps should wait for it before spawning fields,
+/// otherwise robots are invisible until their model is loaded. Always set without a renderer.
+#[derive(Resource, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource, Debug, Default, Clone, PartialEq)]
+pub struct AssetsLoaded(pub bool);
+
+/// Run condition for systems that spawn fields, see [`AssetsLoaded`]
+pub fn assets_loaded(loaded: Res<AssetsLoaded>) -> bool {
+    loaded.0
+}
+
+/// Shows only this field and hides all others, which keep receiving packets in the background.
+/// Many fields at once are hard to follow and expensive to render.
+#[derive(Resource, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Resource, Debug, Default, Clone, PartialEq)]
+pub struct SoloField(pub Option<Entity>);
+
+impl SoloField {
+    /// Solos the next field in spawn order. All fields are shown again after the last one,
+    /// so a single button or gesture can also return to the overview.
+    pub fn cycle(&mut self, fields: impl IntoIterator<Item = Entity>) {
+        let mut fields: Vec<_> = fields.into_iter().collect();
+        fields.sort();
+        let current = self
+            .0
+            .and_then(|solo| fields.iter().position(|field| *field == solo));
+        self.0 = match current {
+            Some(index) => fields.get(index + 1).copied(),
+            None => fields.first().copied(),
+        };
+    }
+}
+
+/// Controls how the displayed world state is sampled from the state filter of each field.
+#[derive(Resource, Reflect, Debug, Default, Clone, Copy, PartialEq)]
+#[reflect(Resource, Debug, Default, Clone, PartialEq)]
+pub struct WorldStateSampling {
+    /// Interpolate between the buffered packets instead of showing the newest one.
+    /// Smoother, but delayed by the buffer time of the state filter.
+    pub interpolate: bool,
+    /// The time the current frame will be displayed at, e.g. predicted by the XR runtime.
+    /// The world state is sampled at the current time if not set.
+    pub display_time: Option<Instant>,
+}
+
+#[cfg(feature = "networking")]
 #[derive(Resource, Debug)]
 struct HostDiscoveryTask {
-    discovery_channel: Receiver<Vec<(SocketAddr, HostAdvertisement)>>,
+    discovery_channel: Receiver<Vec<DiscoveredHost>>,
     discovery_task: Task<()>,
+    /// Kept to pick new addresses when the interface preference changes
+    discovered: Vec<DiscoveredHost>,
 }
 
-#[derive(Clone, Debug, Default)]
+#[derive(Reflect, Clone, Debug, Default, PartialEq, Eq)]
+#[reflect(Debug, Default, Clone, PartialEq)]
 pub enum RobotRenderSettings {
     #[default]
     Detailed,
@@ -113,12 +327,40 @@ pub enum RobotRenderSettings {
     None,
 }
 
-#[derive(Resource, Clone, Debug)]
+/// Light rig spawned as children of every field, so all fields are lit the same wherever they are placed
+#[derive(Reflect, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[reflect(Debug, Default, Clone, PartialEq)]
+pub enum FieldLights {
+    /// No lights at all, e.g. for AR where the real world is lit already
+    Off,
+    /// Soft key and fill light
+    #[default]
+    Soft,
+    /// Additional spots at the corners like in a stadium
+    Stadium,
+}
+
+/// About a 1 cm circle seen from 5 m away
+pub const DEFAULT_VIS_CULL_ANGLE: f32 = 0.002;
+
+#[derive(Resource, Reflect, Clone, Debug)]
+#[reflect(Resource, Debug, Default, Clone)]
 pub struct RenderSettings {
     pub field: bool,
     pub robots: RobotRenderSettings,
     pub ball: bool,
     pub visualizations: bool,
+    /// Small battery, kicker and radio bars above robots that report telemetry
+    pub telemetry: bool,
+    /// 1 m / 0.5 m ruler grid with labeled axes, to judge distances where the floor has no visible depth cues
+    pub grid: bool,
+    /// Floating tags with the standard marker pattern of each robot id above the robot cutouts,
+    /// to check that the vision ids match the physical robots below
+    pub marker_tags: bool,
+    pub lights: FieldLights,
+    /// Visualizations whose parts all appear smaller than this angle (in radians) are hidden, and fade out
+    /// below twice this angle. Reduces clutter and overdraw of tiny elements seen from far away, 0 disables it.
+    pub vis_cull_angle: f32,
 }
 
 impl RenderSettings {
@@ -128,6 +370,11 @@ impl RenderSettings {
             robots: RobotRenderSettings::Detailed,
             ball: true,
             visualizations: true,
+            telemetry: false,
+            grid: false,
+            marker_tags: false,
+            lights: FieldLights::Soft,
+            vis_cull_angle: DEFAULT_VIS_CULL_ANGLE,
         }
     }
     pub fn ar() -> Self {
@@ -136,6 +383,12 @@ impl RenderSettings {
             robots: RobotRenderSettings::Cutout,
             ball: false,
             visualizations: true,
+            telemetry: false,
+            grid: false,
+            marker_tags: false,
+            // The real world is lit already
+            lights: FieldLights::Off,
+            vis_cull_angle: DEFAULT_VIS_CULL_ANGLE,
         }
     }
 }
@@ -147,105 +400,294 @@ impl Default for RenderSettings {
             robots: RobotRenderSettings::default(),
             ball: true,
             visualizations: true,
+            telemetry: false,
+            grid: false,
+            marker_tags: false,
+            lights: FieldLights::default(),
+            vis_cull_angle: DEFAULT_VIS_CULL_ANGLE,
         }
     }
 }
 
-#[derive(Resource, Debug)]
-struct RobotMaskMesh(Handle<Mesh>, Handle<DepthMaskMaterial>);
-
-#[derive(Resource, Debug)]
-struct BallMesh(Handle<Mesh>, Handle<StandardMaterial>);
-
-#[derive(Resource, Debug)]
-struct DefaultMaterial {
-    pub opaque: Handle<StandardMaterial>,
-    pub translucent: Handle<StandardMaterial>,
-}
-
 // ======== Field connection components ========
 
-#[derive(Component, Debug)]
+/// The connection can't be reflected, so fields can't be created from reflection (e.g. scenes).
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component, Debug, from_reflect = false)]
 #[require(
-    Visibility,
     Transform,
     FieldGeometry,
     GameState,
     AvailableVisualizations,
     SelectedVisualizations,
-    WorldStateFilter,
-    VisualizationTracker
+    VisColorOverrides,
+    FieldOrientation,
+    FieldScale,
+    VisSelectionStatus,
+    StateFilter,
+    VisualizationTracker,
+    FieldTelemetry,
+    FieldPlans,
+    PathHistory,
+    GoalReplay,
+    DebugTree,
+    Plots,
+    GameEventDetector,
+    RobotCount,
+    SourceStreams,
+    FieldClock,
+    DecodeErrors
 )]
 pub struct Field {
     pub host: FieldHost,
+    #[reflect(ignore)]
     pub connection: FieldConnection,
+    #[reflect(ignore)]
+    origin: FieldOrigin,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+/// What a field was created from, e.g. to recreate it when restoring a [`Session`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FieldOrigin {
+    /// A network host, see [`Field::host`]
+    Host,
+    Replay {
+        path: PathBuf,
+        /// Played on a [`PlaybackClock`] shared with the other synced replays
+        synced: bool,
+    },
+    Demo,
+    /// Packets pushed by the app, these fields can't be recreated
+    Injected,
+    /// A [`SharedSnapshot`], which is not stored in sessions
+    Snapshot,
+}
+
+#[derive(Reflect, Debug, Clone, PartialEq, Eq, Hash)]
+#[reflect(Debug, Clone, PartialEq, Hash)]
 pub struct FieldHost {
     pub websocket_addr: SocketAddr,
     pub hostname: Option<String>,
+    /// The streams are encrypted with a [`PresharedKey`], e.g. the one in [`ClientKey`]
+    pub encrypted: bool,
+}
+
+impl FieldHost {
+    /// The hostname, or the address for hosts that didn't advertise one
+    pub fn display_name(&self) -> String {
+        self.hostname
+            .clone()
+            .unwrap_or_else(|| self.websocket_addr.to_string())
+    }
 }
 
-#[derive(Debug)]
+/// Cloning the connection shares the io task, see [`Field::duplicate`]
+#[derive(Debug, Clone)]
 pub struct FieldConnection {
     pub sender: Sender<ws_request::Content>,
     receiver: Receiver<UpdatePacket>,
-    io_task: Task<()>,
+    /// Kept running until the last field using the connection is despawned
+    io_task: Arc<Task<()>>,
+    /// Incremented by the io task for every packet that was dropped because it couldn't be decoded
+    decode_errors: Arc<AtomicU32>,
+}
+
+impl FieldConnection {
+    /// Same for all duplicates of a field
+    fn id(&self) -> *const Task<()> {
+        Arc::as_ptr(&self.io_task)
+    }
+
+    fn is_shared(&self) -> bool {
+        Arc::strong_count(&self.io_task) > 1
+    }
 }
 
 impl Field {
-    pub fn bind(host: FieldHost) -> Self {
-        let (rx_sender, rx_receiver) = async_channel::bounded(100);
-        let (tx_sender, tx_receiver) = async_channel::bounded(10);
-        let state_rx_task = IoTaskPool::get().spawn(network_tasks::io_task(
-            host.websocket_addr,
-            rx_sender,
-            tx_receiver,
-        ));
+    /// The key is only used if the host is encrypted, so unencrypted hosts stay reachable while a key is set
+    #[cfg(feature = "networking")]
+    pub fn bind(host: FieldHost, key: Option<&PresharedKey>) -> Self {
+        let field = Self::connect(host, key);
 
         debug!(
             "Spawned new field for host {}{}",
-            host.websocket_addr,
-            host.hostname
+            field.host.websocket_addr,
+            field
+                .host
+                .hostname
                 .as_ref()
                 .map(|name| format!(" ({name})"))
                 .unwrap_or_default()
         );
 
-        tx_sender
-            .send_blocking(ws_request::Content::WsStreamReq(WsStreamRequest {
-                stream: vec![
-                    WsStream::FieldGeometry as i32,
-                    WsStream::GameState as i32,
-                    WsStream::VisMappings as i32,
-                ],
-            }))
-            .unwrap();
-        tx_sender
-            .send_blocking(ws_request::Content::UdpStreamReq(UdpStreamRequest {
-                stream: vec![
-                    UdpStream::WorldState as i32,
-                    UdpStream::Visualizations as i32,
-                ],
-                port: 0,
-            }))
-            .unwrap();
+        SourceStreams::ALL.request(&field.connection);
+
+        field
+    }
+
+    /// Connects to the host without requesting any streams
+    #[cfg(feature = "networking")]
+    pub(crate) fn connect(host: FieldHost, key: Option<&PresharedKey>) -> Self {
+        let key = key.filter(|_| host.encrypted).cloned();
+        Self::from_task(
+            host,
+            FieldOrigin::Host,
+            |host, packets_out, requests_in, decode_errors| {
+                IoTaskPool::get().spawn(network_tasks::io_task(
+                    host.websocket_addr,
+                    key,
+                    packets_out,
+                    requests_in,
+                    decode_errors,
+                ))
+            },
+        )
+    }
+
+    /// Creates a field that plays back a recording created by a [`FieldRecorder`] in a loop.
+    pub fn replay(path: impl AsRef<Path>) -> std::io::Result<Self> {
+        Self::replay_on_clock(path, None)
+    }
+
+    /// Like [`Self::replay`], but advances in lockstep with all other replays on the same clock,
+    /// e.g. to compare two matches side by side.
+    pub fn replay_synced(path: impl AsRef<Path>, clock: &PlaybackClock) -> std::io::Result<Self> {
+        Self::replay_on_clock(path, Some(clock))
+    }
+
+    fn replay_on_clock(
+        path: impl AsRef<Path>,
+        clock: Option<&PlaybackClock>,
+    ) -> std::io::Result<Self> {
+        let records = recording::read_recording(&path)?;
+        let host = FieldHost {
+            websocket_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            hostname: path
+                .as_ref()
+                .file_name()
+                .map(|name| name.to_string_lossy().into_owned()),
+            encrypted: false,
+        };
+
+        debug!(
+            "Replaying {} packets from {}",
+            records.len(),
+            path.as_ref().display()
+        );
+
+        // Absolute, so that sessions can be restored from another working directory
+        let origin = FieldOrigin::Replay {
+            path: std::path::absolute(&path).unwrap_or_else(|_| path.as_ref().to_path_buf()),
+            synced: clock.is_some(),
+        };
+        // Unsynced replays get a clock of their own
+        let clock = clock.cloned().unwrap_or_default();
+
+        Ok(Self::from_task(
+            host,
+            origin,
+            |_, packets_out, requests_in, _| {
+                IoTaskPool::get().spawn(recording::replay_task(
+                    records,
+                    clock,
+                    packets_out,
+                    requests_in,
+                ))
+            },
+        ))
+    }
+
+    /// Creates a field that shows a procedurally generated game, e.g. for showcases without a host or network.
+    pub fn demo() -> Self {
+        let host = FieldHost {
+            websocket_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            hostname: Some("Demo".to_string()),
+            encrypted: false,
+        };
+
+        Self::from_task(host, FieldOrigin::Demo, |_, packets_out, requests_in, _| {
+            IoTaskPool::get().spawn(demo::demo_task(
+                DemoGame::default(),
+                packets_out,
+                requests_in,
+            ))
+        })
+    }
+
+    /// Creates a field without a network host that only receives the packets pushed into the returned sender.
+    /// Used for deterministic tests, demo scenes and scripted tutorials.
+    /// The field is despawned once all senders have been dropped.
+    pub fn with_injector(host: FieldHost) -> (Self, Sender<UpdatePacket>) {
+        let (injector, injected) = async_channel::bounded(100);
+
+        let field = Self::from_task(
+            host,
+            FieldOrigin::Injected,
+            |_, packets_out, requests_in, _| {
+                // There is nobody to handle requests, so senders get an error instead of blocking on a full channel
+                drop(requests_in);
+                IoTaskPool::get().spawn(async move {
+                    while let Ok(packet) = injected.recv().await {
+                        if packets_out.send(packet).await.is_err() {
+                            debug!("Packet receiver dropped, stopping injector task");
+                            return;
+                        }
+                    }
+                })
+            },
+        );
+
+        (field, injector)
+    }
+
+    /// Creates another field that shows the same host over the same connection, e.g. a full-scale AR overlay and a tabletop copy.
+    /// The duplicate has its own state, so its transform, vis selection and recording are independent of this field.
+    pub fn duplicate(&self) -> Self {
+        Field {
+            host: self.host.clone(),
+            connection: self.connection.clone(),
+            origin: self.origin.clone(),
+        }
+    }
+
+    pub fn origin(&self) -> &FieldOrigin {
+        &self.origin
+    }
+
+    /// Sets up the channels of a new field connection and spawns the task feeding them.
+    /// The field will be despawned as soon as the task finishes.
+    fn from_task(
+        host: FieldHost,
+        origin: FieldOrigin,
+        spawn_task: impl FnOnce(
+            &FieldHost,
+            Sender<UpdatePacket>,
+            Receiver<ws_request::Content>,
+            Arc<AtomicU32>,
+        ) -> Task<()>,
+    ) -> Self {
+        let (rx_sender, rx_receiver) = async_channel::bounded(100);
+        let (tx_sender, tx_receiver) = async_channel::bounded(10);
+        let decode_errors = Arc::new(AtomicU32::new(0));
+        let io_task = spawn_task(&host, rx_sender, tx_receiver, decode_errors.clone());
 
         Field {
             host,
             connection: FieldConnection {
                 sender: tx_sender,
                 receiver: rx_receiver,
-                io_task: state_rx_task,
+                io_task: Arc::new(io_task),
+                decode_errors,
             },
+            origin,
         }
     }
 }
 
 // ======== Field state components ========
 
-#[derive(Component, Debug, Clone, PartialEq)]
+#[derive(Component, Reflect, Debug, Clone, PartialEq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
 pub struct FieldGeometry {
     pub play_area_size: Vec2,
     pub boundary_width: f32,
@@ -253,18 +695,152 @@ pub struct FieldGeometry {
     pub goal_width: f32,
 }
 
-#[derive(Component, Deref, Debug, Default, Clone, PartialEq, Eq)]
+#[derive(Component, Reflect, Deref, Debug, Default, Clone, PartialEq, Eq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
 pub struct GameState(proto::remote::GameState);
 
-#[derive(Component, Debug, Default)]
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component, Debug, Default)]
 pub struct AvailableVisualizations {
     pub sources: HashMap<u32, String>,
     pub visualizations: HashMap<u32, String>,
 }
 
-#[derive(Component, Debug, Default, PartialEq)]
+#[derive(Component, Reflect, Debug, Default, Clone, PartialEq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
 pub struct SelectedVisualizations(pub VisualizationFilter);
 
+/// Client side colors of visualizations by name, e.g. when the host colors clash with the passthrough background.
+/// Replaces the colors chosen by the host, but keeps their transparency.
+#[derive(Component, Reflect, Debug, Default, Clone, PartialEq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
+pub struct VisColorOverrides(pub HashMap<String, Color>);
+
+/// Pending selections are sent again after this time. The filter replaces the previous one, so repeating it is safe.
+const VIS_SELECTION_RETRY: Duration = Duration::from_secs(1);
+/// After this many attempts, the host is assumed to ignore filters
+const VIS_SELECTION_ATTEMPTS: u32 = 5;
+
+/// Whether the host has applied the [`SelectedVisualizations`] that were last sent to it.
+/// Hosts don't acknowledge filters, so a selection counts as applied once a visualization update only contains selected visualizations.
+#[derive(Component, Reflect, Debug, Default, Clone, PartialEq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
+pub struct VisSelectionStatus {
+    state: VisSelectionState,
+    /// The filter that was sent, merged with the selections of duplicated fields
+    sent: VisualizationFilter,
+    attempts: u32,
+    #[reflect(ignore)]
+    last_sent: Option<Instant>,
+}
+
+#[derive(Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Debug, Default, Clone, PartialEq)]
+pub enum VisSelectionState {
+    /// No selection has been sent yet
+    #[default]
+    Unsent,
+    /// The host still sends visualizations that aren't selected, the selection is re-sent periodically
+    Pending,
+    Applied,
+    /// The host kept sending unselected visualizations after all retries, so it probably doesn't support filtering
+    Ignored,
+}
+
+impl VisSelectionStatus {
+    pub fn state(&self) -> VisSelectionState {
+        self.state
+    }
+
+    fn selection_sent(&mut self, filter: VisualizationFilter, now: Instant) {
+        *self = VisSelectionStatus {
+            state: VisSelectionState::Pending,
+            sent: filter,
+            attempts: 1,
+            last_sent: Some(now),
+        };
+    }
+
+    fn retry_due(&self, now: Instant) -> bool {
+        self.state == VisSelectionState::Pending
+            && self
+                .last_sent
+                .is_none_or(|last_sent| now >= last_sent + VIS_SELECTION_RETRY)
+    }
+
+    /// Returns false once all attempts have been used up
+    fn retry(&mut self, now: Instant) -> bool {
+        if self.attempts >= VIS_SELECTION_ATTEMPTS {
+            self.state = VisSelectionState::Ignored;
+            return false;
+        }
+        self.attempts += 1;
+        self.last_sent = Some(now);
+        true
+    }
+
+    /// Returns true if the state changed
+    fn vis_update_received(&mut self, vis_update: &proto::remote::VisualizationUpdate) -> bool {
+        let selected = vis_update.visualization_set.iter().all(|set| {
+            set.source
+                .is_none_or(|source| self.sent.allowed_vis_source.contains(&source))
+                && set
+                    .visualization
+                    .iter()
+                    .all(|vis| self.sent.allowed_vis_id.contains(&vis.id))
+        });
+        match (self.state, selected) {
+            (VisSelectionState::Pending | VisSelectionState::Ignored, true) => {
+                self.state = VisSelectionState::Applied;
+            }
+            // The host lost the filter, e.g. after a restart, so the retries start over
+            (VisSelectionState::Applied, false) => {
+                self.state = VisSelectionState::Pending;
+                self.attempts = 0;
+                self.last_sent = None;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Packets from the host that were dropped because they couldn't be decoded.
+#[derive(Component, Reflect, Debug, Default)]
+#[reflect(Component, Debug, Default)]
+pub struct DecodeErrors {
+    pub total: u32,
+    /// Number of new errors per frame in the last minute
+    #[reflect(ignore)]
+    recent: VecDeque<(Instant, u32)>,
+}
+
+impl DecodeErrors {
+    const WINDOW: Duration = Duration::from_secs(60);
+
+    pub fn last_minute(&self) -> u32 {
+        let cutoff = Instant::now() - Self::WINDOW;
+        self.recent
+            .iter()
+            .filter(|(time, _)| *time > cutoff)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn push(&mut self, count: u32) {
+        let now = Instant::now();
+        self.total += count;
+        self.recent.push_back((now, count));
+        while self
+            .recent
+            .front()
+            .is_some_and(|(time, _)| *time < now - Self::WINDOW)
+        {
+            self.recent.pop_front();
+        }
+    }
+}
+
 impl FieldGeometry {
     const DIV_A: Self = Self {
         play_area_size: Vec2::new(12.0, 9.0),
@@ -288,58 +864,114 @@ impl Default for FieldGeometry {
 
 // ======== Field content components =========
 
-#[derive(Component, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[reflect(Component, Debug, Default, Clone, PartialEq, Hash)]
 pub enum Team {
     #[default]
     Yellow,
     Blue,
 }
 
-#[derive(Component, Debug, Clone, Copy)]
+/// Maximum radius of an SSL robot in m
+pub const ROBOT_RADIUS: f32 = 0.09;
+
+#[derive(Component, Reflect, Debug, Clone, Copy)]
+#[reflect(Component, Debug, Clone)]
 #[require(Team, Transform)]
 pub struct Robot(pub u8);
 
-#[derive(Component, Debug, Clone, Copy)]
+/// Radius of an SSL ball in m
+pub const BALL_RADIUS: f32 = 0.0215;
+
+#[derive(Component, Reflect, Debug, Default, Clone, Copy)]
+#[reflect(Component, Debug, Default, Clone)]
 #[require(Transform)]
 pub struct Ball;
 
-#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+/// Velocity of a robot or ball in m/s in the field's coordinate system, if the state filter could estimate it
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
+pub struct Velocity(pub Vec3);
+
+/// Angular velocity of a robot around the y axis in rad/s, as reported by the host or estimated by the state filter
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
+pub struct AngularVelocity(pub f32);
+
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component, Debug, Clone, PartialEq)]
 #[require(Transform)]
 pub struct Visualization(pub u32);
 
+/// The geometry of a visualization as received from the host.
+#[derive(Component, Reflect, Deref, Debug, Clone, PartialEq)]
+#[reflect(Component, Debug, Clone, PartialEq)]
+pub struct VisualizationData(proto::remote::Visualization);
+
+// ======== Messages ========
+
+/// Written for each field whenever the displayed world state changes, i.e. on new packets or while interpolating.
+/// The world state is shared with the state filter instead of being copied for every message.
+#[derive(Message, Debug, Clone)]
+pub struct WorldStateUpdated {
+    pub field: Entity,
+    pub world_state: Arc<WorldSnapshot>,
+    /// The world state is a frame of a goal replay instead of the live state
+    pub replay: bool,
+}
+
+/// Written when the game state of a field has changed, i.e. referee commands, stages, cards or scores.
+#[derive(Message, Debug, Clone)]
+pub struct GameStateChanged {
+    pub field: Entity,
+    pub game_state: GameState,
+}
+
+/// Written when a field has received a new geometry.
+#[derive(Message, Debug, Clone)]
+pub struct GeometryChanged {
+    pub field: Entity,
+    pub geometry: FieldGeometry,
+}
+
 // ======== Systems ========
 
 /// Manages the HostDiscoveryTask and updates the AvailableHosts resource
+#[cfg(feature = "networking")]
 fn receive_host_advertisements(
     mut commands: Commands,
-    running_receiver: Option<Res<HostDiscoveryTask>>,
+    running_receiver: Option<ResMut<HostDiscoveryTask>>,
     mut available_hosts: ResMut<AvailableHosts>,
+    mut host_interfaces: ResMut<HostInterfaces>,
+    interface_preference: Res<InterfacePreference>,
+    client_key: Res<ClientKey>,
 ) {
-    if let Some(discovery_task) = running_receiver {
+    if let Some(mut discovery_task) = running_receiver {
         if discovery_task.discovery_task.is_finished() {
             commands.remove_resource::<HostDiscoveryTask>();
             error!("Host discovery task stopped");
             // A new task will be started next frame
         } else {
             // Handle the new host list if available. There should only ever be one at a time.
-            if let Ok(new_hosts) = discovery_task.discovery_channel.try_recv() {
-                let new_hosts = new_hosts
-                    .into_iter()
-                    .map(|(addr, adv)| {
-                        let mut websocket_addr = addr;
-                        websocket_addr.set_port(adv.websocket_port as u16);
-                        FieldHost {
-                            websocket_addr,
-                            hostname: adv.hostname,
-                        }
-                    })
-                    .collect::<HashSet<_>>();
+            if let Ok(discovered) = discovery_task.discovery_channel.try_recv() {
+                discovery_task.discovered = discovered;
+            } else if !interface_preference.is_changed() && !client_key.is_changed() {
+                return;
+            }
 
-                // Only update the resource (and trigger change detection) when the hosts have actually changed
-                if new_hosts != available_hosts.0 {
-                    available_hosts.0 = new_hosts;
-                }
+            let (new_hosts, new_interfaces) =
+                interface_preference.select(&discovery_task.discovered);
+            // Encrypted hosts can't be connected to without a key
+            let new_hosts = new_hosts
+                .into_iter()
+                .filter(|host| !host.encrypted || client_key.0.is_some())
+                .collect::<HashSet<_>>();
+
+            // Only update the resources (and trigger change detection) when the hosts have actually changed
+            if new_hosts != available_hosts.0 {
+                available_hosts.0 = new_hosts;
             }
+            host_interfaces.set_if_neq(new_interfaces);
         }
     } else {
         // Start a new discovery task
@@ -348,30 +980,146 @@ fn receive_host_advertisements(
         commands.insert_resource(HostDiscoveryTask {
             discovery_channel: rx,
             discovery_task: task,
+            discovered: Vec::new(),
         });
         info!("Host discovery task started");
     }
 }
 
+/// Hosts are reported before the name of hosts that don't advertise one is looked up,
+/// so their fields get the name afterwards
+#[cfg(feature = "networking")]
+fn update_field_hostnames(available_hosts: Res<AvailableHosts>, mut q_fields: Query<&mut Field>) {
+    if !available_hosts.is_changed() {
+        return;
+    }
+    for mut field in &mut q_fields {
+        if field.origin != FieldOrigin::Host {
+            continue;
+        }
+        let hostname = available_hosts
+            .0
+            .iter()
+            .find(|host| host.websocket_addr == field.host.websocket_addr)
+            .and_then(|host| host.hostname.as_ref());
+        if let Some(hostname) = hostname
+            && field.host.hostname.as_ref() != Some(hostname)
+        {
+            field.host.hostname = Some(hostname.clone());
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
 fn receive_field_updates(
     mut commands: Commands,
+    (mut game_state_changes, mut geometry_changes): (
+        MessageWriter<GameStateChanged>,
+        MessageWriter<GeometryChanged>,
+    ),
+    mut q_sources: Query<
+        (
+            &DataSource,
+            &SourceStreams,
+            &ChildOf,
+            &mut AvailableVisualizations,
+            &mut VisualizationTracker,
+            &mut VisSelectionStatus,
+            Entity,
+        ),
+        Without<Field>,
+    >,
     mut q_fields: Query<(
         &Field,
+        &SourceStreams,
         &mut FieldGeometry,
         &mut GameState,
         &mut AvailableVisualizations,
-        &mut WorldStateFilter,
+        &mut StateFilter,
         &mut VisualizationTracker,
+        &mut VisSelectionStatus,
+        &mut FieldTelemetry,
+        &mut FieldPlans,
+        &mut DebugTree,
+        &mut FieldClock,
+        &mut DecodeErrors,
+        Option<&mut FieldRecorder>,
         Entity,
     )>,
+    q_orientations: Query<&FieldOrientation>,
 ) {
+    // Visualizations stay with their source, everything else is merged into the parent field below
+    let mut source_packets: HashMap<Entity, Vec<UpdatePacket>> = HashMap::new();
+    for (source, streams, child_of, mut vis_selection, mut vis_tracker, mut vis_status, entity) in
+        &mut q_sources
+    {
+        if source.connection.io_task.is_finished() {
+            info!(
+                "Connection to data source {} closed",
+                source.host.websocket_addr
+            );
+            commands.entity(entity).despawn();
+            continue;
+        }
+
+        // The sources of a field use the same coordinate convention as its host
+        let orientation = q_orientations
+            .get(child_of.parent())
+            .copied()
+            .unwrap_or_default();
+        while let Ok(mut new_packet) = source.connection.receiver.try_recv() {
+            vis_selection_feedback(&mut vis_status, &new_packet);
+            if !streams.accepts(&new_packet) {
+                continue;
+            }
+            orientation.apply(&mut new_packet);
+            match new_packet {
+                UpdatePacket::VisMappings(new_vis_mappings) => {
+                    vis_selection.sources = new_vis_mappings.source;
+                    vis_selection.visualizations = new_vis_mappings.name;
+                }
+                UpdatePacket::VisualizationUpdate(vis_update) => {
+                    vis_tracker.push_frame(vis_update.into());
+                }
+                packet => source_packets
+                    .entry(child_of.parent())
+                    .or_default()
+                    .push(packet),
+            }
+        }
+    }
+
+    // Duplicated fields share a connection, so each connection is drained once and its packets are given to every field using it
+    let mut connection_packets: HashMap<*const Task<()>, (usize, u32, Vec<UpdatePacket>)> =
+        HashMap::new();
+    for (field, ..) in &q_fields {
+        let (users, _, _) = connection_packets
+            .entry(field.connection.id())
+            .or_insert_with(|| {
+                (
+                    0,
+                    field.connection.decode_errors.swap(0, Ordering::Relaxed),
+                    std::iter::from_fn(|| field.connection.receiver.try_recv().ok()).collect(),
+                )
+            });
+        *users += 1;
+    }
+
     for (
         field,
+        streams,
         mut geom,
         mut game_state,
         mut vis_selection,
         mut world_state,
         mut vis_tracker,
+        mut vis_status,
+        mut telemetry,
+        mut plans,
+        mut debug_tree,
+        mut clock,
+        mut decode_errors,
+        mut recorder,
         entity,
     ) in q_fields.iter_mut()
     {
@@ -383,11 +1131,54 @@ fn receive_field_updates(
             commands.entity(entity).despawn();
             continue;
         }
-        while let Ok(new_packet) = field.connection.receiver.try_recv() {
+
+        let Some((users, new_decode_errors, packets)) =
+            connection_packets.get_mut(&field.connection.id())
+        else {
+            continue;
+        };
+        *users -= 1;
+        let packets = if *users == 0 {
+            std::mem::take(packets)
+        } else {
+            packets.clone()
+        };
+
+        if *new_decode_errors > 0 {
+            // Only warn once per burst, the count is available in the DecodeErrors component
+            if decode_errors.last_minute() == 0 {
+                warn!(
+                    "Dropped undecodable packets from {}",
+                    field.host.websocket_addr
+                );
+            }
+            decode_errors.push(*new_decode_errors);
+        }
+
+        let orientation = q_orientations.get(entity).copied().unwrap_or_default();
+        let mut new_packets = Vec::new();
+        for mut new_packet in packets {
+            if let Some(recorder) = recorder.as_deref_mut()
+                && let Err(e) = recorder.record(&new_packet)
+            {
+                error!("Failed to record packet, stopping recording: {e}");
+                commands.entity(entity).remove::<FieldRecorder>();
+                recorder = None;
+            }
+            vis_selection_feedback(&mut vis_status, &new_packet);
+            if streams.accepts(&new_packet) {
+                // Recordings keep the packets as the host sent them
+                orientation.apply(&mut new_packet);
+                new_packets.push(new_packet);
+            }
+        }
+        new_packets.extend(source_packets.remove(&entity).unwrap_or_default());
+
+        for new_packet in new_packets {
             // The host should only send geom and game state update when they actually changed, but its still safer to check ourselves
             match new_packet {
                 UpdatePacket::FieldGeom(new_geom) => {
-                    geom.set_if_neq(FieldGeometry {
+                    let changed = geom.set_if_neq(FieldGeometry {
                         play_area_size: Vec2::new(new_geom.field_size_x, new_geom.field_size_y),
                         boundary_width: new_geom.boundary_width.unwrap_or(0.0),
                         defense_size: Vec2::new(
@@ -400,92 +1191,188 @@ fn receive_field_updates(
                         ),
                         goal_width: new_geom.goal_width.unwrap_or(new_geom.field_size_y / 5.),
                     });
+                    if changed {
+                        geometry_changes.write(GeometryChanged {
+                            field: entity,
+                            geometry: (*geom).clone(),
+                        });
+                    }
                 }
                 UpdatePacket::GameState(new_game_state) => {
-                    game_state.set_if_neq(GameState(new_game_state));
+                    clock.game_state_received(new_game_state.stage_time_left);
+                    if game_state.set_if_neq(GameState(new_game_state)) {
+                        game_state_changes.write(GameStateChanged {
+                            field: entity,
+                            game_state: (*game_state).clone(),
+                        });
+                    }
                 }
                 UpdatePacket::VisMappings(new_vis_mappings) => {
                     vis_selection.sources = new_vis_mappings.source;
                     vis_selection.visualizations = new_vis_mappings.name;
                 }
                 UpdatePacket::WorldState(new_world_state) => {
-                    world_state.push_packet(new_world_state);
+                    clock.world_state_received();
+                    world_state.push_packet(new_world_state.into());
                 }
                 UpdatePacket::VisualizationUpdate(vis_update) => {
-                    vis_tracker.push_update(vis_update);
+                    vis_tracker.push_frame(vis_update.into());
+                }
+                UpdatePacket::RobotTelemetry(telemetry_update) => {
+                    telemetry.replace(&telemetry_update);
+                }
+                UpdatePacket::DebugValues(debug_values) => {
+                    debug_tree.replace(debug_values);
+                }
+                UpdatePacket::StrategyPlans(new_plans) => {
+                    plans.replace(&new_plans);
                 }
             }
         }
     }
 }
 
-fn send_vis_selection(
-    q_fields: Query<(&Field, &SelectedVisualizations), Changed<SelectedVisualizations>>,
-) {
-    for (field, vis_selection) in q_fields {
-        debug!("Sending vis selection: {:?}", vis_selection.0);
-        _ = field
-            .connection
-            .sender
-            .send_blocking(ws_request::Content::SetVisFilter(vis_selection.0.clone()));
+/// Visualization updates are checked before the stream settings are applied, since they show what the host sends
+fn vis_selection_feedback(status: &mut Mut<VisSelectionStatus>, packet: &UpdatePacket) {
+    if let UpdatePacket::VisualizationUpdate(vis_update) = packet
+        && status
+            .bypass_change_detection()
+            .vis_update_received(vis_update)
+    {
+        status.set_changed();
     }
 }
 
+/// Duplicated fields share a connection, so the host gets the union of their selections and each field filters locally.
+/// Selections that the host hasn't applied yet are sent again, see [`VisSelectionStatus`].
 #[allow(clippy::type_complexity)]
-fn handle_render_settings_change(
-    mut commands: Commands,
-    render_settings: Res<RenderSettings>,
-    (q_fields, q_robots, _q_balls): (
-        Query<Entity, (With<Field>, With<Mesh3d>)>,
-        Query<Entity, With<Robot>>,
-        Query<Entity, With<Ball>>,
-    ),
+fn send_vis_selection(
+    mut q_fields: Query<(&Field, Ref<SelectedVisualizations>, &mut VisSelectionStatus)>,
+    mut q_sources: Query<
+        (
+            &DataSource,
+            Ref<SelectedVisualizations>,
+            &mut VisSelectionStatus,
+        ),
+        Without<Field>,
+    >,
 ) {
-    // Remove all potentially outdated entities. They will be recreated automatically.
-    // Does not affect visualizations and balls, as they get regenerated periodically anyways.
-    if !render_settings.field {
-        // The field entity is also used as a marker for data processing, so only the model is removed
-        for field_entity in q_fields {
-            commands.entity(field_entity).remove::<Mesh3d>();
+    let now = Instant::now();
+
+    let mut connections: HashMap<*const Task<()>, (bool, VisualizationFilter)> = HashMap::new();
+    for (field, selection, _) in &q_fields {
+        let (changed, filter) = connections.entry(field.connection.id()).or_default();
+        *changed |= selection.is_changed();
+        filter
+            .allowed_vis_source
+            .extend(&selection.0.allowed_vis_source);
+        filter.allowed_vis_id.extend(&selection.0.allowed_vis_id);
+    }
+    for (_, filter) in connections.values_mut() {
+        for ids in [&mut filter.allowed_vis_source, &mut filter.allowed_vis_id] {
+            ids.sort_unstable();
+            ids.dedup();
         }
     }
-    q_robots.iter().for_each(|e| commands.entity(e).despawn());
-}
-
-// ======== Update the world from the state filter ========
 
-#[allow(clippy::type_complexity)]
-fn update_field_geometry(
-    mut commands: Commands,
-    render_settings: Res<RenderSettings>,
-    white_material: Res<DefaultMaterial>,
-    mut mesh_assets: ResMut<Assets<Mesh>>,
-    mut q_fields: Query<(Ref<FieldGeometry>, Option<&Mesh3d>, Entity)>,
-) {
-    for (field_geometry, mesh_component, entity) in &mut q_fields {
-        if render_settings.field && (field_geometry.is_changed() || mesh_component.is_none()) {
-            commands.entity(entity).insert((
-                Mesh3d(mesh_assets.add(field_mesh(&field_geometry))),
-                MeshMaterial3d(white_material.opaque.clone()),
-            ));
+    // The status of every field is updated, but each connection only gets the filter once
+    let mut sent_connections = HashSet::new();
+    for (field, _, mut status) in &mut q_fields {
+        let Some((changed, filter)) = connections.get(&field.connection.id()) else {
+            continue;
+        };
+        if update_vis_status(&mut status, *changed, filter, now, &field.host)
+            && sent_connections.insert(field.connection.id())
+        {
+            send_vis_filter(&field.connection, filter.clone());
+        }
+    }
+    for (source, selection, mut status) in &mut q_sources {
+        if update_vis_status(
+            &mut status,
+            selection.is_changed(),
+            &selection.0,
+            now,
+            &source.host,
+        ) {
+            send_vis_filter(&source.connection, selection.0.clone());
         }
     }
 }
 
+/// Returns true if the filter should be sent
+fn update_vis_status(
+    status: &mut Mut<VisSelectionStatus>,
+    changed: bool,
+    filter: &VisualizationFilter,
+    now: Instant,
+    host: &FieldHost,
+) -> bool {
+    if changed {
+        status.selection_sent(filter.clone(), now);
+        return true;
+    }
+    if !status.retry_due(now) {
+        return false;
+    }
+    let retry = status.retry(now);
+    if !retry {
+        warn!(
+            "{} still sends unselected visualizations, it probably ignores vis filters",
+            host.websocket_addr
+        );
+    }
+    retry
+}
+
+fn send_vis_filter(connection: &FieldConnection, filter: VisualizationFilter) {
+    debug!("Sending vis selection: {:?}", filter);
+    _ = connection
+        .sender
+        .send_blocking(ws_request::Content::SetVisFilter(filter));
+}
+
+// ======== Update the world from the state filter ========
+
+/// Robot movements below these thresholds (in m and quaternion components) are not written to the transforms
+const TRANSLATION_EPSILON: f32 = 0.0005;
+const ROTATION_EPSILON: f32 = 0.0005;
+
 #[allow(clippy::type_complexity)]
 fn update_world_state(
     mut commands: Commands,
-    render_settings: Res<RenderSettings>,
-    asset_server: Res<AssetServer>,
-    (ball_mesh, robot_mask_mesh): (Res<BallMesh>, Res<RobotMaskMesh>),
+    mut world_state_updates: MessageWriter<WorldStateUpdated>,
+    mut last_world_states: Local<HashMap<Entity, Arc<WorldSnapshot>>>,
+    sampling: Res<WorldStateSampling>,
     (q_fields, mut q_robots, q_balls): (
-        Query<(&WorldStateFilter, Entity)>,
-        Query<(&Robot, &Team, &mut Transform, &ChildOf, Entity)>,
+        Query<(&StateFilter, &GoalReplay, Entity), With<Field>>,
+        Query<(
+            &Robot,
+            &Team,
+            &mut Transform,
+            Option<&mut Velocity>,
+            Option<&mut AngularVelocity>,
+            &ChildOf,
+            Entity,
+        )>,
         Query<(&Transform, &ChildOf, Entity), (With<Ball>, Without<Robot>)>,
     ),
 ) {
-    for (world_state_filter, field_entity) in &q_fields {
-        let world_state = world_state_filter.current_world_state(false);
+    let sample_time = sampling.display_time.unwrap_or_else(Instant::now);
+    last_world_states.retain(|field, _| q_fields.contains(*field));
+    for (world_state_filter, goal_replay, field_entity) in &q_fields {
+        let replay_frame = goal_replay.current_frame();
+        let replay = replay_frame.is_some();
+        let world_state = replay_frame
+            .unwrap_or_else(|| world_state_filter.sample_at(sample_time, sampling.interpolate));
+        let last = last_world_states.insert(field_entity, Arc::clone(&world_state));
+        if last.is_none_or(|last| !Arc::ptr_eq(&last, &world_state) && *last != *world_state) {
+            world_state_updates.write(WorldStateUpdated {
+                field: field_entity,
+                world_state: Arc::clone(&world_state),
+                replay,
+            });
+        }
 
         // TODO: Correlate new to old balls and move them instead of recreating everything. Don't forget to update handle_render_settings_change
         // Despawn old balls
@@ -499,100 +1386,120 @@ fn update_world_state(
             });
 
         // Spawn new balls
-        for new_ball in world_state.ball {
-            let new_ball_pos = Vec3::new(new_ball.p_x, new_ball.p_z.unwrap_or(0.0), new_ball.p_y);
-
-            let mut new_ball = commands.spawn((Ball, Transform::from_translation(new_ball_pos)));
-            if render_settings.ball {
-                new_ball.insert((
-                    Mesh3d(ball_mesh.0.clone()),
-                    MeshMaterial3d(ball_mesh.1.clone()),
-                ));
+        for new_ball in &world_state.balls {
+            let mut ball_commands =
+                commands.spawn((Ball, Transform::from_translation(new_ball.translation)));
+            if let Some(velocity) = new_ball.velocity {
+                ball_commands.insert(Velocity(velocity));
             }
-            let new_ball = new_ball.id();
-            commands.entity(field_entity).add_child(new_ball);
+            let ball_entity = ball_commands.id();
+            commands.entity(field_entity).add_child(ball_entity);
         }
 
         // Update robots
         let mut leftover_robots = q_robots
             .iter_mut()
-            .filter(|(_, _, _, c, _)| c.parent() == field_entity)
+            .filter(|(.., c, _)| c.parent() == field_entity)
             .collect::<Vec<_>>();
 
-        let mut update_robots = |team: Team, new_robots: Vec<proto::remote::Robot>| {
-            for robot_update in new_robots {
-                let leftover_index = leftover_robots
-                    .iter()
-                    .position(|(r, t, _, _, _)| **t == team && r.0 as u32 == robot_update.id);
-                let new_robot_pos = Vec3::new(robot_update.p_x, 0.0, robot_update.p_y);
-
-                if let Some(i) = leftover_index {
-                    // Robot already exists -> update transform
-                    let (_, _, mut t, _, _) = leftover_robots.remove(i);
-                    t.translation = new_robot_pos;
-                    t.rotation = Quat::from_rotation_y(robot_update.phi);
-                } else {
-                    // Add new robot
-                    let mut new_robot = commands.spawn((
-                        Robot(robot_update.id as u8),
-                        team,
-                        Transform {
-                            translation: new_robot_pos,
-                            rotation: Quat::from_rotation_y(robot_update.phi),
-                            ..Transform::default()
-                        },
-                    ));
-                    match render_settings.robots {
-                        RobotRenderSettings::Detailed => todo!(),
-                        RobotRenderSettings::Fallback => {
-                            new_robot.insert(SceneRoot(
-                                asset_server.load("teams/robots/generic.glb#Scene0"),
-                            ));
-                        }
-                        RobotRenderSettings::Cutout => {
-                            new_robot.insert((
-                                Mesh3d(robot_mask_mesh.0.clone()),
-                                MeshMaterial3d(robot_mask_mesh.1.clone()),
-                            ));
-                        }
-                        RobotRenderSettings::None => {}
-                    }
-                    let new_robot_id = new_robot.id();
-                    commands.entity(field_entity).add_child(new_robot_id);
+        for robot_update in &world_state.robots {
+            let leftover_index = leftover_robots
+                .iter()
+                .position(|(r, t, ..)| **t == robot_update.team && r.0 as u32 == robot_update.id);
+
+            if let Some(i) = leftover_index {
+                // Robot already exists -> update transform
+                let (_, _, mut t, velocity, angular_velocity, _, e) = leftover_robots.remove(i);
+                sync_component(
+                    &mut commands,
+                    e,
+                    velocity,
+                    robot_update.velocity.map(Velocity),
+                );
+                sync_component(
+                    &mut commands,
+                    e,
+                    angular_velocity,
+                    robot_update.angular_velocity.map(AngularVelocity),
+                );
+                let new_transform = robot_update.transform();
+                // Only write actual movements, so that standing robots don't trigger transform propagation
+                if !t
+                    .translation
+                    .abs_diff_eq(new_transform.translation, TRANSLATION_EPSILON)
+                    || !t
+                        .rotation
+                        .abs_diff_eq(new_transform.rotation, ROTATION_EPSILON)
+                {
+                    t.translation = new_transform.translation;
+                    t.rotation = new_transform.rotation;
                 }
+            } else {
+                // Add new robot
+                let mut robot_commands = commands.spawn((
+                    Robot(robot_update.id as u8),
+                    robot_update.team,
+                    robot_update.transform(),
+                ));
+                if let Some(velocity) = robot_update.velocity {
+                    robot_commands.insert(Velocity(velocity));
+                }
+                if let Some(angular_velocity) = robot_update.angular_velocity {
+                    robot_commands.insert(AngularVelocity(angular_velocity));
+                }
+                let new_robot_id = robot_commands.id();
+                commands.entity(field_entity).add_child(new_robot_id);
             }
-        };
-
-        update_robots(Team::Yellow, world_state.yellow_robot);
-        update_robots(Team::Blue, world_state.blue_robot);
+        }
 
         // Despawn all remaining robots
-        leftover_robots.into_iter().for_each(|(_, _, _, _, e)| {
+        leftover_robots.into_iter().for_each(|(.., e)| {
             commands.entity(field_entity).detach_child(e);
             commands.entity(e).despawn()
         });
     }
 }
 
+/// Inserts, updates or removes a component that is only present while its value is known
+fn sync_component<C: Component<Mutability = Mutable> + PartialEq>(
+    commands: &mut Commands,
+    entity: Entity,
+    current: Option<Mut<C>>,
+    new: Option<C>,
+) {
+    match (current, new) {
+        (Some(mut current), Some(new)) => {
+            current.set_if_neq(new);
+        }
+        (None, Some(new)) => {
+            commands.entity(entity).insert(new);
+        }
+        (Some(_), None) => {
+            commands.entity(entity).remove::<C>();
+        }
+        (None, None) => {}
+    }
+}
+
 #[allow(clippy::type_complexity)]
 fn update_visualizations(
     mut commands: Commands,
-    render_settings: Res<RenderSettings>,
-    material: Res<DefaultMaterial>,
-    mut mesh_assets: ResMut<Assets<Mesh>>,
     (mut q_fields, q_visualizations): (
-        Query<(&mut VisualizationTracker, &AvailableVisualizations, Entity)>,
+        Query<(&mut VisualizationTracker, &SelectedVisualizations, Entity)>,
         Query<(&Visualization, &ChildOf, Entity)>,
     ),
 ) {
-    for (mut vis_tracker, vis_names, field_entity) in &mut q_fields {
-        let (group_count, updated_groups, new_visualizations) = vis_tracker.visualization_updates();
-        // No new visualizations -> skip field
-        if new_visualizations.is_empty() {
+    for (mut vis_tracker, selection, field_entity) in &mut q_fields {
+        // Unselected visualizations are dropped here, since duplicated fields get the visualizations of all duplicates
+        // and some hosts don't filter at all
+        let (group_count, updated_groups, new_visualizations) =
+            vis_tracker.visualization_updates(&selection.0);
+        // No updated groups -> skip field. An update without visualizations still clears the old ones.
+        if updated_groups.is_empty() {
             continue;
         }
 
-        // Despawn old visualization meshes
+        // Despawn old visualizations
         q_visualizations
             .iter()
             .filter(|(_, c, _)| c.parent() == field_entity)
@@ -603,20 +1510,14 @@ fn update_visualizations(
                 }
             });
 
-        if render_settings.visualizations {
-            // Generate and Spawn new visualization meshes
-            for visualization in new_visualizations {
-                let vis_id = visualization.id;
-                let vis_mesh =
-                    mesh_assets.add(visualization_mesh(&[visualization], Some(vis_names)));
-
-                commands.entity(field_entity).with_child((
-                    Visualization(vis_id),
-                    Transform::default(),
-                    Mesh3d(vis_mesh),
-                    MeshMaterial3d(material.translucent.clone()),
-                ));
-            }
+        // Spawn new visualizations, meshes are generated by the rendering plugin if available
+        let _span = info_span!("spawn_visualizations", count = new_visualizations.len()).entered();
+        for visualization in new_visualizations {
+            commands.entity(field_entity).with_child((
+                Visualization(visualization.id),
+                VisualizationData(visualization),
+                Transform::default(),
+            ));
         }
     }
 }
@@ -0,0 +1,577 @@
+//! Minimal mDNS client (RFC 6762) for hostname lookups of discovered hosts, and DNS-SD browsing (RFC 6763)
+//! for hosts that are advertised with avahi instead of the beacon.
+//! Only the record types that are needed are decoded, everything else is skipped.
+
+use async_net::UdpSocket;
+use bevy::tasks::futures_lite::FutureExt;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::time::{Duration, Instant};
+
+pub(crate) const MDNS_PORT: u16 = 5353;
+pub(crate) const MDNS_GROUP_V4: SocketAddrV4 =
+    SocketAddrV4::new(Ipv4Addr::new(224, 0, 0, 251), MDNS_PORT);
+pub(crate) const MDNS_GROUP_V6: Ipv6Addr = Ipv6Addr::new(0xFF02, 0, 0, 0, 0, 0, 0, 0xFB);
+/// Responders usually answer within a few ms, this also covers busy wifi
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// DNS-SD service types that hosts are advertised with. The instances point to the websocket port,
+/// and can have `instance_id=<id>` and `encrypted=1` txt entries like the beacon.
+pub(crate) const SERVICE_TYPES: [&str; 2] = ["_xrvis._udp.local", "_ssl-status._udp.local"];
+/// Continuous DNS-SD queries start with this interval and double it up to the maximum
+pub(crate) const MIN_QUERY_INTERVAL: Duration = Duration::from_secs(1);
+pub(crate) const MAX_QUERY_INTERVAL: Duration = Duration::from_secs(60);
+/// Services that weren't announced again for this long are forgotten.
+/// That is longer than two query intervals, so a single lost response doesn't drop a host.
+const SERVICE_EXPIRY: Duration = Duration::from_secs(2 * MAX_QUERY_INTERVAL.as_secs() + 5);
+
+const TYPE_A: u16 = 1;
+const TYPE_PTR: u16 = 12;
+const TYPE_TXT: u16 = 16;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const CLASS_IN: u16 = 1;
+/// The top bit of the class is the cache-flush bit in answers and the unicast-response bit in questions
+const CLASS_MASK: u16 = 0x7FFF;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Record {
+    pub name: String,
+    pub data: RecordData,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum RecordData {
+    Ptr(String),
+    Srv {
+        port: u16,
+        target: String,
+    },
+    Txt(Vec<String>),
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    /// Record types that aren't used
+    Other,
+}
+
+/// Encodes a query with one question per name and record type
+pub(crate) fn encode_query(questions: &[(&str, u16)]) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(512);
+    // Id, flags, then the counts of questions, answers, authority and additional records
+    packet.extend_from_slice(&[0, 0, 0, 0]);
+    packet.extend_from_slice(&(questions.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+
+    for (name, record_type) in questions {
+        for label in name.split('.').filter(|label| !label.is_empty()) {
+            packet.push(label.len().min(63) as u8);
+            packet.extend_from_slice(&label.as_bytes()[..label.len().min(63)]);
+        }
+        packet.push(0);
+        packet.extend_from_slice(&record_type.to_be_bytes());
+        packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+    }
+    packet
+}
+
+/// Decodes the answers and additional records of a response, `None` if the packet is malformed
+pub(crate) fn parse_response(packet: &[u8]) -> Option<Vec<Record>> {
+    let read_u16 = |offset: usize| -> Option<u16> {
+        Some(u16::from_be_bytes(
+            packet.get(offset..offset + 2)?.try_into().ok()?,
+        ))
+    };
+
+    let flags = read_u16(2)?;
+    // Queries of other clients are received on the shared multicast port as well
+    if flags & 0x8000 == 0 {
+        return Some(Vec::new());
+    }
+    let questions = read_u16(4)?;
+    let records = read_u16(6)? as usize + read_u16(8)? as usize + read_u16(10)? as usize;
+
+    let mut offset = 12;
+    for _ in 0..questions {
+        offset = read_name(packet, offset)?.1 + 4;
+    }
+
+    let mut parsed = Vec::with_capacity(records);
+    for _ in 0..records {
+        let (name, next) = read_name(packet, offset)?;
+        let record_type = read_u16(next)?;
+        let class = read_u16(next + 2)? & CLASS_MASK;
+        let length = read_u16(next + 8)? as usize;
+        let data_offset = next + 10;
+        let data = packet.get(data_offset..data_offset + length)?;
+        offset = data_offset + length;
+
+        if class != CLASS_IN {
+            continue;
+        }
+        let data = match record_type {
+            TYPE_PTR => RecordData::Ptr(read_name(packet, data_offset)?.0),
+            TYPE_SRV => RecordData::Srv {
+                port: read_u16(data_offset + 4)?,
+                target: read_name(packet, data_offset + 6)?.0,
+            },
+            TYPE_TXT => RecordData::Txt(read_txt(data)?),
+            TYPE_A => RecordData::A(<[u8; 4]>::try_from(data).ok()?.into()),
+            TYPE_AAAA => RecordData::Aaaa(<[u8; 16]>::try_from(data).ok()?.into()),
+            _ => RecordData::Other,
+        };
+        parsed.push(Record { name, data });
+    }
+    Some(parsed)
+}
+
+/// Reads a possibly compressed name, returns it without the trailing dot and the offset after it
+fn read_name(packet: &[u8], mut offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut end = None;
+    // Every pointer has to point backwards, which also rules out loops
+    let mut limit = offset;
+    loop {
+        let length = *packet.get(offset)? as usize;
+        match length {
+            0 => {
+                return Some((labels.join("."), end.unwrap_or(offset + 1)));
+            }
+            0xC0.. => {
+                let pointer = u16::from_be_bytes([length as u8 & 0x3F, *packet.get(offset + 1)?]);
+                end.get_or_insert(offset + 2);
+                if pointer as usize >= limit {
+                    return None;
+                }
+                offset = pointer as usize;
+                limit = offset;
+            }
+            64.. => return None,
+            _ => {
+                let label = packet.get(offset + 1..offset + 1 + length)?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                offset += 1 + length;
+            }
+        }
+    }
+}
+
+/// Splits the length prefixed strings of a txt record
+fn read_txt(mut data: &[u8]) -> Option<Vec<String>> {
+    let mut entries = Vec::new();
+    while let Some((length, rest)) = data.split_first() {
+        let entry = rest.get(..*length as usize)?;
+        if !entry.is_empty() {
+            entries.push(String::from_utf8_lossy(entry).into_owned());
+        }
+        data = &rest[*length as usize..];
+    }
+    Some(entries)
+}
+
+/// The name of the PTR record of an address, e.g. `4.3.2.1.in-addr.arpa` for 1.2.3.4
+fn reverse_name(ip: IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, d] = ip.octets();
+            format!("{d}.{c}.{b}.{a}.in-addr.arpa")
+        }
+        IpAddr::V6(ip) => {
+            let mut name = String::with_capacity(72);
+            for nibble in (0..32).map(|i| (ip.to_bits() >> (i * 4)) & 0xF) {
+                name.push_str(&format!("{nibble:x}."));
+            }
+            name + "ip6.arpa"
+        }
+    }
+}
+
+/// The name of the host's address, e.g. `field-a.local`. The host itself is asked first, then the system resolver,
+/// which also knows hosts with a DNS entry. `None` if neither knows a name.
+pub(crate) async fn lookup_hostname(host: SocketAddr) -> Option<String> {
+    match query_hostname(host).await {
+        Some(hostname) => Some(hostname),
+        None => resolve_hostname(host.ip()).await,
+    }
+}
+
+/// Looks the address up with the system resolver on its own thread, since the lookup blocks
+async fn resolve_hostname(ip: IpAddr) -> Option<String> {
+    let (tx, rx) = async_channel::bounded(1);
+    std::thread::spawn(move || {
+        _ = tx.send_blocking(net_ext::name_lookup::lookup_addr(ip));
+    });
+    rx.recv().await.ok()?.ok()
+}
+
+/// Asks the host for the name of its address. Hosts that run avahi or another responder answer these
+/// direct queries, `None` if there is no answer within [`LOOKUP_TIMEOUT`].
+async fn query_hostname(host: SocketAddr) -> Option<String> {
+    let bind_addr: SocketAddr = match host {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind_addr).await.ok()?;
+    let reverse_name = reverse_name(host.ip());
+
+    // Queries from another port than 5353 are answered directly to the sender (legacy unicast)
+    let mut target = host;
+    target.set_port(MDNS_PORT);
+    socket
+        .send_to(&encode_query(&[(&reverse_name, TYPE_PTR)]), target)
+        .await
+        .ok()?;
+
+    let receive = async {
+        let mut rx_buf = [0u8; 1500];
+        loop {
+            let (size, source) = socket.recv_from(&mut rx_buf).await.ok()?;
+            if source.ip() != host.ip() {
+                continue;
+            }
+            let hostname = parse_response(&rx_buf[..size])
+                .unwrap_or_default()
+                .into_iter()
+                .find_map(|record| match record.data {
+                    RecordData::Ptr(hostname)
+                        if record.name.eq_ignore_ascii_case(&reverse_name) =>
+                    {
+                        Some(hostname)
+                    }
+                    _ => None,
+                });
+            if hostname.is_some() {
+                return hostname;
+            }
+        }
+    };
+    receive
+        .or(async {
+            async_io::Timer::after(LOOKUP_TIMEOUT).await;
+            None
+        })
+        .await
+}
+
+/// A host that is advertised as a DNS-SD service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ServiceHost {
+    pub hostname: String,
+    /// Address and websocket port of the host
+    pub addr: SocketAddr,
+    /// Merges the service with the beacons of the same host
+    pub instance_id: Option<u32>,
+    pub encrypted: bool,
+}
+
+/// Collects the records of DNS-SD responses until the advertised hosts are resolved
+#[derive(Debug, Default)]
+pub(crate) struct ServiceBrowser {
+    /// Service instances by name, e.g. `field-a._xrvis._udp.local`
+    instances: HashMap<String, Instant>,
+    /// Port and target hostname by instance name
+    services: HashMap<String, (Instant, u16, String)>,
+    txt: HashMap<String, Vec<String>>,
+    /// Addresses by hostname, link-local ipv6 addresses with the interface they were received on
+    addrs: HashMap<String, (Instant, Vec<SocketAddr>)>,
+}
+
+impl ServiceBrowser {
+    /// Asks for all instances of the service types, and for the records that are still missing to resolve them
+    pub(crate) fn query(&self) -> Vec<u8> {
+        let mut questions: Vec<_> = SERVICE_TYPES.iter().map(|t| (*t, TYPE_PTR)).collect();
+        for instance in self
+            .instances
+            .keys()
+            .filter(|instance| !self.services.contains_key(*instance))
+        {
+            questions.extend([(instance.as_str(), TYPE_SRV), (instance.as_str(), TYPE_TXT)]);
+        }
+        for (_, _, target) in self
+            .services
+            .values()
+            .filter(|(_, _, target)| !self.addrs.contains_key(target))
+        {
+            questions.extend([(target.as_str(), TYPE_A), (target.as_str(), TYPE_AAAA)]);
+        }
+        encode_query(&questions)
+    }
+
+    pub(crate) fn add_records(&mut self, records: Vec<Record>, source: SocketAddr) {
+        let now = Instant::now();
+        let is_service = |name: &str| {
+            let name = name.to_lowercase();
+            SERVICE_TYPES.iter().any(|t| name.ends_with(t))
+        };
+
+        // The addresses of a host are all sent in the same response and replace the previous ones
+        let mut addrs: HashMap<String, Vec<SocketAddr>> = HashMap::new();
+        for record in records {
+            match record.data {
+                RecordData::Ptr(instance) if is_service(&record.name) => {
+                    self.instances.insert(instance, now);
+                }
+                RecordData::Srv { port, target } if is_service(&record.name) => {
+                    self.services.insert(record.name, (now, port, target));
+                }
+                RecordData::Txt(entries) if is_service(&record.name) => {
+                    self.txt.insert(record.name, entries);
+                }
+                RecordData::A(ip) => {
+                    addrs.entry(record.name).or_default().push((ip, 0).into());
+                }
+                RecordData::Aaaa(ip) => {
+                    let scope_id = match source {
+                        SocketAddr::V6(source) if ip.is_unicast_link_local() => source.scope_id(),
+                        _ => 0,
+                    };
+                    let addr = SocketAddrV6::new(ip, 0, 0, scope_id);
+                    addrs.entry(record.name).or_default().push(addr.into());
+                }
+                _ => {}
+            }
+        }
+        for (name, addrs) in addrs {
+            self.addrs.insert(name, (now, addrs));
+        }
+    }
+
+    /// Forgets expired records and returns the hosts whose port and address are known
+    pub(crate) fn hosts(&mut self) -> Vec<ServiceHost> {
+        let now = Instant::now();
+        self.instances
+            .retain(|_, time| now - *time < SERVICE_EXPIRY);
+        self.services
+            .retain(|_, (time, ..)| now - *time < SERVICE_EXPIRY);
+        self.txt
+            .retain(|instance, _| self.services.contains_key(instance));
+        self.addrs
+            .retain(|_, (time, _)| now - *time < SERVICE_EXPIRY);
+
+        self.instances
+            .keys()
+            .filter_map(|instance| {
+                let (_, port, target) = self.services.get(instance)?;
+                let (_, addrs) = self.addrs.get(target)?;
+                // Ipv4 first, then global and link-local ipv6 that could be scoped to its interface
+                let addr = addrs.iter().min_by_key(|addr| match addr {
+                    SocketAddr::V4(_) => 0,
+                    SocketAddr::V6(addr) if !addr.ip().is_unicast_link_local() => 1,
+                    SocketAddr::V6(addr) if addr.scope_id() != 0 => 2,
+                    SocketAddr::V6(_) => 3,
+                })?;
+                let mut addr = *addr;
+                addr.set_port(*port);
+
+                let txt = |key: &str| {
+                    self.txt.get(instance)?.iter().find_map(|entry| {
+                        let (entry_key, value) = entry.split_once('=')?;
+                        entry_key.eq_ignore_ascii_case(key).then_some(value)
+                    })
+                };
+                Some(ServiceHost {
+                    hostname: target.clone(),
+                    addr,
+                    instance_id: txt("instance_id").and_then(|id| id.parse().ok()),
+                    encrypted: txt("encrypted")
+                        .is_some_and(|value| value == "1" || value == "true"),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response header with the given number of answers, followed by the records
+    fn response(answers: u16, records: &[u8]) -> Vec<u8> {
+        [
+            &[0, 0, 0x84, 0, 0, 0][..],
+            &answers.to_be_bytes(),
+            &[0, 0, 0, 0],
+            records,
+        ]
+        .concat()
+    }
+
+    /// `_xrvis._udp.local PTR field-a._xrvis._udp.local`, the instance name is compressed
+    const PTR_RESPONSE: &[u8] = b"\x00\x00\x84\x00\x00\x00\x00\x01\x00\x00\x00\x00\
+        \x06_xrvis\x04_udp\x05local\x00\x00\x0c\x00\x01\x00\x00\x00\x78\x00\x0a\x07field-a\xc0\x0c";
+    const HOST: &[u8] = b"\x04host\x05local\x00";
+
+    #[test]
+    fn compressed_names_are_expanded() {
+        assert_eq!(
+            parse_response(PTR_RESPONSE),
+            Some(vec![Record {
+                name: "_xrvis._udp.local".to_string(),
+                data: RecordData::Ptr("field-a._xrvis._udp.local".to_string()),
+            }])
+        );
+    }
+
+    #[test]
+    fn truncated_packets_are_rejected() {
+        for length in 0..PTR_RESPONSE.len() {
+            assert_eq!(
+                parse_response(&PTR_RESPONSE[..length]),
+                None,
+                "length {length}"
+            );
+        }
+    }
+
+    #[test]
+    fn pointer_loops_are_rejected() {
+        let record = b"\x00\x01\x00\x01\x00\x00\x00\x78\x00\x04\x0a\x00\x00\x01";
+        // Pointing to itself, forwards, and in a loop over a label
+        for name in [&b"\xc0\x0c"[..], b"\xc0\x20", b"\x01a\xc0\x0c"] {
+            let packet = response(1, &[name, record].concat());
+            assert_eq!(parse_response(&packet), None, "name {name:x?}");
+        }
+    }
+
+    #[test]
+    fn address_records_need_matching_lengths() {
+        // The cache-flush bit is set in the class
+        let a = |data: &[u8]| {
+            let length = (data.len() as u16).to_be_bytes();
+            response(
+                1,
+                &[HOST, b"\x00\x01\x80\x01\x00\x00\x00\x78", &length, data].concat(),
+            )
+        };
+        assert_eq!(
+            parse_response(&a(&[192, 168, 0, 2])),
+            Some(vec![Record {
+                name: "host.local".to_string(),
+                data: RecordData::A(Ipv4Addr::new(192, 168, 0, 2)),
+            }])
+        );
+        assert_eq!(parse_response(&a(&[0; 16])), None);
+
+        let aaaa = response(
+            1,
+            &[
+                HOST,
+                b"\x00\x1c\x00\x01\x00\x00\x00\x78\x00\x04\x0a\x00\x00\x01",
+            ]
+            .concat(),
+        );
+        assert_eq!(parse_response(&aaaa), None);
+    }
+
+    #[test]
+    fn other_types_and_classes_are_skipped() {
+        // HINFO in IN, then an A record in CH
+        let hinfo = [HOST, b"\x00\x0d\x00\x01\x00\x00\x00\x78\x00\x02\x00\x00"].concat();
+        let chaos_a = [
+            HOST,
+            b"\x00\x01\x00\x03\x00\x00\x00\x78\x00\x04\x0a\x00\x00\x01",
+        ]
+        .concat();
+        assert_eq!(
+            parse_response(&response(2, &[hinfo, chaos_a].concat())),
+            Some(vec![Record {
+                name: "host.local".to_string(),
+                data: RecordData::Other,
+            }])
+        );
+    }
+
+    #[test]
+    fn service_records_are_parsed() {
+        // SRV with priority, weight, port 8080 and a compressed target, then two txt entries
+        let srv = [
+            HOST,
+            b"\x00\x21\x00\x01\x00\x00\x00\x78\x00\x08\x00\x00\x00\x00\x1f\x90\xc0\x0c",
+        ]
+        .concat();
+        let txt = [
+            &b"\xc0\x0c\x00\x10\x00\x01\x00\x00\x00\x78\x00\x1b"[..],
+            b"\x0dinstance_id=7\x00\x0bencrypted=1",
+        ]
+        .concat();
+        assert_eq!(
+            parse_response(&response(2, &[srv, txt].concat())),
+            Some(vec![
+                Record {
+                    name: "host.local".to_string(),
+                    data: RecordData::Srv {
+                        port: 8080,
+                        target: "host.local".to_string(),
+                    },
+                },
+                Record {
+                    name: "host.local".to_string(),
+                    data: RecordData::Txt(vec![
+                        "instance_id=7".to_string(),
+                        "encrypted=1".to_string(),
+                    ]),
+                },
+            ])
+        );
+    }
+
+    #[test]
+    fn queries_of_other_clients_are_ignored() {
+        let query = encode_query(&[(SERVICE_TYPES[0], TYPE_PTR)]);
+        assert_eq!(parse_response(&query), Some(Vec::new()));
+    }
+
+    #[test]
+    fn reverse_names() {
+        assert_eq!(
+            reverse_name(Ipv4Addr::new(1, 2, 3, 4).into()),
+            "4.3.2.1.in-addr.arpa"
+        );
+        assert_eq!(
+            reverse_name(Ipv6Addr::LOCALHOST.into()),
+            format!("1.{}ip6.arpa", "0.".repeat(31))
+        );
+    }
+
+    #[test]
+    fn browsed_services_resolve_to_hosts() {
+        let source: SocketAddr = (Ipv4Addr::new(10, 0, 0, 1), MDNS_PORT).into();
+        let instance = "field-a._xrvis._udp.local".to_string();
+        let record = |name: &str, data| Record {
+            name: name.to_string(),
+            data,
+        };
+        let mut browser = ServiceBrowser::default();
+        browser.add_records(
+            vec![record(SERVICE_TYPES[0], RecordData::Ptr(instance.clone()))],
+            source,
+        );
+        assert!(browser.hosts().is_empty());
+
+        browser.add_records(
+            vec![
+                record(
+                    &instance,
+                    RecordData::Srv {
+                        port: 8080,
+                        target: "host.local".to_string(),
+                    },
+                ),
+                record(
+                    &instance,
+                    RecordData::Txt(vec!["instance_id=7".to_string(), "encrypted=1".to_string()]),
+                ),
+                record("host.local", RecordData::A(Ipv4Addr::new(10, 0, 0, 2))),
+            ],
+            source,
+        );
+        assert_eq!(
+            browser.hosts(),
+            [ServiceHost {
+                hostname: "host.local".to_string(),
+                addr: (Ipv4Addr::new(10, 0, 0, 2), 8080).into(),
+                instance_id: Some(7),
+                encrypted: true,
+            }]
+        );
+    }
+}
@@ -0,0 +1,64 @@
+use crate::{FieldGeometry, Team};
+use bevy::prelude::*;
+
+/// Converts a position in SSL field coordinates (m) to the local space of a field entity
+pub fn field_to_local(position: Vec2) -> Vec3 {
+    Vec3::new(position.x, 0.0, -position.y)
+}
+
+/// Converts a position in the local space of a field entity to SSL field coordinates (m), dropping the height
+pub fn local_to_field(position: Vec3) -> Vec2 {
+    Vec2::new(position.x, -position.z)
+}
+
+/// A distance measured on a field, e.g. for free kick setups. Spawn it as a child of the field.
+/// The transform is kept at the center of the line, so annotations can be attached as children.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Component, Debug, Clone, PartialEq)]
+#[require(Transform)]
+pub struct Measurement {
+    /// In SSL field coordinates
+    pub start: Vec2,
+    /// In SSL field coordinates
+    pub end: Vec2,
+}
+
+impl Measurement {
+    pub fn new(start: Vec2, end: Vec2) -> (Self, Transform) {
+        (
+            Self { start, end },
+            Transform::from_translation(field_to_local(start.midpoint(end))),
+        )
+    }
+
+    pub fn distance(&self) -> f32 {
+        self.start.distance(self.end)
+    }
+
+    /// The goal closer to the start point, as team and goal center in field coordinates
+    pub fn closest_goal(&self, geom: &FieldGeometry) -> (Team, Vec2) {
+        // The yellow goal is on the -x side of the field
+        let goal_x = geom.play_area_size.x / 2.0;
+        if self.start.x < 0.0 {
+            (Team::Yellow, Vec2::new(-goal_x, 0.0))
+        } else {
+            (Team::Blue, Vec2::new(goal_x, 0.0))
+        }
+    }
+
+    /// Angle between the measured line and the line from its start to the center of the closest goal, in degrees
+    pub fn goal_angle(&self, geom: &FieldGeometry) -> Option<f32> {
+        let (_, goal) = self.closest_goal(geom);
+        let line = (self.end - self.start).try_normalize()?;
+        let to_goal = (goal - self.start).try_normalize()?;
+        Some(line.angle_to(to_goal).abs().to_degrees())
+    }
+
+    /// E.g. `1.23 m, 15° to goal`
+    pub fn label(&self, geom: &FieldGeometry) -> String {
+        match self.goal_angle(geom) {
+            Some(angle) => format!("{:.2} m, {angle:.0}° to goal", self.distance()),
+            None => format!("{:.2} m", self.distance()),
+        }
+    }
+}
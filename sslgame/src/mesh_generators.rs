@@ -14,6 +14,9 @@ use std::iter;
 const Z_HEIGHT: f32 = 0.01;
 const LINE_WIDTH: f32 = 0.01;
 
+// Grid parameters, above the field lines
+const GRID_HEIGHT: f32 = 0.0002;
+
 /// A builder for constructing 3D meshes programmatically.
 ///
 /// A "selection" is a set of vertices that can be used by a followup operation.
@@ -46,6 +49,8 @@ struct CustomMeshBuilder {
     indices: Vec<u32>,
     last_operation: usize,
     free_vertices: usize,
+    /// Factor for the number of segments of visualization circles
+    circle_detail: f32,
 }
 
 #[allow(dead_code)]
@@ -57,6 +62,7 @@ impl CustomMeshBuilder {
             indices: Vec::new(),
             last_operation: 0,
             free_vertices: 0,
+            circle_detail: 1.0,
         }
     }
 
@@ -404,6 +410,7 @@ impl CustomMeshBuilder {
 
         // Dynamic vertex count based on radius
         let resolution = (radius as u32 * 64).max(32);
+        let resolution = ((resolution as f32 * self.circle_detail) as u32).max(8);
 
         if let Some(fill) = part.fill_color {
             let fill_radius = if part.border_style.is_some() {
@@ -500,11 +507,15 @@ impl CustomMeshBuilder {
 }
 
 /// Builds a single mesh containing all geometry from the visualization list.
+/// Circles get `circle_detail` times their usual number of segments.
 pub fn visualization_mesh(
     vis_list: &[Visualization],
     debug_names: Option<&AvailableVisualizations>,
+    circle_detail: f32,
 ) -> Mesh {
+    let _span = info_span!("visualization_mesh", count = vis_list.len()).entered();
     let mut mesh = CustomMeshBuilder::new();
+    mesh.circle_detail = circle_detail;
 
     for (vis_id, part) in vis_list
         .iter()
@@ -514,6 +525,8 @@ pub fn visualization_mesh(
             Some(Geom::Circle(_)) => mesh.circle_vis(part),
             Some(Geom::Polygon(poly)) if !poly.point.is_empty() => mesh.polygon_vis(part),
             Some(Geom::Path(path)) if !path.point.is_empty() => mesh.path_vis(part),
+            // Rendered by the registered CustomVisHandlers
+            Some(Geom::Custom(_)) => continue,
             other => {
                 warn!(
                     "Invalid visualization part in {}: {}",
@@ -530,7 +543,36 @@ pub fn visualization_mesh(
     mesh.build(false)
 }
 
+/// Center and size of every part of the visualization, in the coordinates of its mesh.
+/// The size is the diameter of circles and the bounding box diagonal of polygons and paths.
+/// Custom geometry isn't part of the mesh and has no known size, so it is infinitely large.
+pub(crate) fn visualization_part_bounds(visualization: &Visualization) -> Vec<(Vec3, f32)> {
+    let points_bounds = |points: &[proto::remote::Point]| {
+        let (min, max) = points
+            .iter()
+            .map(|p| Vec3::from(vis_point(p)))
+            .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), p| {
+                (min.min(p), max.max(p))
+            });
+        (!points.is_empty()).then(|| ((min + max) / 2.0, min.distance(max) + LINE_WIDTH))
+    };
+    visualization
+        .part
+        .iter()
+        .filter_map(|part| match part.geom.as_ref()? {
+            Geom::Circle(c) => Some((
+                Vec3::new(c.p_x, Z_HEIGHT, c.p_y),
+                2.0 * c.radius + LINE_WIDTH,
+            )),
+            Geom::Polygon(poly) => points_bounds(&poly.point),
+            Geom::Path(path) => points_bounds(&path.point),
+            Geom::Custom(_) => Some((Vec3::ZERO, f32::INFINITY)),
+        })
+        .collect()
+}
+
 pub fn field_mesh(geom: &FieldGeometry) -> Mesh {
+    let _span = info_span!("field_mesh").entered();
     let field_col = Color::srgba_u8(0, 135, 0, 255);
     let wall_col = Color::srgba_u8(0, 0, 0, 255);
     let goal_y_col = Color::srgba_u8(255, 255, 0, 255);
@@ -795,6 +837,163 @@ pub fn field_mesh(geom: &FieldGeometry) -> Mesh {
     mesh.build(false)
 }
 
+/// Generates a 1 m / 0.5 m grid over the field including the boundary, with the meter values written into the boundary.
+/// Lines are centered on the field center, like the SSL coordinate system.
+pub fn grid_mesh(geom: &FieldGeometry) -> Mesh {
+    let _span = info_span!("grid_mesh").entered();
+    let major_col = Color::srgba(1.0, 1.0, 1.0, 0.5);
+    let minor_col = Color::srgba(1.0, 1.0, 1.0, 0.25);
+    let label_col = Color::srgba(1.0, 1.0, 1.0, 0.8);
+
+    static MAJOR_WIDTH: f32 = 0.008;
+    static MINOR_WIDTH: f32 = 0.004;
+    static DIGIT_SIZE: Vec2 = Vec2::new(0.06, 0.12);
+
+    let mut mesh = CustomMeshBuilder::new();
+
+    let field_x = geom.play_area_size.x / 2.0 + geom.boundary_width;
+    let field_y = geom.play_area_size.y / 2.0 + geom.boundary_width;
+
+    // ==== Lines ====
+
+    let steps = |half_size: f32| {
+        let n = (half_size / 0.5).floor() as i32;
+        (-n..=n).map(|i| (i as f32 * 0.5, i % 2 == 0))
+    };
+    for (x, major) in steps(field_x) {
+        let (width, color) = if major {
+            (MAJOR_WIDTH, major_col)
+        } else {
+            (MINOR_WIDTH, minor_col)
+        };
+        mesh.insert_path_quad(
+            [x, GRID_HEIGHT, field_y],
+            [x, GRID_HEIGHT, -field_y],
+            width,
+            color,
+        );
+    }
+    for (y, major) in steps(field_y) {
+        let (width, color) = if major {
+            (MAJOR_WIDTH, major_col)
+        } else {
+            (MINOR_WIDTH, minor_col)
+        };
+        mesh.insert_path_quad(
+            [-field_x, GRID_HEIGHT, y],
+            [field_x, GRID_HEIGHT, y],
+            width,
+            color,
+        );
+    }
+
+    // ==== Labels ====
+
+    // In the middle of the boundary, readable from the -y side (+z) of the field
+    let label_x = -(geom.play_area_size.x / 2.0 + geom.boundary_width / 2.0);
+    let label_y = geom.play_area_size.y / 2.0 + geom.boundary_width / 2.0;
+    for (x, major) in steps(geom.play_area_size.x / 2.0) {
+        if major {
+            insert_label(
+                &mut mesh,
+                Vec2::new(x, label_y),
+                x as i32,
+                DIGIT_SIZE,
+                label_col,
+            );
+        }
+    }
+    for (y, major) in steps(geom.play_area_size.y / 2.0) {
+        if major {
+            // Local z points towards -y in field coordinates
+            insert_label(
+                &mut mesh,
+                Vec2::new(label_x, -y),
+                y as i32,
+                DIGIT_SIZE,
+                label_col,
+            );
+        }
+    }
+
+    mesh.build(false)
+}
+
+/// Writes the number centered on the position (local x/z) with seven segment strokes
+fn insert_label(
+    mesh: &mut CustomMeshBuilder,
+    center: Vec2,
+    value: i32,
+    digit_size: Vec2,
+    color: Color,
+) {
+    const STROKE_WIDTH: f32 = 0.01;
+    let text = value.to_string();
+    let advance = digit_size.x * 1.6;
+    let text_width = advance * (text.len() as f32 - 1.0) + digit_size.x;
+    let origin = center - Vec2::new(text_width, -digit_size.y) / 2.0;
+
+    for (i, character) in text.chars().enumerate() {
+        for (from, to) in seven_segment_strokes(character) {
+            // Up in the label is away from the reader, so towards -z
+            let to_local = |point: Vec2| {
+                let point = point * digit_size;
+                [
+                    origin.x + i as f32 * advance + point.x,
+                    GRID_HEIGHT,
+                    origin.y - point.y,
+                ]
+            };
+            mesh.insert_path_quad(to_local(from), to_local(to), STROKE_WIDTH, color);
+        }
+    }
+}
+
+/// Strokes of a seven segment display in a unit square, bottom left origin
+fn seven_segment_strokes(character: char) -> impl Iterator<Item = (Vec2, Vec2)> {
+    // Segments a (top) to g (middle), clockwise starting at the top
+    const SEGMENTS: [(Vec2, Vec2); 7] = [
+        (Vec2::new(0.0, 1.0), Vec2::new(1.0, 1.0)),
+        (Vec2::new(1.0, 1.0), Vec2::new(1.0, 0.5)),
+        (Vec2::new(1.0, 0.5), Vec2::new(1.0, 0.0)),
+        (Vec2::new(1.0, 0.0), Vec2::new(0.0, 0.0)),
+        (Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.5)),
+        (Vec2::new(0.0, 0.5), Vec2::new(0.0, 1.0)),
+        (Vec2::new(0.0, 0.5), Vec2::new(1.0, 0.5)),
+    ];
+    let active: &[usize] = match character {
+        '0' => &[0, 1, 2, 3, 4, 5],
+        '1' => &[1, 2],
+        '2' => &[0, 1, 6, 4, 3],
+        '3' => &[0, 1, 6, 2, 3],
+        '4' => &[5, 6, 1, 2],
+        '5' => &[0, 5, 6, 2, 3],
+        '6' => &[0, 5, 6, 4, 2, 3],
+        '7' => &[0, 1, 2],
+        '8' => &[0, 1, 2, 3, 4, 5, 6],
+        '9' => &[0, 1, 2, 3, 5, 6],
+        '-' => &[6],
+        _ => &[],
+    };
+    active.iter().map(|&i| SEGMENTS[i])
+}
+
+/// A unit disc that is dark in the middle and fades out towards its edge, scaled to the size of the shadow
+pub fn shadow_blob_mesh() -> Mesh {
+    const SEGMENTS: u32 = 24;
+    let core_col = Color::srgba(0.0, 0.0, 0.0, 0.6);
+    let edge_col = Color::srgba(0.0, 0.0, 0.0, 0.0);
+
+    CustomMeshBuilder::new()
+        .with_filled_circle([0.0; 3], 0.4, SEGMENTS, core_col)
+        .with_quad_loft(
+            with_col(circle_vertices([0.0; 3], 1.0, SEGMENTS), edge_col),
+            true,
+            false,
+        )
+        .build(true)
+}
+
 // ==== Helper functions ====
 
 fn circle_vertices(
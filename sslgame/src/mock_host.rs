@@ -0,0 +1,529 @@
+use crate::compression;
+use crate::demo::{self, DemoGame};
+use crate::encryption::{self, PresharedKey};
+use crate::network_tasks;
+use crate::proto::remote::udp_stream_request::UdpStream;
+use crate::proto::remote::ws_stream_request::WsStream;
+use crate::proto::remote::*;
+use crate::recording::{self, PlaybackClock, Record};
+use crate::services::{BEACON_ADDR_V4, BEACON_ADDR_V6};
+use crate::sources::SourceStreams;
+use crate::update_packet::UpdatePacket;
+use crate::world_state_delta::WorldStateEncoder;
+use async_channel::{Receiver, Sender, TrySendError};
+use async_net::{TcpListener, TcpStream, UdpSocket};
+use async_tungstenite::tungstenite;
+use bevy::prelude::*;
+use bevy::tasks::futures_lite::{FutureExt, StreamExt, future};
+use bevy::tasks::{IoTaskPool, Task};
+use prost::Message;
+use std::collections::HashSet;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+/// Where the packets served by a [`MockHost`] come from.
+#[derive(Debug, Clone)]
+pub enum PacketSource {
+    Demo(DemoGame),
+    Recording(Vec<Record>),
+    /// A recording that advances in lockstep with the replays on the clock, see [`crate::Field::replay_synced`]
+    SyncedRecording(Vec<Record>, PlaybackClock),
+    /// Forwards all streams of the host at this websocket address, e.g. to clients on another network segment.
+    /// The connection to the host is re-established whenever it is lost, encrypted if a key is given.
+    Relay(SocketAddr, Option<PresharedKey>),
+}
+
+impl PacketSource {
+    /// Loads a recording created by a [`crate::FieldRecorder`]
+    pub fn recording(path: impl AsRef<Path>) -> io::Result<Self> {
+        recording::read_recording(path).map(Self::Recording)
+    }
+
+    /// Loads a recording that is served in lockstep with the local replays on the clock,
+    /// so clients see the same moment as the rebroadcasting viewer.
+    pub fn recording_synced(path: impl AsRef<Path>, clock: &PlaybackClock) -> io::Result<Self> {
+        recording::read_recording(path).map(|records| Self::SyncedRecording(records, clock.clone()))
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct MockHostConfig {
+    /// Address of the websocket server. Port 0 picks a free port.
+    pub bind_addr: SocketAddr,
+    pub hostname: Option<String>,
+    /// Send HostAdvertisements to the discovery multicast groups
+    pub advertise: bool,
+    /// Also send the advertisements to these addresses, e.g. a headset or the broadcast address of
+    /// another network segment that the multicast beacons don't reach
+    pub advertise_to: Vec<SocketAddr>,
+    /// Only accept clients that encrypt their connection with this key
+    pub key: Option<PresharedKey>,
+}
+
+impl Default for MockHostConfig {
+    fn default() -> Self {
+        Self {
+            bind_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            hostname: Some("Mock Host".to_string()),
+            advertise: true,
+            advertise_to: Vec::new(),
+            key: None,
+        }
+    }
+}
+
+/// A minimal host implementation that serves a [`PacketSource`] to any number of clients,
+/// using the same discovery, websocket and udp protocol as a real host.
+///
+/// Used for end-to-end tests, the mock host binary and viewers that rebroadcast their replays.
+/// The host stops when it is dropped, e.g. with the field entity it was inserted on.
+#[derive(Resource, Component, Debug)]
+pub struct MockHost {
+    pub websocket_addr: SocketAddr,
+    state: Arc<Mutex<HostState>>,
+    _tasks: Vec<Task<()>>,
+}
+
+/// Current state of the websocket streams for new subscriptions and the channels of all connected clients.
+#[derive(Debug, Default)]
+struct HostState {
+    geometry: Option<FieldGeometry>,
+    game_state: Option<GameState>,
+    vis_mappings: Option<VisMappings>,
+    clients: Vec<Sender<UpdatePacket>>,
+    key: Option<PresharedKey>,
+}
+
+impl MockHost {
+    /// Starts the host on the [`IoTaskPool`]
+    pub fn spawn(config: MockHostConfig, source: PacketSource) -> io::Result<Self> {
+        let listener = std::net::TcpListener::bind(config.bind_addr)?;
+        let websocket_addr = listener.local_addr()?;
+        let listener = TcpListener::try_from(listener)?;
+
+        let encrypted = config.key.is_some();
+        let state = Arc::new(Mutex::new(HostState {
+            key: config.key,
+            ..default()
+        }));
+        let task_pool = IoTaskPool::get();
+
+        // The sources are the same tasks used for client-side playback, so they expect a request channel
+        let (packets_tx, packets_rx) = async_channel::bounded(100);
+        let (_, requests_rx) = async_channel::bounded(1);
+        let source_task = match source {
+            PacketSource::Demo(game) => {
+                task_pool.spawn(demo::demo_task(game, packets_tx, requests_rx))
+            }
+            PacketSource::Recording(records) => task_pool.spawn(recording::replay_task(
+                records,
+                PlaybackClock::default(),
+                packets_tx,
+                requests_rx,
+            )),
+            PacketSource::SyncedRecording(records, clock) => task_pool.spawn(
+                recording::replay_task(records, clock, packets_tx, requests_rx),
+            ),
+            PacketSource::Relay(upstream, key) => {
+                task_pool.spawn(relay_task(upstream, key, packets_tx))
+            }
+        };
+
+        let mut tasks = vec![
+            source_task,
+            task_pool.spawn(distribution_task(packets_rx, state.clone())),
+            task_pool.spawn(accept_task(listener, state.clone())),
+        ];
+        if config.advertise || !config.advertise_to.is_empty() {
+            tasks.push(
+                task_pool.spawn(advertisement_task(
+                    websocket_addr,
+                    config.hostname,
+                    encrypted,
+                    config
+                        .advertise
+                        .then_some([BEACON_ADDR_V4.into(), BEACON_ADDR_V6.into()]),
+                    config.advertise_to,
+                )),
+            );
+        }
+
+        info!("Mock host listening on {websocket_addr}");
+
+        Ok(Self {
+            websocket_addr,
+            state,
+            _tasks: tasks,
+        })
+    }
+}
+
+impl Drop for MockHost {
+    fn drop(&mut self) {
+        // Client tasks are detached and stop as soon as their packet channel is closed
+        self.state.lock().unwrap().clients.clear();
+    }
+}
+
+async fn advertisement_task(
+    websocket_addr: SocketAddr,
+    hostname: Option<String>,
+    encrypted: bool,
+    groups: Option<[SocketAddr; 2]>,
+    targets: Vec<SocketAddr>,
+) {
+    // Random enough to distinguish multiple mock hosts on the same machine
+    let instance_id = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .subsec_nanos()
+        ^ std::process::id();
+    let advertisement = HostAdvertisement {
+        websocket_port: websocket_addr.port() as u32,
+        hostname,
+        instance_id: Some(instance_id),
+        udp_compression: vec![UdpCompression::Zstd as i32],
+        encrypted: Some(encrypted),
+    }
+    .encode_to_vec();
+
+    let socket_v4 = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+        .await
+        .inspect_err(|e| warn!("Failed to bind ipv4 advertisement socket: {e}"));
+    // Targets can be broadcast addresses
+    if let Ok(socket) = &socket_v4 {
+        _ = socket.set_broadcast(true);
+    }
+    // Hosts on a link-local address advertise on its interface, which is selected by binding to the scoped address
+    let bind_addr_v6 = match websocket_addr {
+        SocketAddr::V6(addr) if addr.scope_id() != 0 => {
+            SocketAddrV6::new(*addr.ip(), 0, 0, addr.scope_id())
+        }
+        _ => SocketAddrV6::new(Ipv6Addr::UNSPECIFIED, 0, 0, 0),
+    };
+    let socket_v6 = UdpSocket::bind(bind_addr_v6)
+        .await
+        .inspect_err(|e| warn!("Failed to bind ipv6 advertisement socket: {e}"));
+
+    loop {
+        // Send errors are expected on machines without a v4 or v6 route, the other one still works
+        for target in groups.iter().flatten().chain(&targets) {
+            let socket = if target.is_ipv4() {
+                &socket_v4
+            } else {
+                &socket_v6
+            };
+            if let Ok(socket) = socket {
+                _ = socket.send_to(&advertisement, *target).await;
+            }
+        }
+        async_io::Timer::after(Duration::from_secs(1)).await;
+    }
+}
+
+/// Forwards the packets of the upstream host like a regular client receives them
+async fn relay_task(
+    upstream: SocketAddr,
+    key: Option<PresharedKey>,
+    packets_out: Sender<UpdatePacket>,
+) {
+    loop {
+        let (upstream_tx, upstream_rx) = async_channel::bounded(100);
+        let (requests_tx, requests_rx) = async_channel::bounded(2);
+        for request in SourceStreams::ALL.requests() {
+            _ = requests_tx.try_send(request);
+        }
+
+        let forward = async {
+            while let Ok(packet) = upstream_rx.recv().await {
+                if packets_out.send(packet).await.is_err() {
+                    return true;
+                }
+            }
+            false
+        };
+        let connection = network_tasks::io_task(
+            upstream,
+            key.clone(),
+            upstream_tx,
+            requests_rx,
+            Arc::default(),
+        );
+        // The forwarding ends once the connection task has dropped its sender
+        let ((), stopped) = future::zip(connection, forward).await;
+        if stopped {
+            return;
+        }
+
+        warn!("Lost connection to relayed host {upstream}, reconnecting");
+        async_io::Timer::after(Duration::from_secs(1)).await;
+    }
+}
+
+/// Keeps the current websocket state up to date and forwards all packets to the clients
+async fn distribution_task(packets_in: Receiver<UpdatePacket>, state: Arc<Mutex<HostState>>) {
+    while let Ok(packet) = packets_in.recv().await {
+        let mut state = state.lock().unwrap();
+        match &packet {
+            UpdatePacket::FieldGeom(geom) => state.geometry = Some(*geom),
+            UpdatePacket::GameState(game_state) => state.game_state = Some(game_state.clone()),
+            UpdatePacket::VisMappings(mappings) => state.vis_mappings = Some(mappings.clone()),
+            UpdatePacket::WorldState(_)
+            | UpdatePacket::VisualizationUpdate(_)
+            | UpdatePacket::RobotTelemetry(_)
+            | UpdatePacket::DebugValues(_)
+            | UpdatePacket::StrategyPlans(_) => {}
+        }
+        // Slow clients just miss packets, like they would with a real host
+        state.clients.retain(|client| {
+            !matches!(
+                client.try_send(packet.clone()),
+                Err(TrySendError::Closed(_))
+            )
+        });
+    }
+    debug!("Packet source stopped");
+}
+
+async fn accept_task(listener: TcpListener, state: Arc<Mutex<HostState>>) {
+    loop {
+        match listener.accept().await {
+            Ok((stream, peer)) => {
+                debug!("New client {peer}");
+                let state = state.clone();
+                IoTaskPool::get()
+                    .spawn(async move {
+                        if let Err(e) = client_task(stream, peer, state).await {
+                            debug!("Client {peer} disconnected: {e}");
+                        }
+                    })
+                    .detach();
+            }
+            Err(e) => {
+                error!("Mock host failed to accept connection, stopping: {e}");
+                return;
+            }
+        }
+    }
+}
+
+async fn client_task(
+    stream: TcpStream,
+    peer: SocketAddr,
+    state: Arc<Mutex<HostState>>,
+) -> Result<(), tungstenite::Error> {
+    let websocket = async_tungstenite::accept_async(stream).await?;
+    let (mut ws_sender, mut ws_receiver) = websocket.split();
+
+    let udp_socket = if peer.is_ipv6() {
+        UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0)).await
+    } else {
+        UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await
+    }?;
+
+    let (packets_tx, packets_rx) = async_channel::bounded(100);
+    let key = {
+        let mut state = state.lock().unwrap();
+        state.clients.push(packets_tx);
+        state.key.clone()
+    };
+    let key = key.as_ref();
+
+    let mut ws_streams = HashSet::new();
+    let mut udp_streams = HashSet::new();
+    let mut udp_target = None;
+    let mut udp_compression = UdpCompression::Uncompressed;
+    // Only set if the client requested deltas
+    let mut world_state_encoder = None;
+    let mut vis_filter: Option<VisualizationFilter> = None;
+    let mut udp_sequence = 0u32;
+
+    enum ClientEvent {
+        Request(Option<Result<tungstenite::Message, tungstenite::Error>>),
+        Packet(UpdatePacket),
+        Ping,
+    }
+
+    let mut next_ping = Instant::now();
+    loop {
+        let event = async { ClientEvent::Request(ws_receiver.next().await) }
+            .or(async {
+                match packets_rx.recv().await {
+                    Ok(packet) => ClientEvent::Packet(packet),
+                    // The packet source stopped, so the connection is closed by the ws receiver returning None
+                    Err(_) => ClientEvent::Request(None),
+                }
+            })
+            .or(async {
+                async_io::Timer::at(next_ping).await;
+                ClientEvent::Ping
+            })
+            .await;
+
+        // Packets that have to be sent to the client over the websocket
+        let mut ws_out = Vec::new();
+
+        match event {
+            ClientEvent::Request(None) => return Ok(()),
+            ClientEvent::Request(Some(message)) => {
+                let tungstenite::Message::Binary(bytes) = message? else {
+                    continue;
+                };
+                let decoded = encryption::open(key, &bytes).and_then(|request| {
+                    WsRequest::decode(request.as_ref())
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                });
+                let request = match decoded {
+                    Ok(WsRequest {
+                        content: Some(request),
+                    }) => request,
+                    Ok(_) => continue,
+                    Err(e) => {
+                        warn!("Invalid request from {peer}: {e}");
+                        continue;
+                    }
+                };
+
+                match request {
+                    ws_request::Content::WsStreamReq(req) => {
+                        // New subscriptions always receive the current state once
+                        let state = state.lock().unwrap();
+                        for stream in req.stream() {
+                            ws_streams.insert(stream);
+                            let current = match stream {
+                                WsStream::FieldGeometry => {
+                                    state.geometry.map(UpdatePacket::FieldGeom)
+                                }
+                                WsStream::GameState => {
+                                    state.game_state.clone().map(UpdatePacket::GameState)
+                                }
+                                WsStream::VisMappings => {
+                                    state.vis_mappings.clone().map(UpdatePacket::VisMappings)
+                                }
+                            };
+                            ws_out.extend(current);
+                        }
+                    }
+                    ws_request::Content::UdpStreamReq(req) => {
+                        udp_streams = req.stream().collect();
+                        // Keeps the scope id of link-local peers, which is required to send to them
+                        let mut target = peer;
+                        target.set_port(req.port as u16);
+                        udp_target = Some(target);
+                        udp_compression = req.compression();
+                        world_state_encoder =
+                            req.world_state_deltas().then(WorldStateEncoder::default);
+                    }
+                    ws_request::Content::SetVisFilter(filter) => vis_filter = Some(filter),
+                    ws_request::Content::MoveRobot(command) => {
+                        debug!("Ignoring robot move command from {peer}: {command:?}");
+                    }
+                }
+            }
+            ClientEvent::Packet(packet) => {
+                let udp_packet = match packet {
+                    UpdatePacket::FieldGeom(_) if ws_streams.contains(&WsStream::FieldGeometry) => {
+                        ws_out.push(packet);
+                        None
+                    }
+                    UpdatePacket::GameState(_) if ws_streams.contains(&WsStream::GameState) => {
+                        ws_out.push(packet);
+                        None
+                    }
+                    UpdatePacket::VisMappings(_) if ws_streams.contains(&WsStream::VisMappings) => {
+                        ws_out.push(packet);
+                        None
+                    }
+                    UpdatePacket::WorldState(world_state)
+                        if udp_streams.contains(&UdpStream::WorldState) =>
+                    {
+                        Some(match &mut world_state_encoder {
+                            Some(encoder) => encoder.encode(world_state),
+                            None => udp_packet::Content::WorldState(world_state),
+                        })
+                    }
+                    UpdatePacket::VisualizationUpdate(mut vis_update)
+                        if udp_streams.contains(&UdpStream::Visualizations) =>
+                    {
+                        if let Some(filter) = &vis_filter {
+                            apply_vis_filter(&mut vis_update, filter);
+                        }
+                        Some(udp_packet::Content::VisUpdate(vis_update))
+                    }
+                    UpdatePacket::RobotTelemetry(telemetry)
+                        if udp_streams.contains(&UdpStream::RobotTelemetry) =>
+                    {
+                        Some(udp_packet::Content::RobotTelemetry(telemetry))
+                    }
+                    UpdatePacket::DebugValues(debug_values)
+                        if udp_streams.contains(&UdpStream::DebugValues) =>
+                    {
+                        Some(udp_packet::Content::DebugValues(debug_values))
+                    }
+                    UpdatePacket::StrategyPlans(plans)
+                        if udp_streams.contains(&UdpStream::Visualizations) =>
+                    {
+                        Some(udp_packet::Content::StrategyPlans(plans))
+                    }
+                    _ => None,
+                };
+
+                if let (Some(content), Some(target)) = (udp_packet, udp_target) {
+                    let packet = UdpPacket {
+                        content: Some(content),
+                        sequence: Some(udp_sequence),
+                    };
+                    udp_sequence = udp_sequence.wrapping_add(1);
+                    let datagram = encryption::seal(
+                        key,
+                        compression::compress(packet.encode_to_vec(), udp_compression),
+                    );
+                    udp_socket.send_to(&datagram, target).await?;
+                }
+            }
+            ClientEvent::Ping => {
+                // Clients time out if they don't receive anything for 1.5s
+                next_ping = Instant::now() + Duration::from_millis(500);
+                ws_sender
+                    .send(tungstenite::Message::Ping(Default::default()))
+                    .await?;
+            }
+        }
+
+        for packet in ws_out {
+            let content = match packet {
+                UpdatePacket::FieldGeom(inner) => ws_packet::Content::Geom(inner),
+                UpdatePacket::GameState(inner) => ws_packet::Content::GameState(inner),
+                UpdatePacket::VisMappings(inner) => ws_packet::Content::VisMappings(inner),
+                UpdatePacket::WorldState(_)
+                | UpdatePacket::VisualizationUpdate(_)
+                | UpdatePacket::RobotTelemetry(_)
+                | UpdatePacket::DebugValues(_)
+                | UpdatePacket::StrategyPlans(_) => continue,
+            };
+            let packet = WsPacket {
+                content: Some(content),
+            };
+            ws_sender
+                .send(tungstenite::Message::Binary(
+                    encryption::seal(key, packet.encode_to_vec()).into(),
+                ))
+                .await?;
+        }
+    }
+}
+
+/// Removes everything that isn't whitelisted by the filter, like real hosts do before sending
+fn apply_vis_filter(vis_update: &mut VisualizationUpdate, filter: &VisualizationFilter) {
+    vis_update.visualization_set.retain(|set| {
+        set.source
+            .is_none_or(|source| filter.allowed_vis_source.contains(&source))
+    });
+    for set in &mut vis_update.visualization_set {
+        set.visualization
+            .retain(|vis| filter.allowed_vis_id.contains(&vis.id));
+    }
+}
@@ -0,0 +1,200 @@
+//! Interfaces, multicast memberships and socket errors, to debug venue networks on devices without a shell.
+//! The network tasks run outside of the ECS and without access to it, so they write to a shared log
+//! that is copied into the [`NetworkDiagnostics`] resource regularly.
+
+use bevy::prelude::*;
+use bevy::tasks::futures_lite::future;
+use bevy::tasks::{IoTaskPool, Task, block_on};
+use net_ext::interface_flags::NetworkInterfaceFlagExtension;
+use network_interface::{NetworkInterface, NetworkInterfaceConfig};
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+
+/// Older errors are dropped, repeated errors only count once
+const MAX_ERRORS: usize = 50;
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+static SOCKET_LOG: Mutex<SocketLog> = Mutex::new(SocketLog {
+    memberships: Vec::new(),
+    errors: VecDeque::new(),
+});
+
+pub(crate) fn network_diagnostics_plugin(app: &mut App) {
+    app.init_resource::<NetworkDiagnostics>();
+    app.add_systems(Update, update_network_diagnostics);
+}
+
+#[derive(Debug)]
+struct SocketLog {
+    memberships: Vec<GroupMembership>,
+    errors: VecDeque<SocketError>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InterfaceInfo {
+    pub name: String,
+    pub index: u32,
+    pub addrs: Vec<IpAddr>,
+    pub up: bool,
+    pub multicast: bool,
+}
+
+/// A multicast group a network task joined (or tried to join) on an interface
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GroupMembership {
+    /// The network task that owns the socket, e.g. the host discovery
+    pub task: &'static str,
+    pub interface: String,
+    pub group: IpAddr,
+    /// Set if joining failed
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SocketError {
+    /// When the error was last recorded
+    pub time: SystemTime,
+    pub task: &'static str,
+    pub message: String,
+    /// How often the same error was recorded again, e.g. by a task that retries a failing bind
+    pub repeats: u32,
+}
+
+impl Display for SocketError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.task, self.message)?;
+        if self.repeats > 0 {
+            write!(f, " (×{})", self.repeats + 1)?;
+        }
+        Ok(())
+    }
+}
+
+/// Snapshot of the network state, refreshed every second.
+/// Fields receive their packets over unicast, so only the discovery and snapshot tasks join groups.
+#[derive(Resource, Debug, Default, Clone, PartialEq, Eq)]
+pub struct NetworkDiagnostics {
+    pub interfaces: Vec<InterfaceInfo>,
+    pub memberships: Vec<GroupMembership>,
+    /// Oldest first
+    pub errors: Vec<SocketError>,
+}
+
+/// Records the result of a multicast join, replacing the last result for the same group and interface
+pub(crate) fn record_membership(
+    task: &'static str,
+    interface: &NetworkInterface,
+    group: IpAddr,
+    result: &std::io::Result<()>,
+) {
+    let membership = GroupMembership {
+        task,
+        interface: interface.name.clone(),
+        group,
+        error: result.as_ref().err().map(|e| e.to_string()),
+    };
+    if let Err(e) = result {
+        record_error(
+            task,
+            format_args!("Failed to join {group} on {}: {e}", interface.name),
+        );
+    }
+    let mut log = SOCKET_LOG.lock().unwrap();
+    log.memberships
+        .retain(|m| !(m.task == task && m.interface == membership.interface && m.group == group));
+    log.memberships.push(membership);
+}
+
+/// Forgets the memberships on interfaces that are gone, they are left by the OS
+pub(crate) fn retain_memberships(task: &'static str, interfaces: &[NetworkInterface]) {
+    SOCKET_LOG
+        .lock()
+        .unwrap()
+        .memberships
+        .retain(|m| m.task != task || interfaces.iter().any(|i| i.name == m.interface));
+}
+
+/// Adds an error to the log, in addition to the usual log message.
+/// An error that is already in the log is moved to the end and counted instead, so it doesn't push out the others.
+pub(crate) fn record_error(task: &'static str, message: impl Display) {
+    let message = message.to_string();
+    let mut log = SOCKET_LOG.lock().unwrap();
+    let repeated = log
+        .errors
+        .iter()
+        .position(|error| error.task == task && error.message == message)
+        .and_then(|i| log.errors.remove(i));
+    let repeats = repeated.map_or(0, |error| error.repeats + 1);
+    if log.errors.len() >= MAX_ERRORS {
+        log.errors.pop_front();
+    }
+    log.errors.push_back(SocketError {
+        time: SystemTime::now(),
+        task,
+        message,
+        repeats,
+    });
+}
+
+/// Lists the interfaces, None if that failed
+fn list_interfaces() -> Option<Vec<InterfaceInfo>> {
+    let interfaces = NetworkInterface::show().ok()?;
+    Some(
+        interfaces
+            .into_iter()
+            .map(|i| InterfaceInfo {
+                up: i.is_up(),
+                multicast: i.is_multicast(),
+                addrs: i.addr.iter().map(|a| a.ip()).collect(),
+                name: i.name,
+                index: i.index,
+            })
+            .collect(),
+    )
+}
+
+fn update_network_diagnostics(
+    mut last_update: Local<Option<Instant>>,
+    // Listing the interfaces takes a few syscalls per interface, so it doesn't run on the main thread
+    mut interface_task: Local<Option<Task<Option<Vec<InterfaceInfo>>>>>,
+    mut diagnostics: ResMut<NetworkDiagnostics>,
+) {
+    let listed = interface_task
+        .as_mut()
+        .and_then(|task| block_on(future::poll_once(task)));
+    if listed.is_some() {
+        *interface_task = None;
+    }
+
+    let now = Instant::now();
+    let refresh = last_update.is_none_or(|last| now - last >= REFRESH_INTERVAL);
+    if refresh {
+        *last_update = Some(now);
+        if interface_task.is_none() {
+            *interface_task = Some(IoTaskPool::get().spawn(async { list_interfaces() }));
+        }
+    } else if listed.is_none() {
+        return;
+    }
+
+    // Listing fails rarely, the last list is more useful than none
+    let interfaces = listed
+        .flatten()
+        .unwrap_or_else(|| diagnostics.interfaces.clone());
+    let (memberships, errors) = {
+        let log = SOCKET_LOG.lock().unwrap();
+        (
+            log.memberships.clone(),
+            log.errors.iter().cloned().collect(),
+        )
+    };
+
+    diagnostics.set_if_neq(NetworkDiagnostics {
+        interfaces,
+        memberships,
+        errors,
+    });
+}
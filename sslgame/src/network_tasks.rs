@@ -1,43 +1,289 @@
+use crate::compression;
+use crate::encryption::{self, PresharedKey};
+use crate::mdns;
+use crate::network_diagnostics::{record_error, record_membership, retain_memberships};
 use crate::proto::remote::*;
+use crate::services::{BEACON_ADDR_V4, BEACON_ADDR_V6, ServiceKind};
+use crate::sharing::{MAX_SNAPSHOT_SIZE, SharedSnapshot};
+use crate::update_packet::{PacketOutbox, UpdatePacket};
+use crate::world_state_delta::WorldStateDecoder;
 use async_channel::{Receiver, Sender, TrySendError};
 use async_net::UdpSocket;
 use async_tungstenite::tungstenite;
 use bevy::prelude::*;
-use bevy::tasks::futures_lite::{FutureExt, StreamExt, stream};
+use bevy::tasks::IoTaskPool;
+use bevy::tasks::futures_lite::{FutureExt, StreamExt, future, stream};
 use bytes::BytesMut;
 use net_ext::interface_flags::NetworkInterfaceFlagExtension;
 use net_ext::ssm_socket::SSMSocketExtension;
 use network_interface::{NetworkInterface, NetworkInterfaceConfig};
 use prost::Message;
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
+use std::collections::{HashMap, HashSet};
 use std::io;
-use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV6};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::time::{Duration, Instant};
 
 // TODO: Leave multicast groups before stopping
 
-const BEACON_ADDR_V4: SocketAddrV4 = SocketAddrV4::new(Ipv4Addr::new(239, 1, 1, 1), 11000);
-const BEACON_ADDR_V6: SocketAddrV6 = SocketAddrV6::new(
-    Ipv6Addr::from_bits(0xFF15_0000_0000_0045_5246_6F72_6365_0001), // "ERForce" in hex
-    11000,
-    0,
-    0,
-);
-
-#[derive(PartialEq, Eq, Hash)]
-enum HostKey {
-    Addr(SocketAddr),
-    Id(u32),
+// Names of the network tasks in the network diagnostics
+const DISCOVERY_TASK: &str = "Host discovery";
+const SNAPSHOT_TASK: &str = "Snapshots";
+const CONNECTION_TASK: &str = "Field connection";
+
+/// Joins the multicast groups on all new interfaces, with ipv4 if the interface has an ipv4 address and ipv6 otherwise.
+/// Without a socket for one of the address families, the other one is used wherever the interface supports it.
+fn update_multicast_subscriptions(
+    task: &'static str,
+    socket_v4: Option<&UdpSocket>,
+    socket_v6: Option<&UdpSocket>,
+    group_v4: Ipv4Addr,
+    group_v6: Ipv6Addr,
+    active_interfaces: &mut Vec<NetworkInterface>,
+) {
+    match NetworkInterface::show() {
+        Ok(if_list) => {
+            // Get all relevant interfaces
+            let filtered_if_list: Vec<_> = if_list
+                .into_iter()
+                .filter(|new_if| new_if.is_multicast() && new_if.is_up())
+                .collect();
+
+            // Subscribe on new interfaces
+            filtered_if_list
+                .iter()
+                .filter(|i| {
+                    !active_interfaces
+                        .iter()
+                        .any(|active| active.index == i.index)
+                })
+                .for_each(|new_if| {
+                    let addr_v4 = new_if.addr.iter().find_map(|a| match a {
+                        network_interface::Addr::V4(addr) => Some(addr.ip),
+                        network_interface::Addr::V6(_) => None,
+                    });
+                    if let (Some(socket_v4), Some(addr_v4)) = (socket_v4, addr_v4) {
+                        let result = socket_v4.join_multicast_v4(group_v4, addr_v4);
+                        record_membership(task, new_if, group_v4.into(), &result);
+                    } else if let Some(socket_v6) = socket_v6
+                        && new_if.addr.iter().any(|a| a.ip().is_ipv6())
+                    {
+                        let result = socket_v6.join_multicast_v6(&group_v6, new_if.index);
+                        record_membership(task, new_if, group_v6.into(), &result);
+                    }
+                });
+
+            retain_memberships(task, &filtered_if_list);
+            *active_interfaces = filtered_if_list;
+        }
+        Err(e) => {
+            error!("Failed to get network interface list, skipping interface update: {e}");
+            record_error(
+                task,
+                format_args!("Failed to get network interface list: {e}"),
+            );
+        }
+    }
 }
 
-pub async fn host_discovery_task(hosts_out: Sender<Vec<(SocketAddr, HostAdvertisement)>>) {
-    let socket_v4 = UdpSocket::bind_multicast((Ipv4Addr::UNSPECIFIED, BEACON_ADDR_V4.port()))
-        .expect("Failed to bind ipv4 discovery socket");
-    let socket_v6 = UdpSocket::bind_multicast((Ipv6Addr::UNSPECIFIED, BEACON_ADDR_V6.port()))
-        .expect("Failed to bind ipv6 discovery socket");
+/// The interface whose subnet contains the address. Link-local ipv6 addresses carry their interface as scope id.
+fn interface_of(addr: SocketAddr, interfaces: &[NetworkInterface]) -> Option<&NetworkInterface> {
+    if let SocketAddr::V6(addr) = addr
+        && addr.scope_id() != 0
+    {
+        return interfaces.iter().find(|i| i.index == addr.scope_id());
+    }
+    interfaces.iter().find(|interface| {
+        interface
+            .addr
+            .iter()
+            .any(|if_addr| match (if_addr, addr.ip()) {
+                (network_interface::Addr::V4(if_addr), IpAddr::V4(ip)) => {
+                    let mask = if_addr.netmask.map_or(u32::MAX, u32::from);
+                    u32::from(if_addr.ip) & mask == u32::from(ip) & mask
+                }
+                (network_interface::Addr::V6(if_addr), IpAddr::V6(ip)) => {
+                    let mask = if_addr.netmask.map_or(u128::MAX, u128::from);
+                    u128::from(if_addr.ip) & mask == u128::from(ip) & mask
+                }
+                _ => false,
+            })
+    })
+}
+
+/// Scope ids of link-local addresses are only valid for sockets, urls can't contain them
+fn websocket_url(host: SocketAddr) -> String {
+    match host {
+        SocketAddr::V4(addr) => format!("ws://{addr}"),
+        SocketAddr::V6(addr) => format!("ws://[{}]:{}", addr.ip(), addr.port()),
+    }
+}
+
+/// Parses a socket address like [`SocketAddr::from_str`], but link-local ipv6 addresses can also name their interface,
+/// e.g. `[fe80::1%eth0]:10000`. The interface is replaced by its index, since only that can be passed to sockets.
+pub fn parse_host_addr(s: &str) -> Result<SocketAddr, String> {
+    if let Ok(addr) = s.parse() {
+        return Ok(addr);
+    }
+    let invalid = || format!("invalid socket address: {s}");
+    let (ip, rest) = s
+        .strip_prefix('[')
+        .and_then(|s| s.split_once('%'))
+        .ok_or_else(invalid)?;
+    let (interface, port) = rest.split_once("]:").ok_or_else(invalid)?;
+    let ip: Ipv6Addr = ip.parse().map_err(|_| invalid())?;
+    let port: u16 = port.parse().map_err(|_| invalid())?;
+
+    let interfaces = NetworkInterface::show().map_err(|e| e.to_string())?;
+    let scope_id = interfaces
+        .iter()
+        .find(|i| i.name == interface)
+        .ok_or_else(|| format!("unknown network interface: {interface}"))?
+        .index;
+    Ok(SocketAddrV6::new(ip, port, 0, scope_id).into())
+}
+
+/// A host advertisement received from one address.
+/// Hosts that are visible on several interfaces are reported once per address, see [`crate::InterfacePreference`].
+#[derive(Debug, Clone)]
+pub struct DiscoveredHost {
+    pub addr: SocketAddr,
+    /// Index and name of the interface the advertisement was received on, if it could be determined
+    pub interface: Option<(u32, String)>,
+    pub advertisement: HostAdvertisement,
+}
+
+/// Which discovery mechanism a received packet belongs to
+#[derive(Debug, Clone, Copy)]
+enum DiscoveryPacket {
+    Beacon,
+    MdnsResponse,
+}
+
+/// The sockets of the host discovery, each of them is optional
+struct DiscoverySockets {
+    beacon_v4: Option<UdpSocket>,
+    beacon_v6: Option<UdpSocket>,
+    mdns_v4: Option<UdpSocket>,
+    mdns_v6: Option<UdpSocket>,
+}
+
+impl DiscoverySockets {
+    async fn bind() -> Self {
+        // Machines with one of the address families disabled can still discover hosts using the other one
+        let beacon_v4 = UdpSocket::bind_multicast((Ipv4Addr::UNSPECIFIED, BEACON_ADDR_V4.port()))
+            .inspect_err(|e| {
+                warn!("Failed to bind ipv4 discovery socket, only using ipv6: {e}");
+                record_error(
+                    DISCOVERY_TASK,
+                    format_args!("Failed to bind ipv4 socket: {e}"),
+                );
+            })
+            .ok();
+        let beacon_v6 = UdpSocket::bind_multicast((Ipv6Addr::UNSPECIFIED, BEACON_ADDR_V6.port()))
+            .inspect_err(|e| {
+                warn!("Failed to bind ipv6 discovery socket, only using ipv4: {e}");
+                record_error(
+                    DISCOVERY_TASK,
+                    format_args!("Failed to bind ipv6 socket: {e}"),
+                );
+            })
+            .ok();
+        // DNS-SD queries are sent from ephemeral ports, so the responses are unicast to us and
+        // don't need the mDNS port, which is usually taken by the system's responder
+        let mdns_v4 = UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0))
+            .await
+            .inspect_err(|e| warn!("Failed to bind ipv4 mDNS socket: {e}"))
+            .ok();
+        let mdns_v6 = UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0))
+            .await
+            .inspect_err(|e| warn!("Failed to bind ipv6 mDNS socket: {e}"))
+            .ok();
+        Self {
+            beacon_v4,
+            beacon_v6,
+            mdns_v4,
+            mdns_v6,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.beacon_v4.is_none()
+            && self.beacon_v6.is_none()
+            && self.mdns_v4.is_none()
+            && self.mdns_v6.is_none()
+    }
+}
+
+/// Sends a DNS-SD query to the mDNS groups on all interfaces
+async fn query_services(
+    service_browser: &mdns::ServiceBrowser,
+    mdns_v4: Option<&UdpSocket>,
+    mdns_v6: Option<&UdpSocket>,
+    active_interfaces: &[NetworkInterface],
+) {
+    let query = service_browser.query();
+    if let Some(mdns_v4) = mdns_v4 {
+        _ = mdns_v4.send_to(&query, mdns::MDNS_GROUP_V4).await;
+    }
+    if let Some(mdns_v6) = mdns_v6 {
+        // The link-local group has to be sent to on every interface separately
+        for interface in active_interfaces
+            .iter()
+            .filter(|i| i.addr.iter().any(|a| a.ip().is_ipv6()))
+        {
+            let group = SocketAddrV6::new(mdns::MDNS_GROUP_V6, mdns::MDNS_PORT, 0, interface.index);
+            _ = mdns_v6.send_to(&query, group).await;
+        }
+    }
+}
+
+/// The first retry after no discovery socket could be bound, doubled on every failed retry
+const BIND_RETRY_MIN: Duration = Duration::from_secs(1);
+const BIND_RETRY_MAX: Duration = Duration::from_secs(60);
+
+/// Listens for the beacons of hosts on both ipv4 and ipv6, so hosts on single-stack networks are found as well.
+/// Hosts that are received over both are merged by their instance id, see [`crate::InterfacePreference`].
+/// Hosts that are advertised as DNS-SD services (e.g. by avahi) are browsed for as well,
+/// which also works across subnets with an mDNS reflector.
+pub async fn host_discovery_task(hosts_out: Sender<Vec<DiscoveredHost>>) {
+    // Without any socket, e.g. while the network is still coming up, the binds are retried instead of stopping.
+    // A stopped task would be restarted right away and retry every frame.
+    let mut bind_retry = BIND_RETRY_MIN;
+    let DiscoverySockets {
+        beacon_v4: socket_v4,
+        beacon_v6: socket_v6,
+        mdns_v4,
+        mdns_v6,
+    } = loop {
+        let sockets = DiscoverySockets::bind().await;
+        if !sockets.is_empty() {
+            break sockets;
+        }
+        error!(
+            "No host discovery socket could be bound, retrying in {}s",
+            bind_retry.as_secs()
+        );
+        record_error(DISCOVERY_TASK, "No socket could be bound, retrying");
+        async_io::Timer::after(bind_retry).await;
+        if hosts_out.is_closed() {
+            info!("Host discovery channel dropped, stopping discovery task");
+            return;
+        }
+        bind_retry = (bind_retry * 2).min(BIND_RETRY_MAX);
+    };
 
-    let mut host_map: HashMap<_, (_, _, _)> = HashMap::new();
+    let mut host_map: HashMap<SocketAddr, (Instant, DiscoveredHost)> = HashMap::new();
+    // Looked up names of hosts that don't advertise one, None if the lookup failed.
+    // Failed lookups aren't repeated, so hosts without a responder don't cause a query every second.
+    let mut hostnames: HashMap<IpAddr, Option<String>> = HashMap::new();
+    let mut pending_lookups: HashSet<IpAddr> = HashSet::new();
+    let (lookups_tx, lookups_rx) = async_channel::unbounded();
+    let mut service_browser = mdns::ServiceBrowser::default();
+    // Continuous queries are sent at increasing intervals (RFC 6762 section 5.2), starting over with new interfaces
+    let mut query_interval = mdns::MIN_QUERY_INTERVAL;
+    let mut next_query = Instant::now();
 
     // Forward discovery packets and check for new network interfaces every 3 seconds
     let mut active_interfaces = Vec::new();
@@ -47,64 +293,78 @@ pub async fn host_discovery_task(hosts_out: Sender<Vec<(SocketAddr, HostAdvertis
         // Forget old hosts
         {
             let cutoff = Instant::now() - Duration::from_secs(3);
-            host_map.retain(|_, (t, _, _)| *t > cutoff);
+            host_map.retain(|_, (t, _)| *t > cutoff);
         }
 
         // ======== Update multicast subscriptions ========
 
-        match NetworkInterface::show() {
-            Ok(if_list) => {
-                // Get all relevant interfaces
-                let filtered_if_list: Vec<_> = if_list
-                    .into_iter()
-                    .filter(|new_if| new_if.is_multicast() && new_if.is_up())
-                    .collect();
-
-                // Subscribe on new interfaces
-                filtered_if_list
-                    .iter()
-                    .filter(|i| !active_interfaces.contains(&i.index))
-                    .for_each(|new_if| {
-                        if let Some(network_interface::Addr::V4(addr)) =
-                            new_if.addr.iter().find(|a| a.ip().is_ipv4())
-                        {
-                            _ = socket_v4.join_multicast_v4(*BEACON_ADDR_V4.ip(), addr.ip);
-                        } else if new_if.addr.iter().any(|a| a.ip().is_ipv6()) {
-                            _ = socket_v6.join_multicast_v6(BEACON_ADDR_V6.ip(), new_if.index);
-                        }
-                    });
+        let old_interfaces: Vec<_> = active_interfaces.iter().map(|i| i.index).collect();
+        update_multicast_subscriptions(
+            DISCOVERY_TASK,
+            socket_v4.as_ref(),
+            socket_v6.as_ref(),
+            *BEACON_ADDR_V4.ip(),
+            *BEACON_ADDR_V6.ip(),
+            &mut active_interfaces,
+        );
+        if active_interfaces.iter().map(|i| i.index).ne(old_interfaces) {
+            query_interval = mdns::MIN_QUERY_INTERVAL;
+            next_query = Instant::now();
+        }
 
-                active_interfaces = filtered_if_list.into_iter().map(|i| i.index).collect();
-            }
-            Err(e) => {
-                error!("Failed to get network interface list, skipping interface update: {e}");
-            }
+        // ======== Browse DNS-SD services ========
+
+        if Instant::now() >= next_query {
+            next_query = Instant::now() + query_interval;
+            query_interval = (query_interval * 2).min(mdns::MAX_QUERY_INTERVAL);
+            query_services(
+                &service_browser,
+                mdns_v4.as_ref(),
+                mdns_v6.as_ref(),
+                &active_interfaces,
+            )
+            .await;
         }
 
         // ======== Merge packet streams ========
 
         fn make_packet_stream(
-            socket: &UdpSocket,
-        ) -> impl stream::Stream<Item = io::Result<(usize, SocketAddr, [u8; 256])>> + '_ {
+            socket: Option<&UdpSocket>,
+            kind: DiscoveryPacket,
+        ) -> impl stream::Stream<Item = io::Result<(DiscoveryPacket, usize, SocketAddr, [u8; 1500])>> + '_
+        {
             // Hack to generate a packet stream from an udp socket. The socket is passed along as state.
-            stream::unfold(socket, async |socket| {
-                let mut rx_buf = [0u8; 256]; // Discovery packets are very small
+            stream::unfold(socket, async move |socket| {
+                // Without a socket the stream never yields, so the other one and the timeout still work
+                let socket = match socket {
+                    Some(socket) => socket,
+                    None => std::future::pending().await,
+                };
+                // Beacons are very small, mDNS responses are limited to the usual MTU
+                let mut rx_buf = [0u8; 1500];
                 let result = socket
                     .recv_from(&mut rx_buf)
                     .await
-                    .map(|(size, source_addr)| (size, source_addr, rx_buf));
-                Some((result, socket))
+                    .map(|(size, source_addr)| (kind, size, source_addr, rx_buf));
+                Some((result, Some(socket)))
             })
         }
 
-        let stream_v4 = make_packet_stream(&socket_v4);
-        let stream_v6 = make_packet_stream(&socket_v6);
+        let stream_v4 = make_packet_stream(socket_v4.as_ref(), DiscoveryPacket::Beacon);
+        let stream_v6 = make_packet_stream(socket_v6.as_ref(), DiscoveryPacket::Beacon);
+        let stream_mdns_v4 = make_packet_stream(mdns_v4.as_ref(), DiscoveryPacket::MdnsResponse);
+        let stream_mdns_v6 = make_packet_stream(mdns_v6.as_ref(), DiscoveryPacket::MdnsResponse);
         let stream_timeout = stream::once_future(async {
             async_io::Timer::at(next_interface_refresh).await;
             Err(io::ErrorKind::TimedOut.into())
         });
 
-        let mut merged_stream = stream_v4.or(stream_v6).or(stream_timeout).boxed();
+        let mut merged_stream = stream_v4
+            .or(stream_v6)
+            .or(stream_mdns_v4)
+            .or(stream_mdns_v6)
+            .or(stream_timeout)
+            .boxed();
 
         // ======== Collect packets from the merged stream until the timeout ========
 
@@ -114,8 +374,15 @@ pub async fn host_discovery_task(hosts_out: Sender<Vec<(SocketAddr, HostAdvertis
                 .await
                 .expect("The host discovery stream should never yield None")
             {
-                Ok((size, source_addr, rx_buf)) => {
-                    let new_host = match HostAdvertisement::decode(&rx_buf[..size]) {
+                Ok((DiscoveryPacket::MdnsResponse, size, source_addr, rx_buf)) => {
+                    let Some(records) = mdns::parse_response(&rx_buf[..size]) else {
+                        warn!("Invalid mDNS response received from {source_addr}");
+                        continue;
+                    };
+                    service_browser.add_records(records, source_addr);
+                }
+                Ok((DiscoveryPacket::Beacon, size, source_addr, rx_buf)) => {
+                    let mut new_host = match HostAdvertisement::decode(&rx_buf[..size]) {
                         Ok(host) => {
                             debug!("Received host advertisement from {source_addr}");
                             host
@@ -126,39 +393,91 @@ pub async fn host_discovery_task(hosts_out: Sender<Vec<(SocketAddr, HostAdvertis
                         }
                     };
 
-                    if let Some(instance_id) = new_host.instance_id {
-                        match host_map.entry(HostKey::Id(instance_id)) {
-                            Entry::Occupied(mut entry) => entry.get_mut().0 = Instant::now(),
-                            Entry::Vacant(entry) => {
-                                entry.insert((Instant::now(), source_addr, new_host));
-                            }
-                        }
-                    } else {
-                        match host_map.entry(HostKey::Addr(source_addr)) {
-                            Entry::Occupied(mut entry) => entry.get_mut().0 = Instant::now(),
-                            Entry::Vacant(entry) => {
-                                entry.insert((Instant::now(), source_addr, new_host));
+                    // ======== Hostname lookup ========
+
+                    while let Ok((ip, hostname)) = lookups_rx.try_recv() {
+                        pending_lookups.remove(&ip);
+                        hostnames.insert(ip, hostname);
+                    }
+                    // The host is reported right away and gets its name once the lookup is done.
+                    // Fields are matched by address, so the name doesn't respawn them.
+                    if new_host.hostname.is_none() {
+                        match hostnames.get(&source_addr.ip()) {
+                            Some(hostname) => new_host.hostname = hostname.clone(),
+                            None if pending_lookups.contains(&source_addr.ip()) => {}
+                            None => {
+                                pending_lookups.insert(source_addr.ip());
+                                let lookups_tx = lookups_tx.clone();
+                                IoTaskPool::get()
+                                    .spawn(async move {
+                                        let hostname = mdns::lookup_hostname(source_addr).await;
+                                        if let Some(hostname) = &hostname {
+                                            debug!("Host {source_addr} is named {hostname}");
+                                        }
+                                        _ = lookups_tx.send((source_addr.ip(), hostname)).await;
+                                    })
+                                    .detach();
                             }
                         }
                     }
 
-                    let host_list: Vec<_> = host_map
-                        .iter()
-                        .map(|(_, (_, a, h))| (*a, h.clone()))
-                        .collect();
-
-                    match hosts_out.try_send(host_list) {
-                        Ok(_) => {}
-                        Err(TrySendError::Full(_)) => warn!("Host discovery channel full"),
-                        Err(TrySendError::Closed(_)) => {
-                            info!("Host discovery channel dropped, stopping discovery task");
-                            return;
-                        }
-                    }
+                    let interface = interface_of(source_addr, &active_interfaces)
+                        .map(|interface| (interface.index, interface.name.clone()));
+                    host_map.insert(
+                        source_addr,
+                        (
+                            Instant::now(),
+                            DiscoveredHost {
+                                addr: source_addr,
+                                interface,
+                                advertisement: new_host,
+                            },
+                        ),
+                    );
                 }
                 Err(e) if e.kind() == io::ErrorKind::TimedOut => break,
                 Err(e) => {
                     error!("Host discovery network error, stopping discovery task: {e}");
+                    record_error(
+                        DISCOVERY_TASK,
+                        format_args!("Stopped after network error: {e}"),
+                    );
+                    return;
+                }
+            }
+
+            // ======== Publish hosts ========
+
+            let mut host_list: Vec<_> = host_map.values().map(|(_, host)| host.clone()).collect();
+            for service in service_browser.hosts() {
+                // Hosts that also send beacons are already known with their full advertisement
+                let has_beacon = host_list.iter().any(|host| {
+                    host.addr.ip() == service.addr.ip()
+                        && host.advertisement.websocket_port as u16 == service.addr.port()
+                });
+                if has_beacon {
+                    continue;
+                }
+                let interface = interface_of(service.addr, &active_interfaces)
+                    .map(|interface| (interface.index, interface.name.clone()));
+                host_list.push(DiscoveredHost {
+                    addr: service.addr,
+                    interface,
+                    advertisement: HostAdvertisement {
+                        websocket_port: service.addr.port().into(),
+                        hostname: Some(service.hostname),
+                        instance_id: service.instance_id,
+                        encrypted: service.encrypted.then_some(true),
+                        ..default()
+                    },
+                });
+            }
+
+            match hosts_out.try_send(host_list) {
+                Ok(_) => {}
+                Err(TrySendError::Full(_)) => warn!("Host discovery channel full"),
+                Err(TrySendError::Closed(_)) => {
+                    info!("Host discovery channel dropped, stopping discovery task");
                     return;
                 }
             }
@@ -166,49 +485,113 @@ pub async fn host_discovery_task(hosts_out: Sender<Vec<(SocketAddr, HostAdvertis
     }
 }
 
-/// Combination of the WsPacket and UdpPacket protobuf messages
-pub enum UpdatePacket {
-    FieldGeom(FieldGeometry),
-    GameState(GameState),
-    VisMappings(VisMappings),
-    WorldState(WorldState),
-    VisualizationUpdate(VisualizationUpdate),
+/// The most udp packets that are held back while waiting for a missing earlier packet
+const REORDER_CAPACITY: usize = 8;
+/// How long a missing udp packet is waited for, short compared to the world state filter's buffer
+const REORDER_DELAY: Duration = Duration::from_millis(20);
+/// Packets that are further behind are from a restarted host instead of reordered
+const REORDER_RESET: i32 = 1024;
+
+/// Restores the order of udp packets by their sequence number, so that mildly reordered delivery over wifi
+/// doesn't look like time going backwards to the world state filter.
+/// Packets that arrive after a later one was delivered are dropped, missing packets are skipped after [`REORDER_DELAY`].
+#[derive(Default)]
+struct ReorderBuffer {
+    next: Option<u32>,
+    /// Held back packets, ordered by sequence number
+    held: Vec<(u32, Instant, udp_packet::Content)>,
 }
 
-impl From<ws_packet::Content> for UpdatePacket {
-    fn from(packet: ws_packet::Content) -> Self {
-        match packet {
-            ws_packet::Content::Geom(inner) => Self::FieldGeom(inner),
-            ws_packet::Content::GameState(inner) => Self::GameState(inner),
-            ws_packet::Content::VisMappings(inner) => Self::VisMappings(inner),
+impl ReorderBuffer {
+    /// Returns the packets that can be delivered now, in order
+    fn push(
+        &mut self,
+        sequence: Option<u32>,
+        packet: udp_packet::Content,
+        now: Instant,
+    ) -> Vec<udp_packet::Content> {
+        let (Some(sequence), Some(next)) = (sequence, self.next) else {
+            // First packet, or a host without sequence numbers
+            if let Some(sequence) = sequence {
+                self.next = Some(sequence.wrapping_add(1));
+            }
+            return vec![packet];
+        };
+
+        let offset = sequence.wrapping_sub(next) as i32;
+        if offset < -REORDER_RESET {
+            let mut packets: Vec<_> = self.held.drain(..).map(|(_, _, packet)| packet).collect();
+            packets.push(packet);
+            self.next = Some(sequence.wrapping_add(1));
+            packets
+        } else if offset < 0 {
+            debug!("Dropped late udp packet {sequence}, expected {next}");
+            Vec::new()
+        } else {
+            let index = self
+                .held
+                .partition_point(|(held, _, _)| (held.wrapping_sub(next) as i32) < offset);
+            if self
+                .held
+                .get(index)
+                .is_some_and(|(held, _, _)| *held == sequence)
+            {
+                return Vec::new(); // Duplicate
+            }
+            self.held.insert(index, (sequence, now, packet));
+            self.release(now)
         }
     }
-}
 
-impl From<udp_packet::Content> for UpdatePacket {
-    fn from(packet: udp_packet::Content) -> Self {
-        match packet {
-            udp_packet::Content::WorldState(inner) => Self::WorldState(inner),
-            udp_packet::Content::VisUpdate(inner) => Self::VisualizationUpdate(inner),
+    /// Delivers the held packets that are next in sequence, and skips missing packets that were waited for long enough
+    fn release(&mut self, now: Instant) -> Vec<udp_packet::Content> {
+        let mut packets = Vec::new();
+        while let (Some(next), Some((sequence, _, _))) = (self.next, self.held.first()) {
+            if *sequence != next
+                && self.held.len() <= REORDER_CAPACITY
+                && self.deadline().is_some_and(|deadline| now < deadline)
+            {
+                break;
+            }
+            if *sequence != next {
+                debug!(
+                    "Skipped missing udp packets {next} to {}",
+                    sequence.wrapping_sub(1)
+                );
+            }
+            let (sequence, _, packet) = self.held.remove(0);
+            self.next = Some(sequence.wrapping_add(1));
+            packets.push(packet);
         }
+        packets
+    }
+
+    fn deadline(&self) -> Option<Instant> {
+        self.held
+            .iter()
+            .map(|(_, received, _)| *received + REORDER_DELAY)
+            .min()
     }
 }
 
-#[tracing::instrument(skip(packets_out, requests_in))]
+#[tracing::instrument(skip(key, packets_out, requests_in, decode_errors))]
 pub async fn io_task(
     host: SocketAddr,
+    key: Option<PresharedKey>,
     packets_out: Sender<UpdatePacket>,
     requests_in: Receiver<ws_request::Content>,
+    decode_errors: Arc<AtomicU32>,
 ) {
     // ======== Socket setup ========
 
+    let key = key.as_ref();
     let mut udp_rx_buf = [0u8; 65535]; // Max size of an udp datagram
 
     // Start websocket connection
     let tcp_stream = async_net::TcpStream::connect(host)
         .await
         .unwrap_or_else(|_| panic!("Failed tcp connection to {host}"));
-    let (websocket, _) = async_tungstenite::client_async(format!("ws://{host}"), tcp_stream)
+    let (websocket, _) = async_tungstenite::client_async(websocket_url(host), tcp_stream)
         .await
         .unwrap_or_else(|_| panic!("Failed websocket connection to {host}"));
     let (mut ws_sender, ws_receiver) = websocket.split();
@@ -227,7 +610,7 @@ pub async fn io_task(
     enum StreamEvent {
         WsRequest(ws_request::Content),
         WsPacket(ws_packet::Content),
-        UdpPacket(udp_packet::Content),
+        UdpPacket(Option<u32>, udp_packet::Content),
         None,
     }
 
@@ -236,7 +619,6 @@ pub async fn io_task(
     enum RxError {
         Tungstenite(tungstenite::Error),
         Io(io::Error),
-        Decode(prost::DecodeError),
     }
 
     let ws_mapped = ws_receiver.map(|msg| {
@@ -248,12 +630,22 @@ pub async fn io_task(
                 Ok(StreamEvent::None)
             }
             tungstenite::Message::Binary(bytes) => {
-                let packet = WsPacket::decode(bytes).map_err(RxError::Decode)?;
-                if let Some(packet_content) = packet.content {
-                    Ok(StreamEvent::WsPacket(packet_content))
-                } else {
-                    debug!("Received empty oneof protobuf field");
-                    Ok(StreamEvent::None)
+                let _span = info_span!("decode_ws_packet", size = bytes.len()).entered();
+                let decoded = encryption::open(key, &bytes).and_then(|packet| {
+                    WsPacket::decode(packet.as_ref()).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                });
+                match decoded {
+                    Ok(WsPacket { content: Some(packet_content) }) => Ok(StreamEvent::WsPacket(packet_content)),
+                    Ok(_) => {
+                        debug!("Received empty oneof protobuf field");
+                        Ok(StreamEvent::None)
+                    }
+                    Err(e) => {
+                        // A single corrupt packet should not kill the whole connection
+                        debug!("Failed to decode websocket packet: {e}");
+                        decode_errors.fetch_add(1, Ordering::Relaxed);
+                        Ok(StreamEvent::None)
+                    }
                 }
             }
             tungstenite::Message::Ping(_) => {
@@ -276,18 +668,33 @@ pub async fn io_task(
     }).filter(|r| r.is_err() || r.as_ref().is_ok_and(|e| !matches!(e, StreamEvent::None)));
 
     // Hack to generate a packet stream from an udp socket. The socket is passed along as state.
+    let udp_decode_errors: &AtomicU32 = &decode_errors;
     let udp_mapped = stream::unfold(&udp_socket, |sock| async move {
         let result = sock
             .recv_from(&mut udp_rx_buf)
             .await
             .map_err(RxError::Io)
-            .and_then(|(size, _)| UdpPacket::decode(&udp_rx_buf[..size]).map_err(RxError::Decode))
-            .map(|p| {
-                if let Some(packet_content) = p.content {
-                    StreamEvent::UdpPacket(packet_content)
-                } else {
-                    debug!("Received empty oneof protobuf field");
-                    StreamEvent::None
+            .map(|(size, _)| {
+                let _span = info_span!("decode_udp_packet", size).entered();
+                let decoded = encryption::open(key, &udp_rx_buf[..size]).and_then(|datagram| {
+                    let packet = compression::decompress(&datagram)?;
+                    UdpPacket::decode(packet.as_ref())
+                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+                });
+                match decoded {
+                    Ok(UdpPacket {
+                        content: Some(packet_content),
+                        sequence,
+                    }) => StreamEvent::UdpPacket(sequence, packet_content),
+                    Ok(_) => {
+                        debug!("Received empty oneof protobuf field");
+                        StreamEvent::None
+                    }
+                    Err(e) => {
+                        debug!("Failed to decode udp packet: {e}");
+                        udp_decode_errors.fetch_add(1, Ordering::Relaxed);
+                        StreamEvent::None
+                    }
                 }
             });
         Some((result, sock))
@@ -300,25 +707,39 @@ pub async fn io_task(
     // ======== Event processing ========
 
     let mut warn_cooldown = Instant::now();
+    let mut outbox = PacketOutbox::default();
+    let mut reorder_buffer = ReorderBuffer::default();
+    let mut world_state_decoder = WorldStateDecoder::default();
 
     // Returns false if the receiver was dropped and the thread sould be stopped
-    let mut packet_out_send = |packet: UpdatePacket| match packets_out.try_send(packet) {
-        Ok(_) => true,
-        Err(TrySendError::Full(_)) => {
-            if warn_cooldown < Instant::now() {
-                warn!("Status rx channel full (system can't keep up)");
-                warn_cooldown = Instant::now() + Duration::from_secs(5);
-            }
-            true
-        }
-        Err(TrySendError::Closed(_)) => {
+    let mut packet_out_send = |packet: UpdatePacket| {
+        outbox.push(packet);
+        if !outbox.flush(&packets_out) {
             debug!("Packet receiver dropped, stopping io task");
-            false
+            return false;
+        }
+        if !outbox.is_empty() && warn_cooldown < Instant::now() {
+            warn!(
+                "Status rx channel full (system can't keep up), coalesced {} packets",
+                outbox.take_coalesced()
+            );
+            warn_cooldown = Instant::now() + Duration::from_secs(5);
         }
+        true
     };
 
     while let Some(event) = combined_stream
         .next()
+        .or(async {
+            // Wakes up to release held back udp packets
+            match reorder_buffer.deadline() {
+                Some(deadline) => {
+                    async_io::Timer::at(deadline).await;
+                    Some(Ok(StreamEvent::None))
+                }
+                None => future::pending().await,
+            }
+        })
         .or(async {
             // At least a ping should be received every second
             async_io::Timer::after(Duration::from_millis(1500)).await;
@@ -330,6 +751,7 @@ pub async fn io_task(
             Ok(e) => e,
             Err(e) => {
                 error!("Network error: {e:?}");
+                record_error(CONNECTION_TASK, format_args!("{host}: {e:?}"));
                 return;
             }
         };
@@ -339,6 +761,9 @@ pub async fn io_task(
                 if let ws_request::Content::UdpStreamReq(req) = &request_content {
                     request_content = ws_request::Content::UdpStreamReq(UdpStreamRequest {
                         port: udp_port as u32,
+                        // Hosts that don't support it send uncompressed packets
+                        compression: Some(UdpCompression::Zstd as i32),
+                        world_state_deltas: Some(true),
                         ..req.clone()
                     });
                 }
@@ -348,7 +773,9 @@ pub async fn io_task(
                 let mut buf = BytesMut::new();
                 if request.encode(&mut buf).is_ok() {
                     ws_sender
-                        .send(tungstenite::Message::Binary(buf.into()))
+                        .send(tungstenite::Message::Binary(
+                            encryption::seal(key, buf.to_vec()).into(),
+                        ))
                         .await
                         .expect("Websocket closed");
                 }
@@ -358,14 +785,373 @@ pub async fn io_task(
                     return;
                 }
             }
-            StreamEvent::UdpPacket(packet) => {
-                if !packet_out_send(packet.into()) {
-                    return;
+            StreamEvent::UdpPacket(sequence, packet) => {
+                // Deltas are decoded after reordering, so they are applied to the right keyframe
+                for packet in reorder_buffer.push(sequence, packet, Instant::now()) {
+                    if let Some(packet) = world_state_decoder.decode(packet)
+                        && !packet_out_send(packet)
+                    {
+                        return;
+                    }
+                }
+            }
+            StreamEvent::None => {
+                for packet in reorder_buffer.release(Instant::now()) {
+                    if let Some(packet) = world_state_decoder.decode(packet)
+                        && !packet_out_send(packet)
+                    {
+                        return;
+                    }
                 }
             }
-            StreamEvent::None => {}
         }
     }
 
     info!("Connection to timed out");
 }
+
+/// Forwards the snapshots shared by other viewers, see [`crate::SharedSnapshot`]
+pub async fn snapshot_receiver_task(instance_id: u32, snapshots_out: Sender<SharedSnapshot>) {
+    let (socket_v4, socket_v6) = match (
+        UdpSocket::bind_multicast((Ipv4Addr::UNSPECIFIED, ServiceKind::Snapshots.port())),
+        UdpSocket::bind_multicast((Ipv6Addr::UNSPECIFIED, ServiceKind::Snapshots.port())),
+    ) {
+        (Ok(socket_v4), Ok(socket_v6)) => (socket_v4, socket_v6),
+        (Err(e), _) | (_, Err(e)) => {
+            error!(
+                "Failed to bind snapshot sockets, snapshots from other viewers are ignored: {e}"
+            );
+            record_error(SNAPSHOT_TASK, format_args!("Failed to bind sockets: {e}"));
+            return;
+        }
+    };
+
+    let mut rx_buf_v4 = vec![0u8; MAX_SNAPSHOT_SIZE];
+    let mut rx_buf_v6 = vec![0u8; MAX_SNAPSHOT_SIZE];
+    let mut active_interfaces = Vec::new();
+    loop {
+        // Check for new network interfaces every 3 seconds, like the host discovery
+        update_multicast_subscriptions(
+            SNAPSHOT_TASK,
+            Some(&socket_v4),
+            Some(&socket_v6),
+            *ServiceKind::Snapshots.addr_v4().ip(),
+            *ServiceKind::Snapshots.addr_v6().ip(),
+            &mut active_interfaces,
+        );
+        let next_interface_refresh = Instant::now() + Duration::from_secs(3);
+
+        loop {
+            let received = async {
+                let (size, source_addr) = socket_v4.recv_from(&mut rx_buf_v4).await?;
+                Ok::<_, io::Error>((&rx_buf_v4[..size], source_addr))
+            }
+            .or(async {
+                let (size, source_addr) = socket_v6.recv_from(&mut rx_buf_v6).await?;
+                Ok::<_, io::Error>((&rx_buf_v6[..size], source_addr))
+            })
+            .or(async {
+                async_io::Timer::at(next_interface_refresh).await;
+                Err(io::ErrorKind::TimedOut.into())
+            })
+            .await;
+
+            let (packet, source_addr) = match received {
+                Ok(received) => received,
+                Err(e) if e.kind() == io::ErrorKind::TimedOut => break,
+                Err(e) => {
+                    error!("Snapshot receiver network error, stopping snapshot receiver: {e}");
+                    record_error(
+                        SNAPSHOT_TASK,
+                        format_args!("Stopped after network error: {e}"),
+                    );
+                    return;
+                }
+            };
+
+            let snapshot = match SharedSnapshot::decode(packet) {
+                Ok(snapshot) => snapshot,
+                Err(e) => {
+                    warn!("Invalid snapshot received from {source_addr}: {e}");
+                    continue;
+                }
+            };
+            // Multicast packets are looped back to the sender
+            if snapshot.sender_id == Some(instance_id) {
+                continue;
+            }
+            debug!("Received snapshot from {source_addr}");
+            if snapshots_out.send(snapshot).await.is_err() {
+                info!("Snapshot channel dropped, stopping snapshot receiver");
+                return;
+            }
+        }
+    }
+}
+
+/// Sends an encoded snapshot to the snapshot groups, see [`snapshot_receiver_task`]
+pub async fn send_snapshot(snapshot: Vec<u8>) {
+    match UdpSocket::bind((Ipv4Addr::UNSPECIFIED, 0)).await {
+        Ok(socket) => {
+            _ = socket
+                .send_to(&snapshot, ServiceKind::Snapshots.addr_v4())
+                .await
+                .inspect_err(|e| warn!("Failed to send snapshot over ipv4: {e}"));
+        }
+        Err(e) => warn!("Failed to bind ipv4 snapshot socket: {e}"),
+    }
+    match UdpSocket::bind((Ipv6Addr::UNSPECIFIED, 0)).await {
+        Ok(socket) => {
+            _ = socket
+                .send_to(&snapshot, ServiceKind::Snapshots.addr_v6())
+                .await
+                .inspect_err(|e| warn!("Failed to send snapshot over ipv6: {e}"));
+        }
+        Err(e) => warn!("Failed to bind ipv6 snapshot socket: {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{DemoGame, MockHost, MockHostConfig, PacketSource};
+    use bevy::tasks::{IoTaskPool, TaskPool, block_on};
+
+    /// A link-local address of any interface. Tests that need one are ignored by default, since CI machines may not have one.
+    fn link_local_addr() -> Option<SocketAddrV6> {
+        NetworkInterface::show()
+            .ok()?
+            .into_iter()
+            .filter(|i| i.is_up())
+            .find_map(|i| {
+                i.addr.iter().find_map(|addr| match addr {
+                    network_interface::Addr::V6(addr) if addr.ip.is_unicast_link_local() => {
+                        Some(SocketAddrV6::new(addr.ip, 0, 0, i.index))
+                    }
+                    _ => None,
+                })
+            })
+    }
+
+    /// Connects to a mock host on the address and waits for a websocket and an udp packet
+    fn assert_mock_host_reachable(bind_addr: SocketAddr) {
+        IoTaskPool::get_or_init(TaskPool::new);
+        let host = MockHost::spawn(
+            MockHostConfig {
+                bind_addr,
+                hostname: None,
+                advertise: false,
+                advertise_to: Vec::new(),
+                key: None,
+            },
+            PacketSource::Demo(DemoGame::new(1)),
+        )
+        .expect("Failed to start mock host");
+        assert_eq!(host.websocket_addr.ip(), bind_addr.ip());
+
+        let (packets_tx, packets_rx) = async_channel::bounded(100);
+        let (requests_tx, requests_rx) = async_channel::bounded(10);
+        let _task = IoTaskPool::get().spawn(io_task(
+            host.websocket_addr,
+            None,
+            packets_tx,
+            requests_rx,
+            Arc::new(AtomicU32::new(0)),
+        ));
+        requests_tx
+            .send_blocking(ws_request::Content::WsStreamReq(WsStreamRequest {
+                stream: vec![ws_stream_request::WsStream::FieldGeometry as i32],
+            }))
+            .unwrap();
+        requests_tx
+            .send_blocking(ws_request::Content::UdpStreamReq(UdpStreamRequest {
+                stream: vec![udp_stream_request::UdpStream::WorldState as i32],
+                port: 0,
+                compression: None,
+                world_state_deltas: None,
+            }))
+            .unwrap();
+
+        let (mut geometry, mut world_state) = (false, false);
+        block_on(
+            async {
+                while !(geometry && world_state) {
+                    match packets_rx.recv().await {
+                        Ok(UpdatePacket::FieldGeom(_)) => geometry = true,
+                        Ok(UpdatePacket::WorldState(_)) => world_state = true,
+                        Ok(_) => {}
+                        Err(_) => return,
+                    }
+                }
+            }
+            .or(async {
+                async_io::Timer::after(Duration::from_secs(5)).await;
+            }),
+        );
+        assert!(geometry, "No websocket packet from {bind_addr}");
+        assert!(world_state, "No udp packet from {bind_addr}");
+    }
+
+    fn packet(timestamp: u64) -> udp_packet::Content {
+        udp_packet::Content::WorldState(WorldState {
+            timestamp: Some(timestamp),
+            ..default()
+        })
+    }
+
+    fn timestamps(packets: Vec<udp_packet::Content>) -> Vec<u64> {
+        packets
+            .into_iter()
+            .map(|packet| match packet {
+                udp_packet::Content::WorldState(world_state) => world_state.timestamp.unwrap(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    /// Pushes the sequence numbers at the same time, returning the delivered ones
+    fn push_all(buffer: &mut ReorderBuffer, sequences: &[u32], now: Instant) -> Vec<u64> {
+        sequences
+            .iter()
+            .flat_map(|&sequence| {
+                timestamps(buffer.push(Some(sequence), packet(sequence.into()), now))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reorder_delivers_in_order() {
+        let mut buffer = ReorderBuffer::default();
+        let now = Instant::now();
+        assert_eq!(push_all(&mut buffer, &[0, 1, 2], now), [0, 1, 2]);
+
+        // Packets without sequence numbers are passed through
+        assert_eq!(timestamps(buffer.push(None, packet(7), now)), [7]);
+    }
+
+    #[test]
+    fn reorder_holds_back_until_the_gap_is_filled() {
+        let mut buffer = ReorderBuffer::default();
+        let now = Instant::now();
+        assert_eq!(push_all(&mut buffer, &[0, 2, 3], now), [0]);
+        assert_eq!(push_all(&mut buffer, &[1], now), [1, 2, 3]);
+    }
+
+    #[test]
+    fn reorder_drops_duplicates_and_late_packets() {
+        let mut buffer = ReorderBuffer::default();
+        let now = Instant::now();
+        assert_eq!(push_all(&mut buffer, &[0, 2, 2], now), [0]);
+        assert_eq!(push_all(&mut buffer, &[1], now), [1, 2]);
+        // Already delivered
+        assert!(push_all(&mut buffer, &[1, 2], now).is_empty());
+        assert_eq!(push_all(&mut buffer, &[3], now), [3]);
+    }
+
+    #[test]
+    fn reorder_wraps_around() {
+        let mut buffer = ReorderBuffer::default();
+        let now = Instant::now();
+        assert_eq!(
+            push_all(&mut buffer, &[u32::MAX - 1, u32::MAX], now),
+            [u64::from(u32::MAX - 1), u64::from(u32::MAX)]
+        );
+        assert!(push_all(&mut buffer, &[1], now).is_empty());
+        assert_eq!(push_all(&mut buffer, &[0], now), [0, 1]);
+        // From before the wrap, so it is late
+        assert!(push_all(&mut buffer, &[u32::MAX], now).is_empty());
+    }
+
+    #[test]
+    fn reorder_resets_for_restarted_hosts() {
+        let mut buffer = ReorderBuffer::default();
+        let now = Instant::now();
+        assert_eq!(push_all(&mut buffer, &[5000, 5002], now), [5000]);
+        // The held packet is delivered as well, the order of the old sequence doesn't matter anymore
+        assert_eq!(push_all(&mut buffer, &[10], now), [5002, 10]);
+        assert_eq!(push_all(&mut buffer, &[11], now), [11]);
+    }
+
+    #[test]
+    fn reorder_skips_missing_packets_when_full() {
+        let mut buffer = ReorderBuffer::default();
+        let now = Instant::now();
+        assert_eq!(push_all(&mut buffer, &[0], now), [0]);
+        let held: Vec<u32> = (2..2 + REORDER_CAPACITY as u32).collect();
+        assert!(push_all(&mut buffer, &held, now).is_empty());
+
+        // One more than the capacity, 1 is given up on
+        let next = 2 + REORDER_CAPACITY as u32;
+        let expected: Vec<u64> = (2..=u64::from(next)).collect();
+        assert_eq!(push_all(&mut buffer, &[next], now), expected);
+        assert!(push_all(&mut buffer, &[1], now).is_empty());
+    }
+
+    #[test]
+    fn reorder_skips_missing_packets_after_the_deadline() {
+        let mut buffer = ReorderBuffer::default();
+        let now = Instant::now();
+        assert_eq!(push_all(&mut buffer, &[0, 2], now), [0]);
+        assert_eq!(buffer.deadline(), Some(now + REORDER_DELAY));
+        assert!(timestamps(buffer.release(now + REORDER_DELAY / 2)).is_empty());
+
+        assert_eq!(timestamps(buffer.release(now + REORDER_DELAY)), [2]);
+        assert_eq!(buffer.deadline(), None);
+        assert!(push_all(&mut buffer, &[1], now).is_empty());
+    }
+
+    #[test]
+    fn websocket_url_strips_scope_id() {
+        let addr = SocketAddrV6::new("fe80::1".parse().unwrap(), 10000, 0, 3);
+        assert_eq!(websocket_url(addr.into()), "ws://[fe80::1]:10000");
+        let addr = SocketAddr::from((Ipv4Addr::new(10, 0, 0, 1), 10000));
+        assert_eq!(websocket_url(addr), "ws://10.0.0.1:10000");
+    }
+
+    #[test]
+    fn parse_host_addr_keeps_scope_id() {
+        let SocketAddr::V6(addr) = parse_host_addr("[fe80::1%3]:10000").unwrap() else {
+            panic!("Not an ipv6 address");
+        };
+        assert_eq!(addr.scope_id(), 3);
+        assert_eq!(addr.port(), 10000);
+        assert!(parse_host_addr("[fe80::1%does-not-exist]:10000").is_err());
+        assert!(parse_host_addr("fe80::1%3").is_err());
+    }
+
+    #[test]
+    fn parse_host_addr_resolves_interface_names() {
+        let Some(interface) = NetworkInterface::show().unwrap().into_iter().next() else {
+            return;
+        };
+        let SocketAddr::V6(addr) =
+            parse_host_addr(&format!("[fe80::1%{}]:10000", interface.name)).unwrap()
+        else {
+            panic!("Not an ipv6 address");
+        };
+        assert_eq!(addr.scope_id(), interface.index);
+    }
+
+    #[test]
+    #[ignore = "needs a link-local ipv6 address"]
+    fn interface_of_link_local_uses_scope_id() {
+        let addr = link_local_addr().expect("No link-local ipv6 address");
+        let interfaces = NetworkInterface::show().unwrap();
+        // Any link-local address on the same link, the prefix is shared by all interfaces
+        let peer = SocketAddrV6::new("fe80::1234".parse().unwrap(), 10000, 0, addr.scope_id());
+        let interface = interface_of(peer.into(), &interfaces).unwrap();
+        assert_eq!(interface.index, addr.scope_id());
+    }
+
+    #[test]
+    fn mock_host_on_loopback_v6() {
+        assert_mock_host_reachable(SocketAddr::from((Ipv6Addr::LOCALHOST, 0)));
+    }
+
+    #[test]
+    #[ignore = "needs a link-local ipv6 address"]
+    fn mock_host_on_link_local_v6() {
+        let addr = link_local_addr().expect("No link-local ipv6 address");
+        assert_mock_host_reachable(addr.into());
+    }
+}
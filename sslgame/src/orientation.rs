@@ -0,0 +1,179 @@
+//! Coordinate conventions of hosts that don't have x pointing towards the blue goal.
+//! The packets are remapped in the vision coordinate system before they are converted,
+//! so everything after that can keep assuming the yellow goal on the -x side.
+
+use crate::UpdatePacket;
+use crate::proto::remote::vis_part::Geom;
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+/// How the coordinates of a field's hosts map onto the field, applied to every received packet.
+/// The field itself always shows the yellow goal on the -x side, see [`crate::Field`].
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
+pub enum FieldOrientation {
+    #[default]
+    Normal,
+    /// Only the x axis is flipped, for hosts that define +x towards the yellow goal
+    Mirrored,
+    /// Rotated by 180° around the field center, for hosts whose whole coordinate system is turned around
+    Rotated,
+}
+
+impl FieldOrientation {
+    pub const ALL: [Self; 3] = [Self::Normal, Self::Mirrored, Self::Rotated];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            FieldOrientation::Normal => "Normal",
+            FieldOrientation::Mirrored => "Mirrored",
+            FieldOrientation::Rotated => "Rotated 180°",
+        }
+    }
+
+    fn point(&self, x: &mut f32, y: &mut f32) {
+        match self {
+            FieldOrientation::Normal => {}
+            FieldOrientation::Mirrored => *x = -*x,
+            FieldOrientation::Rotated => {
+                *x = -*x;
+                *y = -*y;
+            }
+        }
+    }
+
+    /// Remaps all positions and rotations in the packet, which is still in the host's coordinate system
+    pub(crate) fn apply(&self, packet: &mut UpdatePacket) {
+        match packet {
+            UpdatePacket::WorldState(world_state) => {
+                for ball in &mut world_state.ball {
+                    self.point(&mut ball.p_x, &mut ball.p_y);
+                }
+                for robot in world_state
+                    .yellow_robot
+                    .iter_mut()
+                    .chain(&mut world_state.blue_robot)
+                {
+                    self.point(&mut robot.p_x, &mut robot.p_y);
+                    match self {
+                        FieldOrientation::Normal => {}
+                        // Mirroring also reverses the direction of rotation
+                        FieldOrientation::Mirrored => {
+                            robot.phi = PI - robot.phi;
+                            robot.v_phi = robot.v_phi.map(|v_phi| -v_phi);
+                        }
+                        FieldOrientation::Rotated => robot.phi += PI,
+                    }
+                }
+            }
+            UpdatePacket::VisualizationUpdate(vis_update) => {
+                let parts = vis_update
+                    .visualization_set
+                    .iter_mut()
+                    .flat_map(|set| &mut set.visualization)
+                    .flat_map(|vis| &mut vis.part);
+                for part in parts {
+                    match &mut part.geom {
+                        Some(Geom::Circle(c)) => self.point(&mut c.p_x, &mut c.p_y),
+                        Some(Geom::Polygon(p)) => {
+                            for point in &mut p.point {
+                                self.point(&mut point.x, &mut point.y);
+                            }
+                        }
+                        Some(Geom::Path(p)) => {
+                            for point in &mut p.point {
+                                self.point(&mut point.x, &mut point.y);
+                            }
+                        }
+                        // Custom payloads are opaque, like in the conversion to bevy's coordinate system
+                        Some(Geom::Custom(_)) | None => {}
+                    }
+                }
+            }
+            UpdatePacket::StrategyPlans(plans) => {
+                for pass in plans.yellow_pass.iter_mut().chain(&mut plans.blue_pass) {
+                    self.point(&mut pass.from_x, &mut pass.from_y);
+                    self.point(&mut pass.to_x, &mut pass.to_y);
+                }
+                for target in plans.yellow_target.iter_mut().chain(&mut plans.blue_target) {
+                    self.point(&mut target.p_x, &mut target.p_y);
+                }
+            }
+            // The geometry is symmetric
+            UpdatePacket::FieldGeom(_)
+            | UpdatePacket::GameState(_)
+            | UpdatePacket::VisMappings(_)
+            | UpdatePacket::RobotTelemetry(_)
+            | UpdatePacket::DebugValues(_) => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::remote::{Ball, Robot, WorldState};
+
+    /// A robot in front of the yellow goal on the -x side, and the ball in the other half
+    fn remapped(orientation: FieldOrientation) -> (Robot, Ball) {
+        let mut packet = UpdatePacket::WorldState(WorldState {
+            timestamp: None,
+            ball: vec![Ball {
+                p_x: 2.0,
+                p_y: -0.5,
+                p_z: Some(0.1),
+            }],
+            yellow_robot: vec![Robot {
+                id: 1,
+                p_x: -4.0,
+                p_y: 1.0,
+                phi: 0.5,
+                v_phi: Some(2.0),
+            }],
+            blue_robot: Vec::new(),
+            keyframe: None,
+        });
+        orientation.apply(&mut packet);
+        match packet {
+            UpdatePacket::WorldState(world_state) => {
+                (world_state.yellow_robot[0], world_state.ball[0])
+            }
+            other => panic!("Expected a world state, got {other:?}"),
+        }
+    }
+
+    fn assert_robot(robot: Robot, p_x: f32, p_y: f32, phi: f32, v_phi: f32) {
+        assert_eq!((robot.id, robot.p_x, robot.p_y), (1, p_x, p_y));
+        assert!((robot.phi - phi).abs() < 1e-6, "phi {} != {phi}", robot.phi);
+        assert_eq!(robot.v_phi, Some(v_phi));
+    }
+
+    fn assert_ball(ball: Ball, p_x: f32, p_y: f32) {
+        assert_eq!((ball.p_x, ball.p_y), (p_x, p_y));
+        // The height doesn't depend on the orientation
+        assert_eq!(ball.p_z, Some(0.1));
+    }
+
+    #[test]
+    fn normal_keeps_coordinates() {
+        let (robot, ball) = remapped(FieldOrientation::Normal);
+        assert_robot(robot, -4.0, 1.0, 0.5, 2.0);
+        assert_ball(ball, 2.0, -0.5);
+    }
+
+    #[test]
+    fn mirrored_flips_x_and_rotation_direction() {
+        let (robot, ball) = remapped(FieldOrientation::Mirrored);
+        // The robot ends up in front of the goal on the +x side, still on the same side of the x axis
+        assert_robot(robot, 4.0, 1.0, PI - 0.5, -2.0);
+        assert_ball(ball, -2.0, -0.5);
+    }
+
+    #[test]
+    fn rotated_flips_both_axes() {
+        let (robot, ball) = remapped(FieldOrientation::Rotated);
+        // A rotation keeps the direction of rotation
+        assert_robot(robot, 4.0, -1.0, 0.5 + PI, 2.0);
+        assert_ball(ball, -2.0, 0.5);
+    }
+}
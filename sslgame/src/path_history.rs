@@ -0,0 +1,88 @@
+use crate::{Field, Team, WorldStateUpdated, update_world_state};
+use bevy::prelude::*;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Samples older than this are dropped while a path is recorded
+pub const PATH_HISTORY_DURATION: Duration = Duration::from_secs(30);
+/// Robots have to move at least this far for a new sample, so standing robots don't fill the history
+const MIN_SAMPLE_DISTANCE: f32 = 0.01;
+
+pub(crate) fn path_history_plugin(app: &mut App) {
+    app.add_systems(PostUpdate, record_paths.after(update_world_state));
+}
+
+/// Recorded path of a robot, in the coordinate system of its field
+#[derive(Debug, Default, Clone)]
+pub struct RobotPath {
+    /// Stopped paths are kept until they are cleared, so they can be analyzed after the robot moved on
+    pub recording: bool,
+    pub samples: VecDeque<(Instant, Vec3)>,
+}
+
+/// Paths of the robots of a field that have path recording enabled, by team and id.
+/// Kept on the field, as robots can be respawned at any time.
+#[derive(Component, Debug, Default)]
+pub struct PathHistory(pub HashMap<(Team, u32), RobotPath>);
+
+impl PathHistory {
+    pub fn is_recording(&self, team: Team, id: u32) -> bool {
+        self.0.get(&(team, id)).is_some_and(|path| path.recording)
+    }
+
+    pub fn set_recording(&mut self, team: Team, id: u32, recording: bool) {
+        self.0.entry((team, id)).or_default().recording = recording;
+    }
+
+    /// Removes all samples of the robot, recording continues if it is enabled
+    pub fn clear(&mut self, team: Team, id: u32) {
+        if let Some(path) = self.0.get_mut(&(team, id)) {
+            path.samples.clear();
+        }
+    }
+}
+
+/// Samples the robots of the live world states. Goal replays are skipped, they would record the past again.
+fn record_paths(
+    mut world_state_updates: MessageReader<WorldStateUpdated>,
+    mut q_histories: Query<&mut PathHistory, With<Field>>,
+) {
+    let now = Instant::now();
+    for update in world_state_updates.read() {
+        if update.replay {
+            continue;
+        }
+        let Ok(mut history) = q_histories.get_mut(update.field) else {
+            continue;
+        };
+        // Most fields don't record anything, this avoids triggering change detection every frame
+        if !history.0.values().any(|path| path.recording) {
+            continue;
+        }
+
+        for robot in &update.world_state.robots {
+            let Some(path) = history
+                .0
+                .get_mut(&(robot.team, robot.id))
+                .filter(|path| path.recording)
+            else {
+                continue;
+            };
+            let position = robot.translation;
+            if path
+                .samples
+                .back()
+                .is_none_or(|(_, last)| last.distance(position) >= MIN_SAMPLE_DISTANCE)
+            {
+                path.samples.push_back((now, position));
+            }
+            while path
+                .samples
+                .front()
+                .is_some_and(|(time, _)| now - *time > PATH_HISTORY_DURATION)
+            {
+                path.samples.pop_front();
+            }
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use crate::Team;
+use crate::proto::remote::StrategyPlans;
+use bevy::prelude::*;
+
+/// A pass the strategy is planning, in field coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PlannedPass {
+    pub team: Team,
+    pub from: Vec2,
+    pub to: Vec2,
+}
+
+/// Position a robot is moving to, in field coordinates
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TargetPosition {
+    pub team: Team,
+    pub robot: u32,
+    pub position: Vec2,
+}
+
+/// Latest strategy plans of a field as last reported by the host.
+/// Unlike generic visualizations, these are rendered with their own style, so plans can be told apart from debug lines.
+#[derive(Component, Debug, Default, Clone, PartialEq)]
+pub struct FieldPlans {
+    pub passes: Vec<PlannedPass>,
+    pub targets: Vec<TargetPosition>,
+}
+
+impl FieldPlans {
+    /// Every update contains all current plans
+    pub(crate) fn replace(&mut self, plans: &StrategyPlans) {
+        self.passes.clear();
+        self.targets.clear();
+        for (team, passes, targets) in [
+            (Team::Yellow, &plans.yellow_pass, &plans.yellow_target),
+            (Team::Blue, &plans.blue_pass, &plans.blue_target),
+        ] {
+            self.passes.extend(passes.iter().map(|pass| PlannedPass {
+                team,
+                from: Vec2::new(pass.from_x, pass.from_y),
+                to: Vec2::new(pass.to_x, pass.to_y),
+            }));
+            self.targets
+                .extend(targets.iter().map(|target| TargetPosition {
+                    team,
+                    robot: target.robot_id,
+                    position: Vec2::new(target.p_x, target.p_y),
+                }));
+        }
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{DebugTree, DebugValue, StateFilter, WorldStateUpdated, update_world_state};
+use bevy::prelude::*;
+use std::collections::VecDeque;
+use std::time::Duration;
+
+/// Samples older than this are dropped, so the time window can't be larger
+pub const MAX_PLOT_WINDOW: Duration = Duration::from_secs(60);
+const MIN_PLOT_WINDOW: Duration = Duration::from_secs(1);
+
+pub(crate) fn plotting_plugin(app: &mut App) {
+    app.add_systems(PostUpdate, record_plots.after(update_world_state));
+}
+
+/// Numeric values that can be recorded over time.
+#[derive(Reflect, Debug, Clone, PartialEq, Eq, Hash)]
+#[reflect(Debug, Clone, PartialEq, Hash)]
+pub enum PlotSource {
+    /// Speed of the first ball in m/s, derived from the displayed positions
+    BallSpeed,
+    /// A value of the [`DebugTree`] by its key. Flags are plotted as 0 and 1, text is skipped.
+    DebugValue(String),
+    /// Number of packets held by the state filter
+    BufferedPackets,
+    /// Smallest remaining buffer time of the state filter in ms
+    MinBufferTime,
+}
+
+impl PlotSource {
+    pub fn label(&self) -> String {
+        match self {
+            PlotSource::BallSpeed => "Ball speed (m/s)".to_string(),
+            PlotSource::DebugValue(key) => key.clone(),
+            PlotSource::BufferedPackets => "Buffered packets".to_string(),
+            PlotSource::MinBufferTime => "Min. buffer time (ms)".to_string(),
+        }
+    }
+}
+
+#[derive(Reflect, Debug, Clone)]
+#[reflect(Debug, Clone)]
+pub struct Plot {
+    pub source: PlotSource,
+    /// Ring buffer of (time in s, value), oldest first
+    samples: VecDeque<(f64, f64)>,
+}
+
+impl Plot {
+    /// Samples as (time in s, value) that were recorded at or after `start`, oldest first.
+    /// The time is the elapsed real time of the app.
+    pub fn samples_since(&self, start: f64) -> impl Iterator<Item = (f64, f64)> + '_ {
+        // Samples are ordered by time, so the search can start from the back
+        let first = self.samples.len()
+            - self
+                .samples
+                .iter()
+                .rev()
+                .take_while(|(t, _)| *t >= start)
+                .count();
+        self.samples.range(first..).copied()
+    }
+
+    /// Smallest and largest value recorded at or after `start`
+    pub fn value_range_since(&self, start: f64) -> Option<(f64, f64)> {
+        self.samples_since(start)
+            .map(|(_, value)| value)
+            .fold(None, |range, value| {
+                Some(match range {
+                    Some((min, max)) => (f64::min(min, value), f64::max(max, value)),
+                    None => (value, value),
+                })
+            })
+    }
+
+    pub fn latest(&self) -> Option<f64> {
+        self.samples.back().map(|(_, value)| *value)
+    }
+
+    fn push(&mut self, time: f64, value: f64) {
+        let cutoff = time - MAX_PLOT_WINDOW.as_secs_f64();
+        while self.samples.front().is_some_and(|(t, _)| *t < cutoff) {
+            self.samples.pop_front();
+        }
+        self.samples.push_back((time, value));
+    }
+}
+
+/// Values of a field that are recorded for plotting, and the time window they are displayed with.
+#[derive(Component, Reflect, Debug, Clone)]
+#[reflect(Component, Debug, Default, Clone)]
+pub struct Plots {
+    window: Duration,
+    plots: Vec<Plot>,
+    /// Host timestamp and position of the ball in the previous world state, for the speed
+    #[reflect(ignore)]
+    last_ball: Option<(u64, Vec3)>,
+}
+
+impl Default for Plots {
+    fn default() -> Self {
+        let mut plots = Self {
+            window: Duration::from_secs(10),
+            plots: Vec::new(),
+            last_ball: None,
+        };
+        plots.add(PlotSource::BallSpeed);
+        plots.add(PlotSource::BufferedPackets);
+        plots
+    }
+}
+
+impl Plots {
+    pub fn plots(&self) -> &[Plot] {
+        &self.plots
+    }
+
+    pub fn contains(&self, source: &PlotSource) -> bool {
+        self.plots.iter().any(|plot| &plot.source == source)
+    }
+
+    /// Starts recording the source, if it isn't recorded already
+    pub fn add(&mut self, source: PlotSource) {
+        if !self.contains(&source) {
+            self.plots.push(Plot {
+                source,
+                samples: VecDeque::new(),
+            });
+        }
+    }
+
+    /// Stops recording the source and drops its samples
+    pub fn remove(&mut self, source: &PlotSource) {
+        self.plots.retain(|plot| &plot.source != source);
+    }
+
+    /// Displayed time span, up to [`MAX_PLOT_WINDOW`]
+    pub fn window(&self) -> Duration {
+        self.window
+    }
+
+    pub fn set_window(&mut self, window: Duration) {
+        self.window = window.clamp(MIN_PLOT_WINDOW, MAX_PLOT_WINDOW);
+    }
+}
+
+fn record_plots(
+    time: Res<Time<Real>>,
+    mut world_state_updates: MessageReader<WorldStateUpdated>,
+    mut q_fields: Query<(&mut Plots, &StateFilter, Option<&DebugTree>)>,
+) {
+    let now = time.elapsed_secs_f64();
+
+    for update in world_state_updates.read() {
+        let Ok((mut plots, _, _)) = q_fields.get_mut(update.field) else {
+            continue;
+        };
+        let Some(ball) = update.world_state.balls.first() else {
+            plots.last_ball = None;
+            continue;
+        };
+        let current = (update.world_state.timestamp, ball.translation);
+        // Without interpolation, the same packet is displayed for multiple frames
+        if let Some((last_timestamp, last_pos)) = plots.last_ball.replace(current)
+            && last_timestamp < current.0
+            && let Some(plot) = plots
+                .plots
+                .iter_mut()
+                .find(|plot| plot.source == PlotSource::BallSpeed)
+        {
+            let dt = (current.0 - last_timestamp) as f64 / 1_000_000.0;
+            plot.push(now, last_pos.distance(current.1) as f64 / dt);
+        }
+    }
+
+    for (mut plots, state_filter, debug_tree) in &mut q_fields {
+        let metrics = state_filter.metrics();
+        for plot in &mut plots.plots {
+            let value = match &plot.source {
+                PlotSource::BallSpeed => continue,
+                PlotSource::DebugValue(key) => match debug_tree.and_then(|tree| tree.get(key)) {
+                    Some(DebugValue::Number(n)) => *n,
+                    Some(DebugValue::Flag(flag)) => *flag as u8 as f64,
+                    Some(DebugValue::Text(_)) | None => continue,
+                },
+                PlotSource::BufferedPackets => metrics.buffered_packets as f64,
+                PlotSource::MinBufferTime => match metrics.min_buffer_time {
+                    Some(time) => time.as_secs_f64() * 1000.0,
+                    None => continue,
+                },
+            };
+            plot.push(now, value);
+        }
+    }
+}
@@ -0,0 +1,269 @@
+use crate::proto::remote::{UdpPacket, WsPacket, udp_packet, ws_packet, ws_request};
+use crate::update_packet::{PacketOutbox, UpdatePacket};
+use async_channel::{Receiver, Sender};
+use bevy::prelude::*;
+use prost::Message;
+use std::fs::File;
+use std::io;
+use std::io::{BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+// File layout: MAGIC, then a sequence of records with
+// [u64 le: µs since recording start][u8: record kind][u32 le: payload length][payload: encoded root packet]
+const MAGIC: &[u8; 8] = b"XRVISREC";
+const KIND_WS: u8 = 0;
+const KIND_UDP: u8 = 1;
+
+/// A single recorded root packet with its arrival time relative to the recording start.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub time: Duration,
+    pub packet: RecordedPacket,
+}
+
+#[derive(Debug, Clone)]
+pub enum RecordedPacket {
+    Ws(WsPacket),
+    Udp(UdpPacket),
+}
+
+impl From<&UpdatePacket> for RecordedPacket {
+    fn from(packet: &UpdatePacket) -> Self {
+        match packet.clone() {
+            UpdatePacket::FieldGeom(inner) => Self::Ws(WsPacket {
+                content: Some(ws_packet::Content::Geom(inner)),
+            }),
+            UpdatePacket::GameState(inner) => Self::Ws(WsPacket {
+                content: Some(ws_packet::Content::GameState(inner)),
+            }),
+            UpdatePacket::VisMappings(inner) => Self::Ws(WsPacket {
+                content: Some(ws_packet::Content::VisMappings(inner)),
+            }),
+            UpdatePacket::WorldState(inner) => Self::Udp(UdpPacket {
+                content: Some(udp_packet::Content::WorldState(inner)),
+                sequence: None,
+            }),
+            UpdatePacket::VisualizationUpdate(inner) => Self::Udp(UdpPacket {
+                content: Some(udp_packet::Content::VisUpdate(inner)),
+                sequence: None,
+            }),
+            UpdatePacket::RobotTelemetry(inner) => Self::Udp(UdpPacket {
+                content: Some(udp_packet::Content::RobotTelemetry(inner)),
+                sequence: None,
+            }),
+            UpdatePacket::DebugValues(inner) => Self::Udp(UdpPacket {
+                content: Some(udp_packet::Content::DebugValues(inner)),
+                sequence: None,
+            }),
+            UpdatePacket::StrategyPlans(inner) => Self::Udp(UdpPacket {
+                content: Some(udp_packet::Content::StrategyPlans(inner)),
+                sequence: None,
+            }),
+        }
+    }
+}
+
+impl RecordedPacket {
+    fn into_update_packet(self) -> Option<UpdatePacket> {
+        match self {
+            RecordedPacket::Ws(packet) => packet.content.map(UpdatePacket::from),
+            // Recordings are written from decoded packets, so they never contain deltas
+            RecordedPacket::Udp(packet) => packet.content.and_then(|c| c.try_into().ok()),
+        }
+    }
+}
+
+/// Writes all packets received by a field into a recording file that can be played back using [`crate::Field::replay`].
+#[derive(Component, Debug)]
+pub struct FieldRecorder {
+    writer: BufWriter<File>,
+    start: Instant,
+}
+
+impl FieldRecorder {
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        writer.write_all(MAGIC)?;
+        Ok(Self {
+            writer,
+            start: Instant::now(),
+        })
+    }
+
+    pub fn record(&mut self, packet: &UpdatePacket) -> io::Result<()> {
+        let (kind, payload) = match RecordedPacket::from(packet) {
+            RecordedPacket::Ws(packet) => (KIND_WS, packet.encode_to_vec()),
+            RecordedPacket::Udp(packet) => (KIND_UDP, packet.encode_to_vec()),
+        };
+
+        self.writer
+            .write_all(&(self.start.elapsed().as_micros() as u64).to_le_bytes())?;
+        self.writer.write_all(&[kind])?;
+        self.writer
+            .write_all(&(payload.len() as u32).to_le_bytes())?;
+        self.writer.write_all(&payload)
+    }
+}
+
+/// Reads a full recording file into memory. Recordings are small enough that streaming is not worth the complexity.
+pub(crate) fn read_recording(path: impl AsRef<Path>) -> io::Result<Vec<Record>> {
+    let mut data = Vec::new();
+    File::open(path)?.read_to_end(&mut data)?;
+
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+
+    let Some(mut rest) = data.strip_prefix(MAGIC) else {
+        return Err(invalid("not an xrvis recording"));
+    };
+
+    let mut records = Vec::new();
+    while !rest.is_empty() {
+        if rest.len() < 13 {
+            return Err(invalid("truncated record header"));
+        }
+        let time = Duration::from_micros(u64::from_le_bytes(rest[0..8].try_into().unwrap()));
+        let kind = rest[8];
+        let len = u32::from_le_bytes(rest[9..13].try_into().unwrap()) as usize;
+        let Some(payload) = rest.get(13..13 + len) else {
+            return Err(invalid("truncated record payload"));
+        };
+
+        let packet = match kind {
+            KIND_WS => {
+                RecordedPacket::Ws(WsPacket::decode(payload).map_err(|e| invalid(&e.to_string()))?)
+            }
+            KIND_UDP => RecordedPacket::Udp(
+                UdpPacket::decode(payload).map_err(|e| invalid(&e.to_string()))?,
+            ),
+            _ => return Err(invalid("unknown record kind")),
+        };
+        records.push(Record { time, packet });
+
+        rest = &rest[13 + len..];
+    }
+
+    Ok(records)
+}
+
+/// A recording that is played back by the app instead of a task, e.g. to render it offline at a fixed frame rate.
+/// The packets can be pushed into a field created with [`crate::Field::with_injector`].
+#[derive(Debug)]
+pub struct Recording {
+    records: Vec<Record>,
+    next: usize,
+}
+
+impl Recording {
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            records: read_recording(path)?,
+            next: 0,
+        })
+    }
+
+    pub fn duration(&self) -> Duration {
+        self.records.last().map_or(Duration::ZERO, |r| r.time)
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.records.len()
+    }
+
+    /// Returns the packets up to `time` since the start of the recording that haven't been returned yet
+    pub fn advance(&mut self, time: Duration) -> impl Iterator<Item = UpdatePacket> + '_ {
+        let start = self.next;
+        self.next += self.records[start..].partition_point(|r| r.time <= time);
+        self.records[start..self.next]
+            .iter()
+            .filter_map(|r| r.packet.clone().into_update_packet())
+    }
+}
+
+/// Shared by replays that advance in lockstep, e.g. to compare two matches or halves side by side.
+/// The loops of all replays on a clock start together and last as long as the longest recording.
+#[derive(Debug, Clone)]
+pub struct PlaybackClock(Arc<ClockState>);
+
+#[derive(Debug)]
+struct ClockState {
+    start: Instant,
+    /// In µs, grows when a longer recording is attached
+    loop_duration: AtomicU64,
+}
+
+impl Default for PlaybackClock {
+    fn default() -> Self {
+        Self(Arc::new(ClockState {
+            start: Instant::now(),
+            loop_duration: AtomicU64::new(0),
+        }))
+    }
+}
+
+impl PlaybackClock {
+    fn attach(&self, loop_duration: Duration) {
+        self.0
+            .loop_duration
+            .fetch_max(loop_duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    fn loop_duration(&self) -> Duration {
+        Duration::from_micros(self.0.loop_duration.load(Ordering::Relaxed).max(1))
+    }
+
+    /// Start of the loop that is playing at the given time
+    fn loop_start(&self, time: Instant) -> Instant {
+        let loop_duration = self.loop_duration().as_micros() as u64;
+        let elapsed = time.saturating_duration_since(self.0.start).as_micros() as u64;
+        self.0.start + Duration::from_micros(elapsed / loop_duration * loop_duration)
+    }
+}
+
+/// Plays back the recording in a loop with its original timing.
+/// Replays that are attached to a clock later start in the current loop, the packets they missed are sent at once.
+pub(crate) async fn replay_task(
+    records: Vec<Record>,
+    clock: PlaybackClock,
+    packets_out: Sender<UpdatePacket>,
+    requests_in: Receiver<ws_request::Content>,
+) {
+    let Some(duration) = records.last().map(|r| r.time) else {
+        warn!("Replaying an empty recording");
+        return;
+    };
+
+    // Leave a small gap between loops so that the last and first packet don't share a timestamp
+    clock.attach(duration + Duration::from_millis(10));
+    let mut loop_start = clock.loop_start(Instant::now());
+    let mut outbox = PacketOutbox::default();
+
+    loop {
+        // World state timestamps have to keep increasing across loops for the state filter
+        let timestamp_offset = (loop_start - clock.0.start).as_micros() as u64;
+        for record in &records {
+            async_io::Timer::at(loop_start + record.time).await;
+
+            // Requests can't be forwarded to a file, so they are just discarded
+            while requests_in.try_recv().is_ok() {}
+
+            let Some(mut packet) = record.packet.clone().into_update_packet() else {
+                continue;
+            };
+            if let UpdatePacket::WorldState(world_state) = &mut packet {
+                world_state.timestamp = world_state.timestamp.map(|t| t + timestamp_offset);
+            }
+
+            outbox.push(packet);
+            if !outbox.flush(&packets_out) {
+                debug!("Packet receiver dropped, stopping replay task");
+                return;
+            }
+        }
+
+        // Shorter recordings wait for the longest one on the same clock
+        loop_start = clock.loop_start(loop_start) + clock.loop_duration();
+    }
+}
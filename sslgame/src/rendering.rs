@@ -0,0 +1,1925 @@
+use crate::depth_mask_material::{DepthMaskMaterial, SHADER_ASSET_PATH};
+use crate::mesh_generators::{
+    field_mesh, grid_mesh, shadow_blob_mesh, visualization_mesh, visualization_part_bounds,
+};
+use crate::proto::remote::{Color as ProtoColor, Visualization};
+use crate::vis_mesh_key::{vis_mesh_key, with_circle_detail};
+use crate::{
+    AssetsLoaded, AvailableVisualizations, BALL_RADIUS, Ball, CheckStatus, ContentQuality,
+    DataSource, Field, FieldGeometry, FieldLights, FieldPlans, FieldScale, GameEvent,
+    GameEventKind, GameState, GhostBall, GhostRobot, GhostSource, Measurement,
+    PATH_HISTORY_DURATION, PathHistory, Paused, ROBOT_RADIUS, RenderSettings, Robot, RobotFlag,
+    RobotRenderSettings, Role, SelfTestReport, SoloField, Team, Telemetry, Velocity,
+    VisColorOverrides, VisMeshTolerance, VisualizationData, field_to_local, receive_field_updates,
+    update_visualizations, update_world_state,
+};
+use bevy::asset::RecursiveDependencyLoadState;
+use bevy::mesh::{CylinderAnchor, CylinderMeshBuilder, SphereKind, SphereMeshBuilder};
+use bevy::prelude::*;
+use bevy::scene::SceneInstance;
+use bevy::shader::Shader;
+use bevy::tasks::futures_lite::future;
+use bevy::tasks::{AsyncComputeTaskPool, Task, block_on};
+use bevy::transform::TransformSystems;
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_2, FRAC_PI_4, TAU};
+use std::time::{Duration, Instant};
+
+/// Number of transparency levels while small visualizations fade out
+const VIS_FADE_STEPS: usize = 3;
+/// Added to the sort distance of visualizations, so they are drawn after other translucent meshes at the same
+/// position like the grid. Small enough to not change the order against ghosts and the other visualizations.
+const VIS_DEPTH_BIAS: f32 = 0.01;
+/// Visualizations are lifted by their id modulo [`VIS_LAYERS`] times this, so overlapping fills are always blended
+/// in the same order instead of flickering between frames
+const VIS_LAYER_HEIGHT: f32 = 0.00005;
+const VIS_LAYERS: u32 = 64;
+
+/// Creates meshes and materials for the field content.
+/// Only added by [`crate::ssl_game_plugin`] if the app has a renderer, the data systems don't depend on it.
+pub(crate) fn rendering_plugin(app: &mut App) {
+    app.add_plugins(MaterialPlugin::<DepthMaskMaterial>::default());
+
+    // Visibility only exists with the render stack, so it can't be required by the components themselves
+    app.register_required_components::<Field, Visibility>();
+    app.register_required_components::<GhostSource, Visibility>();
+    app.register_required_components::<DataSource, Visibility>();
+    app.register_required_components::<Measurement, Visibility>();
+    // Robots without a model still need it, so gizmos can skip the robots of hidden fields
+    app.register_required_components::<Robot, Visibility>();
+
+    let world = app.world_mut();
+
+    // Meshes
+    let mut meshes = world.resource_mut::<Assets<Mesh>>();
+    let robot_mask_mesh = meshes.add(MeshBuilder::build(
+        &CylinderMeshBuilder::new(ROBOT_RADIUS, 0.15, 32).anchor(CylinderAnchor::Bottom),
+    ));
+    // FIXME: Ball in the ground
+    let mut ball_mesh = MeshBuilder::build(&SphereMeshBuilder::new(
+        BALL_RADIUS,
+        SphereKind::Ico { subdivisions: 3 },
+    ));
+    // A darker ring around the ball, otherwise its rolling wouldn't be visible
+    let ball_colors: Vec<[f32; 4]> = ball_mesh
+        .attribute(Mesh::ATTRIBUTE_POSITION)
+        .and_then(|positions| positions.as_float3())
+        .expect("Sphere meshes have positions")
+        .iter()
+        .map(|p| {
+            if p[1].abs() < BALL_RADIUS * 0.25 {
+                [0.5, 0.5, 0.5, 1.0]
+            } else {
+                [1.0; 4]
+            }
+        })
+        .collect();
+    ball_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, ball_colors);
+    let ball_mesh = meshes.add(ball_mesh);
+    let shadow_blob_mesh = meshes.add(shadow_blob_mesh());
+
+    // Materials
+    let robot_mask_material = world
+        .resource_mut::<Assets<DepthMaskMaterial>>()
+        .add(DepthMaskMaterial {});
+    let mut materials = world.resource_mut::<Assets<StandardMaterial>>();
+    let ball_material = materials.add(StandardMaterial::from_color(Color::srgb_u8(255, 136, 0)));
+    let white_mat_opaque = materials.add(StandardMaterial::from_color(Color::WHITE));
+    let white_mat_translucent = materials.add({
+        let mut tmp = StandardMaterial::from_color(Color::WHITE);
+        tmp.alpha_mode = AlphaMode::Blend;
+        tmp
+    });
+    // Base colors are multiplied with the vertex colors, so the fade materials fade all visualization colors equally
+    let vis_material = |alpha: f32| StandardMaterial {
+        alpha_mode: AlphaMode::Blend,
+        depth_bias: VIS_DEPTH_BIAS,
+        ..StandardMaterial::from_color(Color::WHITE.with_alpha(alpha))
+    };
+    let white_mat_visualization = materials.add(vis_material(1.0));
+    let vis_fade_materials = VisFadeMaterials(
+        (1..=VIS_FADE_STEPS)
+            .map(|step| materials.add(vis_material(step as f32 / (VIS_FADE_STEPS + 1) as f32)))
+            .collect(),
+    );
+    let shadow_blob_material = materials.add(StandardMaterial {
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..StandardMaterial::from_color(Color::WHITE)
+    });
+    let ghost_material = |color: Color| StandardMaterial {
+        base_color: color.with_alpha(0.35),
+        alpha_mode: AlphaMode::Blend,
+        unlit: true,
+        ..default()
+    };
+    let ghost_materials = GhostMaterials {
+        yellow: materials.add(ghost_material(Color::srgb(1.0, 0.9, 0.0))),
+        blue: materials.add(ghost_material(Color::srgb(0.1, 0.4, 1.0))),
+        ball: materials.add(ghost_material(Color::srgb_u8(255, 136, 0))),
+    };
+
+    // Loaded right away, so the models are ready when the first robots spawn
+    let asset_server = world.resource::<AssetServer>();
+    let preloaded_assets = PreloadedAssets {
+        robot_scene: asset_server.load(ROBOT_SCENE_PATH),
+        depth_mask_shader: asset_server.load(SHADER_ASSET_PATH),
+    };
+
+    app.insert_resource(preloaded_assets);
+    app.insert_resource(AssetsLoaded(false));
+    app.insert_resource(ghost_materials);
+    app.insert_resource(RobotMaskMesh(robot_mask_mesh, robot_mask_material));
+    app.insert_resource(BallMesh(ball_mesh, ball_material));
+    app.insert_resource(ShadowBlobMesh(shadow_blob_mesh, shadow_blob_material));
+    app.insert_resource(DefaultMaterial {
+        opaque: white_mat_opaque,
+        translucent: white_mat_translucent,
+        visualization: white_mat_visualization,
+    });
+    app.insert_resource(vis_fade_materials);
+    app.init_resource::<VisMeshCache>();
+    app.init_resource::<VisMeshTolerance>();
+    app.register_type::<VisMeshTolerance>();
+
+    // Systems
+    app.add_systems(
+        Update,
+        (
+            check_preloaded_assets.run_if(|loaded: Res<AssetsLoaded>| !loaded.0),
+            apply_solo_field,
+            // The content quality can replace the robot models
+            handle_render_settings_change
+                .run_if(resource_changed::<RenderSettings>.or(resource_changed::<ContentQuality>)),
+            render_field.after(receive_field_updates),
+            render_grid.after(receive_field_updates),
+            (render_field_lights, scale_field_lights)
+                .chain()
+                .after(receive_field_updates),
+            (apply_vis_meshes, render_visualizations)
+                .chain()
+                .after(update_visualizations),
+        ),
+    );
+    app.add_systems(
+        PostUpdate,
+        (
+            render_robots,
+            (
+                render_balls,
+                scale_balls,
+                roll_balls.run_if(|paused: Res<Paused>| !paused.0),
+            )
+                .chain(),
+            render_ghosts,
+            render_shadow_blobs,
+        )
+            .after(update_world_state)
+            .before(TransformSystems::Propagate),
+    );
+    // Gizmos need the propagated transforms
+    app.add_systems(
+        PostUpdate,
+        draw_telemetry_bars
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.telemetry)
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_actuator_states
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.telemetry)
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_chip_arcs
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.ball)
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_ball_streaks
+            .run_if(
+                |render_settings: Res<RenderSettings>, quality: Res<ContentQuality>| {
+                    render_settings.ball && quality.trails()
+                },
+            )
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_path_history
+            .run_if(|quality: Res<ContentQuality>| quality.trails())
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_ghost_offsets.after(TransformSystems::Propagate),
+    );
+    // Only useful over the real robots, the tags would just cover the models otherwise
+    app.add_systems(
+        PostUpdate,
+        draw_marker_tags
+            .run_if(
+                |render_settings: Res<RenderSettings>, quality: Res<ContentQuality>| {
+                    render_settings.marker_tags
+                        && quality.robots(&render_settings.robots) == RobotRenderSettings::Cutout
+                },
+            )
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_measurements.after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_team_situation_rings
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.field)
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_robot_flags.after(TransformSystems::Propagate),
+    );
+    // Roles are strategy data, so they are shown with the visualizations
+    app.add_systems(
+        PostUpdate,
+        draw_role_icons
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.visualizations)
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_strategy_plans
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.visualizations)
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        cull_small_visualizations.after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_event_cues
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.visualizations)
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_out_of_bounds_lines
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.field)
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_near_collision_markers
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.visualizations)
+            .after(TransformSystems::Propagate),
+    );
+    app.add_systems(
+        PostUpdate,
+        draw_defense_area_intrusions
+            .run_if(|render_settings: Res<RenderSettings>| render_settings.field)
+            .after(TransformSystems::Propagate),
+    );
+}
+
+// ======== Resources ========
+
+// TODO: Team specific robot models, until then the generic model is used
+const ROBOT_SCENE_PATH: &str = "teams/robots/generic.glb#Scene0";
+
+/// Assets that are loaded from files, kept loaded even while they aren't used.
+/// Meshes and materials that are generated in code (e.g. for balls) are ready right away.
+#[derive(Resource, Debug)]
+struct PreloadedAssets {
+    robot_scene: Handle<Scene>,
+    depth_mask_shader: Handle<Shader>,
+}
+
+#[derive(Resource, Debug)]
+struct RobotMaskMesh(Handle<Mesh>, Handle<DepthMaskMaterial>);
+
+#[derive(Resource, Debug)]
+struct BallMesh(Handle<Mesh>, Handle<StandardMaterial>);
+
+#[derive(Resource, Debug)]
+struct ShadowBlobMesh(Handle<Mesh>, Handle<StandardMaterial>);
+
+/// Translucent materials for the robots and balls of ghost sources
+#[derive(Resource, Debug)]
+struct GhostMaterials {
+    yellow: Handle<StandardMaterial>,
+    blue: Handle<StandardMaterial>,
+    ball: Handle<StandardMaterial>,
+}
+
+#[derive(Resource, Debug)]
+struct DefaultMaterial {
+    pub opaque: Handle<StandardMaterial>,
+    pub translucent: Handle<StandardMaterial>,
+    /// Translucent with the [`VIS_DEPTH_BIAS`]
+    pub visualization: Handle<StandardMaterial>,
+}
+
+/// Translucent materials for fading out small visualizations, from the most transparent to the least
+#[derive(Resource, Debug)]
+struct VisFadeMaterials(Vec<Handle<StandardMaterial>>);
+
+/// Visualization meshes by their [`vis_mesh_key`], translated copies of a shape share the same mesh.
+/// Tessellating large polygons is expensive, so meshes are generated on the AsyncComputeTaskPool
+/// and reused as long as any entity still uses them.
+#[derive(Resource, Default)]
+struct VisMeshCache {
+    pending: HashMap<u64, Task<Mesh>>,
+    ready: HashMap<u64, AssetId<Mesh>>,
+}
+
+// ======== Components ========
+
+/// Marks a visualization whose mesh is still being generated.
+#[derive(Component, Debug)]
+struct PendingVisMesh(u64);
+
+/// Center and size of the parts of a visualization relative to its entity, see [`visualization_part_bounds`]
+#[derive(Component, Debug)]
+struct VisPartBounds(Vec<(Vec3, f32)>);
+
+/// The ruler grid of the parent field
+#[derive(Component, Debug)]
+struct FieldGrid;
+
+// ======== Systems ========
+
+/// Swaps the models of existing robots and ghosts in place when the robot rendering changes,
+/// so that changing other settings doesn't make them flicker.
+/// Does not affect visualizations and balls, as they get regenerated periodically anyways.
+#[allow(clippy::type_complexity)]
+fn handle_render_settings_change(
+    mut commands: Commands,
+    render_settings: Res<RenderSettings>,
+    quality: Res<ContentQuality>,
+    mut robot_rendering: Local<Option<RobotRenderSettings>>,
+    (preloaded_assets, robot_mask_mesh, ghost_materials): (
+        Res<PreloadedAssets>,
+        Res<RobotMaskMesh>,
+        Res<GhostMaterials>,
+    ),
+    (q_fields, q_robots, q_ghost_robots): (
+        Query<Entity, (With<Field>, With<Mesh3d>)>,
+        Query<Entity, With<Robot>>,
+        Query<(&GhostRobot, Entity)>,
+    ),
+) {
+    if !render_settings.field {
+        // The field entity is also used as a marker for data processing, so only the model is removed
+        for field_entity in q_fields {
+            commands.entity(field_entity).remove::<Mesh3d>();
+        }
+    }
+
+    let robots = quality.robots(&render_settings.robots);
+    if robot_rendering.as_ref() == Some(&robots) {
+        return;
+    }
+    *robot_rendering = Some(robots.clone());
+    for robot_entity in &q_robots {
+        // Robots have no children except for the instance of their scene
+        let mut robot = commands.entity(robot_entity);
+        robot.despawn_related::<Children>().remove::<(
+            SceneRoot,
+            SceneInstance,
+            Mesh3d,
+            MeshMaterial3d<DepthMaskMaterial>,
+        )>();
+        insert_robot_model(&mut robot, &robots, &preloaded_assets, &robot_mask_mesh);
+    }
+    for (ghost, ghost_entity) in &q_ghost_robots {
+        let mut ghost_robot = commands.entity(ghost_entity);
+        ghost_robot.remove::<(Mesh3d, MeshMaterial3d<StandardMaterial>)>();
+        insert_ghost_model(
+            &mut ghost_robot,
+            ghost,
+            &robots,
+            &robot_mask_mesh,
+            &ghost_materials,
+        );
+    }
+}
+
+/// Marks the assets as loaded once all preloaded assets are ready. Failed assets don't block the apps,
+/// robots are just invisible then.
+fn check_preloaded_assets(
+    asset_server: Res<AssetServer>,
+    preloaded_assets: Res<PreloadedAssets>,
+    mut loaded: ResMut<AssetsLoaded>,
+    mut report: ResMut<SelfTestReport>,
+) {
+    let ids = [
+        preloaded_assets.robot_scene.id().untyped(),
+        preloaded_assets.depth_mask_shader.id().untyped(),
+    ];
+    let mut ready = true;
+    let mut failed = Vec::new();
+    for id in ids {
+        match asset_server.get_recursive_dependency_load_state(id) {
+            Some(RecursiveDependencyLoadState::Loaded) => {}
+            Some(RecursiveDependencyLoadState::Failed(e)) => {
+                let path = asset_server
+                    .get_path(id)
+                    .map_or_else(|| format!("{id:?}"), |path| path.to_string());
+                failed.push(format!("{path} ({e})"));
+            }
+            _ => ready = false,
+        }
+    }
+    if ready {
+        if failed.is_empty() {
+            report.record("Assets", CheckStatus::Ok, "Robot model and shaders loaded");
+        } else {
+            report.record(
+                "Assets",
+                CheckStatus::Error,
+                format!(
+                    "Failed to load {}. Start the viewer next to its assets folder or point BEVY_ASSET_ROOT to it.",
+                    failed.join(", ")
+                ),
+            );
+        }
+        loaded.0 = true;
+    }
+}
+
+/// Hides all fields except the solo one. A despawned solo field shows all fields again.
+fn apply_solo_field(
+    mut solo: ResMut<SoloField>,
+    mut q_fields: Query<(&mut Visibility, Entity), With<Field>>,
+) {
+    if solo.0.is_some_and(|field| !q_fields.contains(field)) {
+        solo.0 = None;
+    }
+    for (mut visibility, entity) in &mut q_fields {
+        if solo.0.is_none_or(|field| field == entity) {
+            visibility.set_if_neq(Visibility::Inherited);
+        } else {
+            visibility.set_if_neq(Visibility::Hidden);
+        }
+    }
+}
+
+fn render_field(
+    mut commands: Commands,
+    render_settings: Res<RenderSettings>,
+    white_material: Res<DefaultMaterial>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut q_fields: Query<(Ref<FieldGeometry>, Option<&Mesh3d>, Entity)>,
+) {
+    for (field_geometry, mesh_component, entity) in &mut q_fields {
+        if render_settings.field && (field_geometry.is_changed() || mesh_component.is_none()) {
+            commands.entity(entity).insert((
+                Mesh3d(mesh_assets.add(field_mesh(&field_geometry))),
+                MeshMaterial3d(white_material.opaque.clone()),
+            ));
+        }
+    }
+}
+
+fn render_grid(
+    mut commands: Commands,
+    render_settings: Res<RenderSettings>,
+    material: Res<DefaultMaterial>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    q_fields: Query<(Ref<FieldGeometry>, Option<&Children>, Entity), With<Field>>,
+    q_grids: Query<Entity, With<FieldGrid>>,
+) {
+    for (field_geometry, children, entity) in &q_fields {
+        let grid = children.and_then(|children| children.iter().find(|c| q_grids.contains(*c)));
+        let outdated = !render_settings.grid || field_geometry.is_changed();
+        if let Some(grid) = grid
+            && outdated
+        {
+            commands.entity(grid).despawn();
+        }
+        if !render_settings.grid || (grid.is_some() && !outdated) {
+            continue;
+        }
+        commands.entity(entity).with_child((
+            FieldGrid,
+            Mesh3d(mesh_assets.add(grid_mesh(&field_geometry))),
+            MeshMaterial3d(material.translucent.clone()),
+        ));
+    }
+}
+
+/// Parent of the lights of a field, see [`FieldLights`]
+#[derive(Component, Debug)]
+struct FieldLightRig(FieldLights);
+
+/// A light of a [`FieldLightRig`], with its intensity on an unscaled field
+#[derive(Component, Debug)]
+struct FieldLight {
+    intensity: f32,
+}
+
+/// The lights move with the field transform, but their intensity and range don't scale with it
+fn scaled_light(intensity: f32, scale: &FieldScale) -> (f32, f32) {
+    (intensity * scale.0.powi(2), 30.0 * scale.0)
+}
+
+/// Respawns the lights when the preset or the geometry changes, scale changes are handled by [`scale_field_lights`]
+fn render_field_lights(
+    mut commands: Commands,
+    render_settings: Res<RenderSettings>,
+    q_fields: Query<(Ref<FieldGeometry>, &FieldScale, Option<&Children>, Entity), With<Field>>,
+    q_rigs: Query<&FieldLightRig>,
+) {
+    for (field_geometry, scale, children, entity) in &q_fields {
+        let rig = children.and_then(|children| {
+            children
+                .iter()
+                .find_map(|c| q_rigs.get(c).ok().map(|rig| (c, rig.0)))
+        });
+        let outdated = rig.is_none_or(|(_, lights)| lights != render_settings.lights)
+            || field_geometry.is_changed();
+        if !outdated {
+            continue;
+        }
+        if let Some((rig, _)) = rig {
+            commands.entity(rig).despawn();
+        }
+        if render_settings.lights == FieldLights::Off {
+            // Remembers that the lights are off, so the rig isn't checked again every frame
+            commands
+                .entity(entity)
+                .with_child((FieldLightRig(FieldLights::Off), Transform::default()));
+            continue;
+        }
+
+        let half_size = field_geometry.play_area_size / 2.0 + field_geometry.boundary_width;
+        let light = |intensity: f32| {
+            let (scaled, range) = scaled_light(intensity, scale);
+            (FieldLight { intensity }, scaled, range)
+        };
+        commands.entity(entity).with_children(|parent| {
+            let mut rig = parent.spawn((
+                FieldLightRig(render_settings.lights),
+                Transform::default(),
+                Visibility::default(),
+            ));
+            rig.with_children(|rig| {
+                // Key light from high above one long side, the only one casting shadows
+                let (key, intensity, range) = light(2_000_000.0);
+                rig.spawn((
+                    key,
+                    SpotLight {
+                        intensity,
+                        range,
+                        shadows_enabled: true,
+                        outer_angle: FRAC_PI_4,
+                        ..default()
+                    },
+                    Transform::from_xyz(-half_size.x * 0.3, 8.0, half_size.y)
+                        .looking_at(Vec3::ZERO, Vec3::Y),
+                ));
+                // Weaker fill light from the other side, so nothing is left completely dark
+                let (fill, intensity, range) = light(400_000.0);
+                rig.spawn((
+                    fill,
+                    PointLight {
+                        intensity,
+                        range,
+                        ..default()
+                    },
+                    Transform::from_xyz(half_size.x * 0.5, 5.0, -half_size.y),
+                ));
+                if render_settings.lights == FieldLights::Stadium {
+                    for (x, z) in [(1.0, 1.0), (1.0, -1.0), (-1.0, 1.0), (-1.0, -1.0)] {
+                        let (flood, intensity, range) = light(1_000_000.0);
+                        rig.spawn((
+                            flood,
+                            SpotLight {
+                                intensity,
+                                range,
+                                inner_angle: FRAC_PI_4 * 0.6,
+                                outer_angle: FRAC_PI_4,
+                                ..default()
+                            },
+                            Transform::from_xyz(
+                                x * (half_size.x + 2.0),
+                                12.0,
+                                z * (half_size.y + 2.0),
+                            )
+                            .looking_at(Vec3::ZERO, Vec3::Y),
+                        ));
+                    }
+                }
+            });
+        });
+    }
+}
+
+/// Updates the existing lights in place, the scale changes every frame during a scale gesture
+fn scale_field_lights(
+    q_fields: Query<(&FieldScale, &Children), (With<Field>, Changed<FieldScale>)>,
+    q_rigs: Query<&Children, With<FieldLightRig>>,
+    mut q_lights: Query<(&FieldLight, Option<&mut SpotLight>, Option<&mut PointLight>)>,
+) {
+    for (scale, children) in &q_fields {
+        for rig in children.iter().filter_map(|c| q_rigs.get(c).ok()) {
+            for light in rig.iter() {
+                let Ok((light, spot_light, point_light)) = q_lights.get_mut(light) else {
+                    continue;
+                };
+                let (intensity, range) = scaled_light(light.intensity, scale);
+                if let Some(mut spot_light) = spot_light {
+                    spot_light.intensity = intensity;
+                    spot_light.range = range;
+                }
+                if let Some(mut point_light) = point_light {
+                    point_light.intensity = intensity;
+                    point_light.range = range;
+                }
+            }
+        }
+    }
+}
+
+fn insert_robot_model(
+    robot: &mut EntityCommands,
+    robot_rendering: &RobotRenderSettings,
+    preloaded_assets: &PreloadedAssets,
+    robot_mask_mesh: &RobotMaskMesh,
+) {
+    match robot_rendering {
+        RobotRenderSettings::Detailed | RobotRenderSettings::Fallback => {
+            robot.insert(SceneRoot(preloaded_assets.robot_scene.clone()));
+        }
+        RobotRenderSettings::Cutout => {
+            robot.insert((
+                Mesh3d(robot_mask_mesh.0.clone()),
+                MeshMaterial3d(robot_mask_mesh.1.clone()),
+            ));
+        }
+        RobotRenderSettings::None => {}
+    }
+}
+
+fn render_robots(
+    mut commands: Commands,
+    render_settings: Res<RenderSettings>,
+    quality: Res<ContentQuality>,
+    preloaded_assets: Res<PreloadedAssets>,
+    robot_mask_mesh: Res<RobotMaskMesh>,
+    q_new_robots: Query<Entity, Added<Robot>>,
+) {
+    for robot_entity in &q_new_robots {
+        insert_robot_model(
+            &mut commands.entity(robot_entity),
+            &quality.robots(&render_settings.robots),
+            &preloaded_assets,
+            &robot_mask_mesh,
+        );
+    }
+}
+
+fn render_balls(
+    mut commands: Commands,
+    render_settings: Res<RenderSettings>,
+    ball_mesh: Res<BallMesh>,
+    q_new_balls: Query<Entity, Added<Ball>>,
+) {
+    if !render_settings.ball {
+        return;
+    }
+    for ball_entity in &q_new_balls {
+        commands.entity(ball_entity).insert((
+            Mesh3d(ball_mesh.0.clone()),
+            MeshMaterial3d(ball_mesh.1.clone()),
+        ));
+    }
+}
+
+/// Dark blob under a robot or ball, see [`render_shadow_blobs`]
+#[derive(Component, Debug)]
+struct ShadowBlob;
+
+/// Grounds robots and balls without a shadow casting light, e.g. robot cutouts in passthrough or with the field
+/// lights off. Blobs are reused per field instead of following their robot, since balls are respawned with every
+/// world state.
+fn render_shadow_blobs(
+    mut commands: Commands,
+    render_settings: Res<RenderSettings>,
+    quality: Res<ContentQuality>,
+    shadow_blob_mesh: Res<ShadowBlobMesh>,
+    q_fields: Query<(&Children, &FieldScale, Entity), With<Field>>,
+    q_robots: Query<&Transform, (With<Robot>, Without<ShadowBlob>)>,
+    q_balls: Query<&Transform, (With<Ball>, Without<ShadowBlob>)>,
+    mut q_blobs: Query<&mut Transform, With<ShadowBlob>>,
+) {
+    /// Just above the field surface, below the field lines
+    const BLOB_HEIGHT: f32 = 0.001;
+    const ROBOT_BLOB_RADIUS: f32 = 0.12;
+
+    let robots = quality.robots(&render_settings.robots);
+    let enabled =
+        robots == RobotRenderSettings::Cutout || render_settings.lights == FieldLights::Off;
+    let show_robots = enabled && robots != RobotRenderSettings::None;
+    let show_balls = enabled && render_settings.ball;
+
+    for (children, scale, field) in &q_fields {
+        // Balls are sized with the field scale, see scale_balls
+        let ball_blob_radius = scale.local_size(BALL_RADIUS * 1.5);
+
+        let mut shadows = Vec::new();
+        let mut blobs = Vec::new();
+        for child in children.iter() {
+            if show_robots && let Ok(transform) = q_robots.get(child) {
+                shadows.push((transform.translation, ROBOT_BLOB_RADIUS));
+            } else if show_balls && let Ok(transform) = q_balls.get(child) {
+                // Lifted balls cast a larger, softer shadow
+                let height = transform.translation.y.max(0.0);
+                shadows.push((
+                    transform.translation,
+                    ball_blob_radius * (1.0 + height * 2.0),
+                ));
+            } else if q_blobs.contains(child) {
+                blobs.push(child);
+            }
+        }
+
+        for (i, (position, radius)) in shadows.iter().enumerate() {
+            let blob_transform = Transform::from_translation(position.with_y(BLOB_HEIGHT))
+                .with_scale(Vec3::new(*radius, 1.0, *radius));
+            if let Some(&blob) = blobs.get(i) {
+                if let Ok(mut transform) = q_blobs.get_mut(blob) {
+                    transform.set_if_neq(blob_transform);
+                }
+            } else {
+                commands.entity(field).with_child((
+                    ShadowBlob,
+                    blob_transform,
+                    Mesh3d(shadow_blob_mesh.0.clone()),
+                    MeshMaterial3d(shadow_blob_mesh.1.clone()),
+                ));
+            }
+        }
+        for &blob in blobs.iter().skip(shadows.len()) {
+            commands.entity(blob).despawn();
+        }
+    }
+}
+
+/// Keeps the balls of small fields visible, see [`FieldScale`]
+fn scale_balls(
+    mut q_balls: Query<(&mut Transform, &ChildOf), With<Ball>>,
+    q_scales: Query<&FieldScale>,
+) {
+    for (mut transform, child_of) in &mut q_balls {
+        let scale = q_scales.get(child_of.parent()).copied().unwrap_or_default();
+        // The transform is relative to the field, which is already scaled
+        let ball_scale = Vec3::splat(scale.local_size(1.0));
+        if transform.scale != ball_scale {
+            transform.scale = ball_scale;
+        }
+    }
+}
+
+/// Rotates the balls as if they rolled without slipping. The host doesn't send the spin of the ball.
+/// Balls are respawned with every world state, so the rotation is kept per field.
+fn roll_balls(
+    time: Res<Time>,
+    mut rotations: Local<HashMap<Entity, Quat>>,
+    mut q_balls: Query<(&mut Transform, Option<&Velocity>, &ChildOf), With<Ball>>,
+) {
+    let mut rolled = HashMap::new();
+    for (mut transform, velocity, child_of) in &mut q_balls {
+        let field = child_of.parent();
+        // Several balls of a field share the rotation of the first one
+        let rotation = *rolled.entry(field).or_insert_with(|| {
+            let rotation = rotations.get(&field).copied().unwrap_or_default();
+            let Some(velocity) = velocity else {
+                return rotation;
+            };
+            let ground_velocity = velocity.0.with_y(0.0);
+            let axis = Vec3::Y.cross(ground_velocity).normalize_or_zero();
+            if axis == Vec3::ZERO {
+                return rotation;
+            }
+            let angle = ground_velocity.length() * time.delta_secs() / BALL_RADIUS;
+            (Quat::from_axis_angle(axis, angle) * rotation).normalize()
+        });
+        transform.rotation = rotation;
+    }
+    // Fields without a ball start over
+    *rotations = rolled;
+}
+
+/// Fading lines behind fast balls, so shots read as motion instead of a jumping dot
+fn draw_ball_streaks(
+    mut gizmos: Gizmos,
+    q_balls: Query<(&Velocity, &GlobalTransform, &ChildOf, &InheritedVisibility), With<Ball>>,
+    q_fields: Query<(&GlobalTransform, &FieldScale), With<Field>>,
+) {
+    /// In m/s, slower balls are easy to follow
+    const MIN_SPEED: f32 = 2.0;
+    /// The streak covers the distance the ball travels in this time (in seconds)
+    const STREAK_DURATION: f32 = 0.06;
+
+    for (velocity, ball_transform, child_of, _) in
+        q_balls.iter().filter(|(.., visible)| visible.get())
+    {
+        let speed = velocity.0.length();
+        let Ok((field_transform, scale)) = q_fields.get(child_of.parent()) else {
+            continue;
+        };
+        if speed < MIN_SPEED {
+            continue;
+        }
+        // Fades in with the speed, so the streak doesn't flicker around the threshold
+        let alpha = ((speed - MIN_SPEED) / MIN_SPEED).clamp(0.0, 1.0) * 0.6;
+        let color = Color::srgba_u8(255, 136, 0, 255).with_alpha(alpha);
+
+        let world_velocity = field_transform.affine().transform_vector3(velocity.0);
+        let side = world_velocity.cross(Vec3::Y).normalize_or_zero();
+        let center = ball_transform.translation();
+        let tail = -world_velocity * STREAK_DURATION;
+        for offset in [0.0, 0.6, -0.6] {
+            let start = center + side * offset * scale.size(BALL_RADIUS);
+            gizmos.line_gradient(start, start + tail, color, color.with_alpha(0.0));
+        }
+    }
+}
+
+fn insert_ghost_model(
+    ghost_robot: &mut EntityCommands,
+    ghost: &GhostRobot,
+    robot_rendering: &RobotRenderSettings,
+    robot_mesh: &RobotMaskMesh,
+    ghost_materials: &GhostMaterials,
+) {
+    if *robot_rendering == RobotRenderSettings::None {
+        return;
+    }
+    let material = match ghost.team {
+        Team::Yellow => ghost_materials.yellow.clone(),
+        Team::Blue => ghost_materials.blue.clone(),
+    };
+    ghost_robot.insert((Mesh3d(robot_mesh.0.clone()), MeshMaterial3d(material)));
+}
+
+/// Ghosts always use simple shapes, so that they can be told apart from the actual robots
+#[allow(clippy::type_complexity)]
+fn render_ghosts(
+    mut commands: Commands,
+    render_settings: Res<RenderSettings>,
+    (robot_mesh, ball_mesh, ghost_materials): (
+        Res<RobotMaskMesh>,
+        Res<BallMesh>,
+        Res<GhostMaterials>,
+    ),
+    q_new_robots: Query<(&GhostRobot, Entity), Added<GhostRobot>>,
+    q_new_balls: Query<Entity, Added<GhostBall>>,
+) {
+    for (robot, robot_entity) in &q_new_robots {
+        insert_ghost_model(
+            &mut commands.entity(robot_entity),
+            robot,
+            &render_settings.robots,
+            &robot_mesh,
+            &ghost_materials,
+        );
+    }
+    if render_settings.ball {
+        for ball_entity in &q_new_balls {
+            commands.entity(ball_entity).insert((
+                Mesh3d(ball_mesh.0.clone()),
+                MeshMaterial3d(ghost_materials.ball.clone()),
+            ));
+        }
+    }
+}
+
+/// Meshes new and changed visualizations, and all of them again when the color overrides or the mesh tolerance change
+#[allow(clippy::too_many_arguments)]
+fn render_visualizations(
+    mut commands: Commands,
+    render_settings: Res<RenderSettings>,
+    material: Res<DefaultMaterial>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut mesh_cache: ResMut<VisMeshCache>,
+    mesh_tolerance: Res<VisMeshTolerance>,
+    quality: Res<ContentQuality>,
+    q_visualizations: Query<(&VisualizationData, &ChildOf, Entity)>,
+    q_changed: Query<(&VisualizationData, &ChildOf, Entity), Changed<VisualizationData>>,
+    q_fields: Query<(&AvailableVisualizations, Ref<VisColorOverrides>)>,
+) {
+    if !render_settings.visualizations {
+        return;
+    }
+    let full_pass = render_settings.is_changed()
+        || mesh_tolerance.is_changed()
+        || q_fields.iter().any(|(_, overrides)| overrides.is_changed());
+    let visualizations: Box<dyn Iterator<Item = _>> = if full_pass {
+        Box::new(q_visualizations.iter())
+    } else {
+        Box::new(q_changed.iter())
+    };
+    for (visualization, child_of, vis_entity) in visualizations {
+        let (names, overrides) = q_fields.get(child_of.parent()).ok().unzip();
+        let name = names.and_then(|names| names.visualizations.get(&visualization.id));
+        let visualization = match name
+            .zip(overrides)
+            .and_then(|(name, o)| o.0.get(name).copied())
+        {
+            Some(color) => recolored(&visualization, color),
+            None => visualization.0.clone(),
+        };
+
+        // Translucent meshes are sorted by the position of their entity, so the entity is moved to the center of the
+        // visualization and the mesh is generated relative to it. This sorts the visualizations of a field back to
+        // front. Custom geometry is spawned relative to the entity, so those visualizations stay at the origin.
+        let mut bounds = visualization_part_bounds(&visualization);
+        let origin = if bounds.is_empty() || bounds.iter().any(|(_, size)| !size.is_finite()) {
+            Vec3::ZERO
+        } else {
+            let (min, max) = bounds.iter().fold(
+                (Vec3::INFINITY, Vec3::NEG_INFINITY),
+                |(min, max), (center, _)| (min.min(*center), max.max(*center)),
+            );
+            (min + max) / 2.0
+        };
+        bounds.iter_mut().for_each(|(center, _)| *center -= origin);
+        let circle_detail = quality.circle_detail();
+        let hash = with_circle_detail(
+            vis_mesh_key(&visualization, origin, mesh_tolerance.0),
+            circle_detail,
+        );
+        let layer = (visualization.id % VIS_LAYERS) as f32 * VIS_LAYER_HEIGHT;
+        commands.entity(vis_entity).insert((
+            VisPartBounds(bounds),
+            Transform::from_translation(origin + Vec3::Y * layer),
+        ));
+
+        // Hosts resend unchanged visualizations regularly, so most meshes already exist
+        if let Some(vis_mesh) = mesh_cache
+            .ready
+            .get(&hash)
+            .and_then(|id| mesh_assets.get_strong_handle(*id))
+        {
+            commands.entity(vis_entity).insert((
+                Mesh3d(vis_mesh),
+                MeshMaterial3d(material.visualization.clone()),
+            ));
+            continue;
+        }
+
+        if !mesh_cache.pending.contains_key(&hash) {
+            // Only the name of this visualization is needed for warnings
+            let vis_names = name.map(|name| AvailableVisualizations {
+                sources: default(),
+                visualizations: HashMap::from([(visualization.id, name.clone())]),
+            });
+            let task = AsyncComputeTaskPool::get().spawn(async move {
+                visualization_mesh(
+                    std::slice::from_ref(&visualization),
+                    vis_names.as_ref(),
+                    circle_detail,
+                )
+                .translated_by(-origin)
+            });
+            mesh_cache.pending.insert(hash, task);
+        }
+        commands.entity(vis_entity).insert(PendingVisMesh(hash));
+    }
+}
+
+/// Replaces the border and fill colors of all parts, keeping their alpha
+fn recolored(visualization: &Visualization, color: Color) -> Visualization {
+    let [red, green, blue, _] = color.to_srgba().to_u8_array().map(u32::from);
+    let recolor = |c: &mut ProtoColor| {
+        (c.red, c.green, c.blue) = (red, green, blue);
+    };
+    let mut visualization = visualization.clone();
+    for part in &mut visualization.part {
+        if let Some(border_color) = part
+            .border_style
+            .as_mut()
+            .and_then(|style| style.color.as_mut())
+        {
+            recolor(border_color);
+        }
+        if let Some(fill_color) = &mut part.fill_color {
+            recolor(fill_color);
+        }
+    }
+    visualization
+}
+
+/// Hides visualizations whose parts all appear too small from every camera, and fades them out close to the limit.
+/// Orthographic cameras like the top-down minimap don't get closer or farther, so they are ignored.
+#[allow(clippy::type_complexity)]
+fn cull_small_visualizations(
+    render_settings: Res<RenderSettings>,
+    material: Res<DefaultMaterial>,
+    fade_materials: Res<VisFadeMaterials>,
+    q_cameras: Query<(&Camera, &Projection, &GlobalTransform)>,
+    mut q_visualizations: Query<(
+        &VisPartBounds,
+        &GlobalTransform,
+        &mut Visibility,
+        &mut MeshMaterial3d<StandardMaterial>,
+    )>,
+) {
+    let cull_angle = render_settings.vis_cull_angle;
+    let eyes: Vec<Vec3> = q_cameras
+        .iter()
+        .filter(|(camera, projection, _)| {
+            camera.is_active && !matches!(projection, Projection::Orthographic(_))
+        })
+        .map(|(.., transform)| transform.translation())
+        .collect();
+
+    for (bounds, transform, mut visibility, mut vis_material) in &mut q_visualizations {
+        // 0 at the cull angle, 1 at twice the cull angle
+        let fade = if cull_angle <= 0.0 || eyes.is_empty() {
+            1.0
+        } else {
+            // Largest angle of any part from any camera
+            let scale = transform.scale().max_element();
+            let angle = bounds
+                .0
+                .iter()
+                .flat_map(|(center, size)| {
+                    let center = transform.transform_point(*center);
+                    eyes.iter()
+                        .map(move |eye| size * scale / eye.distance(center).max(0.01))
+                })
+                .fold(0.0, f32::max);
+            (angle / cull_angle - 1.0).clamp(0.0, 1.0)
+        };
+        if fade == 0.0 {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        visibility.set_if_neq(Visibility::Inherited);
+        let new_material = if fade >= 1.0 {
+            &material.visualization
+        } else {
+            let step = (fade * fade_materials.0.len() as f32) as usize;
+            &fade_materials.0[step.min(fade_materials.0.len() - 1)]
+        };
+        if vis_material.0 != *new_material {
+            vis_material.0 = new_material.clone();
+        }
+    }
+}
+
+/// Adds the meshes of finished tessellation tasks to the waiting visualizations
+fn apply_vis_meshes(
+    mut commands: Commands,
+    material: Res<DefaultMaterial>,
+    mut mesh_assets: ResMut<Assets<Mesh>>,
+    mut mesh_cache: ResMut<VisMeshCache>,
+    q_pending: Query<(&PendingVisMesh, Entity)>,
+) {
+    let mut finished = HashMap::new();
+    mesh_cache
+        .pending
+        .retain(|hash, task| match block_on(future::poll_once(task)) {
+            Some(mesh) => {
+                finished.insert(*hash, mesh_assets.add(mesh));
+                false
+            }
+            None => true,
+        });
+    if finished.is_empty() {
+        return;
+    }
+
+    for (pending, vis_entity) in &q_pending {
+        if let Some(vis_mesh) = finished.get(&pending.0) {
+            // The visualization might have been replaced in the meantime
+            commands
+                .entity(vis_entity)
+                .try_remove::<PendingVisMesh>()
+                .try_insert((
+                    Mesh3d(vis_mesh.clone()),
+                    MeshMaterial3d(material.visualization.clone()),
+                ));
+        }
+    }
+
+    // Meshes are freed once no visualization uses them anymore
+    mesh_cache.ready.retain(|_, id| mesh_assets.contains(*id));
+    mesh_cache
+        .ready
+        .extend(finished.iter().map(|(hash, handle)| (*hash, handle.id())));
+}
+
+/// Draws battery, kicker and radio levels as stacked bars above each robot
+fn draw_telemetry_bars(
+    mut gizmos: Gizmos,
+    q_robots: Query<(&Telemetry, &GlobalTransform, &ChildOf, &InheritedVisibility)>,
+    q_scales: Query<&FieldScale>,
+) {
+    const BAR_LENGTH: f32 = 0.16;
+    const BAR_SPACING: f32 = 0.025;
+    const HEIGHT: f32 = 0.22;
+
+    for (telemetry, transform, child_of, _) in q_robots.iter().filter(|(.., visible)| visible.get())
+    {
+        let scale = q_scales.get(child_of.parent()).copied().unwrap_or_default();
+        let bar_length = scale.size(BAR_LENGTH);
+        let levels = [
+            telemetry.battery_level(),
+            telemetry.kicker_charge.map(|charge| charge.clamp(0.0, 1.0)),
+            telemetry.radio_level(),
+        ];
+
+        let mut start =
+            transform.translation() + Vec3::new(-bar_length / 2.0, scale.size(HEIGHT), 0.0);
+        for level in levels.into_iter().flatten() {
+            let end = start + Vec3::X * bar_length;
+            let color = Color::srgb(1.0 - level, level, 0.0);
+            gizmos.line(start, end, Color::srgba(0.2, 0.2, 0.2, 0.6));
+            gizmos.line(start, start.lerp(end, level), color);
+            start.y += scale.size(BAR_SPACING);
+        }
+    }
+}
+
+/// Draws measured distances slightly above the field, with markers at both ends
+fn draw_measurements(
+    mut gizmos: Gizmos,
+    q_measurements: Query<(&Measurement, &ChildOf)>,
+    q_fields: Query<(&GlobalTransform, &FieldScale, &InheritedVisibility), With<Field>>,
+) {
+    const HEIGHT: f32 = 0.01;
+    const MARKER_RADIUS: f32 = 0.03;
+    let color = Color::srgb(1.0, 0.4, 0.9);
+
+    for (measurement, child_of) in &q_measurements {
+        let Ok((field_transform, scale, visible)) = q_fields.get(child_of.parent()) else {
+            continue;
+        };
+        if !visible.get() {
+            continue;
+        }
+        let [start, end] = [measurement.start, measurement.end]
+            .map(|point| field_transform.transform_point(field_to_local(point) + Vec3::Y * HEIGHT));
+        gizmos.line(start, end, color);
+        for point in [start, end] {
+            gizmos.circle(
+                Isometry3d::new(
+                    point,
+                    field_transform.rotation() * Quat::from_rotation_x(FRAC_PI_2),
+                ),
+                scale.size(MARKER_RADIUS),
+                color,
+            );
+        }
+    }
+}
+
+struct EventCue {
+    field: Entity,
+    kind: GameEventKind,
+    position: Vec3,
+    start: Instant,
+}
+
+/// Flashes an expanding ring on the field for every game event
+fn draw_event_cues(
+    mut gizmos: Gizmos,
+    mut game_events: MessageReader<GameEvent>,
+    mut cues: Local<Vec<EventCue>>,
+    q_fields: Query<(&GlobalTransform, &FieldScale, &InheritedVisibility), With<Field>>,
+) {
+    fn cue_style(kind: &GameEventKind) -> (Duration, f32, Color) {
+        match kind {
+            GameEventKind::Goal {
+                scoring_team: Team::Yellow,
+            } => (Duration::from_millis(1500), 1.0, Color::srgb(1.0, 0.9, 0.0)),
+            GameEventKind::Goal {
+                scoring_team: Team::Blue,
+            } => (Duration::from_millis(1500), 1.0, Color::srgb(0.1, 0.4, 1.0)),
+            GameEventKind::Kick { .. } => (Duration::from_millis(400), 0.2, Color::WHITE),
+            GameEventKind::OutOfBounds => {
+                (Duration::from_millis(800), 0.4, Color::srgb(1.0, 0.2, 0.2))
+            }
+            GameEventKind::NearCollision { .. } => {
+                (Duration::from_millis(600), 0.3, Color::srgb(1.0, 0.4, 0.0))
+            }
+        }
+    }
+
+    let now = Instant::now();
+    cues.extend(game_events.read().map(|event| EventCue {
+        field: event.field,
+        kind: event.kind,
+        position: event.position,
+        start: now,
+    }));
+    cues.retain(|cue| now - cue.start < cue_style(&cue.kind).0);
+
+    for cue in cues.iter() {
+        let Ok((field_transform, scale, visible)) = q_fields.get(cue.field) else {
+            continue;
+        };
+        if !visible.get() {
+            continue;
+        }
+        let (duration, max_radius, color) = cue_style(&cue.kind);
+        let progress = (now - cue.start).as_secs_f32() / duration.as_secs_f32();
+        // Gizmo circles are in the xy plane, the rings lie flat on the field
+        let isometry = Isometry3d::new(
+            field_transform.transform_point(cue.position),
+            field_transform.rotation() * Quat::from_rotation_x(FRAC_PI_2),
+        );
+        gizmos.circle(
+            isometry,
+            scale.size(max_radius) * progress,
+            color.with_alpha(1.0 - progress),
+        );
+    }
+}
+
+/// Highlights the part of the field boundary where the ball left the field, so it is clear why play stopped
+fn draw_out_of_bounds_lines(
+    mut gizmos: Gizmos,
+    mut game_events: MessageReader<GameEvent>,
+    mut crossings: Local<Vec<(Entity, Vec3, Instant)>>,
+    q_fields: Query<
+        (
+            &FieldGeometry,
+            &GlobalTransform,
+            &FieldScale,
+            &InheritedVisibility,
+        ),
+        With<Field>,
+    >,
+) {
+    const DURATION: Duration = Duration::from_millis(2500);
+    const SEGMENT_LENGTH: f32 = 1.0;
+    const HEIGHT: f32 = 0.005;
+    const PULSE_FREQUENCY: f32 = 3.0;
+    const LINE_SPACING: f32 = 0.01;
+    let color = Color::srgb(1.0, 0.2, 0.2);
+
+    let now = Instant::now();
+    crossings.extend(
+        game_events
+            .read()
+            .filter(|event| event.kind == GameEventKind::OutOfBounds)
+            .map(|event| (event.field, event.position, now)),
+    );
+    crossings.retain(|(.., start)| now - *start < DURATION);
+
+    for (field, position, start) in crossings.iter() {
+        let Ok((geom, field_transform, scale, visible)) = q_fields.get(*field) else {
+            continue;
+        };
+        if !visible.get() {
+            continue;
+        }
+        let half_size = geom.play_area_size / 2.0;
+        let outside = position.xz().abs() - half_size;
+        // The line that was crossed furthest is the one the ball left through, corners count for the goal lines
+        let (start_point, end_point) = if outside.x >= outside.y {
+            let x = half_size.x.copysign(position.x);
+            let z = position.z.clamp(
+                -half_size.y + SEGMENT_LENGTH / 2.0,
+                half_size.y - SEGMENT_LENGTH / 2.0,
+            );
+            (
+                Vec3::new(x, HEIGHT, z - SEGMENT_LENGTH / 2.0),
+                Vec3::new(x, HEIGHT, z + SEGMENT_LENGTH / 2.0),
+            )
+        } else {
+            let z = half_size.y.copysign(position.z);
+            let x = position.x.clamp(
+                -half_size.x + SEGMENT_LENGTH / 2.0,
+                half_size.x - SEGMENT_LENGTH / 2.0,
+            );
+            (
+                Vec3::new(x - SEGMENT_LENGTH / 2.0, HEIGHT, z),
+                Vec3::new(x + SEGMENT_LENGTH / 2.0, HEIGHT, z),
+            )
+        };
+
+        let elapsed = (now - *start).as_secs_f32();
+        let pulse = 0.75 + 0.25 * (elapsed * PULSE_FREQUENCY * TAU).cos();
+        let alpha = pulse * (1.0 - elapsed / DURATION.as_secs_f32());
+        // Parallel lines slightly apart, so the highlight is thicker than the field lines it covers
+        let offset =
+            (end_point - start_point).cross(Vec3::Y).normalize() * scale.local_size(LINE_SPACING);
+        for side in [-1.0, 0.0, 1.0] {
+            gizmos.line(
+                field_transform.transform_point(start_point + offset * side),
+                field_transform.transform_point(end_point + offset * side),
+                color.with_alpha(alpha),
+            );
+        }
+    }
+}
+
+/// Flashes a line between robots that nearly collided, following the robots while it is shown
+fn draw_near_collision_markers(
+    mut gizmos: Gizmos,
+    mut game_events: MessageReader<GameEvent>,
+    mut markers: Local<Vec<(Entity, [(Team, u32); 2], Instant)>>,
+    q_robots: Query<(&Robot, &Team, &Transform, &ChildOf)>,
+    q_fields: Query<(&GlobalTransform, &FieldScale, &InheritedVisibility), With<Field>>,
+) {
+    const DURATION: Duration = Duration::from_millis(1500);
+    const FLASH_FREQUENCY: f32 = 6.0;
+    /// Just above the robot covers
+    const HEIGHT: f32 = 0.16;
+    const MARKER_RADIUS: f32 = 0.04;
+    let color = Color::srgb(1.0, 0.4, 0.0);
+
+    let now = Instant::now();
+    markers.extend(game_events.read().filter_map(|event| match event.kind {
+        GameEventKind::NearCollision { robots, .. } => Some((event.field, robots, now)),
+        _ => None,
+    }));
+    markers.retain(|(.., start)| now - *start < DURATION);
+
+    for (field, robots, start) in markers.iter() {
+        let Ok((field_transform, scale, visible)) = q_fields.get(*field) else {
+            continue;
+        };
+        if !visible.get() {
+            continue;
+        }
+        let position = |(team, id): (Team, u32)| {
+            q_robots
+                .iter()
+                .find(|(robot, robot_team, _, child_of)| {
+                    child_of.parent() == *field && **robot_team == team && robot.0 as u32 == id
+                })
+                .map(|(.., transform, _)| {
+                    field_transform.transform_point(transform.translation + Vec3::Y * HEIGHT)
+                })
+        };
+        // One of the robots disappeared
+        let (Some(a), Some(b)) = (position(robots[0]), position(robots[1])) else {
+            continue;
+        };
+
+        let elapsed = (now - *start).as_secs_f32();
+        if (elapsed * FLASH_FREQUENCY).fract() > 0.5 {
+            continue;
+        }
+        let alpha = 1.0 - elapsed / DURATION.as_secs_f32();
+        gizmos.line(a, b, color.with_alpha(alpha));
+        gizmos.sphere(
+            Isometry3d::from_translation(a.midpoint(b)),
+            scale.size(MARKER_RADIUS),
+            color.with_alpha(alpha),
+        );
+    }
+}
+
+/// Outlines a defense area in pulsing red while robots of the other team touch it, and circles the intruders
+fn draw_defense_area_intrusions(
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+    q_fields: Query<
+        (
+            &FieldGeometry,
+            &GlobalTransform,
+            &FieldScale,
+            &Children,
+            &InheritedVisibility,
+        ),
+        With<Field>,
+    >,
+    q_robots: Query<(&Team, &Transform), With<Robot>>,
+) {
+    const HEIGHT: f32 = 0.005;
+    const PULSE_FREQUENCY: f32 = 2.0;
+    const LINE_SPACING: f32 = 0.01;
+    const RING_MARGIN: f32 = 0.03;
+    let color = Color::srgb(1.0, 0.1, 0.1);
+
+    let pulse = 0.6 + 0.4 * (time.elapsed_secs() * PULSE_FREQUENCY * TAU).cos();
+    for (geom, field_transform, scale, children, _) in
+        q_fields.iter().filter(|(.., visible)| visible.get())
+    {
+        let line_spacing = scale.local_size(LINE_SPACING);
+        let half_x = geom.play_area_size.x / 2.0;
+        let half_depth = geom.defense_size.y / 2.0;
+
+        // The yellow goal is on the -x side of the field
+        for (defending_team, side) in [(Team::Yellow, -1.0), (Team::Blue, 1.0)] {
+            // Touching the area with any part of the robot counts, the lines belong to the area
+            let intruders: Vec<_> = children
+                .iter()
+                .filter_map(|child| q_robots.get(child).ok())
+                .filter(|(team, transform)| {
+                    let depth = half_x - transform.translation.x * side;
+                    **team != defending_team
+                        && (-ROBOT_RADIUS..geom.defense_size.x + ROBOT_RADIUS).contains(&depth)
+                        && transform.translation.z.abs() < half_depth + ROBOT_RADIUS
+                })
+                .map(|(_, transform)| transform.translation)
+                .collect();
+            if intruders.is_empty() {
+                continue;
+            }
+
+            let color = color.with_alpha(pulse);
+            let inner_x = (half_x - geom.defense_size.x) * side;
+            // Slightly larger outlines, so the highlight is thicker than the field lines it covers
+            for offset in [0.0, line_spacing, 2.0 * line_spacing] {
+                let outline = [
+                    Vec3::new((half_x + offset) * side, HEIGHT, -half_depth - offset),
+                    Vec3::new(inner_x - offset * side, HEIGHT, -half_depth - offset),
+                    Vec3::new(inner_x - offset * side, HEIGHT, half_depth + offset),
+                    Vec3::new((half_x + offset) * side, HEIGHT, half_depth + offset),
+                ];
+                gizmos.linestrip(
+                    outline.map(|point| field_transform.transform_point(point)),
+                    color,
+                );
+            }
+            // Gizmo circles are in the xy plane, the rings lie flat on the field
+            for intruder in intruders {
+                let isometry = Isometry3d::new(
+                    field_transform.transform_point(intruder.with_y(HEIGHT)),
+                    field_transform.rotation() * Quat::from_rotation_x(FRAC_PI_2),
+                );
+                // The ring is around the scaled robot, only its margin is sized like an annotation
+                let radius = ROBOT_RADIUS * scale.0 + scale.size(RING_MARGIN);
+                gizmos.circle(isometry, radius, color);
+            }
+        }
+    }
+}
+
+/// Outlines the half of a team outside the field boundary while the team is in a timeout or has an active yellow card
+fn draw_team_situation_rings(
+    mut gizmos: Gizmos,
+    q_fields: Query<
+        (
+            &GameState,
+            &FieldGeometry,
+            &GlobalTransform,
+            &FieldScale,
+            &InheritedVisibility,
+        ),
+        With<Field>,
+    >,
+) {
+    const MARGIN: f32 = 0.05;
+    const HEIGHT: f32 = 0.005;
+    let timeout_color = Color::srgba(0.3, 0.8, 1.0, 0.6);
+    let card_color = Color::srgba(1.0, 0.75, 0.0, 0.6);
+
+    for (game_state, geom, field_transform, scale, _) in
+        q_fields.iter().filter(|(.., visible)| visible.get())
+    {
+        let half_size = geom.play_area_size / 2.0 + geom.boundary_width + scale.local_size(MARGIN);
+        // The yellow goal is on the -x side of the field
+        for (team_state, side) in [
+            (&game_state.yellow_team, -1.0),
+            (&game_state.blue_team, 1.0),
+        ] {
+            let Some(team_state) = team_state else {
+                continue;
+            };
+            let color = if team_state.timeout_active() {
+                timeout_color
+            } else if !team_state.yellow_card_times.is_empty() {
+                card_color
+            } else {
+                continue;
+            };
+            let corners = [
+                Vec2::new(0.0, -half_size.y),
+                Vec2::new(side * half_size.x, -half_size.y),
+                Vec2::new(side * half_size.x, half_size.y),
+                Vec2::new(0.0, half_size.y),
+            ];
+            gizmos.linestrip(
+                corners.map(|corner| {
+                    field_transform.transform_point(field_to_local(corner) + Vec3::Y * HEIGHT)
+                }),
+                color,
+            );
+        }
+    }
+}
+
+/// Rings around robots that are flagged by the robot count of their field
+fn draw_robot_flags(
+    mut gizmos: Gizmos,
+    q_robots: Query<(&RobotFlag, &GlobalTransform, &ChildOf, &InheritedVisibility)>,
+    q_scales: Query<&FieldScale>,
+) {
+    const RADIUS: f32 = 0.13;
+    const HEIGHT: f32 = 0.01;
+
+    for (flag, robot_transform, child_of, _) in
+        q_robots.iter().filter(|(.., visible)| visible.get())
+    {
+        let scale = q_scales.get(child_of.parent()).copied().unwrap_or_default();
+        let color = match flag {
+            RobotFlag::Excess => Color::srgb(1.0, 0.2, 0.2),
+            RobotFlag::Substitution => Color::srgb(0.3, 1.0, 0.5),
+        };
+        // Flat on the field below the robot, gizmo circles are in the xy plane
+        let rotation = robot_transform.rotation() * Quat::from_rotation_x(FRAC_PI_2);
+        let position = robot_transform.translation() + robot_transform.up() * scale.size(HEIGHT);
+        gizmos.circle(
+            Isometry3d::new(position, rotation),
+            scale.size(RADIUS),
+            color,
+        );
+    }
+}
+
+/// Small shapes above the robots in the color of their strategy role:
+/// Keeper square, defender circle, midfielder diamond, striker triangle
+fn draw_role_icons(
+    mut gizmos: Gizmos,
+    q_robots: Query<(&Telemetry, &GlobalTransform, &ChildOf, &InheritedVisibility)>,
+    q_scales: Query<&FieldScale>,
+) {
+    const SIZE: f32 = 0.05;
+    const HEIGHT: f32 = 0.2;
+
+    for (telemetry, robot_transform, child_of, _) in
+        q_robots.iter().filter(|(.., visible)| visible.get())
+    {
+        let Some(role) = telemetry.role else {
+            continue;
+        };
+        let color = role.color();
+        let scale = q_scales.get(child_of.parent()).copied().unwrap_or_default();
+        let size = scale.size(SIZE);
+        let center = robot_transform.translation() + robot_transform.up() * scale.size(HEIGHT);
+        let (corners, angle) = match role {
+            Role::Defender => {
+                let rotation = robot_transform.rotation() * Quat::from_rotation_x(FRAC_PI_2);
+                gizmos.circle(Isometry3d::new(center, rotation), size, color);
+                continue;
+            }
+            Role::Keeper => (4, FRAC_PI_4),
+            Role::Midfielder => (4, 0.0),
+            Role::Striker => (3, 0.0),
+        };
+        // Regular polygon in the horizontal plane of the robot
+        let points = (0..=corners).map(|i| {
+            let angle = angle + i as f32 * TAU / corners as f32;
+            center + robot_transform.rotation() * Vec3::new(angle.cos(), 0.0, -angle.sin()) * size
+        });
+        gizmos.linestrip(points, color);
+    }
+}
+
+/// Recorded robot paths, colored from blue for the oldest samples to red for the newest ones
+fn draw_path_history(
+    mut gizmos: Gizmos,
+    q_fields: Query<(&PathHistory, &GlobalTransform, &InheritedVisibility), With<Field>>,
+) {
+    const HEIGHT: f32 = 0.01;
+
+    let now = Instant::now();
+    for (history, field_transform, _) in q_fields.iter().filter(|(.., visible)| visible.get()) {
+        for path in history.0.values() {
+            gizmos.linestrip_gradient(path.samples.iter().map(|(time, position)| {
+                let age =
+                    ((now - *time).as_secs_f32() / PATH_HISTORY_DURATION.as_secs_f32()).min(1.0);
+                (
+                    field_transform.transform_point(*position + Vec3::Y * HEIGHT),
+                    Color::hsla(240.0 * age, 0.9, 0.55, 1.0 - 0.6 * age),
+                )
+            }));
+        }
+    }
+}
+
+/// Ballistic arc of balls in the air up to the predicted landing point, so chip kicks don't look like the ball
+/// teleports between its positions on the ground
+fn draw_chip_arcs(
+    mut gizmos: Gizmos,
+    q_balls: Query<(&Transform, &Velocity, &ChildOf), With<Ball>>,
+    q_fields: Query<(&GlobalTransform, &FieldScale, &InheritedVisibility), With<Field>>,
+) {
+    const GRAVITY: f32 = 9.81;
+    /// Balls rolling over uneven ground or with noisy height measurements shouldn't get an arc
+    const MIN_HEIGHT: f32 = 0.05;
+    const MIN_VERTICAL_SPEED: f32 = 0.5;
+    const SEGMENTS: usize = 24;
+    const MARKER_RADIUS: f32 = 0.08;
+    let color = Color::srgba(1.0, 0.55, 0.0, 0.8);
+
+    for (transform, velocity, child_of) in &q_balls {
+        let Ok((field_transform, scale, visible)) = q_fields.get(child_of.parent()) else {
+            continue;
+        };
+        if !visible.get() {
+            continue;
+        }
+        let (position, velocity) = (transform.translation, velocity.0);
+        if position.y < MIN_HEIGHT && velocity.y < MIN_VERTICAL_SPEED {
+            continue;
+        }
+
+        // Time until the ball hits the ground, ignoring air drag and spin
+        let flight_time = (velocity.y
+            + (velocity.y.powi(2) + 2.0 * GRAVITY * position.y.max(0.0)).sqrt())
+            / GRAVITY;
+        let at = |t: f32| position + velocity * t - Vec3::Y * GRAVITY / 2.0 * t * t;
+        gizmos.linestrip(
+            (0..=SEGMENTS).map(|i| {
+                let local = at(flight_time * i as f32 / SEGMENTS as f32);
+                field_transform.transform_point(local.with_y(local.y.max(0.0)))
+            }),
+            color,
+        );
+
+        // Gizmo circles are in the xy plane, the marker lies flat on the field
+        let landing = at(flight_time).with_y(0.005);
+        let isometry = Isometry3d::new(
+            field_transform.transform_point(landing),
+            field_transform.rotation() * Quat::from_rotation_x(FRAC_PI_2),
+        );
+        let marker_radius = scale.size(MARKER_RADIUS);
+        gizmos.circle(isometry, marker_radius, color);
+        gizmos.circle(isometry, marker_radius / 3.0, color);
+    }
+}
+
+/// Marks the front of robots with an armed kicker (pulsing red bar) or an active dribbler (rolling blue ticks),
+/// so the actuators can be checked from across the field
+fn draw_actuator_states(
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+    q_robots: Query<(&Telemetry, &GlobalTransform, &ChildOf, &InheritedVisibility)>,
+    q_scales: Query<&FieldScale>,
+) {
+    const BAR_WIDTH: f32 = 0.07;
+    const KICKER_HEIGHT: f32 = 0.03;
+    const DRIBBLER_HEIGHT: f32 = 0.045;
+    const DRIBBLER_TICKS: usize = 4;
+    /// Ticks per second passing a point, the dribbler rolls the ball towards the robot
+    const DRIBBLER_SPEED: f32 = 6.0;
+
+    let t = time.elapsed_secs();
+    let kicker_color = Color::srgba(1.0, 0.15, 0.1, 0.6 + 0.4 * (t * TAU * 2.0).sin().abs());
+    let dribbler_color = Color::srgb(0.2, 0.7, 1.0);
+
+    for (telemetry, transform, child_of, _) in q_robots.iter().filter(|(.., visible)| visible.get())
+    {
+        let scale = q_scales.get(child_of.parent()).copied().unwrap_or_default();
+        // Robots face their local -z axis
+        let forward = transform.forward().as_vec3();
+        let right = transform.right().as_vec3();
+        let up = transform.up().as_vec3();
+        // The bars stay at the front of the robot, which scales with the field
+        let front = transform.translation() + forward * (ROBOT_RADIUS + 0.005) * scale.0;
+        let half_bar = right * scale.size(BAR_WIDTH) / 2.0;
+
+        if telemetry.kicker_armed == Some(true) {
+            let center = front + up * scale.size(KICKER_HEIGHT);
+            gizmos.line(center - half_bar, center + half_bar, kicker_color);
+        }
+        if telemetry.dribbler_active == Some(true) {
+            let center = front + up * scale.size(DRIBBLER_HEIGHT);
+            gizmos.line(center - half_bar, center + half_bar, dribbler_color);
+            // Short vertical ticks moving along the roller, like the grooves of a spinning dribbler
+            let phase = (t * DRIBBLER_SPEED / DRIBBLER_TICKS as f32).fract();
+            for i in 0..DRIBBLER_TICKS {
+                let offset =
+                    ((i as f32 + phase) / DRIBBLER_TICKS as f32 - 0.5) * scale.size(BAR_WIDTH);
+                let tick = center + right * offset;
+                let tick_height = up * scale.size(0.008);
+                gizmos.line(tick - tick_height, tick + tick_height, dribbler_color);
+            }
+        }
+    }
+}
+
+/// Planned passes and robot targets as dashed lines moving towards the target, so plans stand out from the
+/// static debug lines of generic visualizations
+fn draw_strategy_plans(
+    mut gizmos: Gizmos,
+    time: Res<Time>,
+    q_fields: Query<
+        (
+            &FieldPlans,
+            &GlobalTransform,
+            &FieldScale,
+            Entity,
+            &InheritedVisibility,
+        ),
+        With<Field>,
+    >,
+    q_robots: Query<(&Robot, &Team, &GlobalTransform, &ChildOf)>,
+) {
+    const DASH_LENGTH: f32 = 0.1;
+    /// In meters per second
+    const DASH_SPEED: f32 = 0.4;
+    const TARGET_SIZE: f32 = 0.06;
+    const HEIGHT: f32 = 0.02;
+    let pass_color = |team: Team| match team {
+        Team::Yellow => Color::srgb(1.0, 0.9, 0.2),
+        Team::Blue => Color::srgb(0.3, 0.6, 1.0),
+    };
+
+    // Dashes and gaps have the same length, the pattern moves from the start towards the target
+    let dashed_line =
+        |gizmos: &mut Gizmos, from: Vec3, to: Vec3, color: Color, scale: &FieldScale| {
+            let length = from.distance(to);
+            if length < 0.001 {
+                return;
+            }
+            let dash_length = scale.size(DASH_LENGTH);
+            let phase = (time.elapsed_secs() * scale.size(DASH_SPEED)) % (2.0 * dash_length);
+            let dir = (to - from) / length;
+            let mut start = phase - 2.0 * dash_length;
+            while start < length {
+                let (a, b) = (start.max(0.0), (start + dash_length).min(length));
+                if b > a {
+                    gizmos.line(from + dir * a, from + dir * b, color);
+                }
+                start += 2.0 * dash_length;
+            }
+        };
+
+    for (plans, field_transform, scale, field_entity, _) in
+        q_fields.iter().filter(|(.., visible)| visible.get())
+    {
+        let to_world =
+            |p: Vec2| field_transform.transform_point(field_to_local(p) + Vec3::Y * HEIGHT);
+
+        for pass in &plans.passes {
+            let (from, to) = (to_world(pass.from), to_world(pass.to));
+            dashed_line(&mut gizmos, from, to, pass_color(pass.team), scale);
+        }
+
+        for target in &plans.targets {
+            let robot = q_robots.iter().find(|(robot, team, _, child_of)| {
+                child_of.parent() == field_entity
+                    && **team == target.team
+                    && robot.0 as u32 == target.robot
+            });
+            let to = to_world(target.position);
+            let color = pass_color(target.team).with_alpha(0.6);
+            if let Some((_, _, robot_transform, _)) = robot {
+                let from = robot_transform.translation() + robot_transform.up() * HEIGHT;
+                dashed_line(&mut gizmos, from, to, color, scale);
+            }
+            // Cross at the target, flat on the field
+            let rotation = field_transform.rotation();
+            for diagonal in [Vec3::new(1.0, 0.0, 1.0), Vec3::new(1.0, 0.0, -1.0)] {
+                let offset = rotation * diagonal * (scale.size(TARGET_SIZE) / 2.0);
+                gizmos.line(to - offset, to + offset, color);
+            }
+        }
+    }
+}
+
+/// Connects ghost robots with the actual robots they deviate from
+fn draw_ghost_offsets(
+    mut gizmos: Gizmos,
+    q_ghost_robots: Query<(&GhostRobot, &GlobalTransform, &ChildOf)>,
+    q_ghost_sources: Query<(&ChildOf, &InheritedVisibility), With<GhostSource>>,
+    q_robots: Query<(&Robot, &Team, &GlobalTransform, &ChildOf)>,
+    q_scales: Query<&FieldScale>,
+) {
+    const MIN_OFFSET: f32 = 0.01;
+
+    for (ghost, ghost_transform, ghost_child_of) in &q_ghost_robots {
+        let Ok((field, visible)) = q_ghost_sources.get(ghost_child_of.parent()) else {
+            continue;
+        };
+        if !visible.get() {
+            continue;
+        }
+        let actual_robot = q_robots.iter().find(|(robot, team, _, child_of)| {
+            child_of.parent() == field.parent()
+                && **team == ghost.team
+                && robot.0 as u32 == ghost.id
+        });
+        let scale = q_scales.get(field.parent()).copied().unwrap_or_default();
+        if let Some((_, _, robot_transform, _)) = actual_robot
+            && robot_transform
+                .translation()
+                .distance(ghost_transform.translation())
+                > scale.size(MIN_OFFSET)
+        {
+            gizmos.line(
+                robot_transform.translation(),
+                ghost_transform.translation(),
+                Color::srgb(1.0, 0.2, 0.8),
+            );
+        }
+    }
+}
+
+/// Dots of the standard robot marker pattern around the team dot, in the local frame of a robot (facing -z):
+/// front left, front right, back left, back right
+const MARKER_DOT_OFFSETS: [Vec3; 4] = [
+    Vec3::new(-0.054772, 0.0, -0.035),
+    Vec3::new(0.054772, 0.0, -0.035),
+    Vec3::new(-0.035, 0.0, 0.054772),
+    Vec3::new(0.035, 0.0, 0.054772),
+];
+
+/// Whether each of the [`MARKER_DOT_OFFSETS`] is pink (otherwise green), indexed by the robot id
+const MARKER_PATTERNS: [[bool; 4]; 16] = [
+    [true, false, true, true],
+    [true, false, false, true],
+    [false, false, false, true],
+    [false, false, true, true],
+    [true, true, true, false],
+    [true, true, false, false],
+    [false, true, false, false],
+    [false, true, true, false],
+    [false, false, false, false],
+    [true, true, true, true],
+    [true, true, false, true],
+    [false, false, true, false],
+    [true, false, false, false],
+    [false, true, true, true],
+    [true, false, true, false],
+    [false, true, false, true],
+];
+
+/// The marker pattern of each robot's vision id floating above its cutout, to compare it with the markers of the
+/// real robot below. A red ring warns if a ghost source sees another robot at the same position.
+fn draw_marker_tags(
+    mut gizmos: Gizmos,
+    q_robots: Query<(
+        &Robot,
+        &Team,
+        &GlobalTransform,
+        &ChildOf,
+        &InheritedVisibility,
+    )>,
+    q_ghost_robots: Query<(&GhostRobot, &GlobalTransform, &ChildOf)>,
+    q_ghost_sources: Query<&ChildOf, With<GhostSource>>,
+    q_scales: Query<&FieldScale>,
+) {
+    const HEIGHT: f32 = 0.25;
+    const TEAM_DOT_RADIUS: f32 = 0.025;
+    const ID_DOT_RADIUS: f32 = 0.02;
+    const WARNING_RADIUS: f32 = 0.1;
+    /// Robots of two sources that are closer than a robot radius are the same physical robot
+    const MATCH_DISTANCE: f32 = 0.09;
+    let team_color = |team: Team| match team {
+        Team::Yellow => Color::srgb(1.0, 0.9, 0.2),
+        Team::Blue => Color::srgb(0.3, 0.6, 1.0),
+    };
+    let pink = Color::srgb(1.0, 0.35, 0.75);
+    let green = Color::srgb(0.3, 0.9, 0.3);
+    let warning_color = Color::srgb(1.0, 0.1, 0.1);
+
+    for (robot, team, transform, child_of, _) in
+        q_robots.iter().filter(|(.., visible)| visible.get())
+    {
+        let scale = q_scales.get(child_of.parent()).copied().unwrap_or_default();
+        let center = transform.translation() + transform.up() * scale.size(HEIGHT);
+        // Flat like the markers on the robot, gizmo circles are in the xy plane
+        let rotation = transform.rotation() * Quat::from_rotation_x(FRAC_PI_2);
+        gizmos.circle(
+            Isometry3d::new(center, rotation),
+            scale.size(TEAM_DOT_RADIUS),
+            team_color(*team),
+        );
+        // Ids above 15 have no standard pattern, only the team dot is shown
+        if let Some(pattern) = MARKER_PATTERNS.get(robot.0 as usize) {
+            for (offset, is_pink) in MARKER_DOT_OFFSETS.iter().zip(pattern) {
+                let position = center + transform.rotation() * scale.size(1.0) * *offset;
+                let color = if *is_pink { pink } else { green };
+                let radius = scale.size(ID_DOT_RADIUS);
+                gizmos.circle(Isometry3d::new(position, rotation), radius, color);
+            }
+        }
+
+        let mismatch = q_ghost_robots
+            .iter()
+            .any(|(ghost, ghost_transform, ghost_child_of)| {
+                q_ghost_sources
+                    .get(ghost_child_of.parent())
+                    .is_ok_and(|source| source.parent() == child_of.parent())
+                    && ghost_transform
+                        .translation()
+                        .distance(transform.translation())
+                        < MATCH_DISTANCE * scale.0
+                    && (ghost.id != robot.0 as u32 || ghost.team != *team)
+            });
+        if mismatch {
+            gizmos.circle(
+                Isometry3d::new(center, rotation),
+                scale.size(WARNING_RADIUS),
+                warning_color,
+            );
+        }
+    }
+}
@@ -0,0 +1,149 @@
+use crate::{Field, GameState, Robot, Team, WorldStateUpdated, update_world_state};
+use bevy::prelude::*;
+use std::cmp::Reverse;
+use std::collections::HashSet;
+use std::time::{Duration, Instant};
+
+/// Robots that entered the field while a substitution is allowed are flagged this long
+const SUBSTITUTION_FLAG_DURATION: Duration = Duration::from_secs(5);
+
+pub(crate) fn robot_count_plugin(app: &mut App) {
+    app.register_type::<RobotCount>();
+    app.add_systems(PostUpdate, flag_robots.after(update_world_state));
+}
+
+#[derive(Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Debug, Default, Clone, PartialEq)]
+pub struct TeamRobotCount {
+    pub on_field: u32,
+    /// From the referee, not set if the host doesn't send it
+    pub allowed: Option<u32>,
+}
+
+impl TeamRobotCount {
+    pub fn excess(&self) -> u32 {
+        self.allowed
+            .map_or(0, |allowed| self.on_field.saturating_sub(allowed))
+    }
+}
+
+/// Number of robots of each team in the displayed world state of a field
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
+pub struct RobotCount {
+    pub yellow: TeamRobotCount,
+    pub blue: TeamRobotCount,
+}
+
+impl RobotCount {
+    pub fn team(&self, team: Team) -> &TeamRobotCount {
+        match team {
+            Team::Yellow => &self.yellow,
+            Team::Blue => &self.blue,
+        }
+    }
+}
+
+/// Marks robots that need attention, e.g. to highlight them while practicing substitutions
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RobotFlag {
+    /// The team has more robots on the field than allowed, the robots that entered last are flagged
+    Excess,
+    /// Entered the field while a substitution is allowed
+    Substitution,
+}
+
+/// When the robot appeared in the world state, `None` if it was there in the first world state of its field
+#[derive(Component, Debug)]
+struct EnteredAt(Option<Instant>);
+
+#[allow(clippy::type_complexity)]
+fn flag_robots(
+    mut commands: Commands,
+    mut world_state_updates: MessageReader<WorldStateUpdated>,
+    mut tracked_fields: Local<HashSet<Entity>>,
+    mut q_fields: Query<(&GameState, &mut RobotCount, Entity), With<Field>>,
+    q_robots: Query<
+        (
+            &Team,
+            &ChildOf,
+            Option<&EnteredAt>,
+            Option<&RobotFlag>,
+            Entity,
+        ),
+        With<Robot>,
+    >,
+) {
+    let now = Instant::now();
+    // Robots in the first world state of a field were there before it was connected, they didn't just enter
+    let first_updates: HashSet<Entity> = world_state_updates
+        .read()
+        .map(|update| update.field)
+        .filter(|field| !tracked_fields.contains(field))
+        .collect();
+
+    for (game_state, mut robot_count, field_entity) in &mut q_fields {
+        let entered_now = (!first_updates.contains(&field_entity)).then_some(now);
+        let mut new_count = RobotCount::default();
+        for team in [Team::Yellow, Team::Blue] {
+            let team_state = match team {
+                Team::Yellow => game_state.yellow_team.as_ref(),
+                Team::Blue => game_state.blue_team.as_ref(),
+            };
+
+            // Newest first
+            let mut robots: Vec<_> = q_robots
+                .iter()
+                .filter(|(t, child_of, ..)| **t == team && child_of.parent() == field_entity)
+                .map(|(_, _, entered, flag, entity)| {
+                    if entered.is_none() {
+                        commands.entity(entity).insert(EnteredAt(entered_now));
+                    }
+                    (
+                        entered.map_or(entered_now, |entered| entered.0),
+                        flag.copied(),
+                        entity,
+                    )
+                })
+                .collect();
+            robots.sort_by_key(|(entered, _, entity)| Reverse((*entered, *entity)));
+
+            let count = TeamRobotCount {
+                on_field: robots.len() as u32,
+                allowed: team_state.and_then(|t| t.max_robots),
+            };
+            let substitution = team_state.is_some_and(|t| t.substitution_allowed());
+            for (i, (entered, old_flag, entity)) in robots.into_iter().enumerate() {
+                let flag = if (i as u32) < count.excess() {
+                    Some(RobotFlag::Excess)
+                } else if substitution
+                    && entered.is_some_and(|entered| now - entered < SUBSTITUTION_FLAG_DURATION)
+                {
+                    Some(RobotFlag::Substitution)
+                } else {
+                    None
+                };
+                if flag == old_flag {
+                    continue;
+                }
+                match flag {
+                    Some(flag) => {
+                        commands.entity(entity).insert(flag);
+                    }
+                    None => {
+                        commands.entity(entity).remove::<RobotFlag>();
+                    }
+                }
+            }
+
+            match team {
+                Team::Yellow => new_count.yellow = count,
+                Team::Blue => new_count.blue = count,
+            }
+        }
+        robot_count.set_if_neq(new_count);
+    }
+
+    tracked_fields.extend(first_updates);
+    tracked_fields.retain(|field| q_fields.contains(*field));
+}
@@ -0,0 +1,190 @@
+//! Checks at startup whether this machine can do what the viewer needs.
+//! Blocked multicast joins or missing assets otherwise only show up as an empty host list or invisible robots,
+//! so every check is collected into a [`SelfTestReport`] with a hint on how to fix it.
+
+use bevy::prelude::*;
+
+#[cfg(feature = "networking")]
+use {
+    crate::services::{BEACON_ADDR_V4, BEACON_ADDR_V6},
+    net_ext::interface_flags::NetworkInterfaceFlagExtension,
+    net_ext::ssm_socket::SSMSocketExtension,
+    network_interface::{Addr, NetworkInterface, NetworkInterfaceConfig},
+    std::net::{Ipv4Addr, Ipv6Addr, UdpSocket},
+};
+
+/// Only joined to find out whether the network stack allows source-specific joins, nothing is sent to it.
+/// Has to be inside the ipv6 SSM range (FF3x::/96).
+#[cfg(feature = "networking")]
+const SSM_TEST_GROUP: Ipv6Addr = Ipv6Addr::new(0xFF35, 0, 0, 0, 0, 0, 0x4552, 0x4600);
+
+pub(crate) fn self_test_plugin(app: &mut App) {
+    app.init_resource::<SelfTestReport>();
+    app.register_type::<SelfTestReport>();
+    #[cfg(feature = "networking")]
+    app.add_systems(Startup, check_multicast);
+}
+
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum CheckStatus {
+    Ok,
+    /// Works with limitations
+    Warning,
+    /// A feature doesn't work at all
+    Error,
+}
+
+#[derive(Reflect, Debug, Clone, PartialEq)]
+pub struct SelfTestCheck {
+    pub name: String,
+    pub status: CheckStatus,
+    /// What was found and, if it failed, what the user can do about it
+    pub message: String,
+}
+
+/// Results of the startup checks. Apps can add their own checks (e.g. for XR extensions) with [`Self::record`].
+#[derive(Resource, Reflect, Debug, Default, Clone)]
+#[reflect(Resource, Debug, Default, Clone)]
+pub struct SelfTestReport {
+    checks: Vec<SelfTestCheck>,
+}
+
+impl SelfTestReport {
+    /// Adds the result of a check, replacing an earlier result with the same name.
+    /// Failed checks are logged as well, for headless setups.
+    pub fn record(
+        &mut self,
+        name: impl Into<String>,
+        status: CheckStatus,
+        message: impl Into<String>,
+    ) {
+        let check = SelfTestCheck {
+            name: name.into(),
+            status,
+            message: message.into(),
+        };
+        match status {
+            CheckStatus::Ok => info!("Self-test {}: {}", check.name, check.message),
+            CheckStatus::Warning => warn!("Self-test {}: {}", check.name, check.message),
+            CheckStatus::Error => error!("Self-test {}: {}", check.name, check.message),
+        }
+        match self.checks.iter_mut().find(|c| c.name == check.name) {
+            Some(existing) => *existing = check,
+            None => self.checks.push(check),
+        }
+    }
+
+    pub fn checks(&self) -> &[SelfTestCheck] {
+        &self.checks
+    }
+
+    /// The worst status of all checks
+    pub fn status(&self) -> CheckStatus {
+        self.checks
+            .iter()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(CheckStatus::Ok)
+    }
+}
+
+/// Joins the discovery groups on every interface the same way the discovery task does, but keeps the errors
+#[cfg(feature = "networking")]
+fn check_multicast(mut report: ResMut<SelfTestReport>) {
+    let interfaces = match NetworkInterface::show() {
+        Ok(interfaces) => interfaces,
+        Err(e) => {
+            report.record(
+                "Network interfaces",
+                CheckStatus::Error,
+                format!(
+                    "The interface list isn't available ({e}), so no discovery groups are joined. Add hosts by address instead."
+                ),
+            );
+            return;
+        }
+    };
+    let interfaces: Vec<_> = interfaces
+        .into_iter()
+        .filter(|i| i.is_multicast() && i.is_up())
+        .collect();
+    if interfaces.is_empty() {
+        report.record(
+            "Network interfaces",
+            CheckStatus::Error,
+            "No interface is up and supports multicast, hosts can't be discovered. Connect to the network of the hosts or add them by address.",
+        );
+        return;
+    }
+    report.record(
+        "Network interfaces",
+        CheckStatus::Ok,
+        format!("{} multicast capable interface(s)", interfaces.len()),
+    );
+
+    let port = BEACON_ADDR_V4.port();
+    let socket_v4 = UdpSocket::bind_multicast((Ipv4Addr::UNSPECIFIED, port));
+    let socket_v6 = UdpSocket::bind_multicast((Ipv6Addr::UNSPECIFIED, BEACON_ADDR_V6.port()));
+    for (name, socket) in [
+        ("Discovery socket (ipv4)", &socket_v4),
+        ("Discovery socket (ipv6)", &socket_v6),
+    ] {
+        match socket {
+            Ok(_) => report.record(name, CheckStatus::Ok, format!("Bound to UDP port {port}")),
+            Err(e) => report.record(
+                name,
+                CheckStatus::Warning,
+                format!(
+                    "UDP port {port} can't be bound ({e}). Check whether the address family is disabled or another program holds the port exclusively."
+                ),
+            ),
+        }
+    }
+
+    for interface in &interfaces {
+        let addr_v4 = interface.addr.iter().find_map(|a| match a {
+            Addr::V4(addr) => Some(addr.ip),
+            Addr::V6(_) => None,
+        });
+        let addr_v6 = interface.addr.iter().find_map(|a| match a {
+            Addr::V4(_) => None,
+            Addr::V6(addr) => Some(addr.ip),
+        });
+
+        let mut joined = false;
+        let mut problems = Vec::new();
+        if let (Ok(socket), Some(addr)) = (&socket_v4, addr_v4) {
+            match socket.join_multicast_v4(BEACON_ADDR_V4.ip(), &addr) {
+                Ok(()) => joined = true,
+                Err(e) => problems.push(format!("joining the ipv4 discovery group failed ({e})")),
+            }
+        }
+        if let (Ok(socket), Some(addr)) = (&socket_v6, addr_v6) {
+            match socket.join_multicast_v6(BEACON_ADDR_V6.ip(), interface.index) {
+                Ok(()) => joined = true,
+                Err(e) => problems.push(format!("joining the ipv6 discovery group failed ({e})")),
+            }
+            if let Err(e) = socket.join_ssm_v6(SSM_TEST_GROUP, addr, interface.index) {
+                problems.push(format!("source-specific multicast joins are blocked ({e})"));
+            }
+        }
+
+        let name = format!("Multicast on {}", interface.name);
+        if problems.is_empty() {
+            report.record(name, CheckStatus::Ok, "Discovery groups joined");
+        } else {
+            report.record(
+                name,
+                if joined {
+                    CheckStatus::Warning
+                } else {
+                    CheckStatus::Error
+                },
+                format!(
+                    "{}. Allow IGMP/MLD and UDP port {port} in the firewall, or use another interface.",
+                    problems.join(", ")
+                ),
+            );
+        }
+    }
+}
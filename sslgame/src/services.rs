@@ -0,0 +1,107 @@
+//! Multicast addresses of the services in the local network, shared by clients and the mock host.
+//!
+//! Host advertisements use the beacon groups. Every other service gets its own groups, derived from the beacon groups
+//! by replacing the lowest 16 bits (the magic) with a value from the 0x8000–0xFFFF range, so receivers only
+//! get the packets of the services they joined.
+
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+
+pub(crate) const BEACON_ADDR_V4: SocketAddrV4 =
+    SocketAddrV4::new(Ipv4Addr::new(239, 1, 1, 1), 11000);
+pub(crate) const BEACON_ADDR_V6: SocketAddrV6 = SocketAddrV6::new(
+    Ipv6Addr::from_bits(0xFF15_0000_0000_0045_5246_6F72_6365_0001), // "ERForce" in hex
+    11000,
+    0,
+    0,
+);
+
+/// Magic values of the services, the lower half is reserved for the beacon and host protocols
+pub const SERVICE_MAGIC_RANGE: std::ops::RangeInclusive<u16> = 0x8000..=0xFFFF;
+
+/// A multicast service, see the [module docs](self)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[repr(u16)]
+pub enum ServiceKind {
+    /// Periodic status of viewers and hosts
+    Status = 0x8000,
+    /// Announcements of visualizations that can be selected
+    VisAdvertisements = 0x8001,
+    /// Frozen fields shared between viewers, see [`crate::SharedSnapshot`]
+    Snapshots = 0x8002,
+    /// Reserved for robot telemetry
+    Telemetry = 0x8003,
+}
+
+impl ServiceKind {
+    pub const ALL: [Self; 4] = [
+        Self::Status,
+        Self::VisAdvertisements,
+        Self::Snapshots,
+        Self::Telemetry,
+    ];
+
+    pub const fn magic(self) -> u16 {
+        self as u16
+    }
+
+    pub fn from_magic(magic: u16) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.magic() == magic)
+    }
+
+    /// Each service has its own port next to the beacon port, so the sockets of different services don't
+    /// receive each other's packets when they are bound to the same wildcard address
+    pub const fn port(self) -> u16 {
+        BEACON_ADDR_V4.port() + 1 + (self.magic() - *SERVICE_MAGIC_RANGE.start())
+    }
+
+    pub const fn addr_v4(self) -> SocketAddrV4 {
+        let [a, b, ..] = BEACON_ADDR_V4.ip().octets();
+        let [c, d] = self.magic().to_be_bytes();
+        SocketAddrV4::new(Ipv4Addr::new(a, b, c, d), self.port())
+    }
+
+    pub const fn addr_v6(self) -> SocketAddrV6 {
+        let base = BEACON_ADDR_V6.ip().to_bits() & !0xFFFF;
+        SocketAddrV6::new(
+            Ipv6Addr::from_bits(base | self.magic() as u128),
+            self.port(),
+            0,
+            0,
+        )
+    }
+
+    /// The service of a multicast group, `None` for the beacon groups and addresses outside of the scheme
+    pub fn of_group(group: IpAddr) -> Option<Self> {
+        let magic = match group {
+            IpAddr::V4(ip) => {
+                let [a, b, c, d] = ip.octets();
+                let [base_a, base_b, ..] = BEACON_ADDR_V4.ip().octets();
+                if (a, b) != (base_a, base_b) {
+                    return None;
+                }
+                u16::from_be_bytes([c, d])
+            }
+            IpAddr::V6(ip) => {
+                if ip.to_bits() & !0xFFFF != BEACON_ADDR_V6.ip().to_bits() & !0xFFFF {
+                    return None;
+                }
+                ip.to_bits() as u16
+            }
+        };
+        Self::from_magic(magic)
+    }
+
+    /// Checks that a packet was sent to a group and port of this service
+    pub fn matches(self, addr: SocketAddr) -> bool {
+        addr.port() == self.port() && Self::of_group(addr.ip()) == Some(self)
+    }
+}
+
+impl TryFrom<u16> for ServiceKind {
+    type Error = u16;
+
+    /// Fails with the magic if it is outside of [`SERVICE_MAGIC_RANGE`] or not assigned to a service yet
+    fn try_from(magic: u16) -> Result<Self, Self::Error> {
+        Self::from_magic(magic).ok_or(magic)
+    }
+}
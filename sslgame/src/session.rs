@@ -0,0 +1,470 @@
+//! Saving and restoring the viewer state into .xrvis session files, see `proto/session.proto` for the contents.
+//! Fields, render and accessibility settings are handled here, the apps add their camera and panel layout themselves.
+
+#[cfg(feature = "networking")]
+use crate::PresharedKey;
+use crate::proto::session::field::Origin;
+use crate::proto::session::render_settings::{Lighting, RobotRendering};
+use crate::proto::{remote, session};
+use crate::{
+    AccessibilitySettings, DataSource, Field, FieldHost, FieldLights, FieldOrientation,
+    FieldOrigin, GhostSource, PlaybackClock, RenderSettings, RobotRenderSettings,
+    SelectedVisualizations, SourceStreams, VisColorOverrides,
+};
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use prost::Message;
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+pub use crate::proto::session::Session;
+
+// File layout: MAGIC, then the encoded session
+const MAGIC: &[u8; 8] = b"XRVISSES";
+
+impl Session {
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        let data = fs::read(path)?;
+        let Some(payload) = data.strip_prefix(MAGIC) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an xrvis session",
+            ));
+        };
+        Session::decode(payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut data = MAGIC.to_vec();
+        data.extend(self.encode_to_vec());
+        fs::write(path, data)
+    }
+}
+
+/// Marks fields spawned by [`SessionState::restore`].
+/// Their vis selection comes from the session, so apps shouldn't replace it with their defaults.
+#[derive(Component, Debug)]
+pub struct RestoredField;
+
+/// Captures and restores all fields with their sources, transforms, streams and vis selections,
+/// as well as the render and accessibility settings.
+#[derive(SystemParam)]
+pub struct SessionState<'w, 's> {
+    commands: Commands<'w, 's>,
+    render_settings: ResMut<'w, RenderSettings>,
+    accessibility: ResMut<'w, AccessibilitySettings>,
+    q_fields: Query<
+        'w,
+        's,
+        (
+            &'static Field,
+            &'static Transform,
+            &'static SourceStreams,
+            &'static SelectedVisualizations,
+            &'static VisColorOverrides,
+            &'static FieldOrientation,
+            Option<&'static Children>,
+            Entity,
+        ),
+    >,
+    q_sources: Query<
+        'w,
+        's,
+        (
+            AnyOf<(&'static DataSource, &'static GhostSource)>,
+            Option<&'static SourceStreams>,
+            Option<&'static SelectedVisualizations>,
+            Option<&'static VisColorOverrides>,
+        ),
+    >,
+}
+
+impl SessionState<'_, '_> {
+    /// Fields with injected packets can't be recreated and are skipped, as are snapshots
+    pub fn capture(&self) -> Session {
+        let mut session = Session {
+            render_settings: Some((&*self.render_settings).into()),
+            accessibility: Some((&*self.accessibility).into()),
+            ..default()
+        };
+
+        // Sorted, so that saving the same fields twice results in the same file
+        let mut fields: Vec<_> = self.q_fields.iter().collect();
+        fields.sort_unstable_by_key(|(.., entity)| *entity);
+
+        let mut connections = HashMap::new();
+        for (field, transform, streams, selected, vis_colors, orientation, children, _) in fields {
+            let origin = match field.origin() {
+                FieldOrigin::Host => Origin::Host((&field.host).into()),
+                FieldOrigin::Replay { path, .. } => {
+                    Origin::ReplayPath(path.to_string_lossy().into())
+                }
+                FieldOrigin::Demo => Origin::Demo(true),
+                FieldOrigin::Injected | FieldOrigin::Snapshot => continue,
+            };
+            let index = session.field.len() as u32;
+            let duplicate_of = connections.get(&field.connection.id()).copied();
+            connections.entry(field.connection.id()).or_insert(index);
+
+            let sources = children
+                .into_iter()
+                .flatten()
+                .filter_map(|child| self.q_sources.get(*child).ok())
+                .filter_map(|((data_source, ghost), streams, selected, vis_colors)| {
+                    let host = data_source
+                        .map(|source| &source.host)
+                        .or(ghost.map(|ghost| &ghost.host))?;
+                    // Sources created from replays have no address to reconnect to
+                    if host.websocket_addr.ip().is_unspecified() {
+                        return None;
+                    }
+                    Some(session::Source {
+                        host: Some(host.into()),
+                        ghost: ghost.is_some().then_some(true),
+                        streams: streams.map(Into::into),
+                        selected_visualizations: selected.map(|selected| selected.0.clone()),
+                        vis_color: vis_colors.map(Vec::from).unwrap_or_default(),
+                    })
+                })
+                .collect();
+
+            session.field.push(session::Field {
+                origin: Some(origin),
+                pose: Some(transform.into()),
+                streams: Some(streams.into()),
+                selected_visualizations: Some(selected.0.clone()),
+                source: sources,
+                duplicate_of,
+                vis_color: vis_colors.into(),
+                synced_playback: matches!(field.origin(), FieldOrigin::Replay { synced: true, .. })
+                    .then_some(true),
+                orientation: Some(session::Orientation::from(*orientation).into()),
+            });
+        }
+
+        session
+    }
+
+    /// Replaces all fields with the ones from the session. Encrypted hosts are connected to with the key.
+    #[cfg(feature = "networking")]
+    pub fn restore(&mut self, session: &Session, key: Option<&PresharedKey>) {
+        for (.., entity) in &self.q_fields {
+            self.commands.entity(entity).despawn();
+        }
+        if let Some(render_settings) = &session.render_settings {
+            *self.render_settings = render_settings.into();
+        }
+        if let Some(accessibility) = &session.accessibility {
+            self.accessibility.set_if_neq(accessibility.into());
+        }
+
+        // A handle on every restored connection, for the duplicates
+        let mut connections: Vec<Option<Field>> = Vec::new();
+        let playback_clock = PlaybackClock::default();
+        for saved in &session.field {
+            let original = saved
+                .duplicate_of
+                .and_then(|index| connections.get(index as usize)?.as_ref());
+            let field = match (original, &saved.origin) {
+                (Some(original), _) => Some(original.duplicate()),
+                (None, Some(Origin::Host(host))) => FieldHost::try_from(host)
+                    .ok()
+                    .map(|host| Field::bind(host, key)),
+                (None, Some(Origin::ReplayPath(path))) => match saved.synced_playback {
+                    Some(true) => Field::replay_synced(path, &playback_clock),
+                    _ => Field::replay(path),
+                }
+                .inspect_err(|e| error!("Failed to load recording {path}: {e}"))
+                .ok(),
+                (None, Some(Origin::Demo(_))) => Some(Field::demo()),
+                (None, None) => None,
+            };
+            connections.push(field.as_ref().map(Field::duplicate));
+            let Some(field) = field else {
+                continue;
+            };
+
+            let mut field_entity = self.commands.spawn((
+                field,
+                saved.pose.as_ref().map(Transform::from).unwrap_or_default(),
+                RestoredField,
+            ));
+            if let Some(streams) = &saved.streams {
+                field_entity.insert(SourceStreams::from(streams));
+            }
+            if let Some(selected) = &saved.selected_visualizations {
+                field_entity.insert(SelectedVisualizations(selected.clone()));
+            }
+            field_entity.insert(VisColorOverrides::from(saved.vis_color.as_slice()));
+            field_entity.insert(FieldOrientation::from(saved.orientation()));
+
+            for source in &saved.source {
+                let Some(host) = source
+                    .host
+                    .as_ref()
+                    .and_then(|host| FieldHost::try_from(host).ok())
+                else {
+                    continue;
+                };
+                if source.ghost() {
+                    field_entity.with_child(GhostSource::bind(host, key));
+                    continue;
+                }
+                let streams = source
+                    .streams
+                    .as_ref()
+                    .map(SourceStreams::from)
+                    .unwrap_or_default();
+                let selected = source
+                    .selected_visualizations
+                    .clone()
+                    .map(SelectedVisualizations)
+                    .unwrap_or_default();
+                field_entity.with_child((
+                    DataSource::bind(host, key, streams),
+                    selected,
+                    VisColorOverrides::from(source.vis_color.as_slice()),
+                ));
+            }
+        }
+    }
+}
+
+// ======== Conversions ========
+
+impl From<&FieldHost> for session::Host {
+    fn from(host: &FieldHost) -> Self {
+        Self {
+            websocket_addr: host.websocket_addr.to_string(),
+            hostname: host.hostname.clone(),
+            encrypted: host.encrypted.then_some(true),
+        }
+    }
+}
+
+impl TryFrom<&session::Host> for FieldHost {
+    type Error = std::net::AddrParseError;
+
+    fn try_from(host: &session::Host) -> Result<Self, Self::Error> {
+        let websocket_addr = host
+            .websocket_addr
+            .parse()
+            .inspect_err(|e| warn!("Invalid host address {}: {e}", host.websocket_addr))?;
+        Ok(FieldHost {
+            websocket_addr,
+            hostname: host.hostname.clone(),
+            encrypted: host.encrypted(),
+        })
+    }
+}
+
+/// Only uniform scales are stored
+impl From<&Transform> for session::Pose {
+    fn from(transform: &Transform) -> Self {
+        Self {
+            x: transform.translation.x,
+            y: transform.translation.y,
+            z: transform.translation.z,
+            rot_x: Some(transform.rotation.x),
+            rot_y: Some(transform.rotation.y),
+            rot_z: Some(transform.rotation.z),
+            rot_w: Some(transform.rotation.w),
+            scale: Some(transform.scale.x),
+        }
+    }
+}
+
+impl From<&session::Pose> for Transform {
+    fn from(pose: &session::Pose) -> Self {
+        Transform {
+            translation: Vec3::new(pose.x, pose.y, pose.z),
+            rotation: Quat::from_xyzw(pose.rot_x(), pose.rot_y(), pose.rot_z(), pose.rot_w())
+                .try_normalize()
+                .unwrap_or_default(),
+            scale: Vec3::splat(pose.scale.unwrap_or(1.0)),
+        }
+    }
+}
+
+impl From<&SourceStreams> for session::Streams {
+    fn from(streams: &SourceStreams) -> Self {
+        Self {
+            geometry: Some(streams.geometry),
+            game_state: Some(streams.game_state),
+            world_state: Some(streams.world_state),
+            visualizations: Some(streams.visualizations),
+            telemetry: Some(streams.telemetry),
+            debug_values: Some(streams.debug_values),
+        }
+    }
+}
+
+/// Missing streams are enabled, like on a new field
+impl From<&session::Streams> for SourceStreams {
+    fn from(streams: &session::Streams) -> Self {
+        Self {
+            geometry: streams.geometry.unwrap_or(true),
+            game_state: streams.game_state.unwrap_or(true),
+            world_state: streams.world_state.unwrap_or(true),
+            visualizations: streams.visualizations.unwrap_or(true),
+            telemetry: streams.telemetry.unwrap_or(true),
+            debug_values: streams.debug_values.unwrap_or(true),
+        }
+    }
+}
+
+impl From<FieldOrientation> for session::Orientation {
+    fn from(orientation: FieldOrientation) -> Self {
+        match orientation {
+            FieldOrientation::Normal => session::Orientation::Normal,
+            FieldOrientation::Mirrored => session::Orientation::Mirrored,
+            FieldOrientation::Rotated => session::Orientation::Rotated,
+        }
+    }
+}
+
+impl From<session::Orientation> for FieldOrientation {
+    fn from(orientation: session::Orientation) -> Self {
+        match orientation {
+            session::Orientation::Normal => FieldOrientation::Normal,
+            session::Orientation::Mirrored => FieldOrientation::Mirrored,
+            session::Orientation::Rotated => FieldOrientation::Rotated,
+        }
+    }
+}
+
+/// Sorted by name, so that saving the same overrides twice results in the same file
+impl From<&VisColorOverrides> for Vec<session::VisColor> {
+    fn from(overrides: &VisColorOverrides) -> Self {
+        let mut colors: Vec<_> = overrides
+            .0
+            .iter()
+            .map(|(name, color)| {
+                let [red, green, blue, alpha] = color.to_srgba().to_u8_array().map(u32::from);
+                session::VisColor {
+                    name: name.clone(),
+                    color: remote::Color {
+                        red,
+                        green,
+                        blue,
+                        alpha,
+                    },
+                }
+            })
+            .collect();
+        colors.sort_unstable_by(|a, b| a.name.cmp(&b.name));
+        colors
+    }
+}
+
+impl From<&[session::VisColor]> for VisColorOverrides {
+    fn from(colors: &[session::VisColor]) -> Self {
+        Self(
+            colors
+                .iter()
+                .map(|vis_color| {
+                    let color = &vis_color.color;
+                    let channel = |value: u32| value.min(255) as u8;
+                    (
+                        vis_color.name.clone(),
+                        Color::srgb_u8(
+                            channel(color.red),
+                            channel(color.green),
+                            channel(color.blue),
+                        ),
+                    )
+                })
+                .collect(),
+        )
+    }
+}
+
+impl From<&RenderSettings> for session::RenderSettings {
+    fn from(settings: &RenderSettings) -> Self {
+        let robots = match settings.robots {
+            RobotRenderSettings::Detailed => RobotRendering::Detailed,
+            RobotRenderSettings::Fallback => RobotRendering::Fallback,
+            RobotRenderSettings::Cutout => RobotRendering::Cutout,
+            RobotRenderSettings::None => RobotRendering::None,
+        };
+        let lights = match settings.lights {
+            FieldLights::Off => Lighting::Off,
+            FieldLights::Soft => Lighting::Soft,
+            FieldLights::Stadium => Lighting::Stadium,
+        };
+        Self {
+            field: Some(settings.field),
+            robots: Some(robots as i32),
+            ball: Some(settings.ball),
+            visualizations: Some(settings.visualizations),
+            telemetry: Some(settings.telemetry),
+            grid: Some(settings.grid),
+            marker_tags: Some(settings.marker_tags),
+            lights: Some(lights as i32),
+            vis_cull_angle: Some(settings.vis_cull_angle),
+        }
+    }
+}
+
+/// Missing settings keep their defaults
+impl From<&session::RenderSettings> for RenderSettings {
+    fn from(settings: &session::RenderSettings) -> Self {
+        let defaults = RenderSettings::default();
+        let robots = match settings.robots {
+            None => defaults.robots.clone(),
+            Some(_) => match settings.robots() {
+                RobotRendering::Detailed => RobotRenderSettings::Detailed,
+                RobotRendering::Fallback => RobotRenderSettings::Fallback,
+                RobotRendering::Cutout => RobotRenderSettings::Cutout,
+                RobotRendering::None => RobotRenderSettings::None,
+            },
+        };
+        let lights = match settings.lights {
+            None => defaults.lights,
+            Some(_) => match settings.lights() {
+                Lighting::Off => FieldLights::Off,
+                Lighting::Soft => FieldLights::Soft,
+                Lighting::Stadium => FieldLights::Stadium,
+            },
+        };
+        RenderSettings {
+            field: settings.field.unwrap_or(defaults.field),
+            robots,
+            ball: settings.ball.unwrap_or(defaults.ball),
+            visualizations: settings.visualizations.unwrap_or(defaults.visualizations),
+            telemetry: settings.telemetry.unwrap_or(defaults.telemetry),
+            grid: settings.grid.unwrap_or(defaults.grid),
+            marker_tags: settings.marker_tags.unwrap_or(defaults.marker_tags),
+            lights,
+            vis_cull_angle: settings.vis_cull_angle.unwrap_or(defaults.vis_cull_angle),
+        }
+    }
+}
+
+impl From<&AccessibilitySettings> for session::Accessibility {
+    fn from(settings: &AccessibilitySettings) -> Self {
+        Self {
+            ui_scale: Some(settings.ui_scale),
+            text_scale: Some(settings.text_scale),
+            high_contrast: Some(settings.high_contrast),
+        }
+    }
+}
+
+/// Missing settings keep their defaults, scales outside of the supported range are clamped
+impl From<&session::Accessibility> for AccessibilitySettings {
+    fn from(settings: &session::Accessibility) -> Self {
+        let defaults = AccessibilitySettings::default();
+        AccessibilitySettings {
+            ui_scale: settings
+                .ui_scale
+                .map_or(defaults.ui_scale, AccessibilitySettings::clamp_scale),
+            text_scale: settings
+                .text_scale
+                .map_or(defaults.text_scale, AccessibilitySettings::clamp_scale),
+            high_contrast: settings.high_contrast.unwrap_or(defaults.high_contrast),
+        }
+    }
+}
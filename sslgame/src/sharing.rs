@@ -0,0 +1,289 @@
+//! Frozen copies of a field that are passed between viewers, e.g. so a coach can share an interesting situation.
+//! Snapshots are sent to all viewers in the local network in a single udp packet, or saved into a file.
+
+#[cfg(feature = "networking")]
+use crate::network_tasks;
+use crate::proto::remote::{
+    self, VisMappings, VisualizationSet, VisualizationUpdate, WorldState, ws_request,
+};
+use crate::snapshot::{BallState, RobotState, WorldSnapshot, remap_visualization};
+use crate::{
+    AvailableVisualizations, Ball, Field, FieldGeometry, FieldHost, FieldOrigin, GameState, Robot,
+    Team, UpdatePacket, VisualizationData,
+};
+#[cfg(feature = "networking")]
+use async_channel::Receiver;
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::tasks::IoTaskPool;
+#[cfg(feature = "networking")]
+use bevy::tasks::Task;
+use prost::Message as _;
+use std::collections::HashSet;
+use std::io;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::Path;
+use std::sync::OnceLock;
+use std::time::SystemTime;
+
+pub use crate::proto::viewer::SharedSnapshot;
+
+// File layout: MAGIC, then the encoded snapshot
+const MAGIC: &[u8; 8] = b"XRVISSNP";
+/// The largest udp payload, snapshots are never split into multiple packets
+pub(crate) const MAX_SNAPSHOT_SIZE: usize = 65507;
+
+pub(crate) fn sharing_plugin(app: &mut App) {
+    app.add_message::<SnapshotReceived>();
+    #[cfg(feature = "networking")]
+    app.add_systems(Update, receive_snapshots);
+}
+
+/// A snapshot shared by another viewer in the local network.
+/// Apps decide where to show it, usually as a new field created with [`Field::snapshot`].
+#[derive(Message, Debug, Clone)]
+pub struct SnapshotReceived(pub SharedSnapshot);
+
+#[cfg(feature = "networking")]
+#[derive(Resource, Debug)]
+struct SnapshotReceiver {
+    snapshots: Receiver<SharedSnapshot>,
+    task: Task<()>,
+}
+
+/// Random enough to distinguish multiple viewers on the same machine
+fn instance_id() -> u32 {
+    static INSTANCE_ID: OnceLock<u32> = OnceLock::new();
+    *INSTANCE_ID.get_or_init(|| {
+        SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .subsec_nanos()
+            ^ std::process::id()
+    })
+}
+
+impl SharedSnapshot {
+    pub fn read(path: impl AsRef<Path>) -> io::Result<Self> {
+        let data = std::fs::read(path)?;
+        let Some(payload) = data.strip_prefix(MAGIC) else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "not an xrvis snapshot",
+            ));
+        };
+        SharedSnapshot::decode(payload).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    pub fn write(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let mut data = MAGIC.to_vec();
+        data.extend(self.encode_to_vec());
+        std::fs::write(path, data)
+    }
+
+    /// Sends the snapshot to all viewers in the local network.
+    /// Fails if the snapshot doesn't fit into a single packet, e.g. because of large visualizations.
+    #[cfg(feature = "networking")]
+    pub fn share(&self) -> io::Result<()> {
+        let packet = SharedSnapshot {
+            sender_id: Some(instance_id()),
+            ..self.clone()
+        }
+        .encode_to_vec();
+        if packet.len() > MAX_SNAPSHOT_SIZE {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "snapshot is too large to share ({} bytes), save it into a file instead",
+                    packet.len()
+                ),
+            ));
+        }
+        IoTaskPool::get()
+            .spawn(network_tasks::send_snapshot(packet))
+            .detach();
+        Ok(())
+    }
+
+    /// The packets a host would send for the captured field
+    fn into_packets(self) -> Vec<UpdatePacket> {
+        [
+            self.geometry.map(UpdatePacket::FieldGeom),
+            self.game_state.map(UpdatePacket::GameState),
+            self.vis_mappings.map(UpdatePacket::VisMappings),
+            self.world_state.map(UpdatePacket::WorldState),
+            self.visualizations.map(UpdatePacket::VisualizationUpdate),
+        ]
+        .into_iter()
+        .flatten()
+        .collect()
+    }
+}
+
+impl Field {
+    /// Creates a field that shows a snapshot, e.g. from [`SnapshotReceived`].
+    /// The state is sent once and then stays frozen, since there are no further world state packets.
+    pub fn snapshot(snapshot: SharedSnapshot) -> Self {
+        let host = FieldHost {
+            websocket_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+            hostname: Some(format!(
+                "{} (snapshot)",
+                snapshot.name.as_deref().unwrap_or("Unknown")
+            )),
+            encrypted: false,
+        };
+        let visualizations = snapshot.visualizations.clone();
+        let packets = snapshot.into_packets();
+
+        Self::from_task(
+            host,
+            FieldOrigin::Snapshot,
+            |_, packets_out, requests_in, _| {
+                IoTaskPool::get().spawn(async move {
+                    for packet in packets {
+                        if packets_out.send(packet).await.is_err() {
+                            return;
+                        }
+                    }
+                    // The visualizations are sent again for every new selection, since unselected ones are dropped by the field
+                    while let Ok(request) = requests_in.recv().await {
+                        if let ws_request::Content::SetVisFilter(_) = request
+                            && let Some(visualizations) = &visualizations
+                            && packets_out
+                                .send(UpdatePacket::VisualizationUpdate(visualizations.clone()))
+                                .await
+                                .is_err()
+                        {
+                            return;
+                        }
+                    }
+                })
+            },
+        )
+    }
+}
+
+/// Captures the displayed state of a field into a [`SharedSnapshot`]
+#[derive(SystemParam)]
+pub struct SnapshotCapture<'w, 's> {
+    q_fields: Query<
+        'w,
+        's,
+        (
+            &'static Field,
+            &'static FieldGeometry,
+            &'static GameState,
+            &'static AvailableVisualizations,
+            &'static Children,
+        ),
+    >,
+    q_robots: Query<'w, 's, (&'static Robot, &'static Team, &'static Transform)>,
+    q_balls: Query<'w, 's, &'static Transform, With<Ball>>,
+    q_visualizations: Query<'w, 's, &'static VisualizationData>,
+}
+
+impl SnapshotCapture<'_, '_> {
+    /// Uses the robots, balls and visualizations that are currently shown, so paused fields are captured as they are seen
+    pub fn capture(&self, field: Entity) -> Option<SharedSnapshot> {
+        let (field, geom, game_state, available, children) = self.q_fields.get(field).ok()?;
+        let created_at = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+
+        let world_state = WorldSnapshot {
+            timestamp: created_at,
+            balls: children
+                .iter()
+                .filter_map(|child| self.q_balls.get(child).ok())
+                .map(|transform| BallState {
+                    translation: transform.translation,
+                    velocity: None,
+                })
+                .collect(),
+            robots: children
+                .iter()
+                .filter_map(|child| self.q_robots.get(child).ok())
+                .map(|(robot, team, transform)| RobotState {
+                    id: robot.0 as u32,
+                    team: *team,
+                    translation: transform.translation,
+                    rotation: transform.rotation.to_euler(EulerRot::YXZ).0,
+                    angular_velocity: None,
+                    velocity: None,
+                })
+                .collect(),
+        };
+
+        let visualizations: Vec<_> = children
+            .iter()
+            .filter_map(|child| self.q_visualizations.get(child).ok())
+            .map(|visualization| remap_visualization(visualization.0.clone()))
+            .collect();
+        let vis_ids: HashSet<_> = visualizations.iter().map(|vis| vis.id).collect();
+
+        Some(SharedSnapshot {
+            name: Some(field.host.display_name()),
+            created_at: Some(created_at),
+            sender_id: None,
+            geometry: Some(remote::FieldGeometry {
+                field_size_x: geom.play_area_size.x,
+                field_size_y: geom.play_area_size.y,
+                boundary_width: Some(geom.boundary_width),
+                defense_size_x: Some(geom.defense_size.x),
+                defense_size_y: Some(geom.defense_size.y),
+                goal_width: Some(geom.goal_width),
+            }),
+            game_state: Some((**game_state).clone()),
+            vis_mappings: Some(VisMappings {
+                source: available.sources.clone(),
+                name: available
+                    .visualizations
+                    .iter()
+                    .filter(|(id, _)| vis_ids.contains(id))
+                    .map(|(id, name)| (*id, name.clone()))
+                    .collect(),
+            }),
+            world_state: Some(WorldState::from(&world_state)),
+            visualizations: Some(VisualizationUpdate {
+                visualization_group: None,
+                visualization_set: vec![VisualizationSet {
+                    source: None,
+                    visualization: visualizations,
+                }],
+            }),
+        })
+    }
+}
+
+/// Starts the receiver once and forwards the received snapshots as [`SnapshotReceived`] messages
+#[cfg(feature = "networking")]
+fn receive_snapshots(
+    mut commands: Commands,
+    receiver: Option<Res<SnapshotReceiver>>,
+    mut started: Local<bool>,
+    mut snapshots: MessageWriter<SnapshotReceived>,
+) {
+    let Some(receiver) = receiver else {
+        // Not restarted, the task only stops if the sockets can't be bound
+        if !*started {
+            *started = true;
+            let (tx, rx) = async_channel::bounded(10);
+            let task =
+                IoTaskPool::get().spawn(network_tasks::snapshot_receiver_task(instance_id(), tx));
+            commands.insert_resource(SnapshotReceiver {
+                snapshots: rx,
+                task,
+            });
+        }
+        return;
+    };
+
+    if receiver.task.is_finished() {
+        commands.remove_resource::<SnapshotReceiver>();
+        return;
+    }
+    for snapshot in std::iter::from_fn(|| receiver.snapshots.try_recv().ok()) {
+        snapshots.write(SnapshotReceived(snapshot));
+    }
+}
@@ -0,0 +1,180 @@
+//! Internal representations of the packets received from hosts.
+//! Packets are converted once when they are received, all later processing works in bevy's coordinate system.
+
+use crate::Team;
+use crate::proto::remote::vis_part::Geom;
+use crate::proto::remote::{Visualization, VisualizationUpdate, WorldState};
+use bevy::prelude::*;
+use std::f32::consts::PI;
+
+/// Positions of all robots and balls on a field at a point in time.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct WorldSnapshot {
+    /// Host timestamp in µs
+    pub timestamp: u64,
+    pub balls: Vec<BallState>,
+    pub robots: Vec<RobotState>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RobotState {
+    pub id: u32,
+    pub team: Team,
+    /// Position on the field, y is always 0
+    pub translation: Vec3,
+    /// Rotation around the y axis, 0 is facing -z
+    pub rotation: f32,
+    /// Angular velocity around the y axis in rad/s, as reported by the host or estimated from the previous packet
+    pub angular_velocity: Option<f32>,
+    /// In m/s, estimated from the previous packet
+    pub velocity: Option<Vec3>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BallState {
+    /// Position of the ball, y is 0 if the host doesn't know the height
+    pub translation: Vec3,
+    /// In m/s, estimated from the previous packet
+    pub velocity: Option<Vec3>,
+}
+
+impl RobotState {
+    pub fn transform(&self) -> Transform {
+        Transform::from_translation(self.translation)
+            .with_rotation(Quat::from_rotation_y(self.rotation))
+    }
+}
+
+/// Converts from the vision coordinate system (right-handed, z up, x towards blue goal, +x forward)
+/// to bevy's coordinate system (right-handed, y up, x towards blue goal, -z forward) with y and z swapped
+impl From<WorldState> for WorldSnapshot {
+    fn from(world_state: WorldState) -> Self {
+        let robots = |team: Team, robots: Vec<crate::proto::remote::Robot>| {
+            robots.into_iter().map(move |robot| RobotState {
+                id: robot.id,
+                team,
+                translation: Vec3::new(robot.p_x, 0.0, -robot.p_y),
+                rotation: robot.phi - PI / 2.0,
+                angular_velocity: robot.v_phi,
+                velocity: None,
+            })
+        };
+
+        Self {
+            timestamp: world_state.timestamp.unwrap_or_default(),
+            balls: world_state
+                .ball
+                .into_iter()
+                .map(|ball| BallState {
+                    translation: Vec3::new(ball.p_x, ball.p_z.unwrap_or(0.0), -ball.p_y),
+                    velocity: None,
+                })
+                .collect(),
+            robots: robots(Team::Yellow, world_state.yellow_robot)
+                .chain(robots(Team::Blue, world_state.blue_robot))
+                .collect(),
+        }
+    }
+}
+
+/// The inverse of the conversion from [`WorldState`], e.g. to send a displayed state to other viewers
+impl From<&WorldSnapshot> for WorldState {
+    fn from(snapshot: &WorldSnapshot) -> Self {
+        let robots = |team: Team| {
+            snapshot
+                .robots
+                .iter()
+                .filter(|robot| robot.team == team)
+                .map(|robot| crate::proto::remote::Robot {
+                    id: robot.id,
+                    p_x: robot.translation.x,
+                    p_y: -robot.translation.z,
+                    phi: robot.rotation + PI / 2.0,
+                    v_phi: robot.angular_velocity,
+                })
+                .collect()
+        };
+
+        Self {
+            timestamp: Some(snapshot.timestamp),
+            ball: snapshot
+                .balls
+                .iter()
+                .map(|ball| crate::proto::remote::Ball {
+                    p_x: ball.translation.x,
+                    p_y: -ball.translation.z,
+                    p_z: Some(ball.translation.y),
+                })
+                .collect(),
+            yellow_robot: robots(Team::Yellow),
+            blue_robot: robots(Team::Blue),
+            keyframe: None,
+        }
+    }
+}
+
+/// A visualization update for a single group, with all visualizations already in bevy's coordinate system.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct VisFrame {
+    pub group: u32,
+    pub group_count: u32,
+    pub sets: Vec<VisSet>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct VisSet {
+    pub source: Option<u32>,
+    pub visualizations: Vec<Visualization>,
+}
+
+impl From<VisualizationUpdate> for VisFrame {
+    fn from(update: VisualizationUpdate) -> Self {
+        // No group means that the update contains all visualizations
+        let (group, group_count) = update
+            .visualization_group
+            .map(|g| (g.group, g.group_count))
+            .unwrap_or((0, 1));
+
+        Self {
+            group,
+            group_count,
+            sets: update
+                .visualization_set
+                .into_iter()
+                .map(|set| VisSet {
+                    source: set.source,
+                    visualizations: set
+                        .visualization
+                        .into_iter()
+                        .map(remap_visualization)
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Mirrors the visualization geometry along the x axis, the y axis of the points becomes -z in bevy.
+/// Mirroring is its own inverse, so this also converts back to the vision coordinate system.
+pub(crate) fn remap_visualization(mut vis: Visualization) -> Visualization {
+    for part in &mut vis.part {
+        match &mut part.geom {
+            Some(Geom::Circle(c)) => {
+                c.p_y = -c.p_y;
+            }
+            Some(Geom::Polygon(p)) => {
+                for point in &mut p.point {
+                    point.y = -point.y;
+                }
+            }
+            Some(Geom::Path(p)) => {
+                for point in &mut p.point {
+                    point.y = -point.y;
+                }
+            }
+            // Custom payloads are opaque, handlers have to convert them themselves
+            Some(Geom::Custom(_)) | None => {}
+        }
+    }
+    vis
+}
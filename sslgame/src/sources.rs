@@ -0,0 +1,160 @@
+//! Additional data sources of a field, to merge the streams of multiple hosts into one field.
+//! E.g. the world state can be taken from a tracker host while the visualizations come from a strategy host,
+//! without one of the hosts having to proxy the streams of the other.
+
+#[cfg(feature = "networking")]
+use crate::PresharedKey;
+#[cfg(feature = "networking")]
+use crate::proto::remote::udp_stream_request::UdpStream;
+#[cfg(feature = "networking")]
+use crate::proto::remote::ws_stream_request::WsStream;
+#[cfg(feature = "networking")]
+use crate::proto::remote::{UdpStreamRequest, WsStreamRequest, ws_request};
+use crate::{
+    AvailableVisualizations, Field, FieldConnection, FieldHost, SelectedVisualizations,
+    UpdatePacket, VisColorOverrides, VisSelectionStatus, VisualizationTracker,
+};
+use bevy::prelude::*;
+
+/// The streams that are used from a connection, packets of all other streams are dropped.
+/// Present on every [`Field`] and [`DataSource`], changing it only affects packets received afterwards.
+#[derive(Component, Reflect, Debug, Clone, Copy, PartialEq, Eq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
+pub struct SourceStreams {
+    pub geometry: bool,
+    pub game_state: bool,
+    pub world_state: bool,
+    pub visualizations: bool,
+    pub telemetry: bool,
+    pub debug_values: bool,
+}
+
+impl Default for SourceStreams {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl SourceStreams {
+    pub const ALL: Self = Self {
+        geometry: true,
+        game_state: true,
+        world_state: true,
+        visualizations: true,
+        telemetry: true,
+        debug_values: true,
+    };
+    pub const NONE: Self = Self {
+        geometry: false,
+        game_state: false,
+        world_state: false,
+        visualizations: false,
+        telemetry: false,
+        debug_values: false,
+    };
+
+    pub fn accepts(&self, packet: &UpdatePacket) -> bool {
+        match packet {
+            UpdatePacket::FieldGeom(_) => self.geometry,
+            UpdatePacket::GameState(_) => self.game_state,
+            UpdatePacket::VisMappings(_)
+            | UpdatePacket::VisualizationUpdate(_)
+            | UpdatePacket::StrategyPlans(_) => self.visualizations,
+            UpdatePacket::WorldState(_) => self.world_state,
+            UpdatePacket::RobotTelemetry(_) => self.telemetry,
+            UpdatePacket::DebugValues(_) => self.debug_values,
+        }
+    }
+
+    /// Subscribes to the enabled streams on the host
+    #[cfg(feature = "networking")]
+    pub(crate) fn request(&self, connection: &FieldConnection) {
+        for request in self.requests() {
+            _ = connection.sender.send_blocking(request);
+        }
+    }
+
+    /// The requests that subscribe to the enabled streams
+    #[cfg(feature = "networking")]
+    pub(crate) fn requests(&self) -> [ws_request::Content; 2] {
+        let ws_streams = [
+            (self.geometry, WsStream::FieldGeometry),
+            (self.game_state, WsStream::GameState),
+            (self.visualizations, WsStream::VisMappings),
+        ];
+        let udp_streams = [
+            (self.world_state, UdpStream::WorldState),
+            (self.visualizations, UdpStream::Visualizations),
+            (self.telemetry, UdpStream::RobotTelemetry),
+            (self.debug_values, UdpStream::DebugValues),
+        ];
+
+        [
+            ws_request::Content::WsStreamReq(WsStreamRequest {
+                stream: ws_streams
+                    .into_iter()
+                    .filter(|(enabled, _)| *enabled)
+                    .map(|(_, stream)| stream as i32)
+                    .collect(),
+            }),
+            ws_request::Content::UdpStreamReq(UdpStreamRequest {
+                stream: udp_streams
+                    .into_iter()
+                    .filter(|(enabled, _)| *enabled)
+                    .map(|(_, stream)| stream as i32)
+                    .collect(),
+                port: 0,
+                compression: None,
+                world_state_deltas: None,
+            }),
+        ]
+    }
+}
+
+/// An additional connection whose streams are merged into its parent field.
+/// Spawn it as a child of the field:
+/// `commands.entity(field).with_child(DataSource::bind(host, key, SourceStreams { visualizations: true, ..SourceStreams::NONE }))`
+///
+/// Visualizations are kept per source, so each source has its own [`AvailableVisualizations`] and [`SelectedVisualizations`]
+/// and its visualizations are spawned as its children. All other streams are applied to the field itself.
+#[derive(Component, Reflect, Debug)]
+#[reflect(Component, Debug, from_reflect = false)]
+#[require(
+    Transform,
+    SourceStreams,
+    AvailableVisualizations,
+    SelectedVisualizations,
+    VisColorOverrides,
+    VisSelectionStatus,
+    VisualizationTracker
+)]
+pub struct DataSource {
+    pub host: FieldHost,
+    #[reflect(ignore)]
+    pub(crate) connection: FieldConnection,
+}
+
+impl DataSource {
+    /// Connects to the host and only subscribes to the given streams. The key is only used for encrypted hosts.
+    #[cfg(feature = "networking")]
+    pub fn bind(
+        host: FieldHost,
+        key: Option<&PresharedKey>,
+        streams: SourceStreams,
+    ) -> (Self, SourceStreams) {
+        let field = Field::connect(host, key);
+        streams.request(&field.connection);
+        (field.into_source(), streams)
+    }
+}
+
+impl Field {
+    /// Uses the connection of this field as a data source of another field, e.g. to merge a recording into a live field.
+    /// All streams of the connection are used unless a [`SourceStreams`] is inserted as well.
+    pub fn into_source(self) -> DataSource {
+        DataSource {
+            host: self.host,
+            connection: self.connection,
+        }
+    }
+}
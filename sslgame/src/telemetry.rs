@@ -0,0 +1,164 @@
+use crate::proto::remote::{RobotRole, RobotTelemetry, RobotTelemetryUpdate};
+use crate::{Robot, Team};
+use bevy::prelude::*;
+use std::collections::HashMap;
+use std::ops::RangeInclusive;
+
+/// Battery voltages from empty to fully charged, for a 4S lipo
+const BATTERY_RANGE: RangeInclusive<f32> = 13.2..=16.8;
+/// Radio signal strengths in dBm from unusable to perfect
+const RSSI_RANGE: RangeInclusive<f32> = -90.0..=-40.0;
+
+/// Hardware status of a robot as last reported by the host.
+#[derive(Component, Reflect, Debug, Default, Clone, Copy, PartialEq)]
+#[reflect(Component, Debug, Default, Clone, PartialEq)]
+pub struct Telemetry {
+    pub battery_voltage: Option<f32>,
+    pub kicker_charge: Option<f32>,
+    pub radio_rssi: Option<f32>,
+    pub role: Option<Role>,
+    pub kicker_armed: Option<bool>,
+    pub dribbler_active: Option<bool>,
+}
+
+/// Strategy role of a robot, e.g. to follow the assignments of the strategy during a match
+#[derive(Reflect, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[reflect(Debug, Clone, PartialEq, Hash)]
+pub enum Role {
+    Keeper,
+    Defender,
+    Midfielder,
+    Striker,
+}
+
+impl Role {
+    pub const ALL: [Role; 4] = [
+        Role::Keeper,
+        Role::Defender,
+        Role::Midfielder,
+        Role::Striker,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Role::Keeper => "Keeper",
+            Role::Defender => "Defender",
+            Role::Midfielder => "Midfielder",
+            Role::Striker => "Striker",
+        }
+    }
+
+    /// Distinct from the team colors, so the role is visible on both teams
+    pub fn color(&self) -> Color {
+        match self {
+            Role::Keeper => Color::srgb(0.2, 0.9, 0.3),
+            Role::Defender => Color::srgb(0.3, 0.7, 1.0),
+            Role::Midfielder => Color::srgb(0.8, 0.4, 1.0),
+            Role::Striker => Color::srgb(1.0, 0.35, 0.2),
+        }
+    }
+}
+
+impl From<RobotRole> for Role {
+    fn from(role: RobotRole) -> Self {
+        match role {
+            RobotRole::Keeper => Role::Keeper,
+            RobotRole::Defender => Role::Defender,
+            RobotRole::Midfielder => Role::Midfielder,
+            RobotRole::Striker => Role::Striker,
+        }
+    }
+}
+
+impl From<Role> for RobotRole {
+    fn from(role: Role) -> Self {
+        match role {
+            Role::Keeper => RobotRole::Keeper,
+            Role::Defender => RobotRole::Defender,
+            Role::Midfielder => RobotRole::Midfielder,
+            Role::Striker => RobotRole::Striker,
+        }
+    }
+}
+
+impl Telemetry {
+    /// Battery charge from 0 to 1, estimated from the voltage
+    pub fn battery_level(&self) -> Option<f32> {
+        self.battery_voltage
+            .map(|voltage| normalize(voltage, &BATTERY_RANGE))
+    }
+
+    /// Radio signal quality from 0 to 1
+    pub fn radio_level(&self) -> Option<f32> {
+        self.radio_rssi.map(|rssi| normalize(rssi, &RSSI_RANGE))
+    }
+}
+
+impl From<&RobotTelemetry> for Telemetry {
+    fn from(telemetry: &RobotTelemetry) -> Self {
+        Self {
+            battery_voltage: telemetry.battery_voltage,
+            kicker_charge: telemetry.kicker_charge,
+            radio_rssi: telemetry.radio_rssi,
+            // Unknown roles of newer hosts are ignored
+            role: telemetry
+                .role
+                .and_then(|role| RobotRole::try_from(role).ok())
+                .map(Role::from),
+            kicker_armed: telemetry.kicker_armed,
+            dribbler_active: telemetry.dribbler_active,
+        }
+    }
+}
+
+fn normalize(value: f32, range: &RangeInclusive<f32>) -> f32 {
+    ((value - range.start()) / (range.end() - range.start())).clamp(0.0, 1.0)
+}
+
+/// Latest telemetry of all robots on a field by team and id.
+/// Copied to the robot entities as [`Telemetry`], as robots can be respawned at any time.
+#[derive(Component, Debug, Default)]
+pub struct FieldTelemetry(pub HashMap<(Team, u32), Telemetry>);
+
+impl FieldTelemetry {
+    /// Every update contains all robots with telemetry
+    pub(crate) fn replace(&mut self, update: &RobotTelemetryUpdate) {
+        self.0.clear();
+        for (team, robots) in [
+            (Team::Yellow, &update.yellow_robot),
+            (Team::Blue, &update.blue_robot),
+        ] {
+            self.0.extend(
+                robots
+                    .iter()
+                    .map(|telemetry| ((team, telemetry.id), Telemetry::from(telemetry))),
+            );
+        }
+    }
+}
+
+pub(crate) fn apply_robot_telemetry(
+    mut commands: Commands,
+    q_fields: Query<&FieldTelemetry>,
+    mut q_robots: Query<(&Robot, &Team, &ChildOf, Option<&mut Telemetry>, Entity)>,
+) {
+    for (robot, team, child_of, telemetry, robot_entity) in &mut q_robots {
+        let Ok(field_telemetry) = q_fields.get(child_of.parent()) else {
+            continue;
+        };
+        let new_telemetry = field_telemetry.0.get(&(*team, robot.0 as u32));
+
+        match (telemetry, new_telemetry) {
+            (Some(mut telemetry), Some(new_telemetry)) => {
+                telemetry.set_if_neq(*new_telemetry);
+            }
+            (None, Some(new_telemetry)) => {
+                commands.entity(robot_entity).insert(*new_telemetry);
+            }
+            (Some(_), None) => {
+                commands.entity(robot_entity).remove::<Telemetry>();
+            }
+            (None, None) => {}
+        }
+    }
+}
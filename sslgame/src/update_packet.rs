@@ -0,0 +1,253 @@
+use crate::proto::remote::*;
+use async_channel::{Sender, TrySendError};
+use std::collections::{HashSet, VecDeque};
+
+/// Combination of the WsPacket and UdpPacket protobuf messages
+#[derive(Debug, Clone)]
+pub enum UpdatePacket {
+    FieldGeom(FieldGeometry),
+    GameState(GameState),
+    VisMappings(VisMappings),
+    WorldState(WorldState),
+    VisualizationUpdate(VisualizationUpdate),
+    RobotTelemetry(RobotTelemetryUpdate),
+    DebugValues(DebugValues),
+    StrategyPlans(StrategyPlans),
+}
+
+impl From<ws_packet::Content> for UpdatePacket {
+    fn from(packet: ws_packet::Content) -> Self {
+        match packet {
+            ws_packet::Content::Geom(inner) => Self::FieldGeom(inner),
+            ws_packet::Content::GameState(inner) => Self::GameState(inner),
+            ws_packet::Content::VisMappings(inner) => Self::VisMappings(inner),
+        }
+    }
+}
+
+/// World state deltas can only be converted with their keyframe, so they are returned as the error
+impl TryFrom<udp_packet::Content> for UpdatePacket {
+    type Error = WorldStateDelta;
+
+    fn try_from(packet: udp_packet::Content) -> Result<Self, Self::Error> {
+        Ok(match packet {
+            udp_packet::Content::WorldState(inner) => Self::WorldState(inner),
+            udp_packet::Content::VisUpdate(inner) => Self::VisualizationUpdate(inner),
+            udp_packet::Content::RobotTelemetry(inner) => Self::RobotTelemetry(inner),
+            udp_packet::Content::DebugValues(inner) => Self::DebugValues(inner),
+            udp_packet::Content::StrategyPlans(inner) => Self::StrategyPlans(inner),
+            udp_packet::Content::WorldStateDelta(inner) => return Err(inner),
+        })
+    }
+}
+
+/// Buffers packets while the channel to the field is full, so overload drops redundant packets instead of arbitrary ones.
+/// Geometry, game state and vis mapping packets are queued and never dropped. Visualization updates are merged per group.
+/// World state, telemetry, debug values and plans are complete snapshots, so only the newest one of each is kept.
+#[derive(Debug, Default)]
+pub(crate) struct PacketOutbox {
+    queued: VecDeque<UpdatePacket>,
+    visualizations: Vec<VisualizationUpdate>,
+    world_state: Option<WorldState>,
+    telemetry: Option<RobotTelemetryUpdate>,
+    debug_values: Option<DebugValues>,
+    strategy_plans: Option<StrategyPlans>,
+    /// Packets that were replaced by newer ones since the last call to [`Self::take_coalesced`]
+    coalesced: u32,
+}
+
+impl PacketOutbox {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.queued.is_empty()
+            && self.visualizations.is_empty()
+            && self.world_state.is_none()
+            && self.telemetry.is_none()
+            && self.debug_values.is_none()
+            && self.strategy_plans.is_none()
+    }
+
+    pub(crate) fn take_coalesced(&mut self) -> u32 {
+        std::mem::take(&mut self.coalesced)
+    }
+
+    pub(crate) fn push(&mut self, packet: UpdatePacket) {
+        match packet {
+            UpdatePacket::FieldGeom(_)
+            | UpdatePacket::GameState(_)
+            | UpdatePacket::VisMappings(_) => {
+                self.queued.push_back(packet);
+            }
+            UpdatePacket::WorldState(world_state) => {
+                if self.world_state.replace(world_state).is_some() {
+                    self.coalesced += 1;
+                }
+            }
+            UpdatePacket::VisualizationUpdate(update) => self.push_visualizations(update),
+            // Merging them would keep robots and values that are no longer part of the snapshot
+            UpdatePacket::RobotTelemetry(update) => {
+                if self.telemetry.replace(update).is_some() {
+                    self.coalesced += 1;
+                }
+            }
+            UpdatePacket::DebugValues(update) => {
+                if self.debug_values.replace(update).is_some() {
+                    self.coalesced += 1;
+                }
+            }
+            UpdatePacket::StrategyPlans(plans) => {
+                if self.strategy_plans.replace(plans).is_some() {
+                    self.coalesced += 1;
+                }
+            }
+        }
+    }
+
+    /// Merges the update into a pending update of the same group, keeping the sets of sources it doesn't contain.
+    /// This matches how the [`crate::VisualizationTracker`] combines multiple updates of a group.
+    fn push_visualizations(&mut self, update: VisualizationUpdate) {
+        let group_count =
+            |u: &VisualizationUpdate| u.visualization_group.as_ref().map(|g| g.group_count);
+        // Updates with a different group count are replaced completely, like in the tracker
+        self.visualizations
+            .retain(|pending| group_count(pending) == group_count(&update));
+
+        let Some(pending) = self
+            .visualizations
+            .iter_mut()
+            .find(|pending| pending.visualization_group == update.visualization_group)
+        else {
+            self.visualizations.push(update);
+            return;
+        };
+        let new_sources: HashSet<_> = update
+            .visualization_set
+            .iter()
+            .filter_map(|set| set.source)
+            .collect();
+        let mut sets = update.visualization_set;
+        sets.extend(pending.visualization_set.drain(..).filter(|set| {
+            set.source
+                .is_none_or(|source| !new_sources.contains(&source))
+        }));
+        pending.visualization_set = sets;
+        self.coalesced += 1;
+    }
+
+    /// Sends as many pending packets as fit into the channel, queued packets first.
+    /// Returns false if the receiver was dropped.
+    pub(crate) fn flush(&mut self, sender: &Sender<UpdatePacket>) -> bool {
+        fn send(
+            sender: &Sender<UpdatePacket>,
+            packet: UpdatePacket,
+        ) -> Result<(), Option<UpdatePacket>> {
+            sender.try_send(packet).map_err(|e| match e {
+                TrySendError::Full(packet) => Some(packet),
+                TrySendError::Closed(_) => None,
+            })
+        }
+
+        while let Some(packet) = self.queued.pop_front() {
+            match send(sender, packet) {
+                Ok(()) => {}
+                Err(Some(packet)) => {
+                    self.queued.push_front(packet);
+                    return true;
+                }
+                Err(None) => return false,
+            }
+        }
+
+        let pending = self
+            .visualizations
+            .drain(..)
+            .map(UpdatePacket::VisualizationUpdate)
+            .chain(self.world_state.take().map(UpdatePacket::WorldState))
+            .chain(self.telemetry.take().map(UpdatePacket::RobotTelemetry))
+            .chain(self.debug_values.take().map(UpdatePacket::DebugValues))
+            .chain(self.strategy_plans.take().map(UpdatePacket::StrategyPlans))
+            .collect::<Vec<_>>();
+        let mut pending = pending.into_iter();
+        for packet in pending.by_ref() {
+            match send(sender, packet) {
+                Ok(()) => {}
+                Err(Some(packet)) => {
+                    // The rest is pushed back, in the same order
+                    for packet in std::iter::once(packet).chain(pending.by_ref()) {
+                        self.push(packet);
+                    }
+                    return true;
+                }
+                Err(None) => return false,
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn telemetry(ids: &[u32]) -> UpdatePacket {
+        UpdatePacket::RobotTelemetry(RobotTelemetryUpdate {
+            yellow_robot: ids
+                .iter()
+                .map(|&id| RobotTelemetry {
+                    id,
+                    ..Default::default()
+                })
+                .collect(),
+            blue_robot: Vec::new(),
+        })
+    }
+
+    fn debug_values(keys: &[&str]) -> UpdatePacket {
+        UpdatePacket::DebugValues(DebugValues {
+            value: keys
+                .iter()
+                .map(|key| DebugValue {
+                    key: key.to_string(),
+                    value: None,
+                })
+                .collect(),
+        })
+    }
+
+    fn flush_all(outbox: &mut PacketOutbox) -> Vec<UpdatePacket> {
+        let (sender, receiver) = async_channel::unbounded();
+        assert!(outbox.flush(&sender));
+        assert!(outbox.is_empty());
+        std::iter::from_fn(|| receiver.try_recv().ok()).collect()
+    }
+
+    #[test]
+    fn telemetry_keeps_only_the_newest_snapshot() {
+        let mut outbox = PacketOutbox::default();
+        outbox.push(telemetry(&[1, 2]));
+        // Robot 2 was taken off the field
+        outbox.push(telemetry(&[1]));
+        assert_eq!(outbox.take_coalesced(), 1);
+
+        let packets = flush_all(&mut outbox);
+        let [UpdatePacket::RobotTelemetry(update)] = packets.as_slice() else {
+            panic!("expected a single telemetry packet, got {packets:?}");
+        };
+        let ids: Vec<_> = update.yellow_robot.iter().map(|r| r.id).collect();
+        assert_eq!(ids, [1]);
+    }
+
+    #[test]
+    fn debug_values_keep_only_the_newest_snapshot() {
+        let mut outbox = PacketOutbox::default();
+        outbox.push(debug_values(&["yellow/skill", "yellow/robot 3/skill"]));
+        outbox.push(debug_values(&["yellow/skill"]));
+        assert_eq!(outbox.take_coalesced(), 1);
+
+        let packets = flush_all(&mut outbox);
+        let [UpdatePacket::DebugValues(update)] = packets.as_slice() else {
+            panic!("expected a single debug values packet, got {packets:?}");
+        };
+        let keys: Vec<_> = update.value.iter().map(|v| v.key.as_str()).collect();
+        assert_eq!(keys, ["yellow/skill"]);
+    }
+}
@@ -0,0 +1,200 @@
+//! Cache keys for visualization meshes.
+//! Meshes are generated relative to the center of their visualization, so translated copies of a shape can share
+//! one mesh. Only what ends up in the mesh is hashed: Ids and custom geometry are ignored, coordinates are quantized.
+
+use crate::proto::remote::vis_part::Geom;
+use crate::proto::remote::{Color as ProtoColor, Point, VisPart, Visualization};
+use bevy::prelude::*;
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// Visualizations whose geometry differs by less than this distance (in meters) share a mesh.
+/// Snapping to a grid can still separate nearly identical shapes right at a grid line, so this is an upper bound.
+/// 0 only shares meshes of exactly matching (translated) shapes.
+#[derive(Resource, Reflect, Debug, Clone, Copy, PartialEq)]
+#[reflect(Resource, Debug, Default, Clone, PartialEq)]
+pub struct VisMeshTolerance(pub f32);
+
+impl Default for VisMeshTolerance {
+    fn default() -> Self {
+        // Far below the line width of visualizations
+        Self(0.001)
+    }
+}
+
+/// Hash of the mesh of a visualization whose mesh is generated relative to `origin`
+pub(crate) fn vis_mesh_key(visualization: &Visualization, origin: Vec3, tolerance: f32) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    let quantize = |value: f32| {
+        if tolerance > 0.0 {
+            (value / tolerance).round() as i64
+        } else {
+            // -0.0 and 0.0 result in the same mesh
+            i64::from((value + 0.0).to_bits())
+        }
+    };
+    // Same as in the mesh, vision x/y become x/z
+    let point = |point: &Point| [quantize(point.x - origin.x), quantize(point.y - origin.z)];
+
+    for part in &visualization.part {
+        let geom = match &part.geom {
+            Some(Geom::Circle(c)) => Some((
+                0u8,
+                vec![
+                    quantize(c.p_x - origin.x),
+                    quantize(c.p_y - origin.z),
+                    quantize(c.radius),
+                ],
+            )),
+            Some(Geom::Polygon(p)) => Some((1, p.point.iter().flat_map(point).collect())),
+            Some(Geom::Path(p)) => Some((2, p.point.iter().flat_map(point).collect())),
+            // Custom geometry isn't part of the mesh, invalid parts are skipped
+            Some(Geom::Custom(_)) | None => None,
+        };
+        let Some(geom) = geom else {
+            continue;
+        };
+        geom.hash(&mut hasher);
+        style_key(part).hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Key of the same visualization meshed with fewer circle segments, see [`crate::ContentQuality::circle_detail`].
+/// Full detail keeps the key, so the cached meshes stay valid.
+pub(crate) fn with_circle_detail(key: u64, circle_detail: f32) -> u64 {
+    if circle_detail == 1.0 {
+        return key;
+    }
+    let mut hasher = DefaultHasher::new();
+    (key, circle_detail.to_bits()).hash(&mut hasher);
+    hasher.finish()
+}
+
+fn style_key(part: &VisPart) -> impl Hash {
+    let color = |c: &ProtoColor| [c.red, c.green, c.blue, c.alpha];
+    let border = part
+        .border_style
+        .as_ref()
+        .map(|style| (style.style, style.color.as_ref().map(color)));
+    (border, part.fill_color.as_ref().map(color))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proto::remote::{Circle, Polygon};
+
+    const TOLERANCE: f32 = 0.001;
+
+    fn polygon(points: &[(f32, f32)], offset: (f32, f32)) -> Visualization {
+        Visualization {
+            id: 1,
+            part: vec![VisPart {
+                border_style: None,
+                fill_color: Some(ProtoColor {
+                    red: 255,
+                    green: 0,
+                    blue: 0,
+                    alpha: 128,
+                }),
+                geom: Some(Geom::Polygon(Polygon {
+                    point: points
+                        .iter()
+                        .map(|(x, y)| Point {
+                            x: x + offset.0,
+                            y: y + offset.1,
+                        })
+                        .collect(),
+                })),
+            }],
+        }
+    }
+
+    /// The origin the renderer would pick, the center of the bounding box
+    fn key(visualization: &Visualization, tolerance: f32) -> u64 {
+        let Some(Geom::Polygon(p)) = &visualization.part[0].geom else {
+            unreachable!()
+        };
+        let (min, max) =
+            p.point
+                .iter()
+                .fold((Vec2::INFINITY, Vec2::NEG_INFINITY), |(min, max), p| {
+                    let p = Vec2::new(p.x, p.y);
+                    (min.min(p), max.max(p))
+                });
+        let center = (min + max) / 2.0;
+        vis_mesh_key(visualization, Vec3::new(center.x, 0.0, center.y), tolerance)
+    }
+
+    const TRIANGLE: [(f32, f32); 3] = [(0.0, 0.0), (0.5, 0.0), (0.0, 0.25)];
+
+    #[test]
+    fn translated_copies_share_a_mesh() {
+        let a = polygon(&TRIANGLE, (0.0, 0.0));
+        let b = polygon(&TRIANGLE, (1.5, -2.0));
+        assert_eq!(key(&a, TOLERANCE), key(&b, TOLERANCE));
+    }
+
+    #[test]
+    fn translated_copies_share_a_mesh_without_tolerance() {
+        let a = polygon(&TRIANGLE, (0.0, 0.0));
+        let b = polygon(&TRIANGLE, (2.0, 1.0));
+        assert_eq!(key(&a, 0.0), key(&b, 0.0));
+    }
+
+    #[test]
+    fn near_identical_shapes_share_a_mesh() {
+        let a = polygon(&TRIANGLE, (0.0, 0.0));
+        let b = polygon(&[(0.0, 0.0), (0.5001, 0.0), (0.0, 0.2501)], (0.0, 0.0));
+        assert_eq!(key(&a, TOLERANCE), key(&b, TOLERANCE));
+        assert_ne!(key(&a, 0.0), key(&b, 0.0));
+    }
+
+    #[test]
+    fn different_shapes_get_different_meshes() {
+        let a = polygon(&TRIANGLE, (0.0, 0.0));
+        let b = polygon(&[(0.0, 0.0), (0.5, 0.0), (0.0, 0.3)], (0.0, 0.0));
+        assert_ne!(key(&a, TOLERANCE), key(&b, TOLERANCE));
+
+        let mut recolored = a.clone();
+        recolored.part[0].fill_color.as_mut().unwrap().green = 255;
+        assert_ne!(key(&a, TOLERANCE), key(&recolored, TOLERANCE));
+
+        // Circles and polygons with the same numbers are still different shapes
+        let circle = Visualization {
+            id: 1,
+            part: vec![VisPart {
+                geom: Some(Geom::Circle(Circle {
+                    p_x: 0.0,
+                    p_y: 0.0,
+                    radius: 0.5,
+                })),
+                ..a.part[0].clone()
+            }],
+        };
+        assert_ne!(
+            vis_mesh_key(&circle, Vec3::ZERO, TOLERANCE),
+            vis_mesh_key(&a, Vec3::ZERO, TOLERANCE)
+        );
+    }
+
+    #[test]
+    fn ids_and_custom_geometry_are_ignored() {
+        let a = polygon(&TRIANGLE, (0.0, 0.0));
+        let mut b = a.clone();
+        b.id = 2;
+        b.part.push(VisPart {
+            border_style: None,
+            fill_color: None,
+            geom: Some(Geom::Custom(Default::default())),
+        });
+        assert_eq!(key(&a, TOLERANCE), key(&b, TOLERANCE));
+    }
+
+    #[test]
+    fn reduced_circle_detail_gets_a_different_mesh() {
+        let a = key(&polygon(&TRIANGLE, (0.0, 0.0)), TOLERANCE);
+        assert_eq!(with_circle_detail(a, 1.0), a);
+        assert_ne!(with_circle_detail(a, 0.25), a);
+    }
+}
@@ -1,129 +1,212 @@
-use crate::proto::remote::vis_part::Geom;
-use crate::proto::remote::{Visualization, VisualizationUpdate};
+use crate::proto::remote::{Visualization, VisualizationFilter};
+use crate::snapshot::{VisFrame, VisSet};
 use bevy::prelude::Component;
-use std::collections::{HashMap, HashSet, VecDeque};
+use prost::Message;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
 
 #[derive(Component, Debug, Default)]
 pub struct VisualizationTracker {
-    history: VecDeque<VisualizationUpdate>,
+    group_count: u32,
+    /// The newest set of each source in each group. Hosts that merge multiple sources may send them in separate frames,
+    /// so a set only replaces the older set of the same source, and the sets of other sources are kept.
+    sets: HashMap<u32, Vec<VisSet>>,
+    /// Groups that received a frame since the last call to [`Self::visualization_updates`]
+    pending: HashSet<u32>,
+    /// A hash of the visualizations of each group as they were last returned
+    consumed: HashMap<u32, u64>,
+    /// Set when the group count changed, all visualizations have to be replaced
+    regrouped: bool,
 }
 
 impl VisualizationTracker {
-    // TODO: Filter in case the host doesn't properly handle filters server-side
-    /// Collects all updated visualization since the last call
-    /// and some information about which groups are affected.
+    /// Collects all groups whose visualizations changed since the last call.
+    /// Hosts resend unchanged visualizations regularly, those groups are skipped.
+    /// Visualizations that aren't allowed by the filter are dropped, in case the host doesn't properly handle filters server-side.
     /// (group_count, updated_groups, new_visualizations)
-    pub fn visualization_updates(&mut self) -> (u32, HashSet<u32>, Vec<Visualization>) {
-        if self.history.is_empty() {
+    pub fn visualization_updates(
+        &mut self,
+        filter: &VisualizationFilter,
+    ) -> (u32, HashSet<u32>, Vec<Visualization>) {
+        if self.pending.is_empty() {
             return Default::default();
         }
 
-        let group_count = self
-            .history
-            .front()
-            .and_then(|v| v.visualization_group)
-            .map(|g| g.group_count)
-            .unwrap_or(1);
+        let allowed_sources: HashSet<_> = filter.allowed_vis_source.iter().copied().collect();
+        let allowed_ids: HashSet<_> = filter.allowed_vis_id.iter().copied().collect();
 
-        // Save the set of already collected sources for each group
-        let mut group_sources: HashMap<u32, HashSet<u32>> = HashMap::new();
+        // Everything is replaced after a regrouping, even groups that didn't get a frame yet
+        let mut updated_groups: HashSet<u32> = if self.regrouped {
+            (0..self.group_count).collect()
+        } else {
+            HashSet::new()
+        };
+        self.regrouped = false;
         let mut visualizations = Vec::new();
 
-        self.history
-            .iter()
-            .map(|v| (v.visualization_group.unwrap(), &v.visualization_set))
-            .for_each(|(group, vis_sets)| {
-                let seen_sources = group_sources.entry(group.group).or_default();
-
-                for vis_set in vis_sets {
-                    if vis_set
-                        .source
-                        .is_some_and(|source| seen_sources.contains(&source))
-                    {
-                        // Already collected this source from this group
-                        continue;
-                    } else if let Some(source) = vis_set.source {
-                        // New source for this group
-                        seen_sources.insert(source);
-                    }
-
-                    for vis in &vis_set.visualization {
-                        visualizations.push(vis.clone());
-                    }
-                }
-            });
-
-        // Clear the history so that each update is only returned once
-        self.history.clear();
-
-        (
-            group_count,
-            group_sources.keys().copied().collect(),
-            visualizations,
-        )
-    }
-
-    pub fn push_update(&mut self, mut new_update: VisualizationUpdate) {
-        remap_visualizations(&mut new_update);
-
-        let new_group_count = new_update
-            .visualization_group
-            .map(|g| g.group_count)
-            .unwrap_or(1);
+        let mut encoded = Vec::new();
+        for group in self.pending.drain() {
+            let group_visualizations = || {
+                self.sets
+                    .get(&group)
+                    .into_iter()
+                    .flatten()
+                    .filter(|set| {
+                        set.source
+                            .is_none_or(|source| allowed_sources.contains(&source))
+                    })
+                    .flat_map(|set| &set.visualizations)
+                    .filter(|vis| allowed_ids.contains(&vis.id))
+            };
+
+            // Only a hash is kept to compare the next frame, so unchanged groups aren't cloned
+            let mut hasher = DefaultHasher::new();
+            for vis in group_visualizations() {
+                encoded.clear();
+                vis.encode_raw(&mut encoded);
+                encoded.hash(&mut hasher);
+            }
+            // An update without visualizations still clears the old ones
+            let fingerprint = hasher.finish();
+            if self.consumed.insert(group, fingerprint) == Some(fingerprint) {
+                continue;
+            }
+            updated_groups.insert(group);
+            visualizations.extend(group_visualizations().cloned());
+        }
 
-        self.history.push_front(new_update);
+        (self.group_count, updated_groups, visualizations)
+    }
 
-        // Truncate so that each group in the current group range is contained at least once
-        let mut seen_groups = HashSet::new();
-        let mut truncate_at = None;
+    pub(crate) fn push_frame(&mut self, new_frame: VisFrame) {
+        // Groups of a different group count cover different visualizations, so older frames are useless
+        if new_frame.group_count != self.group_count {
+            self.group_count = new_frame.group_count;
+            self.sets.clear();
+            self.pending.clear();
+            self.consumed.clear();
+            self.regrouped = true;
+        }
 
-        for (i, update) in self.history.iter().enumerate() {
-            if let Some(group) = update.visualization_group {
-                if new_group_count == group.group_count {
-                    seen_groups.insert(group.group);
-                } else {
-                    truncate_at = Some(i);
-                    break;
-                }
+        let sets = self.sets.entry(new_frame.group).or_default();
+        // Only the first set of each source is used
+        let mut seen_sources = HashSet::new();
+        for set in new_frame.sets {
+            if !seen_sources.insert(set.source) {
+                continue;
             }
-
-            if seen_groups.len() >= new_group_count as usize {
-                truncate_at = Some(i + 1);
-                break;
+            match sets.iter_mut().find(|old| old.source == set.source) {
+                Some(old) => *old = set,
+                None => sets.push(set),
             }
         }
+        self.pending.insert(new_frame.group);
+    }
+}
 
-        if let Some(index) = truncate_at {
-            self.history.truncate(index);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(group: u32, group_count: u32, sets: &[(Option<u32>, &[u32])]) -> VisFrame {
+        VisFrame {
+            group,
+            group_count,
+            sets: sets
+                .iter()
+                .map(|(source, ids)| VisSet {
+                    source: *source,
+                    visualizations: ids
+                        .iter()
+                        .map(|&id| Visualization {
+                            id,
+                            part: Vec::new(),
+                        })
+                        .collect(),
+                })
+                .collect(),
         }
     }
-}
 
-/// Converts from the vision coordinate system (right-handed, z up, x towards blue goal, +x forward)
-/// to bevy's coordinate system (right-handed, y up, x towards blue goal, -z forward) with y and z swapped
-fn remap_visualizations(vis_update: &mut VisualizationUpdate) {
-    for vis in vis_update
-        .visualization_set
-        .iter_mut()
-        .flat_map(|set| &mut set.visualization)
-    {
-        for part in &mut vis.part {
-            match &mut part.geom {
-                Some(Geom::Circle(c)) => {
-                    c.p_y = -c.p_y;
-                }
-                Some(Geom::Polygon(p)) => {
-                    for point in &mut p.point {
-                        point.y = -point.y;
-                    }
-                }
-                Some(Geom::Path(p)) => {
-                    for point in &mut p.point {
-                        point.y = -point.y;
-                    }
-                }
-                None => {}
-            }
+    fn allow_all() -> VisualizationFilter {
+        VisualizationFilter {
+            allowed_vis_source: (0..4).collect(),
+            allowed_vis_id: (0..16).collect(),
         }
     }
+
+    fn ids(visualizations: &[Visualization]) -> Vec<u32> {
+        visualizations.iter().map(|vis| vis.id).collect()
+    }
+
+    #[test]
+    fn sources_in_separate_frames_are_combined() {
+        let mut tracker = VisualizationTracker::default();
+        tracker.push_frame(frame(0, 1, &[(Some(0), &[1, 2])]));
+        tracker.push_frame(frame(0, 1, &[(Some(1), &[3])]));
+
+        let (_, groups, visualizations) = tracker.visualization_updates(&allow_all());
+        assert_eq!(groups, HashSet::from([0]));
+        assert_eq!(ids(&visualizations), [1, 2, 3]);
+    }
+
+    #[test]
+    fn sources_are_kept_across_updates() {
+        let mut tracker = VisualizationTracker::default();
+        tracker.push_frame(frame(0, 1, &[(Some(0), &[1]), (Some(1), &[3])]));
+        tracker.visualization_updates(&allow_all());
+
+        // Only source 1 changed, the visualizations of source 0 stay
+        tracker.push_frame(frame(0, 1, &[(Some(1), &[4])]));
+        let (_, groups, visualizations) = tracker.visualization_updates(&allow_all());
+        assert_eq!(groups, HashSet::from([0]));
+        assert_eq!(ids(&visualizations), [1, 4]);
+
+        // An empty set clears its source
+        tracker.push_frame(frame(0, 1, &[(Some(0), &[])]));
+        let (_, _, visualizations) = tracker.visualization_updates(&allow_all());
+        assert_eq!(ids(&visualizations), [4]);
+    }
+
+    #[test]
+    fn unchanged_sources_are_skipped() {
+        let mut tracker = VisualizationTracker::default();
+        tracker.push_frame(frame(0, 1, &[(Some(0), &[1]), (Some(1), &[2])]));
+        tracker.visualization_updates(&allow_all());
+
+        tracker.push_frame(frame(0, 1, &[(Some(1), &[2])]));
+        let (_, groups, visualizations) = tracker.visualization_updates(&allow_all());
+        assert!(groups.is_empty());
+        assert!(visualizations.is_empty());
+    }
+
+    #[test]
+    fn regrouping_drops_all_sources() {
+        let mut tracker = VisualizationTracker::default();
+        tracker.push_frame(frame(0, 1, &[(Some(0), &[1]), (Some(1), &[2])]));
+        tracker.visualization_updates(&allow_all());
+
+        tracker.push_frame(frame(1, 2, &[(Some(1), &[3])]));
+        let (group_count, groups, visualizations) = tracker.visualization_updates(&allow_all());
+        assert_eq!(group_count, 2);
+        assert_eq!(groups, HashSet::from([0, 1]));
+        assert_eq!(ids(&visualizations), [3]);
+    }
+
+    #[test]
+    fn filtered_sources_are_dropped() {
+        let mut tracker = VisualizationTracker::default();
+        tracker.push_frame(frame(
+            0,
+            1,
+            &[(Some(0), &[1]), (Some(2), &[2]), (None, &[3])],
+        ));
+        let filter = VisualizationFilter {
+            allowed_vis_source: vec![0],
+            ..allow_all()
+        };
+
+        let (_, _, visualizations) = tracker.visualization_updates(&filter);
+        assert_eq!(ids(&visualizations), [1, 3]);
+    }
 }
@@ -0,0 +1,356 @@
+//! Delta encoding of the world state stream, see `UdpStreamRequest.world_state_deltas`.
+//! Positions are quantized to millimeters and milliradians relative to a keyframe, which fit into one or two bytes
+//! at high update rates instead of four bytes per float.
+
+use crate::proto::remote::{
+    Ball, BallDelta, Robot, RobotDelta, WorldState, WorldStateDelta, udp_packet,
+};
+use crate::update_packet::UpdatePacket;
+use bevy::prelude::*;
+use std::f32::consts::{PI, TAU};
+
+/// Full world states are sent at least this often, so a lost keyframe is only missed for a short time
+const KEYFRAME_INTERVAL: u32 = 20;
+
+fn quantize(value: f32) -> i32 {
+    (value * 1000.0).round() as i32
+}
+
+fn dequantize(value: Option<i32>) -> f32 {
+    value.unwrap_or(0) as f32 / 1000.0
+}
+
+/// Omits zero values from the encoded packet
+fn non_zero(value: i32) -> Option<i32> {
+    (value != 0).then_some(value)
+}
+
+fn same_robots(a: &[Robot], b: &[Robot]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.id == b.id)
+}
+
+/// Turns world states into keyframes and deltas on the host side
+#[derive(Debug, Default)]
+pub(crate) struct WorldStateEncoder {
+    keyframe: Option<WorldState>,
+    next_keyframe: u32,
+    since_keyframe: u32,
+}
+
+impl WorldStateEncoder {
+    pub(crate) fn encode(&mut self, mut world_state: WorldState) -> udp_packet::Content {
+        self.since_keyframe += 1;
+        if let Some(keyframe) = &self.keyframe
+            && self.since_keyframe < KEYFRAME_INTERVAL
+            && keyframe.ball.len() == world_state.ball.len()
+            && same_robots(&keyframe.yellow_robot, &world_state.yellow_robot)
+            && same_robots(&keyframe.blue_robot, &world_state.blue_robot)
+        {
+            return udp_packet::Content::WorldStateDelta(Self::delta(keyframe, &world_state));
+        }
+
+        world_state.keyframe = Some(self.next_keyframe);
+        self.next_keyframe = self.next_keyframe.wrapping_add(1);
+        self.since_keyframe = 0;
+        self.keyframe = Some(world_state.clone());
+        udp_packet::Content::WorldState(world_state)
+    }
+
+    fn delta(keyframe: &WorldState, world_state: &WorldState) -> WorldStateDelta {
+        let robot_deltas = |keyframe: &[Robot], robots: &[Robot]| -> Vec<RobotDelta> {
+            keyframe
+                .iter()
+                .zip(robots)
+                .map(|(key, robot)| RobotDelta {
+                    id: robot.id,
+                    d_x: non_zero(quantize(robot.p_x - key.p_x)),
+                    d_y: non_zero(quantize(robot.p_y - key.p_y)),
+                    // Wrapped, so robots turning past ±pi don't produce large deltas
+                    d_phi: non_zero(quantize((robot.phi - key.phi + PI).rem_euclid(TAU) - PI)),
+                    v_phi: robot.v_phi,
+                })
+                // Robots that didn't move are omitted
+                .filter(|delta| {
+                    delta.d_x.is_some()
+                        || delta.d_y.is_some()
+                        || delta.d_phi.is_some()
+                        || delta.v_phi.is_some_and(|v| v != 0.0)
+                })
+                .collect()
+        };
+
+        WorldStateDelta {
+            keyframe: keyframe.keyframe.unwrap_or_default(),
+            timestamp: world_state.timestamp,
+            ball: keyframe
+                .ball
+                .iter()
+                .zip(&world_state.ball)
+                .map(|(key, ball)| BallDelta {
+                    d_x: non_zero(quantize(ball.p_x - key.p_x)),
+                    d_y: non_zero(quantize(ball.p_y - key.p_y)),
+                    d_z: non_zero(quantize(ball.p_z() - key.p_z())),
+                })
+                .collect(),
+            yellow_robot: robot_deltas(&keyframe.yellow_robot, &world_state.yellow_robot),
+            blue_robot: robot_deltas(&keyframe.blue_robot, &world_state.blue_robot),
+        }
+    }
+}
+
+/// Reconstructs full world states from keyframes and deltas on the client side
+#[derive(Debug, Default)]
+pub(crate) struct WorldStateDecoder {
+    keyframe: Option<WorldState>,
+}
+
+impl WorldStateDecoder {
+    /// Converts a received packet, returns `None` for deltas of an unknown keyframe
+    pub(crate) fn decode(&mut self, packet: udp_packet::Content) -> Option<UpdatePacket> {
+        match UpdatePacket::try_from(packet) {
+            Ok(UpdatePacket::WorldState(world_state)) => {
+                if world_state.keyframe.is_some() {
+                    self.keyframe = Some(world_state.clone());
+                }
+                Some(UpdatePacket::WorldState(world_state))
+            }
+            Ok(packet) => Some(packet),
+            Err(delta) => {
+                let world_state = self
+                    .keyframe
+                    .as_ref()
+                    .filter(|keyframe| keyframe.keyframe == Some(delta.keyframe))
+                    .map(|keyframe| Self::apply(keyframe, delta));
+                if world_state.is_none() {
+                    debug!("Dropped world state delta of a missing keyframe");
+                }
+                world_state.map(UpdatePacket::WorldState)
+            }
+        }
+    }
+
+    fn apply(keyframe: &WorldState, delta: WorldStateDelta) -> WorldState {
+        let apply_robots = |keyframe: &[Robot], deltas: &[RobotDelta]| -> Vec<Robot> {
+            keyframe
+                .iter()
+                .map(|robot| {
+                    let Some(delta) = deltas.iter().find(|delta| delta.id == robot.id) else {
+                        // Unchanged robots are still, since a moving robot would have a delta
+                        return Robot {
+                            v_phi: robot.v_phi.map(|_| 0.0),
+                            ..*robot
+                        };
+                    };
+                    Robot {
+                        id: robot.id,
+                        p_x: robot.p_x + dequantize(delta.d_x),
+                        p_y: robot.p_y + dequantize(delta.d_y),
+                        phi: robot.phi + dequantize(delta.d_phi),
+                        v_phi: delta.v_phi,
+                    }
+                })
+                .collect()
+        };
+
+        WorldState {
+            timestamp: delta.timestamp,
+            ball: keyframe
+                .ball
+                .iter()
+                .zip(
+                    delta
+                        .ball
+                        .iter()
+                        .chain(std::iter::repeat(&BallDelta::default())),
+                )
+                .map(|(ball, delta)| Ball {
+                    p_x: ball.p_x + dequantize(delta.d_x),
+                    p_y: ball.p_y + dequantize(delta.d_y),
+                    p_z: (ball.p_z.is_some() || delta.d_z.is_some())
+                        .then(|| ball.p_z() + dequantize(delta.d_z)),
+                })
+                .collect(),
+            yellow_robot: apply_robots(&keyframe.yellow_robot, &delta.yellow_robot),
+            blue_robot: apply_robots(&keyframe.blue_robot, &delta.blue_robot),
+            keyframe: None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Quantization rounds to the nearest millimeter, plus a margin for the float math
+    const MAX_ERROR: f32 = 0.0005 + 1e-5;
+
+    fn robot(id: u32, p_x: f32, p_y: f32, phi: f32) -> Robot {
+        Robot {
+            id,
+            p_x,
+            p_y,
+            phi,
+            v_phi: None,
+        }
+    }
+
+    fn world_state(timestamp: u64, yellow_robot: Vec<Robot>, ball: (f32, f32)) -> WorldState {
+        WorldState {
+            timestamp: Some(timestamp),
+            ball: vec![Ball {
+                p_x: ball.0,
+                p_y: ball.1,
+                p_z: None,
+            }],
+            yellow_robot,
+            blue_robot: vec![robot(0, -1.0, -1.0, 0.0)],
+            keyframe: None,
+        }
+    }
+
+    /// Encodes and decodes the world state, returning whether a delta was sent and the decoded state
+    fn roundtrip(
+        encoder: &mut WorldStateEncoder,
+        decoder: &mut WorldStateDecoder,
+        world_state: WorldState,
+    ) -> (bool, WorldState) {
+        let packet = encoder.encode(world_state);
+        let is_delta = matches!(packet, udp_packet::Content::WorldStateDelta(_));
+        match decoder.decode(packet) {
+            Some(UpdatePacket::WorldState(decoded)) => (is_delta, decoded),
+            other => panic!("Expected a world state, got {other:?}"),
+        }
+    }
+
+    fn assert_close(decoded: &WorldState, expected: &WorldState) {
+        assert_eq!(decoded.timestamp, expected.timestamp);
+        for (decoded, expected) in decoded.ball.iter().zip(&expected.ball) {
+            assert!((decoded.p_x - expected.p_x).abs() <= MAX_ERROR);
+            assert!((decoded.p_y - expected.p_y).abs() <= MAX_ERROR);
+        }
+        let robots = |state: &WorldState| -> Vec<Robot> {
+            state
+                .yellow_robot
+                .iter()
+                .chain(&state.blue_robot)
+                .copied()
+                .collect()
+        };
+        let (decoded, expected) = (robots(decoded), robots(expected));
+        assert_eq!(decoded.len(), expected.len());
+        for (decoded, expected) in decoded.iter().zip(&expected) {
+            assert_eq!(decoded.id, expected.id);
+            assert!((decoded.p_x - expected.p_x).abs() <= MAX_ERROR);
+            assert!((decoded.p_y - expected.p_y).abs() <= MAX_ERROR);
+            // The angle may differ by a full turn
+            let d_phi = (decoded.phi - expected.phi + PI).rem_euclid(TAU) - PI;
+            assert!(
+                d_phi.abs() <= MAX_ERROR,
+                "phi {} != {}",
+                decoded.phi,
+                expected.phi
+            );
+        }
+    }
+
+    #[test]
+    fn keyframe_and_deltas_roundtrip() {
+        let mut encoder = WorldStateEncoder::default();
+        let mut decoder = WorldStateDecoder::default();
+        for i in 0..2 * KEYFRAME_INTERVAL {
+            let t = i as f32 * 0.01;
+            let state = world_state(
+                u64::from(i),
+                vec![robot(1, t, 1.0 - t, t * 3.0), robot(4, 2.0, t * 2.0, -t)],
+                (t * 5.0, -t),
+            );
+            let (is_delta, decoded) = roundtrip(&mut encoder, &mut decoder, state.clone());
+            assert_eq!(is_delta, i % KEYFRAME_INTERVAL != 0, "packet {i}");
+            assert_close(&decoded, &state);
+            assert_eq!(decoded.keyframe.is_some(), !is_delta);
+        }
+    }
+
+    #[test]
+    fn quantization_error_is_bounded() {
+        let mut encoder = WorldStateEncoder::default();
+        let mut decoder = WorldStateDecoder::default();
+        let keyframe = world_state(0, vec![robot(1, 0.0, 0.0, 3.1)], (0.0, 0.0));
+        roundtrip(&mut encoder, &mut decoder, keyframe);
+
+        for i in 1..KEYFRAME_INTERVAL {
+            // Odd fractions of a millimeter, and turning past ±pi
+            let offset = i as f32 * 0.0123457;
+            let phi = (3.1 + offset + PI).rem_euclid(TAU) - PI;
+            let state = world_state(
+                u64::from(i),
+                vec![robot(1, 4.5 + offset, -3.0 - offset, phi)],
+                (offset, 0.5 - offset),
+            );
+            let (is_delta, decoded) = roundtrip(&mut encoder, &mut decoder, state.clone());
+            assert!(is_delta);
+            assert_close(&decoded, &state);
+        }
+    }
+
+    #[test]
+    fn robots_are_omitted_and_readded() {
+        let mut encoder = WorldStateEncoder::default();
+        let mut decoder = WorldStateDecoder::default();
+        let robots = vec![robot(1, 1.0, 1.0, 0.0), robot(2, 2.0, 2.0, 0.0)];
+        roundtrip(
+            &mut encoder,
+            &mut decoder,
+            world_state(0, robots.clone(), (0.0, 0.0)),
+        );
+
+        // Robot 2 didn't move, so only robot 1 is in the delta, but both are decoded
+        let moved = vec![robot(1, 1.5, 1.0, 0.0), robot(2, 2.0, 2.0, 0.0)];
+        let packet = encoder.encode(world_state(1, moved.clone(), (0.0, 0.0)));
+        let udp_packet::Content::WorldStateDelta(delta) = &packet else {
+            panic!("Expected a delta");
+        };
+        assert_eq!(delta.yellow_robot.len(), 1);
+        assert_eq!(delta.yellow_robot[0].id, 1);
+        assert!(delta.blue_robot.is_empty());
+        let Some(UpdatePacket::WorldState(decoded)) = decoder.decode(packet) else {
+            panic!("Expected a world state");
+        };
+        assert_close(&decoded, &world_state(1, moved, (0.0, 0.0)));
+
+        // Removing and re-adding a robot changes the robots, which needs a new keyframe each time
+        let removed = world_state(2, vec![robot(1, 1.5, 1.0, 0.0)], (0.0, 0.0));
+        let (is_delta, decoded) = roundtrip(&mut encoder, &mut decoder, removed.clone());
+        assert!(!is_delta);
+        assert_close(&decoded, &removed);
+
+        let readded = world_state(3, robots, (0.0, 0.0));
+        let (is_delta, decoded) = roundtrip(&mut encoder, &mut decoder, readded.clone());
+        assert!(!is_delta);
+        assert_close(&decoded, &readded);
+    }
+
+    #[test]
+    fn deltas_without_their_keyframe_are_dropped() {
+        let mut encoder = WorldStateEncoder::default();
+        let robots = vec![robot(1, 1.0, 1.0, 0.0)];
+        let keyframe = encoder.encode(world_state(0, robots.clone(), (0.0, 0.0)));
+        let delta = encoder.encode(world_state(1, robots, (0.5, 0.0)));
+        assert!(matches!(delta, udp_packet::Content::WorldStateDelta(_)));
+
+        // The keyframe was lost
+        let mut decoder = WorldStateDecoder::default();
+        assert!(decoder.decode(delta.clone()).is_none());
+
+        // A keyframe with another id doesn't help either
+        let udp_packet::Content::WorldState(mut other) = keyframe.clone() else {
+            panic!("Expected a keyframe");
+        };
+        other.keyframe = Some(7);
+        decoder.decode(udp_packet::Content::WorldState(other));
+        assert!(decoder.decode(delta.clone()).is_none());
+
+        decoder.decode(keyframe);
+        assert!(decoder.decode(delta).is_some());
+    }
+}
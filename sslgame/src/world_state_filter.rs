@@ -1,23 +1,75 @@
-use crate::proto::remote::{Ball, Robot, WorldState};
+use crate::snapshot::{BallState, RobotState, WorldSnapshot};
 use bevy::prelude::*;
 use std::collections::VecDeque;
 use std::f32::consts::PI;
+use std::fmt::{self, Debug};
+use std::sync::Arc;
 use std::sync::atomic::Ordering::SeqCst;
 use std::sync::atomic::{AtomicI64, AtomicU32};
 use std::time::{Duration, Instant};
 
+/// Turns the received world states of a field into the displayed world state.
+/// Implement this to replace the default [`BufferedStateFilter`] with a custom tracker, e.g.
+/// `commands.entity(field).insert(StateFilter::new(MyFilter::default()))`.
+pub trait WorldStateFilter: Debug + Send + Sync + 'static {
+    /// Called for every world state received from the host, in order of arrival.
+    fn push_packet(&mut self, packet: WorldSnapshot);
+
+    /// Returns the world state to display at the given time.
+    /// If `interpolate` is false, the filter should return its newest state without additional delay.
+    fn sample_at(&self, time: Instant, interpolate: bool) -> Arc<WorldSnapshot>;
+
+    /// Health information for debugging and display.
+    fn metrics(&self) -> FilterMetrics {
+        FilterMetrics::default()
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FilterMetrics {
+    /// Number of received packets the filter is currently holding
+    pub buffered_packets: usize,
+    /// Smallest remaining buffer time before running out of packets in the current tracking period
+    pub min_buffer_time: Option<Duration>,
+    /// Number of samples in the current tracking period where no newer packet was available
+    pub stutters: u32,
+}
+
+/// The state filter of a field. Defaults to a [`BufferedStateFilter`].
+#[derive(Component, Debug, Deref, DerefMut)]
+pub struct StateFilter(Box<dyn WorldStateFilter>);
+
+impl StateFilter {
+    pub fn new(filter: impl WorldStateFilter) -> Self {
+        Self(Box::new(filter))
+    }
+}
+
+impl Default for StateFilter {
+    fn default() -> Self {
+        Self::new(BufferedStateFilter::default())
+    }
+}
+
+// ======== Default implementation ========
+
 // TODO: Replace all of this with a kalman filter
 
 // TODO: Make this variable based on connection instability
 const TARGET_BUFFER_TIME: Duration = Duration::from_millis(10);
+/// Velocities are only estimated from packets that are at most this far apart
+const MAX_ESTIMATION_INTERVAL: Duration = Duration::from_millis(50);
 
-#[derive(Component, Debug)]
-pub struct WorldStateFilter {
+/// Buffers the received packets and interpolates between them with a delay that adapts to the connection quality.
+#[derive(Debug)]
+pub struct BufferedStateFilter {
     /// Sliding window of the past received packets with their timestamp relative to time_reference.
-    history: VecDeque<(u64, WorldState)>,
+    /// Packets are shared, so that returning an uninterpolated packet doesn't copy it.
+    history: VecDeque<(u64, Arc<WorldSnapshot>)>,
 
     /// Constant reference time to derive the timestamps from
     time_reference: Instant,
+    clock: FilterClock,
     /// Estimated offset of the timestamps in the incoming packets to the local timestamps derived from time_reference.
     /// Is adjusted dynamically to get the optimal buffer delay for the current connection quality.
     time_offset: Option<i64>,
@@ -28,6 +80,22 @@ pub struct WorldStateFilter {
     buffer_health_tracker: Option<BufferHealthTracker>,
 }
 
+/// The system time, or a custom clock, see [`BufferedStateFilter::with_clock`]
+#[derive(Clone, Default)]
+struct FilterClock(Option<Arc<dyn Fn() -> Instant + Send + Sync>>);
+
+impl FilterClock {
+    fn now(&self) -> Instant {
+        self.0.as_ref().map_or_else(Instant::now, |clock| clock())
+    }
+}
+
+impl Debug for FilterClock {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(if self.0.is_some() { "Custom" } else { "System" })
+    }
+}
+
 #[derive(Debug)]
 struct BufferHealthTracker {
     min_buffer_health: AtomicI64,
@@ -35,11 +103,12 @@ struct BufferHealthTracker {
     scheduled_time: Instant,
 }
 
-impl Default for WorldStateFilter {
+impl Default for BufferedStateFilter {
     fn default() -> Self {
         Self {
             history: VecDeque::new(),
             time_reference: Instant::now(),
+            clock: FilterClock::default(),
             time_offset: None,
             health_tracking_period: Duration::from_secs(10),
             buffer_health_tracker: None,
@@ -47,17 +116,35 @@ impl Default for WorldStateFilter {
     }
 }
 
-impl WorldStateFilter {
-    pub fn current_world_state(&self, filter: bool) -> WorldState {
-        if !filter {
+impl BufferedStateFilter {
+    /// Uses the given clock for the arrival of packets instead of the system time, e.g. to play back a recording
+    /// slower than real time. The world state has to be sampled at times of the same clock,
+    /// see [`crate::WorldStateSampling::display_time`].
+    pub fn with_clock(clock: impl Fn() -> Instant + Send + Sync + 'static) -> Self {
+        let clock = FilterClock(Some(Arc::new(clock)));
+        Self {
+            time_reference: clock.now(),
+            clock,
+            ..default()
+        }
+    }
+}
+
+impl WorldStateFilter for BufferedStateFilter {
+    /// Without interpolation, the newest packet is returned regardless of the time.
+    fn sample_at(&self, sample_time: Instant, interpolate: bool) -> Arc<WorldSnapshot> {
+        let _span = info_span!("world_state_filter_query", interpolate).entered();
+        if !interpolate {
             return self
                 .history
                 .front()
-                .map(|(_, state)| state.clone())
+                .map(|(_, state)| Arc::clone(state))
                 .unwrap_or_default();
         }
 
-        let curr_timestamp = self.time_reference.elapsed().as_micros() as u64;
+        let curr_timestamp = sample_time
+            .saturating_duration_since(self.time_reference)
+            .as_micros() as u64;
 
         // Find relevant packets
         let prev_idx = self
@@ -67,7 +154,8 @@ impl WorldStateFilter {
             .find(|(_, (time, _))| time < &curr_timestamp)
             .map(|(idx, _)| idx)
             .unwrap_or(usize::MAX); // Impossible value to also invalidate next_idx
-        let next_idx = prev_idx - 1;
+        // Wraps to an invalid index if prev is the newest packet
+        let next_idx = prev_idx.wrapping_sub(1);
         let (prev, next) = (self.history.get(prev_idx), self.history.get(next_idx));
 
         match (prev, next) {
@@ -81,7 +169,13 @@ impl WorldStateFilter {
                     );
                 }
 
-                interpolate_world_state(curr_timestamp, *prev_time, prev, *next_time, next)
+                Arc::new(interpolate_world_state(
+                    curr_timestamp,
+                    *prev_time,
+                    prev,
+                    *next_time,
+                    next,
+                ))
             }
             // Buffer too small: Already past newest available packet
             (Some((prev_time, prev)), None) => {
@@ -99,32 +193,33 @@ impl WorldStateFilter {
                 if let Some((prev_prev_time, prev_prev)) = prev_prev {
                     // Two past packets available -> extrapolate
                     // TODO: Fix extrapolation
-                    interpolate_world_state(
+                    Arc::new(interpolate_world_state(
                         curr_timestamp,
                         *prev_prev_time,
                         prev_prev,
                         *prev_time,
                         prev,
-                    )
+                    ))
                 } else {
                     // Only one packet available
-                    prev.clone()
+                    Arc::clone(prev)
                 }
             }
             (None, Some(_next)) => {
                 unreachable!("Next can only be derived from an existing prev value")
             }
-            (None, None) => WorldState::default(),
+            (None, None) => Arc::default(),
         }
     }
 
-    pub fn push_packet(&mut self, mut packet: WorldState) {
-        let now = Instant::now();
+    fn push_packet(&mut self, mut packet: WorldSnapshot) {
+        let _span = info_span!("world_state_filter_push").entered();
+        let now = self.clock.now();
         let current_timestamp = (now - self.time_reference).as_micros() as u64;
 
         // Set initial offset
         if self.time_offset.is_none() {
-            self.time_offset = Some(current_timestamp as i64 - packet.timestamp.unwrap() as i64);
+            self.time_offset = Some(current_timestamp as i64 - packet.timestamp as i64);
             self.buffer_health_tracker = Some(BufferHealthTracker {
                 min_buffer_health: AtomicI64::new(i64::MAX),
                 stutter_count: AtomicU32::new(0),
@@ -154,16 +249,21 @@ impl WorldStateFilter {
             }
         }
 
-        remap_world_state(&mut packet);
+        if let Some((_, newest)) = self.history.front() {
+            estimate_angular_velocities(&mut packet, newest);
+            estimate_robot_velocities(&mut packet, newest);
+            estimate_ball_velocity(&mut packet, newest);
+        }
 
         // Insert the new packet into buffer, ordered by its converted local timestamp
-        let new_timestamp = (packet.timestamp.unwrap() as i64 + self.time_offset.unwrap()) as u64;
+        let new_timestamp = (packet.timestamp as i64 + self.time_offset.unwrap()) as u64;
         let insert_index = self
             .history
             .iter()
             .take_while(|(timestamp, _)| *timestamp > new_timestamp)
             .count();
-        self.history.insert(insert_index, (new_timestamp, packet));
+        self.history
+            .insert(insert_index, (new_timestamp, Arc::new(packet)));
 
         // Remove old packets from the buffer
         self.history.truncate(
@@ -173,66 +273,147 @@ impl WorldStateFilter {
                 .count(),
         );
     }
+
+    fn metrics(&self) -> FilterMetrics {
+        let tracker = self.buffer_health_tracker.as_ref();
+        FilterMetrics {
+            buffered_packets: self.history.len(),
+            min_buffer_time: tracker
+                .map(|tracker| tracker.min_buffer_health.load(SeqCst))
+                .filter(|min_time| *min_time != i64::MAX)
+                .map(|min_time| Duration::from_micros(min_time.max(0) as u64)),
+            stutters: tracker.map_or(0, |tracker| tracker.stutter_count.load(SeqCst)),
+        }
+    }
 }
 
-fn interpolate_world_state(
+pub(crate) fn interpolate_world_state(
     curr_time: u64,
     prev_time: u64,
-    prev: &WorldState,
+    prev: &WorldSnapshot,
     next_time: u64,
-    next: &WorldState,
-) -> WorldState {
-    fn interpolate_robots(prev: &[Robot], next: &[Robot], ratio: f32) -> Vec<Robot> {
-        prev.iter()
-            .filter_map(|pr| {
-                next.iter().find(|nr| pr.id == nr.id).map(|nr| Robot {
-                    id: pr.id,
-                    p_x: pr.p_x + ratio * (nr.p_x - pr.p_x),
-                    p_y: pr.p_y + ratio * (nr.p_y - pr.p_y),
-                    phi: pr.phi + ratio * ((nr.phi - pr.phi + PI).rem_euclid(2.0 * PI) - PI),
-                })
-            })
-            .collect()
-    }
-
+    next: &WorldSnapshot,
+) -> WorldSnapshot {
     let ratio = (curr_time as f32 - prev_time as f32) / (next_time as f32 - prev_time as f32);
+    let dt = next.timestamp.abs_diff(prev.timestamp) as f32 / 1_000_000.0;
 
     // TODO: Multi-Ball interpolation and tracking across frames
-    WorldState {
-        timestamp: Some(
-            prev.timestamp.unwrap()
-                + (ratio * (next.timestamp.unwrap() - prev.timestamp.unwrap()) as f32) as u64,
-        ),
-        ball: if prev.ball.len() == 1 && next.ball.len() == 1 {
-            let pb = prev.ball[0];
-            let nb = next.ball[0];
-            vec![Ball {
-                p_x: pb.p_x + ratio * (nb.p_x - pb.p_x),
-                p_y: pb.p_y + ratio * (nb.p_y - pb.p_y),
-                p_z: pb
-                    .p_z
-                    .and_then(|pz| nb.p_z.map(|nz| pz + ratio * (nz - pz))),
+    WorldSnapshot {
+        timestamp: prev.timestamp + (ratio * (next.timestamp - prev.timestamp) as f32) as u64,
+        balls: if prev.balls.len() == 1 && next.balls.len() == 1 {
+            vec![BallState {
+                translation: prev.balls[0]
+                    .translation
+                    .lerp(next.balls[0].translation, ratio),
+                velocity: match (prev.balls[0].velocity, next.balls[0].velocity) {
+                    (Some(prev_velocity), Some(next_velocity)) => {
+                        Some(prev_velocity.lerp(next_velocity, ratio))
+                    }
+                    (_, velocity) => velocity,
+                },
             }]
         } else {
-            next.ball.clone()
+            next.balls.clone()
         },
-        yellow_robot: interpolate_robots(&prev.yellow_robot, &next.yellow_robot, ratio),
-        blue_robot: interpolate_robots(&prev.blue_robot, &next.blue_robot, ratio),
+        robots: prev
+            .robots
+            .iter()
+            .filter_map(|pr| {
+                next.robots
+                    .iter()
+                    .find(|nr| pr.team == nr.team && pr.id == nr.id)
+                    .map(|nr| RobotState {
+                        translation: pr.translation.lerp(nr.translation, ratio),
+                        rotation: pr.rotation + ratio * rotation_delta(pr, nr, dt),
+                        angular_velocity: match (pr.angular_velocity, nr.angular_velocity) {
+                            (Some(prev_velocity), Some(next_velocity)) => {
+                                Some(prev_velocity + ratio * (next_velocity - prev_velocity))
+                            }
+                            (_, velocity) => velocity,
+                        },
+                        velocity: match (pr.velocity, nr.velocity) {
+                            (Some(prev_velocity), Some(next_velocity)) => {
+                                Some(prev_velocity.lerp(next_velocity, ratio))
+                            }
+                            (_, velocity) => velocity,
+                        },
+                        ..*pr
+                    })
+            })
+            .collect(),
     }
 }
 
-/// Converts from the vision coordinate system (right-handed, z up, x towards blue goal, +x forward)
-/// to bevy's coordinate system (right-handed, y up, x towards blue goal, -z forward) with y and z swapped
-fn remap_world_state(world_state: &mut WorldState) {
-    for ball in &mut world_state.ball {
-        ball.p_y = -ball.p_y;
+/// Rotation from prev to next in rad. Without an angular velocity this is the shortest rotation,
+/// otherwise the number of full turns is chosen to match the average angular velocity, so that fast spins keep
+/// their direction even if the robot turned more than half a turn between the packets.
+fn rotation_delta(prev: &RobotState, next: &RobotState, dt: f32) -> f32 {
+    let shortest = (next.rotation - prev.rotation + PI).rem_euclid(2.0 * PI) - PI;
+
+    let average_velocity = match (prev.angular_velocity, next.angular_velocity) {
+        (Some(prev_velocity), Some(next_velocity)) => (prev_velocity + next_velocity) / 2.0,
+        (Some(velocity), None) | (None, Some(velocity)) => velocity,
+        (None, None) => return shortest,
+    };
+    let expected = average_velocity * dt;
+
+    let turns = ((expected - shortest) / (2.0 * PI)).round();
+    shortest + turns * 2.0 * PI
+}
+
+/// Fills in missing angular velocities from the rotation since the previous packet of the same robot
+fn estimate_angular_velocities(packet: &mut WorldSnapshot, previous: &WorldSnapshot) {
+    let dt = packet.timestamp.abs_diff(previous.timestamp) as f32 / 1_000_000.0;
+    // Too far apart to tell how many turns happened in between
+    if dt == 0.0 || dt > MAX_ESTIMATION_INTERVAL.as_secs_f32() {
+        return;
     }
-    for robot in &mut world_state.yellow_robot {
-        robot.p_y = -robot.p_y;
-        robot.phi -= PI / 2.0;
+
+    for robot in packet
+        .robots
+        .iter_mut()
+        .filter(|r| r.angular_velocity.is_none())
+    {
+        if let Some(previous) = previous
+            .robots
+            .iter()
+            .find(|p| p.team == robot.team && p.id == robot.id)
+        {
+            let delta = (robot.rotation - previous.rotation + PI).rem_euclid(2.0 * PI) - PI;
+            robot.angular_velocity = Some(delta / dt);
+        }
+    }
+}
+
+/// Estimates the robot velocities from their positions in the previous packet
+fn estimate_robot_velocities(packet: &mut WorldSnapshot, previous: &WorldSnapshot) {
+    // Reordered packets would reverse the direction
+    let dt = packet.timestamp.saturating_sub(previous.timestamp) as f32 / 1_000_000.0;
+    if dt == 0.0 || dt > MAX_ESTIMATION_INTERVAL.as_secs_f32() {
+        return;
+    }
+
+    for robot in &mut packet.robots {
+        if let Some(previous) = previous
+            .robots
+            .iter()
+            .find(|p| p.team == robot.team && p.id == robot.id)
+        {
+            robot.velocity = Some((robot.translation - previous.translation) / dt);
+        }
     }
-    for robot in &mut world_state.blue_robot {
-        robot.p_y = -robot.p_y;
-        robot.phi -= PI / 2.0;
+}
+
+/// Estimates the ball velocity from the position in the previous packet.
+/// Like the interpolation, this only works with a single ball, as balls aren't tracked across packets.
+fn estimate_ball_velocity(packet: &mut WorldSnapshot, previous: &WorldSnapshot) {
+    // Reordered packets would reverse the direction
+    let dt = packet.timestamp.saturating_sub(previous.timestamp) as f32 / 1_000_000.0;
+    if dt == 0.0 || dt > MAX_ESTIMATION_INTERVAL.as_secs_f32() {
+        return;
+    }
+
+    if let ([ball], [previous]) = (packet.balls.as_mut_slice(), previous.balls.as_slice()) {
+        ball.velocity = Some((ball.translation - previous.translation) / dt);
     }
 }
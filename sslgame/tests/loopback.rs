@@ -0,0 +1,263 @@
+//! End-to-end tests that run sslgame headless against a mock host on the loopback interface.
+
+#![cfg(feature = "networking")]
+
+use bevy::prelude::*;
+use sslgame::proto::remote::{
+    FieldGeometry, Robot as RobotPacket, VisualizationFilter, WorldState,
+};
+use sslgame::*;
+use std::f32::consts::PI;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Resource, Default)]
+struct LatestWorldStates(Vec<WorldStateUpdated>);
+
+fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins(MinimalPlugins);
+    app.add_plugins(ssl_game_plugin);
+
+    app.init_resource::<LatestWorldStates>();
+    app.add_systems(
+        Last,
+        |mut updates: MessageReader<WorldStateUpdated>, mut latest: ResMut<LatestWorldStates>| {
+            latest.0 = updates.read().cloned().collect();
+        },
+    );
+    app
+}
+
+fn loopback_host(advertise: bool) -> MockHost {
+    let config = MockHostConfig {
+        bind_addr: SocketAddr::from((Ipv4Addr::LOCALHOST, 0)),
+        hostname: Some("Loopback Test".to_string()),
+        advertise,
+        advertise_to: Vec::new(),
+        key: None,
+    };
+    MockHost::spawn(config, PacketSource::Demo(DemoGame::new(42))).unwrap()
+}
+
+fn spawn_field(app: &mut App, host: &MockHost) -> Entity {
+    app.world_mut()
+        .spawn(Field::bind(
+            FieldHost {
+                websocket_addr: host.websocket_addr,
+                hostname: None,
+                encrypted: false,
+            },
+            None,
+        ))
+        .id()
+}
+
+/// Runs the app until the condition is met. Returns false on timeout.
+fn update_until(app: &mut App, mut condition: impl FnMut(&mut World) -> bool) -> bool {
+    let deadline = Instant::now() + TIMEOUT;
+    while Instant::now() < deadline {
+        app.update();
+        if condition(app.world_mut()) {
+            return true;
+        }
+        std::thread::sleep(Duration::from_millis(5));
+    }
+    false
+}
+
+fn count<F: bevy::ecs::query::QueryFilter>(world: &mut World) -> usize {
+    world.query_filtered::<(), F>().iter(world).count()
+}
+
+#[test]
+fn robots_and_ball_follow_world_state() {
+    let mut app = headless_app();
+    let host = loopback_host(false);
+    let field = spawn_field(&mut app, &host);
+
+    assert!(
+        update_until(&mut app, |world| count::<With<Robot>>(world) == 12
+            && count::<With<Ball>>(world) == 1),
+        "Robots and ball did not appear"
+    );
+
+    // Compare the transforms with the world state used in the same frame
+    app.update();
+    let world = app.world_mut();
+    let world_state = world
+        .resource::<LatestWorldStates>()
+        .0
+        .iter()
+        .find(|update| update.field == field)
+        .map(|update| update.world_state.clone())
+        .expect("No world state update for the field");
+
+    let mut q_robots = world.query::<(&Robot, &Team, &Transform, &ChildOf)>();
+    for (robot, team, transform, child_of) in q_robots.iter(world) {
+        assert_eq!(child_of.parent(), field);
+        let robot_state = world_state
+            .robots
+            .iter()
+            .find(|r| r.team == *team && r.id == robot.0 as u32)
+            .expect("Robot without world state");
+        // Tiny movements are not applied to the transforms
+        let expected = robot_state.transform();
+        assert!(
+            transform
+                .translation
+                .abs_diff_eq(expected.translation, 0.001)
+        );
+        assert!(transform.rotation.abs_diff_eq(expected.rotation, 0.001));
+    }
+
+    let mut q_balls = world.query_filtered::<&Transform, With<Ball>>();
+    let ball_transform = q_balls.single(world).unwrap();
+    assert_eq!(ball_transform.translation, world_state.balls[0].translation);
+}
+
+#[test]
+fn field_geometry_and_game_state_are_received() {
+    let mut app = headless_app();
+    let host = loopback_host(false);
+    let field = spawn_field(&mut app, &host);
+
+    assert!(
+        update_until(&mut app, |world| {
+            world
+                .get::<GameState>(field)
+                .is_some_and(|game_state| game_state.game_stage.is_some())
+        }),
+        "Game state was not received"
+    );
+
+    let geometry = app.world().get::<sslgame::FieldGeometry>(field).unwrap();
+    assert_eq!(geometry.play_area_size, Vec2::new(12.0, 9.0));
+    assert_eq!(geometry.goal_width, 1.8);
+}
+
+#[test]
+fn visualizations_respect_selection() {
+    let mut app = headless_app();
+    let host = loopback_host(false);
+    let field = spawn_field(&mut app, &host);
+
+    assert!(
+        update_until(&mut app, |world| {
+            world
+                .get::<AvailableVisualizations>(field)
+                .is_some_and(|available| !available.visualizations.is_empty())
+        }),
+        "Visualization mappings were not received"
+    );
+
+    // Select a single visualization
+    let world = app.world_mut();
+    let available = world.get::<AvailableVisualizations>(field).unwrap();
+    let (&target_id, _) = available
+        .visualizations
+        .iter()
+        .find(|(_, name)| *name == "Formation targets")
+        .expect("Missing demo visualization");
+    let filter = VisualizationFilter {
+        allowed_vis_source: available.sources.keys().copied().collect(),
+        allowed_vis_id: vec![target_id],
+    };
+    world
+        .entity_mut(field)
+        .insert(SelectedVisualizations(filter));
+
+    // Visualizations received before the filter was applied by the host are replaced with the next update
+    assert!(
+        update_until(&mut app, |world| {
+            let visualizations: Vec<_> = world
+                .query::<(&Visualization, &ChildOf)>()
+                .iter(world)
+                .map(|(visualization, child_of)| (visualization.0, child_of.parent()))
+                .collect();
+            !visualizations.is_empty()
+                && visualizations
+                    .iter()
+                    .all(|(id, parent)| *id == target_id && *parent == field)
+        }),
+        "Only the selected visualization should be shown"
+    );
+}
+
+#[test]
+#[ignore = "requires multicast on a network interface, which is not available in every CI environment"]
+fn host_is_discovered() {
+    let mut app = headless_app();
+    let host = loopback_host(true);
+
+    assert!(
+        update_until(&mut app, |world| {
+            world
+                .resource::<AvailableHosts>()
+                .0
+                .iter()
+                .any(|h| h.websocket_addr.port() == host.websocket_addr.port())
+        }),
+        "Mock host was not discovered"
+    );
+}
+
+#[test]
+fn injected_world_state_is_remapped() {
+    let mut app = headless_app();
+    let (field, injector) = Field::with_injector(FieldHost {
+        websocket_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        hostname: None,
+        encrypted: false,
+    });
+    let field = app.world_mut().spawn(field).id();
+
+    injector
+        .send_blocking(UpdatePacket::FieldGeom(FieldGeometry {
+            field_size_x: 9.0,
+            field_size_y: 6.0,
+            ..default()
+        }))
+        .unwrap();
+    injector
+        .send_blocking(UpdatePacket::WorldState(WorldState {
+            timestamp: Some(1),
+            ball: vec![],
+            yellow_robot: vec![RobotPacket {
+                id: 3,
+                p_x: 1.0,
+                p_y: 2.0,
+                phi: PI,
+                v_phi: None,
+            }],
+            blue_robot: vec![],
+            keyframe: None,
+        }))
+        .unwrap();
+
+    assert!(
+        update_until(&mut app, |world| count::<With<Robot>>(world) == 1),
+        "Injected robot did not appear"
+    );
+
+    let world = app.world_mut();
+    let (robot, team, transform) = world
+        .query::<(&Robot, &Team, &Transform)>()
+        .single(world)
+        .unwrap();
+    assert_eq!(robot.0, 3);
+    assert_eq!(*team, Team::Yellow);
+    // Vision coordinates are converted to bevy's y-up coordinate system
+    assert_eq!(transform.translation, Vec3::new(1.0, 0.0, -2.0));
+    assert!(
+        transform
+            .rotation
+            .angle_between(Quat::from_rotation_y(PI / 2.0))
+            < 0.001
+    );
+
+    let geometry = world.get::<sslgame::FieldGeometry>(field).unwrap();
+    assert_eq!(geometry.play_area_size, Vec2::new(9.0, 6.0));
+}
@@ -0,0 +1,97 @@
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use sslgame::{AccessibilitySettings, Language};
+
+pub fn accessibility_plugin(app: &mut App) {
+    app.add_systems(EguiPrimaryContextPass, apply_accessibility);
+}
+
+/// Applies the settings to the egui style. Tracked locally instead of with change detection,
+/// since the context doesn't exist yet in the first frames.
+fn apply_accessibility(
+    mut contexts: bevy_egui::EguiContexts,
+    settings: Res<AccessibilitySettings>,
+    mut applied: Local<Option<AccessibilitySettings>>,
+) -> Result {
+    if *applied == Some(*settings) {
+        return Ok(());
+    }
+    let ctx = contexts.ctx_mut()?;
+
+    ctx.set_zoom_factor(settings.ui_scale);
+    let default_text_styles = egui::Style::default().text_styles;
+    ctx.all_styles_mut(|style| {
+        for (text_style, font) in &mut style.text_styles {
+            if let Some(default) = default_text_styles.get(text_style) {
+                font.size = default.size * settings.text_scale;
+            }
+        }
+        style.visuals = if style.visuals.dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        };
+        if settings.high_contrast {
+            high_contrast(&mut style.visuals);
+        }
+    });
+
+    *applied = Some(*settings);
+    Ok(())
+}
+
+/// Plain background and foreground colors, and outlines around every widget
+fn high_contrast(visuals: &mut egui::Visuals) {
+    let (background, foreground) = if visuals.dark_mode {
+        (egui::Color32::BLACK, egui::Color32::WHITE)
+    } else {
+        (egui::Color32::WHITE, egui::Color32::BLACK)
+    };
+    visuals.override_text_color = Some(foreground);
+    visuals.panel_fill = background;
+    visuals.window_fill = background;
+    visuals.extreme_bg_color = background;
+    visuals.faint_bg_color = background;
+    visuals.window_stroke = egui::Stroke::new(2., foreground);
+    visuals.selection.stroke = egui::Stroke::new(2., foreground);
+    let widgets = &mut visuals.widgets;
+    for widget in [
+        &mut widgets.noninteractive,
+        &mut widgets.inactive,
+        &mut widgets.hovered,
+        &mut widgets.active,
+        &mut widgets.open,
+    ] {
+        widget.fg_stroke = egui::Stroke::new(widget.fg_stroke.width.max(1.5), foreground);
+        widget.bg_stroke = egui::Stroke::new(widget.bg_stroke.width.max(1.), foreground);
+    }
+}
+
+/// Status bar menu with the scale sliders and the contrast toggle, returns the edited settings
+pub fn accessibility_menu_ui(
+    ui: &mut egui::Ui,
+    settings: AccessibilitySettings,
+    language: Language,
+) -> AccessibilitySettings {
+    let mut edited = settings;
+    ui.menu_button(language.tr("Accessibility"), |ui| {
+        for (value, label) in [
+            (&mut edited.ui_scale, "UI scale"),
+            (&mut edited.text_scale, "Text size"),
+        ] {
+            ui.add(
+                egui::Slider::new(value, AccessibilitySettings::SCALE_RANGE)
+                    .step_by(AccessibilitySettings::SCALE_STEP as f64)
+                    .custom_formatter(|value, _| format!("{:.0}%", value * 100.))
+                    .text(language.tr(label)),
+            );
+        }
+        ui.checkbox(&mut edited.high_contrast, language.tr("High contrast"));
+        if ui.button(language.tr("Reset")).clicked() {
+            edited = AccessibilitySettings::default();
+        }
+    });
+    edited
+}
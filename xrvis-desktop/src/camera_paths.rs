@@ -0,0 +1,275 @@
+//! Keyframed camera flights, e.g. intro fly-throughs of the field for streams. Paths are stored with the session.
+
+use crate::director::AutoDirector;
+use crate::session::PanelLayout;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use sslgame::Language;
+use sslgame::proto::session;
+
+/// Time added after the last keyframe for new keyframes, in seconds
+const DEFAULT_KEYFRAME_GAP: f32 = 2.0;
+
+pub fn camera_paths_plugin(app: &mut App) {
+    app.init_resource::<CameraPaths>();
+    app.add_systems(Update, play_camera_path);
+    app.add_systems(EguiPrimaryContextPass, camera_paths_ui);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CameraKeyframe {
+    /// Seconds since the start of the path
+    pub time: f32,
+    pub position: Vec3,
+    pub look_at: Vec3,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct CameraPath {
+    pub name: String,
+    /// Sorted by time
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraPath {
+    pub fn duration(&self) -> f32 {
+        self.keyframes.last().map_or(0.0, |keyframe| keyframe.time)
+    }
+
+    /// Catmull-Rom interpolation through the keyframes, so the camera doesn't change direction abruptly at a keyframe
+    pub fn sample(&self, time: f32) -> Option<(Vec3, Vec3)> {
+        let keyframes = &self.keyframes;
+        let next = keyframes.partition_point(|keyframe| keyframe.time <= time);
+        if next == 0 || next == keyframes.len() {
+            // Before the first or after the last keyframe
+            let keyframe = keyframes.get(next.min(keyframes.len().saturating_sub(1)))?;
+            return Some((keyframe.position, keyframe.look_at));
+        }
+
+        let (k1, k2) = (&keyframes[next - 1], &keyframes[next]);
+        let k0 = &keyframes[next.saturating_sub(2)];
+        let k3 = keyframes.get(next + 1).unwrap_or(k2);
+        let t = ((time - k1.time) / (k2.time - k1.time).max(f32::EPSILON)).clamp(0.0, 1.0);
+        let catmull_rom = |p0: Vec3, p1: Vec3, p2: Vec3, p3: Vec3| {
+            0.5 * (2.0 * p1
+                + (p2 - p0) * t
+                + (2.0 * p0 - 5.0 * p1 + 4.0 * p2 - p3) * t * t
+                + (3.0 * p1 - p0 - 3.0 * p2 + p3) * t * t * t)
+        };
+        Some((
+            catmull_rom(k0.position, k1.position, k2.position, k3.position),
+            catmull_rom(k0.look_at, k1.look_at, k2.look_at, k3.look_at),
+        ))
+    }
+}
+
+/// All camera paths of the session and the playback state
+#[derive(Resource, Debug, Default)]
+pub struct CameraPaths {
+    pub paths: Vec<CameraPath>,
+    /// Index of the playing path and the playback time
+    playing: Option<(usize, f32)>,
+}
+
+impl CameraPaths {
+    pub fn play(&mut self, index: usize) {
+        self.playing = Some((index, 0.0));
+    }
+
+    pub fn stop(&mut self) {
+        self.playing = None;
+    }
+}
+
+fn vec3(point: &session::Point) -> Vec3 {
+    Vec3::new(point.x, point.y, point.z)
+}
+
+fn point(v: Vec3) -> session::Point {
+    session::Point {
+        x: v.x,
+        y: v.y,
+        z: v.z,
+    }
+}
+
+impl From<&CameraPath> for session::CameraPath {
+    fn from(path: &CameraPath) -> Self {
+        Self {
+            name: path.name.clone(),
+            keyframe: path
+                .keyframes
+                .iter()
+                .map(|keyframe| session::CameraKeyframe {
+                    time: keyframe.time,
+                    position: point(keyframe.position),
+                    look_at: point(keyframe.look_at),
+                })
+                .collect(),
+        }
+    }
+}
+
+impl From<&session::CameraPath> for CameraPath {
+    fn from(path: &session::CameraPath) -> Self {
+        let mut keyframes: Vec<_> = path
+            .keyframe
+            .iter()
+            .map(|keyframe| CameraKeyframe {
+                time: keyframe.time,
+                position: vec3(&keyframe.position),
+                look_at: vec3(&keyframe.look_at),
+            })
+            .collect();
+        keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Self {
+            name: path.name.clone(),
+            keyframes,
+        }
+    }
+}
+
+/// Moves the orbit camera directly, so the smoothing of the camera doesn't lag behind the path
+fn set_orbit(camera: &mut PanOrbitCamera, position: Vec3, look_at: Vec3) {
+    let offset = position - look_at;
+    let radius = offset.length().max(0.01);
+    let yaw = offset.x.atan2(offset.z);
+    let pitch = (offset.y / radius).clamp(-1.0, 1.0).asin();
+
+    camera.focus = look_at;
+    camera.target_focus = look_at;
+    camera.yaw = Some(yaw);
+    camera.target_yaw = yaw;
+    camera.pitch = Some(pitch);
+    camera.target_pitch = pitch;
+    camera.radius = Some(radius);
+    camera.target_radius = radius;
+    camera.force_update = true;
+}
+
+fn play_camera_path(
+    time: Res<Time>,
+    mut camera_paths: ResMut<CameraPaths>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+) {
+    let Some((index, playback_time)) = camera_paths.playing else {
+        return;
+    };
+    let Some(path) = camera_paths.paths.get(index) else {
+        camera_paths.stop();
+        return;
+    };
+
+    let playback_time = playback_time + time.delta_secs();
+    if let Some((position, look_at)) = path.sample(playback_time) {
+        for mut camera in &mut cameras {
+            set_orbit(&mut camera, position, look_at);
+        }
+    }
+    // The camera stays at the last keyframe
+    camera_paths.playing = (playback_time < path.duration()).then_some((index, playback_time));
+}
+
+fn camera_paths_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
+    mut camera_paths: ResMut<CameraPaths>,
+    mut director: ResMut<AutoDirector>,
+    q_cameras: Query<(&Transform, &PanOrbitCamera)>,
+    language: Res<Language>,
+) -> Result {
+    let camera_paths = &mut *camera_paths;
+    panel_layout
+        .window("Camera paths", *language)
+        .collapsible(true)
+        .resizable(true)
+        .default_open(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            let mut play = None;
+            let mut remove = None;
+            for (index, path) in camera_paths.paths.iter_mut().enumerate() {
+                ui.push_id(index, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.text_edit_singleline(&mut path.name);
+                        let playing = camera_paths.playing.is_some_and(|(i, _)| i == index);
+                        if playing {
+                            if ui.button(language.tr("Stop")).clicked() {
+                                camera_paths.playing = None;
+                            }
+                        } else if ui.button(language.tr("Play")).clicked() {
+                            play = Some(index);
+                        }
+                        if ui.button(language.tr("Delete")).clicked() {
+                            remove = Some(index);
+                        }
+                    });
+
+                    let mut remove_keyframe = None;
+                    for (i, keyframe) in path.keyframes.iter_mut().enumerate() {
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::DragValue::new(&mut keyframe.time)
+                                    .range(0.0..=600.0)
+                                    .speed(0.05)
+                                    .suffix(" s"),
+                            );
+                            let coordinate = |value: f32| format!("{value:.1}");
+                            ui.weak(language.trf(
+                                "at ({x}, {y}, {z})",
+                                &[
+                                    ("x", &coordinate(keyframe.position.x)),
+                                    ("y", &coordinate(keyframe.position.y)),
+                                    ("z", &coordinate(keyframe.position.z)),
+                                ],
+                            ));
+                            if ui.small_button("x").clicked() {
+                                remove_keyframe = Some(i);
+                            }
+                        });
+                    }
+                    if let Some(i) = remove_keyframe {
+                        path.keyframes.remove(i);
+                    }
+                    path.keyframes.sort_by(|a, b| a.time.total_cmp(&b.time));
+
+                    if ui.button(language.tr("Add current view")).clicked()
+                        && let Some((transform, orbit)) = q_cameras.iter().next()
+                    {
+                        path.keyframes.push(CameraKeyframe {
+                            time: path
+                                .keyframes
+                                .last()
+                                .map_or(0.0, |keyframe| keyframe.time + DEFAULT_KEYFRAME_GAP),
+                            position: transform.translation,
+                            look_at: orbit.focus,
+                        });
+                    }
+                    ui.separator();
+                });
+            }
+
+            if ui.button(language.tr("New path")).clicked() {
+                camera_paths.paths.push(CameraPath {
+                    name: language.trf(
+                        "Path {number}",
+                        &[("number", &(camera_paths.paths.len() + 1))],
+                    ),
+                    keyframes: Vec::new(),
+                });
+            }
+
+            if let Some(index) = remove {
+                camera_paths.paths.remove(index);
+                camera_paths.playing = None;
+            }
+            if let Some(index) = play {
+                // The director would fight over the camera
+                director.enabled = false;
+                camera_paths.play(index);
+            }
+        });
+    Ok(())
+}
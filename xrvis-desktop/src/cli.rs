@@ -0,0 +1,184 @@
+use bevy::prelude::*;
+use clap::{Parser, ValueEnum};
+use sslgame::{
+    DataSource, FieldHost, GhostSource, Language, PresharedKey, RenderSettings, SourceStreams,
+    parse_host_addr,
+};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+/// Desktop viewer for sslgame hosts
+#[derive(Parser, Resource, Debug, Clone)]
+#[command(version, about)]
+pub struct Cli {
+    /// Connect to the websocket address of a host instead of using host discovery. Can be repeated.
+    #[arg(long, value_name = "ADDR", value_parser = parse_host_addr)]
+    pub connect: Vec<SocketAddr>,
+
+    /// Prefer this network interface for hosts that are visible on several interfaces.
+    /// Can be repeated, earlier interfaces are preferred.
+    #[arg(long, value_name = "NAME")]
+    pub interface: Vec<String>,
+
+    /// Encrypt the connections to all hosts with a pre-shared key. Hosts that use a different key can't be connected.
+    #[arg(long, value_name = "PASSPHRASE")]
+    pub psk: Option<String>,
+
+    /// Show the world state of this host as translucent ghosts on the first field, to compare two trackers
+    #[arg(long, value_name = "ADDR", value_parser = parse_host_addr)]
+    pub ghost: Option<SocketAddr>,
+
+    /// Take the visualizations of the first field from this host instead, e.g. a strategy host next to the tracker host
+    #[arg(long, value_name = "ADDR", value_parser = parse_host_addr)]
+    pub vis_source: Option<SocketAddr>,
+
+    /// Show a copy of every field next to it over the same connection, e.g. to compare visualization selections
+    #[arg(long)]
+    pub duplicate: bool,
+
+    /// Only enable visualizations whose name contains the pattern (case-insensitive). Can be repeated.
+    #[arg(long, value_name = "PATTERN")]
+    pub vis: Vec<String>,
+
+    /// Render settings to start with
+    #[arg(long, value_enum)]
+    pub render_preset: Option<RenderPreset>,
+
+    /// Play back a recording instead of using host discovery. Can be repeated, e.g. to compare two matches.
+    #[arg(long, value_name = "FILE")]
+    pub replay: Vec<PathBuf>,
+
+    /// Advance all --replay recordings on a shared playback clock, so they stay in lockstep
+    #[arg(long, requires = "replay")]
+    pub sync_replays: bool,
+
+    /// Show a procedurally generated demo game, no host or network required
+    #[arg(long)]
+    pub demo: bool,
+
+    /// Serve the --connect, --replay and --demo fields to other viewers on the network, advertised like a real host.
+    /// Connected hosts are relayed, e.g. to headsets on a guest wifi that can't reach the host themselves.
+    /// Replays are served in lockstep with the local playback. Clients need the --psk if it is set.
+    #[arg(long)]
+    pub rebroadcast: bool,
+
+    /// Also send the advertisements of --rebroadcast to this address, e.g. a headset or the broadcast address
+    /// of another network segment. Can be repeated.
+    #[arg(long, value_name = "ADDR", value_parser = parse_host_addr, requires = "rebroadcast")]
+    pub advertise_to: Vec<SocketAddr>,
+
+    /// Show a snapshot that was saved with "Save snapshot"
+    #[arg(long, value_name = "FILE")]
+    pub snapshot: Option<PathBuf>,
+
+    /// Record every spawned field into a separate file in this directory
+    #[arg(long, value_name = "DIR")]
+    pub record: Option<PathBuf>,
+
+    /// Restore the session from this file at startup if it exists, and save into it with Ctrl+S.
+    /// Defaults to session.xrvis in the working directory.
+    #[arg(long, value_name = "FILE")]
+    pub session: Option<PathBuf>,
+
+    /// Run without a window, e.g. for test benches in combination with --record or --frame-output
+    #[arg(long)]
+    pub headless: bool,
+
+    /// Start with the automatic camera that follows the play, for unattended spectator displays
+    #[arg(long)]
+    pub director: bool,
+
+    /// Stream raw rgba frames into a file, FIFO or v4l2loopback device
+    #[arg(long, value_name = "PATH")]
+    pub frame_output: Option<PathBuf>,
+
+    /// Render the first --replay recording offline into raw stereo 360 frames in this file or FIFO, then exit
+    #[arg(long, value_name = "PATH", requires = "replay")]
+    pub export_360: Option<PathBuf>,
+
+    /// Frame rate of --export-360
+    #[arg(long, value_name = "FPS", default_value_t = 30)]
+    pub export_fps: u32,
+
+    /// Language of the user interface, "en" or "de". Defaults to the system locale.
+    #[arg(long, value_name = "CODE", value_parser = parse_language)]
+    pub language: Option<Language>,
+}
+
+fn parse_language(code: &str) -> Result<Language, String> {
+    Language::from_locale(code).ok_or_else(|| format!("unsupported language {code}"))
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RenderPreset {
+    /// Only robot cutouts and visualizations for camera overlays
+    Ar,
+    /// Everything including the field
+    Full,
+}
+
+impl RenderPreset {
+    pub fn render_settings(&self) -> RenderSettings {
+        match self {
+            RenderPreset::Ar => RenderSettings::ar(),
+            RenderPreset::Full => RenderSettings::full(),
+        }
+    }
+}
+
+impl Cli {
+    /// Whether fields are spawned from the command line instead of from discovered hosts
+    pub fn has_static_sources(&self) -> bool {
+        !self.connect.is_empty() || !self.replay.is_empty() || self.snapshot.is_some() || self.demo
+    }
+
+    pub fn session_path(&self) -> PathBuf {
+        self.session
+            .clone()
+            .unwrap_or_else(|| PathBuf::from("session.xrvis"))
+    }
+
+    /// The key from --psk, if set
+    pub fn preshared_key(&self) -> Option<PresharedKey> {
+        self.psk.as_deref().map(PresharedKey::new)
+    }
+
+    /// Creates the ghost source from --ghost, if set
+    pub fn ghost_source(&self, key: Option<&PresharedKey>) -> Option<GhostSource> {
+        self.ghost.map(|addr| {
+            GhostSource::bind(
+                FieldHost {
+                    websocket_addr: addr,
+                    hostname: None,
+                    encrypted: self.psk.is_some(),
+                },
+                key,
+            )
+        })
+    }
+
+    /// Creates the visualization source from --vis-source, if set
+    pub fn vis_source(&self, key: Option<&PresharedKey>) -> Option<(DataSource, SourceStreams)> {
+        self.vis_source.map(|addr| {
+            DataSource::bind(
+                FieldHost {
+                    websocket_addr: addr,
+                    hostname: None,
+                    encrypted: self.psk.is_some(),
+                },
+                key,
+                SourceStreams {
+                    visualizations: true,
+                    ..SourceStreams::NONE
+                },
+            )
+        })
+    }
+
+    pub fn vis_matches(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.vis
+            .iter()
+            .any(|pattern| name.contains(&pattern.to_lowercase()))
+    }
+}
@@ -0,0 +1,168 @@
+use crate::director::AutoDirector;
+use crate::session::PanelLayout;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use sslgame::proto::remote::TeamState;
+use sslgame::{
+    DecodeErrors, Field, FieldClock, GameState, Language, SoloField, StateFilter, format_stage_time,
+};
+
+/// Distance of the camera after jumping to a field, about the overview preset
+const JUMP_RADIUS: f32 = 12.0;
+
+pub fn dashboard_plugin(app: &mut App) {
+    app.add_systems(EguiPrimaryContextPass, dashboard_ui);
+}
+
+/// Summary of all fields for tournament setups, with buttons to move the camera to each field or show only it
+#[allow(clippy::type_complexity)]
+fn dashboard_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
+    mut director: ResMut<AutoDirector>,
+    mut solo_field: ResMut<SoloField>,
+    q_fields: Query<(
+        &Field,
+        &GameState,
+        &FieldClock,
+        &DecodeErrors,
+        &StateFilter,
+        &GlobalTransform,
+        Entity,
+    )>,
+    mut q_cameras: Query<&mut PanOrbitCamera>,
+    language: Res<Language>,
+) -> Result {
+    let mut jump_to = None;
+    panel_layout
+        .window("Fields", *language)
+        .collapsible(true)
+        .resizable(true)
+        .default_open(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            let mut solo = solo_field.0;
+            ui.horizontal(|ui| {
+                if ui.button(language.tr("Solo next")).clicked() {
+                    let mut next = SoloField(solo);
+                    next.cycle(q_fields.iter().map(|(.., entity)| entity));
+                    solo = next.0;
+                }
+                if ui
+                    .add_enabled(solo.is_some(), egui::Button::new(language.tr("Show all")))
+                    .clicked()
+                {
+                    solo = None;
+                }
+            });
+
+            egui::Grid::new("field_dashboard")
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in [
+                        "Field",
+                        "Yellow",
+                        "Score",
+                        "Blue",
+                        "Stage",
+                        "Connection",
+                        "Solo",
+                    ] {
+                        ui.strong(language.tr(header));
+                    }
+                    ui.end_row();
+
+                    for (
+                        field,
+                        game_state,
+                        clock,
+                        decode_errors,
+                        state_filter,
+                        transform,
+                        entity,
+                    ) in &q_fields
+                    {
+                        let field_name = field.host.display_name();
+                        let team_name = |team: &Option<TeamState>| {
+                            team.as_ref()
+                                .and_then(|team| team.name.clone())
+                                .unwrap_or_else(|| "-".to_string())
+                        };
+                        let score =
+                            |team: &Option<TeamState>| team.as_ref().map_or(0, |team| team.score());
+
+                        if ui
+                            .button(field_name.as_str())
+                            .on_hover_text(language.tr("Go to field"))
+                            .clicked()
+                        {
+                            jump_to = Some(transform.translation());
+                        }
+                        ui.label(team_name(&game_state.yellow_team));
+                        ui.label(format!(
+                            "{} : {}",
+                            score(&game_state.yellow_team),
+                            score(&game_state.blue_team)
+                        ));
+                        ui.label(team_name(&game_state.blue_team));
+                        let stage_time = clock
+                            .stage_time_left()
+                            .map(format_stage_time)
+                            .unwrap_or_default();
+                        ui.label(format!(
+                            "{} {stage_time}",
+                            language.stage(game_state.game_stage())
+                        ));
+
+                        // Stale vision is the most important problem, decode errors and stutters hint at the cause
+                        let decode_errors = decode_errors.last_minute();
+                        let stutters = state_filter.metrics().stutters;
+                        match clock.world_state_age() {
+                            _ if clock.is_stale() => {
+                                ui.colored_label(
+                                    egui::Color32::RED,
+                                    language.tr("no recent vision"),
+                                );
+                            }
+                            Some(age) if decode_errors > 0 || stutters > 0 => {
+                                ui.colored_label(
+                                    egui::Color32::YELLOW,
+                                    language.trf(
+                                        "{age} ms, {errors} errors, {stutters} stutters",
+                                        &[
+                                            ("age", &age.as_millis()),
+                                            ("errors", &decode_errors),
+                                            ("stutters", &stutters),
+                                        ],
+                                    ),
+                                );
+                            }
+                            Some(age) => {
+                                ui.label(format!("{} ms", age.as_millis()));
+                            }
+                            None => {
+                                ui.weak(language.tr("no vision"));
+                            }
+                        }
+                        let is_solo = solo == Some(entity);
+                        if ui.selectable_label(is_solo, language.tr("Solo")).clicked() {
+                            solo = (!is_solo).then_some(entity);
+                        }
+                        ui.end_row();
+                    }
+                });
+            solo_field.set_if_neq(SoloField(solo));
+        });
+
+    if let Some(focus) = jump_to {
+        // The director would move the camera back to its field right away
+        director.enabled = false;
+        for mut camera in &mut q_cameras {
+            camera.target_focus = focus;
+            camera.target_radius = JUMP_RADIUS;
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,183 @@
+//! Automatic camera for unattended spectator displays. Follows the ball and cuts between preset angles
+//! depending on where the play is going, and shows goals up close.
+
+use crate::shortcuts::DesktopAction;
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use sslgame::{Ball, Field, FieldGeometry, GameEvent, GameEventKind};
+use std::f32::consts::FRAC_PI_2;
+
+/// Shots are held at least this long, so the camera doesn't jump back and forth
+const MIN_SHOT_DURATION: f32 = 4.0;
+const GOAL_SHOT_DURATION: f32 = 6.0;
+/// The ball has to be faster than this to be followed closely, in m/s
+const FOLLOW_SPEED: f32 = 1.0;
+/// The camera aims at where the ball will be after this time, in seconds
+const LEAD_TIME: f32 = 0.4;
+/// Fraction of the half field length from which the play counts as an attack on a goal
+const ATTACK_ZONE: f32 = 0.5;
+
+pub fn director_plugin(app: &mut App) {
+    app.init_resource::<AutoDirector>();
+    app.add_systems(Update, (toggle_director, direct_camera).chain());
+}
+
+/// Controls the camera while enabled, manual camera controls are overridden.
+/// Toggled with a [`DesktopAction`], and disabled by selecting a camera preset.
+#[derive(Resource, Debug, Default)]
+pub struct AutoDirector {
+    pub enabled: bool,
+    shot: Shot,
+    /// Seconds since the last cut
+    shot_time: f32,
+    last_ball: Option<Vec3>,
+    /// Smoothed, in field coordinates
+    ball_velocity: Vec3,
+}
+
+impl AutoDirector {
+    pub fn enabled() -> Self {
+        Self {
+            enabled: true,
+            ..default()
+        }
+    }
+
+    fn cut(&mut self, shot: Shot) {
+        self.shot = shot;
+        self.shot_time = 0.0;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+enum Shot {
+    /// The whole field
+    #[default]
+    Wide,
+    /// Close to the ball, from the side
+    Follow,
+    /// Behind the attacking team, towards the goal on the +x (1.0) or -x (-1.0) side
+    Attack(f32),
+    /// Close to a goal that was just scored, in field coordinates
+    Goal(Vec3),
+}
+
+fn toggle_director(mut actions: MessageReader<DesktopAction>, mut director: ResMut<AutoDirector>) {
+    for action in actions.read() {
+        match action {
+            DesktopAction::ToggleAutoDirector => {
+                director.enabled = !director.enabled;
+                // Start with an overview, instead of cutting from wherever the user left the camera
+                director.cut(Shot::Wide);
+                director.last_ball = None;
+                info!(
+                    "Auto director {}",
+                    if director.enabled {
+                        "enabled"
+                    } else {
+                        "disabled"
+                    }
+                );
+            }
+            DesktopAction::CameraPreset(_) => director.enabled = false,
+            _ => {}
+        }
+    }
+}
+
+/// Directs the camera at the first field
+pub fn direct_camera(
+    time: Res<Time>,
+    mut director: ResMut<AutoDirector>,
+    mut game_events: MessageReader<GameEvent>,
+    q_fields: Query<(Entity, &GlobalTransform, &FieldGeometry, &Children), With<Field>>,
+    q_balls: Query<&Transform, With<Ball>>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+) {
+    let field = q_fields.iter().min_by_key(|(entity, ..)| *entity);
+    let (Some((field, field_transform, geom, children)), true) = (field, director.enabled) else {
+        game_events.clear();
+        return;
+    };
+
+    // ======== Ball tracking ========
+
+    let dt = time.delta_secs();
+    director.shot_time += dt;
+    let ball = children
+        .iter()
+        .find_map(|child| q_balls.get(child).ok())
+        .map(|transform| transform.translation);
+    if let (Some(ball), Some(last_ball)) = (ball, director.last_ball)
+        && dt > 0.0
+    {
+        let velocity = (ball - last_ball) / dt;
+        director.ball_velocity = director.ball_velocity.lerp(velocity, (dt * 4.0).min(1.0));
+    }
+    director.last_ball = ball;
+
+    // ======== Shot selection ========
+
+    let half_length = geom.play_area_size.x / 2.0;
+    for event in game_events.read().filter(|event| event.field == field) {
+        match event.kind {
+            GameEventKind::Goal { .. } => director.cut(Shot::Goal(event.position)),
+            // The restart happens somewhere else, so the whole field is shown
+            GameEventKind::OutOfBounds if director.shot_time > MIN_SHOT_DURATION => {
+                director.cut(Shot::Wide)
+            }
+            GameEventKind::OutOfBounds
+            | GameEventKind::Kick { .. }
+            | GameEventKind::NearCollision { .. } => {}
+        }
+    }
+
+    let shot_duration = match director.shot {
+        Shot::Goal(_) => GOAL_SHOT_DURATION,
+        _ => MIN_SHOT_DURATION,
+    };
+    if director.shot_time > shot_duration {
+        let next = match ball {
+            None => Shot::Wide,
+            Some(ball) => {
+                let side = ball.x.signum();
+                let towards_goal = director.ball_velocity.x * side >= 0.0;
+                if ball.x.abs() > half_length * ATTACK_ZONE && towards_goal {
+                    Shot::Attack(side)
+                } else if director.ball_velocity.length() > FOLLOW_SPEED {
+                    Shot::Follow
+                } else {
+                    Shot::Wide
+                }
+            }
+        };
+        if next != director.shot {
+            director.cut(next);
+        }
+    }
+
+    // ======== Framing ========
+
+    let lead = ball.map_or(Vec3::ZERO, |ball| {
+        (ball + director.ball_velocity * LEAD_TIME).with_y(0.0)
+    });
+    let (focus, yaw, pitch, radius) = match director.shot {
+        Shot::Wide => (Vec3::ZERO, 0.0, 0.7, 12.0),
+        Shot::Follow => (lead, 0.0, 0.6, 7.0),
+        Shot::Attack(side) => {
+            let goal = Vec3::X * side * half_length;
+            (lead.lerp(goal, 0.5), -side * FRAC_PI_2, 0.4, 6.0)
+        }
+        Shot::Goal(position) => {
+            let side = position.x.signum();
+            (position.with_y(0.0), -side * FRAC_PI_2, 0.3, 3.5)
+        }
+    };
+    // The camera smooths the transition to the new targets
+    for mut camera in &mut cameras {
+        camera.target_focus = field_transform.transform_point(focus);
+        camera.target_yaw = yaw;
+        camera.target_pitch = pitch;
+        camera.target_radius = radius;
+    }
+}
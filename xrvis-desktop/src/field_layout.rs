@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use std::time::Duration;
+
+const MOVE_DURATION: Duration = Duration::from_millis(800);
+
+pub fn field_layout_plugin(app: &mut App) {
+    app.add_systems(Update, animate_layout_moves);
+}
+
+/// Eases a field to its new position when the layout changes, instead of letting it jump there
+#[derive(Component, Debug)]
+pub struct LayoutMove {
+    from: Vec3,
+    to: Vec3,
+    start: Duration,
+}
+
+impl LayoutMove {
+    pub fn new(from: Vec3, to: Vec3, time: &Time) -> Self {
+        Self {
+            from,
+            to,
+            start: time.elapsed(),
+        }
+    }
+}
+
+/// Only the translation is animated, so fields keep their orientation (e.g. with swapped sides)
+fn animate_layout_moves(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_fields: Query<(&LayoutMove, &mut Transform, Entity)>,
+) {
+    for (layout_move, mut transform, entity) in &mut q_fields {
+        let progress =
+            (time.elapsed() - layout_move.start).as_secs_f32() / MOVE_DURATION.as_secs_f32();
+        let eased = EaseFunction::CubicInOut.sample_clamped(progress);
+        transform.translation = layout_move.from.lerp(layout_move.to, eased);
+        if progress >= 1.0 {
+            commands.entity(entity).remove::<LayoutMove>();
+        }
+    }
+}
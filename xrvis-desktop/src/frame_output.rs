@@ -0,0 +1,132 @@
+use bevy::asset::RenderAssetUsages;
+use bevy::camera::RenderTarget;
+use bevy::core_pipeline::prepass::DepthPrepass;
+use bevy::prelude::*;
+use bevy::render::gpu_readback::{Readback, ReadbackComplete};
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::transform::TransformSystems;
+use bevy_panorbit_camera::PanOrbitCamera;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::mpsc::SyncSender;
+
+/// Streams the rendered view as raw RGBA8 frames into a file, FIFO or device node.
+///
+/// This keeps the app free of any broadcast SDKs: A v4l2loopback device can be used directly as a virtual camera,
+/// and a FIFO can be fed into ffmpeg (e.g. `-f rawvideo -pix_fmt rgba -s 1920x1080 -i <path>`) to publish an NDI source.
+#[derive(Resource, Clone, Debug)]
+pub struct FrameOutput {
+    pub path: PathBuf,
+    pub size: UVec2,
+}
+
+/// Marks the offscreen camera that mirrors the main camera into the output image.
+#[derive(Component, Debug)]
+struct FrameOutputCamera;
+
+#[derive(Resource, Debug)]
+struct FrameSink(SyncSender<Vec<u8>>);
+
+pub fn frame_output_plugin(app: &mut App) {
+    app.add_systems(Startup, setup_frame_output);
+    app.add_systems(
+        PostUpdate,
+        follow_main_camera.before(TransformSystems::Propagate),
+    );
+}
+
+fn setup_frame_output(
+    mut commands: Commands,
+    frame_output: Res<FrameOutput>,
+    mut image_assets: ResMut<Assets<Image>>,
+) {
+    let FrameOutput { path, size } = frame_output.clone();
+
+    // Writing to a FIFO blocks until a reader is connected, so the file is handled on its own thread.
+    // Frames are dropped instead of stalling the renderer if the consumer can't keep up.
+    let (frame_tx, frame_rx) = std::sync::mpsc::sync_channel::<Vec<u8>>(2);
+    std::thread::spawn(move || {
+        let mut file = match OpenOptions::new().write(true).create(true).open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open frame output {}: {e}", path.display());
+                return;
+            }
+        };
+        info!(
+            "Writing {}x{} rgba frames to {}",
+            size.x,
+            size.y,
+            path.display()
+        );
+        for frame in frame_rx {
+            if let Err(e) = file.write_all(&frame) {
+                error!("Frame output stopped: {e}");
+                return;
+            }
+        }
+    });
+    commands.insert_resource(FrameSink(frame_tx));
+
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: size.x,
+            height: size.y,
+            ..default()
+        },
+        TextureDimension::D2,
+        &[0, 0, 0, 255],
+        TextureFormat::Rgba8UnormSrgb,
+        RenderAssetUsages::default(),
+    );
+    image.texture_descriptor.usage =
+        TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_SRC | TextureUsages::RENDER_ATTACHMENT;
+    let image_handle = image_assets.add(image);
+
+    commands.spawn((
+        Camera3d::default(),
+        Camera {
+            // Render after the window camera, the ordering is only relevant for ambiguity warnings
+            order: 1,
+            ..default()
+        },
+        RenderTarget::Image(image_handle.clone().into()),
+        DepthPrepass,
+        Transform::default(),
+        FrameOutputCamera,
+    ));
+
+    // A readback is scheduled every frame as long as the component exists
+    commands.spawn(Readback::texture(image_handle)).observe(
+        move |readback: On<ReadbackComplete>, sink: Res<FrameSink>| {
+            let data: &[u8] = &readback;
+            // A full channel drops the frame, a disconnected one has already been logged by the writer thread
+            _ = sink.0.try_send(unpad_rows(data, size));
+        },
+    );
+}
+
+fn follow_main_camera(
+    main_camera: Option<Single<&Transform, (With<PanOrbitCamera>, Without<FrameOutputCamera>)>>,
+    mut output_camera: Single<&mut Transform, With<FrameOutputCamera>>,
+) {
+    if let Some(main_transform) = main_camera {
+        output_camera.set_if_neq(**main_transform);
+    }
+}
+
+/// Texture copies are padded to a row alignment of 256 bytes, which has to be removed for raw video consumers.
+pub(crate) fn unpad_rows(data: &[u8], size: UVec2) -> Vec<u8> {
+    let row_bytes = size.x as usize * 4;
+    if data.len() == row_bytes * size.y as usize {
+        return data.to_vec();
+    }
+
+    let padded_row_bytes = row_bytes.div_ceil(256) * 256;
+    data.chunks(padded_row_bytes)
+        .take(size.y as usize)
+        .flat_map(|row| &row[..row_bytes.min(row.len())])
+        .copied()
+        .collect()
+}
@@ -0,0 +1,113 @@
+//! Gamepad controls, so the viewer can be used on a TV at team events without a mouse.
+//! The left stick orbits, the right stick pans sideways and zooms, or pans forward while the right bumper is held.
+
+use crate::shortcuts::{CameraPreset, DesktopAction};
+use bevy::prelude::*;
+use bevy_panorbit_camera::PanOrbitCamera;
+use std::f32::consts::FRAC_PI_2;
+
+/// Stick deflections below this are ignored, worn sticks don't rest exactly at zero
+const STICK_DEADZONE: f32 = 0.15;
+/// At full deflection, in radians per second
+const ORBIT_SPEED: f32 = 1.5;
+/// At full deflection, in orbit radii per second
+const PAN_SPEED: f32 = 0.8;
+/// At full deflection, the radius changes by a factor of e per second
+const ZOOM_SPEED: f32 = 1.0;
+
+pub fn gamepad_plugin(app: &mut App) {
+    app.insert_resource(GamepadBindings::default());
+    app.add_systems(Update, (trigger_gamepad_actions, gamepad_camera));
+}
+
+/// Button bindings for desktop actions, the sticks always control the camera
+#[derive(Resource, Clone, Debug)]
+pub struct GamepadBindings(pub Vec<(GamepadButton, DesktopAction)>);
+
+impl Default for GamepadBindings {
+    fn default() -> Self {
+        Self(vec![
+            (GamepadButton::South, DesktopAction::TogglePause),
+            (GamepadButton::East, DesktopAction::ToggleVisualizations),
+            (GamepadButton::North, DesktopAction::CycleRobotRendering),
+            (GamepadButton::West, DesktopAction::ToggleTelemetry),
+            (GamepadButton::Select, DesktopAction::ToggleField),
+            (GamepadButton::Start, DesktopAction::ToggleAutoDirector),
+            (
+                GamepadButton::DPadUp,
+                DesktopAction::CameraPreset(CameraPreset::Overview),
+            ),
+            (
+                GamepadButton::DPadDown,
+                DesktopAction::CameraPreset(CameraPreset::TopDown),
+            ),
+            (
+                GamepadButton::DPadLeft,
+                DesktopAction::CameraPreset(CameraPreset::YellowGoal),
+            ),
+            (
+                GamepadButton::DPadRight,
+                DesktopAction::CameraPreset(CameraPreset::BlueGoal),
+            ),
+        ])
+    }
+}
+
+fn trigger_gamepad_actions(
+    gamepads: Query<&Gamepad>,
+    bindings: Res<GamepadBindings>,
+    mut actions: MessageWriter<DesktopAction>,
+) {
+    for gamepad in &gamepads {
+        for (button, action) in &bindings.0 {
+            if gamepad.just_pressed(*button) {
+                actions.write(*action);
+            }
+        }
+    }
+}
+
+fn deadzone(stick: Vec2) -> Vec2 {
+    if stick.length() < STICK_DEADZONE {
+        Vec2::ZERO
+    } else {
+        stick
+    }
+}
+
+/// Moves the orbit targets, so the camera smoothing also applies to gamepad input
+fn gamepad_camera(
+    time: Res<Time>,
+    gamepads: Query<&Gamepad>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+) {
+    let dt = time.delta_secs();
+    for gamepad in &gamepads {
+        let orbit = deadzone(gamepad.left_stick());
+        let pan = deadzone(gamepad.right_stick());
+        if orbit == Vec2::ZERO && pan == Vec2::ZERO {
+            continue;
+        }
+        let pan_forward = gamepad.pressed(GamepadButton::RightTrigger);
+
+        // Disabled e.g. while measuring
+        for mut camera in cameras.iter_mut().filter(|camera| camera.enabled) {
+            camera.target_yaw -= orbit.x * ORBIT_SPEED * dt;
+            // Not over the top, the stick would be inverted afterwards
+            camera.target_pitch = (camera.target_pitch + orbit.y * ORBIT_SPEED * dt)
+                .clamp(-FRAC_PI_2 + 0.01, FRAC_PI_2 - 0.01);
+
+            // Relative to the view direction, in the ground plane
+            let yaw = camera.target_yaw;
+            let right = Vec3::new(yaw.cos(), 0.0, -yaw.sin());
+            let forward = Vec3::new(-yaw.sin(), 0.0, -yaw.cos());
+            let pan_distance = camera.target_radius * PAN_SPEED * dt;
+            camera.target_focus += right * pan.x * pan_distance;
+            if pan_forward {
+                camera.target_focus += forward * pan.y * pan_distance;
+            } else {
+                camera.target_radius *= (-pan.y * ZOOM_SPEED * dt).exp();
+            }
+        }
+    }
+}
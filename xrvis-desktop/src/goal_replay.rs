@@ -0,0 +1,107 @@
+use crate::director::direct_camera;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use sslgame::{Field, FieldGeometry, GoalReplay, Language, SLOW_MOTION_SPEED};
+use std::f32::consts::FRAC_PI_2;
+
+pub fn goal_replay_plugin(app: &mut App) {
+    app.add_systems(Update, follow_goal_cam.after(direct_camera));
+    app.add_systems(EguiPrimaryContextPass, goal_replay_ui);
+}
+
+/// Offers the replay of the last goal of every field at the bottom of the screen
+fn goal_replay_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut goal_cam: Local<bool>,
+    mut q_fields: Query<(&Field, &mut GoalReplay)>,
+    language: Res<Language>,
+) -> Result {
+    // Only accessing the replays mutably when a button is clicked keeps their change detection meaningful
+    if !q_fields.iter().any(|(_, replay)| replay.clip().is_some()) {
+        return Ok(());
+    }
+
+    egui::Area::new(egui::Id::new("goal_replay"))
+        .anchor(egui::Align2::CENTER_BOTTOM, [0.0, -40.0])
+        .show(contexts.ctx_mut()?, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                for (field, mut replay) in &mut q_fields {
+                    let Some(clip) = replay.clip() else {
+                        continue;
+                    };
+                    let field_name = field.host.display_name();
+                    let duration = clip.duration();
+
+                    ui.horizontal(|ui| match replay.playback().copied() {
+                        Some(playback) => {
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                language.trf(
+                                    "Replay {field}  {position} / {duration} s",
+                                    &[
+                                        ("field", &field_name),
+                                        (
+                                            "position",
+                                            &format!("{:.1}", playback.position.as_secs_f32()),
+                                        ),
+                                        ("duration", &format!("{:.1}", duration.as_secs_f32())),
+                                    ],
+                                ),
+                            );
+                            if ui.button(language.tr("Back to live")).clicked() {
+                                replay.stop();
+                            }
+                        }
+                        None => {
+                            ui.strong(language.trf("Goal on {field}", &[("field", &field_name)]));
+                            if ui.button(language.tr("Replay")).clicked() {
+                                replay.play(1.0, *goal_cam);
+                            }
+                            if ui.button(language.tr("Slow motion")).clicked() {
+                                replay.play(SLOW_MOTION_SPEED, *goal_cam);
+                            }
+                            ui.checkbox(&mut goal_cam, language.tr("Goal cam"));
+                        }
+                    });
+                }
+            });
+        });
+    Ok(())
+}
+
+/// Moves the camera behind the goal while a replay with the goal cam is playing, overriding the auto director
+fn follow_goal_cam(
+    q_fields: Query<(&GoalReplay, &FieldGeometry, &GlobalTransform), With<Field>>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+) {
+    let Some((goal_position, geom, field_transform)) =
+        q_fields.iter().find_map(|(replay, geom, field_transform)| {
+            replay
+                .playback()
+                .filter(|playback| playback.goal_cam)
+                .and(replay.clip())
+                .map(|clip| (clip.goal_position, geom, field_transform))
+        })
+    else {
+        return;
+    };
+
+    let side = goal_position.x.signum();
+    let focus = Vec3::new(
+        side * geom.play_area_size.x / 2.0,
+        0.0,
+        goal_position
+            .z
+            .clamp(-geom.goal_width / 2.0, geom.goal_width / 2.0),
+    );
+    for mut camera in &mut cameras {
+        camera.target_focus = field_transform.transform_point(focus);
+        // Looking from behind the goal towards the field
+        camera.target_yaw = side * FRAC_PI_2;
+        camera.target_pitch = 0.25;
+        camera.target_radius = 2.5;
+    }
+}
@@ -1,6 +1,41 @@
+mod accessibility;
+mod camera_paths;
+mod cli;
+mod dashboard;
+mod director;
+mod field_layout;
+mod frame_output;
+mod gamepad;
+mod goal_replay;
+mod measurement;
+mod network_diagnostics;
+mod picture_in_picture;
+mod pointer;
+mod self_test;
+mod session;
+mod sharing;
+mod shortcuts;
+mod spherical_export;
+
+use crate::cli::Cli;
+use crate::director::AutoDirector;
+use crate::field_layout::LayoutMove;
+use crate::frame_output::FrameOutput;
+use crate::session::{PanelLayout, SessionLoaded};
+use crate::spherical_export::SphericalExport;
+use bevy::app::ScheduleRunnerPlugin;
 use bevy::prelude::*;
+use bevy::window::ExitCondition;
+use bevy::winit::WinitPlugin;
+use clap::Parser;
 use sslgame::{
-    AvailableHosts, AvailableVisualizations, Field, SelectedVisualizations, ssl_game_plugin,
+    AvailableHosts, AvailableVisualizations, ClientKey, DataSource, DebugNode, DebugTree,
+    DebugValue, DecodeErrors, DemoGame, Field, FieldClock, FieldHost, FieldOrientation,
+    FieldOrigin, FieldRecorder, HostInterfaces, InterfacePreference, Language, MAX_PLOT_WINDOW,
+    MockHost, MockHostConfig, PacketSource, PathHistory, PlaybackClock, Plot, PlotSource, Plots,
+    PresharedKey, Robot, RobotCount, Role, SelectedVisualizations, SharedSnapshot, SourceStreams,
+    Team, TeamRobotCount, Telemetry, VisColorOverrides, VisSelectionState, VisSelectionStatus,
+    assets_loaded, format_stage_time, format_wall_clock, ssl_game_plugin,
 };
 /*use bevy_nokhwa::BevyNokhwaPlugin;
 use bevy_nokhwa::camera::BackgroundCamera;
@@ -13,87 +48,485 @@ use bevy_inspector_egui::egui;
 use bevy_inspector_egui::quick::WorldInspectorPlugin;
 use bevy_panorbit_camera::{PanOrbitCamera, PanOrbitCameraPlugin};
 use sslgame::proto::remote::VisualizationFilter;
+use std::collections::HashMap;
+use std::f32::consts::PI;
+use std::io;
+use std::time::{Duration, SystemTime};
 
 fn main() {
+    let cli = Cli::parse();
     let mut app = App::new();
 
-    app.add_plugins(DefaultPlugins);
+    if cli.headless {
+        // The render world is kept for the asset types, but there is no window or event loop
+        app.add_plugins(
+            DefaultPlugins
+                .set(WindowPlugin {
+                    primary_window: None,
+                    exit_condition: ExitCondition::DontExit,
+                    ..default()
+                })
+                .disable::<WinitPlugin>(),
+        );
+        app.add_plugins(ScheduleRunnerPlugin::run_loop(Duration::from_secs_f64(
+            1.0 / 60.0,
+        )));
+    } else {
+        app.add_plugins(DefaultPlugins);
+    }
     //app.add_plugins(BevyNokhwaPlugin);
     app.add_plugins(ssl_game_plugin);
+    app.add_plugins(field_layout::field_layout_plugin);
 
-    // Dev plugins
-    app.add_plugins(PanOrbitCameraPlugin);
-    app.insert_resource(EguiGlobalSettings {
-        enable_absorb_bevy_input_system: true,
-        ..Default::default()
+    if let Some(preset) = cli.render_preset {
+        app.insert_resource(preset.render_settings());
+    }
+    app.insert_resource(InterfacePreference {
+        priority: cli.interface.clone(),
+        ..default()
     });
-    app.add_plugins(EguiPlugin::default());
-    app.add_plugins(WorldInspectorPlugin::new());
-    app.add_systems(EguiPrimaryContextPass, vis_selection_ui);
+    app.insert_resource(cli.language.unwrap_or_else(Language::from_env));
+    app.insert_resource(ClientKey(cli.preshared_key()));
+
+    // Dev plugins
+    if !cli.headless {
+        app.add_plugins(PanOrbitCameraPlugin);
+        app.insert_resource(EguiGlobalSettings {
+            enable_absorb_bevy_input_system: true,
+            ..Default::default()
+        });
+        app.add_plugins(EguiPlugin::default());
+        app.add_plugins(WorldInspectorPlugin::new());
+        app.add_systems(
+            EguiPrimaryContextPass,
+            (
+                vis_selection_ui,
+                robot_info_ui,
+                role_legend_ui,
+                debug_values_ui,
+                plots_ui,
+                clock_overlay_ui,
+            ),
+        );
+        app.add_plugins(shortcuts::shortcuts_plugin);
+        app.add_plugins(gamepad::gamepad_plugin);
+        app.add_plugins(pointer::pointer_plugin);
+        app.add_plugins(accessibility::accessibility_plugin);
+        app.add_plugins(measurement::measurement_plugin);
+        app.add_plugins(session::session_plugin);
+        app.add_plugins(sharing::sharing_plugin);
+        app.add_plugins(director::director_plugin);
+        app.add_plugins(goal_replay::goal_replay_plugin);
+        app.add_plugins(camera_paths::camera_paths_plugin);
+        app.add_plugins(picture_in_picture::picture_in_picture_plugin);
+        app.add_plugins(dashboard::dashboard_plugin);
+        app.add_plugins(self_test::self_test_plugin);
+        app.add_plugins(network_diagnostics::network_diagnostics_plugin);
+        if cli.director {
+            app.insert_resource(AutoDirector::enabled());
+        }
+    }
+
+    // Optional raw frame output for virtual cameras and broadcast pipelines
+    if let Some(path) = cli.frame_output.clone() {
+        app.insert_resource(FrameOutput {
+            path,
+            size: UVec2::new(1920, 1080),
+        });
+        app.add_plugins(frame_output::frame_output_plugin);
+    }
+
+    // Offline stereo 360 rendering of a recording
+    if let (Some(output), Some(recording)) = (cli.export_360.clone(), cli.replay.first().cloned()) {
+        app.insert_resource(SphericalExport {
+            recording,
+            output,
+            fps: cli.export_fps,
+        });
+        app.add_plugins(spherical_export::spherical_export_plugin);
+    }
 
     #[cfg(feature = "3d-panels")]
     {
         app.add_plugins(xrvis_vr_lib::panels::xr_panel_plugin);
         app.add_plugins(xrvis_vr_lib::panels::game_state::game_state_panel_plugin);
+        app.add_plugins(xrvis_vr_lib::panels::debug_values::debug_values_panel_plugin);
+        app.add_plugins(xrvis_vr_lib::panels::plots::plots_panel_plugin);
     }
 
     app.add_systems(Startup, test_init);
-    app.add_systems(
-        Update,
-        spawn_new_hosts.run_if(resource_changed::<AvailableHosts>),
-    );
+    // Fields are only spawned once the robot models are loaded
+    if cli.has_static_sources() {
+        app.add_systems(
+            Update,
+            // A session loaded in the meantime replaces the static fields
+            spawn_static_fields.run_if(
+                assets_loaded
+                    .and(run_once)
+                    .and(not(resource_exists::<SessionLoaded>)),
+            ),
+        );
+    } else {
+        app.add_systems(
+            Update,
+            // A loaded session replaces the discovered fields. Host changes while loading are still detected
+            // afterwards, since the change condition isn't evaluated before.
+            spawn_new_hosts.run_if(assets_loaded.and(
+                resource_changed::<AvailableHosts>.and(not(resource_exists::<SessionLoaded>)),
+            )),
+        );
+    }
+    if !cli.vis.is_empty() {
+        app.add_systems(Update, apply_vis_patterns);
+    }
+    app.insert_resource(cli);
 
     app.run();
 }
 
+/// Spreads the fields in a line along the z axis
+fn field_transform(index: usize, count: usize) -> Transform {
+    let z_pos = (index * 10) as f32 - ((count - 1) as f32 * 5.0);
+    Transform::from_xyz(0.0, 0.0, z_pos)
+}
+
+/// Position of the --duplicate copy relative to its field
+const DUPLICATE_OFFSET: Vec3 = Vec3::new(14.0, 0.0, 0.0);
+
+/// Marks the copy of a field spawned for --duplicate
+#[derive(Component, Debug)]
+struct DuplicateField;
+
+/// Marks the field that the sources from --ghost and --vis-source are attached to
+#[derive(Component, Debug)]
+struct ExtraSources;
+
+/// Spawns a copy of the field next to it for --duplicate, without recording or extra sources
+fn spawn_duplicate(commands: &mut Commands, field: &Field, index: usize, count: usize) {
+    let mut transform = field_transform(index, count);
+    transform.translation += DUPLICATE_OFFSET;
+    commands.spawn((field.duplicate(), transform, DuplicateField));
+}
+
+/// Creates a recorder in the --record directory, if recording is enabled
+fn field_recorder(cli: &Cli, host: &FieldHost) -> Option<FieldRecorder> {
+    let dir = cli.record.as_ref()?;
+    let timestamp = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+    let name = host
+        .display_name()
+        .replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "_");
+    let path = dir.join(format!("{name}-{timestamp}.xrvisrec"));
+
+    FieldRecorder::create(&path)
+        .inspect(|_| info!("Recording {} into {}", name, path.display()))
+        .inspect_err(|e| error!("Failed to create recording {}: {e}", path.display()))
+        .ok()
+}
+
+/// Starts a host that serves the same packets as a field for --rebroadcast
+fn rebroadcast(cli: &Cli, field: &Field, source: io::Result<PacketSource>) -> Option<MockHost> {
+    let config = MockHostConfig {
+        // Connected hosts aren't named until their advertisement is received, which doesn't happen here
+        hostname: Some(field.host.display_name()),
+        advertise_to: cli.advertise_to.clone(),
+        key: cli.preshared_key(),
+        ..default()
+    };
+    source
+        .and_then(|source| MockHost::spawn(config, source))
+        .inspect(|host| info!("Rebroadcasting field on {}", host.websocket_addr))
+        .inspect_err(|e| error!("Failed to rebroadcast field: {e}"))
+        .ok()
+}
+
+fn spawn_static_fields(mut commands: Commands, cli: Res<Cli>, client_key: Res<ClientKey>) {
+    // Fields with the host that rebroadcasts them, if any
+    let mut fields: Vec<(Field, Option<MockHost>)> = cli
+        .connect
+        .iter()
+        .map(|addr| {
+            let field = Field::bind(
+                FieldHost {
+                    websocket_addr: *addr,
+                    hostname: None,
+                    encrypted: cli.psk.is_some(),
+                },
+                client_key.0.as_ref(),
+            );
+            // The relay has a connection of its own to the host, independent of the field
+            let host = cli.rebroadcast.then(|| {
+                rebroadcast(
+                    &cli,
+                    &field,
+                    Ok(PacketSource::Relay(*addr, client_key.0.clone())),
+                )
+            });
+            (field, host.flatten())
+        })
+        .collect();
+
+    // The export plays back the recording itself, frame by frame
+    if cli.export_360.is_none() {
+        let playback_clock = PlaybackClock::default();
+        for path in &cli.replay {
+            // Rebroadcast replays share the clock with their host, so they have to be synced
+            let field = if cli.sync_replays || cli.rebroadcast {
+                Field::replay_synced(path, &playback_clock)
+            } else {
+                Field::replay(path)
+            };
+            match field {
+                Ok(field) => {
+                    let host = cli.rebroadcast.then(|| {
+                        let source = PacketSource::recording_synced(path, &playback_clock);
+                        rebroadcast(&cli, &field, source)
+                    });
+                    fields.push((field, host.flatten()));
+                }
+                Err(e) => error!("Failed to load recording {}: {e}", path.display()),
+            }
+        }
+    }
+    if let Some(path) = &cli.snapshot {
+        match SharedSnapshot::read(path) {
+            Ok(snapshot) => fields.push((Field::snapshot(snapshot), None)),
+            Err(e) => error!("Failed to load snapshot {}: {e}", path.display()),
+        }
+    }
+    if cli.demo {
+        let field = Field::demo();
+        let host = cli
+            .rebroadcast
+            .then(|| rebroadcast(&cli, &field, Ok(PacketSource::Demo(DemoGame::default()))));
+        fields.push((field, host.flatten()));
+    }
+
+    let count = fields.len();
+    for (i, (field, host)) in fields.into_iter().enumerate() {
+        if cli.duplicate {
+            spawn_duplicate(&mut commands, &field, i, count);
+        }
+        let recorder = field_recorder(&cli, &field.host);
+        let mut field_entity = commands.spawn((field, field_transform(i, count)));
+        if let Some(recorder) = recorder {
+            field_entity.insert(recorder);
+        }
+        // The host stops when the field is despawned
+        if let Some(host) = host {
+            field_entity.insert(host);
+        }
+        if i == 0 {
+            attach_extra_sources(&cli, client_key.0.as_ref(), &mut field_entity);
+        }
+    }
+}
+
+/// Adds the sources from --ghost and --vis-source to a field
+fn attach_extra_sources(cli: &Cli, key: Option<&PresharedKey>, field_entity: &mut EntityCommands) {
+    field_entity.insert(ExtraSources);
+    if let Some(ghost) = cli.ghost_source(key) {
+        field_entity.with_child(ghost);
+    }
+    if let Some(vis_source) = cli.vis_source(key) {
+        // The visualizations of the field's own host are replaced, not merged
+        field_entity.insert(SourceStreams {
+            visualizations: false,
+            ..SourceStreams::ALL
+        });
+        field_entity.with_child(vis_source);
+    }
+}
+
+fn apply_vis_patterns(
+    cli: Res<Cli>,
+    mut q_fields: Query<
+        (&AvailableVisualizations, &mut SelectedVisualizations),
+        Changed<AvailableVisualizations>,
+    >,
+) {
+    for (available, mut selected) in q_fields.iter_mut() {
+        selected.set_if_neq(SelectedVisualizations(VisualizationFilter {
+            allowed_vis_source: available.sources.keys().copied().collect(),
+            allowed_vis_id: available
+                .visualizations
+                .iter()
+                .filter(|(_, name)| cli.vis_matches(name))
+                .map(|(id, _)| *id)
+                .collect(),
+        }));
+    }
+}
+
 fn spawn_new_hosts(
     mut commands: Commands,
+    cli: Res<Cli>,
+    time: Res<Time>,
     available_hosts: Res<AvailableHosts>,
-    mut q_spawned_fields: Query<Entity, With<Field>>,
+    client_key: Res<ClientKey>,
+    q_spawned_fields: Query<(
+        &Field,
+        &Transform,
+        Has<DuplicateField>,
+        Has<ExtraSources>,
+        Entity,
+    )>,
 ) {
     if !available_hosts.is_changed() {
         return;
     }
 
-    // Remove old fields
-    q_spawned_fields
-        .iter_mut()
-        .for_each(|field_entity| commands.entity(field_entity).despawn());
-
     // Spawn fields for each new host in a line. Sort by address to maintain a consistent order
     // of the remaining elements after one of them has been removed.
-    let mut new_hosts = available_hosts.0.iter().collect::<Vec<_>>();
+    // The ghost and vis source hosts are only shown on top of the first field
+    let mut new_hosts = available_hosts
+        .0
+        .iter()
+        .filter(|h| Some(h.websocket_addr) != cli.ghost && Some(h.websocket_addr) != cli.vis_source)
+        .collect::<Vec<_>>();
     new_hosts.sort_unstable_by_key(|h| h.websocket_addr);
     debug!("New Hosts: {:?}", new_hosts);
+
+    // Fields of hosts that are still available keep their connection and are only moved,
+    // the others are removed. Received snapshots are kept.
+    let mut kept_fields = HashMap::<_, Vec<_>>::new();
+    let mut extra_sources_kept = false;
+    for (field, transform, duplicate, extra_sources, field_entity) in &q_spawned_fields {
+        let addr = field.host.websocket_addr;
+        match field.origin() {
+            FieldOrigin::Snapshot => {}
+            FieldOrigin::Host if new_hosts.iter().any(|h| h.websocket_addr == addr) => {
+                kept_fields.entry(addr).or_default().push((
+                    field_entity,
+                    transform.translation,
+                    duplicate,
+                ));
+                extra_sources_kept |= extra_sources;
+            }
+            _ => commands.entity(field_entity).despawn(),
+        }
+    }
+
+    let count = new_hosts.len();
     new_hosts.into_iter().enumerate().for_each(|(i, new_host)| {
-        let z_pos = (i * 10) as f32 - ((available_hosts.0.len() - 1) as f32 * 5.0);
-        commands.spawn((
-            Field::bind(new_host.clone()),
-            Transform::from_xyz(0.0, 0.0, z_pos),
-        ));
+        if let Some(fields) = kept_fields.get(&new_host.websocket_addr) {
+            let target = field_transform(i, count).translation;
+            for (field_entity, translation, duplicate) in fields {
+                let to = if *duplicate {
+                    target + DUPLICATE_OFFSET
+                } else {
+                    target
+                };
+                if *translation != to {
+                    commands
+                        .entity(*field_entity)
+                        .insert(LayoutMove::new(*translation, to, &time));
+                }
+            }
+            return;
+        }
+
+        let field = Field::bind(new_host.clone(), client_key.0.as_ref());
+        if cli.duplicate {
+            spawn_duplicate(&mut commands, &field, i, count);
+        }
+        let mut field_entity = commands.spawn((field, field_transform(i, count)));
+        if let Some(recorder) = field_recorder(&cli, new_host) {
+            field_entity.insert(recorder);
+        }
+        // A kept field already has them
+        if i == 0 && !extra_sources_kept {
+            attach_extra_sources(&cli, client_key.0.as_ref(), &mut field_entity);
+        }
     });
 }
 
 fn vis_selection_ui(
     mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
     mut q_fields: Query<(
-        &Field,
+        AnyOf<(&Field, &DataSource)>,
+        Option<&DecodeErrors>,
         &AvailableVisualizations,
         &mut SelectedVisualizations,
+        &mut VisColorOverrides,
+        &VisSelectionStatus,
+        Option<(&mut FieldOrientation, &mut Transform)>,
+        Entity,
     )>,
+    host_interfaces: Res<HostInterfaces>,
+    mut interface_preference: ResMut<InterfacePreference>,
+    language: Res<Language>,
 ) -> Result {
-    egui::Window::new("Visualizations")
+    panel_layout
+        .window("Visualizations", *language)
         .scroll([false, true])
         .collapsible(true)
         .resizable(true)
         .show(contexts.ctx_mut()?, |ui| {
-            for (field, available, mut selected) in q_fields.iter_mut() {
-                let field_name = field
-                    .host
-                    .hostname
-                    .clone()
-                    .unwrap_or_else(|| field.host.websocket_addr.to_string());
-                ui.label(field_name);
+            for (
+                (field, source),
+                decode_errors,
+                available,
+                mut selected,
+                mut vis_colors,
+                vis_status,
+                orientation,
+                entity,
+            ) in q_fields.iter_mut()
+            {
+                // Data sources have their own visualizations, listed below their field
+                let (host, prefix) = match (field, source) {
+                    (Some(field), _) => (&field.host, ""),
+                    (None, Some(source)) => (&source.host, "↳ "),
+                    (None, None) => unreachable!(),
+                };
+                let field_name = host.display_name();
+                ui.horizontal(|ui| {
+                    ui.label(format!("{prefix}{field_name}"));
+                    match vis_status.state() {
+                        VisSelectionState::Unsent | VisSelectionState::Applied => {}
+                        VisSelectionState::Pending => {
+                            ui.weak(language.tr("selection pending"));
+                        }
+                        VisSelectionState::Ignored => {
+                            ui.colored_label(
+                                egui::Color32::YELLOW,
+                                language.tr("selection ignored by host"),
+                            );
+                        }
+                    }
+                    // Hosts that are visible on several interfaces can be switched to another one
+                    if let Some(hostname) = &host.hostname
+                        && let Some(interfaces) = host_interfaces.0.get(hostname)
+                        && interfaces.len() > 1
+                    {
+                        interface_combo(
+                            ui,
+                            *language,
+                            hostname,
+                            interfaces,
+                            &mut interface_preference,
+                        );
+                    }
+                    // Data sources use the orientation of their field
+                    if let Some((orientation, transform)) = orientation {
+                        orientation_edit(ui, *language, entity, orientation, transform);
+                    }
+                });
+
+                let recent_decode_errors = decode_errors.map_or(0, |e| e.last_minute());
+                if recent_decode_errors > 0 {
+                    ui.colored_label(
+                        egui::Color32::YELLOW,
+                        language.trf(
+                            "{count} decode errors in the last minute",
+                            &[("count", &recent_decode_errors)],
+                        ),
+                    );
+                }
 
                 let mut flags: Vec<_> = available
                     .visualizations
@@ -101,9 +534,14 @@ fn vis_selection_ui(
                     .map(|(id, name)| (id, name, selected.0.allowed_vis_id.contains(id)))
                     .collect();
                 flags.sort_by_key(|(_, name, _)| *name);
+                let mut edited_colors = vis_colors.clone();
                 for (_, name, checked) in flags.iter_mut() {
-                    ui.checkbox(checked, *name);
+                    ui.horizontal(|ui| {
+                        ui.checkbox(checked, *name);
+                        vis_color_edit(ui, *language, name, &mut edited_colors);
+                    });
                 }
+                vis_colors.set_if_neq(edited_colors);
 
                 selected.set_if_neq(SelectedVisualizations(VisualizationFilter {
                     allowed_vis_source: available.sources.keys().copied().collect(),
@@ -118,9 +556,517 @@ fn vis_selection_ui(
     Ok(())
 }
 
+/// Color button that overrides the host colors of the visualization, with a reset button while overridden
+fn vis_color_edit(
+    ui: &mut egui::Ui,
+    language: Language,
+    name: &str,
+    overrides: &mut VisColorOverrides,
+) {
+    let current = overrides.0.get(name).copied();
+    let mut color = egui_color(current.unwrap_or(Color::srgb(0.5, 0.5, 0.5)));
+    if egui::color_picker::color_edit_button_srgba(
+        ui,
+        &mut color,
+        egui::color_picker::Alpha::Opaque,
+    )
+    .changed()
+    {
+        overrides.0.insert(
+            name.to_string(),
+            Color::srgb_u8(color.r(), color.g(), color.b()),
+        );
+    }
+    if current.is_some()
+        && ui
+            .small_button("↺")
+            .on_hover_text(language.tr("Use host colors"))
+            .clicked()
+    {
+        overrides.0.remove(name);
+    }
+}
+
+fn interface_combo(
+    ui: &mut egui::Ui,
+    language: Language,
+    hostname: &str,
+    interfaces: &[String],
+    interface_preference: &mut ResMut<InterfacePreference>,
+) {
+    let current = interface_preference.host_overrides.get(hostname);
+    let mut selected = current.cloned();
+    egui::ComboBox::from_id_salt(("interface", hostname))
+        .selected_text(selected.as_deref().unwrap_or(language.tr("auto")))
+        .show_ui(ui, |ui| {
+            ui.selectable_value(&mut selected, None, language.tr("auto"));
+            for interface in interfaces {
+                ui.selectable_value(&mut selected, Some(interface.clone()), interface);
+            }
+        });
+    if selected.as_ref() != current {
+        match selected {
+            Some(interface) => {
+                interface_preference
+                    .host_overrides
+                    .insert(hostname.to_string(), interface);
+            }
+            None => {
+                interface_preference.host_overrides.remove(hostname);
+            }
+        }
+    }
+}
+
+/// Coordinate convention of the field's hosts, and a button to view the field from the other side
+fn orientation_edit(
+    ui: &mut egui::Ui,
+    language: Language,
+    entity: Entity,
+    mut orientation: Mut<FieldOrientation>,
+    mut transform: Mut<Transform>,
+) {
+    let mut selected = *orientation;
+    egui::ComboBox::from_id_salt(("orientation", entity))
+        .selected_text(language.tr(selected.label()))
+        .show_ui(ui, |ui| {
+            for option in FieldOrientation::ALL {
+                ui.selectable_value(&mut selected, option, language.tr(option.label()));
+            }
+        });
+    orientation.set_if_neq(selected);
+    // Turns the whole field including the goals, so the positions stay on the right side of the field
+    if ui
+        .button(format!("⇄ {}", language.tr("Swap sides")))
+        .on_hover_text(language.tr("View the field from the other side"))
+        .clicked()
+    {
+        transform.rotate_local_y(PI);
+    }
+}
+
+/// Wall clock, stage time and world state age of every field in the top right corner
+fn clock_overlay_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    q_fields: Query<(&Field, &FieldClock, &RobotCount)>,
+    language: Res<Language>,
+) -> Result {
+    egui::Area::new(egui::Id::new("clock_overlay"))
+        .anchor(egui::Align2::RIGHT_TOP, [-8.0, 8.0])
+        .interactable(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            egui::Frame::popup(ui.style()).show(ui, |ui| {
+                ui.strong(format_wall_clock(SystemTime::now()));
+                for (field, clock, robot_count) in &q_fields {
+                    let field_name = field.host.display_name();
+                    let stage_time = clock
+                        .stage_time_left()
+                        .map(format_stage_time)
+                        .unwrap_or_else(|| "-".to_string());
+                    let vision = match clock.world_state_age() {
+                        Some(age) => language.trf("vision {age} ms", &[("age", &age.as_millis())]),
+                        None => language.tr("no vision").to_string(),
+                    };
+                    let robots = |count: &TeamRobotCount| match count.allowed {
+                        Some(allowed) => format!("{}/{allowed}", count.on_field),
+                        None => count.on_field.to_string(),
+                    };
+                    let robot_counts = language.trf(
+                        "robots Y {yellow} B {blue}",
+                        &[
+                            ("yellow", &robots(&robot_count.yellow)),
+                            ("blue", &robots(&robot_count.blue)),
+                        ],
+                    );
+                    let text = format!("{field_name}  {stage_time}  {vision}  {robot_counts}");
+                    let excess = robot_count.yellow.excess() > 0 || robot_count.blue.excess() > 0;
+                    if clock.is_stale() || excess {
+                        ui.colored_label(egui::Color32::RED, text);
+                    } else {
+                        ui.label(text);
+                    }
+                }
+            });
+        });
+    Ok(())
+}
+
+/// Info cards for all robots, with the telemetry of the robots that report it
+fn robot_info_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
+    q_robots: Query<(&Robot, &Team, Option<&Telemetry>, &ChildOf)>,
+    mut q_paths: Query<&mut PathHistory>,
+    language: Res<Language>,
+) -> Result {
+    if q_robots.is_empty() {
+        return Ok(());
+    }
+
+    let mut robots: Vec<_> = q_robots.iter().collect();
+    robots.sort_by_key(|(robot, team, ..)| (**team as u8, robot.0));
+
+    panel_layout
+        .window("Robots", *language)
+        .scroll([false, true])
+        .collapsible(true)
+        .default_open(false)
+        .resizable(true)
+        .show(contexts.ctx_mut()?, |ui| {
+            egui::Grid::new("robot_telemetry")
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in [
+                        "Robot", "Battery", "Kicker", "Dribbler", "Radio", "Role", "Path",
+                    ] {
+                        ui.strong(language.tr(header));
+                    }
+                    ui.end_row();
+
+                    let format = |value: Option<f32>, unit: &str, precision: usize| {
+                        value
+                            .map(|value| format!("{value:.precision$} {unit}"))
+                            .unwrap_or_else(|| "-".to_string())
+                    };
+                    let mut path_buttons =
+                        |ui: &mut egui::Ui, child_of: &ChildOf, team: Team, id: u32| {
+                            let Ok(mut paths) = q_paths.get_mut(child_of.parent()) else {
+                                ui.label("-");
+                                return;
+                            };
+                            ui.horizontal(|ui| {
+                                let mut recording = paths.is_recording(team, id);
+                                if ui.checkbox(&mut recording, language.tr("Record")).changed() {
+                                    paths.set_recording(team, id, recording);
+                                }
+                                if ui.button(language.tr("Clear")).clicked() {
+                                    paths.clear(team, id);
+                                }
+                            });
+                        };
+                    for (robot, team, telemetry, child_of) in robots {
+                        ui.label(format!("{team:?} {}", robot.0));
+                        // Paths are recorded from the world state, so they are available for every robot
+                        let Some(telemetry) = telemetry else {
+                            for _ in 0..5 {
+                                ui.label("-");
+                            }
+                            path_buttons(ui, child_of, *team, robot.0 as u32);
+                            ui.end_row();
+                            continue;
+                        };
+                        let battery = format(telemetry.battery_voltage, "V", 1);
+                        if telemetry.battery_level().is_some_and(|level| level < 0.2) {
+                            ui.colored_label(egui::Color32::YELLOW, battery);
+                        } else {
+                            ui.label(battery);
+                        }
+                        let kicker =
+                            format(telemetry.kicker_charge.map(|charge| charge * 100.0), "%", 0);
+                        if telemetry.kicker_armed == Some(true) {
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                language.trf("{charge} armed", &[("charge", &kicker)]),
+                            );
+                        } else {
+                            ui.label(kicker);
+                        }
+                        ui.label(language.tr(match telemetry.dribbler_active {
+                            Some(true) => "on",
+                            Some(false) => "off",
+                            None => "-",
+                        }));
+                        ui.label(format(telemetry.radio_rssi, "dBm", 0));
+                        match telemetry.role {
+                            Some(role) => ui
+                                .colored_label(egui_color(role.color()), language.tr(role.label())),
+                            None => ui.label("-"),
+                        };
+                        path_buttons(ui, child_of, *team, robot.0 as u32);
+                        ui.end_row();
+                    }
+                });
+        });
+    Ok(())
+}
+
+fn egui_color(color: Color) -> egui::Color32 {
+    let [r, g, b, a] = color.to_srgba().to_u8_array();
+    egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+}
+
+/// Explains the role icons above the robots, only shown if the host sends roles
+fn role_legend_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
+    q_robots: Query<(&Team, &Telemetry)>,
+    language: Res<Language>,
+) -> Result {
+    let roles: Vec<_> = q_robots
+        .iter()
+        .filter_map(|(team, telemetry)| Some((*team, telemetry.role?)))
+        .collect();
+    if roles.is_empty() {
+        return Ok(());
+    }
+
+    panel_layout
+        .window("Roles", *language)
+        .collapsible(true)
+        .resizable(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            egui::Grid::new("role_legend").show(ui, |ui| {
+                for header in ["Role", "Icon", "Yellow", "Blue"] {
+                    ui.strong(language.tr(header));
+                }
+                ui.end_row();
+
+                for role in Role::ALL {
+                    let icon = match role {
+                        Role::Keeper => "Square",
+                        Role::Defender => "Circle",
+                        Role::Midfielder => "Diamond",
+                        Role::Striker => "Triangle",
+                    };
+                    ui.colored_label(egui_color(role.color()), language.tr(role.label()));
+                    ui.label(language.tr(icon));
+                    for team in [Team::Yellow, Team::Blue] {
+                        let count = roles.iter().filter(|r| **r == (team, role)).count();
+                        ui.label(count.to_string());
+                    }
+                    ui.end_row();
+                }
+            });
+        });
+    Ok(())
+}
+
+/// Debug value tree of each field, with pinned values listed first
+fn debug_values_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
+    mut q_fields: Query<(&Field, &mut DebugTree)>,
+    language: Res<Language>,
+) -> Result {
+    if q_fields.iter().all(|(_, tree)| tree.values().is_empty()) {
+        return Ok(());
+    }
+
+    panel_layout
+        .window("Debug values", *language)
+        .scroll([false, true])
+        .collapsible(true)
+        .default_open(false)
+        .resizable(true)
+        .show(contexts.ctx_mut()?, |ui| {
+            for (field, mut debug_tree) in q_fields.iter_mut() {
+                if debug_tree.values().is_empty() {
+                    continue;
+                }
+                // Pins are only applied after drawing, the nodes borrow from the tree
+                let mut pin_changes = Vec::new();
+
+                let field_name = field.host.display_name();
+                egui::CollapsingHeader::new(field_name)
+                    .default_open(true)
+                    .show(ui, |ui| {
+                        for (key, value) in debug_tree.pinned() {
+                            ui.horizontal(|ui| {
+                                if ui.selectable_label(true, "📌").clicked() {
+                                    pin_changes.push((key.to_string(), false));
+                                }
+                                let value = value.map(|v| v.to_string());
+                                ui.label(format!("{key}: {}", value.as_deref().unwrap_or("-")));
+                            });
+                        }
+                        if debug_tree.pinned().next().is_some() {
+                            ui.separator();
+                        }
+                        debug_node_ui(ui, &debug_tree, &debug_tree.root(), &mut pin_changes);
+                    });
+
+                for (key, pinned) in pin_changes {
+                    debug_tree.set_pinned(&key, pinned);
+                }
+            }
+        });
+    Ok(())
+}
+
+fn debug_node_ui(
+    ui: &mut egui::Ui,
+    debug_tree: &DebugTree,
+    node: &DebugNode,
+    pin_changes: &mut Vec<(String, bool)>,
+) {
+    if let Some(value) = node.value {
+        let name = node
+            .key
+            .rsplit(sslgame::DEBUG_KEY_SEPARATOR)
+            .next()
+            .unwrap_or_default();
+        let pinned = debug_tree.is_pinned(node.key);
+        ui.horizontal(|ui| {
+            if ui.selectable_label(pinned, "📌").clicked() {
+                pin_changes.push((node.key.to_string(), !pinned));
+            }
+            ui.label(format!("{name}: {value}"));
+        });
+    }
+    for (name, child) in &node.children {
+        if child.children.is_empty() {
+            debug_node_ui(ui, debug_tree, child, pin_changes);
+        } else {
+            egui::CollapsingHeader::new(*name)
+                .id_salt(child.key)
+                .show(ui, |ui| debug_node_ui(ui, debug_tree, child, pin_changes));
+        }
+    }
+}
+
+/// Line charts of the recorded values of each field
+fn plots_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
+    time: Res<Time<Real>>,
+    mut q_fields: Query<(&Field, &mut Plots, &DebugTree)>,
+    language: Res<Language>,
+) -> Result {
+    let now = time.elapsed_secs_f64();
+
+    panel_layout
+        .window("Plots", *language)
+        .scroll([false, true])
+        .collapsible(true)
+        .default_open(false)
+        .resizable(true)
+        .show(contexts.ctx_mut()?, |ui| {
+            for (field, mut plots, debug_tree) in q_fields.iter_mut() {
+                let field_name = field.host.display_name();
+                ui.label(field_name);
+
+                let mut window = plots.window().as_secs_f32();
+                if ui
+                    .add(
+                        egui::Slider::new(&mut window, 1.0..=MAX_PLOT_WINDOW.as_secs_f32())
+                            .text(language.tr("Time window (s)")),
+                    )
+                    .changed()
+                {
+                    plots.set_window(Duration::from_secs_f32(window));
+                }
+
+                let mut removed = None;
+                for plot in plots.plots() {
+                    ui.horizontal(|ui| {
+                        if ui.small_button("x").clicked() {
+                            removed = Some(plot.source.clone());
+                        }
+                        let latest = plot.latest().map(|value| format!("{value:.3}"));
+                        ui.label(format!(
+                            "{}: {}",
+                            language.tr(&plot.source.label()),
+                            latest.as_deref().unwrap_or("-")
+                        ));
+                    });
+                    plot_chart_ui(ui, plot, now, plots.window());
+                }
+                if let Some(source) = removed {
+                    plots.remove(&source);
+                }
+
+                // Text values can't be plotted
+                let candidates: Vec<_> = [
+                    PlotSource::BallSpeed,
+                    PlotSource::BufferedPackets,
+                    PlotSource::MinBufferTime,
+                ]
+                .into_iter()
+                .chain(
+                    debug_tree
+                        .values()
+                        .iter()
+                        .filter(|(_, value)| !matches!(value, DebugValue::Text(_)))
+                        .map(|(key, _)| PlotSource::DebugValue(key.clone())),
+                )
+                .filter(|source| !plots.contains(source))
+                .collect();
+                let mut added = None;
+                egui::ComboBox::from_id_salt(("add_plot", &field.host.websocket_addr))
+                    .selected_text(language.tr("Add plot"))
+                    .show_ui(ui, |ui| {
+                        for source in candidates {
+                            if ui
+                                .selectable_label(false, language.tr(&source.label()))
+                                .clicked()
+                            {
+                                added = Some(source);
+                            }
+                        }
+                    });
+                if let Some(source) = added {
+                    plots.add(source);
+                }
+                ui.separator();
+            }
+        });
+    Ok(())
+}
+
+fn plot_chart_ui(ui: &mut egui::Ui, plot: &Plot, now: f64, window: Duration) {
+    let (rect, _) =
+        ui.allocate_exact_size(egui::vec2(ui.available_width(), 60.), egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2., ui.visuals().extreme_bg_color);
+
+    let start = now - window.as_secs_f64();
+    let Some((min, max)) = plot.value_range_since(start) else {
+        return;
+    };
+    // Constant values are drawn in the middle
+    let (min, max) = if max - min < 1e-9 {
+        (min - 1., max + 1.)
+    } else {
+        (min, max)
+    };
+
+    let area = rect.shrink(4.);
+    let points = plot
+        .samples_since(start)
+        .map(|(t, value)| {
+            egui::pos2(
+                area.left() + ((t - start) / window.as_secs_f64()) as f32 * area.width(),
+                area.bottom() - ((value - min) / (max - min)) as f32 * area.height(),
+            )
+        })
+        .collect();
+    painter.add(egui::Shape::line(
+        points,
+        egui::Stroke::new(1.5, ui.visuals().selection.stroke.color),
+    ));
+
+    let font = egui::FontId::monospace(10.);
+    let text_color = ui.visuals().weak_text_color();
+    painter.text(
+        rect.left_top(),
+        egui::Align2::LEFT_TOP,
+        format!("{max:.2}"),
+        font.clone(),
+        text_color,
+    );
+    painter.text(
+        rect.left_bottom(),
+        egui::Align2::LEFT_BOTTOM,
+        format!("{min:.2}"),
+        font,
+        text_color,
+    );
+}
+
 fn test_init(mut commands: Commands) {
     commands.spawn((
-        Transform::from_xyz(0.0, 8.0, 9.0),
+        // Looking at the origin is only required without the orbit controls in headless mode
+        Transform::from_xyz(0.0, 8.0, 9.0).looking_at(Vec3::ZERO, Vec3::Y),
         PanOrbitCamera::default(),
         bevy::core_pipeline::prepass::DepthPrepass,
         /*BackgroundCamera::new(
@@ -134,15 +1080,4 @@ fn test_init(mut commands: Commands) {
         )
         .unwrap(),*/
     ));
-    commands.spawn((
-        Transform {
-            translation: Vec3::new(0.0, 5.0, 5.0),
-            rotation: Quat::from_rotation_z(90.0_f32.to_radians()),
-            ..Default::default()
-        },
-        DirectionalLight {
-            illuminance: 1000.0,
-            ..DirectionalLight::default()
-        },
-    ));
 }
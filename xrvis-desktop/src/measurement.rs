@@ -0,0 +1,142 @@
+use crate::pointer::FieldCursor;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use sslgame::{FieldGeometry, Measurement, field_to_local};
+
+pub fn measurement_plugin(app: &mut App) {
+    app.init_resource::<MeasurementTool>();
+    app.add_systems(
+        Update,
+        (
+            place_measurement_points,
+            lock_camera,
+            draw_measurement_preview,
+        )
+            .chain(),
+    );
+    app.add_systems(EguiPrimaryContextPass, measurement_labels_ui);
+}
+
+/// While active, clicks on a field mark the start and end of a measurement instead of orbiting the camera
+#[derive(Resource, Debug, Default)]
+pub struct MeasurementTool {
+    pub active: bool,
+    /// Field and start point of the measurement that is being placed
+    start: Option<(Entity, Vec2)>,
+}
+
+impl MeasurementTool {
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        self.start = None;
+        info!(
+            "Measurement mode {}",
+            if self.active { "enabled" } else { "disabled" }
+        );
+    }
+}
+
+fn place_measurement_points(
+    mut commands: Commands,
+    mut tool: ResMut<MeasurementTool>,
+    mouse: Res<ButtonInput<MouseButton>>,
+    field_cursor: Res<FieldCursor>,
+) {
+    if !tool.active || !mouse.just_pressed(MouseButton::Left) {
+        return;
+    }
+    let Some((field, position)) = field_cursor.hit else {
+        return;
+    };
+
+    match tool.start.take() {
+        Some((start_field, start)) if start_field == field => {
+            commands
+                .entity(field)
+                .with_child(Measurement::new(start, position));
+        }
+        // The first point or a click on another field starts a new measurement
+        _ => tool.start = Some((field, position)),
+    }
+}
+
+/// Left clicks are used for placing points while measuring
+fn lock_camera(tool: Res<MeasurementTool>, mut q_cameras: Query<&mut PanOrbitCamera>) {
+    if tool.is_changed() {
+        for mut camera in &mut q_cameras {
+            camera.enabled = !tool.active;
+        }
+    }
+}
+
+/// Shows the line from the placed start point to the cursor
+fn draw_measurement_preview(
+    mut gizmos: Gizmos,
+    tool: Res<MeasurementTool>,
+    field_cursor: Res<FieldCursor>,
+    q_fields: Query<&GlobalTransform>,
+) {
+    if let Some((field, start)) = tool.start
+        && let Some((cursor_field, end)) = field_cursor.hit
+        && cursor_field == field
+        && let Ok(field_transform) = q_fields.get(field)
+    {
+        gizmos.line(
+            field_transform.transform_point(field_to_local(start)),
+            field_transform.transform_point(field_to_local(end)),
+            Color::srgba(1.0, 0.4, 0.9, 0.5),
+        );
+    }
+}
+
+/// Labels every measurement and the one that is being placed with its distance
+fn measurement_labels_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    tool: Res<MeasurementTool>,
+    field_cursor: Res<FieldCursor>,
+    q_cameras: Query<(&Camera, &GlobalTransform), With<PanOrbitCamera>>,
+    q_measurements: Query<(&Measurement, &GlobalTransform, &ChildOf)>,
+    q_fields: Query<(&FieldGeometry, &GlobalTransform)>,
+) -> Result {
+    let Ok((camera, camera_transform)) = q_cameras.single() else {
+        return Ok(());
+    };
+    let painter = contexts
+        .ctx_mut()?
+        .layer_painter(egui::LayerId::background());
+
+    let label = |world_position: Vec3, text: String| {
+        if let Ok(screen_position) = camera.world_to_viewport(camera_transform, world_position) {
+            painter.text(
+                egui::pos2(screen_position.x, screen_position.y),
+                egui::Align2::CENTER_BOTTOM,
+                text,
+                egui::FontId::proportional(14.0),
+                egui::Color32::WHITE,
+            );
+        }
+    };
+
+    for (measurement, transform, child_of) in &q_measurements {
+        if let Ok((geom, _)) = q_fields.get(child_of.parent()) {
+            label(transform.translation(), measurement.label(geom));
+        }
+    }
+
+    if let Some((field, start)) = tool.start
+        && let Some((cursor_field, end)) = field_cursor.hit
+        && cursor_field == field
+        && let Ok((geom, field_transform)) = q_fields.get(field)
+    {
+        let preview = Measurement { start, end };
+        label(
+            field_transform.transform_point(field_to_local(start.midpoint(end))),
+            preview.label(geom),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use crate::session::PanelLayout;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use sslgame::{Language, NetworkDiagnostics, format_wall_clock};
+
+pub fn network_diagnostics_plugin(app: &mut App) {
+    app.add_systems(EguiPrimaryContextPass, network_diagnostics_ui);
+}
+
+/// Interfaces, joined multicast groups and recent socket errors, for debugging venue networks
+fn network_diagnostics_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
+    diagnostics: Res<NetworkDiagnostics>,
+    language: Res<Language>,
+) -> Result {
+    let flag = |set: bool| language.tr(if set { "yes" } else { "no" });
+    panel_layout
+        .window("Network", *language)
+        .collapsible(true)
+        .resizable(true)
+        .default_open(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            ui.heading(language.tr("Interfaces"));
+            egui::Grid::new("network_interfaces")
+                .striped(true)
+                .show(ui, |ui| {
+                    for header in ["Name", "Index", "Up", "Multicast", "Addresses"] {
+                        ui.strong(language.tr(header));
+                    }
+                    ui.end_row();
+                    for interface in &diagnostics.interfaces {
+                        ui.label(&interface.name);
+                        ui.label(interface.index.to_string());
+                        ui.label(flag(interface.up));
+                        ui.label(flag(interface.multicast));
+                        let addrs: Vec<_> =
+                            interface.addrs.iter().map(ToString::to_string).collect();
+                        ui.label(addrs.join(", "));
+                        ui.end_row();
+                    }
+                });
+
+            ui.heading(language.tr("Multicast groups"));
+            if diagnostics.memberships.is_empty() {
+                ui.label(language.tr("No groups joined"));
+            }
+            egui::Grid::new("network_memberships")
+                .striped(true)
+                .show(ui, |ui| {
+                    for membership in &diagnostics.memberships {
+                        ui.label(membership.task);
+                        ui.label(&membership.interface);
+                        ui.label(membership.group.to_string());
+                        match &membership.error {
+                            None => ui.colored_label(egui::Color32::GREEN, language.tr("joined")),
+                            Some(e) => ui.colored_label(egui::Color32::RED, e),
+                        };
+                        ui.end_row();
+                    }
+                });
+
+            ui.heading(language.tr("Recent errors"));
+            if diagnostics.errors.is_empty() {
+                ui.label(language.tr("No errors"));
+            }
+            egui::ScrollArea::vertical()
+                .max_height(200.0)
+                .stick_to_bottom(true)
+                .show(ui, |ui| {
+                    for error in &diagnostics.errors {
+                        ui.label(format!("{} {}", format_wall_clock(error.time), error));
+                    }
+                });
+        });
+    Ok(())
+}
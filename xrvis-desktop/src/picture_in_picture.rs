@@ -0,0 +1,255 @@
+//! Second camera rendered into an inset of the window, e.g. a top-down minimap while the main camera shows a
+//! broadcast view of the play.
+
+use crate::session::PanelLayout;
+use bevy::camera::{ScalingMode, Viewport};
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use sslgame::{Field, FieldGeometry, Language};
+
+/// Distance of the inset from the window border, in logical pixels
+const INSET_MARGIN: f32 = 12.0;
+/// Space around the field in the top-down view, in meters
+const TOP_DOWN_MARGIN: f32 = 0.3;
+
+pub fn picture_in_picture_plugin(app: &mut App) {
+    app.init_resource::<PictureInPicture>();
+    app.add_systems(Startup, spawn_pip_camera);
+    app.add_systems(Update, (update_pip_viewport, update_pip_view));
+    app.add_systems(EguiPrimaryContextPass, picture_in_picture_ui);
+}
+
+#[derive(Resource, Debug, Clone, PartialEq)]
+pub struct PictureInPicture {
+    pub enabled: bool,
+    pub view: PipView,
+    pub corner: PipCorner,
+    /// Width of the inset as a fraction of the window width
+    pub size: f32,
+    /// Height divided by width
+    pub aspect: f32,
+}
+
+impl Default for PictureInPicture {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            view: PipView::TopDown,
+            corner: PipCorner::BottomRight,
+            size: 0.3,
+            aspect: 9.0 / 16.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipView {
+    /// Orthographic minimap of the whole field
+    TopDown,
+    /// Behind the goal on the -x side
+    YellowGoal,
+    /// Behind the goal on the +x side
+    BlueGoal,
+}
+
+impl PipView {
+    const ALL: [PipView; 3] = [PipView::TopDown, PipView::YellowGoal, PipView::BlueGoal];
+
+    fn label(&self) -> &'static str {
+        match self {
+            PipView::TopDown => "Top-down",
+            PipView::YellowGoal => "Yellow goal",
+            PipView::BlueGoal => "Blue goal",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PipCorner {
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl PipCorner {
+    const ALL: [PipCorner; 4] = [
+        PipCorner::TopLeft,
+        PipCorner::TopRight,
+        PipCorner::BottomLeft,
+        PipCorner::BottomRight,
+    ];
+
+    fn label(&self) -> &'static str {
+        match self {
+            PipCorner::TopLeft => "Top left",
+            PipCorner::TopRight => "Top right",
+            PipCorner::BottomLeft => "Bottom left",
+            PipCorner::BottomRight => "Bottom right",
+        }
+    }
+}
+
+#[derive(Component, Debug)]
+struct PipCamera;
+
+fn spawn_pip_camera(mut commands: Commands) {
+    commands.spawn((
+        Camera3d::default(),
+        Camera {
+            // After the main camera and the frame output, so the inset isn't part of the output frames
+            order: 2,
+            is_active: false,
+            ..default()
+        },
+        Transform::default(),
+        PipCamera,
+    ));
+}
+
+/// Keeps the inset in its corner when the window or the settings change
+fn update_pip_viewport(
+    settings: Res<PictureInPicture>,
+    window: Option<Single<&Window, With<PrimaryWindow>>>,
+    mut camera: Single<&mut Camera, With<PipCamera>>,
+) {
+    let Some(window) = window else {
+        return;
+    };
+    if camera.is_active != settings.enabled {
+        camera.is_active = settings.enabled;
+    }
+    if !settings.enabled {
+        return;
+    }
+
+    let window_size = window.physical_size().as_vec2();
+    let margin = INSET_MARGIN * window.scale_factor();
+    let width = (window_size.x * settings.size).min(window_size.x - 2.0 * margin);
+    let size = Vec2::new(width, width * settings.aspect)
+        .min(window_size - 2.0 * margin)
+        .max(Vec2::ONE);
+    let far = window_size - size - margin;
+    let position = match settings.corner {
+        PipCorner::TopLeft => Vec2::splat(margin),
+        PipCorner::TopRight => Vec2::new(far.x, margin),
+        PipCorner::BottomLeft => Vec2::new(margin, far.y),
+        PipCorner::BottomRight => far,
+    };
+
+    let (physical_position, physical_size) = (position.max(Vec2::ZERO).as_uvec2(), size.as_uvec2());
+    let current = camera
+        .viewport
+        .as_ref()
+        .map(|viewport| (viewport.physical_position, viewport.physical_size));
+    if current != Some((physical_position, physical_size)) {
+        camera.viewport = Some(Viewport {
+            physical_position,
+            physical_size,
+            ..default()
+        });
+    }
+}
+
+/// Positions the camera relative to the first field
+fn update_pip_view(
+    settings: Res<PictureInPicture>,
+    q_fields: Query<(Entity, &GlobalTransform, &FieldGeometry), With<Field>>,
+    camera: Single<(&mut Transform, &mut Projection), With<PipCamera>>,
+) {
+    let Some((_, field_transform, geom)) = q_fields.iter().min_by_key(|(entity, ..)| *entity)
+    else {
+        return;
+    };
+    if !settings.enabled {
+        return;
+    }
+
+    let (mut transform, mut projection) = camera.into_inner();
+    let half_length = geom.play_area_size.x / 2.0;
+    let (position, look_at, up, new_projection) = match settings.view {
+        PipView::TopDown => {
+            let extent = geom.play_area_size + 2.0 * (geom.boundary_width + TOP_DOWN_MARGIN);
+            let projection = Projection::Orthographic(OrthographicProjection {
+                scaling_mode: ScalingMode::AutoMin {
+                    min_width: extent.x,
+                    min_height: extent.y,
+                },
+                ..OrthographicProjection::default_3d()
+            });
+            // Ssl +y is up in the inset
+            (Vec3::Y * 10.0, Vec3::ZERO, Vec3::NEG_Z, projection)
+        }
+        PipView::YellowGoal | PipView::BlueGoal => {
+            let side = if settings.view == PipView::YellowGoal {
+                -1.0
+            } else {
+                1.0
+            };
+            let position = Vec3::new(side * (half_length + 1.5), 1.2, 0.0);
+            let look_at = Vec3::new(side * (half_length - 1.0), 0.0, 0.0);
+            (position, look_at, Vec3::Y, Projection::default())
+        }
+    };
+
+    let new_transform = Transform::from_translation(field_transform.transform_point(position))
+        .looking_at(
+            field_transform.transform_point(look_at),
+            field_transform.rotation() * up,
+        );
+    transform.set_if_neq(new_transform);
+    let unchanged = match (&*projection, &new_projection) {
+        (Projection::Orthographic(current), Projection::Orthographic(new)) => {
+            current.scaling_mode == new.scaling_mode
+        }
+        (Projection::Perspective(_), Projection::Perspective(_)) => true,
+        _ => false,
+    };
+    if !unchanged {
+        *projection = new_projection;
+    }
+}
+
+fn picture_in_picture_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
+    mut settings: ResMut<PictureInPicture>,
+    language: Res<Language>,
+) -> Result {
+    let mut edited = settings.clone();
+    panel_layout
+        .window("Picture in picture", *language)
+        .collapsible(true)
+        .resizable(false)
+        .default_open(false)
+        .show(contexts.ctx_mut()?, |ui| {
+            ui.checkbox(&mut edited.enabled, language.tr("Show inset"));
+            egui::ComboBox::new("pip_view", language.tr("View"))
+                .selected_text(language.tr(edited.view.label()))
+                .show_ui(ui, |ui| {
+                    for view in PipView::ALL {
+                        ui.selectable_value(&mut edited.view, view, language.tr(view.label()));
+                    }
+                });
+            egui::ComboBox::new("pip_corner", language.tr("Corner"))
+                .selected_text(language.tr(edited.corner.label()))
+                .show_ui(ui, |ui| {
+                    for corner in PipCorner::ALL {
+                        ui.selectable_value(
+                            &mut edited.corner,
+                            corner,
+                            language.tr(corner.label()),
+                        );
+                    }
+                });
+            ui.add(egui::Slider::new(&mut edited.size, 0.1..=0.6).text(language.tr("Size")));
+            ui.add(
+                egui::Slider::new(&mut edited.aspect, 0.3..=1.5).text(language.tr("Aspect ratio")),
+            );
+        });
+    settings.set_if_neq(edited);
+    Ok(())
+}
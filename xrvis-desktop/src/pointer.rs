@@ -0,0 +1,109 @@
+use crate::accessibility::accessibility_menu_ui;
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use sslgame::{AccessibilitySettings, Field, FieldGeometry, Language, local_to_field};
+
+pub fn pointer_plugin(app: &mut App) {
+    app.init_resource::<FieldCursor>();
+    app.add_systems(PreUpdate, update_field_cursor);
+    app.add_systems(EguiPrimaryContextPass, status_bar_ui);
+}
+
+/// The field position under the mouse cursor, if the cursor is on a field (including its boundary)
+#[derive(Resource, Debug, Default)]
+pub struct FieldCursor {
+    /// Field entity and position in SSL field coordinates
+    pub hit: Option<(Entity, Vec2)>,
+}
+
+fn update_field_cursor(
+    mut field_cursor: ResMut<FieldCursor>,
+    q_windows: Query<&Window, With<PrimaryWindow>>,
+    q_cameras: Query<(&Camera, &GlobalTransform), With<PanOrbitCamera>>,
+    q_fields: Query<(&FieldGeometry, &GlobalTransform, Entity), With<Field>>,
+) {
+    field_cursor.hit = None;
+    let Some(cursor) = q_windows.single().ok().and_then(|w| w.cursor_position()) else {
+        return;
+    };
+    let Ok((camera, camera_transform)) = q_cameras.single() else {
+        return;
+    };
+    let Ok(ray) = camera.viewport_to_world(camera_transform, cursor) else {
+        return;
+    };
+
+    // Overlapping fields are resolved by distance to the camera
+    field_cursor.hit = q_fields
+        .iter()
+        .filter_map(|(geom, field_transform, entity)| {
+            let distance = ray.intersect_plane(
+                field_transform.translation(),
+                InfinitePlane3d::new(field_transform.up()),
+            )?;
+            let local = field_transform
+                .affine()
+                .inverse()
+                .transform_point3(ray.get_point(distance));
+            let position = local_to_field(local);
+            let half_size = geom.play_area_size / 2.0 + geom.boundary_width;
+            (position.x.abs() <= half_size.x && position.y.abs() <= half_size.y)
+                .then_some((distance, entity, position))
+        })
+        .min_by(|(a, _, _), (b, _, _)| a.total_cmp(b))
+        .map(|(_, entity, position)| (entity, position));
+}
+
+/// Shows the field coordinates under the cursor, and the language picker and accessibility menu on the right
+fn status_bar_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    field_cursor: Res<FieldCursor>,
+    mut language: ResMut<Language>,
+    mut accessibility: ResMut<AccessibilitySettings>,
+    q_fields: Query<&Field>,
+) -> Result {
+    egui::TopBottomPanel::bottom("status_bar").show(contexts.ctx_mut()?, |ui| {
+        ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+            let mut selected = *language;
+            egui::ComboBox::from_id_salt("language")
+                .selected_text(selected.name())
+                .show_ui(ui, |ui| {
+                    for option in Language::ALL {
+                        ui.selectable_value(&mut selected, option, option.name());
+                    }
+                });
+            language.set_if_neq(selected);
+            let edited = accessibility_menu_ui(ui, *accessibility, *language);
+            accessibility.set_if_neq(edited);
+
+            ui.with_layout(egui::Layout::left_to_right(egui::Align::Center), |ui| {
+                cursor_position_ui(ui, &field_cursor, *language, &q_fields);
+            });
+        });
+    });
+    Ok(())
+}
+
+fn cursor_position_ui(
+    ui: &mut egui::Ui,
+    field_cursor: &FieldCursor,
+    language: Language,
+    q_fields: &Query<&Field>,
+) {
+    let Some((field, position)) = field_cursor.hit else {
+        ui.weak(language.tr("Cursor not on a field"));
+        return;
+    };
+    let field_name = q_fields
+        .get(field)
+        .map(|field| field.host.display_name())
+        .unwrap_or_default();
+    ui.monospace(format!(
+        "{field_name}  x {:>7.3} m  y {:>7.3} m",
+        position.x, position.y
+    ));
+}
@@ -0,0 +1,42 @@
+use crate::session::PanelLayout;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use sslgame::{CheckStatus, Language, SelfTestReport};
+
+pub fn self_test_plugin(app: &mut App) {
+    app.add_systems(EguiPrimaryContextPass, self_test_ui);
+}
+
+/// Shows the startup checks, opened right away if any of them failed
+fn self_test_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut panel_layout: ResMut<PanelLayout>,
+    report: Res<SelfTestReport>,
+    language: Res<Language>,
+) -> Result {
+    panel_layout
+        .window("Self-test", *language)
+        .collapsible(true)
+        .resizable(true)
+        .default_open(report.status() != CheckStatus::Ok)
+        .show(contexts.ctx_mut()?, |ui| {
+            egui::Grid::new("self_test_report")
+                .striped(true)
+                .show(ui, |ui| {
+                    for check in report.checks() {
+                        let (status, color) = match check.status {
+                            CheckStatus::Ok => ("ok", egui::Color32::GREEN),
+                            CheckStatus::Warning => ("warning", egui::Color32::YELLOW),
+                            CheckStatus::Error => ("error", egui::Color32::RED),
+                        };
+                        ui.colored_label(color, language.tr(status));
+                        ui.label(language.tr(&check.name));
+                        ui.label(language.tr(&check.message));
+                        ui.end_row();
+                    }
+                });
+        });
+    Ok(())
+}
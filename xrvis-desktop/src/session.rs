@@ -0,0 +1,213 @@
+use crate::camera_paths::CameraPaths;
+use crate::cli::Cli;
+use crate::shortcuts::DesktopAction;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use sslgame::proto::session::Panel;
+use sslgame::{ClientKey, Language, PresharedKey, Session, SessionState};
+use std::collections::HashMap;
+use std::io;
+use std::path::Path;
+
+/// Windows whose position and size are stored in sessions
+const PANELS: [&str; 10] = [
+    "Visualizations",
+    "Robots",
+    "Debug values",
+    "Plots",
+    "Roles",
+    "Camera paths",
+    "Picture in picture",
+    "Fields",
+    "Self-test",
+    "Network",
+];
+
+pub fn session_plugin(app: &mut App) {
+    app.init_resource::<PanelLayout>();
+    // Static fields wait for the robot models, so they are never spawned after a session from the command line
+    app.add_systems(PostStartup, load_cli_session);
+    app.add_systems(Update, load_session);
+    // The window rects are only available with the egui context
+    app.add_systems(EguiPrimaryContextPass, save_session);
+}
+
+/// Inserted once a session has been loaded, fields are no longer replaced by discovered hosts afterwards
+#[derive(Resource, Debug)]
+pub struct SessionLoaded;
+
+/// Window rects from a loaded session, applied the next time the window is shown
+#[derive(Resource, Debug, Default)]
+pub struct PanelLayout(HashMap<String, egui::Rect>);
+
+impl PanelLayout {
+    /// Creates the window, moved to its saved position if a session was just loaded.
+    /// The size is only applied to windows that haven't been shown yet.
+    /// The English title stays the id, so the layout is kept across languages.
+    pub fn window(&mut self, title: &'static str, language: Language) -> egui::Window<'static> {
+        let window = egui::Window::new(language.tr(title)).id(egui::Id::new(title));
+        match self.0.remove(title) {
+            Some(rect) => window.current_pos(rect.min).default_size(rect.size()),
+            None => window,
+        }
+    }
+}
+
+fn restore_session(
+    path: &Path,
+    commands: &mut Commands,
+    state: &mut SessionState,
+    key: Option<&PresharedKey>,
+    q_cameras: &mut Query<&mut PanOrbitCamera>,
+    panel_layout: &mut PanelLayout,
+    camera_paths: &mut CameraPaths,
+) -> io::Result<()> {
+    let session = Session::read(path)?;
+    state.restore(&session, key);
+    commands.insert_resource(SessionLoaded);
+
+    if let Some(pose) = &session.camera {
+        let transform = Transform::from(pose);
+        for mut camera in q_cameras.iter_mut() {
+            // The orbit rotation is yaw around y, then negative pitch around x
+            let (yaw, pitch, _) = transform.rotation.to_euler(EulerRot::YXZ);
+            // The focus is usually on the field plane
+            let radius = Ray3d::new(transform.translation, transform.forward())
+                .intersect_plane(Vec3::ZERO, InfinitePlane3d::new(Vec3::Y))
+                .unwrap_or(camera.target_radius);
+            camera.target_yaw = yaw;
+            camera.target_pitch = -pitch;
+            camera.target_radius = radius;
+            camera.target_focus = transform.translation + transform.forward() * radius;
+        }
+    }
+
+    panel_layout.0 = session
+        .panel
+        .iter()
+        .filter_map(|panel| {
+            let rect = egui::Rect::from_min_size(
+                egui::pos2(panel.x?, panel.y?),
+                egui::vec2(panel.width?, panel.height?),
+            );
+            Some((panel.name.clone(), rect))
+        })
+        .collect();
+
+    camera_paths.stop();
+    camera_paths.paths = session.camera_path.iter().map(Into::into).collect();
+
+    info!(
+        "Restored {} fields from {}",
+        session.field.len(),
+        path.display()
+    );
+    Ok(())
+}
+
+/// Restores the --session file if it already exists, otherwise it is only used for saving
+fn load_cli_session(
+    mut commands: Commands,
+    cli: Res<Cli>,
+    mut state: SessionState,
+    client_key: Res<ClientKey>,
+    mut q_cameras: Query<&mut PanOrbitCamera>,
+    mut panel_layout: ResMut<PanelLayout>,
+    mut camera_paths: ResMut<CameraPaths>,
+) {
+    let Some(path) = &cli.session else {
+        return;
+    };
+    if !path.exists() {
+        info!("Session {} will be created on save", path.display());
+        return;
+    }
+    if let Err(e) = restore_session(
+        path,
+        &mut commands,
+        &mut state,
+        client_key.0.as_ref(),
+        &mut q_cameras,
+        &mut panel_layout,
+        &mut camera_paths,
+    ) {
+        error!("Failed to load session {}: {e}", path.display());
+    }
+}
+
+fn load_session(
+    mut commands: Commands,
+    mut actions: MessageReader<DesktopAction>,
+    cli: Res<Cli>,
+    mut state: SessionState,
+    client_key: Res<ClientKey>,
+    mut q_cameras: Query<&mut PanOrbitCamera>,
+    mut panel_layout: ResMut<PanelLayout>,
+    mut camera_paths: ResMut<CameraPaths>,
+) {
+    for action in actions.read() {
+        if *action != DesktopAction::LoadSession {
+            continue;
+        }
+        let path = cli.session_path();
+        if let Err(e) = restore_session(
+            &path,
+            &mut commands,
+            &mut state,
+            client_key.0.as_ref(),
+            &mut q_cameras,
+            &mut panel_layout,
+            &mut camera_paths,
+        ) {
+            error!("Failed to load session {}: {e}", path.display());
+        }
+    }
+}
+
+fn save_session(
+    mut contexts: bevy_egui::EguiContexts,
+    mut actions: MessageReader<DesktopAction>,
+    cli: Res<Cli>,
+    state: SessionState,
+    q_cameras: Query<&Transform, With<PanOrbitCamera>>,
+    camera_paths: Res<CameraPaths>,
+) -> Result {
+    if !actions
+        .read()
+        .any(|action| *action == DesktopAction::SaveSession)
+    {
+        return Ok(());
+    }
+
+    let mut session = state.capture();
+    session.camera = q_cameras.single().ok().map(Into::into);
+    session.camera_path = camera_paths.paths.iter().map(Into::into).collect();
+    let ctx = contexts.ctx_mut()?;
+    session.panel = PANELS
+        .iter()
+        .filter_map(|name| {
+            let rect = ctx.memory(|memory| memory.area_rect(egui::Id::new(*name)))?;
+            Some(Panel {
+                name: name.to_string(),
+                x: Some(rect.min.x),
+                y: Some(rect.min.y),
+                width: Some(rect.width()),
+                height: Some(rect.height()),
+            })
+        })
+        .collect();
+
+    let path = cli.session_path();
+    match session.write(&path) {
+        Ok(()) => info!(
+            "Saved {} fields into {}",
+            session.field.len(),
+            path.display()
+        ),
+        Err(e) => error!("Failed to save session {}: {e}", path.display()),
+    }
+    Ok(())
+}
@@ -0,0 +1,107 @@
+use crate::pointer::FieldCursor;
+use crate::shortcuts::DesktopAction;
+use bevy::prelude::*;
+use sslgame::proto::remote::VisualizationFilter;
+use sslgame::{
+    AvailableVisualizations, Field, FieldOrigin, SelectedVisualizations, SnapshotCapture,
+    SnapshotReceived,
+};
+use std::time::SystemTime;
+
+pub fn sharing_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            capture_snapshots,
+            spawn_received_snapshots,
+            select_snapshot_visualizations,
+        ),
+    );
+}
+
+/// Shares or saves the field under the cursor, or the first field if the cursor is not on a field
+fn capture_snapshots(
+    mut actions: MessageReader<DesktopAction>,
+    field_cursor: Res<FieldCursor>,
+    capture: SnapshotCapture,
+    q_fields: Query<Entity, With<Field>>,
+) {
+    for action in actions.read() {
+        if !matches!(
+            action,
+            DesktopAction::ShareSnapshot | DesktopAction::SaveSnapshot
+        ) {
+            continue;
+        }
+        let field = field_cursor
+            .hit
+            .map(|(field, _)| field)
+            .or_else(|| q_fields.iter().min());
+        let Some(snapshot) = field.and_then(|field| capture.capture(field)) else {
+            warn!("No field to capture a snapshot from");
+            continue;
+        };
+
+        if *action == DesktopAction::ShareSnapshot {
+            match snapshot.share() {
+                Ok(()) => info!("Shared snapshot of {}", snapshot.name()),
+                Err(e) => error!("Failed to share snapshot: {e}"),
+            }
+            continue;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let name = snapshot
+            .name()
+            .replace(|c: char| !c.is_ascii_alphanumeric() && c != '-', "_");
+        let path = format!("{name}-{timestamp}.xrvissnap");
+        match snapshot.write(&path) {
+            Ok(()) => info!("Saved snapshot into {path}"),
+            Err(e) => error!("Failed to save snapshot {path}: {e}"),
+        }
+    }
+}
+
+/// Received snapshots are shown in a line behind the -x side of the fields
+fn spawn_received_snapshots(
+    mut commands: Commands,
+    mut snapshots: MessageReader<SnapshotReceived>,
+    q_fields: Query<&Field>,
+) {
+    let mut count = q_fields
+        .iter()
+        .filter(|field| *field.origin() == FieldOrigin::Snapshot)
+        .count();
+    for SnapshotReceived(snapshot) in snapshots.read() {
+        info!("Received snapshot of {}", snapshot.name());
+        commands.spawn((
+            Field::snapshot(snapshot.clone()),
+            Transform::from_xyz(-14.0, 0.0, count as f32 * 10.0),
+        ));
+        count += 1;
+    }
+}
+
+/// Snapshots only contain the visualizations that were selected by the sender, so all of them are shown
+fn select_snapshot_visualizations(
+    mut q_fields: Query<
+        (
+            &Field,
+            &AvailableVisualizations,
+            &mut SelectedVisualizations,
+        ),
+        Changed<AvailableVisualizations>,
+    >,
+) {
+    for (field, available, mut selected) in &mut q_fields {
+        if *field.origin() != FieldOrigin::Snapshot {
+            continue;
+        }
+        selected.set_if_neq(SelectedVisualizations(VisualizationFilter {
+            allowed_vis_source: available.sources.keys().copied().collect(),
+            allowed_vis_id: available.visualizations.keys().copied().collect(),
+        }));
+    }
+}
@@ -0,0 +1,423 @@
+use crate::measurement::MeasurementTool;
+use bevy::prelude::*;
+use bevy_inspector_egui::bevy_egui;
+use bevy_inspector_egui::bevy_egui::EguiPrimaryContextPass;
+use bevy_inspector_egui::egui;
+use bevy_panorbit_camera::PanOrbitCamera;
+use sslgame::{
+    Field, FieldLights, Language, Measurement, Paused, RenderSettings, RobotRenderSettings,
+    SoloField,
+};
+use std::f32::consts::FRAC_PI_2;
+
+pub fn shortcuts_plugin(app: &mut App) {
+    app.add_message::<DesktopAction>();
+    app.insert_resource(Shortcuts::default());
+    app.insert_resource(CommandPalette::default());
+
+    app.add_systems(Update, (trigger_shortcuts, execute_actions).chain());
+    app.add_systems(EguiPrimaryContextPass, command_palette_ui);
+}
+
+/// Everything that can be bound to a shortcut or run from the command palette.
+#[derive(Message, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DesktopAction {
+    ToggleVisualizations,
+    ToggleField,
+    ToggleBall,
+    CycleRobotRendering,
+    ToggleTelemetry,
+    ToggleGrid,
+    ToggleMarkerTags,
+    CycleLights,
+    TogglePause,
+    CameraPreset(CameraPreset),
+    ToggleAutoDirector,
+    CycleSoloField,
+    ShowAllFields,
+    ToggleMeasurement,
+    ClearMeasurements,
+    SaveSession,
+    LoadSession,
+    ShareSnapshot,
+    SaveSnapshot,
+    ToggleCommandPalette,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraPreset {
+    Overview,
+    TopDown,
+    YellowGoal,
+    BlueGoal,
+}
+
+impl DesktopAction {
+    /// All actions that should be listed in the command palette
+    pub const ALL: [DesktopAction; 20] = [
+        DesktopAction::ToggleVisualizations,
+        DesktopAction::ToggleField,
+        DesktopAction::ToggleBall,
+        DesktopAction::CycleRobotRendering,
+        DesktopAction::ToggleTelemetry,
+        DesktopAction::ToggleGrid,
+        DesktopAction::ToggleMarkerTags,
+        DesktopAction::CycleLights,
+        DesktopAction::TogglePause,
+        DesktopAction::CameraPreset(CameraPreset::Overview),
+        DesktopAction::CameraPreset(CameraPreset::TopDown),
+        DesktopAction::CameraPreset(CameraPreset::YellowGoal),
+        DesktopAction::CameraPreset(CameraPreset::BlueGoal),
+        DesktopAction::ToggleAutoDirector,
+        DesktopAction::CycleSoloField,
+        DesktopAction::ShowAllFields,
+        DesktopAction::ToggleMeasurement,
+        DesktopAction::ClearMeasurements,
+        DesktopAction::SaveSession,
+        DesktopAction::LoadSession,
+        DesktopAction::ShareSnapshot,
+        DesktopAction::SaveSnapshot,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            DesktopAction::ToggleVisualizations => "Toggle visualizations",
+            DesktopAction::ToggleField => "Toggle field",
+            DesktopAction::ToggleBall => "Toggle ball",
+            DesktopAction::CycleRobotRendering => "Cycle robot rendering",
+            DesktopAction::ToggleTelemetry => "Toggle telemetry bars",
+            DesktopAction::ToggleGrid => "Toggle ruler grid",
+            DesktopAction::ToggleMarkerTags => "Toggle robot marker tags",
+            DesktopAction::CycleLights => "Cycle field lights",
+            DesktopAction::TogglePause => "Pause/Resume",
+            DesktopAction::CameraPreset(CameraPreset::Overview) => "Camera: Overview",
+            DesktopAction::CameraPreset(CameraPreset::TopDown) => "Camera: Top down",
+            DesktopAction::CameraPreset(CameraPreset::YellowGoal) => "Camera: Behind yellow goal",
+            DesktopAction::CameraPreset(CameraPreset::BlueGoal) => "Camera: Behind blue goal",
+            DesktopAction::ToggleAutoDirector => "Camera: Auto director",
+            DesktopAction::CycleSoloField => "Show only the next field",
+            DesktopAction::ShowAllFields => "Show all fields",
+            DesktopAction::ToggleMeasurement => "Measure distances",
+            DesktopAction::ClearMeasurements => "Clear measurements",
+            DesktopAction::SaveSession => "Save session",
+            DesktopAction::LoadSession => "Load session",
+            DesktopAction::ShareSnapshot => "Share snapshot with other viewers",
+            DesktopAction::SaveSnapshot => "Save snapshot",
+            DesktopAction::ToggleCommandPalette => "Command palette",
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Shortcut {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub shift: bool,
+}
+
+impl Shortcut {
+    pub const fn key(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: false,
+        }
+    }
+
+    pub const fn ctrl(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: true,
+            shift: false,
+        }
+    }
+
+    pub const fn shift(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            shift: true,
+        }
+    }
+
+    fn just_pressed(&self, keys: &ButtonInput<KeyCode>) -> bool {
+        let ctrl = keys.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+        let shift = keys.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+        keys.just_pressed(self.key) && ctrl == self.ctrl && shift == self.shift
+    }
+
+    pub fn display(&self) -> String {
+        let mut out = String::new();
+        if self.ctrl {
+            out += "Ctrl+";
+        }
+        if self.shift {
+            out += "Shift+";
+        }
+        out += format!("{:?}", self.key)
+            .trim_start_matches("Key")
+            .trim_start_matches("Digit");
+        out
+    }
+}
+
+/// Keyboard bindings for desktop actions. Can be replaced or modified at runtime.
+#[derive(Resource, Clone, Debug)]
+pub struct Shortcuts(pub Vec<(Shortcut, DesktopAction)>);
+
+impl Shortcuts {
+    pub fn for_action(&self, action: DesktopAction) -> Option<Shortcut> {
+        self.0
+            .iter()
+            .find(|(_, a)| *a == action)
+            .map(|(shortcut, _)| *shortcut)
+    }
+}
+
+impl Default for Shortcuts {
+    fn default() -> Self {
+        Self(vec![
+            (
+                Shortcut::key(KeyCode::KeyV),
+                DesktopAction::ToggleVisualizations,
+            ),
+            (Shortcut::key(KeyCode::KeyF), DesktopAction::ToggleField),
+            (Shortcut::key(KeyCode::KeyB), DesktopAction::ToggleBall),
+            (
+                Shortcut::key(KeyCode::KeyR),
+                DesktopAction::CycleRobotRendering,
+            ),
+            (Shortcut::key(KeyCode::KeyT), DesktopAction::ToggleTelemetry),
+            (Shortcut::key(KeyCode::KeyG), DesktopAction::ToggleGrid),
+            (
+                Shortcut::key(KeyCode::KeyI),
+                DesktopAction::ToggleMarkerTags,
+            ),
+            (Shortcut::key(KeyCode::KeyL), DesktopAction::CycleLights),
+            (Shortcut::key(KeyCode::Space), DesktopAction::TogglePause),
+            (
+                Shortcut::key(KeyCode::Digit1),
+                DesktopAction::CameraPreset(CameraPreset::Overview),
+            ),
+            (
+                Shortcut::key(KeyCode::Digit2),
+                DesktopAction::CameraPreset(CameraPreset::TopDown),
+            ),
+            (
+                Shortcut::key(KeyCode::Digit3),
+                DesktopAction::CameraPreset(CameraPreset::YellowGoal),
+            ),
+            (
+                Shortcut::key(KeyCode::Digit4),
+                DesktopAction::CameraPreset(CameraPreset::BlueGoal),
+            ),
+            (
+                Shortcut::key(KeyCode::KeyD),
+                DesktopAction::ToggleAutoDirector,
+            ),
+            (Shortcut::key(KeyCode::KeyN), DesktopAction::CycleSoloField),
+            (Shortcut::shift(KeyCode::KeyN), DesktopAction::ShowAllFields),
+            (
+                Shortcut::key(KeyCode::KeyM),
+                DesktopAction::ToggleMeasurement,
+            ),
+            (
+                Shortcut::shift(KeyCode::KeyM),
+                DesktopAction::ClearMeasurements,
+            ),
+            (Shortcut::ctrl(KeyCode::KeyS), DesktopAction::SaveSession),
+            (Shortcut::ctrl(KeyCode::KeyO), DesktopAction::LoadSession),
+            (Shortcut::shift(KeyCode::KeyS), DesktopAction::ShareSnapshot),
+            (
+                Shortcut::ctrl(KeyCode::KeyP),
+                DesktopAction::ToggleCommandPalette,
+            ),
+        ])
+    }
+}
+
+#[derive(Resource, Debug, Default)]
+struct CommandPalette {
+    open: bool,
+    query: String,
+}
+
+/// Converts key presses into actions. Egui absorbs keyboard input while it has focus, so typing in text fields is safe.
+fn trigger_shortcuts(
+    keys: Res<ButtonInput<KeyCode>>,
+    shortcuts: Res<Shortcuts>,
+    mut actions: MessageWriter<DesktopAction>,
+) {
+    for (shortcut, action) in &shortcuts.0 {
+        if shortcut.just_pressed(&keys) {
+            actions.write(*action);
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn execute_actions(
+    mut commands: Commands,
+    mut actions: MessageReader<DesktopAction>,
+    mut render_settings: ResMut<RenderSettings>,
+    mut paused: ResMut<Paused>,
+    mut solo_field: ResMut<SoloField>,
+    mut palette: ResMut<CommandPalette>,
+    mut measurement_tool: ResMut<MeasurementTool>,
+    mut cameras: Query<&mut PanOrbitCamera>,
+    q_measurements: Query<Entity, With<Measurement>>,
+    q_fields: Query<Entity, With<Field>>,
+) {
+    for action in actions.read() {
+        match action {
+            DesktopAction::ToggleVisualizations => {
+                render_settings.visualizations = !render_settings.visualizations;
+            }
+            DesktopAction::ToggleField => render_settings.field = !render_settings.field,
+            DesktopAction::ToggleBall => render_settings.ball = !render_settings.ball,
+            DesktopAction::CycleRobotRendering => {
+                // Detailed models are not implemented yet, so they are skipped
+                render_settings.robots = match render_settings.robots {
+                    RobotRenderSettings::Detailed | RobotRenderSettings::Fallback => {
+                        RobotRenderSettings::Cutout
+                    }
+                    RobotRenderSettings::Cutout => RobotRenderSettings::None,
+                    RobotRenderSettings::None => RobotRenderSettings::Fallback,
+                };
+                info!("Robot rendering: {:?}", render_settings.robots);
+            }
+            DesktopAction::ToggleTelemetry => {
+                render_settings.telemetry = !render_settings.telemetry;
+            }
+            DesktopAction::ToggleGrid => render_settings.grid = !render_settings.grid,
+            DesktopAction::ToggleMarkerTags => {
+                render_settings.marker_tags = !render_settings.marker_tags;
+            }
+            DesktopAction::CycleLights => {
+                render_settings.lights = match render_settings.lights {
+                    FieldLights::Off => FieldLights::Soft,
+                    FieldLights::Soft => FieldLights::Stadium,
+                    FieldLights::Stadium => FieldLights::Off,
+                };
+            }
+            DesktopAction::TogglePause => {
+                paused.0 = !paused.0;
+                info!("{}", if paused.0 { "Paused" } else { "Resumed" });
+            }
+            DesktopAction::CameraPreset(preset) => {
+                for mut camera in &mut cameras {
+                    apply_camera_preset(&mut camera, *preset);
+                }
+            }
+            DesktopAction::CycleSoloField => solo_field.cycle(&q_fields),
+            DesktopAction::ShowAllFields => solo_field.0 = None,
+            DesktopAction::ToggleMeasurement => measurement_tool.toggle(),
+            DesktopAction::ClearMeasurements => {
+                for entity in &q_measurements {
+                    commands.entity(entity).despawn();
+                }
+            }
+            // Handled by the director, session and sharing plugins
+            DesktopAction::ToggleAutoDirector
+            | DesktopAction::SaveSession
+            | DesktopAction::LoadSession
+            | DesktopAction::ShareSnapshot
+            | DesktopAction::SaveSnapshot => {}
+            DesktopAction::ToggleCommandPalette => {
+                palette.open = !palette.open;
+                palette.query.clear();
+            }
+        }
+    }
+}
+
+fn apply_camera_preset(camera: &mut PanOrbitCamera, preset: CameraPreset) {
+    let (yaw, pitch, radius) = match preset {
+        CameraPreset::Overview => (0.0, 0.7, 12.0),
+        // Not exactly straight down to keep the orbit orientation stable
+        CameraPreset::TopDown => (0.0, FRAC_PI_2 - 0.01, 14.0),
+        // The yellow goal is on the -x side of the field
+        CameraPreset::YellowGoal => (-FRAC_PI_2, 0.35, 9.0),
+        CameraPreset::BlueGoal => (FRAC_PI_2, 0.35, 9.0),
+    };
+    camera.target_focus = Vec3::ZERO;
+    camera.target_yaw = yaw;
+    camera.target_pitch = pitch;
+    camera.target_radius = radius;
+}
+
+fn command_palette_ui(
+    mut contexts: bevy_egui::EguiContexts,
+    mut palette: ResMut<CommandPalette>,
+    shortcuts: Res<Shortcuts>,
+    language: Res<Language>,
+    mut actions: MessageWriter<DesktopAction>,
+) -> Result {
+    if !palette.open {
+        return Ok(());
+    }
+
+    // The English labels are searched as well, so commands can be found in both languages
+    let query = palette.query.to_lowercase();
+    let matches: Vec<_> = DesktopAction::ALL
+        .into_iter()
+        .filter(|action| {
+            [action.label(), language.tr(action.label())]
+                .iter()
+                .any(|label| label.to_lowercase().contains(&query))
+        })
+        .collect();
+
+    let mut selected = None;
+    let mut close = false;
+
+    egui::Window::new("Command Palette")
+        .title_bar(false)
+        .collapsible(false)
+        .resizable(false)
+        .anchor(egui::Align2::CENTER_TOP, [0.0, 60.0])
+        .show(contexts.ctx_mut()?, |ui| {
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut palette.query)
+                    .hint_text(language.tr("Type a command..."))
+                    .desired_width(300.0),
+            );
+            response.request_focus();
+
+            for action in &matches {
+                let shortcut = shortcuts
+                    .for_action(*action)
+                    .map(|s| s.display())
+                    .unwrap_or_default();
+                ui.horizontal(|ui| {
+                    if ui
+                        .selectable_label(false, language.tr(action.label()))
+                        .clicked()
+                    {
+                        selected = Some(*action);
+                    }
+                    ui.weak(shortcut);
+                });
+            }
+
+            ui.input(|input| {
+                if input.key_pressed(egui::Key::Enter) {
+                    selected = selected.or(matches.first().copied());
+                }
+                if input.key_pressed(egui::Key::Escape) {
+                    close = true;
+                }
+            });
+        });
+
+    if let Some(action) = selected {
+        actions.write(action);
+        close = true;
+    }
+    if close {
+        palette.open = false;
+        palette.query.clear();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,327 @@
+use crate::frame_output::unpad_rows;
+use bevy::asset::RenderAssetUsages;
+use bevy::camera::RenderTarget;
+use bevy::core_pipeline::prepass::DepthPrepass;
+use bevy::prelude::*;
+use bevy::render::gpu_readback::{Readback, ReadbackComplete};
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use bevy::time::TimeUpdateStrategy;
+use bevy::transform::TransformSystems;
+use bevy_panorbit_camera::PanOrbitCamera;
+use sslgame::{
+    BufferedStateFilter, Field, FieldHost, Recording, StateFilter, UpdatePacket, WorldStateSampling,
+};
+use std::collections::BTreeMap;
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::SyncSender;
+use std::time::{Duration, Instant};
+
+/// Edge length of the rendered cube faces. Each eye is exported as a 4:2 equirectangular image of 4 faces width.
+const FACE_SIZE: u32 = 1024;
+const EYE_DISTANCE: f32 = 0.064;
+/// 6 cube faces for each eye
+const FACE_COUNT: usize = 12;
+/// Frames rendered after the end of the recording, so the last readbacks can complete before exiting
+const TAIL_FRAMES: u32 = 8;
+
+/// Renders a recording offline into stereo 360 frames, e.g. to watch match highlights later in any headset.
+///
+/// The frames are written as raw RGBA8 top-bottom stereo equirectangular images (left eye on top), like the
+/// [`FrameOutput`](crate::frame_output::FrameOutput) they can be encoded with ffmpeg:
+/// `-f rawvideo -pix_fmt rgba -s 4096x4096 -r <fps> -i <path> -c:v libx265 highlight_360_TB.mp4`.
+/// The `_360_TB` suffix makes headset players like Quest TV show the video as stereo 360.
+///
+/// Time advances by exactly one frame per rendered frame, so the export doesn't depend on the rendering speed.
+/// The state filter of the field runs on the same playback clock instead of the system time.
+#[derive(Resource, Clone, Debug)]
+pub struct SphericalExport {
+    pub recording: PathBuf,
+    pub output: PathBuf,
+    pub fps: u32,
+}
+
+pub fn spherical_export_plugin(app: &mut App) {
+    let fps = app.world().resource::<SphericalExport>().fps.max(1);
+    app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f64(
+        1.0 / fps as f64,
+    )));
+    app.add_systems(Startup, setup_export);
+    app.add_systems(Update, feed_recording);
+    app.add_systems(
+        PostUpdate,
+        follow_main_camera.before(TransformSystems::Propagate),
+    );
+}
+
+#[derive(Resource)]
+struct ExportPlayback {
+    recording: Recording,
+    injector: async_channel::Sender<UpdatePacket>,
+    tail_frames: u32,
+    /// Start of the playback clock
+    epoch: Instant,
+    /// Playback time in µs, shared with the state filter of the field
+    playback_time: Arc<AtomicU64>,
+}
+
+/// Readback data of the faces by frame, indexed by eye * 6 + face.
+/// Every readback completes once per rendered frame and in order, so the n-th completion of each face belongs
+/// to the n-th frame, even if the readbacks of consecutive frames complete in the same update.
+#[derive(Resource, Default)]
+struct ExportFaces {
+    completed: [u64; FACE_COUNT],
+    frames: BTreeMap<u64, [Option<Vec<u8>>; FACE_COUNT]>,
+    sink: Option<SyncSender<Vec<Vec<u8>>>>,
+}
+
+/// The cameras are children of the rig, which follows the position of the main camera but stays level
+#[derive(Component, Debug)]
+struct ExportRig;
+
+/// Rotations of the cube face cameras relative to the rig. The horizontal faces come first, they get an eye offset.
+fn face_rotations() -> [Quat; 6] {
+    [
+        Quat::IDENTITY,
+        Quat::from_rotation_y(-FRAC_PI_2),
+        Quat::from_rotation_y(PI),
+        Quat::from_rotation_y(FRAC_PI_2),
+        Quat::from_rotation_x(FRAC_PI_2),
+        Quat::from_rotation_x(-FRAC_PI_2),
+    ]
+}
+
+fn setup_export(
+    mut commands: Commands,
+    export: Res<SphericalExport>,
+    mut image_assets: ResMut<Assets<Image>>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    let recording = match Recording::read(&export.recording) {
+        Ok(recording) => recording,
+        Err(e) => {
+            error!(
+                "Failed to load recording {}: {e}",
+                export.recording.display()
+            );
+            exit.write(AppExit::error());
+            return;
+        }
+    };
+    info!(
+        "Exporting {:.1}s of {} at {} fps into {}",
+        recording.duration().as_secs_f32(),
+        export.recording.display(),
+        export.fps,
+        export.output.display()
+    );
+
+    let (field, injector) = Field::with_injector(FieldHost {
+        websocket_addr: SocketAddr::from((Ipv4Addr::UNSPECIFIED, 0)),
+        hostname: export
+            .recording
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned()),
+        encrypted: false,
+    });
+    let epoch = Instant::now();
+    let playback_time = Arc::new(AtomicU64::new(0));
+    let filter_time = Arc::clone(&playback_time);
+    let state_filter = StateFilter::new(BufferedStateFilter::with_clock(move || {
+        epoch + Duration::from_micros(filter_time.load(Ordering::Relaxed))
+    }));
+    commands.spawn((field, Transform::default(), state_filter));
+    commands.insert_resource(ExportPlayback {
+        recording,
+        injector,
+        tail_frames: TAIL_FRAMES,
+        epoch,
+        playback_time,
+    });
+
+    // The conversion and writing happen on a separate thread. Unlike the frame output, frames are never dropped,
+    // a slow consumer stalls the app instead, which is fine for an offline export.
+    let (frame_tx, frame_rx) = std::sync::mpsc::sync_channel::<Vec<Vec<u8>>>(2);
+    let path = export.output.clone();
+    std::thread::spawn(move || {
+        let mut file = match OpenOptions::new().write(true).create(true).open(&path) {
+            Ok(file) => file,
+            Err(e) => {
+                error!("Failed to open export output {}: {e}", path.display());
+                return;
+            }
+        };
+        let lookup = equirect_lookup();
+        let mut frame = Vec::new();
+        for faces in frame_rx {
+            frame.clear();
+            for eye in faces.chunks(6) {
+                frame.extend(lookup.iter().flat_map(|&(face, offset)| {
+                    let offset = offset as usize * 4;
+                    eye[face as usize][offset..offset + 4].iter().copied()
+                }));
+            }
+            if let Err(e) = file.write_all(&frame) {
+                error!("Export stopped: {e}");
+                return;
+            }
+        }
+    });
+
+    let rig = commands
+        .spawn((ExportRig, Transform::default(), Visibility::default()))
+        .id();
+    let faces = ExportFaces {
+        sink: Some(frame_tx),
+        ..default()
+    };
+    for (eye, eye_side) in [-1.0, 1.0].into_iter().enumerate() {
+        for (face, rotation) in face_rotations().into_iter().enumerate() {
+            let index = eye * 6 + face;
+            // Each horizontal face is rendered from the eye position of someone looking at its center.
+            // This leaves small seams in the depth, but avoids rendering every column separately.
+            let offset = if face < 4 {
+                rotation * Vec3::X * eye_side * EYE_DISTANCE / 2.0
+            } else {
+                Vec3::ZERO
+            };
+
+            let mut image = Image::new_fill(
+                Extent3d {
+                    width: FACE_SIZE,
+                    height: FACE_SIZE,
+                    ..default()
+                },
+                TextureDimension::D2,
+                &[0, 0, 0, 255],
+                TextureFormat::Rgba8UnormSrgb,
+                RenderAssetUsages::default(),
+            );
+            image.texture_descriptor.usage = TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::RENDER_ATTACHMENT;
+            let image_handle = image_assets.add(image);
+
+            commands.entity(rig).with_child((
+                Camera3d::default(),
+                Camera {
+                    order: 10 + index as isize,
+                    ..default()
+                },
+                Projection::Perspective(PerspectiveProjection {
+                    fov: FRAC_PI_2,
+                    aspect_ratio: 1.0,
+                    ..default()
+                }),
+                RenderTarget::Image(image_handle.clone().into()),
+                DepthPrepass,
+                Transform::from_translation(offset).with_rotation(rotation),
+            ));
+
+            commands.spawn(Readback::texture(image_handle)).observe(
+                move |readback: On<ReadbackComplete>, mut faces: ResMut<ExportFaces>| {
+                    let faces = &mut *faces;
+                    let frame = faces.completed[index];
+                    faces.completed[index] += 1;
+                    let frame_faces = faces.frames.entry(frame).or_default();
+                    frame_faces[index] = Some(unpad_rows(&readback, UVec2::splat(FACE_SIZE)));
+                    // Frames complete in order, a later frame can't be complete before all faces of this one arrived
+                    if frame_faces.iter().all(Option::is_some)
+                        && let Some(frame_faces) = faces.frames.remove(&frame)
+                    {
+                        let frame = frame_faces.into_iter().flatten().collect();
+                        if let Some(sink) = &faces.sink
+                            && sink.send(frame).is_err()
+                        {
+                            // The writer thread has already logged the error
+                            faces.sink = None;
+                        }
+                    }
+                },
+            );
+        }
+    }
+    commands.insert_resource(faces);
+}
+
+/// Pushes the packets up to the current time into the field and exits after the end of the recording
+fn feed_recording(
+    time: Res<Time>,
+    playback: Option<ResMut<ExportPlayback>>,
+    mut sampling: ResMut<WorldStateSampling>,
+    mut exit: MessageWriter<AppExit>,
+) {
+    let Some(mut playback) = playback else {
+        return;
+    };
+    let playback = &mut *playback;
+    let elapsed = time.elapsed();
+    // The packets arrive and the world state is sampled at the playback time, however long rendering takes
+    playback
+        .playback_time
+        .store(elapsed.as_micros() as u64, Ordering::Relaxed);
+    sampling.display_time = Some(playback.epoch + elapsed);
+    for packet in playback.recording.advance(elapsed) {
+        // Blocking, so packets aren't lost if the field falls behind
+        _ = playback.injector.send_blocking(packet);
+    }
+
+    if playback.recording.is_finished() {
+        if playback.tail_frames == 0 {
+            info!("Export finished");
+            exit.write(AppExit::Success);
+        }
+        playback.tail_frames = playback.tail_frames.saturating_sub(1);
+    }
+}
+
+fn follow_main_camera(
+    main_camera: Option<Single<&Transform, (With<PanOrbitCamera>, Without<ExportRig>)>>,
+    mut rig: Single<&mut Transform, With<ExportRig>>,
+) {
+    if let Some(main_transform) = main_camera {
+        // Tilting or rolling the horizon is uncomfortable in a headset
+        let (yaw, _, _) = main_transform.rotation.to_euler(EulerRot::YXZ);
+        rig.set_if_neq(
+            Transform::from_translation(main_transform.translation)
+                .with_rotation(Quat::from_rotation_y(yaw)),
+        );
+    }
+}
+
+/// Cube face and pixel index for every pixel of an equirectangular image of one eye.
+/// The center of the image is the forward direction of the rig.
+fn equirect_lookup() -> Vec<(u8, u32)> {
+    let (width, height) = (FACE_SIZE * 4, FACE_SIZE * 2);
+    let inverse_rotations = face_rotations().map(|rotation| rotation.inverse());
+    let mut lookup = Vec::with_capacity((width * height) as usize);
+    for y in 0..height {
+        let latitude = FRAC_PI_2 - (y as f32 + 0.5) / height as f32 * PI;
+        for x in 0..width {
+            let longitude = (x as f32 + 0.5) / width as f32 * TAU - PI;
+            let direction = Vec3::new(
+                latitude.cos() * longitude.sin(),
+                latitude.sin(),
+                -latitude.cos() * longitude.cos(),
+            );
+            // Cameras look along -z, the face that looks most directly in the direction contains it
+            let (face, local) = inverse_rotations
+                .iter()
+                .map(|rotation| *rotation * direction)
+                .enumerate()
+                .min_by(|(_, a), (_, b)| a.z.total_cmp(&b.z))
+                .unwrap();
+            let u = (local.x / -local.z + 1.0) / 2.0;
+            let v = (1.0 - local.y / -local.z) / 2.0;
+            let px = ((u * FACE_SIZE as f32) as u32).min(FACE_SIZE - 1);
+            let py = ((v * FACE_SIZE as f32) as u32).min(FACE_SIZE - 1);
+            lookup.push((face as u8, py * FACE_SIZE + px));
+        }
+    }
+    lookup
+}
@@ -0,0 +1,289 @@
+//! Comfort options for moving the field around the user: animated placement, vignette and fade.
+//! The app has no locomotion, the user walks physically, so only field placement moves the virtual world.
+
+use bevy::asset::RenderAssetUsages;
+use bevy::camera::visibility::RenderLayers;
+use bevy::mesh::{Indices, PrimitiveTopology};
+use bevy::prelude::*;
+use bevy_mod_xr::camera::XrCamera;
+use sslgame::Field;
+use std::f32::consts::{PI, TAU};
+
+/// In seconds
+const PLACEMENT_DURATION: f32 = 0.6;
+/// Distance of the vignette in front of the eyes
+const VIGNETTE_DISTANCE: f32 = 0.2;
+/// Scale of the vignette mesh that keeps its hole outside the field of view
+const VIGNETTE_OPEN_SCALE: f32 = 0.4;
+/// Scale of the vignette mesh at full strength, leaving a clear view straight ahead
+const VIGNETTE_CLOSED_SCALE: f32 = 0.08;
+/// Each eye only sees its own vignette, starting at this layer
+const VIGNETTE_LAYER: usize = 8;
+
+pub fn comfort_plugin(app: &mut App) {
+    app.init_resource::<ComfortSettings>();
+    app.add_systems(Startup, setup_comfort_assets);
+    app.add_systems(
+        Update,
+        (
+            spawn_vignettes,
+            (animate_field_placement, update_vignettes).chain(),
+        ),
+    );
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComfortSettings {
+    /// Darkens the edges of the view while the field moves
+    pub vignette: bool,
+    pub placement: PlacementTransition,
+}
+
+impl Default for ComfortSettings {
+    fn default() -> Self {
+        Self {
+            vignette: true,
+            placement: PlacementTransition::Animated,
+        }
+    }
+}
+
+/// How a field moves to a new placement
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PlacementTransition {
+    /// Jumps to the new placement
+    Instant,
+    /// Glides to the new placement
+    #[default]
+    Animated,
+    /// Fades the view out, jumps and fades back in
+    Fade,
+}
+
+impl PlacementTransition {
+    pub fn name(self) -> &'static str {
+        match self {
+            PlacementTransition::Instant => "Instant",
+            PlacementTransition::Animated => "Animated",
+            PlacementTransition::Fade => "Fade",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            PlacementTransition::Instant => PlacementTransition::Animated,
+            PlacementTransition::Animated => PlacementTransition::Fade,
+            PlacementTransition::Fade => PlacementTransition::Instant,
+        }
+    }
+}
+
+/// Moves a field from its placement at the start to the target, see [`place_field`]
+#[derive(Component, Debug)]
+struct PlacementAnimation {
+    start: Transform,
+    target: Transform,
+    transition: PlacementTransition,
+    /// From 0 to 1
+    progress: f32,
+}
+
+/// Moves the field to a new placement with the configured transition
+pub fn place_field(
+    commands: &mut Commands,
+    settings: &ComfortSettings,
+    field: Entity,
+    transform: &mut Transform,
+    target: Transform,
+) {
+    if settings.placement == PlacementTransition::Instant {
+        *transform = target;
+        commands.entity(field).remove::<PlacementAnimation>();
+        return;
+    }
+    commands.entity(field).insert(PlacementAnimation {
+        start: *transform,
+        target,
+        transition: settings.placement,
+        progress: 0.0,
+    });
+}
+
+/// Darkens the edges of the view
+#[derive(Component, Debug)]
+struct Vignette;
+
+/// Covers the whole view in the middle of a fade
+#[derive(Component, Debug)]
+struct FadeCover;
+
+#[derive(Resource, Debug)]
+struct VignetteAssets {
+    mesh: Handle<Mesh>,
+    material: Handle<StandardMaterial>,
+    cover_mesh: Handle<Mesh>,
+    cover_material: Handle<StandardMaterial>,
+}
+
+/// A disc with a hole of radius 1 that turns black towards the outside.
+/// The outer edge stays outside the field of view at all scales.
+fn vignette_mesh() -> Mesh {
+    const SEGMENTS: u32 = 32;
+    // Radius and alpha of the rings, the outer one covers the view even at full strength
+    const RINGS: [(f32, f32); 3] = [(1.0, 0.0), (1.6, 1.0), (10.0, 1.0)];
+
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    for (radius, alpha) in RINGS {
+        for i in 0..SEGMENTS {
+            let phi = TAU * i as f32 / SEGMENTS as f32;
+            positions.push([phi.cos() * radius, phi.sin() * radius, 0.0]);
+            colors.push([0.0, 0.0, 0.0, alpha]);
+        }
+    }
+    let mut indices = Vec::new();
+    for ring in 0..RINGS.len() as u32 - 1 {
+        for i in 0..SEGMENTS {
+            let next = (i + 1) % SEGMENTS;
+            let inner = ring * SEGMENTS;
+            let outer = inner + SEGMENTS;
+            indices.extend([
+                inner + i,
+                outer + i,
+                outer + next,
+                inner + i,
+                outer + next,
+                inner + next,
+            ]);
+        }
+    }
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_indices(Indices::U32(indices))
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+}
+
+fn setup_comfort_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    commands.insert_resource(VignetteAssets {
+        mesh: meshes.add(vignette_mesh()),
+        material: materials.add(StandardMaterial {
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            cull_mode: None,
+            ..StandardMaterial::from_color(Color::WHITE)
+        }),
+        cover_mesh: meshes.add(Rectangle::new(2.0, 2.0)),
+        cover_material: materials.add(StandardMaterial {
+            unlit: true,
+            ..StandardMaterial::from_color(Color::BLACK)
+        }),
+    });
+}
+
+/// Every eye gets its own vignette, since the hole has to be centered on that eye
+fn spawn_vignettes(
+    mut commands: Commands,
+    assets: Res<VignetteAssets>,
+    q_cameras: Query<(&XrCamera, Entity), Added<XrCamera>>,
+) {
+    for (camera, entity) in &q_cameras {
+        let layer = VIGNETTE_LAYER + camera.0 as usize;
+        commands
+            .entity(entity)
+            .insert(RenderLayers::from_layers(&[0, layer]))
+            .with_child((
+                Vignette,
+                Mesh3d(assets.mesh.clone()),
+                MeshMaterial3d(assets.material.clone()),
+                Transform::from_xyz(0.0, 0.0, -VIGNETTE_DISTANCE),
+                RenderLayers::layer(layer),
+                Visibility::Hidden,
+            ))
+            .with_child((
+                FadeCover,
+                Mesh3d(assets.cover_mesh.clone()),
+                MeshMaterial3d(assets.cover_material.clone()),
+                Transform::from_xyz(0.0, 0.0, -VIGNETTE_DISTANCE),
+                RenderLayers::layer(layer),
+                Visibility::Hidden,
+            ));
+    }
+}
+
+fn animate_field_placement(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut q_fields: Query<(&mut PlacementAnimation, &mut Transform, Entity), With<Field>>,
+) {
+    for (mut animation, mut transform, field) in &mut q_fields {
+        animation.progress = (animation.progress + time.delta_secs() / PLACEMENT_DURATION).min(1.0);
+        *transform = match animation.transition {
+            // Jumps while the view is dark
+            PlacementTransition::Fade | PlacementTransition::Instant => {
+                if animation.progress < 0.5 {
+                    animation.start
+                } else {
+                    animation.target
+                }
+            }
+            PlacementTransition::Animated => {
+                let t = EaseFunction::SmoothStep.sample_clamped(animation.progress);
+                Transform {
+                    translation: animation
+                        .start
+                        .translation
+                        .lerp(animation.target.translation, t),
+                    rotation: animation.start.rotation.slerp(animation.target.rotation, t),
+                    scale: animation.start.scale.lerp(animation.target.scale, t),
+                }
+            }
+        };
+        if animation.progress >= 1.0 {
+            commands.entity(field).remove::<PlacementAnimation>();
+        }
+    }
+}
+
+fn update_vignettes(
+    settings: Res<ComfortSettings>,
+    q_animations: Query<&PlacementAnimation>,
+    mut q_vignettes: Query<(&mut Transform, &mut Visibility), With<Vignette>>,
+    mut q_covers: Query<&mut Visibility, (With<FadeCover>, Without<Vignette>)>,
+) {
+    // Rises and falls with the animation, so the view doesn't darken abruptly
+    let strength = q_animations
+        .iter()
+        .map(|animation| {
+            let envelope = (animation.progress * PI).sin();
+            match animation.transition {
+                PlacementTransition::Fade => 1.0_f32.min(envelope * 2.0),
+                PlacementTransition::Animated if settings.vignette => envelope * 0.7,
+                _ => 0.0,
+            }
+        })
+        .fold(0.0, f32::max);
+
+    for (mut transform, mut visibility) in &mut q_vignettes {
+        if strength <= 0.0 {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        visibility.set_if_neq(Visibility::Inherited);
+        let scale = VIGNETTE_OPEN_SCALE.lerp(VIGNETTE_CLOSED_SCALE, strength);
+        transform.scale = Vec3::new(scale, scale, 1.0);
+    }
+    for mut visibility in &mut q_covers {
+        visibility.set_if_neq(if strength >= 1.0 {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+    }
+}
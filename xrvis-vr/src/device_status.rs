@@ -0,0 +1,286 @@
+//! Battery and thermal state of the headset, read from the android system services.
+//! Other platforms don't report anything, so no warnings are shown there.
+
+use bevy::prelude::*;
+use sslgame::Language;
+#[cfg(target_os = "android")]
+use std::time::{Duration, Instant};
+
+/// The status changes slowly, and every query is a round trip through the JVM
+#[cfg(target_os = "android")]
+const POLL_INTERVAL: Duration = Duration::from_secs(30);
+/// Battery percentage below which a warning is shown while not charging
+const LOW_BATTERY: u8 = 20;
+const CRITICAL_BATTERY: u8 = 10;
+
+pub fn device_status_plugin(app: &mut App) {
+    app.init_resource::<DeviceStatus>();
+    #[cfg(target_os = "android")]
+    app.add_systems(Update, poll_device_status);
+}
+
+/// Last known device status, fields are `None` where the platform doesn't expose them
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct DeviceStatus {
+    /// In percent
+    pub battery: Option<u8>,
+    pub charging: bool,
+    pub thermal: Option<ThermalStatus>,
+}
+
+/// Mirrors the `THERMAL_STATUS_*` constants of the android `PowerManager`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ThermalStatus {
+    None,
+    Light,
+    /// The device starts to throttle from here on
+    Moderate,
+    Severe,
+    Critical,
+    Emergency,
+    Shutdown,
+}
+
+impl ThermalStatus {
+    #[cfg_attr(not(target_os = "android"), allow(dead_code))]
+    fn from_android(status: i32) -> Option<Self> {
+        Some(match status {
+            0 => ThermalStatus::None,
+            1 => ThermalStatus::Light,
+            2 => ThermalStatus::Moderate,
+            3 => ThermalStatus::Severe,
+            4 => ThermalStatus::Critical,
+            5 => ThermalStatus::Emergency,
+            6 => ThermalStatus::Shutdown,
+            _ => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum WarningLevel {
+    Warning,
+    Critical,
+}
+
+impl DeviceStatus {
+    /// Human readable warnings, most severe first
+    pub fn warnings(&self, language: Language) -> Vec<(WarningLevel, String)> {
+        let mut warnings = Vec::new();
+        if let Some(thermal) = self.thermal.filter(|t| *t >= ThermalStatus::Moderate) {
+            let level = if thermal >= ThermalStatus::Severe {
+                WarningLevel::Critical
+            } else {
+                WarningLevel::Warning
+            };
+            warnings.push((
+                level,
+                language.trf(
+                    "Headset hot, throttling ({level})",
+                    &[("level", &language.tr(&format!("{thermal:?}")))],
+                ),
+            ));
+        }
+        if let Some(battery) = self.battery.filter(|b| *b <= LOW_BATTERY && !self.charging) {
+            let level = if battery <= CRITICAL_BATTERY {
+                WarningLevel::Critical
+            } else {
+                WarningLevel::Warning
+            };
+            warnings.push((
+                level,
+                language.trf("Battery {percent}%", &[("percent", &battery)]),
+            ));
+        }
+        warnings.sort_by_key(|(level, _)| std::cmp::Reverse(*level));
+        warnings
+    }
+}
+
+#[cfg(target_os = "android")]
+fn poll_device_status(
+    mut last_poll: Local<Option<Instant>>,
+    mut device_status: ResMut<DeviceStatus>,
+) {
+    let now = Instant::now();
+    if last_poll.is_some_and(|last| now - last < POLL_INTERVAL) {
+        return;
+    }
+    *last_poll = Some(now);
+
+    match read_device_status() {
+        Ok(status) => {
+            if status != *device_status {
+                for (_, warning) in status.warnings(Language::English) {
+                    warn!("Device status: {warning}");
+                }
+                *device_status = status;
+            }
+        }
+        Err(e) => warn!("Failed to read the device status: {e}"),
+    }
+}
+
+#[cfg(target_os = "android")]
+fn read_device_status() -> Result<DeviceStatus, jni::errors::Error> {
+    use jni::JavaVM;
+    use jni::objects::JObject;
+
+    let Some(app) = bevy::android::ANDROID_APP.get() else {
+        return Ok(DeviceStatus::default());
+    };
+    // Both pointers stay valid for the lifetime of the app
+    let vm = unsafe { JavaVM::from_raw(app.vm_as_ptr().cast()) }?;
+    let activity = unsafe { JObject::from_raw(app.activity_as_ptr().cast()) };
+    let mut env = vm.attach_current_thread_permanently()?;
+
+    // The local references are freed with the frame, since the thread stays attached.
+    // Battery and thermal status are read independently, so a missing service or method only loses its own part.
+    env.with_local_frame(16, |env| {
+        let (battery, charging) = cleared(env, |env| read_battery(env, &activity))
+            .inspect_err(|e| warn!("Failed to read the battery status: {e}"))
+            .unwrap_or_default();
+        let thermal = cleared(env, |env| read_thermal(env, &activity))
+            .inspect_err(|e| warn!("Failed to read the thermal status: {e}"))
+            .ok()
+            .flatten();
+        Ok(DeviceStatus {
+            battery,
+            charging,
+            thermal,
+        })
+    })
+}
+
+/// Clears the java exception a failed call leaves behind, every later call on the thread would fail otherwise
+#[cfg(target_os = "android")]
+fn cleared<T>(
+    env: &mut jni::JNIEnv,
+    read: impl FnOnce(&mut jni::JNIEnv) -> Result<T, jni::errors::Error>,
+) -> Result<T, jni::errors::Error> {
+    let result = read(env);
+    if result.is_err() && env.exception_check().unwrap_or(true) {
+        let _ = env.exception_clear();
+    }
+    result
+}
+
+#[cfg(target_os = "android")]
+fn system_service<'local>(
+    env: &mut jni::JNIEnv<'local>,
+    activity: &jni::objects::JObject,
+    name: &str,
+) -> Result<jni::objects::JObject<'local>, jni::errors::Error> {
+    let name = env.new_string(name)?;
+    env.call_method(
+        activity,
+        "getSystemService",
+        "(Ljava/lang/String;)Ljava/lang/Object;",
+        &[jni::objects::JValue::Object(&name)],
+    )?
+    .l()
+}
+
+#[cfg(target_os = "android")]
+fn read_battery(
+    env: &mut jni::JNIEnv,
+    activity: &jni::objects::JObject,
+) -> Result<(Option<u8>, bool), jni::errors::Error> {
+    // BatteryManager.BATTERY_PROPERTY_CAPACITY
+    const BATTERY_PROPERTY_CAPACITY: i32 = 4;
+
+    let battery_manager = system_service(env, activity, "batterymanager")?;
+    let battery = env
+        .call_method(
+            &battery_manager,
+            "getIntProperty",
+            "(I)I",
+            &[jni::objects::JValue::Int(BATTERY_PROPERTY_CAPACITY)],
+        )?
+        .i()?;
+    let charging = env
+        .call_method(&battery_manager, "isCharging", "()Z", &[])?
+        .z()?;
+    // Integer.MIN_VALUE if the capacity isn't supported
+    Ok((u8::try_from(battery).ok(), charging))
+}
+
+/// `getCurrentThermalStatus` only exists from API level 29 on, older systems fail here
+#[cfg(target_os = "android")]
+fn read_thermal(
+    env: &mut jni::JNIEnv,
+    activity: &jni::objects::JObject,
+) -> Result<Option<ThermalStatus>, jni::errors::Error> {
+    let power_manager = system_service(env, activity, "power")?;
+    let thermal = env
+        .call_method(&power_manager, "getCurrentThermalStatus", "()I", &[])?
+        .i()?;
+    Ok(ThermalStatus::from_android(thermal))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(status: DeviceStatus) -> Vec<WarningLevel> {
+        status
+            .warnings(Language::English)
+            .into_iter()
+            .map(|(level, _)| level)
+            .collect()
+    }
+
+    #[test]
+    fn unknown_status_has_no_warnings() {
+        assert!(levels(DeviceStatus::default()).is_empty());
+    }
+
+    #[test]
+    fn thermal_warnings_start_at_throttling() {
+        let status = |thermal| DeviceStatus {
+            thermal: Some(thermal),
+            ..default()
+        };
+        assert!(levels(status(ThermalStatus::Light)).is_empty());
+        assert_eq!(
+            levels(status(ThermalStatus::Moderate)),
+            [WarningLevel::Warning]
+        );
+        assert_eq!(
+            levels(status(ThermalStatus::Severe)),
+            [WarningLevel::Critical]
+        );
+    }
+
+    #[test]
+    fn battery_warnings_only_while_discharging() {
+        let status = |battery, charging| DeviceStatus {
+            battery: Some(battery),
+            charging,
+            ..default()
+        };
+        assert!(levels(status(LOW_BATTERY + 1, false)).is_empty());
+        assert_eq!(levels(status(LOW_BATTERY, false)), [WarningLevel::Warning]);
+        assert_eq!(
+            levels(status(CRITICAL_BATTERY, false)),
+            [WarningLevel::Critical]
+        );
+        assert!(levels(status(CRITICAL_BATTERY, true)).is_empty());
+    }
+
+    #[test]
+    fn most_severe_warning_comes_first() {
+        let status = DeviceStatus {
+            battery: Some(CRITICAL_BATTERY),
+            charging: false,
+            thermal: Some(ThermalStatus::Moderate),
+        };
+        assert_eq!(
+            levels(status),
+            [WarningLevel::Critical, WarningLevel::Warning]
+        );
+        let warnings = status.warnings(Language::English);
+        assert_eq!(warnings[0].1, "Battery 10%");
+        assert_eq!(warnings[1].1, "Headset hot, throttling (Moderate)");
+    }
+}
@@ -0,0 +1,79 @@
+use crate::interaction::picking::{XrPointer, field_hit};
+use crate::panels::XrPanelSpawner;
+use bevy::color::palettes::tailwind::*;
+use bevy::picking::pointer::PointerId;
+use bevy::prelude::*;
+use sslgame::{Field, FieldGeometry, field_to_local};
+
+/// Height of the tag above the hit point
+const TAG_OFFSET: f32 = 0.06;
+
+pub fn xr_coordinates_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (spawn_coordinate_tags, update_coordinate_tags).chain(),
+    );
+}
+
+/// Shows the field coordinates under a pointer. The tag is the display mesh, the text is referenced separately.
+#[derive(Component, Debug)]
+struct CoordinateTag {
+    pointer: Entity,
+    text: Entity,
+}
+
+fn spawn_coordinate_tags(
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    q_pointers: Query<Entity, Added<XrPointer>>,
+) {
+    for pointer in &q_pointers {
+        let mut text = Entity::PLACEHOLDER;
+        let display = panel_spawner.spawn_panel(
+            &mut commands,
+            Transform::from_scale(Vec3::new(0.12, 0.025, 1.)),
+            ZINC_800.into(),
+            |parent| {
+                text = parent
+                    .spawn((Text::default(), TextFont::from_font_size(1.2)))
+                    .id();
+            },
+        );
+        commands
+            .entity(display)
+            .insert((CoordinateTag { pointer, text }, Visibility::Hidden));
+    }
+}
+
+fn update_coordinate_tags(
+    xr_pointers: Query<(&XrPointer, &PointerId)>,
+    q_fields: Query<(&FieldGeometry, &GlobalTransform, Entity), With<Field>>,
+    mut q_tags: Query<(&CoordinateTag, &mut Transform, &mut Visibility)>,
+    mut q_texts: Query<&mut Text>,
+) {
+    for (tag, mut transform, mut visibility) in &mut q_tags {
+        let hit = xr_pointers
+            .get(tag.pointer)
+            .ok()
+            .and_then(|(pointer, _)| Some((pointer, field_hit(pointer, &q_fields)?)));
+        let Some((pointer, (field, position))) = hit else {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        };
+        let Ok((_, field_transform, _)) = q_fields.get(field) else {
+            continue;
+        };
+        visibility.set_if_neq(Visibility::Inherited);
+
+        let hit_point = field_transform.transform_point(field_to_local(position));
+        transform.translation = hit_point + Vec3::Y * TAG_OFFSET;
+        transform.look_at(pointer.ray.origin, Vec3::Y);
+
+        if let Ok(mut text) = q_texts.get_mut(tag.text) {
+            let new_text = format!("x {:.3}  y {:.3}", position.x, position.y);
+            if text.0 != new_text {
+                text.0 = new_text;
+            }
+        }
+    }
+}
@@ -0,0 +1,195 @@
+use bevy::prelude::*;
+use bevy_mod_openxr::resources::OxrEnabledExtensions;
+use bevy_mod_xr::hands::{HandBone, XrHandBoneEntities, XrHandBoneRadius};
+use sslgame::DepthMaskMaterial;
+
+/// Joints are a bit larger than the tracked radius, since the tracking lags behind fast hands
+const INFLATE: f32 = 1.2;
+
+/// Bones connected by the finger links, from the wrist to the tip
+const FINGERS: [&[HandBone]; 5] = [
+    &[
+        HandBone::Wrist,
+        HandBone::ThumbMetacarpal,
+        HandBone::ThumbProximal,
+        HandBone::ThumbDistal,
+        HandBone::ThumbTip,
+    ],
+    &[
+        HandBone::Wrist,
+        HandBone::IndexMetacarpal,
+        HandBone::IndexProximal,
+        HandBone::IndexIntermediate,
+        HandBone::IndexDistal,
+        HandBone::IndexTip,
+    ],
+    &[
+        HandBone::Wrist,
+        HandBone::MiddleMetacarpal,
+        HandBone::MiddleProximal,
+        HandBone::MiddleIntermediate,
+        HandBone::MiddleDistal,
+        HandBone::MiddleTip,
+    ],
+    &[
+        HandBone::Wrist,
+        HandBone::RingMetacarpal,
+        HandBone::RingProximal,
+        HandBone::RingIntermediate,
+        HandBone::RingDistal,
+        HandBone::RingTip,
+    ],
+    &[
+        HandBone::Wrist,
+        HandBone::LittleMetacarpal,
+        HandBone::LittleProximal,
+        HandBone::LittleIntermediate,
+        HandBone::LittleDistal,
+        HandBone::LittleTip,
+    ],
+];
+
+pub fn xr_hand_occlusion_plugin(app: &mut App) {
+    app.init_resource::<HandOcclusion>();
+    app.add_systems(Startup, setup_occluder_assets);
+    app.add_systems(
+        Update,
+        (
+            spawn_hand_occluders,
+            show_hand_occluders,
+            update_hand_occluders.run_if(|occlusion: Res<HandOcclusion>| occlusion.enabled),
+        )
+            .chain()
+            .run_if(passthrough_enabled),
+    );
+}
+
+/// Coarse occlusion of virtual content by the real hands. Every occluder is drawn in the depth prepass,
+/// so it can be turned off when the frame time is tight.
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HandOcclusion {
+    pub enabled: bool,
+}
+
+impl Default for HandOcclusion {
+    fn default() -> Self {
+        Self { enabled: true }
+    }
+}
+
+/// Holes in the virtual content are only filled by the real hands with passthrough
+fn passthrough_enabled(extensions: Option<Res<OxrEnabledExtensions>>) -> bool {
+    extensions.is_some_and(|extensions| extensions.fb_passthrough)
+}
+
+/// Unit sphere and cylinder that only write depth, so the real hands in the passthrough occlude virtual content
+#[derive(Resource, Debug)]
+struct OccluderAssets {
+    joint: Handle<Mesh>,
+    link: Handle<Mesh>,
+    material: Handle<DepthMaskMaterial>,
+}
+
+/// Depth-only shape of a hand joint, or of the link to the next joint of the finger.
+/// Children of the first bone, so they move with the tracking.
+#[derive(Component, Debug)]
+struct HandOccluder {
+    bone: Entity,
+    next_bone: Option<Entity>,
+}
+
+fn setup_occluder_assets(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<DepthMaskMaterial>>,
+) {
+    commands.insert_resource(OccluderAssets {
+        joint: meshes.add(Sphere::new(1.0).mesh().ico(2).unwrap()),
+        link: meshes.add(Cylinder::new(1.0, 1.0)),
+        material: materials.add(DepthMaskMaterial {}),
+    });
+}
+
+fn spawn_hand_occluders(
+    mut commands: Commands,
+    assets: Res<OccluderAssets>,
+    q_hands: Query<&XrHandBoneEntities, Added<XrHandBoneEntities>>,
+) {
+    for bones in &q_hands {
+        for &bone in &bones.0 {
+            commands.entity(bone).with_child((
+                HandOccluder {
+                    bone,
+                    next_bone: None,
+                },
+                Mesh3d(assets.joint.clone()),
+                MeshMaterial3d(assets.material.clone()),
+            ));
+        }
+        for finger in FINGERS {
+            for pair in finger.windows(2) {
+                let bone = bones.0[pair[0] as usize];
+                commands.entity(bone).with_child((
+                    HandOccluder {
+                        bone,
+                        next_bone: Some(bones.0[pair[1] as usize]),
+                    },
+                    Mesh3d(assets.link.clone()),
+                    MeshMaterial3d(assets.material.clone()),
+                ));
+            }
+        }
+    }
+}
+
+/// Hides all occluders while the occlusion is off, links of overlapping bones are hidden again by [`update_hand_occluders`]
+fn show_hand_occluders(
+    occlusion: Res<HandOcclusion>,
+    mut q_occluders: Query<(&mut Visibility, Ref<HandOccluder>)>,
+) {
+    let visibility = if occlusion.enabled {
+        Visibility::Inherited
+    } else {
+        Visibility::Hidden
+    };
+    for (mut occluder_visibility, occluder) in &mut q_occluders {
+        if occlusion.is_changed() || occluder.is_added() {
+            occluder_visibility.set_if_neq(visibility);
+        }
+    }
+}
+
+/// Joints and links follow the tracked radii and bone positions
+fn update_hand_occluders(
+    mut q_occluders: Query<(&HandOccluder, &mut Transform, &mut Visibility)>,
+    q_bones: Query<(&Transform, &XrHandBoneRadius), Without<HandOccluder>>,
+) {
+    for (occluder, mut transform, mut visibility) in &mut q_occluders {
+        let Ok((bone_transform, bone_radius)) = q_bones.get(occluder.bone) else {
+            continue;
+        };
+        let Some(next_bone) = occluder.next_bone else {
+            transform.set_if_neq(Transform::from_scale(Vec3::splat(bone_radius.0 * INFLATE)));
+            continue;
+        };
+        let Ok((next_transform, next_radius)) = q_bones.get(next_bone) else {
+            continue;
+        };
+
+        // Both bones share their parent, so the offset is rotated into the space of the first one
+        let offset = bone_transform.rotation.inverse()
+            * (next_transform.translation - bone_transform.translation);
+        let length = offset.length();
+        if length <= f32::EPSILON {
+            visibility.set_if_neq(Visibility::Hidden);
+            continue;
+        }
+        visibility.set_if_neq(Visibility::Inherited);
+        let radius = bone_radius.0.min(next_radius.0) * INFLATE;
+        transform.set_if_neq(Transform {
+            translation: offset / 2.0,
+            rotation: Quat::from_rotation_arc(Vec3::Y, offset / length),
+            scale: Vec3::new(radius, length, radius),
+        });
+    }
+}
@@ -0,0 +1,119 @@
+use crate::interaction::picking::{LEFT_HAND_POINTER_ID, XrPointer, field_hit};
+use crate::panels::XrPanelSpawner;
+use bevy::color::palettes::tailwind::*;
+use bevy::picking::pointer::PointerId;
+use bevy::prelude::*;
+use sslgame::{Field, FieldGeometry, Measurement, field_to_local};
+use std::f32::consts::PI;
+
+pub fn xr_measurement_plugin(app: &mut App) {
+    app.init_resource::<MeasurementMode>();
+    app.add_systems(
+        Update,
+        (place_measurement_points, draw_measurement_preview).chain(),
+    );
+    app.add_systems(Update, spawn_measurement_labels);
+}
+
+/// While active, the left hand trigger marks the start and end of a measurement on a field instead of dragging robots
+#[derive(Resource, Debug, Default)]
+pub struct MeasurementMode {
+    pub active: bool,
+    /// Field and start point of the measurement that is being placed
+    start: Option<(Entity, Vec2)>,
+    /// Whether the trigger was pressed in the last frame, points are placed on press
+    trigger_pressed: bool,
+}
+
+impl MeasurementMode {
+    pub fn toggle(&mut self) {
+        self.active = !self.active;
+        self.start = None;
+    }
+}
+
+fn left_pointer<'a>(xr_pointers: &'a Query<(&XrPointer, &PointerId)>) -> Option<&'a XrPointer> {
+    xr_pointers
+        .iter()
+        .find(|(_, id)| **id == LEFT_HAND_POINTER_ID)
+        .map(|(pointer, _)| pointer)
+}
+
+fn place_measurement_points(
+    mut commands: Commands,
+    mut mode: ResMut<MeasurementMode>,
+    xr_pointers: Query<(&XrPointer, &PointerId)>,
+    q_fields: Query<(&FieldGeometry, &GlobalTransform, Entity), With<Field>>,
+) {
+    let Some(pointer) = left_pointer(&xr_pointers) else {
+        return;
+    };
+    let just_pressed = pointer.trigger_pressed && !mode.trigger_pressed;
+    mode.trigger_pressed = pointer.trigger_pressed;
+    if !mode.active || !just_pressed {
+        return;
+    }
+    let Some((field, position)) = field_hit(pointer, &q_fields) else {
+        return;
+    };
+
+    match mode.start.take() {
+        Some((start_field, start)) if start_field == field => {
+            commands
+                .entity(field)
+                .with_child(Measurement::new(start, position));
+        }
+        // The first point or a point on another field starts a new measurement
+        _ => mode.start = Some((field, position)),
+    }
+}
+
+fn draw_measurement_preview(
+    mut gizmos: Gizmos,
+    mode: Res<MeasurementMode>,
+    xr_pointers: Query<(&XrPointer, &PointerId)>,
+    q_fields: Query<(&FieldGeometry, &GlobalTransform, Entity), With<Field>>,
+) {
+    let Some((field, start)) = mode.start else {
+        return;
+    };
+    if let Some(pointer) = left_pointer(&xr_pointers)
+        && let Some((hit_field, end)) = field_hit(pointer, &q_fields)
+        && hit_field == field
+        && let Ok((_, field_transform, _)) = q_fields.get(field)
+    {
+        gizmos.line(
+            field_transform.transform_point(field_to_local(start)),
+            field_transform.transform_point(field_to_local(end)),
+            Color::srgba(1.0, 0.4, 0.9, 0.5),
+        );
+    }
+}
+
+/// Measurements are static, so their label panel only has to be built once
+fn spawn_measurement_labels(
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    q_measurements: Query<(&Measurement, &ChildOf, Entity), Added<Measurement>>,
+    q_fields: Query<&FieldGeometry>,
+) {
+    for (measurement, child_of, entity) in &q_measurements {
+        let Ok(geom) = q_fields.get(child_of.parent()) else {
+            continue;
+        };
+        let label = measurement.label(geom);
+        let display = panel_spawner.spawn_panel(
+            &mut commands,
+            Transform {
+                translation: Vec3::new(0., 0.1, 0.),
+                rotation: Quat::from_rotation_x(-PI / 4.),
+                scale: Vec3::new(0.4, 0.06, 1.),
+            },
+            ZINC_800.into(),
+            move |parent| {
+                parent.spawn((Text::new(label), TextFont::from_font_size(3.)));
+            },
+        );
+        commands.entity(entity).add_child(display);
+    }
+}
@@ -1,7 +1,17 @@
+pub mod coordinates;
+pub mod hand_occlusion;
 pub mod input;
+pub mod measurement;
 pub mod picking;
+pub mod pointer_ray;
+pub mod solo_field;
 
 pub fn interaction_plugins(app: &mut bevy::prelude::App) {
     app.add_plugins(input::xr_input_plugin);
     app.add_plugins(picking::xr_picking_plugin);
+    app.add_plugins(pointer_ray::xr_pointer_ray_plugin);
+    app.add_plugins(measurement::xr_measurement_plugin);
+    app.add_plugins(coordinates::xr_coordinates_plugin);
+    app.add_plugins(hand_occlusion::xr_hand_occlusion_plugin);
+    app.add_plugins(solo_field::xr_solo_field_plugin);
 }
@@ -1,4 +1,5 @@
 use crate::interaction::input::{LeftHandPointer, PointerActions, RightHandPointer};
+use crate::interaction::measurement::MeasurementMode;
 use crate::panels::{XrPanel, XrUiRoot};
 use bevy::app::App;
 use bevy::asset::uuid::Uuid;
@@ -15,7 +16,7 @@ use sslgame::{Field, FieldGeometry, Robot, Team};
 use std::ops::Range;
 use std::time::Instant;
 
-const LEFT_HAND_POINTER_ID: PointerId = PointerId::Custom(Uuid::from_u128(10101010));
+pub(crate) const LEFT_HAND_POINTER_ID: PointerId = PointerId::Custom(Uuid::from_u128(10101010));
 const RIGHT_HAND_POINTER_ID: PointerId = PointerId::Custom(Uuid::from_u128(20202020));
 
 pub fn xr_picking_plugin(app: &mut App) {
@@ -26,32 +27,40 @@ pub fn xr_picking_plugin(app: &mut App) {
             .chain()
             .in_set(PickingSystems::Input),
     );
-    app.add_systems(Update, drive_field_dragging);
+    // The left hand places measurement points instead while measuring
+    app.add_systems(
+        Update,
+        drive_field_dragging.run_if(|mode: Res<MeasurementMode>| !mode.active),
+    );
 
     app.register_required_components_with::<LeftHandPointer, _>(|| LEFT_HAND_POINTER_ID);
     app.register_required_components_with::<LeftHandPointer, _>(|| XrPointer {
         ray: Ray3d::new(Vec3::ZERO, Dir3::NEG_Z),
         range: 0.0..10.0,
         trigger_pressed: false,
+        ui_hit_depth: None,
     });
     app.register_required_components_with::<RightHandPointer, _>(|| RIGHT_HAND_POINTER_ID);
     app.register_required_components_with::<RightHandPointer, _>(|| XrPointer {
         ray: Ray3d::new(Vec3::ZERO, Dir3::NEG_Z),
         range: 0.0..10.0,
         trigger_pressed: false,
+        ui_hit_depth: None,
     });
 }
 
 #[derive(Component)]
 pub struct XrPointer {
-    ray: Ray3d,
+    pub(crate) ray: Ray3d,
     range: Range<f32>,
-    trigger_pressed: bool,
+    pub(crate) trigger_pressed: bool,
+    /// Distance to the panel the pointer is on, if any
+    pub(crate) ui_hit_depth: Option<f32>,
 }
 
 pub struct XrSurfaceHit {
-    pos: Vec2,
-    depth: f32,
+    pub(crate) pos: Vec2,
+    pub(crate) depth: f32,
     in_bounds: bool,
     in_range: bool,
 }
@@ -90,7 +99,6 @@ impl XrPointer {
 }
 
 pub fn update_hand_pointer_rays(
-    mut gizmos: Gizmos,
     pointer_actions: Res<PointerActions>,
     action_values: Query<&BoolActionValue>,
     pointers: Query<(&mut XrPointer, &PointerId, &GlobalTransform)>,
@@ -108,22 +116,14 @@ pub fn update_hand_pointer_rays(
             origin: transform.translation(),
             direction: transform.forward(),
         };
-
-        let trigger_float = trigger_bool as u8 as f32;
-        gizmos.line(
-            xr_pointer.ray.origin,
-            xr_pointer.ray.get_point(1.),
-            Color::srgb(1. - trigger_float, 1. - trigger_float, 1.),
-        );
     }
 }
 
 /// Forwards pointer events from openxr to virtual pointers on the UI panels
 #[allow(clippy::too_many_arguments)]
 pub fn drive_ui_pointers(
-    mut gizmos: Gizmos,
     // Pointers
-    pointers: Query<(&XrPointer, &PointerId, &PointerLocation, &PointerPress)>,
+    pointers: Query<(&mut XrPointer, &PointerId, &PointerLocation, &PointerPress)>,
     // Panels
     panels: Query<&GlobalTransform, With<XrPanel>>,
     ui_roots: Query<(&UiTargetCamera, &XrUiRoot)>,
@@ -137,7 +137,7 @@ pub fn drive_ui_pointers(
         depth: f32,
     }
 
-    for (xr_pointer, pointer_id, prev_pointer_loc, pointer_press) in pointers {
+    for (mut xr_pointer, pointer_id, prev_pointer_loc, pointer_press) in pointers {
         let mut hits: Vec<PanelHit> = Vec::new();
 
         // Collect pointer hits by looking up the 3d panel for each UI root.
@@ -212,8 +212,10 @@ pub fn drive_ui_pointers(
         // Sort hits by distance
         hits.sort_unstable_by(|a, b| a.depth.partial_cmp(&b.depth).unwrap());
 
-        // Get the closest hit
-        let Some(closest_hit) = hits.into_iter().next() else {
+        // Get the closest hit, the ray visual ends there
+        let closest_hit = hits.into_iter().next();
+        xr_pointer.ui_hit_depth = closest_hit.as_ref().map(|hit| hit.depth);
+        let Some(closest_hit) = closest_hit else {
             // Cancel the pointer interaction if there are no hits
             // The location is still set as prev_loc, otherwise the event would be discarded as out-of-bounds before the cancel is processed.
             if let Some(prev_loc) = &prev_pointer_loc.location {
@@ -229,13 +231,6 @@ pub fn drive_ui_pointers(
         // Sending a cancel event when moving to a different panel is not necessary because
         // dragging locks the cursor to one panel and hover state works across panels
 
-        // Draw hit marker
-        gizmos.sphere(
-            xr_pointer.ray.get_point(closest_hit.depth),
-            0.01,
-            Color::WHITE,
-        );
-
         // Get pointer locations
         let pointer_loc = closest_hit.location;
         let prev_pointer_loc = prev_pointer_loc.location.as_ref();
@@ -282,18 +277,25 @@ pub fn drive_ui_pointers(
 #[derive(Component, Debug)]
 pub struct FieldDragAction(PointerId, u8, Team, Instant);
 
-fn field_intersection(
+/// The bounds and the hit position are in field coordinates, like the field geometry.
+/// Only the depth is in world units, since it's compared with other hits along the ray.
+pub(crate) fn field_intersection(
     pointer: &XrPointer,
     field_transform: &GlobalTransform,
     bounds: Vec2,
 ) -> Option<XrSurfaceHit> {
-    let hit = pointer.intersect_plane(
+    let scale = field_transform.scale().max_element();
+    if scale <= 0.0 {
+        return None;
+    }
+    let mut hit = pointer.intersect_plane(
         field_transform.translation(),
         field_transform.up(),
         field_transform.right(),
         field_transform.forward(),
-        bounds,
+        bounds * scale,
     )?;
+    hit.pos /= scale;
 
     if hit.in_bounds && hit.in_range {
         Some(hit)
@@ -302,6 +304,17 @@ fn field_intersection(
     }
 }
 
+/// Returns the first field under the pointer and the position on it in SSL field coordinates, including the boundary
+pub(crate) fn field_hit<'a>(
+    pointer: &XrPointer,
+    fields: impl IntoIterator<Item = (&'a FieldGeometry, &'a GlobalTransform, Entity)>,
+) -> Option<(Entity, Vec2)> {
+    fields.into_iter().find_map(|(geom, transform, entity)| {
+        let bounds = geom.play_area_size + geom.boundary_width * 2.0;
+        field_intersection(pointer, transform, bounds).map(|hit| (entity, hit.pos))
+    })
+}
+
 fn find_hit_robot(
     robots: &Query<(&Robot, &Team, &Transform, &ChildOf)>,
     field_entity: Entity,
@@ -320,7 +333,6 @@ fn find_hit_robot(
 }
 
 pub fn drive_field_dragging(
-    mut gizmos: Gizmos,
     mut commands: Commands,
     xr_pointers: Query<(&XrPointer, &PointerId)>,
     mut fields: Query<(
@@ -346,9 +358,7 @@ pub fn drive_field_dragging(
                     .filter(|(p, _)| p.trigger_pressed)
                     .find(|(_, id)| **id == *pointer_id)
                     .and_then(|(pointer, _)| {
-                        field_intersection(pointer, field_transform, drag_bounds).inspect(|hit| {
-                            gizmos.sphere(pointer.ray.get_point(hit.depth), 0.01, Color::WHITE);
-                        })
+                        field_intersection(pointer, field_transform, drag_bounds)
                     });
 
                 let Some(hit) = hit else {
@@ -374,13 +384,6 @@ pub fn drive_field_dragging(
                     .find_map(|(pointer, pointer_id)| {
                         field_intersection(pointer, field_transform, drag_bounds)
                             .map(|hit| (hit, *pointer_id))
-                            .inspect(|hit| {
-                                gizmos.sphere(
-                                    pointer.ray.get_point(hit.0.depth),
-                                    0.01,
-                                    Color::WHITE,
-                                );
-                            })
                     })
                 else {
                     continue;
@@ -0,0 +1,229 @@
+//! Visible rays for the hand pointers, with a reticle where they hit a panel or field.
+//! The rays fade out towards their end, so they don't cover the passthrough view in AR.
+
+use crate::interaction::picking::{XrPointer, field_intersection};
+use bevy::asset::RenderAssetUsages;
+use bevy::color::palettes::tailwind::*;
+use bevy::mesh::{Indices, PrimitiveTopology};
+use bevy::prelude::*;
+use bevy::transform::TransformSystems;
+use sslgame::{Field, FieldGeometry};
+
+/// Size of the reticle relative to its distance, so it covers the same angle at every depth
+const RETICLE_ANGULAR_SIZE: f32 = 0.012;
+const RETICLE_MIN_RADIUS: f32 = 0.004;
+/// Distance of the reticle in front of the target, so it doesn't z-fight with panels
+const RETICLE_OFFSET: f32 = 0.002;
+
+pub fn xr_pointer_ray_plugin(app: &mut App) {
+    app.init_resource::<PointerRaySettings>();
+    app.add_systems(Startup, setup_pointer_ray_assets);
+    // The ui hits are collected in First, the fields only move in Update
+    app.add_systems(
+        PostUpdate,
+        (spawn_pointer_rays, update_pointer_rays)
+            .chain()
+            .before(TransformSystems::Propagate),
+    );
+}
+
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct PointerRaySettings {
+    /// Length of the ray without a target, rays to a target end at the hit point
+    pub length: f32,
+    pub width: f32,
+    pub color: Color,
+    /// Color while the trigger is pressed
+    pub pressed_color: Color,
+    /// Fades the ray out towards its end, otherwise it is drawn with constant opacity
+    pub fade: bool,
+    /// Only shows the ray while it points at a panel or field
+    pub hide_without_target: bool,
+    /// Marks the hit point on the target
+    pub reticle: bool,
+}
+
+impl Default for PointerRaySettings {
+    fn default() -> Self {
+        Self {
+            length: 1.0,
+            width: 0.002,
+            color: Color::WHITE,
+            pressed_color: SKY_400.into(),
+            fade: true,
+            hide_without_target: true,
+            reticle: true,
+        }
+    }
+}
+
+/// The visible ray of a pointer and its reticle. Both are top-level entities, placed along the pointer ray every frame.
+#[derive(Component, Debug)]
+struct PointerRay {
+    pointer: Entity,
+    reticle: Entity,
+    material: Handle<StandardMaterial>,
+}
+
+#[derive(Resource, Debug)]
+struct PointerRayAssets {
+    faded_mesh: Handle<Mesh>,
+    solid_mesh: Handle<Mesh>,
+    reticle_mesh: Handle<Mesh>,
+}
+
+/// Two crossed quads of unit width along -z from 0 to 1, so the ray is visible from every side.
+/// The alpha runs from `end_alpha` at the far end to 1 at the origin.
+fn ray_mesh(end_alpha: f32) -> Mesh {
+    let mut positions = Vec::new();
+    let mut colors = Vec::new();
+    for side in [Vec3::X, Vec3::Y] {
+        for (z, alpha) in [(0.0, 1.0), (-1.0, end_alpha)] {
+            for offset in [-0.5, 0.5] {
+                positions.push((side * offset + Vec3::Z * z).to_array());
+                colors.push([1.0, 1.0, 1.0, alpha]);
+            }
+        }
+    }
+    Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::default(),
+    )
+    .with_inserted_indices(Indices::U16(vec![0, 1, 2, 1, 3, 2, 4, 5, 6, 5, 7, 6]))
+    .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, positions)
+    .with_inserted_attribute(Mesh::ATTRIBUTE_COLOR, colors)
+}
+
+fn setup_pointer_ray_assets(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>) {
+    commands.insert_resource(PointerRayAssets {
+        faded_mesh: meshes.add(ray_mesh(0.0)),
+        solid_mesh: meshes.add(ray_mesh(1.0)),
+        reticle_mesh: meshes.add(Annulus::new(0.6, 1.0)),
+    });
+}
+
+fn spawn_pointer_rays(
+    mut commands: Commands,
+    assets: Res<PointerRayAssets>,
+    settings: Res<PointerRaySettings>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_pointers: Query<Entity, Added<XrPointer>>,
+) {
+    for pointer in &q_pointers {
+        // Every pointer gets its own material, the color shows its trigger state
+        let material = materials.add(StandardMaterial {
+            alpha_mode: AlphaMode::Blend,
+            unlit: true,
+            cull_mode: None,
+            ..StandardMaterial::from_color(settings.color)
+        });
+        let reticle = commands
+            .spawn((
+                Mesh3d(assets.reticle_mesh.clone()),
+                MeshMaterial3d(material.clone()),
+                Transform::default(),
+                Visibility::Hidden,
+            ))
+            .id();
+        commands.spawn((
+            PointerRay {
+                pointer,
+                reticle,
+                material: material.clone(),
+            },
+            Mesh3d(assets.faded_mesh.clone()),
+            MeshMaterial3d(material),
+            Transform::default(),
+            Visibility::Hidden,
+        ));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_pointer_rays(
+    mut commands: Commands,
+    settings: Res<PointerRaySettings>,
+    assets: Res<PointerRayAssets>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    q_pointers: Query<&XrPointer>,
+    q_fields: Query<(&FieldGeometry, &GlobalTransform), With<Field>>,
+    mut q_rays: Query<(
+        &PointerRay,
+        &mut Mesh3d,
+        &mut Transform,
+        &mut Visibility,
+        Entity,
+    )>,
+    mut q_reticles: Query<(&mut Transform, &mut Visibility), Without<PointerRay>>,
+) {
+    for (ray, mut mesh, mut transform, mut visibility, entity) in &mut q_rays {
+        let Ok(pointer) = q_pointers.get(ray.pointer) else {
+            // The hand went away with its pointer
+            commands.entity(ray.reticle).despawn();
+            commands.entity(entity).despawn();
+            continue;
+        };
+
+        // The closest of the panel picked by the ui pointers and the fields under the ray
+        let target_depth = q_fields
+            .iter()
+            .filter_map(|(geom, field_transform)| {
+                let bounds = geom.play_area_size + geom.boundary_width * 2.0;
+                field_intersection(pointer, field_transform, bounds).map(|hit| hit.depth)
+            })
+            .chain(pointer.ui_hit_depth)
+            .min_by(f32::total_cmp);
+
+        let visible = target_depth.is_some() || !settings.hide_without_target;
+        visibility.set_if_neq(if visible {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        });
+        if let Ok((mut reticle_transform, mut reticle_visibility)) = q_reticles.get_mut(ray.reticle)
+        {
+            match target_depth.filter(|_| settings.reticle) {
+                Some(depth) => {
+                    let radius = (depth * RETICLE_ANGULAR_SIZE).max(RETICLE_MIN_RADIUS);
+                    *reticle_transform =
+                        Transform::from_translation(pointer.ray.get_point(depth - RETICLE_OFFSET))
+                            .looking_to(pointer.ray.direction, Vec3::Y)
+                            .with_scale(Vec3::splat(radius));
+                    reticle_visibility.set_if_neq(Visibility::Inherited);
+                }
+                None => {
+                    reticle_visibility.set_if_neq(Visibility::Hidden);
+                }
+            }
+        }
+        if !visible {
+            continue;
+        }
+
+        let length = target_depth.unwrap_or(settings.length);
+        *transform = Transform::from_translation(pointer.ray.origin)
+            .looking_to(pointer.ray.direction, Vec3::Y)
+            .with_scale(Vec3::new(settings.width, settings.width, length));
+
+        let ray_mesh = if settings.fade {
+            &assets.faded_mesh
+        } else {
+            &assets.solid_mesh
+        };
+        if mesh.0 != *ray_mesh {
+            mesh.0 = ray_mesh.clone();
+        }
+        let color = if pointer.trigger_pressed {
+            settings.pressed_color
+        } else {
+            settings.color
+        };
+        // Only looked up mutably on a change, since that re-uploads the material
+        if let Some(material) = materials.get(&ray.material)
+            && material.base_color != color
+            && let Some(material) = materials.get_mut(&ray.material)
+        {
+            material.base_color = color;
+        }
+    }
+}
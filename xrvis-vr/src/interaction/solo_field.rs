@@ -0,0 +1,55 @@
+use bevy::prelude::*;
+use bevy_mod_openxr::openxr_session_running;
+use bevy_mod_xr::hands::{HandBone, LeftHand, XrHandBoneEntities, XrHandBoneRadius};
+use sslgame::{Field, SoloField};
+
+pub fn xr_solo_field_plugin(app: &mut App) {
+    app.add_systems(Update, solo_field_gesture.run_if(openxr_session_running));
+}
+
+/// Shows only the next field, or all fields again after the last one
+pub fn cycle_solo_field(
+    _click: On<Pointer<Click>>,
+    mut solo_field: ResMut<SoloField>,
+    q_fields: Query<Entity, With<Field>>,
+) {
+    solo_field.cycle(&q_fields);
+}
+
+/// Pinching the left thumb and middle finger cycles the solo field.
+/// The index finger pinch of the left hand is already used to cycle the render settings.
+fn solo_field_gesture(
+    mut triggered: Local<bool>,
+    mut solo_field: ResMut<SoloField>,
+    left_hand: Option<Single<&XrHandBoneEntities, With<LeftHand>>>,
+    q_bones: Query<(&XrHandBoneRadius, &Transform)>,
+    q_fields: Query<Entity, With<Field>>,
+) {
+    let Some(bones) = left_hand else {
+        return;
+    };
+    let Ok(
+        [
+            (middle_radius, middle_transform),
+            (thumb_radius, thumb_transform),
+        ],
+    ) = q_bones.get_many([
+        bones.0[HandBone::MiddleTip as usize],
+        bones.0[HandBone::ThumbTip as usize],
+    ])
+    else {
+        return;
+    };
+
+    let distance = thumb_transform
+        .translation
+        .distance(middle_transform.translation);
+    let touch_distance = thumb_radius.0 + middle_radius.0;
+    // Releasing needs a larger distance, so a pinch right at the threshold doesn't trigger repeatedly
+    if !*triggered && distance < touch_distance {
+        solo_field.cycle(&q_fields);
+        *triggered = true;
+    } else if *triggered && distance > touch_distance * 1.5 {
+        *triggered = false;
+    }
+}
@@ -1,7 +1,8 @@
+use crate::comfort::{ComfortSettings, place_field};
 use bevy::prelude::*;
 use bevy_mod_openxr::openxr_session_running;
 use bevy_mod_xr::hands::{HandBone, LeftHand, RightHand, XrHandBoneEntities, XrHandBoneRadius};
-use sslgame::{Field, RenderSettings, RobotRenderSettings};
+use sslgame::{DEFAULT_VIS_CULL_ANGLE, Field, FieldLights, RenderSettings, RobotRenderSettings};
 
 // TODO: Replace this with UI panels and system-level input actions
 
@@ -37,18 +38,33 @@ pub fn insert_left_hand_interaction_state(
                     robots: RobotRenderSettings::Fallback,
                     ball: true,
                     visualizations: true,
+                    telemetry: false,
+                    grid: false,
+                    marker_tags: false,
+                    lights: FieldLights::Soft,
+                    vis_cull_angle: DEFAULT_VIS_CULL_ANGLE,
                 },
                 RenderSettings {
                     field: true,
                     robots: RobotRenderSettings::Fallback,
                     ball: true,
                     visualizations: false,
+                    telemetry: false,
+                    grid: false,
+                    marker_tags: false,
+                    lights: FieldLights::Soft,
+                    vis_cull_angle: DEFAULT_VIS_CULL_ANGLE,
                 },
                 RenderSettings {
                     field: false,
                     robots: RobotRenderSettings::Cutout,
                     ball: false,
                     visualizations: true,
+                    telemetry: false,
+                    grid: false,
+                    marker_tags: false,
+                    lights: FieldLights::Off,
+                    vis_cull_angle: DEFAULT_VIS_CULL_ANGLE,
                 },
             ],
             next_index: 0,
@@ -109,7 +125,8 @@ pub struct RightHandInteractionState {
 fn right_hand_interaction(
     mut gizmos: Gizmos,
     mut commands: Commands,
-    mut field: Option<Single<&mut Transform, With<Field>>>,
+    comfort: Res<ComfortSettings>,
+    mut field: Option<Single<(&mut Transform, Entity), With<Field>>>,
     mut right_hand: Option<
         Single<(
             &RightHand,
@@ -118,9 +135,9 @@ fn right_hand_interaction(
             Entity,
         )>,
     >,
-    q_bones: Query<(&XrHandBoneRadius, &Transform), Without<Field>>,
+    q_bones: Query<(&XrHandBoneRadius, &Transform, &GlobalTransform), Without<Field>>,
 ) {
-    let Some(field_transform) = field.as_deref_mut() else {
+    let Some((field_transform, field)) = field.as_deref_mut() else {
         return;
     };
 
@@ -128,17 +145,19 @@ fn right_hand_interaction(
         return;
     };
 
-    let Ok((index_radius, index_transform)) = q_bones.get(bones.0[HandBone::IndexTip as usize])
+    let Ok((index_radius, index_transform, _)) = q_bones.get(bones.0[HandBone::IndexTip as usize])
     else {
         return;
     };
 
-    let Ok((thumb_radius, thumb_transform)) = q_bones.get(bones.0[HandBone::ThumbTip as usize])
+    let Ok((thumb_radius, thumb_transform, thumb_global)) =
+        q_bones.get(bones.0[HandBone::ThumbTip as usize])
     else {
         return;
     };
 
-    let finger_pos = thumb_transform.translation;
+    // The tracking root is lowered while seated, see crate::play_space
+    let finger_pos = thumb_global.translation();
 
     if let Some(state) = state {
         gizmos.line(state.start_finger_pos, finger_pos, Color::WHITE);
@@ -150,7 +169,7 @@ fn right_hand_interaction(
             // Interaction finished -> Check results
             if state.start_finger_pos.distance(finger_pos) > 1. {
                 // Only accept interaction with >1m of distance
-                field_transform.translation = state.start_finger_pos;
+                let mut target = field_transform.with_translation(state.start_finger_pos);
                 let mut dir = finger_pos - state.start_finger_pos;
                 dir.y = 0.0;
                 if dir.length_squared() > 1e-6 {
@@ -160,8 +179,9 @@ fn right_hand_interaction(
                     // Angle around Y axis between -Z and `dir`:
                     let yaw = f32::atan2(dir.x, dir.z);
                     // Rotate around Y by -yaw so that -Z ends up pointing along `dir`.
-                    field_transform.rotation = Quat::from_rotation_y(yaw);
+                    target.rotation = Quat::from_rotation_y(yaw);
                 }
+                place_field(&mut commands, &comfort, *field, field_transform, target);
             }
             commands.entity(*hand).remove::<RightHandInteractionState>();
         }
@@ -169,7 +189,7 @@ fn right_hand_interaction(
         .translation
         .distance(index_transform.translation)
         < thumb_radius.0 + index_radius.0
-        && thumb_transform.translation.y < 0.5
+        && thumb_global.translation().y < 0.5
     {
         // Start interaction
         commands.entity(*hand).insert(RightHandInteractionState {
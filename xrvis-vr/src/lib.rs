@@ -1,3 +1,5 @@
+use crate::session::SessionRestored;
+use crate::sharing::ReceivedSnapshot;
 use bevy::core_pipeline::prepass::DepthPrepass;
 use bevy::prelude::*;
 use bevy::render::pipelined_rendering::PipelinedRenderingPlugin;
@@ -6,16 +8,23 @@ use bevy_mod_openxr::add_xr_plugins;
 use bevy_mod_openxr::exts::OxrExtensions;
 use bevy_mod_openxr::features::fb_passthrough::OxrFbPassthroughPlugin;
 use bevy_mod_openxr::init::OxrInitPlugin;
-use bevy_mod_openxr::resources::OxrSessionConfig;
+use bevy_mod_openxr::resources::{OxrFrameState, OxrSessionConfig};
 use bevy_mod_openxr::types::EnvironmentBlendMode;
 use sslgame::proto::remote::VisualizationFilter;
 use sslgame::{
-    AvailableHosts, AvailableVisualizations, Field, SelectedVisualizations, ssl_game_plugin,
+    AvailableHosts, AvailableVisualizations, ClientKey, Field, RestoredField,
+    SelectedVisualizations, WorldStateSampling, assets_loaded, ssl_game_plugin,
 };
+use std::time::{Duration, Instant};
 
+mod comfort;
+mod device_status;
 mod interaction;
 mod interaction_old;
 pub mod panels;
+mod play_space;
+mod session;
+mod sharing;
 
 #[bevy_main]
 pub fn main() -> AppExit {
@@ -50,11 +59,19 @@ pub fn main() -> AppExit {
 
     // App setup
     app.add_plugins(ssl_game_plugin)
+        // Sampling at the display time only moves anything between packets while interpolating
+        .insert_resource(WorldStateSampling {
+            interpolate: true,
+            display_time: None,
+        })
+        .add_systems(Startup, session::load_preshared_key)
+        .add_systems(Update, predict_display_time)
         .add_systems(
             Update,
+            // Restored fields keep the vis selection from their session
             |mut q_fields: Query<
                 (&AvailableVisualizations, &mut SelectedVisualizations),
-                Changed<AvailableVisualizations>,
+                (Changed<AvailableVisualizations>, Without<RestoredField>),
             >| {
                 for (available, mut selected) in q_fields.iter_mut() {
                     let new_filter = VisualizationFilter {
@@ -73,15 +90,41 @@ pub fn main() -> AppExit {
                 }
             },
         )
+        .add_plugins(comfort::comfort_plugin)
+        .add_plugins(play_space::play_space_plugin)
+        .add_plugins(device_status::device_status_plugin)
         .add_plugins(interaction_old::old_interaction_plugin)
         .add_plugins(interaction::interaction_plugins)
         .add_plugins(panels::xr_panel_plugin)
         .add_plugins(panels::game_state::game_state_panel_plugin)
+        .add_plugins(panels::debug_values::debug_values_panel_plugin)
+        .add_plugins(panels::plots::plots_panel_plugin)
+        .add_plugins(panels::clock::clock_panel_plugin)
+        .add_plugins(panels::self_test::self_test_panel_plugin)
+        .add_plugins(panels::network::network_panel_plugin)
+        .add_plugins(panels::fields::fields_panel_plugin)
+        .add_plugins(panels::passthrough::passthrough_panel_plugin)
+        .add_plugins(panels::comfort::comfort_panel_plugin)
+        .add_plugins(panels::performance::performance_panel_plugin)
+        .add_plugins(panels::device_warnings::device_warnings_panel_plugin)
+        .add_plugins(sharing::sharing_plugin)
         .add_systems(Startup, setup)
         .add_systems(Update, modify_cameras)
         .add_systems(
             Update,
-            spawn_new_hosts.run_if(resource_changed::<AvailableHosts>),
+            // Host changes while the robot models load are still detected afterwards,
+            // since the change condition isn't evaluated before
+            spawn_new_hosts.run_if(assets_loaded.and(
+                resource_changed::<AvailableHosts>.and(not(resource_exists::<SessionRestored>)),
+            )),
+        )
+        .add_systems(
+            Update,
+            spawn_demo_field.run_if(
+                assets_loaded
+                    .and(run_once)
+                    .and(not(resource_exists::<SessionRestored>)),
+            ),
         )
         .insert_resource(GlobalAmbientLight {
             color: Default::default(),
@@ -92,6 +135,40 @@ pub fn main() -> AppExit {
     app.run()
 }
 
+/// Samples the world state at the time the frame is shown on the display instead of when it is simulated
+fn predict_display_time(
+    frame_state: Option<Res<OxrFrameState>>,
+    mut sampling: ResMut<WorldStateSampling>,
+) {
+    sampling.display_time = frame_state.map(|frame_state| {
+        let lead = display_time_lead(frame_state.predicted_display_time.as_nanos())
+            // One frame ahead is close enough on runtimes with a different clock
+            .unwrap_or_else(|| frame_state.predicted_display_period.as_nanos());
+        Instant::now() + Duration::from_nanos(lead.max(0) as u64)
+    });
+}
+
+/// Time from now until the predicted display time, in ns.
+/// On android, XrTime is the monotonic clock, so the runtime's prediction can be used directly.
+#[cfg(target_os = "android")]
+fn display_time_lead(predicted_display_time: i64) -> Option<i64> {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `now` is a valid timespec to write to
+    if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) } != 0 {
+        return None;
+    }
+    let now = now.tv_sec as i64 * 1_000_000_000 + now.tv_nsec as i64;
+    Some(predicted_display_time - now)
+}
+
+#[cfg(not(target_os = "android"))]
+fn display_time_lead(_predicted_display_time: i64) -> Option<i64> {
+    None
+}
+
 #[derive(Component)]
 struct CameraModified;
 
@@ -115,7 +192,9 @@ fn modify_cameras(
 fn spawn_new_hosts(
     mut commands: Commands,
     available_hosts: Res<AvailableHosts>,
-    q_spawned_field: Option<Single<(&Field, Entity)>>,
+    client_key: Res<ClientKey>,
+    // Received snapshots are shown next to the live field
+    q_spawned_field: Option<Single<(&Field, Entity), Without<ReceivedSnapshot>>>,
 ) {
     let new_hosts = &available_hosts.0;
 
@@ -128,19 +207,36 @@ fn spawn_new_hosts(
                     .any(|h| field.host.websocket_addr == h.websocket_addr) =>
             {
                 commands.entity(*entity).despawn();
-                commands.spawn((Field::bind((*new_host).clone()), Transform::IDENTITY));
+                commands.spawn((
+                    Field::bind((*new_host).clone(), client_key.0.as_ref()),
+                    Transform::IDENTITY,
+                ));
             }
             // Spawn a new field if there isn't one currently spawned
             None => {
-                commands.spawn((Field::bind(new_host.clone()), Transform::IDENTITY));
+                commands.spawn((
+                    Field::bind(new_host.clone(), client_key.0.as_ref()),
+                    Transform::IDENTITY,
+                ));
             }
             _ => {}
         }
     }
 }
 
+/// Shows the demo game until a host is discovered, which then replaces it like any other field
+fn spawn_demo_field(
+    mut commands: Commands,
+    available_hosts: Res<AvailableHosts>,
+    q_fields: Query<(), With<Field>>,
+) {
+    if available_hosts.0.is_empty() && q_fields.is_empty() {
+        commands.spawn((Field::demo(), Transform::IDENTITY));
+    }
+}
+
 fn setup(mut commands: Commands, mut gizmo_assets: ResMut<Assets<GizmoAsset>>) {
-    // Origin marker
+    // Origin marker, on the virtual floor which is raised while seated
     let mut asset = GizmoAsset::new();
     asset.sphere(
         Isometry3d::IDENTITY,
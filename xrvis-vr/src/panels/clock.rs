@@ -0,0 +1,244 @@
+use crate::interaction::input::LeftHandPointer;
+use crate::interaction::measurement::MeasurementMode;
+use crate::interaction::solo_field::cycle_solo_field;
+use crate::panels::comfort::toggle_comfort_panel;
+use crate::panels::fields::toggle_fields_panel;
+use crate::panels::network::toggle_network_panel;
+use crate::panels::passthrough::toggle_passthrough_panel;
+use crate::panels::performance::toggle_performance_panel;
+use crate::panels::{Translated, XrPanelSpawner};
+use crate::session::{load_session, save_session};
+use crate::sharing::share_snapshot;
+use bevy::color::palettes::tailwind::*;
+use bevy::prelude::*;
+use sslgame::{
+    Field, FieldClock, Language, Measurement, RenderSettings, format_stage_time, format_wall_clock,
+};
+use std::f32::consts::PI;
+use std::time::{Duration, Instant, SystemTime};
+
+const UPDATE_INTERVAL: Duration = Duration::from_millis(100);
+const FONT_SIZE: f32 = 0.8;
+
+pub fn clock_panel_plugin(app: &mut App) {
+    app.add_systems(Update, (spawn_clock_panel, update_clock_panel));
+}
+
+/// Marks the display mesh of the wrist clock
+#[derive(Component, Debug)]
+struct ClockDisplay;
+
+/// The text node of the wrist clock
+#[derive(Component, Debug)]
+struct ClockText;
+
+/// Toggles the measurement mode
+#[derive(Component, Debug)]
+struct MeasureButton;
+
+/// Removes all measurements
+#[derive(Component, Debug)]
+struct ClearMeasurementsButton;
+
+/// Toggles the ruler grid
+#[derive(Component, Debug)]
+struct GridButton;
+
+/// Toggles the marker tags above the robot cutouts
+#[derive(Component, Debug)]
+struct TagsButton;
+
+pub(crate) fn text_button(text: &'static str) -> impl Bundle {
+    (
+        Node {
+            padding: UiRect::horizontal(px(0.3)),
+            border_radius: BorderRadius::all(px(0.2)),
+            ..default()
+        },
+        BackgroundColor(ZINC_600.into()),
+        children![(
+            Text::new(text),
+            Translated(text),
+            TextFont::from_font_size(FONT_SIZE)
+        )],
+    )
+}
+
+fn spawn_clock_panel(
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    q_hands: Query<Entity, Added<LeftHandPointer>>,
+) {
+    for hand in &q_hands {
+        // Above the back of the left hand, facing the user when the palm points down
+        let display = panel_spawner.spawn_panel(
+            &mut commands,
+            Transform {
+                translation: Vec3::new(0., 0.04, 0.08),
+                rotation: Quat::from_rotation_x(-PI / 3.),
+                scale: Vec3::new(0.12, 0.1, 1.),
+            },
+            ZINC_800.into(),
+            |parent| {
+                parent
+                    .spawn(Node {
+                        width: percent(100),
+                        height: percent(100),
+                        padding: UiRect::all(px(0.3)),
+                        flex_direction: FlexDirection::Column,
+                        justify_content: JustifyContent::SpaceBetween,
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn((
+                            ClockText,
+                            Text::default(),
+                            TextFont::from_font_size(FONT_SIZE),
+                        ));
+                        parent
+                            .spawn(Node {
+                                flex_direction: FlexDirection::Row,
+                                column_gap: px(0.5),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent
+                                    .spawn((text_button("Measure"), MeasureButton))
+                                    .observe(toggle_measurement);
+                                parent
+                                    .spawn((text_button("Clear"), ClearMeasurementsButton))
+                                    .observe(clear_measurements);
+                                parent
+                                    .spawn((text_button("Grid"), GridButton))
+                                    .observe(toggle_grid);
+                                parent.spawn(text_button("Solo")).observe(cycle_solo_field);
+                                parent
+                                    .spawn((text_button("Tags"), TagsButton))
+                                    .observe(toggle_marker_tags);
+                                parent
+                                    .spawn(text_button("Fields"))
+                                    .observe(toggle_fields_panel);
+                            });
+                        parent
+                            .spawn(Node {
+                                flex_direction: FlexDirection::Row,
+                                column_gap: px(0.5),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(text_button("Save")).observe(save_session);
+                                parent.spawn(text_button("Load")).observe(load_session);
+                                parent.spawn(text_button("Share")).observe(share_snapshot);
+                                parent
+                                    .spawn(text_button("Net"))
+                                    .observe(toggle_network_panel);
+                                parent
+                                    .spawn(text_button("Pass"))
+                                    .observe(toggle_passthrough_panel);
+                                parent
+                                    .spawn(text_button("Comfort"))
+                                    .observe(toggle_comfort_panel);
+                                parent
+                                    .spawn(text_button("Perf"))
+                                    .observe(toggle_performance_panel);
+                                // Labelled in the current language, so the button shows the result
+                                parent.spawn(text_button("Language")).observe(
+                                    |_click: On<Pointer<Click>>, mut language: ResMut<Language>| {
+                                        *language = language.next();
+                                    },
+                                );
+                            });
+                    });
+            },
+        );
+        commands.entity(display).insert(ClockDisplay);
+        commands.entity(hand).add_child(display);
+    }
+}
+
+fn toggle_measurement(
+    click: On<Pointer<Click>>,
+    mut mode: ResMut<MeasurementMode>,
+    mut q_buttons: Query<&mut BackgroundColor, With<MeasureButton>>,
+) {
+    mode.toggle();
+    if let Ok(mut background) = q_buttons.get_mut(click.entity) {
+        background.0 = if mode.active { SKY_600 } else { ZINC_600 }.into();
+    }
+}
+
+fn clear_measurements(
+    _click: On<Pointer<Click>>,
+    mut commands: Commands,
+    q_measurements: Query<Entity, With<Measurement>>,
+) {
+    for entity in &q_measurements {
+        commands.entity(entity).despawn();
+    }
+}
+
+fn toggle_grid(
+    click: On<Pointer<Click>>,
+    mut render_settings: ResMut<RenderSettings>,
+    mut q_buttons: Query<&mut BackgroundColor, With<GridButton>>,
+) {
+    render_settings.grid = !render_settings.grid;
+    if let Ok(mut background) = q_buttons.get_mut(click.entity) {
+        background.0 = if render_settings.grid {
+            SKY_600
+        } else {
+            ZINC_600
+        }
+        .into();
+    }
+}
+
+fn toggle_marker_tags(
+    click: On<Pointer<Click>>,
+    mut render_settings: ResMut<RenderSettings>,
+    mut q_buttons: Query<&mut BackgroundColor, With<TagsButton>>,
+) {
+    render_settings.marker_tags = !render_settings.marker_tags;
+    if let Ok(mut background) = q_buttons.get_mut(click.entity) {
+        background.0 = if render_settings.marker_tags {
+            SKY_600
+        } else {
+            ZINC_600
+        }
+        .into();
+    }
+}
+
+fn update_clock_panel(
+    mut last_update: Local<Option<Instant>>,
+    language: Res<Language>,
+    q_fields: Query<(&Field, &FieldClock)>,
+    mut q_texts: Query<(&mut Text, &mut TextColor), With<ClockText>>,
+) {
+    let now = Instant::now();
+    if last_update.is_some_and(|last| now - last < UPDATE_INTERVAL) {
+        return;
+    }
+    *last_update = Some(now);
+
+    let mut text = format_wall_clock(SystemTime::now());
+    let mut any_stale = false;
+    for (field, clock) in &q_fields {
+        let field_name = field.host.display_name();
+        let stage_time = clock
+            .stage_time_left()
+            .map(format_stage_time)
+            .unwrap_or_else(|| "-".to_string());
+        let vision = match clock.world_state_age() {
+            Some(age) => format!("{} ms", age.as_millis()),
+            None => language.tr("no vision").to_string(),
+        };
+        text += &format!("\n{field_name} {stage_time} {vision}");
+        any_stale |= clock.is_stale();
+    }
+
+    for (mut clock_text, mut color) in &mut q_texts {
+        clock_text.0.clone_from(&text);
+        color.0 = if any_stale { RED_400 } else { ZINC_100 }.into();
+    }
+}
@@ -0,0 +1,221 @@
+use crate::comfort::ComfortSettings;
+use crate::interaction::input::LeftHandPointer;
+use crate::panels::clock::text_button;
+use crate::panels::{XrPanelSpawner, translate_texts};
+use crate::play_space::{PlaySpace, Posture};
+use bevy::color::palettes::tailwind::*;
+use bevy::ecs::relationship::RelatedSpawnerCommands;
+use bevy::prelude::*;
+use sslgame::{AccessibilitySettings, Language};
+use std::f32::consts::PI;
+
+pub fn comfort_panel_plugin(app: &mut App) {
+    // The placement and origin buttons show their value after the translated label
+    app.add_systems(Update, update_comfort_panel.after(translate_texts));
+}
+
+/// Comfort, play space and accessibility settings above the wrist clock, toggled with its "Comfort" button
+#[derive(Component, Debug)]
+struct ComfortPanel;
+
+#[derive(Component, Debug)]
+struct VignetteButton;
+
+#[derive(Component, Debug)]
+struct PlacementButton;
+
+#[derive(Component, Debug)]
+struct SeatedButton;
+
+#[derive(Component, Debug)]
+struct OriginButton;
+
+#[derive(Component, Debug)]
+struct ContrastButton;
+
+pub fn toggle_comfort_panel(
+    _click: On<Pointer<Click>>,
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    left_hand: Option<Single<Entity, With<LeftHandPointer>>>,
+    q_panels: Query<Entity, With<ComfortPanel>>,
+) {
+    if !q_panels.is_empty() {
+        for panel in &q_panels {
+            commands.entity(panel).despawn();
+        }
+        return;
+    }
+    let Some(hand) = left_hand else {
+        return;
+    };
+
+    // Above the wrist clock, tilted the same way
+    let panel = panel_spawner.spawn_panel(
+        &mut commands,
+        Transform {
+            translation: Vec3::new(0., 0.095, 0.031),
+            rotation: Quat::from_rotation_x(-PI / 3.),
+            scale: Vec3::new(0.16, 0.06, 1.),
+        },
+        ZINC_800.into(),
+        |parent| {
+            parent
+                .spawn(Node {
+                    width: percent(100),
+                    height: percent(100),
+                    padding: UiRect::all(px(0.3)),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: px(0.5),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent
+                        .spawn(Node {
+                            flex_direction: FlexDirection::Row,
+                            column_gap: px(0.5),
+                            ..default()
+                        })
+                        .with_children(comfort_buttons);
+                    parent
+                        .spawn(Node {
+                            flex_direction: FlexDirection::Row,
+                            column_gap: px(0.5),
+                            ..default()
+                        })
+                        .with_children(accessibility_buttons);
+                });
+        },
+    );
+    commands.entity(panel).insert(ComfortPanel);
+    commands.entity(*hand).add_child(panel);
+}
+
+fn comfort_buttons(parent: &mut RelatedSpawnerCommands<ChildOf>) {
+    parent
+        .spawn((text_button("Vignette"), VignetteButton))
+        .observe(
+            |_click: On<Pointer<Click>>, mut settings: ResMut<ComfortSettings>| {
+                settings.vignette = !settings.vignette;
+            },
+        );
+    parent
+        .spawn((text_button("Placement"), PlacementButton))
+        .observe(
+            |_click: On<Pointer<Click>>, mut settings: ResMut<ComfortSettings>| {
+                settings.placement = settings.placement.next();
+            },
+        );
+    parent.spawn((text_button("Seated"), SeatedButton)).observe(
+        |_click: On<Pointer<Click>>, mut play_space: ResMut<PlaySpace>| {
+            play_space.posture = match play_space.posture {
+                Posture::Standing => Posture::Seated,
+                Posture::Seated => Posture::Standing,
+            };
+        },
+    );
+    parent.spawn((text_button("Origin"), OriginButton)).observe(
+        |_click: On<Pointer<Click>>, mut play_space: ResMut<PlaySpace>| {
+            play_space.origin = play_space.origin.next();
+        },
+    );
+}
+
+/// Scale steps and the contrast toggle, the panels are resized immediately
+fn accessibility_buttons(parent: &mut RelatedSpawnerCommands<ChildOf>) {
+    for (label, steps) in [("UI -", -1), ("UI +", 1)] {
+        parent.spawn(text_button(label)).observe(
+            move |_click: On<Pointer<Click>>, mut settings: ResMut<AccessibilitySettings>| {
+                settings.ui_scale = AccessibilitySettings::step_scale(settings.ui_scale, steps);
+            },
+        );
+    }
+    for (label, steps) in [("Text -", -1), ("Text +", 1)] {
+        parent.spawn(text_button(label)).observe(
+            move |_click: On<Pointer<Click>>, mut settings: ResMut<AccessibilitySettings>| {
+                settings.text_scale = AccessibilitySettings::step_scale(settings.text_scale, steps);
+            },
+        );
+    }
+    parent
+        .spawn((text_button("Contrast"), ContrastButton))
+        .observe(
+            |_click: On<Pointer<Click>>, mut settings: ResMut<AccessibilitySettings>| {
+                settings.high_contrast = !settings.high_contrast;
+            },
+        );
+}
+
+#[allow(clippy::type_complexity)]
+fn update_comfort_panel(
+    settings: Res<ComfortSettings>,
+    play_space: Res<PlaySpace>,
+    accessibility: Res<AccessibilitySettings>,
+    language: Res<Language>,
+    mut q_toggle_buttons: Query<
+        (
+            &mut BackgroundColor,
+            Option<Ref<VignetteButton>>,
+            Option<Ref<SeatedButton>>,
+            Option<Ref<ContrastButton>>,
+        ),
+        Or<(
+            With<VignetteButton>,
+            With<SeatedButton>,
+            With<ContrastButton>,
+        )>,
+    >,
+    q_placement_buttons: Query<(&Children, Ref<PlacementButton>)>,
+    q_origin_buttons: Query<(&Children, Ref<OriginButton>)>,
+    mut q_texts: Query<&mut Text>,
+) {
+    for (mut background, vignette_button, seated_button, contrast_button) in &mut q_toggle_buttons {
+        let active = if let Some(button) = vignette_button {
+            if !settings.is_changed() && !button.is_added() {
+                continue;
+            }
+            settings.vignette
+        } else if let Some(button) = seated_button {
+            if !play_space.is_changed() && !button.is_added() {
+                continue;
+            }
+            play_space.posture == Posture::Seated
+        } else if let Some(button) = contrast_button {
+            if !accessibility.is_changed() && !button.is_added() {
+                continue;
+            }
+            accessibility.high_contrast
+        } else {
+            continue;
+        };
+        background.0 = if active { SKY_600 } else { ZINC_600 }.into();
+    }
+    for (children, button) in &q_origin_buttons {
+        if !play_space.is_changed() && !language.is_changed() && !button.is_added() {
+            continue;
+        }
+        for child in children.iter() {
+            if let Ok(mut text) = q_texts.get_mut(child) {
+                text.0 = format!(
+                    "{}: {}",
+                    language.tr("Origin"),
+                    language.tr(play_space.origin.name())
+                );
+            }
+        }
+    }
+    for (children, button) in &q_placement_buttons {
+        if !settings.is_changed() && !language.is_changed() && !button.is_added() {
+            continue;
+        }
+        for child in children.iter() {
+            if let Ok(mut text) = q_texts.get_mut(child) {
+                text.0 = format!(
+                    "{}: {}",
+                    language.tr("Placement"),
+                    language.tr(settings.placement.name())
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,501 @@
+use crate::panels::{Translated, XrPanelSpawner};
+use bevy::color::palettes::tailwind::*;
+use bevy::prelude::*;
+use sslgame::{DebugNode, DebugTree, DebugValue, FieldGeometry, PlotSource, Plots};
+use std::collections::HashSet;
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::time::{Duration, Instant};
+
+/// Values change with every packet, updating the rows at a lower rate is easier to read
+const REBUILD_INTERVAL: Duration = Duration::from_millis(100);
+/// Rows that fit on one page of the panel
+const MAX_ROWS: usize = 15;
+const ROW_HEIGHT: f32 = 5.5;
+const FONT_SIZE: f32 = 4.;
+
+pub fn debug_values_panel_plugin(app: &mut App) {
+    app.add_systems(Update, manage_debug_values_panels);
+    app.add_systems(Update, update_debug_values_panels);
+}
+
+/// Marks the display mesh of a debug values panel, which is a child of its field
+#[derive(Component, Debug)]
+struct DebugValuesDisplay;
+
+fn panel_transform(field_geom: &FieldGeometry) -> Transform {
+    // Next to the field on the +x side, facing the field center and tilted slightly upwards
+    Transform {
+        translation: Vec3::new(
+            field_geom.play_area_size.x / 2.0 + field_geom.boundary_width + 0.3,
+            0.6,
+            0.,
+        ),
+        rotation: Quat::from_rotation_y(FRAC_PI_2) * Quat::from_rotation_x(-PI / 12.),
+        scale: Vec3::new(0.8, 0.9, 1.),
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn manage_debug_values_panels(
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    (q_fields, mut q_panels): (
+        Query<(Ref<FieldGeometry>, Entity), With<DebugTree>>,
+        Query<(&mut Transform, &ChildOf), With<DebugValuesDisplay>>,
+    ),
+) {
+    for (field_geom, field_entity) in q_fields {
+        let display = q_panels
+            .iter_mut()
+            .find(|(_, c)| c.parent() == field_entity);
+
+        match display {
+            Some((mut display_transform, _)) if field_geom.is_changed() => {
+                *display_transform = panel_transform(&field_geom);
+            }
+            None => {
+                let display = panel_spawner.spawn_panel(
+                    &mut commands,
+                    panel_transform(&field_geom),
+                    Color::srgba(0., 0., 0., 0.),
+                    move |parent| {
+                        parent.spawn(debug_values_panel(field_entity));
+                    },
+                );
+                commands.entity(display).insert(DebugValuesDisplay);
+                commands.entity(field_entity).add_child(display);
+            }
+            _ => {}
+        }
+    }
+}
+
+// ======== Debug Values Panel ========
+
+#[derive(Component, Debug)]
+struct DebugValuesPanel {
+    state_source: Entity,
+    /// Keys of the expanded inner nodes
+    expanded: HashSet<String>,
+    /// Index of the first row on the current page
+    first_row: usize,
+    dirty: bool,
+    last_rebuild: Option<Instant>,
+    /// Rows on the current page with their value text, which is updated in place while the layout stays the same
+    shown: Vec<(DebugRow, Option<Entity>)>,
+    /// Rows over all pages when the current page was spawned
+    row_count: usize,
+}
+
+/// Toggles pinning of the key in the [`DebugTree`] of the panel's source
+#[derive(Component, Debug)]
+struct PinButton {
+    panel: Entity,
+    key: String,
+}
+
+/// Adds or removes the value with the key from the [`Plots`] of the panel's source
+#[derive(Component, Debug)]
+struct PlotButton {
+    panel: Entity,
+    key: String,
+}
+
+/// Expands or collapses the inner node with the key
+#[derive(Component, Debug)]
+struct ExpandButton {
+    panel: Entity,
+    key: String,
+}
+
+/// Moves the panel by one page, backwards or forwards
+#[derive(Component, Debug)]
+struct PageButton {
+    panel: Entity,
+    forward: bool,
+}
+
+fn debug_values_panel(state_source: Entity) -> impl Bundle {
+    (
+        Node {
+            width: percent(100),
+            height: percent(100),
+            padding: UiRect::all(px(4.)),
+            border_radius: BorderRadius::all(px(5.)),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::FlexStart,
+            align_items: AlignItems::Stretch,
+            ..default()
+        },
+        BackgroundColor(ZINC_700.into()),
+        children![
+            (
+                Text::new("Debug values"),
+                Translated("Debug values"),
+                TextFont::from_font_size(7.)
+            ),
+            (
+                DebugValuesPanel {
+                    state_source,
+                    expanded: HashSet::new(),
+                    first_row: 0,
+                    dirty: true,
+                    last_rebuild: None,
+                    shown: Vec::new(),
+                    row_count: 0,
+                },
+                Node {
+                    flex_direction: FlexDirection::Column,
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+            )
+        ],
+    )
+}
+
+#[derive(Debug)]
+struct DebugRow {
+    key: String,
+    label: String,
+    depth: usize,
+    value: Option<String>,
+    /// Whether the row is expanded, if it has children
+    expanded: Option<bool>,
+    pinned: bool,
+    /// Whether the value is plotted, if it can be plotted
+    plotted: Option<bool>,
+}
+
+impl DebugRow {
+    /// Whether both rows have the same buttons and label, so only the value may differ
+    fn same_layout(&self, other: &DebugRow) -> bool {
+        self.key == other.key
+            && self.label == other.label
+            && self.depth == other.depth
+            && self.value.is_some() == other.value.is_some()
+            && self.expanded == other.expanded
+            && self.pinned == other.pinned
+            && self.plotted == other.plotted
+    }
+}
+
+/// Pinned values first, then the expanded parts of the tree
+fn debug_rows(
+    debug_tree: &DebugTree,
+    plots: Option<&Plots>,
+    expanded: &HashSet<String>,
+) -> Vec<DebugRow> {
+    // Text values can't be plotted
+    let plotted = |key: &str, value: Option<&DebugValue>| match (plots, value) {
+        (Some(plots), Some(DebugValue::Number(_) | DebugValue::Flag(_))) => {
+            Some(plots.contains(&PlotSource::DebugValue(key.to_string())))
+        }
+        _ => None,
+    };
+
+    fn tree_rows(
+        node: &DebugNode,
+        depth: usize,
+        debug_tree: &DebugTree,
+        plotted: &dyn Fn(&str, Option<&DebugValue>) -> Option<bool>,
+        expanded: &HashSet<String>,
+        rows: &mut Vec<DebugRow>,
+    ) {
+        for (name, child) in &node.children {
+            let is_expanded = expanded.contains(child.key);
+            rows.push(DebugRow {
+                key: child.key.to_string(),
+                label: name.to_string(),
+                depth,
+                value: child.value.map(|v| v.to_string()),
+                expanded: (!child.children.is_empty()).then_some(is_expanded),
+                pinned: debug_tree.is_pinned(child.key),
+                plotted: plotted(child.key, child.value),
+            });
+            if is_expanded {
+                tree_rows(child, depth + 1, debug_tree, plotted, expanded, rows);
+            }
+        }
+    }
+
+    let mut rows: Vec<_> = debug_tree
+        .pinned()
+        .map(|(key, value)| DebugRow {
+            key: key.to_string(),
+            label: key.to_string(),
+            depth: 0,
+            value: Some(
+                value
+                    .map(|v| v.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+            ),
+            expanded: None,
+            pinned: true,
+            plotted: plotted(key, value),
+        })
+        .collect();
+    tree_rows(
+        &debug_tree.root(),
+        0,
+        debug_tree,
+        &plotted,
+        expanded,
+        &mut rows,
+    );
+    rows
+}
+
+fn update_debug_values_panels(
+    mut commands: Commands,
+    q_trees: Query<(Ref<DebugTree>, Option<&Plots>)>,
+    q_panels: Query<(&mut DebugValuesPanel, Entity)>,
+    mut q_texts: Query<&mut Text>,
+) {
+    let now = Instant::now();
+    for (mut panel, panel_entity) in q_panels {
+        let Ok((debug_tree, plots)) = q_trees.get(panel.state_source) else {
+            continue;
+        };
+        panel.dirty |= debug_tree.is_changed();
+        if !panel.dirty
+            || panel
+                .last_rebuild
+                .is_some_and(|last| now - last < REBUILD_INTERVAL)
+        {
+            continue;
+        }
+        panel.dirty = false;
+        panel.last_rebuild = Some(now);
+
+        let rows = debug_rows(&debug_tree, plots, &panel.expanded);
+        let row_count = rows.len();
+        // Collapsing nodes can leave the current page empty
+        panel.first_row = panel
+            .first_row
+            .min(row_count.saturating_sub(1) / MAX_ROWS * MAX_ROWS);
+        let rows: Vec<_> = rows
+            .into_iter()
+            .skip(panel.first_row)
+            .take(MAX_ROWS)
+            .collect();
+
+        if panel.row_count == row_count
+            && panel.shown.len() == rows.len()
+            && panel
+                .shown
+                .iter()
+                .zip(&rows)
+                .all(|((shown, _), row)| shown.same_layout(row))
+        {
+            for ((_, value_text), row) in panel.shown.iter().zip(&rows) {
+                if let (Some(text_entity), Some(value)) = (value_text, &row.value)
+                    && let Ok(mut text) = q_texts.get_mut(*text_entity)
+                    && text.0 != *value
+                {
+                    text.0.clone_from(value);
+                }
+            }
+            continue;
+        }
+
+        let first_row = panel.first_row;
+        let mut shown = Vec::with_capacity(rows.len());
+        commands
+            .entity(panel_entity)
+            .despawn_related::<Children>()
+            .with_children(|parent| {
+                for row in rows {
+                    let mut value_text = None;
+                    parent
+                        .spawn(Node {
+                            height: px(ROW_HEIGHT),
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            column_gap: px(2.),
+                            padding: UiRect::left(px(row.depth as f32 * 4.)),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            let pin_color = if row.pinned { AMBER_400 } else { ZINC_500 };
+                            parent
+                                .spawn((
+                                    PinButton {
+                                        panel: panel_entity,
+                                        key: row.key.clone(),
+                                    },
+                                    Node {
+                                        width: px(FONT_SIZE),
+                                        height: px(FONT_SIZE),
+                                        border_radius: BorderRadius::all(percent(100.)),
+                                        ..default()
+                                    },
+                                    BackgroundColor(pin_color.into()),
+                                ))
+                                .observe(toggle_pin);
+                            if let Some(plotted) = row.plotted {
+                                let plot_color = if plotted { SKY_400 } else { ZINC_500 };
+                                parent
+                                    .spawn((
+                                        PlotButton {
+                                            panel: panel_entity,
+                                            key: row.key.clone(),
+                                        },
+                                        Node {
+                                            width: px(FONT_SIZE),
+                                            height: px(FONT_SIZE),
+                                            border_radius: BorderRadius::all(px(1.)),
+                                            ..default()
+                                        },
+                                        BackgroundColor(plot_color.into()),
+                                    ))
+                                    .observe(toggle_plot);
+                            }
+                            if let Some(expanded) = row.expanded {
+                                parent
+                                    .spawn((
+                                        ExpandButton {
+                                            panel: panel_entity,
+                                            key: row.key.clone(),
+                                        },
+                                        Text::new(if expanded { "-" } else { "+" }),
+                                        TextFont::from_font_size(FONT_SIZE),
+                                    ))
+                                    .observe(toggle_expanded);
+                            }
+                            parent.spawn((
+                                Text::new(row.label.clone()),
+                                TextFont::from_font_size(FONT_SIZE),
+                            ));
+                            if let Some(value) = &row.value {
+                                value_text = Some(
+                                    parent
+                                        .spawn((
+                                            Text::new(value.clone()),
+                                            TextFont::from_font_size(FONT_SIZE),
+                                            TextColor(SKY_300.into()),
+                                        ))
+                                        .id(),
+                                );
+                            }
+                        });
+                    shown.push((row, value_text));
+                }
+                if row_count > MAX_ROWS {
+                    let last_row = (first_row + MAX_ROWS).min(row_count);
+                    parent
+                        .spawn(Node {
+                            height: px(ROW_HEIGHT),
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            column_gap: px(3.),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for (label, forward) in [("<", false), (">", true)] {
+                                parent
+                                    .spawn((
+                                        PageButton {
+                                            panel: panel_entity,
+                                            forward,
+                                        },
+                                        Text::new(label),
+                                        TextFont::from_font_size(FONT_SIZE),
+                                    ))
+                                    .observe(turn_page);
+                            }
+                            parent.spawn((
+                                Text::new(format!("{}-{last_row} / {row_count}", first_row + 1)),
+                                TextFont::from_font_size(FONT_SIZE),
+                                TextColor(ZINC_400.into()),
+                            ));
+                        });
+                }
+            });
+        panel.shown = shown;
+        panel.row_count = row_count;
+    }
+}
+
+fn turn_page(
+    click: On<Pointer<Click>>,
+    q_buttons: Query<&PageButton>,
+    mut q_panels: Query<&mut DebugValuesPanel>,
+) {
+    let Ok(button) = q_buttons.get(click.entity) else {
+        return;
+    };
+    let Ok(mut panel) = q_panels.get_mut(button.panel) else {
+        return;
+    };
+    // Paging past the last row is clamped with the next update
+    panel.first_row = if button.forward {
+        panel.first_row + MAX_ROWS
+    } else {
+        panel.first_row.saturating_sub(MAX_ROWS)
+    };
+    panel.dirty = true;
+    panel.last_rebuild = None;
+}
+
+fn toggle_pin(
+    click: On<Pointer<Click>>,
+    q_buttons: Query<&PinButton>,
+    mut q_panels: Query<&mut DebugValuesPanel>,
+    mut q_trees: Query<&mut DebugTree>,
+) {
+    let Ok(button) = q_buttons.get(click.entity) else {
+        return;
+    };
+    let Ok(mut panel) = q_panels.get_mut(button.panel) else {
+        return;
+    };
+    if let Ok(mut debug_tree) = q_trees.get_mut(panel.state_source) {
+        let pinned = debug_tree.is_pinned(&button.key);
+        debug_tree.set_pinned(&button.key, !pinned);
+    }
+    panel.dirty = true;
+    panel.last_rebuild = None;
+}
+
+fn toggle_expanded(
+    click: On<Pointer<Click>>,
+    q_buttons: Query<&ExpandButton>,
+    mut q_panels: Query<&mut DebugValuesPanel>,
+) {
+    let Ok(button) = q_buttons.get(click.entity) else {
+        return;
+    };
+    let Ok(mut panel) = q_panels.get_mut(button.panel) else {
+        return;
+    };
+    if !panel.expanded.remove(&button.key) {
+        panel.expanded.insert(button.key.clone());
+    }
+    panel.dirty = true;
+    panel.last_rebuild = None;
+}
+
+fn toggle_plot(
+    click: On<Pointer<Click>>,
+    q_buttons: Query<&PlotButton>,
+    mut q_panels: Query<&mut DebugValuesPanel>,
+    mut q_plots: Query<&mut Plots>,
+) {
+    let Ok(button) = q_buttons.get(click.entity) else {
+        return;
+    };
+    let Ok(mut panel) = q_panels.get_mut(button.panel) else {
+        return;
+    };
+    if let Ok(mut plots) = q_plots.get_mut(panel.state_source) {
+        let source = PlotSource::DebugValue(button.key.clone());
+        if plots.contains(&source) {
+            plots.remove(&source);
+        } else {
+            plots.add(source);
+        }
+    }
+    panel.dirty = true;
+    panel.last_rebuild = None;
+}
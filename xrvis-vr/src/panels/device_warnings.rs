@@ -0,0 +1,84 @@
+use crate::device_status::{DeviceStatus, WarningLevel};
+use crate::interaction::input::LeftHandPointer;
+use crate::panels::XrPanelSpawner;
+use bevy::color::palettes::tailwind::*;
+use bevy::prelude::*;
+use sslgame::Language;
+use std::f32::consts::PI;
+
+const FONT_SIZE: f32 = 0.8;
+
+pub fn device_warnings_panel_plugin(app: &mut App) {
+    app.add_systems(Update, update_device_warnings);
+}
+
+/// Battery and thermal warnings below the wrist clock, only present while there is something to warn about
+#[derive(Component, Debug)]
+struct DeviceWarningsPanel;
+
+#[derive(Component, Debug)]
+struct DeviceWarningsText;
+
+fn update_device_warnings(
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    device_status: Res<DeviceStatus>,
+    language: Res<Language>,
+    q_left_hand: Query<(Entity, Ref<LeftHandPointer>)>,
+    q_panels: Query<Entity, With<DeviceWarningsPanel>>,
+    mut q_texts: Query<(&mut Text, &mut TextColor), With<DeviceWarningsText>>,
+) {
+    let hand_added = q_left_hand.iter().any(|(_, hand)| hand.is_added());
+    if !device_status.is_changed() && !language.is_changed() && !hand_added {
+        return;
+    }
+
+    let warnings = device_status.warnings(*language);
+    if warnings.is_empty() {
+        for panel in &q_panels {
+            commands.entity(panel).despawn();
+        }
+        return;
+    }
+    let text = warnings
+        .iter()
+        .map(|(_, warning)| warning.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    let color = match warnings[0].0 {
+        WarningLevel::Critical => RED_400,
+        WarningLevel::Warning => AMBER_400,
+    };
+
+    if !q_panels.is_empty() {
+        for (mut warnings_text, mut text_color) in &mut q_texts {
+            warnings_text.0.clone_from(&text);
+            text_color.0 = color.into();
+        }
+        return;
+    }
+    let Ok((hand, _)) = q_left_hand.single() else {
+        return;
+    };
+
+    // Below the wrist clock, tilted the same way
+    let panel = panel_spawner.spawn_panel(
+        &mut commands,
+        Transform {
+            translation: Vec3::new(0., 0.008, 0.136),
+            rotation: Quat::from_rotation_x(-PI / 3.),
+            scale: Vec3::new(0.12, 0.025, 1.),
+        },
+        ZINC_800.into(),
+        |parent| {
+            parent.spawn((
+                DeviceWarningsText,
+                Text::new(text),
+                TextFont::from_font_size(FONT_SIZE),
+                TextColor(color.into()),
+            ));
+        },
+    );
+    commands.entity(panel).insert(DeviceWarningsPanel);
+    commands.entity(hand).add_child(panel);
+}
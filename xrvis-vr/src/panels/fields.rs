@@ -0,0 +1,217 @@
+use crate::interaction::input::LeftHandPointer;
+use crate::panels::{Translated, XrPanelSpawner};
+use crate::play_space::move_user_to;
+use bevy::color::palettes::tailwind::*;
+use bevy::prelude::*;
+use bevy_mod_xr::camera::XrCamera;
+use bevy_mod_xr::session::XrTrackingRoot;
+use sslgame::proto::remote::TeamState;
+use sslgame::{Field, FieldClock, FieldGeometry, GameState, Language, format_stage_time};
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+const FONT_SIZE: f32 = 0.6;
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+/// Distance between the user and the touch line after going to a field
+const TOUCH_LINE_DISTANCE: f32 = 0.5;
+
+pub fn fields_panel_plugin(app: &mut App) {
+    app.add_systems(Update, update_fields_panel);
+}
+
+/// Summary of all fields above the wrist clock, toggled with its "Fields" button.
+/// Clicking the row of a field moves the user next to it.
+#[derive(Component, Debug)]
+struct FieldsPanel;
+
+/// Parent of the field rows
+#[derive(Component, Debug)]
+struct FieldRows;
+
+/// Row of a field, moves the user to its touch line
+#[derive(Component, Debug)]
+struct GoToFieldButton(Entity);
+
+pub fn toggle_fields_panel(
+    _click: On<Pointer<Click>>,
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    left_hand: Option<Single<Entity, With<LeftHandPointer>>>,
+    q_panels: Query<Entity, With<FieldsPanel>>,
+) {
+    if !q_panels.is_empty() {
+        for panel in &q_panels {
+            commands.entity(panel).despawn();
+        }
+        return;
+    }
+    let Some(hand) = left_hand else {
+        return;
+    };
+
+    // Above the wrist clock, tilted the same way
+    let panel = panel_spawner.spawn_panel(
+        &mut commands,
+        Transform {
+            translation: Vec3::new(0., 0.12, 0.02),
+            rotation: Quat::from_rotation_x(-PI / 3.),
+            scale: Vec3::new(0.2, 0.1, 1.),
+        },
+        ZINC_800.into(),
+        |parent| {
+            parent
+                .spawn(Node {
+                    width: percent(100),
+                    height: percent(100),
+                    padding: UiRect::all(px(0.3)),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: px(0.3),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        Text::new("Fields"),
+                        Translated("Fields"),
+                        TextFont::from_font_size(FONT_SIZE),
+                    ));
+                    parent.spawn((
+                        FieldRows,
+                        Node {
+                            flex_direction: FlexDirection::Column,
+                            row_gap: px(0.2),
+                            ..default()
+                        },
+                    ));
+                });
+        },
+    );
+    commands.entity(panel).insert(FieldsPanel);
+    commands.entity(*hand).add_child(panel);
+}
+
+/// Teams, score, stage and vision age of a field in one line
+fn field_summary(
+    field: &Field,
+    game_state: &GameState,
+    clock: &FieldClock,
+    language: Language,
+) -> String {
+    let team_name = |team: &Option<TeamState>| {
+        team.as_ref()
+            .and_then(|team| team.name.clone())
+            .unwrap_or_else(|| "-".to_string())
+    };
+    let score = |team: &Option<TeamState>| team.as_ref().map_or(0, |team| team.score());
+    let stage_time = clock
+        .stage_time_left()
+        .map(format_stage_time)
+        .unwrap_or_default();
+    let vision = match clock.world_state_age() {
+        _ if clock.is_stale() => language.tr("no recent vision").to_string(),
+        Some(age) => format!("{} ms", age.as_millis()),
+        None => language.tr("no vision").to_string(),
+    };
+    format!(
+        "{}  {} {} : {} {}  {} {stage_time}  {vision}",
+        field.host.display_name(),
+        team_name(&game_state.yellow_team),
+        score(&game_state.yellow_team),
+        score(&game_state.blue_team),
+        team_name(&game_state.blue_team),
+        language.stage(game_state.game_stage()),
+    )
+}
+
+/// Adds and removes rows with the fields, and updates the texts of the existing rows in place
+fn update_fields_panel(
+    mut commands: Commands,
+    mut last_update: Local<Option<Instant>>,
+    language: Res<Language>,
+    q_fields: Query<(&Field, &GameState, &FieldClock, Entity)>,
+    q_lists: Query<Entity, With<FieldRows>>,
+    mut q_rows: Query<(&GoToFieldButton, &mut Text, &mut TextColor, Entity)>,
+) {
+    let now = Instant::now();
+    if q_lists.is_empty()
+        || (last_update.is_some_and(|last| now - last < UPDATE_INTERVAL) && !language.is_changed())
+    {
+        return;
+    }
+    *last_update = Some(now);
+
+    for (button, .., row) in &q_rows {
+        if !q_fields.contains(button.0) {
+            commands.entity(row).despawn();
+        }
+    }
+    for (field, game_state, clock, field_entity) in &q_fields {
+        let summary = field_summary(field, game_state, clock, *language);
+        let color: Color = if clock.is_stale() { RED_400 } else { ZINC_100 }.into();
+        match q_rows
+            .iter_mut()
+            .find(|(button, ..)| button.0 == field_entity)
+        {
+            Some((_, mut text, mut text_color, _)) => {
+                if text.0 != summary {
+                    text.0 = summary;
+                }
+                text_color.set_if_neq(TextColor(color));
+            }
+            None => {
+                for list in &q_lists {
+                    let row = commands
+                        .spawn((
+                            GoToFieldButton(field_entity),
+                            Text::new(summary.clone()),
+                            TextFont::from_font_size(FONT_SIZE),
+                            TextColor(color),
+                            Node {
+                                padding: UiRect::all(px(0.2)),
+                                border_radius: BorderRadius::all(px(0.2)),
+                                ..default()
+                            },
+                            BackgroundColor(ZINC_600.into()),
+                        ))
+                        .observe(go_to_field)
+                        .id();
+                    commands.entity(list).add_child(row);
+                }
+            }
+        }
+    }
+}
+
+fn go_to_field(
+    click: On<Pointer<Click>>,
+    q_buttons: Query<&GoToFieldButton>,
+    q_fields: Query<(&GlobalTransform, &FieldGeometry)>,
+    q_cameras: Query<&GlobalTransform, With<XrCamera>>,
+    mut q_roots: Query<&mut Transform, With<XrTrackingRoot>>,
+) {
+    let Ok(button) = q_buttons.get(click.entity) else {
+        return;
+    };
+    let Ok((field_transform, field_geom)) = q_fields.get(button.0) else {
+        return;
+    };
+    // Both eyes are cameras, the head is between them
+    let eyes: Vec<_> = q_cameras.iter().map(|eye| eye.translation()).collect();
+    if eyes.is_empty() {
+        return;
+    }
+    let head = eyes.iter().sum::<Vec3>() / eyes.len() as f32;
+
+    // Outside the -z touch line of the field
+    let touch_line = field_transform.transform_point(Vec3::new(
+        0.,
+        0.,
+        -(field_geom.play_area_size.y / 2.0 + field_geom.boundary_width),
+    ));
+    let outwards = (touch_line - field_transform.translation())
+        .with_y(0.)
+        .normalize_or_zero();
+    let target = touch_line + outwards * TOUCH_LINE_DISTANCE;
+    for mut root in &mut q_roots {
+        move_user_to(target, head, &mut root);
+    }
+}
@@ -1,13 +1,23 @@
-use crate::panels::{XrPanelAnchor, XrPanelSpawner};
+use crate::panels::{Translated, XrPanelAnchor, XrPanelSpawner};
 use bevy::color::palettes::tailwind::*;
+use bevy::ecs::relationship::RelatedSpawnerCommands;
 use bevy::prelude::*;
-use sslgame::{FieldGeometry, GameState, Team};
+use sslgame::{
+    FieldGeometry, GameEvent, GameEventKind, GameState, GoalReplay, Language, RobotCount,
+    SLOW_MOTION_SPEED, Team,
+};
 use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+/// How long the score panel lights up after a goal
+const GOAL_FLASH_DURATION: Duration = Duration::from_millis(2000);
 
 pub fn game_state_panel_plugin(app: &mut App) {
     app.add_systems(Update, manage_game_state_panels);
     app.add_systems(Update, update_score_panel);
+    app.add_systems(Update, flash_score_panel);
     app.add_systems(Update, update_team_panel);
+    app.add_systems(Update, update_replay_panel);
 }
 
 #[allow(clippy::type_complexity)]
@@ -48,6 +58,18 @@ fn manage_game_state_panels(
                         parent.spawn(score_panel(field_entity));
                     },
                 );
+                let replay_panel = panel_spawner.spawn_panel(
+                    &mut commands,
+                    Transform {
+                        translation: Vec3::new(0., 0.2, 0.),
+                        rotation: Quat::from_rotation_x(PI / 6.),
+                        scale: Vec3::new(0.5, 0.1, 1.),
+                    },
+                    Color::srgba(0., 0., 0., 0.),
+                    move |parent| {
+                        spawn_replay_panel(parent, field_entity);
+                    },
+                );
                 let team_icon_left = asset_server.load("teams/logos/erforce_light.png");
                 let team_icon_right = team_icon_left.clone();
                 let card_icon_left = asset_server.load("icons/card.png");
@@ -101,7 +123,7 @@ fn manage_game_state_panels(
                         .looking_at(Vec3::ZERO, Vec3::Y),
                         XrPanelAnchor,
                     ))
-                    .add_children(&[score_panel, left_panel, right_panel])
+                    .add_children(&[score_panel, replay_panel, left_panel, right_panel])
                     .id();
                 commands.entity(field_entity).add_child(panel_anchor);
             }
@@ -117,6 +139,7 @@ struct ScorePanel {
     state_source: Entity,
     left: Team,
     right: Team,
+    goal_flash: Option<Instant>,
 }
 
 fn score_panel(state_source: Entity) -> impl Bundle {
@@ -125,6 +148,7 @@ fn score_panel(state_source: Entity) -> impl Bundle {
             state_source,
             left: Team::Yellow,
             right: Team::Blue,
+            goal_flash: None,
         },
         Node {
             width: percent(100),
@@ -147,13 +171,14 @@ fn score_panel(state_source: Entity) -> impl Bundle {
 }
 
 fn update_score_panel(
+    language: Res<Language>,
     state_sources: Query<Ref<GameState>>,
     panels: Query<(&ScorePanel, &Children)>,
     mut texts: Query<&mut Text>,
 ) {
     for (score_panel, children) in panels.iter() {
         let game_state = state_sources.get(score_panel.state_source).unwrap();
-        if !game_state.is_changed() {
+        if !game_state.is_changed() && !language.is_changed() {
             continue;
         }
         let left_team = match score_panel.left {
@@ -173,7 +198,152 @@ fn update_score_panel(
             left_team.and_then(|l| l.score).unwrap_or(0),
             right_team.and_then(|r| r.score).unwrap_or(0)
         );
-        game_stage_text.0 = format!("{:?}", game_state.game_stage);
+        game_stage_text.0 = language.stage(game_state.game_stage()).to_string();
+    }
+}
+
+fn flash_score_panel(
+    mut game_events: MessageReader<GameEvent>,
+    mut panels: Query<(&mut ScorePanel, &mut BackgroundColor)>,
+) {
+    let now = Instant::now();
+    for event in game_events.read() {
+        if !matches!(event.kind, GameEventKind::Goal { .. }) {
+            continue;
+        }
+        for (mut score_panel, _) in &mut panels {
+            if score_panel.state_source == event.field {
+                score_panel.goal_flash = Some(now);
+            }
+        }
+    }
+
+    for (mut score_panel, mut background) in &mut panels {
+        let Some(flash_start) = score_panel.goal_flash else {
+            continue;
+        };
+        let progress = (now - flash_start).as_secs_f32() / GOAL_FLASH_DURATION.as_secs_f32();
+        if progress >= 1.0 {
+            score_panel.goal_flash = None;
+            background.0 = ZINC_700.into();
+        } else {
+            background.0 = AMBER_500.mix(&ZINC_700, progress).into();
+        }
+    }
+}
+
+// ======== Replay Panel  ========
+
+#[derive(Component, Debug)]
+struct ReplayPanel {
+    state_source: Entity,
+}
+
+#[derive(Component, Debug, Clone, Copy, PartialEq)]
+enum ReplayButton {
+    Play,
+    SlowMotion,
+    Live,
+}
+
+/// Buttons to replay the last goal, only shown while a replay is available
+fn spawn_replay_panel(parent: &mut RelatedSpawnerCommands<ChildOf>, state_source: Entity) {
+    parent
+        .spawn((
+            ReplayPanel { state_source },
+            Node {
+                width: percent(100),
+                height: percent(100),
+                display: Display::None,
+                flex_direction: FlexDirection::Row,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Stretch,
+                column_gap: px(5.),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            for (button, label) in [
+                (ReplayButton::Play, "Replay"),
+                (ReplayButton::SlowMotion, "Slow motion"),
+                (ReplayButton::Live, "Live"),
+            ] {
+                parent
+                    .spawn((
+                        button,
+                        Node {
+                            padding: UiRect::horizontal(px(5.)),
+                            border_radius: BorderRadius::all(px(5.)),
+                            justify_content: JustifyContent::Center,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        BackgroundColor(ZINC_700.into()),
+                        children![(
+                            Text::new(label),
+                            Translated(label),
+                            TextFont::from_font_size(10.)
+                        )],
+                    ))
+                    .observe(click_replay_button);
+            }
+        });
+}
+
+fn update_replay_panel(
+    q_replays: Query<&GoalReplay>,
+    mut q_panels: Query<(&ReplayPanel, &mut Node, &Children), Without<ReplayButton>>,
+    mut q_buttons: Query<(&ReplayButton, &mut Node)>,
+) {
+    let display = |visible| {
+        if visible {
+            Display::Flex
+        } else {
+            Display::None
+        }
+    };
+
+    for (panel, mut node, children) in &mut q_panels {
+        let Ok(replay) = q_replays.get(panel.state_source) else {
+            continue;
+        };
+        // The replay is updated every frame, so only actual changes are written to avoid relayouts
+        let panel_display = display(replay.clip().is_some());
+        if node.display != panel_display {
+            node.display = panel_display;
+        }
+        let playing = replay.playback().is_some();
+        for child in children.iter() {
+            if let Ok((button, mut button_node)) = q_buttons.get_mut(child) {
+                let button_display = display((*button == ReplayButton::Live) == playing);
+                if button_node.display != button_display {
+                    button_node.display = button_display;
+                }
+            }
+        }
+    }
+}
+
+fn click_replay_button(
+    click: On<Pointer<Click>>,
+    q_buttons: Query<(&ReplayButton, &ChildOf)>,
+    q_panels: Query<&ReplayPanel>,
+    mut q_replays: Query<&mut GoalReplay>,
+) {
+    let Ok((button, child_of)) = q_buttons.get(click.entity) else {
+        return;
+    };
+    let Ok(panel) = q_panels.get(child_of.parent()) else {
+        return;
+    };
+    let Ok(mut replay) = q_replays.get_mut(panel.state_source) else {
+        return;
+    };
+    // The user's view can't be moved in VR, so there is no goal cam
+    match button {
+        ReplayButton::Play => replay.play(1.0, false),
+        ReplayButton::SlowMotion => replay.play(SLOW_MOTION_SPEED, false),
+        ReplayButton::Live => replay.stop(),
     }
 }
 
@@ -281,6 +451,18 @@ fn team_panel(
                             ),
                             card_pill(YELLOW_400.into(), card_icon.clone(), "3"),
                             card_pill(RED_400.into(), card_icon, "1"),
+                            (
+                                Node {
+                                    height: percent(100.),
+                                    border_radius: BorderRadius::all(percent(100.)),
+                                    padding: UiRect::horizontal(px(3.5)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..default()
+                                },
+                                BackgroundColor(ZINC_500.into()),
+                                children![(Text::new("T 4"), TextFont::from_font_size(6.))]
+                            ),
                         ]
                     )
                 ]
@@ -290,15 +472,17 @@ fn team_panel(
 }
 
 fn update_team_panel(
-    state_sources: Query<Ref<GameState>>,
+    state_sources: Query<(Ref<GameState>, Ref<RobotCount>)>,
     panels: Query<(&TeamPanel, &Children)>,
     nodes: Query<&Children, With<Node>>,
     mut icons: Query<&mut ImageNode>,
     mut texts: Query<&mut Text>,
+    mut backgrounds: Query<&mut BackgroundColor>,
+    language: Res<Language>,
 ) {
     for (team_panel, children) in panels.iter() {
-        let game_state = state_sources.get(team_panel.state_source).unwrap();
-        if !game_state.is_changed() {
+        let (game_state, robot_count) = state_sources.get(team_panel.state_source).unwrap();
+        if !game_state.is_changed() && !robot_count.is_changed() && !language.is_changed() {
             continue;
         }
         let team_state = match team_panel.team {
@@ -319,6 +503,8 @@ fn update_team_panel(
         //       - <Node> Red Pill
         //         - <Image> Card Icon
         //         - <Text> Red Cards
+        //       - <Node> Timeout Pill
+        //         - <Text> Timeouts
 
         let mut _icon = icons.get_mut(children[0].entity()).unwrap();
         // TODO: Update icon
@@ -326,23 +512,37 @@ fn update_team_panel(
         let content_parent = nodes.get(children[1].entity()).unwrap();
 
         let mut team_name = texts.get_mut(content_parent[0].entity()).unwrap();
-        team_name.0 = team_state
-            .and_then(|t| t.name.clone())
-            .unwrap_or_else(|| "Unknown".to_string());
+        let robots = robot_count.team(team_panel.team);
+        team_name.0 = format!(
+            "{}  {}{}",
+            team_state
+                .and_then(|t| t.name.as_deref())
+                .unwrap_or(language.tr("Unknown")),
+            robots.on_field,
+            robots
+                .allowed
+                .map(|allowed| format!("/{allowed}"))
+                .unwrap_or_default()
+        );
 
         // Get pill nodes
         let pill_parent = nodes.get(content_parent[1].entity()).unwrap();
         let foul_pill = nodes.get(pill_parent[0].entity()).unwrap();
-        let [yellow_pill, red_pill] = nodes
-            .get_many([pill_parent[1].entity(), pill_parent[2].entity()])
+        let [yellow_pill, red_pill, timeout_pill] = nodes
+            .get_many([
+                pill_parent[1].entity(),
+                pill_parent[2].entity(),
+                pill_parent[3].entity(),
+            ])
             .unwrap();
 
         // Update pill texts
-        let [mut fouls, mut yellow_cards, mut red_cards] = texts
+        let [mut fouls, mut yellow_cards, mut red_cards, mut timeouts] = texts
             .get_many_mut([
                 foul_pill[0].entity(),
                 yellow_pill[1].entity(),
                 red_pill[1].entity(),
+                timeout_pill[0].entity(),
             ])
             .unwrap();
         fouls.0 = team_state.and_then(|b| b.fouls).unwrap_or(0).to_string();
@@ -354,5 +554,22 @@ fn update_team_panel(
             .and_then(|b| b.red_cards)
             .unwrap_or(0)
             .to_string();
+        // The remaining time of the card that expires first
+        if let Some(card_time) = team_state.and_then(|b| b.yellow_card_times.iter().min()) {
+            yellow_cards.0 += &format!(" ({}s)", card_time / 1_000_000);
+        }
+        let timeout_active = team_state.is_some_and(|b| b.timeout_active());
+        timeouts.0 = format!(
+            "T {}",
+            team_state.and_then(|b| b.timeouts_left).unwrap_or(0)
+        );
+
+        // Active cards and timeouts are highlighted
+        let [mut yellow_background, mut timeout_background] = backgrounds
+            .get_many_mut([pill_parent[1].entity(), pill_parent[3].entity()])
+            .unwrap();
+        let cards_active = team_state.is_some_and(|b| !b.yellow_card_times.is_empty());
+        yellow_background.0 = if cards_active { AMBER_500 } else { YELLOW_400 }.into();
+        timeout_background.0 = if timeout_active { SKY_400 } else { ZINC_500 }.into();
     }
 }
@@ -1,3 +1,4 @@
+use crate::interaction::input::LeftHandPointer;
 use bevy::asset::RenderAssetUsages;
 use bevy::camera::RenderTarget;
 use bevy::ecs::relationship::RelatedSpawnerCommands;
@@ -5,8 +6,22 @@ use bevy::ecs::system::SystemParam;
 use bevy::mesh::{Indices, PrimitiveTopology};
 use bevy::prelude::*;
 use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat, TextureUsages};
+use sslgame::{AccessibilitySettings, Language};
 
+pub mod clock;
+pub mod comfort;
+pub mod debug_values;
+pub mod device_warnings;
+pub mod fields;
 pub mod game_state;
+pub mod network;
+pub mod passthrough;
+pub mod performance;
+pub mod plots;
+pub mod self_test;
+
+// 1000 res -> 1pixel=1mm, 10x scale -> 1unit=1cm
+const BASE_UI_SCALE: f32 = 10.;
 
 pub fn xr_panel_plugin(app: &mut App) {
     // Build a 1x1, -z forward, plane with mirrored uvs,
@@ -35,11 +50,27 @@ pub fn xr_panel_plugin(app: &mut App) {
     );
     app.insert_resource(XrPanelMesh(mesh_handle));
 
-    // 1000 res -> 1pixel=1mm, 10x scale -> 1unit=1cm
-    app.insert_resource(UiScale(10.));
+    app.insert_resource(UiScale(BASE_UI_SCALE));
     app.insert_resource(XrPanelResolution {
         pixels_per_meter: 1000.,
     });
+
+    app.add_systems(Update, (translate_texts, apply_accessibility, scale_texts));
+}
+
+/// Static panel text, replaced with its translation whenever the [`Language`] changes
+#[derive(Component, Debug, Clone, Copy)]
+pub struct Translated(pub &'static str);
+
+pub(crate) fn translate_texts(
+    language: Res<Language>,
+    mut q_texts: Query<(&mut Text, Ref<Translated>)>,
+) {
+    for (mut text, translated) in &mut q_texts {
+        if language.is_changed() || translated.is_added() {
+            text.0 = language.tr(translated.0).to_string();
+        }
+    }
 }
 
 /// Marks the display mesh of an xr panel, and references the root of its UI hierarchy.
@@ -70,10 +101,116 @@ pub struct XrPanelResolution {
     pub pixels_per_meter: f32,
 }
 
+/// Size, offset and background of a panel as spawned, before the [`AccessibilitySettings`] are applied
+#[derive(Component, Debug)]
+struct XrPanelBase {
+    size: Vec2,
+    translation: Vec3,
+    background: Color,
+}
+
+/// Grows the panels with the UI scale. The render resolution grows with the panels,
+/// so the UI layout keeps its size and never overflows the panel, unlike larger text, see [`scale_texts`].
+#[allow(clippy::type_complexity)]
+fn apply_accessibility(
+    settings: Res<AccessibilitySettings>,
+    panel_res: Res<XrPanelResolution>,
+    mut ui_scale: ResMut<UiScale>,
+    mut image_assets: ResMut<Assets<Image>>,
+    material_assets: Res<Assets<StandardMaterial>>,
+    mut q_panels: Query<(
+        &XrPanelBase,
+        &mut Transform,
+        &MeshMaterial3d<StandardMaterial>,
+        &XrPanel,
+        Option<&ChildOf>,
+    )>,
+    q_hands: Query<(), With<LeftHandPointer>>,
+    q_roots: Query<&UiTargetCamera>,
+    mut q_cameras: Query<&mut Camera>,
+) {
+    let scale = BASE_UI_SCALE * settings.ui_scale;
+    if ui_scale.0 != scale {
+        ui_scale.0 = scale;
+    }
+
+    // Checked every frame, since some panels get their whole transform replaced when their field changes
+    for (base, mut transform, material, panel, parent) in &mut q_panels {
+        let size = (base.size * settings.ui_scale).extend(transform.scale.z);
+        if transform.scale != size {
+            transform.scale = size;
+        }
+        // Wrist panels are laid out around the hand, so their offsets grow with them and they don't overlap
+        let on_wrist = parent.is_some_and(|parent| q_hands.contains(parent.parent()));
+        let translation = base.translation * settings.ui_scale;
+        if on_wrist && transform.translation != translation {
+            transform.translation = translation;
+        }
+
+        if !settings.is_changed() {
+            continue;
+        }
+        let resolution = panel_resolution(size.truncate(), panel_res.pixels_per_meter);
+        if let Some(image) = material_assets
+            .get(material)
+            .and_then(|material| material.base_color_texture.as_ref())
+            .and_then(|image| image_assets.get_mut(image))
+            && image.texture_descriptor.size != resolution
+        {
+            image.resize(resolution);
+        }
+        if let Ok(mut camera) = q_roots
+            .get(panel.0)
+            .and_then(|target| q_cameras.get_mut(target.0))
+        {
+            camera.clear_color = ClearColorConfig::Custom(if settings.high_contrast {
+                Color::BLACK
+            } else {
+                base.background
+            });
+        }
+    }
+}
+
+/// Font size of a panel text as spawned, before the text scale is applied
+#[derive(Component, Debug)]
+struct TextBaseSize(f32);
+
+/// Scales the font sizes of all panel texts with the text scale of the [`AccessibilitySettings`],
+/// without changing the rest of the layout
+fn scale_texts(
+    mut commands: Commands,
+    settings: Res<AccessibilitySettings>,
+    mut q_new_texts: Query<(&mut TextFont, Entity), (With<Text>, Without<TextBaseSize>)>,
+    mut q_texts: Query<(&TextBaseSize, &mut TextFont)>,
+) {
+    for (mut font, entity) in &mut q_new_texts {
+        commands.entity(entity).insert(TextBaseSize(font.font_size));
+        font.font_size *= settings.text_scale;
+    }
+    if settings.is_changed() {
+        for (base, mut font) in &mut q_texts {
+            let font_size = base.0 * settings.text_scale;
+            if font.font_size != font_size {
+                font.font_size = font_size;
+            }
+        }
+    }
+}
+
+fn panel_resolution(size: Vec2, pixels_per_meter: f32) -> Extent3d {
+    Extent3d {
+        width: (size.x * pixels_per_meter) as u32,
+        height: (size.y * pixels_per_meter) as u32,
+        ..default()
+    }
+}
+
 #[derive(SystemParam)]
 pub struct XrPanelSpawner<'w> {
     panel_mesh: Res<'w, XrPanelMesh>,
     panel_res: Res<'w, XrPanelResolution>,
+    accessibility: Res<'w, AccessibilitySettings>,
     image_assets: ResMut<'w, Assets<Image>>,
     material_assets: ResMut<'w, Assets<StandardMaterial>>,
 }
@@ -83,22 +220,27 @@ impl XrPanelSpawner<'_> {
     ///
     /// The physical size of the panel is determined by the `x` and `y` components of the `transform` scale (in meters).
     /// The render resolution is calculated based on the physical size and the `XrPanelResolution` resource.
+    /// Both grow with the UI scale of the [`AccessibilitySettings`].
     ///
     /// The `ui_spawner` closure is used to build the UI hierarchy by spawning children under an
     /// automatically generated root node that covers the entire panel.
     pub fn spawn_panel(
         &mut self,
         commands: &mut Commands,
-        transform: Transform,
+        mut transform: Transform,
         background_color: Color,
         ui_spawner: impl FnOnce(&mut RelatedSpawnerCommands<ChildOf>),
     ) -> Entity {
+        let base = XrPanelBase {
+            size: transform.scale.truncate(),
+            translation: transform.translation,
+            background: background_color,
+        };
+        let size = base.size * self.accessibility.ui_scale;
+        transform.scale = size.extend(transform.scale.z);
+
         let mut image = Image::new_fill(
-            Extent3d {
-                width: (transform.scale.x * self.panel_res.pixels_per_meter) as u32,
-                height: (transform.scale.y * self.panel_res.pixels_per_meter) as u32,
-                ..default()
-            },
+            panel_resolution(size, self.panel_res.pixels_per_meter),
             TextureDimension::D2,
             &[0, 0, 0, 0],
             TextureFormat::Bgra8UnormSrgb,
@@ -130,7 +272,11 @@ impl XrPanelSpawner<'_> {
                 Camera {
                     // render before the "main pass" camera
                     order: -1,
-                    clear_color: ClearColorConfig::Custom(background_color),
+                    clear_color: ClearColorConfig::Custom(if self.accessibility.high_contrast {
+                        Color::BLACK
+                    } else {
+                        background_color
+                    }),
                     ..default()
                 },
                 RenderTarget::Image(image_handle.into()),
@@ -157,6 +303,7 @@ impl XrPanelSpawner<'_> {
                 Mesh3d(mesh_handle),
                 MeshMaterial3d(material_handle),
                 transform,
+                base,
             ))
             .add_one_related::<XrUiRoot>(ui_root)
             .id();
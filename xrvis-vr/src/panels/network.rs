@@ -0,0 +1,144 @@
+use crate::interaction::input::LeftHandPointer;
+use crate::panels::XrPanelSpawner;
+use bevy::color::palettes::tailwind::*;
+use bevy::prelude::*;
+use sslgame::{DecodeErrors, Field, Language, NetworkDiagnostics, format_wall_clock};
+use std::f32::consts::PI;
+
+const FONT_SIZE: f32 = 0.6;
+/// Only the newest errors fit on the panel
+const SHOWN_ERRORS: usize = 8;
+
+pub fn network_panel_plugin(app: &mut App) {
+    app.add_systems(Update, update_network_panel);
+}
+
+/// Interfaces, multicast groups, socket and decode errors above the wrist clock, toggled with its "Net" button
+#[derive(Component, Debug)]
+struct NetworkPanel;
+
+#[derive(Component, Debug)]
+struct NetworkText;
+
+pub fn toggle_network_panel(
+    _click: On<Pointer<Click>>,
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    left_hand: Option<Single<Entity, With<LeftHandPointer>>>,
+    q_panels: Query<Entity, With<NetworkPanel>>,
+) {
+    if !q_panels.is_empty() {
+        for panel in &q_panels {
+            commands.entity(panel).despawn();
+        }
+        return;
+    }
+    let Some(hand) = left_hand else {
+        return;
+    };
+
+    // Above the wrist clock, tilted the same way
+    let panel = panel_spawner.spawn_panel(
+        &mut commands,
+        Transform {
+            translation: Vec3::new(0., 0.13, 0.02),
+            rotation: Quat::from_rotation_x(-PI / 3.),
+            scale: Vec3::new(0.2, 0.14, 1.),
+        },
+        ZINC_800.into(),
+        |parent| {
+            parent
+                .spawn(Node {
+                    width: percent(100),
+                    height: percent(100),
+                    padding: UiRect::all(px(0.3)),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        NetworkText,
+                        Text::default(),
+                        TextFont::from_font_size(FONT_SIZE),
+                    ));
+                });
+        },
+    );
+    commands.entity(panel).insert(NetworkPanel);
+    commands.entity(*hand).add_child(panel);
+}
+
+fn update_network_panel(
+    diagnostics: Res<NetworkDiagnostics>,
+    language: Res<Language>,
+    q_fields: Query<(&Field, Ref<DecodeErrors>)>,
+    mut q_texts: Query<(&mut Text, &mut TextColor, Ref<NetworkText>)>,
+) {
+    let decode_errors_changed = q_fields.iter().any(|(_, errors)| errors.is_changed());
+    for (mut text, mut color, network_text) in &mut q_texts {
+        if !diagnostics.is_changed()
+            && !language.is_changed()
+            && !decode_errors_changed
+            && !network_text.is_added()
+        {
+            continue;
+        }
+
+        let mut content = language.tr("Interfaces").to_string();
+        for interface in &diagnostics.interfaces {
+            let addrs: Vec<_> = interface.addrs.iter().map(ToString::to_string).collect();
+            content += &format!(
+                "\n{} {}{} {}",
+                interface.name,
+                language.tr(if interface.up { "up" } else { "down" }),
+                if interface.multicast {
+                    format!(" {}", language.tr("multicast"))
+                } else {
+                    String::new()
+                },
+                addrs.join(" ")
+            );
+        }
+        content += &format!("\n\n{}", language.tr("Multicast groups"));
+        for membership in &diagnostics.memberships {
+            content += &format!(
+                "\n{} {} {}: {}",
+                membership.task,
+                membership.interface,
+                membership.group,
+                membership.error.as_deref().unwrap_or(language.tr("joined"))
+            );
+        }
+        content += &format!("\n\n{}", language.tr("Recent errors"));
+        let skipped = diagnostics.errors.len().saturating_sub(SHOWN_ERRORS);
+        for error in &diagnostics.errors[skipped..] {
+            content += &format!("\n{} {}", format_wall_clock(error.time), error);
+        }
+
+        let mut any_decode_errors = false;
+        for (field, errors) in &q_fields {
+            let recent = errors.last_minute();
+            if recent > 0 {
+                any_decode_errors = true;
+                content += &format!(
+                    "\n{}: {}",
+                    field.host.display_name(),
+                    language.trf(
+                        "{count} decode errors in the last minute",
+                        &[("count", &recent)]
+                    )
+                );
+            }
+        }
+
+        text.0 = content;
+        let any_failed = diagnostics.memberships.iter().any(|m| m.error.is_some());
+        color.0 = if any_failed {
+            RED_400
+        } else if any_decode_errors {
+            AMBER_400
+        } else {
+            ZINC_100
+        }
+        .into();
+    }
+}
@@ -0,0 +1,251 @@
+use crate::interaction::hand_occlusion::HandOcclusion;
+use crate::interaction::input::LeftHandPointer;
+use crate::panels::clock::text_button;
+use crate::panels::{Translated, XrPanelSpawner};
+use bevy::color::palettes::tailwind::*;
+use bevy::prelude::*;
+use bevy_mod_openxr::openxr::sys;
+use bevy_mod_openxr::resources::{OxrInstance, OxrPassthroughLayerFB};
+use std::f32::consts::PI;
+use std::ptr;
+
+const FONT_SIZE: f32 = 0.8;
+
+pub fn passthrough_panel_plugin(app: &mut App) {
+    app.init_resource::<PassthroughStyle>();
+    app.add_systems(
+        Update,
+        (
+            apply_passthrough_style.run_if(resource_exists::<OxrPassthroughLayerFB>.and(
+                resource_changed::<PassthroughStyle>.or(resource_added::<OxrPassthroughLayerFB>),
+            )),
+            update_passthrough_panel,
+        ),
+    );
+}
+
+/// Color adjustment of the passthrough layer, e.g. to keep the field lines visible in bright venues
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct PassthroughStyle {
+    /// -100 to 100, 0 keeps the camera image
+    pub brightness: f32,
+    /// 0 to 2, 1 keeps the camera image
+    pub contrast: f32,
+    /// 0 to 2, 1 keeps the camera image, 0 is grayscale
+    pub saturation: f32,
+}
+
+impl Default for PassthroughStyle {
+    fn default() -> Self {
+        Self {
+            brightness: 0.0,
+            contrast: 1.0,
+            saturation: 1.0,
+        }
+    }
+}
+
+/// Property of the [`PassthroughStyle`] changed by a button
+#[derive(Debug, Clone, Copy)]
+enum StyleProperty {
+    Brightness,
+    Contrast,
+    Saturation,
+}
+
+impl StyleProperty {
+    fn name(self) -> &'static str {
+        match self {
+            StyleProperty::Brightness => "Brightness",
+            StyleProperty::Contrast => "Contrast",
+            StyleProperty::Saturation => "Saturation",
+        }
+    }
+
+    /// Changes the property by one step in the given direction
+    fn step(self, style: &mut PassthroughStyle, direction: f32) {
+        match self {
+            StyleProperty::Brightness => {
+                style.brightness = (style.brightness + direction * 10.0).clamp(-100.0, 100.0);
+            }
+            StyleProperty::Contrast => {
+                style.contrast = (style.contrast + direction * 0.1).clamp(0.0, 2.0);
+            }
+            StyleProperty::Saturation => {
+                style.saturation = (style.saturation + direction * 0.1).clamp(0.0, 2.0);
+            }
+        }
+    }
+
+    fn value(self, style: &PassthroughStyle) -> String {
+        match self {
+            StyleProperty::Brightness => format!("{:+.0}", style.brightness),
+            StyleProperty::Contrast => format!("{:.1}", style.contrast),
+            StyleProperty::Saturation => format!("{:.1}", style.saturation),
+        }
+    }
+}
+
+/// Passthrough adjustments above the wrist clock, toggled with its "Pass" button
+#[derive(Component, Debug)]
+struct PassthroughPanel;
+
+#[derive(Component, Debug)]
+struct StyleValueText(StyleProperty);
+
+/// Toggles the [`HandOcclusion`], highlighted while it is on
+#[derive(Component, Debug)]
+struct OcclusionButton;
+
+pub fn toggle_passthrough_panel(
+    _click: On<Pointer<Click>>,
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    left_hand: Option<Single<Entity, With<LeftHandPointer>>>,
+    q_panels: Query<Entity, With<PassthroughPanel>>,
+) {
+    if !q_panels.is_empty() {
+        for panel in &q_panels {
+            commands.entity(panel).despawn();
+        }
+        return;
+    }
+    let Some(hand) = left_hand else {
+        return;
+    };
+
+    // Above the wrist clock, tilted the same way
+    let panel = panel_spawner.spawn_panel(
+        &mut commands,
+        Transform {
+            translation: Vec3::new(0., 0.11, 0.03),
+            rotation: Quat::from_rotation_x(-PI / 3.),
+            scale: Vec3::new(0.12, 0.08, 1.),
+        },
+        ZINC_800.into(),
+        |parent| {
+            parent
+                .spawn(Node {
+                    width: percent(100),
+                    height: percent(100),
+                    padding: UiRect::all(px(0.3)),
+                    flex_direction: FlexDirection::Column,
+                    row_gap: px(0.3),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for property in [
+                        StyleProperty::Brightness,
+                        StyleProperty::Contrast,
+                        StyleProperty::Saturation,
+                    ] {
+                        parent
+                            .spawn(Node {
+                                flex_direction: FlexDirection::Row,
+                                column_gap: px(0.5),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn((
+                                    Text::new(property.name()),
+                                    Translated(property.name()),
+                                    TextFont::from_font_size(FONT_SIZE),
+                                ));
+                                parent.spawn(text_button("-")).observe(
+                                    move |_click: On<Pointer<Click>>,
+                                          mut style: ResMut<PassthroughStyle>| {
+                                        property.step(&mut style, -1.0);
+                                    },
+                                );
+                                parent.spawn((
+                                    StyleValueText(property),
+                                    Text::default(),
+                                    TextFont::from_font_size(FONT_SIZE),
+                                ));
+                                parent.spawn(text_button("+")).observe(
+                                    move |_click: On<Pointer<Click>>,
+                                          mut style: ResMut<PassthroughStyle>| {
+                                        property.step(&mut style, 1.0);
+                                    },
+                                );
+                            });
+                    }
+                    parent
+                        .spawn(Node {
+                            flex_direction: FlexDirection::Row,
+                            column_gap: px(0.5),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent.spawn(text_button("Reset")).observe(
+                                |_click: On<Pointer<Click>>, mut style: ResMut<PassthroughStyle>| {
+                                    style.set_if_neq(PassthroughStyle::default());
+                                },
+                            );
+                            parent
+                                .spawn((text_button("Occlusion"), OcclusionButton))
+                                .observe(
+                                    |_click: On<Pointer<Click>>,
+                                     mut occlusion: ResMut<HandOcclusion>| {
+                                        occlusion.enabled = !occlusion.enabled;
+                                    },
+                                );
+                        });
+                });
+        },
+    );
+    commands.entity(panel).insert(PassthroughPanel);
+    commands.entity(*hand).add_child(panel);
+}
+
+fn update_passthrough_panel(
+    style: Res<PassthroughStyle>,
+    occlusion: Res<HandOcclusion>,
+    mut q_texts: Query<(&mut Text, Ref<StyleValueText>)>,
+    mut q_buttons: Query<(&mut BackgroundColor, Ref<OcclusionButton>)>,
+) {
+    for (mut text, value_text) in &mut q_texts {
+        if style.is_changed() || value_text.is_added() {
+            text.0 = value_text.0.value(&style);
+        }
+    }
+    for (mut background, button) in &mut q_buttons {
+        if occlusion.is_changed() || button.is_added() {
+            background.0 = if occlusion.enabled { SKY_600 } else { ZINC_600 }.into();
+        }
+    }
+}
+
+/// Sets the style of the FB passthrough layer. The runtime keeps it until it is set again.
+fn apply_passthrough_style(
+    style: Res<PassthroughStyle>,
+    instance: Res<OxrInstance>,
+    layer: Res<OxrPassthroughLayerFB>,
+) {
+    let Some(passthrough) = instance.exts().fb_passthrough else {
+        return;
+    };
+    let adjustment = sys::PassthroughBrightnessContrastSaturationFB {
+        ty: sys::PassthroughBrightnessContrastSaturationFB::TYPE,
+        next: ptr::null(),
+        brightness: style.brightness,
+        contrast: style.contrast,
+        saturation: style.saturation,
+    };
+    let layer_style = sys::PassthroughStyleFB {
+        ty: sys::PassthroughStyleFB::TYPE,
+        next: ptr::from_ref(&adjustment).cast(),
+        texture_opacity_factor: 1.0,
+        edge_color: sys::Color4f {
+            r: 0.0,
+            g: 0.0,
+            b: 0.0,
+            a: 0.0,
+        },
+    };
+    // SAFETY: The layer handle is valid while its resource exists, the style chain lives until the call returns
+    let result = unsafe { (passthrough.passthrough_layer_set_style)(layer.inner(), &layer_style) };
+    if result.into_raw() < 0 {
+        warn!("Failed to set the passthrough style: {result}");
+    }
+}
@@ -0,0 +1,234 @@
+use crate::interaction::input::LeftHandPointer;
+use crate::panels::XrPanelSpawner;
+use bevy::app::MainScheduleOrder;
+use bevy::color::palettes::tailwind::*;
+use bevy::diagnostic::{
+    DiagnosticsStore, EntityCountDiagnosticsPlugin, FrameTimeDiagnosticsPlugin,
+};
+use bevy::ecs::schedule::ScheduleLabel;
+use bevy::prelude::*;
+use bevy_mod_openxr::resources::OxrFrameState;
+use sslgame::{ContentQuality, Language};
+use std::f32::consts::PI;
+use std::time::{Duration, Instant};
+
+const FONT_SIZE: f32 = 0.6;
+const UPDATE_INTERVAL: Duration = Duration::from_millis(500);
+/// Frames taking this much longer than the display period missed their vsync
+const DROPPED_FRAME_FACTOR: f32 = 1.5;
+/// Main schedules timed between the marks before and after them
+const TIMED_SCHEDULES: [&str; 3] = ["PreUpdate", "Update", "PostUpdate"];
+
+pub fn performance_panel_plugin(app: &mut App) {
+    if !app.is_plugin_added::<FrameTimeDiagnosticsPlugin>() {
+        app.add_plugins(FrameTimeDiagnosticsPlugin::default());
+    }
+    if !app.is_plugin_added::<EntityCountDiagnosticsPlugin>() {
+        app.add_plugins(EntityCountDiagnosticsPlugin::default());
+    }
+
+    // Marks around the timed schedules, the fixed update loop is counted to Update
+    let mut order = app.world_mut().resource_mut::<MainScheduleOrder>();
+    order.insert_before(PreUpdate, TimingMark(0));
+    order.insert_after(PreUpdate, TimingMark(1));
+    order.insert_after(Update, TimingMark(2));
+    order.insert_after(PostUpdate, TimingMark(3));
+    for mark in 0..=TIMED_SCHEDULES.len() {
+        app.add_systems(
+            TimingMark(mark),
+            move |mut timings: ResMut<ScheduleTimings>| timings.mark(mark),
+        );
+    }
+
+    app.init_resource::<ScheduleTimings>();
+    app.init_resource::<DroppedFrames>();
+    app.add_systems(Startup, enable_auto_quality);
+    app.add_systems(Update, (count_dropped_frames, update_performance_panel));
+}
+
+/// Dropped frames are uncomfortable in a headset, and passthrough shows the real robots behind cutouts
+fn enable_auto_quality(mut quality: ResMut<ContentQuality>) {
+    quality.auto = true;
+    quality.allow_cutout = true;
+}
+
+/// Performance numbers above the wrist clock, toggled with its "Perf" button, for tuning without a laptop
+#[derive(Component, Debug)]
+struct PerformancePanel;
+
+#[derive(Component, Debug)]
+struct PerformanceText;
+
+#[derive(ScheduleLabel, Debug, Clone, PartialEq, Eq, Hash)]
+struct TimingMark(usize);
+
+/// Smoothed durations of the [`TIMED_SCHEDULES`] in seconds
+#[derive(Resource, Debug, Default)]
+struct ScheduleTimings {
+    marks: [Option<Instant>; TIMED_SCHEDULES.len() + 1],
+    durations: [f32; TIMED_SCHEDULES.len()],
+}
+
+impl ScheduleTimings {
+    fn mark(&mut self, mark: usize) {
+        let now = Instant::now();
+        if mark > 0
+            && let Some(previous) = self.marks[mark - 1]
+        {
+            let duration = (now - previous).as_secs_f32();
+            // Exponential smoothing, the numbers would be unreadable otherwise
+            self.durations[mark - 1] = self.durations[mark - 1] * 0.9 + duration * 0.1;
+        }
+        self.marks[mark] = Some(now);
+    }
+}
+
+/// Frames that took longer than the display period predicted by the runtime
+#[derive(Resource, Debug, Default)]
+struct DroppedFrames {
+    total: u64,
+    display_period: Option<Duration>,
+}
+
+pub fn toggle_performance_panel(
+    _click: On<Pointer<Click>>,
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    left_hand: Option<Single<Entity, With<LeftHandPointer>>>,
+    q_panels: Query<Entity, With<PerformancePanel>>,
+) {
+    if !q_panels.is_empty() {
+        for panel in &q_panels {
+            commands.entity(panel).despawn();
+        }
+        return;
+    }
+    let Some(hand) = left_hand else {
+        return;
+    };
+
+    // Above the wrist clock, tilted the same way
+    let panel = panel_spawner.spawn_panel(
+        &mut commands,
+        Transform {
+            translation: Vec3::new(0., 0.12, 0.02),
+            rotation: Quat::from_rotation_x(-PI / 3.),
+            scale: Vec3::new(0.14, 0.1, 1.),
+        },
+        ZINC_800.into(),
+        |parent| {
+            parent
+                .spawn(Node {
+                    width: percent(100),
+                    height: percent(100),
+                    padding: UiRect::all(px(0.3)),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        PerformanceText,
+                        Text::default(),
+                        TextFont::from_font_size(FONT_SIZE),
+                    ));
+                });
+        },
+    );
+    commands.entity(panel).insert(PerformancePanel);
+    commands.entity(*hand).add_child(panel);
+}
+
+fn count_dropped_frames(
+    time: Res<Time<Real>>,
+    frame_state: Option<Res<OxrFrameState>>,
+    mut dropped: ResMut<DroppedFrames>,
+    mut quality: ResMut<ContentQuality>,
+) {
+    let Some(frame_state) = frame_state else {
+        return;
+    };
+    let display_period =
+        Duration::from_nanos(frame_state.predicted_display_period.as_nanos().max(0) as u64);
+    dropped.display_period = Some(display_period);
+    // The content quality is reduced when frames don't fit into the display period
+    if !display_period.is_zero() && quality.frame_budget != display_period {
+        quality.frame_budget = display_period;
+    }
+    if !display_period.is_zero()
+        && time.delta().as_secs_f32() > display_period.as_secs_f32() * DROPPED_FRAME_FACTOR
+    {
+        dropped.total += 1;
+    }
+}
+
+fn update_performance_panel(
+    mut last_update: Local<Option<Instant>>,
+    diagnostics: Res<DiagnosticsStore>,
+    timings: Res<ScheduleTimings>,
+    dropped: Res<DroppedFrames>,
+    quality: Res<ContentQuality>,
+    language: Res<Language>,
+    meshes: Res<Assets<Mesh>>,
+    mut q_texts: Query<(&mut Text, &mut TextColor, Ref<PerformanceText>)>,
+) {
+    let now = Instant::now();
+    let due = last_update.is_none_or(|last| now - last >= UPDATE_INTERVAL);
+    if !due && !q_texts.iter().any(|(.., text)| text.is_added()) {
+        return;
+    }
+    *last_update = Some(now);
+
+    let frame_time = diagnostics
+        .get(&FrameTimeDiagnosticsPlugin::FRAME_TIME)
+        .and_then(|frame_time| frame_time.smoothed());
+    let entities = diagnostics
+        .get(&EntityCountDiagnosticsPlugin::ENTITY_COUNT)
+        .and_then(|count| count.value());
+
+    let mut content = match frame_time {
+        Some(ms) => language.trf(
+            "Frame {ms} ms ({fps} fps)",
+            &[
+                ("ms", &format!("{ms:.1}")),
+                ("fps", &format!("{:.0}", 1000.0 / ms)),
+            ],
+        ),
+        None => format!("{} -", language.tr("Frame")),
+    };
+    if let Some(period) = dropped.display_period.filter(|p| !p.is_zero()) {
+        content += ", ";
+        content += &language.trf(
+            "display {rate} Hz",
+            &[("rate", &format!("{:.0}", 1.0 / period.as_secs_f32()))],
+        );
+    }
+    content += &format!("\n{} {}", language.tr("Dropped frames"), dropped.total);
+    content += &format!(
+        "\n{} {}{}",
+        language.tr("Quality"),
+        language.tr(&format!("{:?}", quality.level)),
+        if quality.auto { " (auto)" } else { "" }
+    );
+    content += "\n";
+    content += &language.trf(
+        "Entities {entities}, meshes {meshes}",
+        &[
+            (
+                "entities",
+                &entities.map_or("-".to_string(), |count| format!("{count:.0}")),
+            ),
+            ("meshes", &meshes.len()),
+        ],
+    );
+    for (name, duration) in TIMED_SCHEDULES.iter().zip(timings.durations) {
+        content += &format!("\n{name} {:.2} ms", duration * 1000.0);
+    }
+
+    // Red when the frames don't fit into the display period
+    let too_slow = frame_time
+        .zip(dropped.display_period)
+        .is_some_and(|(ms, period)| !period.is_zero() && ms / 1000.0 > period.as_secs_f64() * 1.05);
+    for (mut text, mut color, _) in &mut q_texts {
+        text.0.clone_from(&content);
+        color.0 = if too_slow { RED_400 } else { ZINC_100 }.into();
+    }
+}
@@ -0,0 +1,384 @@
+use crate::panels::{Translated, XrPanelSpawner};
+use bevy::asset::RenderAssetUsages;
+use bevy::color::palettes::tailwind::*;
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use sslgame::{FieldGeometry, Language, Plot, PlotSource, Plots};
+use std::f32::consts::{FRAC_PI_2, PI};
+use std::time::{Duration, Instant};
+
+/// Charts are redrawn at a lower rate than the frame rate, drawing them on the cpu isn't free
+const REDRAW_INTERVAL: Duration = Duration::from_millis(50);
+/// Plots that fit on the panel, the rest is not shown
+const MAX_PLOTS: usize = 4;
+const CHART_SIZE: UVec2 = UVec2::new(360, 90);
+const CHART_BACKGROUND: Srgba = ZINC_800;
+const CHART_LINE: Srgba = SKY_400;
+const FONT_SIZE: f32 = 4.;
+
+pub fn plots_panel_plugin(app: &mut App) {
+    app.add_systems(Update, manage_plots_panels);
+    app.add_systems(Update, (rebuild_plots_panels, redraw_plots).chain());
+}
+
+/// Marks the display mesh of a plots panel, which is a child of its field
+#[derive(Component, Debug)]
+struct PlotsDisplay;
+
+fn panel_transform(field_geom: &FieldGeometry) -> Transform {
+    // Next to the field on the -x side, facing the field center and tilted slightly upwards
+    Transform {
+        translation: Vec3::new(
+            -field_geom.play_area_size.x / 2.0 - field_geom.boundary_width - 0.3,
+            0.6,
+            0.,
+        ),
+        rotation: Quat::from_rotation_y(-FRAC_PI_2) * Quat::from_rotation_x(-PI / 12.),
+        scale: Vec3::new(0.8, 0.9, 1.),
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn manage_plots_panels(
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    (q_fields, mut q_panels): (
+        Query<(Ref<FieldGeometry>, Entity), With<Plots>>,
+        Query<(&mut Transform, &ChildOf), With<PlotsDisplay>>,
+    ),
+) {
+    for (field_geom, field_entity) in q_fields {
+        let display = q_panels
+            .iter_mut()
+            .find(|(_, c)| c.parent() == field_entity);
+
+        match display {
+            Some((mut display_transform, _)) if field_geom.is_changed() => {
+                *display_transform = panel_transform(&field_geom);
+            }
+            None => {
+                let display = panel_spawner.spawn_panel(
+                    &mut commands,
+                    panel_transform(&field_geom),
+                    Color::srgba(0., 0., 0., 0.),
+                    move |parent| {
+                        parent.spawn(plots_panel(field_entity));
+                    },
+                );
+                commands.entity(display).insert(PlotsDisplay);
+                commands.entity(field_entity).add_child(display);
+            }
+            _ => {}
+        }
+    }
+}
+
+// ======== Plots Panel ========
+
+#[derive(Component, Debug)]
+struct PlotsPanel {
+    state_source: Entity,
+    /// Plots and time window the panel content was built for
+    shown: Option<(Vec<PlotSource>, Duration)>,
+}
+
+/// Multiplies the time window of the panel's source
+#[derive(Component, Debug)]
+struct WindowButton {
+    panel: Entity,
+    factor: f32,
+}
+
+/// Removes the plot from the panel's source
+#[derive(Component, Debug)]
+struct RemoveButton {
+    panel: Entity,
+    source: PlotSource,
+}
+
+/// The label and chart image of a plot
+#[derive(Component, Debug)]
+struct PlotChart {
+    panel: Entity,
+    source: PlotSource,
+    label: Entity,
+}
+
+fn plots_panel(state_source: Entity) -> impl Bundle {
+    (
+        PlotsPanel {
+            state_source,
+            shown: None,
+        },
+        Node {
+            width: percent(100),
+            height: percent(100),
+            padding: UiRect::all(px(4.)),
+            border_radius: BorderRadius::all(px(5.)),
+            flex_direction: FlexDirection::Column,
+            justify_content: JustifyContent::FlexStart,
+            align_items: AlignItems::Stretch,
+            row_gap: px(2.),
+            ..default()
+        },
+        BackgroundColor(ZINC_700.into()),
+    )
+}
+
+fn text_button(text: &str) -> impl Bundle {
+    (
+        Node {
+            padding: UiRect::horizontal(px(2.)),
+            border_radius: BorderRadius::all(px(1.)),
+            ..default()
+        },
+        BackgroundColor(ZINC_500.into()),
+        children![(Text::new(text), TextFont::from_font_size(FONT_SIZE))],
+    )
+}
+
+/// Rebuilds the panel content when plots are added or removed, or the window changed
+fn rebuild_plots_panels(
+    mut commands: Commands,
+    mut image_assets: ResMut<Assets<Image>>,
+    q_plots: Query<&Plots>,
+    q_panels: Query<(&mut PlotsPanel, Entity)>,
+) {
+    for (mut panel, panel_entity) in q_panels {
+        let Ok(plots) = q_plots.get(panel.state_source) else {
+            continue;
+        };
+        let sources: Vec<_> = plots
+            .plots()
+            .iter()
+            .take(MAX_PLOTS)
+            .map(|plot| plot.source.clone())
+            .collect();
+        let shown = Some((sources.clone(), plots.window()));
+        if panel.shown == shown {
+            continue;
+        }
+        panel.shown = shown;
+
+        let window = plots.window();
+        commands
+            .entity(panel_entity)
+            .despawn_related::<Children>()
+            .with_children(|parent| {
+                parent
+                    .spawn(Node {
+                        flex_direction: FlexDirection::Row,
+                        align_items: AlignItems::Center,
+                        column_gap: px(2.),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn((
+                            Text::new("Plots"),
+                            Translated("Plots"),
+                            TextFont::from_font_size(7.),
+                            Node {
+                                flex_grow: 1.,
+                                ..default()
+                            },
+                        ));
+                        parent
+                            .spawn((
+                                text_button("-"),
+                                WindowButton {
+                                    panel: panel_entity,
+                                    factor: 0.5,
+                                },
+                            ))
+                            .observe(change_window);
+                        parent.spawn((
+                            Text::new(format!("{} s", window.as_secs_f32())),
+                            TextFont::from_font_size(FONT_SIZE),
+                        ));
+                        parent
+                            .spawn((
+                                text_button("+"),
+                                WindowButton {
+                                    panel: panel_entity,
+                                    factor: 2.,
+                                },
+                            ))
+                            .observe(change_window);
+                    });
+
+                for source in sources {
+                    let image = image_assets.add(Image::new_fill(
+                        Extent3d {
+                            width: CHART_SIZE.x,
+                            height: CHART_SIZE.y,
+                            ..default()
+                        },
+                        TextureDimension::D2,
+                        &CHART_BACKGROUND.to_u8_array(),
+                        TextureFormat::Rgba8UnormSrgb,
+                        RenderAssetUsages::default(),
+                    ));
+
+                    let mut label = Entity::PLACEHOLDER;
+                    parent
+                        .spawn(Node {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            column_gap: px(2.),
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            parent
+                                .spawn((
+                                    text_button("x"),
+                                    RemoveButton {
+                                        panel: panel_entity,
+                                        source: source.clone(),
+                                    },
+                                ))
+                                .observe(remove_plot);
+                            label = parent
+                                .spawn((
+                                    Text::new(source.label()),
+                                    TextFont::from_font_size(FONT_SIZE),
+                                ))
+                                .id();
+                        });
+                    parent.spawn((
+                        PlotChart {
+                            panel: panel_entity,
+                            source,
+                            label,
+                        },
+                        ImageNode::new(image),
+                        Node {
+                            width: percent(100),
+                            aspect_ratio: Some(CHART_SIZE.x as f32 / CHART_SIZE.y as f32),
+                            ..default()
+                        },
+                    ));
+                }
+            });
+    }
+}
+
+fn redraw_plots(
+    time: Res<Time<Real>>,
+    language: Res<Language>,
+    mut last_redraw: Local<Option<Instant>>,
+    mut image_assets: ResMut<Assets<Image>>,
+    q_plots: Query<&Plots>,
+    q_panels: Query<&PlotsPanel>,
+    q_charts: Query<(&PlotChart, &ImageNode)>,
+    mut q_texts: Query<&mut Text>,
+) {
+    let now = Instant::now();
+    if last_redraw.is_some_and(|last| now - last < REDRAW_INTERVAL) {
+        return;
+    }
+    *last_redraw = Some(now);
+
+    for (chart, image_node) in q_charts {
+        let Some(plots) = q_panels
+            .get(chart.panel)
+            .ok()
+            .and_then(|panel| q_plots.get(panel.state_source).ok())
+        else {
+            continue;
+        };
+        let Some(plot) = plots.plots().iter().find(|p| p.source == chart.source) else {
+            continue;
+        };
+
+        if let Ok(mut label) = q_texts.get_mut(chart.label) {
+            let source_label = plot.source.label();
+            let source_label = language.tr(&source_label);
+            label.0 = match plot.latest() {
+                Some(latest) => format!("{source_label}: {latest:.2}"),
+                None => source_label.to_string(),
+            };
+        }
+        if let Some(image) = image_assets.get_mut(&image_node.image) {
+            draw_chart(image, plot, time.elapsed_secs_f64(), plots.window());
+        }
+    }
+}
+
+/// Draws the samples in the time window as a line, scaled to the value range
+fn draw_chart(image: &mut Image, plot: &Plot, now: f64, window: Duration) {
+    let Some(data) = image.data.as_mut() else {
+        return;
+    };
+    let background = CHART_BACKGROUND.to_u8_array();
+    for pixel in data.chunks_exact_mut(4) {
+        pixel.copy_from_slice(&background);
+    }
+
+    let start = now - window.as_secs_f64();
+    let Some((min, max)) = plot.value_range_since(start) else {
+        return;
+    };
+    // Constant values are drawn in the middle
+    let (min, max) = if max - min < 1e-9 {
+        (min - 1., max + 1.)
+    } else {
+        (min, max)
+    };
+
+    let size = CHART_SIZE.as_vec2() - 1.;
+    let to_pixel = |(t, value): (f64, f64)| {
+        Vec2::new(
+            ((t - start) / window.as_secs_f64()) as f32 * size.x,
+            (1. - ((value - min) / (max - min)) as f32) * size.y,
+        )
+    };
+    let line = CHART_LINE.to_u8_array();
+    let mut set_pixel = |pos: Vec2| {
+        let pos = pos.round().as_uvec2().min(CHART_SIZE - 1);
+        let index = ((pos.y * CHART_SIZE.x + pos.x) * 4) as usize;
+        data[index..index + 4].copy_from_slice(&line);
+    };
+
+    let mut previous = None;
+    for pos in plot.samples_since(start).map(to_pixel) {
+        let from = previous.unwrap_or(pos);
+        let steps = (pos - from).abs().max_element().ceil().max(1.) as usize;
+        for step in 0..=steps {
+            set_pixel(from.lerp(pos, step as f32 / steps as f32));
+        }
+        previous = Some(pos);
+    }
+}
+
+fn change_window(
+    click: On<Pointer<Click>>,
+    q_buttons: Query<&WindowButton>,
+    q_panels: Query<&PlotsPanel>,
+    mut q_plots: Query<&mut Plots>,
+) {
+    let Ok(button) = q_buttons.get(click.entity) else {
+        return;
+    };
+    if let Ok(panel) = q_panels.get(button.panel)
+        && let Ok(mut plots) = q_plots.get_mut(panel.state_source)
+    {
+        let window = plots.window().mul_f32(button.factor);
+        plots.set_window(window);
+    }
+}
+
+fn remove_plot(
+    click: On<Pointer<Click>>,
+    q_buttons: Query<&RemoveButton>,
+    q_panels: Query<&PlotsPanel>,
+    mut q_plots: Query<&mut Plots>,
+) {
+    let Ok(button) = q_buttons.get(click.entity) else {
+        return;
+    };
+    if let Ok(panel) = q_panels.get(button.panel)
+        && let Ok(mut plots) = q_plots.get_mut(panel.state_source)
+    {
+        plots.remove(&button.source);
+    }
+}
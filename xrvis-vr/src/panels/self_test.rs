@@ -0,0 +1,155 @@
+use crate::panels::{Translated, XrPanelSpawner};
+use bevy::color::palettes::tailwind::*;
+use bevy::prelude::*;
+use bevy_mod_openxr::resources::OxrEnabledExtensions;
+use sslgame::{CheckStatus, Language, SelfTestReport};
+
+const FONT_SIZE: f32 = 1.6;
+
+pub fn self_test_panel_plugin(app: &mut App) {
+    app.add_systems(
+        Update,
+        (
+            check_xr_extensions.run_if(resource_added::<OxrEnabledExtensions>),
+            show_self_test_panel
+                .run_if(resource_changed::<SelfTestReport>.or(resource_changed::<Language>)),
+        )
+            .chain(),
+    );
+}
+
+/// Floats in front of the play area while any startup check failed, until it is dismissed
+#[derive(Component, Debug)]
+struct SelfTestPanel;
+
+#[derive(Component, Debug)]
+struct SelfTestText;
+
+/// Only the extensions that are both requested and available are enabled
+fn check_xr_extensions(enabled: Res<OxrEnabledExtensions>, mut report: ResMut<SelfTestReport>) {
+    let checks = [
+        (
+            "Hand tracking",
+            enabled.ext_hand_tracking,
+            CheckStatus::Error,
+            "The runtime doesn't offer hand tracking, gestures and the wrist panel are unavailable. Enable hand tracking in the headset settings.",
+        ),
+        (
+            "Hand interaction",
+            enabled.ext_hand_interaction,
+            CheckStatus::Warning,
+            "The runtime doesn't offer the hand interaction profile, so panels can't be clicked with a pinch. Update the headset software.",
+        ),
+        (
+            "Passthrough",
+            enabled.fb_passthrough,
+            CheckStatus::Warning,
+            "Passthrough isn't available, fields are shown in front of black. Allow passthrough for the app in the headset settings.",
+        ),
+    ];
+    for (name, available, missing_status, hint) in checks {
+        if available {
+            report.record(name, CheckStatus::Ok, "Extension enabled");
+        } else {
+            report.record(name, missing_status, hint);
+        }
+    }
+}
+
+fn show_self_test_panel(
+    mut commands: Commands,
+    mut panel_spawner: XrPanelSpawner,
+    report: Res<SelfTestReport>,
+    language: Res<Language>,
+    q_panels: Query<Entity, With<SelfTestPanel>>,
+    mut q_texts: Query<&mut Text, With<SelfTestText>>,
+) {
+    // A language change only relabels the open panel, a dismissed one stays closed
+    if !report.is_changed() && q_panels.is_empty() {
+        return;
+    }
+    let text = report
+        .checks()
+        .iter()
+        .filter(|check| check.status != CheckStatus::Ok)
+        .map(|check| {
+            format!(
+                "{}: {}",
+                language.tr(&check.name),
+                language.tr(&check.message)
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n");
+    if text.is_empty() {
+        return;
+    }
+
+    // Failures found later (e.g. the assets) are added to the open panel or reopen it
+    if !q_panels.is_empty() {
+        for mut self_test_text in &mut q_texts {
+            self_test_text.0.clone_from(&text);
+        }
+        return;
+    }
+    let panel = panel_spawner.spawn_panel(
+        &mut commands,
+        Transform {
+            translation: Vec3::new(0., 1.4, -1.2),
+            rotation: Quat::IDENTITY,
+            scale: Vec3::new(0.8, 0.5, 1.),
+        },
+        ZINC_800.into(),
+        move |parent| {
+            parent
+                .spawn(Node {
+                    width: percent(100),
+                    height: percent(100),
+                    padding: UiRect::all(px(1.)),
+                    flex_direction: FlexDirection::Column,
+                    justify_content: JustifyContent::SpaceBetween,
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn((
+                        Text::new("Self-test"),
+                        Translated("Self-test"),
+                        TextFont::from_font_size(FONT_SIZE * 1.5),
+                        TextColor(AMBER_400.into()),
+                    ));
+                    parent.spawn((
+                        SelfTestText,
+                        Text::new(text),
+                        TextFont::from_font_size(FONT_SIZE),
+                    ));
+                    parent
+                        .spawn((
+                            Node {
+                                align_self: AlignSelf::End,
+                                padding: UiRect::horizontal(px(0.6)),
+                                border_radius: BorderRadius::all(px(0.4)),
+                                ..default()
+                            },
+                            BackgroundColor(ZINC_600.into()),
+                            children![(
+                                Text::new("Dismiss"),
+                                Translated("Dismiss"),
+                                TextFont::from_font_size(FONT_SIZE)
+                            )],
+                        ))
+                        .observe(dismiss_self_test_panel);
+                });
+        },
+    );
+    commands.entity(panel).insert(SelfTestPanel);
+}
+
+fn dismiss_self_test_panel(
+    _click: On<Pointer<Click>>,
+    mut commands: Commands,
+    q_panels: Query<Entity, With<SelfTestPanel>>,
+) {
+    for panel in &q_panels {
+        commands.entity(panel).despawn();
+    }
+}
@@ -0,0 +1,129 @@
+//! Where the virtual floor is relative to the user: seated or standing, and which tracking origin it follows.
+
+use bevy::prelude::*;
+use bevy_mod_openxr::openxr::ReferenceSpaceType;
+use bevy_mod_openxr::session::OxrSession;
+use bevy_mod_xr::session::XrTrackingRoot;
+use bevy_mod_xr::spaces::XrPrimaryReferenceSpace;
+use std::sync::Arc;
+
+/// Height of the virtual floor above the real one while seated
+const SEATED_FLOOR_OFFSET: f32 = 0.5;
+
+pub fn play_space_plugin(app: &mut App) {
+    app.init_resource::<PlaySpace>();
+    app.add_systems(
+        Update,
+        (
+            apply_floor_origin.run_if(
+                resource_exists::<OxrSession>
+                    .and(resource_changed::<PlaySpace>.or(resource_added::<OxrSession>)),
+            ),
+            apply_posture,
+        ),
+    );
+}
+
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PlaySpace {
+    pub posture: Posture,
+    pub origin: FloorOrigin,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Posture {
+    #[default]
+    Standing,
+    /// Raises the virtual floor, so the field isn't as far below a seated user
+    Seated,
+}
+
+impl Posture {
+    pub fn floor_offset(self) -> f32 {
+        match self {
+            Posture::Standing => 0.0,
+            Posture::Seated => SEATED_FLOOR_OFFSET,
+        }
+    }
+}
+
+/// Tracking origin the virtual floor is anchored to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FloorOrigin {
+    /// Center of the configured guardian boundary, stays put between sessions
+    #[default]
+    Stage,
+    /// Floor below where the headset was recentered, for play spaces without a boundary
+    LocalFloor,
+}
+
+impl FloorOrigin {
+    pub fn name(self) -> &'static str {
+        match self {
+            FloorOrigin::Stage => "Stage",
+            FloorOrigin::LocalFloor => "Local",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        match self {
+            FloorOrigin::Stage => FloorOrigin::LocalFloor,
+            FloorOrigin::LocalFloor => FloorOrigin::Stage,
+        }
+    }
+
+    fn reference_space_type(self) -> ReferenceSpaceType {
+        match self {
+            FloorOrigin::Stage => ReferenceSpaceType::STAGE,
+            FloorOrigin::LocalFloor => ReferenceSpaceType::LOCAL_FLOOR,
+        }
+    }
+}
+
+/// Replaces the primary reference space, which all tracked poses are relative to
+fn apply_floor_origin(
+    mut commands: Commands,
+    mut last_origin: Local<Option<FloorOrigin>>,
+    play_space: Res<PlaySpace>,
+    session: Res<OxrSession>,
+) {
+    // Changes of the posture alone keep the reference space
+    if session.is_changed() {
+        *last_origin = None;
+    }
+    if *last_origin == Some(play_space.origin) {
+        return;
+    }
+    *last_origin = Some(play_space.origin);
+
+    match session.create_reference_space(
+        play_space.origin.reference_space_type(),
+        Transform::IDENTITY,
+    ) {
+        Ok(space) => commands.insert_resource(XrPrimaryReferenceSpace(Arc::new(space))),
+        Err(e) => warn!(
+            "Failed to use the {} floor origin: {e}",
+            play_space.origin.name()
+        ),
+    }
+}
+
+/// Tracked poses are children of the tracking root, so lowering it raises the floor for the user.
+/// The horizontal position is left to [`move_user_to`].
+fn apply_posture(
+    play_space: Res<PlaySpace>,
+    mut q_roots: Query<&mut Transform, With<XrTrackingRoot>>,
+) {
+    let height = -play_space.posture.floor_offset();
+    for mut transform in &mut q_roots {
+        if transform.translation.y != height {
+            transform.translation.y = height;
+        }
+    }
+}
+
+/// Moves the tracking root horizontally, so the user's `head` ends up above `target` at the same floor height
+pub fn move_user_to(target: Vec3, head: Vec3, root: &mut Transform) {
+    let offset = (target - head).with_y(0.);
+    root.translation += offset;
+}
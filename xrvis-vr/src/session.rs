@@ -0,0 +1,74 @@
+use bevy::prelude::*;
+use sslgame::{ClientKey, PresharedKey, Session, SessionState};
+use std::io;
+use std::path::PathBuf;
+
+/// Inserted once a session has been restored, fields are no longer replaced by discovered hosts afterwards
+#[derive(Resource, Debug)]
+pub struct SessionRestored;
+
+/// On android, this is in the external data directory of the app, so files can be exchanged with the desktop app over adb
+pub(crate) fn data_path(file_name: &str) -> PathBuf {
+    #[cfg(target_os = "android")]
+    if let Some(dir) = bevy::android::ANDROID_APP
+        .get()
+        .and_then(|app| app.external_data_path())
+    {
+        return dir.join(file_name);
+    }
+    PathBuf::from(file_name)
+}
+
+fn session_path() -> PathBuf {
+    data_path("session.xrvis")
+}
+
+/// There is no keyboard for the passphrase of encrypted hosts, so it is read from a file next to the session.
+/// Hosts that advertise encrypted streams are ignored without it.
+pub fn load_preshared_key(mut client_key: ResMut<ClientKey>) {
+    let path = data_path("psk.txt");
+    match std::fs::read_to_string(&path) {
+        Ok(passphrase) if !passphrase.trim().is_empty() => {
+            client_key.0 = Some(PresharedKey::new(passphrase.trim()));
+            info!("Using the pre-shared key from {}", path.display());
+        }
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => warn!("Failed to read the pre-shared key {}: {e}", path.display()),
+    }
+}
+
+/// VR has no camera or window layout, so only the fields, render and accessibility settings are saved
+pub fn save_session(_click: On<Pointer<Click>>, state: SessionState) {
+    let path = session_path();
+    let session = state.capture();
+    match session.write(&path) {
+        Ok(()) => info!(
+            "Saved {} fields into {}",
+            session.field.len(),
+            path.display()
+        ),
+        Err(e) => error!("Failed to save session {}: {e}", path.display()),
+    }
+}
+
+pub fn load_session(
+    _click: On<Pointer<Click>>,
+    mut commands: Commands,
+    mut state: SessionState,
+    client_key: Res<ClientKey>,
+) {
+    let path = session_path();
+    match Session::read(&path) {
+        Ok(session) => {
+            state.restore(&session, client_key.0.as_ref());
+            commands.insert_resource(SessionRestored);
+            info!(
+                "Restored {} fields from {}",
+                session.field.len(),
+                path.display()
+            );
+        }
+        Err(e) => error!("Failed to load session {}: {e}", path.display()),
+    }
+}
@@ -0,0 +1,64 @@
+use bevy::prelude::*;
+use bevy_mod_xr::camera::XrCamera;
+use sslgame::{Field, SnapshotCapture, SnapshotReceived};
+
+/// Scale of received snapshots, small enough to be viewed like a tabletop model
+const SNAPSHOT_SCALE: f32 = 0.1;
+/// Distance of received snapshots in front of the user
+const SNAPSHOT_DISTANCE: f32 = 0.8;
+
+pub fn sharing_plugin(app: &mut App) {
+    app.add_systems(Update, spawn_received_snapshot);
+}
+
+/// Marks the field showing the last received snapshot, so it isn't mistaken for the live field
+#[derive(Component, Debug)]
+pub struct ReceivedSnapshot;
+
+/// Shares the live field with the other viewers in the local network
+pub fn share_snapshot(
+    _click: On<Pointer<Click>>,
+    capture: SnapshotCapture,
+    q_fields: Query<Entity, (With<Field>, Without<ReceivedSnapshot>)>,
+) {
+    let Some(snapshot) = q_fields
+        .iter()
+        .min()
+        .and_then(|field| capture.capture(field))
+    else {
+        warn!("No field to capture a snapshot from");
+        return;
+    };
+    match snapshot.share() {
+        Ok(()) => info!("Shared snapshot of {}", snapshot.name()),
+        Err(e) => error!("Failed to share snapshot: {e}"),
+    }
+}
+
+/// Shows the latest received snapshot as a tabletop copy in front of the user, replacing the previous one
+fn spawn_received_snapshot(
+    mut commands: Commands,
+    mut snapshots: MessageReader<SnapshotReceived>,
+    q_cameras: Query<&GlobalTransform, With<XrCamera>>,
+    q_snapshots: Query<Entity, With<ReceivedSnapshot>>,
+) {
+    let Some(SnapshotReceived(snapshot)) = snapshots.read().last() else {
+        return;
+    };
+    info!("Received snapshot of {}", snapshot.name());
+    for entity in &q_snapshots {
+        commands.entity(entity).despawn();
+    }
+
+    // Placed at table height, facing the user
+    let head = q_cameras.iter().next().copied().unwrap_or_default();
+    let forward = head.forward().with_y(0.0).normalize_or(Vec3::NEG_Z);
+    let translation = (head.translation() + forward * SNAPSHOT_DISTANCE).with_y(0.8);
+    commands.spawn((
+        Field::snapshot(snapshot.clone()),
+        Transform::from_translation(translation)
+            .looking_to(forward, Vec3::Y)
+            .with_scale(Vec3::splat(SNAPSHOT_SCALE)),
+        ReceivedSnapshot,
+    ));
+}